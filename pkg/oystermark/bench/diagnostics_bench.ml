@@ -0,0 +1,58 @@
+(** LSP diagnostics after typing one character, recomputed in full and
+    through {!Lsp_lib.Diagnostics.Incremental}, on notes of growing size. Run
+    with [make bench]. *)
+
+open Core
+module Diagnostics = Lsp_lib.Diagnostics
+
+let index : Oystermark.Vault.Index.t =
+  Oystermark.Vault.build_index
+    ~md_docs:
+      (List.init 100 ~f:(fun i ->
+         sprintf "note%d.md" i, Oystermark.Parse.of_string (sprintf "# Note %d\n" i)))
+    ~other_files:[]
+    ~dirs:[]
+;;
+
+let rel_path = "big.md"
+
+(* [n] paragraphs, each with a resolved and an unresolved link, under a
+   heading every ten. *)
+let note_of_size (n : int) : string =
+  String.concat
+    ~sep:"\n\n"
+    (List.init n ~f:(fun i ->
+       let paragraph =
+         sprintf "Paragraph %d links [[note%d]] and [[missing%d]]." i (i mod 100) i
+       in
+       if i mod 10 = 0 then sprintf "## Part %d\n\n%s" i paragraph else paragraph))
+  ^ "\n"
+;;
+
+(* The note with one character typed in its middle paragraph. *)
+let edited (note : string) : string =
+  let middle = String.length note / 2 in
+  let at = Option.value_exn (String.substr_index note ~pattern:" links" ~pos:middle) in
+  String.prefix note at ^ "x" ^ String.drop_prefix note at
+;;
+
+let sizes = [ 1_000; 10_000 ]
+
+let benchmarks : Core_bench.Bench.Test.t list =
+  let open Core_bench.Bench.Test in
+  [ create_indexed ~name:"didChange diagnostics (full)" ~args:sizes (fun n ->
+      let content = edited (note_of_size n) in
+      Staged.stage (fun () ->
+        let diagnostics = Diagnostics.compute ~index ~rel_path ~content () in
+        ignore (diagnostics : Diagnostics.diagnostic list)))
+  ; create_indexed ~name:"didChange diagnostics (incremental)" ~args:sizes (fun n ->
+      let note = note_of_size n in
+      let state = Diagnostics.Incremental.build ~index ~rel_path note in
+      let content = edited note in
+      Staged.stage (fun () ->
+        let state = Diagnostics.Incremental.update ~index ~rel_path state content in
+        ignore (Diagnostics.Incremental.diagnostics state : Diagnostics.diagnostic list)))
+  ]
+;;
+
+let () = Command_unix.run (Core_bench.Bench.make_command benchmarks)
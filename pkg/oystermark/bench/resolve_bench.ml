@@ -0,0 +1,63 @@
+(** Link resolution on generated vaults of growing size, with and without
+    {!Oystermark.Vault.Index.Lookup}. Run with [make bench]. *)
+
+open Core
+module Index = Oystermark.Vault.Index
+module Link_ref = Oystermark.Vault.Link_ref
+module Resolve = Oystermark.Vault.Resolve
+
+(* [n] notes spread over nested folders; every name appears in two folders,
+   so bare links go through the ambiguous-name path. *)
+let index_of_size (n : int) : Index.t =
+  { files =
+      List.init n ~f:(fun i ->
+        ({ rel_path = sprintf "area%d/topic%d/note%d.md" (i mod 20) (i mod 50) (i / 2)
+         ; headings = []
+         ; blocks = []
+         ; attrs = []
+         }
+         : Index.file_entry))
+  ; dirs = []
+  }
+;;
+
+(* 100 links: bare names, folder-qualified names, full paths and misses. *)
+let links_for (n : int) : Link_ref.t list =
+  List.init 100 ~f:(fun k ->
+    let i = k * (n / 100) in
+    let target =
+      match k mod 4 with
+      | 0 -> sprintf "note%d" (i / 2)
+      | 1 -> sprintf "topic%d/note%d" (i mod 50) (i / 2)
+      | 2 -> sprintf "area%d/topic%d/note%d.md" (i mod 20) (i mod 50) (i / 2)
+      | _ -> sprintf "missing%d" i
+    in
+    ({ target = Some target; fragment = None } : Link_ref.t))
+;;
+
+let sizes = [ 1_000; 10_000 ]
+
+let resolve_all ?lookup index links () =
+  List.iter links ~f:(fun link_ref ->
+    let target = Resolve.resolve ?lookup link_ref "area0/topic0/today.md" index in
+    ignore (target : Resolve.target))
+;;
+
+let benchmarks : Core_bench.Bench.Test.t list =
+  let open Core_bench.Bench.Test in
+  [ create_indexed ~name:"resolve 100 links (scan)" ~args:sizes (fun n ->
+      let index = index_of_size n in
+      let links = links_for n in
+      Staged.stage (resolve_all index links))
+  ; create_indexed ~name:"resolve 100 links (lookup)" ~args:sizes (fun n ->
+      let index = index_of_size n in
+      let lookup = Index.Lookup.create index.files in
+      let links = links_for n in
+      Staged.stage (resolve_all ~lookup index links))
+  ; create_indexed ~name:"Lookup.create" ~args:sizes (fun n ->
+      let index = index_of_size n in
+      Staged.stage (fun () -> ignore (Index.Lookup.create index.files : Index.Lookup.t)))
+  ]
+;;
+
+let () = Command_unix.run (Core_bench.Bench.make_command benchmarks)
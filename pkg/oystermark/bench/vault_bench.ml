@@ -0,0 +1,136 @@
+(** Whole-build benchmarks on generated vaults: parsing a large note, indexing
+    and link resolution over many notes, and {!Oystermark.render_site} end to
+    end. [run] takes core_bench's flags; [fixture] writes a generated vault to
+    disk for profiling a real build; [gate] times one end-to-end build and fails
+    over a budget. Run with [make bench]. *)
+
+open Core
+module Parse = Oystermark.Parse
+module Vault = Oystermark.Vault
+
+(** {1 Fixture} *)
+
+let note_path (i : int) : string =
+  sprintf "area%d/topic%d/note%d.md" (i mod 20) (i mod 50) i
+;;
+
+(* Note [i] of [n]: frontmatter, sections with wikilinks to other notes and
+   their headings, a block id, a list and a code block. *)
+let note_source ~(n : int) (i : int) : string =
+  let other (k : int) : int = ((i * 7) + (k * 13) + 1) mod n in
+  String.concat
+    [ sprintf "---\npublish: true\ntags: [area%d, topic%d]\n---\n\n" (i mod 20) (i mod 50)
+    ; sprintf "# Note %d\n\n" i
+    ; sprintf "Intro linking [[note%d]] and [[note%d#Details]].\n\n" (other 0) (other 1)
+    ; "## Details\n\n"
+    ; sprintf
+        "A paragraph about topic %d with *emphasis*, `code` and a \
+         [link](https://example.com/%d). ^b%d\n\n"
+        (i mod 50)
+        i
+        i
+    ; sprintf
+        "- see [[topic%d/note%d]]\n- and [[note%d#^b%d]]\n\n"
+        (other 2 mod 50)
+        (other 2)
+        (other 3)
+        (other 3)
+    ; "## Code\n\n```ocaml\nlet x = 1\n```\n"
+    ]
+;;
+
+(* [n] generated notes, in memory. *)
+let notes (n : int) : (string * string) list =
+  List.init n ~f:(fun i -> note_path i, note_source ~n i)
+;;
+
+(** Write [n] generated notes under [dir]. *)
+let write_fixture ~(dir : string) (n : int) : unit =
+  List.iter (notes n) ~f:(fun (rel_path, src) ->
+    let path = Filename.concat dir rel_path in
+    Core_unix.mkdir_p (Filename.dirname path);
+    Out_channel.write_all path ~data:src)
+;;
+
+(* [n] generated notes in a fresh temporary directory, removed at exit. *)
+let temp_fixture (n : int) : string =
+  let dir = Filename_unix.temp_dir "oyster-bench" "" in
+  at_exit (fun () ->
+    let (_ : Core_unix.Exit_or_signal.t) =
+      Core_unix.system (sprintf "rm -rf %s" (Filename.quote dir))
+    in
+    ());
+  write_fixture ~dir n;
+  dir
+;;
+
+let parsed (n : int) : (string * Cmarkit.Doc.t) list =
+  List.map (notes n) ~f:(fun (p, src) -> p, Parse.of_string src)
+;;
+
+let index_of (docs : (string * Cmarkit.Doc.t) list) : Vault.Index.t =
+  Vault.build_index ~md_docs:docs ~other_files:[] ~dirs:[]
+;;
+
+let render (dir : string) : unit =
+  let _vault, pages = Oystermark.render_site ~backend_blocks:true ~safe:false dir in
+  ignore (pages : (string * string) list)
+;;
+
+(** {1 Benchmarks} *)
+
+let benchmarks : Core_bench.Bench.Test.t list =
+  let open Core_bench.Bench.Test in
+  [ create_indexed ~name:"parse note of n sections" ~args:[ 100; 10_000 ] (fun n ->
+      let src = String.concat (List.map (notes n) ~f:snd) in
+      Staged.stage (fun () -> ignore (Parse.of_string src : Cmarkit.Doc.t)))
+  ; create_indexed ~name:"build_index" ~args:[ 1_000; 10_000 ] (fun n ->
+      let docs = parsed n in
+      Staged.stage (fun () -> ignore (index_of docs : Vault.Index.t)))
+  ; create_indexed ~name:"resolve_docs" ~args:[ 1_000; 10_000 ] (fun n ->
+      let docs = parsed n in
+      let index = index_of docs in
+      Staged.stage (fun () ->
+        ignore (Vault.Resolve.resolve_docs docs index : (string * Cmarkit.Doc.t) list)))
+  ; create_indexed ~name:"render_site" ~args:[ 100; 1_000 ] (fun n ->
+      let dir = temp_fixture n in
+      Staged.stage (fun () -> render dir))
+  ]
+;;
+
+(** {1 Commands} *)
+
+let fixture_cmd : Command.t =
+  Command.basic
+    ~summary:"Write a generated vault to DIR"
+    (let%map_open.Command dir = anon ("dir" %: string)
+     and n = flag "-notes" (optional_with_default 10_000 int) ~doc:"N notes (10000)" in
+     fun () -> write_fixture ~dir n)
+;;
+
+let gate_cmd : Command.t =
+  Command.basic
+    ~summary:"Render a generated vault once; fail if it takes longer than the budget"
+    (let%map_open.Command n =
+       flag "-notes" (optional_with_default 10_000 int) ~doc:"N notes (10000)"
+     and max_seconds =
+       flag "-max-seconds" (required float) ~doc:"S budget for the build, in seconds"
+     in
+     fun () ->
+       let dir = temp_fixture n in
+       let start = Time_ns.now () in
+       render dir;
+       let took = Time_ns.Span.to_sec (Time_ns.diff (Time_ns.now ()) start) in
+       printf "render_site, %d notes: %.2fs (budget %.2fs)\n" n took max_seconds;
+       if Float.(took > max_seconds) then exit 1)
+;;
+
+let () =
+  Command_unix.run
+    (Command.group
+       ~summary:"Vault build benchmarks"
+       [ "run", Core_bench.Bench.make_command benchmarks
+       ; "fixture", fixture_cmd
+       ; "gate", gate_cmd
+       ])
+;;
@@ -40,6 +40,8 @@ let serve ~(env : Eio_unix.Stdenv.base) ~port ~dir =
     let path =
       if String.is_suffix resource ~suffix:"/" then resource ^ "index.html" else resource
     in
+    (* Files are written under decoded names, e.g. [C#] for [/C%23] *)
+    let path = Oystermark.Vault.Link_ref.percent_decode path in
     (* Prevent path traversal *)
     let path = String.substr_replace_all path ~pattern:".." ~with_:"" in
     let file_path = dir ^ path in
@@ -97,8 +99,9 @@ let rec scan_mtimes (dir : string) : (string * float) list =
   | exception _ -> []
 ;;
 
-(** Watch [watch_dir] for changes and call [on_change] when detected. *)
-let watch ~(env : Eio_unix.Stdenv.base) ~watch_dir ~on_change =
+(** Watch [watch_dir] for changes and call [on_change] when detected, saying
+    it is [doing] that ("re-rendering"). *)
+let watch ?(doing = "re-rendering") ~(env : Eio_unix.Stdenv.base) ~watch_dir ~on_change =
   let prev = ref (scan_mtimes watch_dir) in
   while true do
     Eio.Time.sleep (Eio.Stdenv.clock env) 1.0;
@@ -111,7 +114,7 @@ let watch ~(env : Eio_unix.Stdenv.base) ~watch_dir ~on_change =
            !prev)
     then (
       prev := curr;
-      printf "\nChange detected, re-rendering...\n%!";
+      printf "\nChange detected, %s...\n%!" doing;
       on_change ();
       printf "Done.\n%!")
   done
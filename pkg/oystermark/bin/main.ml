@@ -52,14 +52,139 @@ let file_cmd : Command.t =
        | None -> print_string html)
 ;;
 
-(** Render vault and write output files + copy assets. Returns unit. *)
-let do_render ~verbose ~config ~theme ~vault_root ~output_dir =
+(** Parse a [--mount PREFIX=PATH] flag value. *)
+let mount_of_string (s : string) : Vault.mount =
+  match String.lsplit2 s ~on:'=' with
+  | Some (prefix, root) ->
+    { prefix = String.strip ~drop:(Char.equal '/') prefix; root }
+  | None -> failwithf "Invalid --mount %S, expected PREFIX=PATH" s ()
+;;
+
+(** Render vault and write output files + copy assets. Returns unit.
+    With non-empty [mounts], [vault_root] is mounted at the top level and the
+    extra vaults under their prefixes; see {!Oystermark.render_vaults}.
+    Only assets referenced by published pages are copied unless
+    [copy_all_assets]. Also writes the build manifest and [changes.json]
+    (notes changed since the previous build); see {!Vault.Manifest}.
+    With [effective_config], prints the settings of each note that differ
+    from [config]; see {!Vault.Folder_config}. With [a11y_check], warns about
+    accessibility issues of the pages and theme; see {!A11y}. Runs the
+    config's [before_write] and [after_build] hooks; see {!Pipeline.Hook}. *)
+let do_render
+      ?(mounts : Vault.mount list = [])
+      ?(copy_all_assets : bool = false)
+      ?(effective_config : bool = false)
+      ?(a11y_check : bool = false)
+      ~verbose
+      ~config
+      ~theme
+      ~vault_root
+      ~output_dir
+      ()
+  =
   let cache = Cache.load_cache ~dir:output_dir in
-  let pipeline : Pipeline.t = Pipeline.of_config ~cache ~config () in
-  let results =
-    render_vault ~pipeline ~theme ~config ~backend_blocks:true ~safe:false vault_root
+  (* Kept with the vault, as the output directory is often wiped or deployed *)
+  let preview_cache_dir = Filename.concat vault_root ".oyster" in
+  let preview_cache = Vault.Link_preview.load_cache ~dir:preview_cache_dir in
+  let mounts : Vault.mount list =
+    match mounts with
+    | [] -> []
+    | extra -> { prefix = ""; root = vault_root } :: extra
+  in
+  let all_entries : string list =
+    match mounts with
+    | [] -> Vault.list_entries vault_root
+    | mounts -> Vault.list_mounted_entries mounts
+  in
+  let src_path (rel_path : string) : string =
+    Vault.mount_full_path mounts rel_path
+    |> Option.value ~default:(Filename.concat vault_root rel_path)
+  in
+  (* Hash every note source against the previous build's manifest *)
+  let prev_manifest : Vault.Manifest.t = Vault.Manifest.load ~dir:output_dir in
+  let manifest : Vault.Manifest.t =
+    let now : string =
+      Time_float_unix.now ()
+      |> Time_float.to_string_iso8601_basic ~zone:Time_float.Zone.utc
+    in
+    List.filter_map all_entries ~f:(fun p ->
+      if String.is_suffix p ~suffix:".md"
+      then Some (p, In_channel.read_all (src_path p))
+      else None)
+    |> Vault.Manifest.update prev_manifest ~now
+  in
+  let folders : Vault.Folder_config.t =
+    Vault.Folder_config.load
+      ~read_file:(fun p ->
+        let path = src_path p in
+        if Sys_unix.file_exists_exn path then Some (In_channel.read_all path) else None)
+      (List.filter all_entries ~f:(String.is_suffix ~suffix:"/"))
+  in
+  let pipeline : Pipeline.t =
+    Pipeline.of_config
+      ~on_warning:(fun msg -> eprintf "warning: %s\n" msg)
+      ~cache
+      ~preview_cache
+      ~folders
+      ~manifest
+      ?diagram_colors:(Theme.diagram_colors config.theme)
+      ~config
+      ()
+  in
+  let final_vault, results =
+    render_site
+      ~on_embed_issues:
+        (List.iter ~f:(fun i ->
+           eprintf "warning: %s\n" (Vault.Embed.issue_to_string_hum i)))
+      ~on_warning:(fun msg -> eprintf "warning: %s\n" msg)
+      ~pipeline
+      ~theme
+      ~config
+      ~mounts
+      ~backend_blocks:true
+      ~safe:false
+      vault_root
   in
+  let results : (string * string) list =
+    List.map results ~f:(fun (out_rel, html) ->
+      if String.is_suffix out_rel ~suffix:".html"
+      then
+        ( out_rel
+        , Pipeline.Hook.before_write
+            ~on_warning:(fun msg -> eprintf "warning: %s\n" msg)
+            ~commands:config.hooks.before_write
+            ~path:out_rel
+            html )
+      else out_rel, html)
+  in
+  List.iter (Anchors.check results) ~f:(fun b ->
+    eprintf "warning: %s\n" (Anchors.to_string_hum b));
+  if a11y_check
+  then
+    List.iter
+      (A11y.check ?colors:(Theme.colors_of_name config.theme) results)
+      ~f:(fun i -> eprintf "warning: %s\n" (A11y.issue_to_string_hum i));
+  if effective_config
+  then
+    List.iter final_vault.docs ~f:(fun (p, doc) ->
+      if String.is_suffix p ~suffix:".md"
+      then (
+        match Config.diff_json ~base:config (note_config ~config ~folders p doc) with
+        | `Null -> ()
+        | diff -> printf "%s: %s\n" p (Yojson.Safe.to_string diff)));
   Cache.save_cache cache ~dir:output_dir;
+  (match config.link_previews.mode with
+   | Config.Link_preview_mode_def.Off -> ()
+   | Fetch | Offline ->
+     Vault.Link_preview.save_cache preview_cache ~dir:preview_cache_dir);
+  Vault.Manifest.save manifest ~dir:output_dir;
+  Vault.Manifest.changes_json
+    ~prev:prev_manifest
+    ~include_:(fun p ->
+      List.Assoc.mem (Vault.listed_docs final_vault) ~equal:String.equal p)
+    ~url_of:(Url.note_url_path (Url.of_docs ~style:config.url_style final_vault.docs))
+    manifest
+  |> Yojson.Basic.to_file (Filename.concat output_dir "changes.json");
   List.iteri results ~f:(fun i (out_rel, html) ->
     let out_path = Filename.concat output_dir out_rel in
     let out_dir = Filename.dirname out_path in
@@ -73,19 +198,99 @@ let do_render ~verbose ~config ~theme ~vault_root ~output_dir =
       print_char '.';
       Out_channel.flush Out_channel.stdout));
   (* Copy non-markdown assets (images, etc.) to the output directory *)
-  let all_entries = Vault.list_entries vault_root in
-  let is_asset (p : string) : bool =
-    (not (String.is_suffix p ~suffix:".md")) && not (String.is_suffix p ~suffix:"/")
+  let assets : string list =
+    if copy_all_assets
+    then
+      List.filter all_entries ~f:(fun p ->
+        (not (String.is_suffix p ~suffix:".md")) && not (String.is_suffix p ~suffix:"/"))
+    else Vault.Assets.referenced_files final_vault.docs
+  in
+  List.iter assets ~f:(fun rel_path ->
+    let src = Vault.full_path final_vault rel_path in
+    let dst = Filename.concat output_dir rel_path in
+    let dst_dir = Filename.dirname dst in
+    Core_unix.mkdir_p dst_dir;
+    let content = In_channel.read_all src in
+    Out_channel.write_all dst ~data:content);
+  Pipeline.Hook.after_build
+    ~on_warning:(fun msg -> eprintf "warning: %s\n" msg)
+    ~commands:config.hooks.after_build
+    ~output_dir
+    (List.map results ~f:fst)
+;;
+
+(** Render the published notes of the vault into the one HTML file [path];
+    see {!Oystermark.Single_file}. [mounts] are as for {!do_render}. The
+    config's [before_write] hooks see the file as the one page, its name
+    relative to its directory, and the [after_build] hooks that directory as
+    the output directory. *)
+let do_render_single_file
+      ?(mounts : Vault.mount list = [])
+      ~config
+      ~theme
+      ~vault_root
+      ~path
+      ()
+  =
+  let mounts : Vault.mount list =
+    match mounts with
+    | [] -> []
+    | extra -> { prefix = ""; root = vault_root } :: extra
+  in
+  let src_path (rel_path : string) : string =
+    Vault.mount_full_path mounts rel_path
+    |> Option.value ~default:(Filename.concat vault_root rel_path)
+  in
+  let folders : Vault.Folder_config.t =
+    let dirs : string list =
+      (match mounts with
+       | [] -> Vault.list_entries vault_root
+       | mounts -> Vault.list_mounted_entries mounts)
+      |> List.filter ~f:(String.is_suffix ~suffix:"/")
+    in
+    Vault.Folder_config.load
+      ~read_file:(fun p ->
+        let path = src_path p in
+        if Sys_unix.file_exists_exn path then Some (In_channel.read_all path) else None)
+      dirs
+  in
+  let pipeline : Pipeline.t =
+    Pipeline.of_config
+      ~on_warning:(fun msg -> eprintf "warning: %s\n" msg)
+      ~folders
+      ?diagram_colors:(Theme.diagram_colors config.theme)
+      ~config
+      ()
   in
-  List.iter all_entries ~f:(fun rel_path ->
-    if is_asset rel_path
-    then (
-      let src = Filename.concat vault_root rel_path in
-      let dst = Filename.concat output_dir rel_path in
-      let dst_dir = Filename.dirname dst in
-      Core_unix.mkdir_p dst_dir;
-      let content = In_channel.read_all src in
-      Out_channel.write_all dst ~data:content))
+  let _final_vault, html =
+    render_single_file
+      ~on_embed_issues:
+        (List.iter ~f:(fun i ->
+           eprintf "warning: %s\n" (Vault.Embed.issue_to_string_hum i)))
+      ~pipeline
+      ~theme
+      ~config
+      ~mounts
+      ~backend_blocks:true
+      ~safe:false
+      vault_root
+  in
+  let on_warning (msg : string) : unit = eprintf "warning: %s\n" msg in
+  let output_dir, page = Filename.dirname path, Filename.basename path in
+  let html : string =
+    Pipeline.Hook.before_write
+      ~on_warning
+      ~commands:config.hooks.before_write
+      ~path:page
+      html
+  in
+  Core_unix.mkdir_p output_dir;
+  Out_channel.write_all path ~data:html;
+  Pipeline.Hook.after_build
+    ~on_warning
+    ~commands:config.hooks.after_build
+    ~output_dir
+    [ page ]
 ;;
 
 let vault_cmd : Command.t =
@@ -119,30 +324,351 @@ let vault_cmd : Command.t =
          "--port"
          (optional_with_default 8080 int)
          ~doc:"PORT Port for serve mode (default: 8080)"
+     and (mounts : string list) =
+       flag
+         "--mount"
+         (listed string)
+         ~doc:
+           "PREFIX=PATH Merge another vault into the site under PREFIX (repeatable). \
+            Links resolve across all mounted vaults"
+     and (copy_all_assets : bool) =
+       flag
+         "--copy-all-assets"
+         no_arg
+         ~doc:"Copy all vault assets, not only those referenced by published pages"
+     and (no_syntax : string list) =
+       flag
+         "--no-syntax"
+         (listed string)
+         ~doc:
+           "NAME Disable a markdown extension (extensions, wikilink, block_id, div, \
+            attributes, callout, definition_list, smart_punctuation; repeatable)"
+     and raw_text =
+       flag
+         "--raw-text"
+         no_arg
+         ~doc:" Keep text as written in the source: no smart punctuation, merged text"
+     and (raw_html : string option) =
+       flag
+         "--raw-html"
+         (optional string)
+         ~doc:"MODE Raw HTML in notes: allow (default), sanitize or strip"
+     and (url_style : string option) =
+       flag
+         "--url-style"
+         (optional string)
+         ~doc:"STYLE Page URLs: pretty (default) or obsidian_publish"
+     and (redirects : string option) =
+       flag
+         "--redirects"
+         (optional string)
+         ~doc:
+           "FORMAT Redirects for aliases and redirect_from: html stub pages \
+            (default) or redirects_file (a _redirects file for Netlify/Cloudflare)"
+     and (print_variants : bool) =
+       flag
+         "--print-variants"
+         no_arg
+         ~doc:" Also write a print-friendly NOTE.print.html for every note"
+     and (sidebar_order : string option) =
+       flag
+         "--sidebar-order"
+         (optional string)
+         ~doc:
+           "ORDER Sidebar entries: toc_order (default), alphabetical or frontmatter \
+            (by each note's numeric order field)"
+     and (menu : string list) =
+       flag
+         "--menu"
+         (listed string)
+         ~doc:
+           "LABEL=TARGET Header menu entry linking a note or URL; PARENT>LABEL=TARGET \
+            puts it under entry PARENT (repeatable)"
+     and (frontmatter_fields : string list) =
+       flag
+         "--frontmatter-field"
+         (listed string)
+         ~doc:
+           "KEY[:RENDER][=LABEL] Show only these frontmatter keys, in order \
+            (repeatable); RENDER is text (default), chips, date or link"
+     and (date_format : string option) =
+       flag
+         "--date-format"
+         (optional string)
+         ~doc:"FORMAT Frontmatter dates under the date renderer (default %Y-%m-%d)"
+     and (share_headings : bool) =
+       flag
+         "--share-headings"
+         no_arg
+         ~doc:" Give every heading a copy-link button"
+     and (share_paragraphs : bool) =
+       flag
+         "--share-paragraphs"
+         no_arg
+         ~doc:" Give every paragraph a copy-link button (a #:~:text= text fragment)"
+     and (image_captions : string option) =
+       flag
+         "--image-captions"
+         (optional string)
+         ~doc:
+           "MODE Caption images standing alone in a paragraph: off (default), alt (from \
+            the alt text) or italic (from an italic line right under the image)"
+     and (no_lightbox : bool) =
+       flag "--no-lightbox" no_arg ~doc:" Don't zoom images when they're clicked"
+     and (sticky_table_headers : bool) =
+       flag
+         "--sticky-table-headers"
+         no_arg
+         ~doc:" Keep a table's header row in view while scrolling through the table"
+     and (sortable_tables : int option) =
+       flag
+         "--sortable-tables"
+         (optional int)
+         ~doc:
+           "ROWS Sort tables of at least ROWS rows by a column when its header is \
+            clicked"
+     and (link_previews : string option) =
+       flag
+         "--link-previews"
+         (optional string)
+         ~doc:
+           "MODE Previews of external links: off (default), fetch (download pages \
+            missing from the cache) or offline (cached previews only)"
+     and (link_preview_style : string option) =
+       flag
+         "--link-preview-style"
+         (optional string)
+         ~doc:"STYLE Show link previews as hover (default) cards or inline boxes"
+     and (page_previews : bool) =
+       flag
+         "--page-previews"
+         no_arg
+         ~doc:" Preview a note (first heading and paragraph) when a link to it is hovered"
+     and (recent_changes : bool) =
+       flag
+         "--recent-changes"
+         no_arg
+         ~doc:" Add a recent.md page listing the latest updated notes"
+     and (glossary : string option) =
+       flag
+         "--glossary"
+         (optional string)
+         ~doc:
+           "NOTE Mark the first occurrence of each term defined in glossary note NOTE \
+            on every page"
+     and (glossary_style : string option) =
+       flag
+         "--glossary-style"
+         (optional string)
+         ~doc:"STYLE Mark glossary terms as abbr (default, definition on hover) or link"
+     and (social_cards : string option) =
+       flag
+         "--social-cards"
+         (optional string)
+         ~doc:
+           "BASE_URL Make a social card image for each note and point its og:image at \
+            it, under BASE_URL (e.g. https://notes.example.com). Cards are SVG, which \
+            most link previews skip, unless --social-card-png converts them"
+     and (social_card_png : string option) =
+       flag
+         "--social-card-png"
+         (optional string)
+         ~doc:
+           "CMD Convert each social card to PNG with shell command CMD, reading the SVG \
+            on stdin and printing the PNG (e.g. 'rsvg-convert -f png')"
+     and (effective_config : bool) =
+       flag
+         "--effective-config"
+         no_arg
+         ~doc:
+           " Report each note's settings that differ from the site config (from \
+            .oyster.toml files and frontmatter)"
+     and (a11y_check : bool) =
+       flag
+         "--a11y-check"
+         no_arg
+         ~doc:
+           " Warn about images without alt text, skipped heading levels and \
+            low-contrast theme colors"
+     and (drafts : bool) =
+       flag
+         "--drafts"
+         no_arg
+         ~doc:" Publish every note, drafts and publish: false ones included"
+     and (profile : string option) =
+       flag
+         "--profile"
+         (optional string)
+         ~doc:
+           "NAME Apply a build profile over the config: dev (drafts on), prod, or one \
+            defined under the config file's profile field. Flags override it"
+     and (single_file : string option) =
+       flag
+         "--single-file"
+         (optional string)
+         ~doc:
+           "PATH Write every published note into one HTML file at PATH, with an \
+            in-page nav and images inlined, instead of a site. highlight.js and \
+            KaTeX still load from their CDNs: offline, code goes uncolored and math \
+            shows as TeX"
+     and (preset : string option) =
+       flag
+         "--preset"
+         (optional string)
+         ~doc:
+           "NAME Apply a preset over the config: obsidian_publish (Publish URLs, \
+            permalinks and heading anchors, for sites migrating off Obsidian Publish)"
      in
      fun () ->
        (* ::: config-resolving *)
+       (* The profile goes under the flags, which override what it sets *)
+       let with_profile (config : Config.t) : Config.t =
+         match profile with
+         | None -> config
+         | Some name ->
+           let profiles =
+             Option.value_map config_file ~default:[] ~f:Config.profiles_of_file
+           in
+           (match Config.with_profile ~profiles config name with
+            | Ok config -> config
+            | Error e ->
+              eprintf "error: --profile: %s\n" (Error.to_string_hum e);
+              exit 1)
+       in
        let config : Config.t =
          match config_file with
-         | Some path -> Config.of_file path
+         | Some path -> with_profile (Config.of_file path)
          | None ->
-           { Config.default with
+           let base : Config.t = with_profile Config.default in
+           { base with
              theme =
                Option.value_map
                  theme
-                 ~default:Config.default.theme
+                 ~default:base.theme
                  ~f:Config.Theme.of_string
            ; css_snippets =
                (match css_snippets with
-                | [] -> Config.default.css_snippets
+                | [] -> base.css_snippets
                 | snippets -> snippets)
            ; pipeline_profile =
                Option.value_map
                  pipeline_profile
-                 ~default:Config.default.pipeline_profile
+                 ~default:base.pipeline_profile
                  ~f:Config.Pipeline_profile.of_string
+           ; parse =
+               (let parse =
+                  match
+                    List.fold_result
+                      no_syntax
+                      ~init:base.parse
+                      ~f:Config.Parse_options.disable
+                  with
+                  | Ok parse -> parse
+                  | Error msg ->
+                    eprintf "error: --no-syntax: %s\n" msg;
+                    exit 1
+                in
+                if raw_text
+                then { parse with smart_punctuation = false; coalesce_text = true }
+                else parse)
+           ; raw_html =
+               { base.raw_html with
+                 mode =
+                   Option.value_map
+                     raw_html
+                     ~default:base.raw_html.mode
+                     ~f:Config.Raw_html_mode.of_string
+               }
+           ; url_style =
+               Option.value_map
+                 url_style
+                 ~default:base.url_style
+                 ~f:Config.Url_style.of_string
+           ; redirects =
+               { format =
+                   Option.value_map
+                     redirects
+                     ~default:base.redirects.format
+                     ~f:Config.Redirect_format.of_string
+               }
+           ; print = { variants = print_variants || base.print.variants }
+           ; sidebar =
+               { order =
+                   Option.value_map
+                     sidebar_order
+                     ~default:base.sidebar.order
+                     ~f:Config.Sidebar_order.of_string
+               }
+           ; menu =
+               (match menu with
+                | [] -> base.menu
+                | menu -> Config.Menu.of_flags menu)
+           ; frontmatter =
+               { fields =
+                   (match frontmatter_fields with
+                    | [] -> base.frontmatter.fields
+                    | fields ->
+                      List.map fields ~f:Config.Frontmatter_display.field_of_flag)
+               ; date_format =
+                   Option.value
+                     date_format
+                     ~default:base.frontmatter.date_format
+               }
+           ; share =
+               { headings = share_headings || base.share.headings
+               ; paragraphs = share_paragraphs || base.share.paragraphs
+               }
+           ; images =
+               { caption =
+                   Option.value_map
+                     image_captions
+                     ~default:base.images.caption
+                     ~f:Config.Image_caption.of_string
+               ; lightbox = base.images.lightbox && not no_lightbox
+               }
+           ; tables =
+               { sticky_header = sticky_table_headers || base.tables.sticky_header
+               ; sortable = Option.is_some sortable_tables || base.tables.sortable
+               ; sortable_min_rows =
+                   Option.value
+                     sortable_tables
+                     ~default:base.tables.sortable_min_rows
+               }
+           ; link_previews =
+               { base.link_previews with
+                 mode =
+                   Option.value_map
+                     link_previews
+                     ~default:base.link_previews.mode
+                     ~f:Config.Link_preview_mode.of_string
+               ; style =
+                   Option.value_map
+                     link_preview_style
+                     ~default:base.link_previews.style
+                     ~f:Config.Link_preview_style.of_string
+               }
+           ; page_previews = { enabled = page_previews || base.page_previews.enabled }
+           ; glossary =
+               { note = Option.value glossary ~default:base.glossary.note
+               ; style =
+                   Option.value_map
+                     glossary_style
+                     ~default:base.glossary.style
+                     ~f:Config.Glossary_style.of_string
+               }
+           ; social_cards =
+               (match social_cards with
+                | Some base_url ->
+                  { enabled = true
+                  ; base_url
+                  ; png_command = Option.value social_card_png ~default:""
+                  }
+                | None -> base.social_cards)
+           ; publish = { base.publish with drafts = drafts || base.publish.drafts }
+           ; recent = { base.recent with enabled = recent_changes || base.recent.enabled }
            }
        in
+       let config : Config.t = Option.fold preset ~init:config ~f:Config.with_preset in
        let css_snippet_contents : string list =
          List.map config.css_snippets ~f:In_channel.read_all
        in
@@ -157,7 +683,28 @@ let vault_cmd : Command.t =
            let curr_dir = Sys_unix.getcwd () in
            curr_dir ^ "/_site"
        in
-       let render () = do_render ~verbose ~config ~theme ~vault_root ~output_dir in
+       let mounts : Vault.mount list = List.map mounts ~f:mount_of_string in
+       if serve && Option.is_some single_file
+       then (
+         eprintf "error: --serve serves a site; drop it with --single-file\n";
+         exit 1);
+       let render () =
+         match single_file with
+         | Some path ->
+           do_render_single_file ~mounts ~config ~theme ~vault_root ~path ()
+         | None ->
+           do_render
+             ~mounts
+             ~copy_all_assets
+             ~effective_config
+             ~a11y_check
+             ~verbose
+             ~config
+             ~theme
+             ~vault_root
+             ~output_dir
+             ()
+       in
        (* Initial render *)
        render ();
        (* Serve and/or watch *)
@@ -192,9 +739,1211 @@ let graph_cmd : Command.t =
        | None -> print_string html)
 ;;
 
+let outline_diagram_cmd : Command.t =
+  Command.basic
+    ~summary:"Print a note's section hierarchy or link neighborhood as a diagram"
+    ~readme:(fun () ->
+      "NOTE is a path under --vault. The diagram shows the note's headings \
+       nested by level, or with --links the notes it links to and those \
+       linking to it. In a note, an outline-diagram code fence is replaced by \
+       the same diagram at build time, e.g. ```outline-diagram {view=links \
+       format=dot}.")
+    (let%map_open.Command (note : string) = anon ("note" %: string)
+     and (format : Vault.Outline_diagram.format) =
+       flag
+         "--format"
+         (optional_with_default
+            Vault.Outline_diagram.Mermaid
+            (Arg_type.create (fun s ->
+               match Vault.Outline_diagram.format_of_string s with
+               | Some f -> f
+               | None -> failwithf "unknown format %S" s ())))
+         ~doc:"mermaid|dot Diagram language (default mermaid)"
+     and (links : bool) =
+       flag "--links" no_arg ~doc:" Show the note's link neighborhood, not its sections"
+     and (vault_root : string) =
+       flag
+         "--vault"
+         (optional_with_default "." string)
+         ~doc:"DIR Vault root NOTE is relative to (default: .)"
+     in
+     fun () ->
+       let note = String.chop_prefix_if_exists note ~prefix:"./" in
+       let diagram : Vault.Outline_diagram.t =
+         if links
+         then (
+           let vault = Vault.of_root_path ~skip_expand:true vault_root in
+           if not (List.Assoc.mem vault.docs ~equal:String.equal note)
+           then (
+             eprintf "error: no note %s in %s\n" note vault_root;
+             exit 1);
+           Vault.Outline_diagram.of_links ~path:note (Vault.Links.of_docs vault.docs))
+         else (
+           match
+             Option.try_with (fun () ->
+               In_channel.read_all (Filename.concat vault_root note))
+           with
+           | Some content ->
+             Vault.Outline_diagram.of_sections ~path:note (Parse.of_string content)
+           | None ->
+             eprintf "error: cannot read %s\n" (Filename.concat vault_root note);
+             exit 1)
+       in
+       print_string (Vault.Outline_diagram.render format diagram))
+;;
+
+let query_cmd : Command.t =
+  Command.basic
+    ~summary:"Query the section structure of a markdown file"
+    ~readme:(fun () ->
+      "Expressions run left to right from the whole document, e.g.\n\n\
+      \  oystermark query note.md -- --field Usage --codemeta\n\
+      \  oystermark query note.md --html -- --field Usage --root-level 1\n\n\
+       Without FILE, or with -, the markdown is read from stdin, e.g.\n\n\
+      \  cat note.md | oystermark query - -- --titles\n\n\
+       With --doc N, the file is read as documents separated by top-level \
+       thematic breaks (---), as in slide decks, and only the Nth is queried, \
+       e.g.\n\n\
+      \  oystermark query deck.md --doc 2 -- --titles\n\n\
+       Section ids (the id of --summary-json sections) hash the note's path; give \
+       --vault for them to match the exported site's data-section-id, e.g.\n\n\
+      \  oystermark query vault/guide.md --vault vault -- --summary-json\n\n\
+       With --journal, FILE is a vault root instead, and the daily and weekly \
+       notes covering a day in the range are listed by date, one per line as \
+       DATE day|week PATH, DATE being the day or the Monday of the week; the \
+       config's journal patterns tell them apart, e.g.\n\n\
+      \  oystermark query vault --journal 2024-05-01..2024-05-31\n\n\
+       Expressions:\n"
+      ^ Query.Expr.help)
+    (let%map_open.Command (file : string option) = anon (maybe ("file" %: string))
+     and (mode : Query.Expr.mode) =
+       choose_one
+         ~if_nothing_chosen:(Default_to Query.Expr.Strict)
+         [ flag
+             "--strict"
+             (no_arg_some Query.Expr.Strict)
+             ~doc:"Missing selections are errors (default)"
+         ; flag
+             "--lenient"
+             (no_arg_some Query.Expr.Lenient)
+             ~doc:"Missing selections print nothing and exit 0"
+         ]
+     and (gap_policy : Query.Section.gap_policy) =
+       flag
+         "--gaps"
+         (optional_with_default
+            Query.Section.Attach
+            (Arg_type.create (fun s ->
+               match Query.Section.gap_policy_of_string s with
+               | Some p -> p
+               | None -> failwithf "unknown gap policy %S" s ())))
+         ~doc:
+           "attach|pad|error Headings that skip a level: nest under the enclosing \
+            heading (default), pad with untitled sections, or fail"
+     and (root : string) =
+       flag
+         "--root"
+         (optional_with_default "." string)
+         ~doc:"DIR Directory code fences' file= paths are relative to (default: .)"
+     and (html : bool) =
+       flag "--html" no_arg ~doc:" Print selected sections as HTML instead of markdown"
+     and (doc : int option) =
+       flag
+         "--doc"
+         (optional int)
+         ~doc:
+           "N Query only the Nth document (0-based, negative from the end) of those \
+            separated by top-level thematic breaks"
+     and (exprs : string list option) =
+       flag "--" escape ~doc:"EXPR... query expressions (see -help)"
+     and (journal : string option) =
+       flag
+         "--journal"
+         (optional string)
+         ~doc:"FROM..TO List the vault's journal notes in a date range (YYYY-MM-DD)"
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     and (vault_root : string option) =
+       flag
+         "--vault"
+         (optional string)
+         ~doc:
+           "DIR Vault FILE belongs to; section ids hash its path relative to DIR, as \
+            the exported site's do (default: FILE as given)"
+     in
+     fun () ->
+       (* [None] for stdin *)
+       let file : string option =
+         match file with
+         | None | Some "-" -> None
+         | Some file -> Some file
+       in
+       (* What section ids hash: the vault-relative path of [file]. *)
+       let id_path : string option =
+         Option.map file ~f:(fun file ->
+           let file = String.chop_prefix_if_exists file ~prefix:"./" in
+           match vault_root with
+           | None -> file
+           | Some vault_root ->
+             let prefix =
+               String.chop_prefix_if_exists vault_root ~prefix:"./"
+               |> String.chop_suffix_if_exists ~suffix:"/"
+             in
+             if String.is_empty prefix || String.equal prefix "."
+             then file
+             else (
+               match String.chop_prefix file ~prefix:(prefix ^ "/") with
+               | Some rel -> rel
+               | None ->
+                 eprintf "error: %s is not in the vault %s\n" file vault_root;
+                 exit 1))
+       in
+       let config : Config.t option = Option.map config_file ~f:Config.of_file in
+       Option.iter journal ~f:(fun range ->
+         let from, until =
+           match Vault.Journal.range_of_string range with
+           | Ok range -> range
+           | Error e ->
+             eprintf "error: %s\n" (Error.to_string_hum e);
+             exit 1
+         in
+         let config = Option.value config ~default:Config.default in
+         let vault_root : string =
+           match file with
+           | Some root -> root
+           | None ->
+             eprintf "error: --journal needs a vault root\n";
+             exit 1
+         in
+         Vault.list_entries vault_root
+         |> Vault.Journal.of_paths ~config:config.journal
+         |> Vault.Journal.in_range ?from ?until
+         |> List.iter ~f:(fun (e : Vault.Journal.entry) ->
+           printf
+             !"%{Date} %s %s\n"
+             e.date
+             (match e.period with
+              | Vault.Journal.Day -> "day"
+              | Vault.Journal.Week -> "week")
+             e.path);
+         exit 0);
+       let exprs : Query.Expr.t list =
+         match Query.Expr.of_args (Option.value exprs ~default:[]) with
+         | Ok exprs -> exprs
+         | Error e ->
+           eprint_s [%sexp (e : Error.t)];
+           exit 1
+       in
+       let root : Query.Section.t =
+         let read_file (path : string) : string option =
+           Option.try_with (fun () -> In_channel.read_all (Filename.concat root path))
+         in
+         match
+           Query.sections
+             ?config
+             ~read_file
+             ?path:id_path
+             ~gap_policy
+             ?doc
+             (match file with
+              | Some file -> In_channel.read_all file
+              | None -> In_channel.input_all In_channel.stdin)
+         with
+         | Ok root -> root
+         | Error e ->
+           eprintf "error: %s\n" (Error.to_string_hum e);
+           exit 1
+       in
+       let section_html (s : Query.Section.t) : string =
+         Html.of_doc ~backend_blocks:true ~safe:false (Query.Section.to_doc s)
+       in
+       match Query.Expr.eval ~mode exprs root with
+       | Ok (Query.Expr.Section s) when html -> print_string (section_html s)
+       | Ok (Query.Expr.Many vs) when html ->
+         (* Each pipeline's section in turn; other values have no HTML form *)
+         let sections : Query.Section.t list =
+           List.filter_map vs ~f:(function
+             | Query.Expr.Section s -> Some s
+             | Query.Expr.Empty -> None
+             | Query.Expr.Json _ | Query.Expr.Text _ | Query.Expr.Many _ ->
+               eprintf "error: --html needs every value of the query to be a section\n";
+               exit 1)
+         in
+         List.iter sections ~f:(fun s -> print_string (section_html s))
+       | Ok v ->
+         (* Nothing at all for an empty result, not a blank line *)
+         (match Query.Expr.value_to_string v with
+          | "" -> ()
+          | s -> print_endline s)
+       | Error e ->
+         eprintf "error: %s\n" (Query.Expr.Eval_error.to_string_hum e);
+         exit 1)
+;;
+
+let ast_cmd : Command.t =
+  Command.basic
+    ~summary:"Print the parsed AST of a markdown file"
+    ~readme:(fun () ->
+      "JSON nodes carry kind, source range, attributes and children; see \
+       Parse.Ast_json for the shape.")
+    (let%map_open.Command (file : string) = anon ("file" %: string)
+     and (format : [ `Json | `Sexp ]) =
+       flag
+         "--format"
+         (optional_with_default
+            `Json
+            (Arg_type.of_alist_exn [ "json", `Json; "sexp", `Sexp ]))
+         ~doc:"json|sexp Output format (default json)"
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     in
+     fun () ->
+       let config : Config.t option = Option.map config_file ~f:Config.of_file in
+       let doc = Parse.of_string ?config (In_channel.read_all file) in
+       match format with
+       | `Json ->
+         print_endline (Yojson.Basic.pretty_to_string (Parse.Ast_json.of_doc doc))
+       | `Sexp -> print_endline (Sexp.to_string_hum (Parse.sexp_of_doc doc)))
+;;
+
+(** Current state of the vault at [vault_root] as a {!Vault.Snapshot.t}. *)
+let snapshot_of_vault (vault_root : string) : Vault.Snapshot.t =
+  let now : string =
+    Time_float_unix.now () |> Time_float.to_string_iso8601_basic ~zone:Time_float.Zone.utc
+  in
+  let vault = Vault.of_root_path ~skip_expand:true vault_root in
+  Vault.Snapshot.create
+    ~now
+    ~content_of:(fun rel -> In_channel.read_all (Filename.concat vault_root rel))
+    vault.docs
+;;
+
+let snapshot_cmd : Command.t =
+  let create =
+    Command.basic
+      ~summary:"Record the vault's notes, sections and links"
+      ~readme:(fun () ->
+        "Writes to .oyster/snapshots/TIME.json under the vault root, TIME the \
+         ISO 8601 time with - for :, unless --output is given, and prints the \
+         path written.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (output : string option) =
+         flag "--output" (optional string) ~doc:"PATH Write the snapshot here"
+       in
+       fun () ->
+         let snapshot = snapshot_of_vault vault_root in
+         let path =
+           Option.value
+             output
+             ~default:
+               (Filename.concat
+                  vault_root
+                  (sprintf
+                     ".oyster/snapshots/%s.json"
+                     (* [:] is not allowed in Windows file names *)
+                     (String.tr snapshot.created ~target:':' ~replacement:'-')))
+         in
+         Vault.Snapshot.save snapshot ~path;
+         print_endline path)
+  in
+  let diff =
+    Command.basic
+      ~summary:"Report changes since an earlier snapshot"
+      ~readme:(fun () ->
+        "Compares OLD with the vault's current state, or with the snapshot given \
+         by --against. Lines: + added note, - removed note, ~ edited note (with \
+         sections added/removed), link +/- SOURCE -> TARGET.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (old : string) = anon ("old" %: string)
+       and (against : string option) =
+         flag "--against" (optional string) ~doc:"PATH Compare with this snapshot"
+       and (check : bool) = flag "--check" no_arg ~doc:" Exit 1 if anything changed" in
+       fun () ->
+         let old = Vault.Snapshot.load old in
+         let new_ =
+           match against with
+           | Some path -> Vault.Snapshot.load path
+           | None -> snapshot_of_vault vault_root
+         in
+         let d = Vault.Snapshot.diff ~old new_ in
+         print_string (Vault.Snapshot.diff_to_string_hum d);
+         if check && not (Vault.Snapshot.is_empty_diff d) then exit 1)
+  in
+  Command.group
+    ~summary:"Vault snapshots for reviewing changes over time"
+    [ "create", create; "diff", diff ]
+;;
+
+let doctor_cmd : Command.t =
+  Command.basic
+    ~summary:"Check the vault for ambiguous names and duplicate files"
+    ~readme:(fun () ->
+      "Reports notes sharing a file name (ambiguous wikilinks), paths differing \
+       only by case, files with identical content and assets sharing a name, \
+       each with a suggested fix, then embed cycles and embeds nested past \
+       embeds.max_depth. Exits 1 if anything is found.")
+    (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     in
+     fun () ->
+       let files : string list =
+         Vault.list_entries vault_root
+         |> List.filter ~f:(fun p -> not (String.is_suffix p ~suffix:"/"))
+       in
+       let findings : Vault.Doctor.finding list =
+         let digest_of rel = Vault.Doctor.digest_file (Filename.concat vault_root rel) in
+         Vault.Doctor.check ~digest_of files
+       in
+       List.iter findings ~f:(fun f -> print_string (Vault.Doctor.to_string_hum f));
+       let config =
+         Option.value_map config_file ~default:Config.default ~f:Config.of_file
+       in
+       let embed_issues =
+         let vault = Vault.of_root_path ~skip_expand:true ~config vault_root in
+         Vault.Embed.check ~max_depth:config.embeds.max_depth vault.docs
+       in
+       List.iter embed_issues ~f:(fun i ->
+         print_endline (Vault.Embed.issue_to_string_hum i));
+       if not (List.is_empty findings && List.is_empty embed_issues) then exit 1)
+;;
+
+let lint_cmd : Command.t =
+  Command.basic
+    ~summary:"Check the writing of the vault's notes"
+    ~readme:(fun () ->
+      "With --prose, checks the text of every note, skipping code and links: \
+       spelling against a dictionary (--dictionary, by default \
+       /usr/share/dict/words, plus the vault's .oyster/wordlist.txt, one word \
+       per line) and style (repeated words, wordy phrases, a space before a \
+       comma or period). With --headings, checks headings against the \
+       config's heading_style rules: case, length, trailing punctuation and \
+       titles repeated in a note; --fix rewrites case and punctuation. With \
+       --encrypted, flags published notes holding encrypted blocks (Meld \
+       Encrypt, age or PGP ciphertext), which the site leaves out. Prints \
+       each issue as PATH:LINE[:COLUMN]: MESSAGE and exits 1 if anything is \
+       found (and not fixed).")
+    (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     and (prose : bool) =
+       flag "--prose" no_arg ~doc:" Check spelling and style of the notes' text"
+     and (dictionary : string option) =
+       flag
+         "--dictionary"
+         (optional string)
+         ~doc:"PATH Word list to spell against (default /usr/share/dict/words)"
+     and (headings : bool) =
+       flag "--headings" no_arg ~doc:" Check headings against the heading_style rules"
+     and (fix : bool) =
+       flag "--fix" no_arg ~doc:" Rewrite headings whose case or punctuation is off"
+     and (encrypted : bool) =
+       flag "--encrypted" no_arg ~doc:" Flag published notes with encrypted blocks"
+     in
+     fun () ->
+       if not (prose || headings || encrypted)
+       then (
+         eprintf "error: nothing to check; pass --prose, --headings or --encrypted\n";
+         exit 2);
+       let config =
+         Option.value_map config_file ~default:Config.default ~f:Config.of_file
+       in
+       let dictionary = Vault.Prose_lint.load_dictionary ?dictionary vault_root in
+       if prose && Vault.Prose_lint.Dictionary.is_empty dictionary
+       then eprintf "warning: no dictionary found; checking style only\n";
+       let entries = Vault.list_entries vault_root in
+       let folders =
+         Vault.Folder_config.load
+           ~read_file:(fun p ->
+             Option.try_with (fun () ->
+               In_channel.read_all (Filename.concat vault_root p)))
+           (List.filter entries ~f:(String.is_suffix ~suffix:"/"))
+       in
+       let found =
+         entries
+         |> List.filter ~f:(String.is_suffix ~suffix:".md")
+         |> List.fold ~init:false ~f:(fun found rel_path ->
+           let file = Filename.concat vault_root rel_path in
+           let content = In_channel.read_all file in
+           let prose_issues =
+             if prose
+             then Vault.Prose_lint.check ~dictionary (Parse.of_string ~config content)
+             else []
+           in
+           List.iter prose_issues ~f:(fun issue ->
+             print_endline
+               (Vault.Prose_lint.to_string_hum ~path:rel_path ~content issue));
+           let heading_issues =
+             if headings
+             then
+               Query.Heading_style.check
+                 ~config:config.heading_style
+                 ~content
+                 (Or_error.ok_exn (Query.sections ~config content))
+             else []
+           in
+           let fixable (i : Query.Heading_style.issue) : bool =
+             fix && Option.is_some i.fix
+           in
+           List.iter heading_issues ~f:(fun (i : Query.Heading_style.issue) ->
+             printf
+               "%s:%d: %s%s\n"
+               rel_path
+               i.line
+               i.message
+               (if fixable i then " (fixed)" else ""));
+           if List.exists heading_issues ~f:fixable
+           then
+             Out_channel.write_all
+               file
+               ~data:(Query.Heading_style.apply heading_issues content);
+           let encrypted_blocks =
+             let doc = Parse.of_string ~config content in
+             if encrypted && Pipeline.publishes ~folders ~config rel_path doc
+             then Parse.Encrypted.find doc
+             else []
+           in
+           List.iter encrypted_blocks ~f:(fun (f : Parse.Encrypted.found) ->
+             printf
+               "%s%s: encrypted content (%s) in a published note; the site leaves it \
+                out\n"
+               rel_path
+               (Option.value_map f.lines ~default:"" ~f:(fun (l, _) -> sprintf ":%d" l))
+               (Parse.Encrypted.kind_to_string f.kind));
+           found
+           || (not (List.is_empty prose_issues))
+           || (not (List.is_empty encrypted_blocks))
+           || List.exists heading_issues ~f:(Fn.non fixable))
+       in
+       if found then exit 1)
+;;
+
+let fix_levels_cmd : Command.t =
+  Command.basic
+    ~summary:"Rewrite heading levels to remove skipped levels"
+    ~readme:(fun () ->
+      "PATH is a markdown file or a vault directory. Each heading that skips a \
+       level (H1 then H3) becomes one level below its enclosing heading; a \
+       line is printed per change.")
+    (let%map_open.Command (path : string) = anon ("path" %: string)
+     and (check : bool) =
+       flag
+         "--check"
+         no_arg
+         ~doc:"Report without rewriting; exit 1 if anything would change"
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     in
+     fun () ->
+       let config : Config.t option = Option.map config_file ~f:Config.of_file in
+       let files : string list =
+         match Sys_unix.is_directory path with
+         | `Yes ->
+           Vault.list_entries path
+           |> List.filter ~f:(String.is_suffix ~suffix:".md")
+           |> List.map ~f:(fun rel -> Filename.concat path rel)
+         | `No | `Unknown -> [ path ]
+       in
+       let changed : int =
+         List.count files ~f:(fun file ->
+           let content : string = In_channel.read_all file in
+           let changes : Query.Fix_levels.change list =
+             Query.Fix_levels.changes (Or_error.ok_exn (Query.sections ?config content))
+           in
+           List.iter changes ~f:(fun c ->
+             printf "%s:%s\n" file (Query.Fix_levels.change_to_string_hum c));
+           if (not check) && not (List.is_empty changes)
+           then Out_channel.write_all file ~data:(Query.Fix_levels.apply changes content);
+           not (List.is_empty changes))
+       in
+       if check && changed > 0 then exit 1)
+;;
+
+let links_cmd : Command.t =
+  let convert =
+    Command.basic
+      ~summary:"Rewrite every link of a vault to wikilink or Markdown syntax"
+      ~readme:(fun () ->
+        "For migrating to or from Obsidian. Aliases and display text, embeds and \
+         heading/block fragments are kept: [[Note#Usage|setup]] and \
+         [setup](Note.md#Usage) convert into each other. Targets resolve \
+         through the vault, so [[Ideas]] becomes a path relative to the note and \
+         a Markdown link the shortest wikilink naming its file. External and \
+         reference-style links, and Markdown links with a title, stay as they are. \
+         A line is printed per changed file.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (to_ : Vault.Link_convert.syntax) =
+         flag
+           "--to"
+           (required
+              (Arg_type.of_alist_exn
+                 [ "markdown", Vault.Link_convert.Markdown
+                 ; "wiki", Vault.Link_convert.Wiki
+                 ]))
+           ~doc:"SYNTAX Target link syntax: markdown or wiki"
+       and (check : bool) =
+         flag
+           "--check"
+           no_arg
+           ~doc:"Report without rewriting; exit 1 if anything would change"
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       in
+       fun () ->
+         let config =
+           Option.value_map config_file ~default:Config.default ~f:Config.of_file
+         in
+         let vault = Vault.of_root_path ~skip_expand:true ~config vault_root in
+         let changed : int =
+           Vault.list_entries vault_root
+           |> List.filter ~f:(String.is_suffix ~suffix:".md")
+           |> List.count ~f:(fun rel ->
+             let file = Filename.concat vault_root rel in
+             let content : string = In_channel.read_all file in
+             let converted, n =
+               Vault.Link_convert.convert
+                 ~config
+                 ~prefer:config.link_resolution.prefer
+                 ~relative:config.link_resolution.relative
+                 ~index:vault.index
+                 ~path:rel
+                 ~to_
+                 content
+             in
+             if n > 0
+             then (
+               printf "%s: %d links\n" rel n;
+               if not check then Out_channel.write_all file ~data:converted);
+             n > 0)
+         in
+         if check && changed > 0 then exit 1)
+  in
+  let rename_heading =
+    Command.basic
+      ~summary:"Rename a heading and rewrite the links pointing at it"
+      ~readme:(fun () ->
+        "HEADING names the heading as a link fragment would: [Old] or a nested \
+         [Parent#Old]. Run before editing the heading: links are matched by \
+         what they resolve to now, so [[NOTE#Old]], [[NOTE#Parent#Old]] and \
+         [text](NOTE.md#Old) are all found. A line is printed per changed file.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (note : string) = anon ("note" %: string)
+       and (old : string) = anon ("heading" %: string)
+       and (new_text : string) = anon ("new-heading" %: string)
+       and (check : bool) =
+         flag
+           "--check"
+           no_arg
+           ~doc:"Report without rewriting; exit 1 if anything would change"
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       in
+       fun () ->
+         let config =
+           Option.value_map config_file ~default:Config.default ~f:Config.of_file
+         in
+         let vault = Vault.of_root_path ~skip_expand:true ~config vault_root in
+         let heading =
+           match Vault.Heading_rename.find_heading vault.index ~path:note old with
+           | Some h -> h
+           | None ->
+             eprintf "No heading %S in %s\n" old note;
+             exit 1
+         in
+         let lookup = Vault.Index.Lookup.create vault.index.files in
+         let changed : int =
+           Vault.list_entries vault_root
+           |> List.filter ~f:(String.is_suffix ~suffix:".md")
+           |> List.count ~f:(fun rel ->
+             let file = Filename.concat vault_root rel in
+             let content : string = In_channel.read_all file in
+             let renamed, n =
+               Vault.Heading_rename.rename
+                 ~config
+                 ~lookup
+                 ~prefer:config.link_resolution.prefer
+                 ~relative:config.link_resolution.relative
+                 vault.index
+                 ~curr_file:rel
+                 ~path:note
+                 ~heading
+                 ~new_text
+                 content
+             in
+             let changed = not (String.equal renamed content) in
+             if changed
+             then (
+               printf "%s: %d links\n" rel n;
+               if not check then Out_channel.write_all file ~data:renamed);
+             changed)
+         in
+         if check && changed > 0 then exit 1)
+  in
+  let list =
+    Command.basic
+      ~summary:"Print every internal link of a vault as JSON"
+      ~readme:(fun () ->
+        "Each link has its source note, location, the link as written and what it \
+         resolves to; see Vault.Links for the schema. With --to, only the \
+         backlinks of that note.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (to_ : string option) =
+         flag "--to" (optional string) ~doc:"PATH Only links into this note"
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       in
+       fun () ->
+         let config = Option.map config_file ~f:Config.of_file in
+         let vault = Vault.of_root_path ~skip_expand:true ?config vault_root in
+         let links = Vault.Links.of_docs vault.docs in
+         let links =
+           match to_ with
+           | Some path -> Vault.Links.backlinks links ~path
+           | None -> links
+         in
+         print_endline (Yojson.Safe.pretty_to_string (Vault.Links.to_json links)))
+  in
+  Command.group
+    ~summary:"Bulk link operations on a vault"
+    [ "convert", convert; "list", list; "rename-heading", rename_heading ]
+;;
+
+let assets_cmd : Command.t =
+  let unused =
+    Command.basic
+      ~summary:"List attachments no note links to or embeds"
+      ~readme:(fun () ->
+        "An asset counts as used when a wikilink, embed, Markdown link or image \
+         resolves to it, when a note's raw HTML points at it (<img src=..>) or \
+         when a frontmatter value names it; the exporter copies the linked ones. \
+         Canvases, bases, stylesheets, scripts and config files are never listed. \
+         Files only reachable from CSS are listed, so review the list: --delete \
+         prints what it would remove, and removes it only with --yes.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (delete : bool) = flag "--delete" no_arg ~doc:" Remove the unused files"
+       and (yes : bool) =
+         flag "--yes" no_arg ~doc:" With --delete, remove rather than only list"
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       in
+       fun () ->
+         let config =
+           Option.value_map config_file ~default:Config.default ~f:Config.of_file
+         in
+         let vault = Vault.of_root_path ~config ~skip_expand:true vault_root in
+         let entries = Vault.list_entries vault_root in
+         let unused = Vault.Assets.unused ~entries vault.docs in
+         match delete, yes with
+         | true, true ->
+           List.iter unused ~f:(fun rel ->
+             print_endline rel;
+             Sys_unix.remove (Filename.concat vault_root rel))
+         | true, false ->
+           List.iter unused ~f:(printf "would delete %s\n");
+           if not (List.is_empty unused)
+           then eprintf "dry run: pass --yes with --delete to remove these files\n"
+         | false, _ -> List.iter unused ~f:print_endline)
+  in
+  Command.group ~summary:"Vault attachments" [ "unused", unused ]
+;;
+
+let new_cmd : Command.t =
+  Command.basic
+    ~summary:"Create a note from a template"
+    ~readme:(fun () ->
+      "Copies TEMPLATES/NAME.md to NOTE, replacing {{title}} with NOTE's file name, \
+       {{date}} with today's date and each {{key}} given by --var. Other \
+       placeholders are kept. Refuses to overwrite an existing note.")
+    (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+     and (note : string) = anon ("note" %: string)
+     and (template : string) = flag "--template" (required string) ~doc:"NAME Template"
+     and (folder : string) =
+       flag
+         "--templates"
+         (optional_with_default Config.Templates.default.folder string)
+         ~doc:"DIR Templates folder, relative to the vault root (default: templates)"
+     and (vars : string list) =
+       flag "--var" (listed string) ~doc:"KEY=VALUE Substitute {{KEY}} with VALUE"
+     in
+     fun () ->
+       let note = if String.is_suffix note ~suffix:".md" then note else note ^ ".md" in
+       let file = Filename.concat vault_root note in
+       if Sys_unix.file_exists_exn file
+       then (
+         eprintf "%s already exists\n" note;
+         exit 1);
+       let template_file =
+         match Vault.Template.path ~folder template with
+         | Some path -> Filename.concat vault_root path
+         | None ->
+           eprintf "Template %s is outside %s\n" template folder;
+           exit 1
+       in
+       if not (Sys_unix.file_exists_exn template_file)
+       then (
+         eprintf "No template %s\n" template_file;
+         exit 1);
+       let vars : (string * string) list =
+         List.map vars ~f:(fun kv ->
+           match String.lsplit2 kv ~on:'=' with
+           | Some (k, v) -> String.strip k, v
+           | None ->
+             eprintf "--var %S: expected KEY=VALUE\n" kv;
+             exit 1)
+       in
+       let today : string =
+         Time_float_unix.now ()
+         |> Time_float.to_date ~zone:(Lazy.force Time_float_unix.default_timezone)
+         |> Date.to_string
+       in
+       let vars =
+         vars
+         @ [ "title", Filename.chop_extension (Filename.basename note); "date", today ]
+       in
+       let content =
+         Vault.Template.substitute ~vars (In_channel.read_all template_file)
+       in
+       Core_unix.mkdir_p (Filename.dirname file);
+       Out_channel.write_all file ~data:content;
+       print_endline note)
+;;
+
+let daemon_cmd : Command.t =
+  Command.basic
+    ~summary:"Keep a vault in memory and answer JSON-RPC requests on a unix socket"
+    ~readme:(fun () ->
+      "One JSON-RPC 2.0 request per line, one response per line. Methods: query \
+       {path, args}, links {path}, search {text, limit?, tag?, path?, section?}, \
+       backlinks {path}, render {path} and reload; see Oystermark.Daemon. The \
+       vault is rescanned, every note read and parsed again, when its files \
+       change; search and backlinks reuse what they took from the notes that \
+       didn't change. Only the owner can connect to the socket.")
+    (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+     and (socket : string option) =
+       flag
+         "--socket"
+         (optional string)
+         ~doc:"PATH Socket to listen on (default VAULT/.oyster/daemon.sock)"
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     in
+     fun () ->
+       let config =
+         Option.value_map config_file ~default:Config.default ~f:Config.of_file
+       in
+       let socket =
+         Option.value socket ~default:(Filename.concat vault_root ".oyster/daemon.sock")
+       in
+       let daemon = Daemon.load ~config vault_root in
+       Core_unix.mkdir_p (Filename.dirname socket);
+       if Sys_unix.file_exists_exn socket then Core_unix.unlink socket;
+       printf "Listening on %s\n%!" socket;
+       Eio_main.run
+       @@ fun env ->
+       Eio.Switch.run
+       @@ fun sw ->
+       let listener =
+         (* Owner-only from creation on, as whoever connects can read every note *)
+         let umask = Core_unix.umask 0o177 in
+         Exn.protect
+           ~f:(fun () ->
+             Eio.Net.listen ~sw ~backlog:16 (Eio.Stdenv.net env) (`Unix socket))
+           ~finally:(fun () -> ignore (Core_unix.umask umask : int))
+       in
+       Core_unix.chmod socket ~perm:0o600;
+       let serve_connection flow _addr =
+         let reader = Eio.Buf_read.of_flow flow ~max_size:(64 * 1024 * 1024) in
+         Eio.Buf_write.with_flow flow (fun writer ->
+           Eio.Buf_read.lines reader
+           |> Seq.iter (fun line ->
+             Eio.Buf_write.string writer (Daemon.handle daemon line);
+             Eio.Buf_write.char writer '\n'))
+       in
+       Eio.Fiber.both
+         (fun () ->
+           Eio.Net.run_server
+             listener
+             serve_connection
+             ~on_error:(fun e -> eprintf "daemon: %s\n%!" (Exn.to_string e)))
+         (fun () ->
+           Dev_server.watch
+             ~doing:"rescanning"
+             ~env
+             ~watch_dir:vault_root
+             ~on_change:(fun () -> Daemon.reload daemon)))
+;;
+
+let index_cmd : Command.t =
+  Command.basic
+    ~summary:"Export the vault index to a SQLite database"
+    ~readme:(fun () ->
+      "Writes notes, sections, frontmatter fields, tags, links and block ids, \
+       plus an FTS5 full-text table notes_fts, replacing those tables if the \
+       database has them. The schema is documented in Oystermark.Sql_export. \
+       Needs the sqlite3 command, unless --sql prints the script instead.")
+    (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+     and (db : string option) =
+       flag "--sqlite" (optional string) ~doc:"PATH Database to write, e.g. vault.db"
+     and (print_sql : bool) =
+       flag "--sql" no_arg ~doc:" Print the SQL script to stdout instead"
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     in
+     fun () ->
+       let config = Option.map config_file ~f:Config.of_file in
+       let vault = Vault.of_root_path ~skip_expand:true ?config vault_root in
+       let sql =
+         Sql_export.to_sql
+           ~source_of:(fun p -> In_channel.read_all (Filename.concat vault_root p))
+           vault
+       in
+       match print_sql, db with
+       | true, _ -> print_string sql
+       | false, Some db -> Sql_export.write ~db sql |> ok_exn
+       | false, None ->
+         eprintf "error: give --sqlite PATH or --sql\n";
+         exit 1)
+;;
+
+let search_cmd : Command.t =
+  Command.basic
+    ~summary:"Full-text search over a vault's notes"
+    ~readme:(fun () ->
+      "Ranks sections by BM25 over their text, counting words of headings, \
+       note titles and tags more. Each result is NOTE:LINE and the section \
+       heading, then the best matching line with the query words highlighted.\n\n\
+       With --semantic, ranks sections by similarity of embeddings instead, \
+       from --embed-command (reads a JSON list of texts, prints a JSON list of \
+       vectors) or --embed-url (an OpenAI-style embeddings endpoint; the key is \
+       read from OYSTER_EMBED_API_KEY). Vectors are kept in the --db SQLite \
+       database and only changed sections are embedded again.")
+    (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+     and (query : string) = anon ("query" %: string)
+     and (tag : string option) =
+       flag "--tag" (optional string) ~doc:"TAG Only notes with this frontmatter tag"
+     and (path : string option) =
+       flag "--path" (optional string) ~doc:"PREFIX Only notes under this path"
+     and (section : string option) =
+       flag "--section" (optional string) ~doc:"TEXT Only sections whose heading has TEXT"
+     and (limit : int) =
+       flag "--limit" (optional_with_default 20 int) ~doc:"N At most N results (20)"
+     and (semantic : bool) =
+       flag "--semantic" no_arg ~doc:" Rank by embedding similarity"
+     and (db : string option) =
+       flag
+         "--db"
+         (optional string)
+         ~doc:"PATH Database for embeddings (default VAULT/.oyster/index.db)"
+     and (embed_command : string option) =
+       flag "--embed-command" (optional string) ~doc:"CMD Local embedding command"
+     and (embed_url : string option) =
+       flag "--embed-url" (optional string) ~doc:"URL Embeddings endpoint"
+     and (embed_model : string option) =
+       flag "--embed-model" (optional string) ~doc:"NAME Model to ask the endpoint for"
+     and (config_file : string option) =
+       flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+     in
+     fun () ->
+       let config = Option.map config_file ~f:Config.of_file in
+       let vault = Vault.of_root_path ~skip_expand:true ?config vault_root in
+       let index =
+         Query.Search.build
+           (List.map vault.docs ~f:(fun (p, doc) ->
+              p, doc, In_channel.read_all (Filename.concat vault_root p)))
+       in
+       let highlight =
+         if Core_unix.isatty Core_unix.stdout then sprintf "\027[1m%s\027[0m" else Fn.id
+       in
+       let results =
+         if not semantic
+         then Query.Search.search ?tag ?path ?section ~limit ~highlight index query
+         else (
+           let provider : Embedding.provider =
+             match embed_command, embed_url, embed_model with
+             | Some cmd, None, model ->
+               Embedding.command ~model:(Option.value model ~default:cmd) cmd
+             | None, Some url, Some model ->
+               Embedding.http ?api_key:(Sys.getenv "OYSTER_EMBED_API_KEY") ~model url
+             | _ ->
+               eprintf
+                 "error: --semantic needs --embed-command, or --embed-url and \
+                  --embed-model\n";
+               exit 1
+           in
+           let db =
+             Option.value db ~default:(Filename.concat vault_root ".oyster/index.db")
+           in
+           Core_unix.mkdir_p (Filename.dirname db);
+           let embedded = Embedding.update ~provider ~db index |> ok_exn in
+           if embedded > 0 then eprintf "embedded %d sections\n%!" embedded;
+           Embedding.search ?tag ?path ?section ~limit ~provider ~db index query
+           |> ok_exn)
+       in
+       results
+       |> List.iter ~f:(fun (r : Query.Search.result) ->
+         printf
+           "%s%s  %s\n"
+           r.note
+           (Option.value_map r.line ~default:"" ~f:(sprintf ":%d"))
+           r.section_title;
+         if not (String.is_empty r.snippet) then printf "    %s\n" r.snippet))
+;;
+
+let export_cmd : Command.t =
+  let corpus =
+    Command.basic
+      ~summary:"Write a vault's notes as JSONL chunks for retrieval pipelines"
+      ~readme:(fun () ->
+        "One JSON object per line: {id, note, section_path, text, links, tags}. \
+         Ids are the note path, then #anchor of the section's heading, then :k \
+         for the k-th part of a chunk split to fit --max-tokens (counted as four \
+         characters a token). Only notes the site would publish and list are \
+         exported. See Oystermark.Corpus.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (chunk_by : Corpus.chunk_by) =
+         flag
+           "--chunk-by"
+           (optional_with_default
+              Corpus.Section
+              (Arg_type.create (fun s ->
+                 match Corpus.chunk_by_of_string s with
+                 | Some c -> c
+                 | None -> failwithf "unknown chunking %s (section, note)" s ())))
+           ~doc:"UNIT section or note (default section)"
+       and (max_tokens : int option) =
+         flag "--max-tokens" (optional int) ~doc:"N Split chunks over N tokens"
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       and (output : string option) =
+         flag "--output" (optional string) ~doc:"PATH Write to file instead of stdout"
+       in
+       fun () ->
+         let config =
+           Option.value_map config_file ~default:Config.default ~f:Config.of_file
+         in
+         let vault = Vault.of_root_path ~skip_expand:true ~config vault_root in
+         let folders =
+           Vault.Folder_config.load
+             ~read_file:(fun p ->
+               Option.try_with (fun () ->
+                 In_channel.read_all (Filename.concat vault_root p)))
+             (List.filter
+                (Vault.list_entries vault_root)
+                ~f:(String.is_suffix ~suffix:"/"))
+         in
+         let lines =
+           Corpus.of_vault
+             ~chunk_by
+             ?max_tokens
+             ~publishes:(Pipeline.publishes ~folders ~config)
+             ~source_of:(fun p -> In_channel.read_all (Filename.concat vault_root p))
+             vault
+           |> List.map ~f:(fun c -> Yojson.Safe.to_string (Corpus.json_of_chunk c) ^ "\n")
+         in
+         match output with
+         | Some path -> Out_channel.write_all path ~data:(String.concat lines)
+         | None -> List.iter lines ~f:print_string)
+  in
+  let mdbook =
+    Command.basic
+      ~summary:"Write a vault folder as an mdBook (book.toml, src/SUMMARY.md, chapters)"
+      ~readme:(fun () ->
+        "The book follows FOLDER's tree, or with --index the lists of an index \
+         note. Only notes the site would publish (see the config's publish \
+         field) become chapters. Wikilinks become relative Markdown links and \
+         linked files are copied. See Oystermark.Mdbook.")
+      (let%map_open.Command (vault_root : string) = anon ("vault-root" %: string)
+       and (folder : string option) = anon (maybe ("folder" %: string))
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       and (index : string option) =
+         flag
+           "--index"
+           (optional string)
+           ~doc:"NOTE Take the book's chapters from the lists of index note NOTE"
+       and (title : string option) =
+         flag "--title" (optional string) ~doc:"TITLE Book title (default: the folder's)"
+       and (output : string) =
+         flag
+           "--output"
+           (optional_with_default "book" string)
+           ~doc:"DIR Directory to write the book to (default: book)"
+       in
+       fun () ->
+         let folder = Option.value folder ~default:"" in
+         let config =
+           Option.value_map config_file ~default:Config.default ~f:Config.of_file
+         in
+         let vault = Vault.of_root_path ~skip_expand:true ~config vault_root in
+         let folders =
+           Vault.Folder_config.load
+             ~read_file:(fun p ->
+               Option.try_with (fun () ->
+                 In_channel.read_all (Filename.concat vault_root p)))
+             (List.filter
+                (Vault.list_entries vault_root)
+                ~f:(String.is_suffix ~suffix:"/"))
+         in
+         let book : Mdbook.t =
+           match
+             Mdbook.of_vault
+               ~compare_path:
+                 (Pipeline.compare_path_of_sidebar_order
+                    ~toc_order:config.toc_order
+                    config.sidebar.order
+                    vault.docs)
+               ~publishes:(Pipeline.publishes ~folders ~config)
+               ?index
+               ~title:
+                 (Option.value_or_thunk title ~default:(fun () ->
+                    let dir = if String.is_empty folder then vault_root else folder in
+                    if String.equal dir "."
+                    then Filename.basename (Sys_unix.getcwd ())
+                    else Filename.basename (String.rstrip dir ~drop:(Char.equal '/'))))
+               ~folder
+               ~source_of:(fun p -> In_channel.read_all (Filename.concat vault_root p))
+               vault
+           with
+           | Ok book -> book
+           | Error e ->
+             eprintf "error: --index: %s\n" (Error.to_string_hum e);
+             exit 1
+         in
+         let write (rel_path : string) (data : string) : unit =
+           let path = Filename.concat output rel_path in
+           Core_unix.mkdir_p (Filename.dirname path);
+           Out_channel.write_all path ~data
+         in
+         List.iter book.files ~f:(fun (rel_path, data) -> write rel_path data);
+         List.iter book.assets ~f:(fun (rel_path, src) ->
+           write rel_path (In_channel.read_all (Filename.concat vault_root src))))
+  in
+  let slides =
+    Command.basic
+      ~summary:"Write a note as a reveal.js slide deck or as Marp markdown"
+      ~readme:(fun () ->
+        "NOTE is a path under --vault. Slides end at top-level thematic breaks \
+         (---), or with --split h2 start at each ## heading; a > [!notes] \
+         callout on a slide holds its speaker notes. As on the site, only \
+         published notes are decks or embedded in one, and encrypted blocks \
+         are left out. The reveal.js page is in the --theme's colors; build \
+         the Marp markdown with marp-cli, written next to NOTE, as its links \
+         to images and files are relative to it. See Oystermark.Slides.")
+      (let%map_open.Command (note : string) = anon ("note" %: string)
+       and (split : Slides.split) =
+         flag
+           "--split"
+           (optional_with_default
+              Slides.Rule
+              (Arg_type.create (fun s ->
+                 match Slides.split_of_string s with
+                 | Some split -> split
+                 | None -> failwithf "unknown split %S (rule, h2)" s ())))
+           ~doc:"rule|h2 Where slides start (default rule)"
+       and (format : Slides.format) =
+         flag
+           "--format"
+           (optional_with_default
+              Slides.Reveal
+              (Arg_type.create (fun s ->
+                 match Slides.format_of_string s with
+                 | Some format -> format
+                 | None -> failwithf "unknown format %S (reveal, marp)" s ())))
+           ~doc:"reveal|marp Deck format (default reveal)"
+       and (theme : string option) =
+         flag
+           "--theme"
+           (optional string)
+           ~doc:"NAME Theme whose colors the deck takes (default: the config's)"
+       and (vault_root : string) =
+         flag
+           "--vault"
+           (optional_with_default "." string)
+           ~doc:"DIR Vault root NOTE is relative to (default: .)"
+       and (config_file : string option) =
+         flag "--config" (optional string) ~doc:"PATH Path to a JSON config file"
+       and (output : string option) =
+         flag "--output" (optional string) ~doc:"PATH Write to file instead of stdout"
+       in
+       fun () ->
+         let note = String.chop_prefix_if_exists note ~prefix:"./" in
+         let config =
+           Option.value_map config_file ~default:Config.default ~f:Config.of_file
+         in
+         let folders =
+           Vault.Folder_config.load
+             ~read_file:(fun p ->
+               Option.try_with (fun () ->
+                 In_channel.read_all (Filename.concat vault_root p)))
+             (List.filter
+                (Vault.list_entries vault_root)
+                ~f:(String.is_suffix ~suffix:"/"))
+         in
+         (* As the site build does: only published notes, without their
+            encrypted blocks, and embeds expanded among those alone *)
+         let vault = Vault.of_root_path ~skip_expand:true ~config vault_root in
+         let docs : (string * Cmarkit.Doc.t) list =
+           List.filter_map vault.docs ~f:(fun (path, doc) ->
+             Option.some_if
+               (Pipeline.publishes ~folders ~config path doc)
+               (path, Parse.Encrypted.strip doc))
+           |> Vault.Embed.expand_docs
+                ~slug_style:(Parse.Heading_slug.of_config config.parse.slug_style)
+                ~max_depth:config.embeds.max_depth
+         in
+         let vault = { vault with docs } in
+         let doc : Cmarkit.Doc.t =
+           match List.Assoc.find vault.docs ~equal:String.equal note with
+           | Some doc -> doc
+           | None ->
+             eprintf "error: no published note %s in %s\n" note vault_root;
+             exit 1
+         in
+         let colors : Theme.colors option =
+           Option.value_map theme ~default:config.theme ~f:Config.Theme.of_string
+           |> Theme.colors_of_name
+         in
+         let slides : Slides.slide list = Slides.of_doc ~split doc in
+         let data : string =
+           match format with
+           | Slides.Reveal ->
+             Slides.to_reveal
+               ?colors
+               ~urls:(Url.of_docs ~style:config.url_style vault.docs)
+               ~title:
+                 (String.chop_suffix_if_exists (Filename.basename note) ~suffix:".md")
+               slides
+           | Slides.Marp -> Slides.to_marp ?colors ~path:note slides
+         in
+         match output with
+         | Some path -> Out_channel.write_all path ~data
+         | None -> print_string data)
+  in
+  Command.group
+    ~summary:"Export a vault for other tools"
+    [ "corpus", corpus; "mdbook", mdbook; "slides", slides ]
+;;
+
 let () =
   Command.group
     ~summary:"Oystermark renderer"
-    [ "file", file_cmd; "vault", vault_cmd; "graph", graph_cmd ]
+    [ "file", file_cmd
+    ; "vault", vault_cmd
+    ; "graph", graph_cmd
+    ; "query", query_cmd
+    ; "outline-diagram", outline_diagram_cmd
+    ; "fix-levels", fix_levels_cmd
+    ; "ast", ast_cmd
+    ; "snapshot", snapshot_cmd
+    ; "doctor", doctor_cmd
+    ; "lint", lint_cmd
+    ; "links", links_cmd
+    ; "assets", assets_cmd
+    ; "new", new_cmd
+    ; "daemon", daemon_cmd
+    ; "index", index_cmd
+    ; "search", search_cmd
+    ; "export", export_cmd
+    ]
   |> Command_unix.run ~version:"0.1.0"
 ;;
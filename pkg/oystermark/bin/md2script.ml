@@ -20,17 +20,8 @@ let is_python_lang (lang : string) : bool =
 
 (** Classify a block as a Python code block or not. *)
 let python_code_block_content (b : Cmarkit.Block.t) : string option =
-  match b with
-  | Cmarkit.Block.Code_block (cb, meta) ->
-    let cb_info = Cmarkit.Meta.find Cb_attribute.meta_key meta in
-    (match cb_info with
-     | Some { lang; _ } when is_python_lang lang ->
-       let content =
-         List.map (Cmarkit.Block.Code_block.code cb) ~f:Cmarkit.Block_line.to_string
-         |> String.concat ~sep:"\n"
-       in
-       Some content
-     | _ -> None)
+  match Query.Code_meta.of_block b with
+  | Some { lang = Some lang; content; _ } when is_python_lang lang -> Some content
   | _ -> None
 ;;
 
@@ -337,7 +328,7 @@ let decode (script : string) : Cmarkit.Doc.t =
   let all_blocks = List.rev !blocks in
   let all_blocks =
     match !frontmatter with
-    | Some fm -> Frontmatter.Frontmatter fm :: all_blocks
+    | Some fm -> Frontmatter.Frontmatter (fm, Frontmatter.Yaml) :: all_blocks
     | None -> all_blocks
   in
   let top = Cmarkit.Block.Blocks (all_blocks, Cmarkit.Meta.none) in
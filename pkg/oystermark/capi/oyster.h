@@ -0,0 +1,44 @@
+/* C API of oystermark, for native editor plugins that embed the parser
+   instead of spawning the oystermark binary. Link against oyster_capi.so.
+
+   Call oyster_init once before anything else, from the thread that makes
+   every later call; until then every function answers
+   {"error": "oyster_init was not called"}. Functions returning char * return a NUL-terminated JSON
+   string, {"result": ...} or {"error": "..."}, owned by the caller: release
+   it with oyster_free. */
+
+#ifndef OYSTER_H
+#define OYSTER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Start the OCaml runtime. Safe to call more than once. */
+void oyster_init(void);
+
+/* The parsed document: {"kind": "Doc", ...} as `oystermark ast --format json`
+   prints it. */
+char *oyster_parse_json(const char *source);
+
+/* Sections of the document: titles, paths and word counts, as
+   `oystermark query FILE -- --summary-json`. */
+char *oyster_sections_json(const char *source);
+
+/* Where link (e.g. "[[Note#Usage]]", "Note#^id" or "notes/a.md") points from
+   curr_file, a path relative to vault_root. The vault is indexed on the
+   first call for vault_root and reused until oyster_invalidate. */
+char *oyster_resolve_link(const char *vault_root, const char *curr_file,
+                          const char *link);
+
+/* Drop the index of vault_root, e.g. after its files changed. */
+void oyster_invalidate(const char *vault_root);
+
+/* Release a string returned by this API. */
+void oyster_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* OYSTER_H */
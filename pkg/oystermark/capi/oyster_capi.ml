@@ -0,0 +1,49 @@
+(** OCaml side of the C API in [oyster.h]: each function is registered under
+    the name its C wrapper ([oyster_stubs.c]) looks up, takes and returns
+    strings, and answers in JSON as {!Query.Json_api} does. *)
+
+open Core
+open Oystermark
+
+(* Index of each vault asked about, built on first use; see [invalidate]. *)
+let vaults : (string, Vault.Index.t * Vault.Index.Lookup.t) Hashtbl.t =
+  Hashtbl.create (module String)
+;;
+
+let index_of (vault_root : string) : Vault.Index.t * Vault.Index.Lookup.t =
+  Hashtbl.find_or_add vaults vault_root ~default:(fun () ->
+    let vault = Vault.of_root_path ~skip_expand:true vault_root in
+    vault.index, Vault.Index.Lookup.create vault.index.files)
+;;
+
+(** Where [link] — a wikilink with or without its brackets ([[[Note#Usage]]],
+    [Note#^id], [Note|shown]) or a Markdown destination — points from
+    [curr_file] in the vault at [vault_root]: [{"result": target}] with
+    [target] as {!Vault.Resolve.target}'s JSON. *)
+let resolve_link (vault_root : string) (curr_file : string) (link : string) : string =
+  Query.Json_api.guard (fun () ->
+    let link =
+      String.chop_prefix_if_exists (String.strip link) ~prefix:"!"
+      |> String.chop_prefix_if_exists ~prefix:"[["
+      |> String.chop_suffix_if_exists ~suffix:"]]"
+    in
+    let link = Option.value_map (String.lsplit2 link ~on:'|') ~default:link ~f:fst in
+    match Vault.Link_ref.of_cmark_dest link with
+    | None -> Query.Json_api.error_json ("external link: " ^ link)
+    | Some link_ref ->
+      let index, lookup = index_of vault_root in
+      let target = Vault.Resolve.resolve ~lookup link_ref curr_file index in
+      `Assoc [ "result", Vault.Resolve.yojson_of_target target ])
+;;
+
+(** Forget the index of [vault_root], after its files changed. *)
+let invalidate (vault_root : string) : unit = Hashtbl.remove vaults vault_root
+
+let () =
+  Callback.register "oyster_parse_json" (fun (source : string) ->
+    Query.Json_api.parse source);
+  Callback.register "oyster_sections_json" (fun (source : string) ->
+    Query.Json_api.query [ "--summary-json" ] source);
+  Callback.register "oyster_resolve_link" resolve_link;
+  Callback.register "oyster_invalidate" invalidate
+;;
@@ -0,0 +1,93 @@
+/* C wrappers of the functions oyster_capi.ml registers; see oyster.h. */
+
+#include <stdlib.h>
+#include <string.h>
+
+#define CAML_NAME_SPACE
+#include <caml/alloc.h>
+#include <caml/callback.h>
+#include <caml/memory.h>
+#include <caml/mlvalues.h>
+
+#include "oyster.h"
+
+static int initialized = 0;
+
+void oyster_init(void)
+{
+  static char *argv[] = { "oyster", NULL };
+  if (!initialized) {
+    caml_startup(argv);
+    initialized = 1;
+  }
+}
+
+static char *error_json(const char *msg)
+{
+  const char *prefix = "{\"error\":\"";
+  const char *suffix = "\"}";
+  char *out = malloc(strlen(prefix) + strlen(msg) + strlen(suffix) + 1);
+  if (out != NULL) {
+    strcpy(out, prefix);
+    strcat(out, msg);
+    strcat(out, suffix);
+  }
+  return out;
+}
+
+/* Call the OCaml function registered as [name] on [argc] strings. Returns a
+   copy of its string result, or NULL when [name] returns unit. */
+static char *call_ocaml(const char *name, int argc, const char **args)
+{
+  CAMLparam0();
+  CAMLlocal1(res);
+  CAMLlocalN(vargs, 3);
+  const value *f = caml_named_value(name);
+  char *out;
+  int i;
+  if (f == NULL) CAMLreturnT(char *, error_json("unknown function"));
+  for (i = 0; i < argc; i++) vargs[i] = caml_copy_string(args[i]);
+  res = caml_callbackN_exn(*f, argc, vargs);
+  if (Is_exception_result(res))
+    out = error_json("uncaught OCaml exception");
+  else if (Is_long(res))
+    out = NULL;
+  else
+    out = strdup(String_val(res));
+  CAMLreturnT(char *, out);
+}
+
+/* [call_ocaml], once the runtime is up: the CAML macros touch the runtime's
+   state, so nothing of it may run before oyster_init. */
+static char *call(const char *name, int argc, const char **args)
+{
+  if (!initialized) return error_json("oyster_init was not called");
+  return call_ocaml(name, argc, args);
+}
+
+char *oyster_parse_json(const char *source)
+{
+  const char *args[] = { source };
+  return call("oyster_parse_json", 1, args);
+}
+
+char *oyster_sections_json(const char *source)
+{
+  const char *args[] = { source };
+  return call("oyster_sections_json", 1, args);
+}
+
+char *oyster_resolve_link(const char *vault_root, const char *curr_file,
+                          const char *link)
+{
+  const char *args[] = { vault_root, curr_file, link };
+  return call("oyster_resolve_link", 3, args);
+}
+
+void oyster_invalidate(const char *vault_root)
+{
+  const char *args[] = { vault_root };
+  free(call("oyster_invalidate", 1, args));
+}
+
+void oyster_free(char *s) { free(s); }
@@ -0,0 +1,52 @@
+/* Calls the C API before and after oyster_init, as a plugin might. Prints a
+   line per failed check and exits 1 if there was any. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "oyster.h"
+
+static int failures = 0;
+
+static void check(const char *what, int ok, const char *got)
+{
+  if (!ok) {
+    printf("FAIL %s: %s\n", what, got == NULL ? "NULL" : got);
+    failures++;
+  }
+}
+
+static int starts_with(const char *s, const char *prefix)
+{
+  return s != NULL && strncmp(s, prefix, strlen(prefix)) == 0;
+}
+
+int main(void)
+{
+  const char *not_init = "{\"error\":\"oyster_init was not called\"}";
+  char *s;
+
+  s = oyster_parse_json("# A\n");
+  check("parse before init", s != NULL && strcmp(s, not_init) == 0, s);
+  oyster_free(s);
+  s = oyster_sections_json("# A\n");
+  check("sections before init", s != NULL && strcmp(s, not_init) == 0, s);
+  oyster_free(s);
+  s = oyster_resolve_link(".", "a.md", "[[b]]");
+  check("resolve before init", s != NULL && strcmp(s, not_init) == 0, s);
+  oyster_free(s);
+  oyster_invalidate(".");
+
+  oyster_init();
+  oyster_init();
+
+  s = oyster_parse_json("# A\n");
+  check("parse after init", s != NULL && !starts_with(s, "{\"error\""), s);
+  oyster_free(s);
+  s = oyster_sections_json("# A\n");
+  check("sections after init", starts_with(s, "{\"result\""), s);
+  oyster_free(s);
+  oyster_invalidate(".");
+
+  return failures == 0 ? 0 : 1;
+}
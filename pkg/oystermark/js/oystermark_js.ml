@@ -0,0 +1,28 @@
+(** [oyster.parse(source)] and [oyster.query(source, args)] for web apps and
+    Obsidian plugins, each returning a JSON string; see {!Query.Json_api}.
+    [args] are query expressions as on the command line, e.g.
+    [["--field", "Usage", "--codemeta"]].
+
+    ocaml-yaml's C stubs have no JavaScript implementation, so this build
+    reads YAML frontmatter with [Yaml_lite], which covers the subset
+    frontmatter uses. *)
+
+open Js_of_ocaml
+
+let () =
+  Js.export
+    "oyster"
+    (object%js
+       method parse (source : Js.js_string Js.t) : Js.js_string Js.t =
+         Js.string (Query.Json_api.parse (Js.to_string source))
+
+       method query
+         (source : Js.js_string Js.t)
+         (args : Js.js_string Js.t Js.js_array Js.t)
+         : Js.js_string Js.t =
+         let args : string list =
+           Array.to_list (Array.map Js.to_string (Js.to_array args))
+         in
+         Js.string (Query.Json_api.query args (Js.to_string source))
+    end)
+;;
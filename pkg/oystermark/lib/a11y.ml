@@ -0,0 +1,176 @@
+(** Accessibility checks on a rendered site, for [oystermark vault --a11y-check]:
+    images without alt text and skipped heading levels in each page's
+    [<main>], and theme colors whose text contrast is below WCAG AA. *)
+
+open Core
+
+type problem =
+  | Missing_alt of string (** the image's [src] *)
+  | Heading_skip of int * int (** a heading level, then the deeper one after it *)
+  | Low_contrast of
+      { fg : string (** color names, as in {!Theme.colors} *)
+      ; bg : string
+      ; ratio : float
+      ; min : float
+      }
+[@@deriving sexp_of]
+
+type issue =
+  { page : string option (** output path; [None] for theme issues *)
+  ; problem : problem
+  }
+[@@deriving sexp_of]
+
+let issue_to_string_hum (i : issue) : string =
+  let what =
+    match i.problem with
+    | Missing_alt src -> sprintf "image %s has no alt text" src
+    | Heading_skip (a, b) -> sprintf "heading h%d follows h%d" b a
+    | Low_contrast { fg; bg; ratio; min } ->
+      sprintf "theme %s on %s has contrast %.2f:1, below %.1f:1" fg bg ratio min
+  in
+  match i.page with
+  | Some page -> page ^ ": " ^ what
+  | None -> what
+;;
+
+(* {2 Contrast} *)
+
+(* Relative luminance of a [#rrggbb] color, per WCAG 2. *)
+let luminance (color : string) : float option =
+  match String.chop_prefix color ~prefix:"#" with
+  | Some hex when String.length hex = 6 && String.for_all hex ~f:Char.is_hex_digit ->
+    let channel (i : int) : float =
+      let c = Float.of_int (Int.of_string ("0x" ^ String.sub hex ~pos:i ~len:2)) in
+      let c = c /. 255. in
+      Float.O.(if c <= 0.03928 then c / 12.92 else ((c + 0.055) / 1.055) ** 2.4)
+    in
+    Some ((0.2126 *. channel 0) +. (0.7152 *. channel 2) +. (0.0722 *. channel 4))
+  | _ -> None
+;;
+
+(** WCAG contrast ratio of two [#rrggbb] colors, from 1 to 21; [None] for
+    anything else, e.g. a [var(...)]. *)
+let contrast_ratio (a : string) (b : string) : float option =
+  let%bind.Option la = luminance a in
+  let%map.Option lb = luminance b in
+  (Float.max la lb +. 0.05) /. (Float.min la lb +. 0.05)
+;;
+
+(** Text colors of [c] against the backgrounds they're drawn on: body text and
+    links need AA's 4.5:1, dimmed secondary text 3:1. *)
+let contrast (c : Theme.colors) : issue list =
+  [ "fg", c.fg, "bg", c.bg, 4.5
+  ; "fg", c.fg, "bg_alt", c.bg_alt, 4.5
+  ; "accent", c.accent, "bg", c.bg, 4.5
+  ; "fg_dim", c.fg_dim, "bg", c.bg, 3.
+  ]
+  |> List.filter_map ~f:(fun (fg, fg_color, bg, bg_color, min) ->
+    match contrast_ratio fg_color bg_color with
+    | Some ratio when Float.(ratio < min) ->
+      Some { page = None; problem = Low_contrast { fg; bg; ratio; min } }
+    | _ -> None)
+;;
+
+(* {2 Pages} *)
+
+(* The start tags in [html] named [name], each from ['<'] to ['>']. *)
+let tags (name : string) (html : string) : string list =
+  let open_ = "<" ^ name in
+  let rec go (pos : int) (acc : string list) : string list =
+    match String.substr_index html ~pattern:open_ ~pos with
+    | None -> List.rev acc
+    | Some i ->
+      let after = i + String.length open_ in
+      let stop =
+        String.index_from html i '>' |> Option.value ~default:(String.length html - 1)
+      in
+      (* [<image] isn't an [<img] *)
+      if after < String.length html && Char.is_alphanum html.[after]
+      then go after acc
+      else go (stop + 1) (String.sub html ~pos:i ~len:(stop + 1 - i) :: acc)
+  in
+  go 0 []
+;;
+
+(* The page's [<main>], or all of it without one: the theme's chrome around
+   it isn't the note's to fix. *)
+let main_of (html : string) : string =
+  match String.substr_index html ~pattern:"<main" with
+  | None -> html
+  | Some start ->
+    let stop =
+      String.substr_index html ~pattern:"</main>" ~pos:start
+      |> Option.value ~default:(String.length html)
+    in
+    String.sub html ~pos:start ~len:(stop - start)
+;;
+
+let missing_alt (main : string) : problem list =
+  List.filter_map (tags "img" main) ~f:(fun tag ->
+    match Component.Anchors.attr_values "alt" tag with
+    | alt :: _ when not (String.is_empty (String.strip alt)) -> None
+    | _ ->
+      let src = List.hd (Component.Anchors.attr_values "src" tag) in
+      Some (Missing_alt (Option.value src ~default:"(no src)")))
+;;
+
+(* Levels of the [<h1>]..[<h6>] tags in [main], in order. *)
+let heading_levels (main : string) : int list =
+  List.concat_map (List.range 1 7) ~f:(fun level ->
+    let rec go (pos : int) (acc : (int * int) list) =
+      match String.substr_index main ~pattern:(sprintf "<h%d" level) ~pos with
+      | None -> acc
+      | Some i -> go (i + 3) ((i, level) :: acc)
+    in
+    go 0 [])
+  |> List.sort ~compare:(fun (a, _) (b, _) -> Int.compare a b)
+  |> List.map ~f:snd
+;;
+
+(* A heading more than one level deeper than the one before it. *)
+let heading_skips (main : string) : problem list =
+  let rec skips : int list -> problem list = function
+    | a :: (b :: _ as rest) when b > a + 1 -> Heading_skip (a, b) :: skips rest
+    | _ :: rest -> skips rest
+    | [] -> []
+  in
+  skips (heading_levels main)
+;;
+
+(** Issues of the rendered [pages] (output path and content; only [.html]
+    outputs are looked at) and, given its [colors], of the theme. *)
+let check ?(colors : Theme.colors option) (pages : (string * string) list) : issue list =
+  let page_issues =
+    List.concat_map pages ~f:(fun (page, html) ->
+      if not (String.is_suffix page ~suffix:".html")
+      then []
+      else (
+        let main = main_of html in
+        missing_alt main @ heading_skips main
+        |> List.map ~f:(fun problem -> { page = Some page; problem })))
+  in
+  Option.value_map colors ~default:[] ~f:contrast @ page_issues
+;;
+
+let%expect_test "check" =
+  let page =
+    {|<img src="/logo.png" alt="">
+<main id="content"><h1 class="page-title">Setup</h1>
+<p><img src="/a.png" alt="Diagram"> <img src="/b.png" alt=""> <img src="/c.png"></p>
+<h2 id="x">X</h2><h4 id="y">Y</h4><h3 id="z">Z</h3><header>not a heading</header>
+</main>|}
+  in
+  let colors : Theme.colors =
+    { Theme.tokyonight_colors with fg_dim = "#3b4261"; accent = "var(--fg)" }
+  in
+  check ~colors [ "Setup/index.html", page; "search.json", page ]
+  |> List.iter ~f:(fun i -> print_endline (issue_to_string_hum i));
+  [%expect
+    {|
+    theme fg_dim on bg has contrast 1.74:1, below 3.0:1
+    Setup/index.html: image /b.png has no alt text
+    Setup/index.html: image /c.png has no alt text
+    Setup/index.html: heading h4 follows h2
+    |}]
+;;
@@ -5,11 +5,13 @@ include Common
 module Cache = Cache
 module Uv = Uv
 module Jupyter = Jupyter
+module Process = Process
 
-(** Hash function that keys on code blocks of language [lang], ignoring config *)
-let hash_fn_of_lang (lang : string) : exec_ctx -> string =
+(** Hash function that keys on code blocks of language [lang], ignoring config.
+    [salt] keys on whatever else shapes the output, e.g. diagram colors. *)
+let hash_fn_of_lang ?(salt : string option) (lang : string) : exec_ctx -> string =
   Cache.make_hash_fn
-    ~config_filter:(fun _ -> None)
+    ~config_filter:(fun _ -> salt)
     ~cell_filter:(fun (c : cell) ->
       match c.lang with
       | Some l when String.equal (String.lowercase l) lang -> Some c.content
@@ -192,14 +194,43 @@ let bash_executor
   outputs |> List.concat |> List.sort ~compare:(fun a b -> Int.compare a.id b.id)
 ;;
 
+(** Colors build-time diagrams are drawn in, taken from the site theme so a
+    diagram doesn't sit as a white box on a dark page. *)
+type diagram_colors =
+  { fg : string (** text, lines and outlines *)
+  ; bg : string (** node fill *)
+  ; accent : string (** cluster outlines *)
+  }
+[@@deriving sexp]
+
+(* Graphviz defaults for [colors]: a transparent canvas and theme-colored
+   text, nodes and edges. Attributes set in the source still win. *)
+let dot_color_args (colors : diagram_colors) : string =
+  [ "-Gbgcolor=transparent"
+  ; "-Gcolor=" ^ colors.accent
+  ; "-Gfontcolor=" ^ colors.fg
+  ; "-Ncolor=" ^ colors.fg
+  ; "-Nfontcolor=" ^ colors.fg
+  ; "-Nfillcolor=" ^ colors.bg
+  ; "-Ecolor=" ^ colors.fg
+  ; "-Efontcolor=" ^ colors.fg
+  ]
+  |> List.map ~f:Filename.quote
+  |> String.concat ~sep:" "
+;;
+
 (** Executor for [dot] code blocks. Pipes each cell through [dot -Tsvg] (or
     the layout engine specified via the [layout] attribute) and returns inline
     SVG.
 
+    @param colors draw in these colors by default (see {!diagram_colors}).
     @param on_error [`Keep_original] (default) omits the output so the source
     block is left untouched; [`Show_error] returns an [Error] result so the
     renderer shows the stderr message. *)
-let dot_executor ?(on_error : [ `Keep_original | `Show_error ] = `Keep_original)
+let dot_executor
+      ?(colors : diagram_colors option)
+      ?(on_error : [ `Keep_original | `Show_error ] = `Keep_original)
+      ()
   : executor
   =
   fun ctx ->
@@ -212,7 +243,12 @@ let dot_executor ?(on_error : [ `Keep_original | `Show_error ] = `Keep_original)
           Option.value (List.Assoc.find kvs ~equal:String.equal "layout") ~default:"dot"
         | None -> "dot"
       in
-      let cmd = sprintf "%s -Tsvg" layout in
+      let cmd =
+        sprintf
+          "%s -Tsvg%s"
+          layout
+          (Option.value_map colors ~default:"" ~f:(fun c -> " " ^ dot_color_args c))
+      in
       let env = Core_unix.environment () in
       let pc = Core_unix.open_process_full ~env cmd in
       Out_channel.output_string pc.stdin cell.content;
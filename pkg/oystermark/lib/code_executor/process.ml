@@ -0,0 +1,39 @@
+(** Shell commands fed a string on stdin, for hooks and embedding providers. *)
+
+open Core
+
+(** The output of shell command [cmd] given [input] on stdin; an error if [cmd]
+    exits non-zero. [input] goes through a temporary file rather than a pipe,
+    so a command that prints before it has read everything can't block on us
+    while we block writing to it. *)
+let run (cmd : string) ~(input : string) : string Or_error.t =
+  let file = Filename_unix.temp_file "oyster" ".stdin" in
+  Exn.protect
+    ~finally:(fun () ->
+      try Sys_unix.remove file with
+      | _ -> ())
+    ~f:(fun () ->
+      match
+        Out_channel.write_all file ~data:input;
+        let ic =
+          Core_unix.open_process_in (sprintf "(%s\n) < %s" cmd (Filename.quote file))
+        in
+        let output = In_channel.input_all ic in
+        output, Core_unix.close_process_in ic
+      with
+      | output, Ok () -> Ok output
+      | _, Error _ -> Or_error.errorf "%s failed" cmd
+      | exception e -> Or_error.of_exn e)
+;;
+
+let%expect_test "run" =
+  let big = String.make 1_000_000 'x' in
+  (match run "cat" ~input:big with
+   | Ok output -> printf "%b\n" (String.equal output big)
+   | Error e -> print_endline (Error.to_string_hum e));
+  [%expect {| true |}];
+  (match run "cat > /dev/null; exit 3" ~input:big with
+   | Ok _ -> print_endline "ok"
+   | Error e -> print_endline (Error.to_string_hum e));
+  [%expect {| cat > /dev/null; exit 3 failed |}]
+;;
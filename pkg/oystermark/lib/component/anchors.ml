@@ -0,0 +1,135 @@
+(** Broken anchors in a rendered site: intra-site links
+    ([href="/Guide/#install"], [href="#usage"]) to a page that has no element
+    with that id. These usually come from the link and the heading slugging a
+    title differently. Checked on the rendered pages, so every link the
+    renderer wrote is covered, wherever it came from. *)
+
+open Core
+
+type broken =
+  { page : string (** output path of the linking page *)
+  ; href : string
+  ; target : string (** output path of the linked page *)
+  ; anchor : string (** the missing id, decoded *)
+  }
+[@@deriving sexp_of]
+
+let unescape (s : string) : string =
+  List.fold
+    [ "&quot;", "\""
+    ; "&#39;", "'"
+    ; "&#x27;", "'"
+    ; "&lt;", "<"
+    ; "&gt;", ">"
+    ; "&amp;", "&"
+    ]
+    ~init:s
+    ~f:(fun s (pattern, with_) -> String.substr_replace_all s ~pattern ~with_)
+;;
+
+(* Double-quoted values of attribute [name] in [html], in order. *)
+let attr_values (name : string) (html : string) : string list =
+  let needle = " " ^ name ^ "=\"" in
+  let rec go (pos : int) (acc : string list) : string list =
+    match String.substr_index html ~pattern:needle ~pos with
+    | None -> List.rev acc
+    | Some i ->
+      let start = i + String.length needle in
+      (match String.index_from html start '"' with
+       | None -> List.rev acc
+       | Some stop ->
+         go (stop + 1) (unescape (String.sub html ~pos:start ~len:(stop - start)) :: acc))
+  in
+  go 0 []
+;;
+
+(* [path] with [.] and [..] segments folded away. *)
+let normalize (path : string) : string =
+  String.split path ~on:'/'
+  |> List.fold ~init:[] ~f:(fun acc seg ->
+    match seg, acc with
+    | ".", _ -> acc
+    | "..", _ :: rest -> rest
+    | "..", [] -> []
+    | seg, acc -> seg :: acc)
+  |> List.rev
+  |> String.concat ~sep:"/"
+;;
+
+let is_external (url : string) : bool =
+  String.is_prefix url ~prefix:"//"
+  ||
+  match String.lfindi url ~f:(fun _ c -> Char.equal c ':' || Char.equal c '/') with
+  | Some i -> Char.equal url.[i] ':'
+  | None -> false
+;;
+
+(* Output path of the page [href] (without its fragment) points at, from the
+   page at output path [page]; [None] for external links and ones leading out
+   of the site. *)
+let target_page ~(page : string) (href : string) : string option =
+  if is_external href
+  then None
+  else if String.is_empty href
+  then Some page
+  else if String.is_prefix href ~prefix:"/"
+  then Url.output_path_of_url href
+  else (
+    (* A page at [a/b/index.html] is served at [/a/b/], which relative links
+       start from. *)
+    let dir = Filename.dirname page in
+    let joined = if String.equal dir "." then href else dir ^ "/" ^ href in
+    Url.output_path_of_url (normalize joined))
+;;
+
+(** Links among [pages] (output path and content) to anchors missing from
+    their page. Only [.html] outputs count as pages; text fragments
+    ([#:~:text=]) and links to anything else are skipped. *)
+let check (pages : (string * string) list) : broken list =
+  let pages =
+    List.filter pages ~f:(fun (out, _) -> String.is_suffix out ~suffix:".html")
+  in
+  let ids : String.Set.t String.Map.t =
+    List.map pages ~f:(fun (out, html) ->
+      out, String.Set.of_list (attr_values "id" html))
+    |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+  in
+  List.concat_map pages ~f:(fun (page, html) ->
+    List.filter_map (attr_values "href" html) ~f:(fun href ->
+      match String.lsplit2 href ~on:'#' with
+      | None -> None
+      | Some (_, "") -> None
+      | Some (_, fragment) when String.is_prefix fragment ~prefix:":~:" -> None
+      | Some (path, fragment) ->
+        let anchor = Vault.Link_ref.percent_decode fragment in
+        let%bind.Option target = target_page ~page path in
+        let%bind.Option page_ids = Map.find ids target in
+        if Set.mem page_ids anchor then None else Some { page; href; target; anchor }))
+;;
+
+let to_string_hum (b : broken) : string =
+  sprintf "%s: link %s: no id %S in %s" b.page b.href b.anchor b.target
+;;
+
+let%expect_test "check" =
+  let pages =
+    [ ( "Guide/index.html"
+      , {|<h2 id="install">Install</h2><p><a href="#usage">u</a> <a href="#install">i</a></p>|}
+      )
+    ; ( "index.html"
+      , {|<a href="/Guide/#install">ok</a> <a href="/Guide/#Install">case</a>
+<a href="Guide/#setup">rel</a> <a href="https://ocaml.org/#top">ext</a>
+<a href="/img/a.png#x">asset</a> <a href="/Guide/#:~:text=Install">text</a>
+<a href="Guide/../Guide/#inst%C3%A4ll">encoded</a>|}
+      )
+    ]
+  in
+  List.iter (check pages) ~f:(fun b -> print_endline (to_string_hum b));
+  [%expect
+    {|
+    Guide/index.html: link #usage: no id "usage" in Guide/index.html
+    index.html: link /Guide/#Install: no id "Install" in Guide/index.html
+    index.html: link Guide/#setup: no id "setup" in Guide/index.html
+    index.html: link Guide/../Guide/#inst%C3%A4ll: no id "inst\195\164ll" in Guide/index.html
+    |}]
+;;
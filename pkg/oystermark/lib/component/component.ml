@@ -7,6 +7,8 @@
 
 open Core
 module Html = Html
+module Url = Url
+module Anchors = Anchors
 
 type html = string
 type doc_component = string * Cmarkit.Doc.t -> html
@@ -96,7 +98,7 @@ let toc_html
     | None -> dir
     | Some href -> spf {|<a href="%s">%s</a>|} href dir
   in
-  let rec render_entries (entries : toc_entry list) : html =
+  let rec render_entries ~(prefix : string) (entries : toc_entry list) : html =
     let items =
       List.map entries ~f:(fun entry ->
         match entry with
@@ -104,13 +106,17 @@ let toc_html
           let href = leaf_href path in
           spf {|<li><a href="%s">%s</a></li>|} href (strip_md_ext name)
         | Dir { name; children } ->
-          let subtree = render_entries children in
+          let dir_path = prefix ^ name in
+          let subtree = render_entries ~prefix:(dir_path ^ "/") children in
           let label = render_dir_label name in
           if collapsible
           then (
             let open_attr = if collapsed_by_default then "" else " open" in
+            (* [data-path] keys the folder's open state, which the page script
+               remembers across pages *)
             spf
-              {|<li style="list-style: none"><details%s><summary>%s</summary>%s</details></li>|}
+              {|<li style="list-style: none"><details data-path="%s"%s><summary>%s</summary>%s</details></li>|}
+              dir_path
               open_attr
               label
               subtree)
@@ -118,7 +124,7 @@ let toc_html
     in
     "<ul>\n" ^ String.concat ~sep:"\n" items ^ "\n</ul>"
   in
-  render_entries (build_toc_entries ?compare_path paths)
+  render_entries ~prefix:"" (build_toc_entries ?compare_path paths)
 ;;
 
 let%expect_test "toc_html" =
@@ -160,9 +166,9 @@ let%expect_test "toc_html" =
     {|
     <ul>
     <li><a href="a.jpg">a.jpg</a></li>
-    <li style="list-style: none"><details open><summary><a href="x/index">x</a></summary><ul>
+    <li style="list-style: none"><details data-path="x" open><summary><a href="x/index">x</a></summary><ul>
     <li><a href="x/q">q</a></li>
-    <li style="list-style: none"><details open><summary><a href="y/index">y</a></summary><ul>
+    <li style="list-style: none"><details data-path="x/y" open><summary><a href="y/index">y</a></summary><ul>
     <li><a href="x/y/t">t</a></li>
     <li><a href="x/y/z">z</a></li>
     </ul></details></li>
@@ -322,6 +328,36 @@ let title (ctx : Vault.t) : html list =
   List.map ctx.docs ~f:(fun (rel_path, _doc) -> title_of_path rel_path)
 ;;
 
+(** Classes a note asks its page to carry, from its [cssclasses] frontmatter
+    (a list, or one string of space or comma separated names; Obsidian's older
+    [cssclass] too), as snippets written for Obsidian target them. Characters
+    that can't be in a class name are dropped. *)
+let css_classes (doc : Cmarkit.Doc.t) : string list =
+  let class_char (c : char) : bool =
+    Char.is_alphanum c || Char.equal c '-' || Char.equal c '_'
+  in
+  Url.strings_field doc "cssclasses" @ Url.strings_field doc "cssclass"
+  |> List.concat_map ~f:(String.split_on_chars ~on:[ ' '; ','; '\t' ])
+  |> List.map ~f:(String.filter ~f:class_char)
+  |> List.filter ~f:(Fn.non String.is_empty)
+  |> List.stable_dedup ~compare:String.compare
+;;
+
+let%expect_test "css_classes" =
+  let show src = print_s [%sexp (css_classes (Parse.of_string src) : string list)] in
+  show "---\ncssclasses:\n  - wide-page\n  - no_title\n---\n# A\n";
+  show "---\ncssclasses: wide-page, cards\ncssclass: cards\n---\n";
+  show "---\ncssclasses: ['x\"><b>']\n---\n";
+  show "# No frontmatter\n";
+  [%expect
+    {|
+    (wide-page no_title)
+    (wide-page cards)
+    (xb)
+    ()
+    |}]
+;;
+
 module Backlink = struct
   (** Path that a resolved target points to. *)
   let path_of_resolved (target : Vault.Resolve.target) : string option =
@@ -411,24 +447,29 @@ module Backlink = struct
   ;;
 
   (** Render a single block to HTML using the oystermark renderer. *)
-  let render_block (block : Cmarkit.Block.t) : string =
+  let render_block ?(urls : Url.t option) (block : Cmarkit.Block.t) : string =
     let doc : Cmarkit.Doc.t = Cmarkit.Doc.make block in
-    Html.of_doc ~backend_blocks:true ~safe:false doc
+    Html.of_doc ~backend_blocks:true ~safe:false ?urls doc
   ;;
 
   (** Extract the minimal leaf blocks from [doc] that contain a link to
       [target_path], rendered as HTML strings. Descends into container blocks
       (lists, block quotes, splicing) to find the innermost paragraph or heading
       that mentions the target. *)
-  let extract_backlink_blocks (target_path : string) (doc : Cmarkit.Doc.t) : string list =
+  let extract_backlink_blocks
+        ?(urls : Url.t option)
+        (target_path : string)
+        (doc : Cmarkit.Doc.t)
+    : string list
+    =
     let rec collect (b : Cmarkit.Block.t) : string list =
       match b with
       | Cmarkit.Block.Paragraph (p, _meta) ->
         let inline : Cmarkit.Inline.t = Cmarkit.Block.Paragraph.inline p in
-        if inline_links_to target_path inline then [ render_block b ] else []
+        if inline_links_to target_path inline then [ render_block ?urls b ] else []
       | Cmarkit.Block.Heading (h, _meta) ->
         let inline : Cmarkit.Inline.t = Cmarkit.Block.Heading.inline h in
-        if inline_links_to target_path inline then [ render_block b ] else []
+        if inline_links_to target_path inline then [ render_block ?urls b ] else []
       | Cmarkit.Block.List (l, _meta) ->
         let items : Cmarkit.Block.List_item.t Cmarkit.node list =
           Cmarkit.Block.List'.items l
@@ -446,11 +487,11 @@ module Backlink = struct
   (** Render backlinks for [rel_path]: links grouped by source file under
       [<details>] elements. Each item shows the minimum containing block
       rendered as HTML. *)
-  let backlinks (rel_path : string) : vault_component =
+  let backlinks ?(urls : Url.t = Url.pretty) (rel_path : string) : vault_component =
     fun (vault : Vault.t) ->
     let sources : (string * string list) list =
       List.filter_map vault.docs ~f:(fun (src_path, doc) ->
-        let blocks : string list = extract_backlink_blocks rel_path doc in
+        let blocks : string list = extract_backlink_blocks ~urls rel_path doc in
         match blocks with
         | [] -> None
         | _ -> Some (src_path, blocks))
@@ -461,7 +502,7 @@ module Backlink = struct
     | groups ->
       let items : string list =
         List.map groups ~f:(fun (src_path, blocks) ->
-          let src_href : string = Html.note_url_path src_path in
+          let src_href : string = Url.note_url_path urls src_path in
           let src_name : string = strip_md_ext (Filename.basename src_path) in
           let block_items : string =
             List.map blocks ~f:(fun block_html ->
@@ -598,15 +639,22 @@ end
     Always includes a Home link. Adds intermediate directory links as ancestors.
     Does not include the current page itself.
     Example: url_path="/foo/bar/" → Home / foo *)
-let nav_of_url_path ?(home_path = Config.Home.default.path) (url_path : string) : html =
+let nav_of_url_path
+      ?(home_path = Config.Home.default.path)
+      ?(urls : Url.t = Url.pretty)
+      (url_path : string)
+  : html
+  =
   let sep : string = {|<span class="sep">/</span>|} in
-  let home_href = Html.note_url_path home_path in
+  let home_href = Url.note_url_path urls home_path in
   let home : string = {%string|<a href="%{home_href}">Home</a>|} in
   match url_path with
   | "/" -> ""
   | _ ->
     let trimmed : string =
-      url_path |> String.chop_prefix_exn ~prefix:"/" |> String.chop_suffix_exn ~suffix:"/"
+      url_path
+      |> String.chop_prefix_exn ~prefix:"/"
+      |> String.chop_suffix_if_exists ~suffix:"/"
     in
     let parts : string list =
       String.split trimmed ~on:'/' |> List.filter ~f:(fun s -> not (String.is_empty s))
@@ -616,11 +664,172 @@ let nav_of_url_path ?(home_path = Config.Home.default.path) (url_path : string)
     let crumbs : string list =
       List.mapi ancestors ~f:(fun i name ->
         let href : string =
-          "/" ^ String.concat ~sep:"/" (List.take parts (i + 1)) ^ "/"
+          Url.folder_url_path urls (String.concat ~sep:"/" (List.take parts (i + 1)))
         in
-        spf {|<a href="%s">%s</a>|} href name)
+        spf {|<a href="%s">%s</a>|} href (Url.segment_text urls name))
+    in
+    spf
+      {|<nav class="breadcrumb" aria-label="Breadcrumb">%s</nav>|}
+      (String.concat ~sep (home :: crumbs))
+;;
+
+(* Href of menu [target]: a URL as written, or the page of a vault path in
+   [paths] ([.md] optional, leading [/] ignored). *)
+let menu_href ~(urls : Url.t) ~(paths : string list) (target : string) : string option =
+  let target = String.strip target in
+  if Parse.Link_kind.is_external target
+  then Some target
+  else (
+    let path = String.chop_prefix_if_exists target ~prefix:"/" in
+    List.find [ path; path ^ ".md" ] ~f:(fun p ->
+      (not (String.is_empty path)) && List.mem paths p ~equal:String.equal)
+    |> Option.map ~f:(Url.file_url_path urls))
+;;
+
+(** Header menu from {!Config.Menu}; targets are resolved against the vault's
+    [paths]. Entries whose target names nothing there are shown as plain
+    labels. Empty for an empty menu. *)
+let menu_html ?(urls : Url.t = Url.pretty) ~(paths : string list) (menu : Config.Menu.t)
+  : html
+  =
+  let escape (s : string) : string =
+    String.concat_map s ~f:(function
+      | '&' -> "&amp;"
+      | '<' -> "&lt;"
+      | '>' -> "&gt;"
+      | '"' -> "&quot;"
+      | c -> String.of_char c)
+  in
+  let entry ~(label : string) ~(target : string) : string =
+    match menu_href ~urls ~paths target with
+    | Some href -> spf {|<a href="%s">%s</a>|} (escape href) (escape label)
+    | None -> spf {|<span>%s</span>|} (escape label)
+  in
+  let item ({ label; target; children } : Config.Menu.Item.t) : string =
+    match children with
+    | [] -> spf {|<li>%s</li>|} (entry ~label ~target)
+    | children ->
+      let links =
+        List.map children ~f:(fun ({ label; target } : Config.Menu.Link.t) ->
+          spf {|<li>%s</li>|} (entry ~label ~target))
+      in
+      spf
+        {|<li class="menu-group">%s<ul>%s</ul></li>|}
+        (entry ~label ~target)
+        (String.concat links)
+  in
+  match menu with
+  | [] -> ""
+  | items ->
+    spf
+      {|<nav class="menu" aria-label="Menu"><ul>%s</ul></nav>|}
+      (String.concat (List.map items ~f:item))
+;;
+
+let%expect_test "menu_html" =
+  let menu =
+    Config.Menu.of_flags
+      [ "Guides=guides/index"
+      ; "Guides>Install=/guides/Install.md"
+      ; "Guides>Missing=guides/nope"
+      ; "Q&A=https://example.com/?a=1&b=2"
+      ]
+  in
+  print_endline (menu_html ~paths:[ "guides/index.md"; "guides/Install.md" ] menu);
+  [%expect
+    {| <nav class="menu" aria-label="Menu"><ul><li class="menu-group"><a href="/guides/">Guides</a><ul><li><a href="/guides/Install/">Install</a></li><li><span>Missing</span></li></ul></li><li><a href="https://example.com/?a=1&amp;b=2">Q&amp;A</a></li></ul></nav> |}]
+;;
+
+(** Calendar of [month] of [year] as a [<table class="calendar">], a row per
+    week from Monday. Days with a daily note among [entries] link to it, and
+    so do the week numbers of weeks with a weekly note. [caption] heads the
+    table, linking to [caption_href] when given. *)
+let calendar_html
+      ?(urls : Url.t = Url.pretty)
+      ?(caption : string option)
+      ?(caption_href : string option)
+      ~(year : int)
+      ~(month : Month.t)
+      (entries : Vault.Journal.entry list)
+  : html
+  =
+  let href_of (period : Vault.Journal.period) (date : Date.t) : string option =
+    List.find entries ~f:(fun (e : Vault.Journal.entry) ->
+      Vault.Journal.equal_period e.period period && Date.equal e.date date)
+    |> Option.map ~f:(fun (e : Vault.Journal.entry) -> Url.note_url_path urls e.path)
+  in
+  let link (href : string option) (text : string) : string =
+    match href with
+    | Some href -> spf {|<a href="%s">%s</a>|} href text
+    | None -> text
+  in
+  let first : Date.t = Date.create_exn ~y:year ~m:month ~d:1 in
+  let last : Date.t = Date.add_days (Date.add_months first 1) (-1) in
+  let rec weeks (monday : Date.t) : Date.t list =
+    if Date.( > ) monday last then [] else monday :: weeks (Date.add_days monday 7)
+  in
+  let row (monday : Date.t) : string =
+    let week : string =
+      spf
+        {|<th scope="row">%s</th>|}
+        (link (href_of Vault.Journal.Week monday) (spf "W%02d" (Date.week_number monday)))
+    in
+    let days : string list =
+      List.init 7 ~f:(fun i ->
+        let day = Date.add_days monday i in
+        if Month.equal (Date.month day) month
+        then spf "<td>%s</td>" (link (href_of Vault.Journal.Day day) (Int.to_string (Date.day day)))
+        else "<td></td>")
     in
-    spf {|<nav class="breadcrumb">%s</nav>|} (String.concat ~sep (home :: crumbs))
+    spf "<tr>%s%s</tr>" week (String.concat days)
+  in
+  let caption : string =
+    match caption with
+    | Some text -> spf "<caption>%s</caption>\n" (link caption_href text)
+    | None -> ""
+  in
+  let header : string =
+    [ "Wk"; "Mon"; "Tue"; "Wed"; "Thu"; "Fri"; "Sat"; "Sun" ]
+    |> List.map ~f:(spf "<th>%s</th>")
+    |> String.concat
+  in
+  let start : Date.t =
+    Date.add_days
+      first
+      (1 - Day_of_week.iso_8601_weekday_number (Date.day_of_week first))
+  in
+  spf
+    "<table class=\"calendar\">\n%s<thead><tr>%s</tr></thead>\n<tbody>\n%s\n</tbody>\n\
+     </table>"
+    caption
+    header
+    (String.concat ~sep:"\n" (List.map (weeks start) ~f:row))
+;;
+
+let%expect_test "calendar_html" =
+  Vault.Journal.of_paths
+    ~config:Config.Journal.default
+    [ "2024-W18.md"; "2024-05-01.md"; "daily/2024-05-31.md" ]
+  |> calendar_html
+       ~caption:"May"
+       ~caption_href:"/journal/2024-05/"
+       ~year:2024
+       ~month:Month.May
+  |> print_endline;
+  [%expect
+    {|
+    <table class="calendar">
+    <caption><a href="/journal/2024-05/">May</a></caption>
+    <thead><tr><th>Wk</th><th>Mon</th><th>Tue</th><th>Wed</th><th>Thu</th><th>Fri</th><th>Sat</th><th>Sun</th></tr></thead>
+    <tbody>
+    <tr><th scope="row"><a href="/2024-W18/">W18</a></th><td></td><td></td><td><a href="/2024-05-01/">1</a></td><td>2</td><td>3</td><td>4</td><td>5</td></tr>
+    <tr><th scope="row">W19</th><td>6</td><td>7</td><td>8</td><td>9</td><td>10</td><td>11</td><td>12</td></tr>
+    <tr><th scope="row">W20</th><td>13</td><td>14</td><td>15</td><td>16</td><td>17</td><td>18</td><td>19</td></tr>
+    <tr><th scope="row">W21</th><td>20</td><td>21</td><td>22</td><td>23</td><td>24</td><td>25</td><td>26</td></tr>
+    <tr><th scope="row">W22</th><td>27</td><td>28</td><td>29</td><td>30</td><td><a href="/daily/2024-05-31/">31</a></td><td></td><td></td></tr>
+    </tbody>
+    </table>
+    |}]
 ;;
 
 let backlinks = Backlink.backlinks
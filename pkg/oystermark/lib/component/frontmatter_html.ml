@@ -0,0 +1,180 @@
+(** The frontmatter block of an HTML page, laid out by
+    {!Config.Frontmatter_display}: which keys show, in what order, under which
+    labels, and how each value is drawn. *)
+
+open Core
+
+(** Frontmatter key that, set to [true], hides a note's frontmatter block. It
+    is never shown itself. *)
+let hide_key = "hide_frontmatter"
+
+let month_names =
+  [| "January"
+   ; "February"
+   ; "March"
+   ; "April"
+   ; "May"
+   ; "June"
+   ; "July"
+   ; "August"
+   ; "September"
+   ; "October"
+   ; "November"
+   ; "December"
+  |]
+;;
+
+(** [date] in [fmt]: [%Y] year, [%m] and [%d] zero-padded month and day, [%e]
+    day, [%B] and [%b] full and short month name, [%%] a percent sign. Other
+    characters are copied. *)
+let format_date ~(fmt : string) (date : Date.t) : string =
+  let month = Month.to_int (Date.month date) in
+  let buf = Buffer.create 16 in
+  let rec go i =
+    if i < String.length fmt
+    then
+      if Char.equal fmt.[i] '%' && i + 1 < String.length fmt
+      then (
+        (match fmt.[i + 1] with
+         | 'Y' -> Buffer.add_string buf (Int.to_string (Date.year date))
+         | 'm' -> Buffer.add_string buf (sprintf "%02d" month)
+         | 'd' -> Buffer.add_string buf (sprintf "%02d" (Date.day date))
+         | 'e' -> Buffer.add_string buf (Int.to_string (Date.day date))
+         | 'B' -> Buffer.add_string buf month_names.(month - 1)
+         | 'b' -> Buffer.add_string buf (String.prefix month_names.(month - 1) 3)
+         | '%' -> Buffer.add_char buf '%'
+         | c ->
+           Buffer.add_char buf '%';
+           Buffer.add_char buf c);
+        go (i + 2))
+      else (
+        Buffer.add_char buf fmt.[i];
+        go (i + 1))
+  in
+  go 0;
+  Buffer.contents buf
+;;
+
+let escape = Parse.Frontmatter.escape_html
+
+(* [v] under [render], or [None] when the value doesn't suit it. *)
+let render_value ~(date_format : string) (render : Config.Frontmatter_render.t) v
+  : string option
+  =
+  let items : Parse.Value.t list =
+    match Parse.Value.of_yaml v with
+    | Parse.Value.List vs -> vs
+    | v -> [ v ]
+  in
+  let all (f : Parse.Value.t -> string option) : string list option =
+    Option.all (List.map items ~f)
+  in
+  match render with
+  | Config.Frontmatter_render_def.Text -> None
+  | Config.Frontmatter_render_def.Chips ->
+    all (function
+      | Parse.Value.String s -> Some (escape s)
+      | ( Parse.Value.Int _ | Parse.Value.Float _ | Parse.Value.Bool _
+        | Parse.Value.Date _ ) as v -> Some (escape (Parse.Value.to_markdown v))
+      | _ -> None)
+    |> Option.map ~f:(fun chips ->
+      String.concat_map chips ~f:(sprintf {|<span class="chip">%s</span>|}))
+  | Config.Frontmatter_render_def.Date ->
+    all (function
+      | Parse.Value.Date d ->
+        Some (escape (format_date ~fmt:date_format d.Parse.Timestamp.date))
+      | _ -> None)
+    |> Option.map ~f:(String.concat ~sep:", ")
+  | Config.Frontmatter_render_def.Link ->
+    all (function
+      | Parse.Value.String s when Parse.Link_kind.is_external s ->
+        Some (sprintf {|<a href="%s">%s</a>|} (escape s) (escape s))
+      | _ -> None)
+    |> Option.map ~f:(String.concat ~sep:", ")
+;;
+
+(** The frontmatter block for [fm], empty when the note hides it or nothing
+    is left to show. *)
+let to_html
+      ?(display : Config.Frontmatter_display.t = Config.Frontmatter_display.default)
+      (fm : Yaml.value)
+  : string
+  =
+  match fm with
+  | `Null -> ""
+  | `O pairs ->
+    let hidden =
+      match List.Assoc.find pairs ~equal:String.equal hide_key with
+      | Some (`Bool true) -> true
+      | _ -> false
+    in
+    let pairs = List.filter pairs ~f:(fun (k, _) -> not (String.equal k hide_key)) in
+    let rows : (string * string) list =
+      match display.fields with
+      | [] -> List.map pairs ~f:(fun (k, v) -> k, Parse.Frontmatter.value_to_html v)
+      | fields ->
+        List.filter_map fields ~f:(fun (field : Config.Frontmatter_display.Field.t) ->
+          List.Assoc.find pairs ~equal:String.equal field.key
+          |> Option.map ~f:(fun v ->
+            let label = if String.is_empty field.label then field.key else field.label in
+            let value =
+              match render_value ~date_format:display.date_format field.render v with
+              | Some html -> html
+              | None -> Parse.Frontmatter.value_to_html v
+            in
+            label, value))
+    in
+    if hidden || (List.is_empty rows && not (List.is_empty display.fields))
+    then ""
+    else (
+      let rows =
+        List.map rows ~f:(fun (label, value) ->
+          sprintf "<tr><th>%s</th><td>%s</td></tr>" (escape label) value)
+      in
+      sprintf "<div class=\"frontmatter\"><table>%s</table></div>\n" (String.concat rows))
+  | v ->
+    sprintf "<div class=\"frontmatter\">%s</div>\n" (Parse.Frontmatter.to_html (Some v))
+;;
+
+let%expect_test "to_html" =
+  let fm =
+    Parse.Frontmatter.of_doc
+      (Parse.of_string
+         "---\n\
+          title: Oyster\n\
+          tags: [ocaml, notes]\n\
+          date: 2024-05-01\n\
+          source: https://example.com/a?b=1&c=2\n\
+          draft: false\n\
+          ---\n")
+    |> Option.value_exn
+  in
+  print_string (to_html fm);
+  [%expect
+    {| <div class="frontmatter"><table><tr><th>title</th><td>Oyster</td></tr><tr><th>tags</th><td><ul><li>ocaml</li><li>notes</li></ul></td></tr><tr><th>date</th><td>2024-05-01</td></tr><tr><th>source</th><td>https://example.com/a?b=1&amp;c=2</td></tr><tr><th>draft</th><td>false</td></tr></table></div> |}];
+  let field ?(label = "") key render : Config.Frontmatter_display.Field.t =
+    { key; label; render }
+  in
+  let display : Config.Frontmatter_display.t =
+    { fields =
+        Config.Frontmatter_render_def.
+          [ field "date" ~label:"Published" Date
+          ; field "tags" Chips
+          ; field "source" Link
+          ; field "title" Date
+          ; field "missing" Text
+          ]
+    ; date_format = "%e %B %Y"
+    }
+  in
+  print_string (to_html ~display fm);
+  [%expect
+    {| <div class="frontmatter"><table><tr><th>Published</th><td>1 May 2024</td></tr><tr><th>tags</th><td><span class="chip">ocaml</span><span class="chip">notes</span></td></tr><tr><th>source</th><td><a href="https://example.com/a?b=1&amp;c=2">https://example.com/a?b=1&amp;c=2</a></td></tr><tr><th>title</th><td>Oyster</td></tr></table></div> |}];
+  let hidden =
+    Parse.Frontmatter.of_doc
+      (Parse.of_string "---\ntitle: Oyster\nhide_frontmatter: true\n---\n")
+    |> Option.value_exn
+  in
+  print_string (to_html hidden);
+  [%expect {| |}]
+;;
@@ -8,39 +8,37 @@ open Cmarkit
 module C = Cmarkit_renderer.Context
 module Resolve = Vault.Resolve
 module Embed = Vault.Embed
+module Link_preview = Vault.Link_preview
+module Glossary = Vault.Glossary
 module Cb_attribute = Parse.Cb_attribute
 module Heading_slug = Parse.Heading_slug
+module Html_fragment = Parse.Html_fragment
+module Definition_list = Parse.Definition_list
+module Gfm_alert = Parse.Gfm_alert
 module H = Tyxml.Html
 
 let elt_to_string (e : 'a H.elt) : string = Format.asprintf "%a" (H.pp_elt ()) e
 
-(** URL path for a note: "foo/bar.md" → "/foo/bar/", "foo/index.md" → "/foo/". *)
-let note_url_path (rel_path : string) : string =
-  let base = String.chop_suffix_exn rel_path ~suffix:".md" in
-  if String.is_suffix base ~suffix:"/index"
-  then "/" ^ String.chop_suffix_exn base ~suffix:"/index" ^ "/"
-  else "/" ^ base ^ "/"
-;;
+(** URL path for a note: "foo/bar.md" → "/foo/bar/", "foo/index.md" → "/foo/".
+    Other URL styles: {!Url}. *)
+let note_url_path (rel_path : string) : string = Url.note_url_path Url.pretty rel_path
 
 (** Output file path for a note: derived from its URL path.
     "foo/bar.md" → "foo/bar/index.html", "foo/index.md" → "foo/index.html". *)
 let note_output_path (rel_path : string) : string =
-  let base = String.chop_suffix_exn rel_path ~suffix:".md" in
-  if String.is_suffix base ~suffix:"/index" then base ^ ".html" else base ^ "/index.html"
+  Url.note_output_path Url.pretty rel_path
 ;;
 
 (** URL path for any file: notes get pretty URLs, others get literal paths. *)
-let file_url_path (path : string) : string =
-  if String.is_suffix path ~suffix:".md" then note_url_path path else "/" ^ path
-;;
+let file_url_path (path : string) : string = Url.file_url_path Url.pretty path
 
 (* Convert a resolved target to an href string. *)
-let target_to_href : Resolve.target -> string = function
-  | Resolve.Note { path } -> note_url_path path
-  | Resolve.File { path } -> "/" ^ path
-  | Resolve.Heading { path; slug; _ } -> file_url_path path ^ "#" ^ slug
-  | Resolve.Block { path; block_id } -> file_url_path path ^ "#^" ^ block_id
-  | Resolve.Attr { path; id; _ } -> file_url_path path ^ "#" ^ id
+let target_to_href ~(urls : Url.t) : Resolve.target -> string = function
+  | Resolve.Note { path } -> Url.note_url_path urls path
+  | Resolve.File { path } -> Url.file_url_path urls path
+  | Resolve.Heading { path; slug; _ } -> Url.file_url_path urls path ^ "#" ^ slug
+  | Resolve.Block { path; block_id } -> Url.file_url_path urls path ^ "#^" ^ block_id
+  | Resolve.Attr { path; id; _ } -> Url.file_url_path urls path ^ "#" ^ id
   | Resolve.Curr_file -> ""
   | Resolve.Curr_heading { slug; _ } -> "#" ^ slug
   | Resolve.Curr_block { block_id } -> "#^" ^ block_id
@@ -54,6 +52,19 @@ let is_unresolved (meta : Meta.t) : bool =
   | _ -> false
 ;;
 
+(* URL of the hover preview of the note a link resolves to, when [previews]. *)
+let preview_url ~(urls : Url.t) ~(previews : bool) (meta : Meta.t) : string option =
+  match Meta.find Resolve.resolved_key meta with
+  | Some
+      ( Resolve.Note { path }
+      | Resolve.Heading { path; _ }
+      | Resolve.Block { path; _ }
+      | Resolve.Attr { path; _ } )
+    when previews && String.is_suffix path ~suffix:".md" ->
+    Some (Url.preview_url_path urls path)
+  | _ -> None
+;;
+
 (* Attribute-to-HTML helpers
 ---------------------------- *)
 
@@ -151,6 +162,9 @@ let parse_image_dims (s : string) : (int * int option) option =
 
 (* Render a wikilink as HTML. Handles embed=true for media content. *)
 let render_wikilink
+      ~(urls : Url.t)
+      ~(lightbox : bool)
+      ~(previews : bool)
       (c : Cmarkit_renderer.context)
       (w : Cmarkit.Inline.Wikilink.t)
       (meta : Meta.t)
@@ -158,7 +172,7 @@ let render_wikilink
   =
   let href_of_meta (meta : Meta.t) =
     match Meta.find Resolve.resolved_key meta with
-    | Some target -> target_to_href target
+    | Some target -> target_to_href ~urls target
     | None -> "#"
   in
   let href = href_of_meta meta in
@@ -178,7 +192,9 @@ let render_wikilink
           | None -> [], display
         in
         let img = H.img ~src:href ~alt ~a:dim_attrs () in
-        elt_to_string (H.a ~a:[ H.a_href href ] [ img ])
+        if lightbox
+        then elt_to_string (H.a ~a:[ H.a_href href ] [ img ])
+        else elt_to_string img
       | `Video ->
         elt_to_string
           (H.video
@@ -201,13 +217,19 @@ let render_wikilink
     C.string c s)
   else (
     let attrs =
-      H.a_href href :: (if is_unresolved meta then [ H.a_class [ "unresolved" ] ] else [])
+      List.filter_opt
+        [ Some (H.a_href href)
+        ; Option.some_if (is_unresolved meta) (H.a_class [ "unresolved" ])
+        ; Option.map (preview_url ~urls ~previews meta) ~f:(H.a_user_data "preview")
+        ]
     in
     C.string c (elt_to_string (H.a ~a:attrs [ H.txt display ])))
 ;;
 
 (* Render a standard link, overriding href if a resolved target is present. *)
 let render_link
+      ~(urls : Url.t)
+      ~(previews : bool)
       ?(attr : Attribute.t option)
       (c : Cmarkit_renderer.context)
       (l : Inline.Link.t)
@@ -216,7 +238,7 @@ let render_link
   =
   match Meta.find Resolve.resolved_key meta with
   | Some target ->
-    let href = target_to_href target in
+    let href = target_to_href ~urls target in
     let buf = Buffer.create 128 in
     let sub_ctx = C.make (C.renderer c) buf in
     C.init sub_ctx (C.get_doc c);
@@ -230,6 +252,11 @@ let render_link
       let classes = Option.value_map attr ~default:[] ~f:Attribute.classes in
       let kvs = Option.value_map attr ~default:[] ~f:Attribute.key_values in
       let classes = if is_unresolved meta then classes @ [ "unresolved" ] else classes in
+      let kvs =
+        kvs
+        @ Option.value_map (preview_url ~urls ~previews meta) ~default:[] ~f:(fun url ->
+          [ "data-preview", url ])
+      in
       emit_html_attrs ~id ~classes ~kvs ()
     in
     C.string c (sprintf "<a href=\"%s\"%s>%s</a>" href_esc attrs_str inner_html);
@@ -237,8 +264,11 @@ let render_link
   | None -> false
 ;;
 
-(* Render an image with resolved target. *)
+(* Render an image with resolved target; linked to itself for the lightbox
+   when [lightbox]. *)
 let render_image
+      ~(urls : Url.t)
+      ~(lightbox : bool)
       ?(attr : Attribute.t option)
       (c : Cmarkit_renderer.context)
       (l : Inline.Link.t)
@@ -247,7 +277,7 @@ let render_image
   =
   match Meta.find Resolve.resolved_key meta with
   | Some target ->
-    let href = target_to_href target in
+    let href = target_to_href ~urls target in
     let buf = Buffer.create 64 in
     let rec extract_text = function
       | Inline.Text (s, _) -> Buffer.add_string buf s
@@ -271,23 +301,40 @@ let render_image
     buffer_add_attr_value alt_buf alt;
     let alt_esc = Buffer.contents alt_buf in
     let extra_attrs = Option.value_map attr ~default:"" ~f:cmarkit_attr_html in
-    C.string
-      c
-      (sprintf
-         "<a href=\"%s\"><img src=\"%s\" alt=\"%s\"%s%s/></a>"
-         href_esc
-         href_esc
-         alt_esc
-         dim_str
-         extra_attrs);
+    let img =
+      sprintf "<img src=\"%s\" alt=\"%s\"%s%s/>" href_esc alt_esc dim_str extra_attrs
+    in
+    C.string c (if lightbox then sprintf "<a href=\"%s\">%s</a>" href_esc img else img);
     true
   | None -> false
 ;;
 
+(* A glossary term (see {!Vault.Glossary}): an [<abbr>] holding its
+   definition, inside a link to the term's heading when [mark.link]. *)
+let render_glossary_term ~(urls : Url.t) c (s : string) (mark : Glossary.mark) : unit =
+  let esc = Parse.Frontmatter.escape_html in
+  let abbr =
+    sprintf
+      {|<abbr class="glossary-term" title="%s">%s</abbr>|}
+      (esc mark.entry.definition)
+      (esc s)
+  in
+  if mark.link
+  then (
+    let href = Url.file_url_path urls mark.entry.path ^ "#" ^ mark.entry.slug in
+    C.string c (sprintf {|<a href="%s">%s</a>|} (esc href) abbr))
+  else C.string c abbr
+;;
+
 (** Render an inline, optionally carrying a Djot [attr] from an enclosing
     {!Cmarkit.Inline.Ext_attributes} wrapper. Returns [false] (defer to the
     default renderer) for inlines that need no oystermark-specific handling. *)
-let render_inline ?(attr : Attribute.t option) (c : Cmarkit_renderer.context)
+let render_inline
+      ~(urls : Url.t)
+      ~(lightbox : bool)
+      ~(previews : bool)
+      ?(attr : Attribute.t option)
+      (c : Cmarkit_renderer.context)
   : Inline.t -> bool
   =
   let with_attr ~tag render =
@@ -301,12 +348,16 @@ let render_inline ?(attr : Attribute.t option) (c : Cmarkit_renderer.context)
   in
   function
   | Cmarkit.Inline.Ext_wikilink (w, meta) ->
-    render_wikilink c w meta;
+    render_wikilink ~urls ~lightbox ~previews c w meta;
     true
-  | Inline.Link (l, meta) -> render_link ?attr c l meta
-  | Inline.Image (l, meta) -> render_image ?attr c l meta
-  | Inline.Text (s, _) ->
-    with_attr ~tag:"span" (fun () -> Cmarkit_html.html_escaped_string c s)
+  | Inline.Link (l, meta) -> render_link ~urls ~previews ?attr c l meta
+  | Inline.Image (l, meta) -> render_image ~urls ~lightbox ?attr c l meta
+  | Inline.Text (s, meta) ->
+    (match Meta.find Glossary.meta_key meta with
+     | Some mark ->
+       render_glossary_term ~urls c s mark;
+       true
+     | None -> with_attr ~tag:"span" (fun () -> Cmarkit_html.html_escaped_string c s))
   | Inline.Emphasis (e, _) ->
     with_attr ~tag:"em" (fun () -> C.inline c (Inline.Emphasis.inline e))
   | Inline.Strong_emphasis (e, _) ->
@@ -333,10 +384,22 @@ let render_inline ?(attr : Attribute.t option) (c : Cmarkit_renderer.context)
   | _ -> false
 ;;
 
-let inline (c : Cmarkit_renderer.context) : Inline.t -> bool = function
+let inline
+      ~(urls : Url.t)
+      ~(lightbox : bool)
+      ~(previews : bool)
+      (c : Cmarkit_renderer.context)
+  : Inline.t -> bool
+  = function
   | Inline.Ext_attributes (a, _) ->
-    render_inline ~attr:(Inline.Attributes.attributes a) c (Inline.Attributes.inline a)
-  | i -> render_inline c i
+    render_inline
+      ~urls
+      ~lightbox
+      ~previews
+      ~attr:(Inline.Attributes.attributes a)
+      c
+      (Inline.Attributes.inline a)
+  | i -> render_inline ~urls ~lightbox ~previews c i
 ;;
 
 let render_callout
@@ -347,7 +410,8 @@ let render_callout
   =
   let inner = Block.Block_quote.block bq in
   let body = Block.Callout.strip_header inner in
-  let kind = Block.Callout.kind callout in
+  (* GitHub alerts are written [[!NOTE]]; the theme styles lower-case kinds. *)
+  let kind = Gfm_alert.callout_kind (Block.Callout.kind callout) in
   let render_title () =
     match Block.Callout.title callout inner with
     | Some title -> C.inline c title
@@ -550,11 +614,74 @@ let render_keyed_list ~(style : struct_style) c (l : Block.List'.t) =
   C.string c closing
 ;;
 
+let render_definition_list
+      ?(attr : Attribute.t option)
+      (c : Cmarkit_renderer.context)
+      (items : Definition_list.item list)
+  : unit
+  =
+  let attr_html = Option.value_map attr ~default:"" ~f:cmarkit_attr_html in
+  C.string c (sprintf "<dl%s>\n" attr_html);
+  List.iter items ~f:(fun { Definition_list.term; definitions } ->
+    C.string c "<dt>";
+    C.inline c term;
+    C.string c "</dt>\n";
+    List.iter definitions ~f:(fun d ->
+      C.string c "<dd>";
+      C.inline c d;
+      C.string c "</dd>\n"));
+  C.string c "</dl>\n"
+;;
+
+(* A paragraph that is just an image, as [(image, caption)] under [caption]:
+   the image's alt text, or the italic line right under it. *)
+let figure_of_paragraph ~(caption : Config.Image_caption.t) (inline : Inline.t)
+  : (Inline.t * [ `Inline of Inline.t | `Text of string ]) option
+  =
+  let is_image : Inline.t -> bool = function
+    | Inline.Image _ -> true
+    | Inline.Ext_wikilink (w, meta) ->
+      Inline.Wikilink.embed w
+      && (match Meta.find Resolve.resolved_key meta with
+        | Some (Resolve.File { path }) ->
+          (match media_type_of_href path with
+           | `Image -> true
+           | _ -> false)
+        | _ -> false)
+    | _ -> false
+  in
+  let alt : Inline.t -> string = function
+    | Inline.Image (l, _) -> Heading_slug.inline_to_plain_text (Inline.Link.text l)
+    | Inline.Ext_wikilink (w, _) -> Option.value (Inline.Wikilink.display w) ~default:""
+    | _ -> ""
+  in
+  let parts =
+    match inline with
+    | Inline.Inlines (is, _) ->
+      List.filter is ~f:(function
+        | Inline.Text (s, _) -> not (String.is_empty (String.strip s))
+        | _ -> true)
+    | i -> [ i ]
+  in
+  match caption, parts with
+  | Config.Image_caption_def.Alt, [ image ] when is_image image ->
+    let alt = String.strip (alt image) in
+    (* Dimension specs ([![[a.png|300]]]) are no caption *)
+    if String.is_empty alt || Option.is_some (parse_image_dims alt)
+    then None
+    else Some (image, `Text alt)
+  | Config.Image_caption_def.Italic, [ image; Inline.Break _; Inline.Emphasis (e, _) ]
+    when is_image image -> Some (image, `Inline (Inline.Emphasis.inline e))
+  | _ -> None
+;;
+
 (** Render a block, optionally carrying a Djot [attr] from an enclosing
     {!Cmarkit.Block.Ext_attributes} wrapper. Returns [false] (defer) for
     blocks that need no oystermark-specific handling and no attribute. *)
 let render_block
       ~(struct_style : struct_style ref)
+      ~(share : Config.Share.t)
+      ~(images : Config.Images.t)
       ?(attr : Attribute.t option)
       (c : Cmarkit_renderer.context)
   : Block.t -> bool
@@ -573,9 +700,10 @@ let render_block
   function
   | Block.Heading (h, meta) ->
     let slug = Meta.find Heading_slug.meta_key meta in
-    (match slug, attr with
-     | None, None -> false
-     | _, _ ->
+    let section_id = Meta.find Parse.Section_id.meta_key meta in
+    (match slug, attr, section_id with
+     | None, None, None -> false
+     | _, _, _ ->
        let level = Block.Heading.level h in
        (* The attribute id wins over the auto slug if both present (djot says
           last id wins; the user-written attribute is more specific). *)
@@ -585,8 +713,21 @@ let render_block
          | None, Some s -> sprintf " id=\"%s\"" s
          | None, None -> ""
        in
-       C.string c (sprintf "<h%d%s%s>" level id_attr attr_non_id_html);
+       let section_id_attr =
+         Option.value_map section_id ~default:"" ~f:(sprintf " data-section-id=\"%s\"")
+       in
+       C.string
+         c
+         (sprintf "<h%d%s%s%s>" level id_attr attr_non_id_html section_id_attr);
        C.inline c (Block.Heading.inline h);
+       (match Option.first_some attr_id slug with
+        | Some id when share.headings ->
+          C.string
+            c
+            (sprintf
+               {|<a class="heading-link" href="#%s" aria-label="Link to this section">#</a>|}
+               id)
+        | _ -> ());
        C.string c (sprintf "</h%d>\n" level);
        true)
   | Block.Block_quote (bq, meta) ->
@@ -602,22 +743,62 @@ let render_block
           C.block c (Block.Block_quote.block bq);
           C.string c "</blockquote>\n";
           true))
+  | Block.Paragraph (p, meta) when Definition_list.is_definition_list meta ->
+    (match Definition_list.items (Block.Paragraph.inline p) with
+     | None -> false
+     | Some items ->
+       render_definition_list ?attr c items;
+       true)
   | Block.Paragraph (p, meta) ->
     let block_id = Block.Block_id.find meta in
-    (match block_id, attr with
-     | None, None -> false
-     | _, _ ->
-       let id_attr =
-         match attr_id, block_id with
-         | Some id, _ -> sprintf " id=\"%s\"" id
-         | None, Some (b : Block.Block_id.t) ->
-           sprintf " id=\"^%s\"" (Block.Block_id.id b)
-         | None, None -> ""
+    let id_attr =
+      match attr_id, block_id with
+      | Some id, _ -> sprintf " id=\"%s\"" id
+      | None, Some (b : Block.Block_id.t) -> sprintf " id=\"^%s\"" (Block.Block_id.id b)
+      | None, None -> ""
+    in
+    (match figure_of_paragraph ~caption:images.caption (Block.Paragraph.inline p) with
+     | Some (image, caption) ->
+       C.string c (sprintf "<figure%s%s>" id_attr attr_non_id_html);
+       C.inline c image;
+       C.string c "<figcaption>";
+       (match caption with
+        | `Inline i -> C.inline c i
+        | `Text s -> Cmarkit_html.html_escaped_string c s);
+       C.string c "</figcaption></figure>\n";
+       true
+     | None ->
+       let fragment =
+         if share.paragraphs
+         then
+           Url.text_fragment
+             (Parse.Heading_slug.inline_to_plain_text (Block.Paragraph.inline p))
+         else ""
        in
-       C.string c (sprintf "<p%s%s>" id_attr attr_non_id_html);
-       C.inline c (Block.Paragraph.inline p);
-       C.string c "</p>\n";
-       true)
+       (match block_id, attr with
+        | None, None when String.is_empty fragment -> false
+        | _, _ ->
+          C.string c (sprintf "<p%s%s>" id_attr attr_non_id_html);
+          C.inline c (Block.Paragraph.inline p);
+          if not (String.is_empty fragment)
+          then (
+            C.string c {|<a class="fragment-link" href="|};
+            C.string c fragment;
+            C.string c {|" aria-label="Link to this paragraph">¶</a>|});
+          C.string c "</p>\n";
+          true))
+  | Block.Code_block (cb, meta)
+    when Option.exists
+           (Meta.find Cb_attribute.meta_key meta)
+           ~f:(fun (ci : Cb_attribute.code_block_info) ->
+             String.equal (String.lowercase ci.lang) "mermaid") ->
+    (* Left as source for the theme's client-side mermaid to draw. *)
+    C.string c "<pre class=\"mermaid\">";
+    List.iter (Block.Code_block.code cb) ~f:(fun bl ->
+      Cmarkit_html.html_escaped_string c (Block_line.to_string bl);
+      C.byte c '\n');
+    C.string c "</pre>\n";
+    true
   | Block.Code_block (cb, meta) ->
     (* Render with [data-attr-*] when a Pandoc-style attribute is
        attached. Otherwise let the default cmarkit_html renderer
@@ -636,10 +817,6 @@ let render_block
          C.byte c '\n');
        C.string c "</code></pre>\n";
        true)
-  | Parse.Frontmatter.Frontmatter y ->
-    let inner = Parse.Frontmatter.to_html (Some y) in
-    C.string c (sprintf "<div class=\"frontmatter\">%s</div>\n" inner);
-    true
   | Block.Ext_div (d, _) ->
     let class_name = Option.map (Block.Div.class' d) ~f:fst in
     let body = Block.Div.block d in
@@ -668,8 +845,16 @@ let render_block
   | Block.Blocks (blocks, meta) ->
     (match Meta.find Embed.embed_meta_key meta with
      | None -> false
-     | Some { depth; _ } ->
-       C.string c (sprintf "<div class=\"embed\" data-embed-depth=\"%d\">\n" depth);
+     | Some { depth; view; _ } ->
+       let view_attr =
+         match view with
+         | Embed.Full -> ""
+         | Embed.Summary | Embed.List ->
+           sprintf " data-embed-view=\"%s\"" (Embed.view_to_string view)
+       in
+       C.string
+         c
+         (sprintf "<div class=\"embed\" data-embed-depth=\"%d\"%s>\n" depth view_attr);
        List.iter blocks ~f:(C.block c);
        C.string c "</div>\n";
        true)
@@ -679,7 +864,11 @@ let render_block
 (* [struct_style] is threaded as a ref so that [Ext_div] can push a
    local override for the duration of its body and restore it afterwards.
    Rendering is depth-first and synchronous, so a single ref is safe. *)
-let block ~(struct_style : struct_style ref) (c : Cmarkit_renderer.context)
+let block
+      ~(struct_style : struct_style ref)
+      ~(share : Config.Share.t)
+      ~(images : Config.Images.t)
+      (c : Cmarkit_renderer.context)
   : Block.t -> bool
   = function
   | Block.Ext_attributes (a, _) ->
@@ -688,7 +877,7 @@ let block ~(struct_style : struct_style ref) (c : Cmarkit_renderer.context)
      (* Orphan attribute (e.g. [{#x}] before a blank line): no target, no HTML. *)
      | Block.Blocks ([], _) -> true
      | inner ->
-       if render_block ~struct_style ~attr c inner
+       if render_block ~struct_style ~share ~images ~attr c inner
        then true
        else (
          (* Generic fallback for a target with no dedicated rendering: wrap in
@@ -697,26 +886,275 @@ let block ~(struct_style : struct_style ref) (c : Cmarkit_renderer.context)
          C.block c inner;
          C.string c "</div>\n";
          true))
-  | b -> render_block ~struct_style c b
+  | b -> render_block ~struct_style ~share ~images c b
+;;
+
+(* Raw HTML under a [sanitize] or [strip] policy; [allow] leaves it to the
+   default renderer. *)
+let raw_html_renderer (policy : Config.Raw_html.t) : Cmarkit_renderer.t option =
+  let allowed_tags = Html_fragment.default_allowed_tags @ policy.extra_tags in
+  let emit c (html : string) =
+    match policy.mode with
+    | Config.Raw_html_mode_def.Allow -> false
+    | Config.Raw_html_mode_def.Strip -> true
+    | Config.Raw_html_mode_def.Sanitize ->
+      C.string
+        c
+        (Html_fragment.to_string
+           (Html_fragment.sanitize ~allowed_tags (Html_fragment.of_string html)));
+      true
+  in
+  match policy.mode with
+  | Config.Raw_html_mode_def.Allow -> None
+  | Strip | Sanitize ->
+    let inline c : Inline.t -> bool = function
+      | Inline.Raw_html (lines, _) ->
+        emit c (List.map lines ~f:Block_line.tight_to_string |> String.concat ~sep:"\n")
+      | _ -> false
+    in
+    let block c : Block.t -> bool = function
+      | Block.Html_block (lines, _) ->
+        let html = List.map lines ~f:Block_line.to_string |> String.concat ~sep:"\n" in
+        (match policy.mode with
+         | Strip -> true
+         | _ -> emit c (html ^ "\n"))
+      | _ -> false
+    in
+    Some (Cmarkit_renderer.make ~inline ~block ())
+;;
+
+(* The preview card of an external link: its image, title and description. *)
+let link_preview_card (p : Link_preview.t) : string =
+  let esc = Parse.Frontmatter.escape_html in
+  String.concat
+    [ "<span class=\"link-preview-card\">"
+    ; Option.value_map p.image ~default:"" ~f:(fun src ->
+        sprintf {|<img src="%s" alt="" loading="lazy"/>|} (esc src))
+    ; Option.value_map p.title ~default:"" ~f:(fun t ->
+        sprintf {|<span class="link-preview-title">%s</span>|} (esc t))
+    ; Option.value_map p.description ~default:"" ~f:(fun d ->
+        sprintf {|<span class="link-preview-description">%s</span>|} (esc d))
+    ; "</span>"
+    ]
+;;
+
+(* Links to [http], [https], [mailto] and [ftp] destinations with the
+   [target]/[rel] attributes of [policy], and the preview the pipeline put on
+   them shown as [previews] asks; [None] when it adds nothing. A link keeps
+   its title and the attributes written after it ([{.class}]). *)
+let external_links_renderer
+      ?(previews : Config.Link_previews.t = Config.Link_previews.default)
+      (policy : Config.External_links.t)
+  : Cmarkit_renderer.t option
+  =
+  let rel =
+    (if policy.new_tab then [ "noopener" ] else [])
+    @ List.filter policy.rel ~f:(fun r -> not (String.equal r "noopener"))
+  in
+  let attrs_str =
+    let target = if policy.new_tab then " target=\"_blank\"" else "" in
+    match rel with
+    | [] -> target
+    | rel -> sprintf "%s rel=\"%s\"" target (String.concat ~sep:" " rel)
+  in
+  let preview_style =
+    match previews.style with
+    | Config.Link_preview_style_def.Hover -> "hover"
+    | Config.Link_preview_style_def.Inline -> "inline"
+  in
+  let link
+        c
+        ?(title : string option)
+        ?(attr : Attribute.t option)
+        (href : string)
+        (meta : Meta.t)
+        (text : unit -> unit)
+    =
+    let preview = Meta.find Link_preview.meta_key meta in
+    if Option.is_some preview
+    then
+      C.string c (sprintf {|<span class="link-preview" data-style="%s">|} preview_style);
+    let attr_value (v : string) : string =
+      let buf = Buffer.create 64 in
+      buffer_add_attr_value buf v;
+      Buffer.contents buf
+    in
+    C.string
+      c
+      (sprintf
+         "<a href=\"%s\"%s%s%s>"
+         (attr_value href)
+         (Option.value_map attr ~default:"" ~f:cmarkit_attr_html)
+         (Option.value_map title ~default:"" ~f:(fun t ->
+            sprintf " title=\"%s\"" (attr_value t)))
+         attrs_str);
+    text ();
+    C.string c "</a>";
+    Option.iter preview ~f:(fun p ->
+      C.string c (link_preview_card p);
+      C.string c "</span>")
+  in
+  let render_link ?(attr : Attribute.t option) c : Inline.t -> bool = function
+    | Inline.Link (l, meta) ->
+      (match Parse.Link_def.of_link ~defs:(Doc.defs (C.get_doc c)) l with
+       | Some (Parse.Link_def.Link ld) ->
+         (match Parse.Link_def.dest ld with
+          | Some dest when Parse.Link_kind.is_external dest ->
+            let title : string option =
+              Option.map (Link_definition.title ld) ~f:(fun lines ->
+                List.map lines ~f:Block_line.tight_to_string |> String.concat ~sep:"\n")
+            in
+            link c ?title ?attr dest meta (fun () -> C.inline c (Inline.Link.text l));
+            true
+          | _ -> false)
+       | Some (Parse.Link_def.Footnote _) | None -> false)
+    | Inline.Autolink (a, meta) ->
+      let url, _ = Inline.Autolink.link a in
+      let href = if Inline.Autolink.is_email a then "mailto:" ^ url else url in
+      if Parse.Link_kind.is_external href
+      then (
+        link c ?attr href meta (fun () -> Cmarkit_html.html_escaped_string c url);
+        true)
+      else false
+    | _ -> false
+  in
+  let inline c : Inline.t -> bool = function
+    | Inline.Ext_attributes (a, _) ->
+      render_link ~attr:(Inline.Attributes.attributes a) c (Inline.Attributes.inline a)
+    | i -> render_link c i
+  in
+  match previews.mode with
+  | Config.Link_preview_mode_def.Off when String.is_empty attrs_str -> None
+  | _ -> Some (Cmarkit_renderer.make ~inline ())
+;;
+
+(* The frontmatter block, laid out by [display]; see {!Frontmatter_html}. *)
+let frontmatter_renderer (display : Config.Frontmatter_display.t) : Cmarkit_renderer.t =
+  let block c : Block.t -> bool = function
+    | Parse.Frontmatter.Frontmatter (y, _) ->
+      C.string c (Frontmatter_html.to_html ~display y);
+      true
+    | _ -> false
+  in
+  Cmarkit_renderer.make ~block ()
+;;
+
+(* GFM tables in a [table-wrapper] that scrolls sideways, cells aligned as the
+   delimiter row says; see {!Config.Tables} for the rest. *)
+let tables_renderer (tables : Config.Tables.t) : Cmarkit_renderer.t =
+  let block c : Block.t -> bool = function
+    | Block.Ext_table (table, _) ->
+      let rows = List.map (Block.Table.rows table) ~f:(fun ((row, _), _) -> row) in
+      let aligns : Block.Table.align option list =
+        List.find_map rows ~f:(function
+          | `Sep seps -> Some (List.map seps ~f:(fun ((align, _), _) -> align))
+          | _ -> None)
+        |> Option.value ~default:[]
+      in
+      let cols = Block.Table.col_count table in
+      let header =
+        List.filter_map rows ~f:(function
+          | `Header cells -> Some cells
+          | _ -> None)
+      in
+      let body =
+        List.filter_map rows ~f:(function
+          | `Data cells -> Some cells
+          | _ -> None)
+      in
+      let row (tag : string) (cells : (Inline.t * _) list) =
+        C.string c "<tr>\n";
+        List.iteri (List.take cells cols) ~f:(fun i (inline, _) ->
+          let style =
+            match Option.join (List.nth aligns i) with
+            | Some `Left -> {| style="text-align: left"|}
+            | Some `Center -> {| style="text-align: center"|}
+            | Some `Right -> {| style="text-align: right"|}
+            | None -> ""
+          in
+          C.string c (sprintf "<%s%s>" tag style);
+          C.inline c inline;
+          C.string c (sprintf "</%s>\n" tag));
+        (* Short rows are padded to the header's width *)
+        List.iter (List.range (List.length cells) cols) ~f:(fun _ ->
+          C.string c (sprintf "<%s></%s>\n" tag tag));
+        C.string c "</tr>\n"
+      in
+      let classes =
+        List.filter_opt
+          [ Option.some_if tables.sticky_header "sticky-header"
+          ; Option.some_if
+              (tables.sortable && List.length body >= tables.sortable_min_rows)
+              "sortable"
+          ]
+      in
+      C.string
+        c
+        (match classes with
+         | [] -> "<div class=\"table-wrapper\"><table>\n"
+         | classes ->
+           sprintf
+             "<div class=\"table-wrapper\"><table class=\"%s\">\n"
+             (String.concat ~sep:" " classes));
+      if not (List.is_empty header)
+      then (
+        C.string c "<thead>\n";
+        List.iter header ~f:(row "th");
+        C.string c "</thead>\n");
+      if not (List.is_empty body)
+      then (
+        C.string c "<tbody>\n";
+        List.iter body ~f:(row "td");
+        C.string c "</tbody>\n");
+      C.string c "</table></div>\n";
+      true
+    | _ -> false
+  in
+  Cmarkit_renderer.make ~block ()
 ;;
 
 let renderer
       ~(backend_blocks : bool)
       ~(safe : bool)
       ?(struct_style : struct_style = `Plain)
+      ?(raw_html : Config.Raw_html.t = Config.Raw_html.default)
+      ?(external_links : Config.External_links.t = Config.External_links.default)
+      ?(frontmatter : Config.Frontmatter_display.t = Config.Frontmatter_display.default)
+      ?(share : Config.Share.t = Config.Share.default)
+      ?(images : Config.Images.t = Config.Images.default)
+      ?(tables : Config.Tables.t = Config.Tables.default)
+      ?(link_previews : Config.Link_previews.t = Config.Link_previews.default)
+      ?(page_previews : Config.Page_previews.t = Config.Page_previews.default)
+      ?(urls : Url.t = Url.pretty)
       ()
   : Cmarkit_renderer.t
   =
   let style_ref = ref struct_style in
-  let custom = Cmarkit_renderer.make ~inline ~block:(block ~struct_style:style_ref) () in
+  let custom =
+    Cmarkit_renderer.make
+      ~inline:(inline ~urls ~lightbox:images.lightbox ~previews:page_previews.enabled)
+      ~block:(block ~struct_style:style_ref ~share ~images)
+      ()
+  in
   let default = Cmarkit_html.renderer ~backend_blocks ~safe () in
-  Cmarkit_renderer.compose default custom
+  List.fold
+    [ Some (frontmatter_renderer frontmatter)
+    ; Some (tables_renderer tables)
+    ; external_links_renderer ~previews:link_previews external_links
+    ; raw_html_renderer raw_html
+    ]
+    ~init:(Cmarkit_renderer.compose default custom)
+    ~f:(fun r extra ->
+      match extra with
+      | None -> r
+      | Some extra -> Cmarkit_renderer.compose r extra)
 ;;
 
 let of_doc
       ~(backend_blocks : bool)
       ~(safe : bool)
       ?(config = Config.default)
+      ?(urls : Url.t option)
       (* ?(struct_style : struct_style = `Plain) *)
         (doc : Doc.t)
   : string
@@ -727,7 +1165,65 @@ let of_doc
     | Config.Struct_style_def.Basic -> `Basic
     | Config.Struct_style_def.Graph -> `Graph
   in
-  Cmarkit_renderer.doc_to_string (renderer ~backend_blocks ~safe ~struct_style ()) doc
+  Cmarkit_renderer.doc_to_string
+    (renderer
+       ~backend_blocks
+       ~safe
+       ~struct_style
+       ~raw_html:config.raw_html
+       ~external_links:config.external_links
+       ~frontmatter:config.frontmatter
+       ~share:config.share
+       ~images:config.images
+       ~tables:config.tables
+       ~link_previews:config.link_previews
+       ~page_previews:config.page_previews
+       ?urls
+       ())
+    doc
+;;
+
+(** The hover preview of a note (see {!Config.Page_previews}): its first
+    heading, else [title], over its first paragraph. *)
+let preview_html
+      ~(backend_blocks : bool)
+      ~(safe : bool)
+      ?(config = Config.default)
+      ?(urls : Url.t option)
+      ~(title : string)
+      (doc : Doc.t)
+  : string
+  =
+  let blocks : Block.t list =
+    match Doc.block doc with
+    | Block.Blocks (blocks, _) -> blocks
+    | b -> [ b ]
+  in
+  let heading : string option =
+    List.find_map blocks ~f:(function
+      | Block.Heading (h, _) ->
+        Some (Heading_slug.inline_to_plain_text (Block.Heading.inline h))
+      | _ -> None)
+  in
+  let paragraph : string =
+    match
+      List.find blocks ~f:(function
+        | Block.Paragraph _ -> true
+        | _ -> false)
+    with
+    | None -> ""
+    | Some p ->
+      of_doc
+        ~backend_blocks
+        ~safe
+        ~config:{ config with share = Config.Share.default }
+        ?urls
+        (Doc.make ~defs:(Doc.defs doc) p)
+  in
+  sprintf
+    "<div class=\"page-preview\"><p class=\"page-preview-title\">%s</p>\n%s</div>\n"
+    (Parse.Frontmatter.escape_html (Option.value heading ~default:title))
+    paragraph
 ;;
 
 module For_test = struct
@@ -768,6 +1264,37 @@ let%expect_test "block attribute on blockquote" =
     |}]
 ;;
 
+let%expect_test "definition list" =
+  let open For_test in
+  let doc = Parse.of_string "Apple\n: A fruit\n: A *company*\nOrange\n: Also a fruit" in
+  Format.printf "%a%!" (pp_doc `Plain) doc;
+  [%expect
+    {|
+    <dl>
+    <dt>Apple</dt>
+    <dd>A fruit</dd>
+    <dd>A <em>company</em></dd>
+    <dt>Orange</dt>
+    <dd>Also a fruit</dd>
+    </dl>
+    |}]
+;;
+
+let%expect_test "GitHub alert renders as a callout" =
+  let open For_test in
+  let doc = Parse.of_string "> [!IMPORTANT]\n> Read this first." in
+  Format.printf "%a%!" (pp_doc `Plain) doc;
+  [%expect
+    {|
+    <div class="callout" data-callout="important">
+    <div class="callout-title">Important</div>
+    <div class="callout-content">
+    <p>Read this first.</p>
+    </div>
+    </div>
+    |}]
+;;
+
 let%expect_test "code block pandoc attribute renders as data-attr-*" =
   let open For_test in
   let src = "```python {#snippet .runnable timeout=30}\nprint('hi')\n```" in
@@ -788,6 +1315,164 @@ let%expect_test "orphan block attribute emits no HTML" =
   [%expect {| <p>A paragraph.</p> |}]
 ;;
 
+let%expect_test "raw HTML policy" =
+  let src =
+    "<div onclick=\"x()\">\n<script>alert(1)</script>\n</div>\n\nSome <b \
+     style=\"c\">bold</b> and <font>font</font>."
+  in
+  let render mode =
+    let config = { Config.default with raw_html = { mode; extra_tags = [] } } in
+    print_string (of_doc ~backend_blocks:false ~safe:false ~config (Parse.of_string src))
+  in
+  render Config.Raw_html_mode_def.Allow;
+  [%expect
+    {|
+    <div onclick="x()">
+    <script>alert(1)</script>
+    </div>
+    <p>Some <b style="c">bold</b> and <font>font</font>.</p>
+    |}];
+  render Config.Raw_html_mode_def.Sanitize;
+  [%expect
+    {|
+    <div>
+
+    </div>
+    <p>Some <b>bold</b> and font.</p>
+    |}];
+  render Config.Raw_html_mode_def.Strip;
+  [%expect {| <p>Some bold and font.</p> |}]
+;;
+
+let%expect_test "external links" =
+  let src =
+    "[OCaml](https://ocaml.org), <mailto:a@b.c> and [local](notes/a.md)\n\n\
+     [Docs][d]\n\n\
+     [d]: https://ocaml.org/docs"
+  in
+  let render external_links =
+    let config = { Config.default with external_links } in
+    print_string (of_doc ~backend_blocks:false ~safe:false ~config (Parse.of_string src))
+  in
+  render Config.External_links.default;
+  [%expect
+    {|
+    <p><a href="https://ocaml.org">OCaml</a>, <a href="mailto:a@b.c">a@b.c</a> and <a href="notes/a.md">local</a></p>
+    <p><a href="https://ocaml.org/docs">Docs</a></p>
+    |}];
+  render { new_tab = true; rel = [ "nofollow" ] };
+  [%expect
+    {|
+    <p><a href="https://ocaml.org" target="_blank" rel="noopener nofollow">OCaml</a>, <a href="mailto:a@b.c" target="_blank" rel="noopener nofollow">a@b.c</a> and <a href="notes/a.md">local</a></p>
+    <p><a href="https://ocaml.org/docs" target="_blank" rel="noopener nofollow">Docs</a></p>
+    |}];
+  (* Titles and attributes stay *)
+  let config = { Config.default with external_links = { new_tab = true; rel = [] } } in
+  Parse.of_string "[Home](https://example.com \"The home page\"){#home .ext}\n"
+  |> of_doc ~backend_blocks:false ~safe:false ~config
+  |> print_string;
+  [%expect
+    {| <p><a href="https://example.com" id="home" class="ext" title="The home page" target="_blank" rel="noopener">Home</a></p> |}]
+;;
+
+let%expect_test "glossary terms" =
+  let entries =
+    Glossary.entries
+      ~path:"Glossary.md"
+      (Parse.of_string "## TTL\n\nTime to live, in seconds & ticks.\n")
+  in
+  let doc = Parse.of_string "Set the TTL; the TTL is kept.\n" in
+  List.iter [ false; true ] ~f:(fun link ->
+    print_string
+      (of_doc ~backend_blocks:false ~safe:false (Glossary.mark ~link entries doc)));
+  [%expect
+    {|
+    <p>Set the <abbr class="glossary-term" title="Time to live, in seconds &amp; ticks.">TTL</abbr>; the TTL is kept.</p>
+    <p>Set the <a href="/Glossary/#ttl"><abbr class="glossary-term" title="Time to live, in seconds &amp; ticks.">TTL</abbr></a>; the TTL is kept.</p>
+    |}]
+;;
+
+let%expect_test "tables" =
+  let src = "| Name | Qty | Note |\n| :--- | ---: | :-: |\n| a | 1 |\n| b | 22 | x |\n" in
+  let render tables =
+    let config = { Config.default with tables } in
+    print_string (of_doc ~backend_blocks:false ~safe:false ~config (Parse.of_string src))
+  in
+  render Config.Tables.default;
+  [%expect
+    {|
+    <div class="table-wrapper"><table>
+    <thead>
+    <tr>
+    <th style="text-align: left">Name</th>
+    <th style="text-align: right">Qty</th>
+    <th style="text-align: center">Note</th>
+    </tr>
+    </thead>
+    <tbody>
+    <tr>
+    <td style="text-align: left">a</td>
+    <td style="text-align: right">1</td>
+    <td></td>
+    </tr>
+    <tr>
+    <td style="text-align: left">b</td>
+    <td style="text-align: right">22</td>
+    <td style="text-align: center">x</td>
+    </tr>
+    </tbody>
+    </table></div>
+    |}];
+  render { sticky_header = true; sortable = true; sortable_min_rows = 2 };
+  [%expect.output] |> String.split_lines |> List.hd_exn |> print_endline;
+  [%expect {| <div class="table-wrapper"><table class="sticky-header sortable"> |}];
+  render { sticky_header = false; sortable = true; sortable_min_rows = 3 };
+  [%expect.output] |> String.split_lines |> List.hd_exn |> print_endline;
+  [%expect {| <div class="table-wrapper"><table> |}]
+;;
+
+let%expect_test "share links" =
+  let config =
+    { Config.default with share = { headings = true; paragraphs = true } }
+  in
+  Parse.of_string "## Why it works\n\nShort, sweet & to-the-point.\n\n- item\n"
+  |> of_doc ~backend_blocks:false ~safe:false ~config
+  |> print_string;
+  [%expect
+    {|
+    <h2 id="why-it-works">Why it works<a class="heading-link" href="#why-it-works" aria-label="Link to this section">#</a></h2>
+    <p>Short, sweet &amp; to-the-point.<a class="fragment-link" href="#:~:text=Short%2C%20sweet%20%26%20to%2Dthe%2Dpoint." aria-label="Link to this paragraph">¶</a></p>
+    <ul>
+    <li>item</li>
+    </ul>
+    |}]
+;;
+
+let%expect_test "page previews" =
+  let doc =
+    Parse.of_string
+      "---\ntags: [a]\n---\n# Oysters & pearls\n\nBivalves *galore*.\n\nMore.\n"
+  in
+  print_string (preview_html ~backend_blocks:false ~safe:false ~title:"oysters" doc);
+  [%expect
+    {|
+    <div class="page-preview"><p class="page-preview-title">Oysters &amp; pearls</p>
+    <p>Bivalves <em>galore</em>.</p>
+    </div>
+    |}];
+  print_string
+    (preview_html
+       ~backend_blocks:false
+       ~safe:false
+       ~title:"empty"
+       (Parse.of_string "- just a list\n"));
+  [%expect
+    {|
+    <div class="page-preview"><p class="page-preview-title">empty</p>
+    </div>
+    |}]
+;;
+
 let%expect_test "struct: unified HTML across styles" =
   let open For_test in
   let src =
@@ -938,3 +1623,19 @@ let%test_module "don't throw" =
     ;;
   end)
 ;;
+
+let%expect_test "mermaid blocks are left for client-side rendering" =
+  let open For_test in
+  let doc =
+    Parse.of_string "```mermaid\ngraph TD\n  A --> B\n```\n\n```ocaml\nlet x = 1\n```"
+  in
+  Format.printf "%a%!" (pp_doc `Plain) doc;
+  [%expect
+    {|
+    <pre class="mermaid">graph TD
+      A --&gt; B
+    </pre>
+    <pre><code class="language-ocaml">let x = 1
+    </code></pre>
+    |}]
+;;
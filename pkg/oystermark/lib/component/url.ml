@@ -0,0 +1,468 @@
+(** Page URLs and output paths of a built site, under a {!Config.Url_style}.
+
+    - [pretty] (default): ["foo/Bar Baz.md"] is served at [/foo/Bar Baz/] from
+      [foo/Bar Baz/index.html]; ["foo/index.md"] is the folder's page [/foo/].
+    - [obsidian_publish]: URLs as Obsidian Publish writes them, so inbound links
+      to a site migrated off Publish keep working. ["foo/Bar Baz.md"] is
+      [/foo/Bar+Baz]: no extension, no trailing slash, case kept, spaces as
+      [+], other reserved characters percent-encoded. A note's [permalink]
+      frontmatter field replaces its path, as on Publish.
+
+    Notes name URLs that should still reach them in [aliases] and
+    [redirect_from] frontmatter; Publish also matches URLs case-insensitively
+    and redirects a permalinked note's old path. A static host does none of
+    this, so {!redirects} lists the URLs to redirect, served as stub pages
+    ({!redirect_html}) or a [_redirects] file ({!redirects_file}). *)
+
+open Core
+
+type t =
+  { style : Config.Url_style.t
+  ; permalinks : string String.Map.t (** note path to its [permalink] field *)
+  }
+
+let pretty : t = { style = Config.Url_style_def.Pretty; permalinks = String.Map.empty }
+
+(* The [permalink] fields of [docs] under [obsidian_publish], slashes and
+   blanks stripped. *)
+let permalink_fields
+      ~(style : Config.Url_style.t)
+      (docs : (string * Cmarkit.Doc.t) list)
+  : (string * string) list
+  =
+  match style with
+  | Config.Url_style_def.Pretty -> []
+  | Config.Url_style_def.Obsidian_publish ->
+    List.filter_map docs ~f:(fun (path, doc) ->
+      match Parse.Frontmatter.field doc "permalink" with
+      | Some (Parse.Value.String p) ->
+        let p =
+          String.strip p ~drop:(fun c -> Char.equal c '/' || Char.is_whitespace c)
+        in
+        if String.is_empty p then None else Some (path, p)
+      | _ -> None)
+;;
+
+(* Why [permalink] can't be a page's path, if it can't: it must be a path
+   within the site. *)
+let permalink_problem (permalink : string) : string option =
+  if String.is_substring permalink ~substring:"://"
+  then Some "is not a path within the site"
+  else if String.exists permalink ~f:(fun c -> Char.equal c '?' || Char.equal c '#')
+  then Some "has a query or fragment"
+  else if List.mem (String.split permalink ~on:'/') ".." ~equal:String.equal
+  then Some "leads out of the site"
+  else None
+;;
+
+(** Problems with [docs]' [permalink] fields, one line each: a permalink that
+    isn't a plain path within the site, which {!of_docs} ignores, and one
+    several notes share. *)
+let permalink_errors
+      ~(style : Config.Url_style.t)
+      (docs : (string * Cmarkit.Doc.t) list)
+  : string list
+  =
+  let fields = permalink_fields ~style docs in
+  let invalid =
+    List.filter_map fields ~f:(fun (path, p) ->
+      Option.map (permalink_problem p) ~f:(fun why ->
+        sprintf "%s: permalink %s %s" path p why))
+  in
+  let shared =
+    List.filter fields ~f:(fun (_, p) -> Option.is_none (permalink_problem p))
+    |> List.Assoc.inverse
+    |> String.Map.of_alist_multi
+    |> Map.to_alist
+    |> List.filter_map ~f:(fun (p, paths) ->
+      match paths with
+      | [] | [ _ ] -> None
+      | paths ->
+        Some (sprintf "permalink %s is shared by %s" p (String.concat ~sep:", " paths)))
+  in
+  invalid @ shared
+;;
+
+(** URL scheme of a vault: [permalink] fields are read from [docs]' frontmatter
+    (only under [obsidian_publish]); see {!permalink_errors} for the ones left
+    out. *)
+let of_docs ~(style : Config.Url_style.t) (docs : (string * Cmarkit.Doc.t) list) : t =
+  let permalinks : string String.Map.t =
+    permalink_fields ~style docs
+    |> List.filter ~f:(fun (_, p) -> Option.is_none (permalink_problem p))
+    |> String.Map.of_alist_reduce ~f:(fun first _ -> first)
+  in
+  { style; permalinks }
+;;
+
+(* One path segment as Publish writes it. *)
+let encode_segment (s : string) : string =
+  String.concat_map s ~f:(fun c ->
+    match c with
+    | ' ' -> "+"
+    | 'A' .. 'Z' | 'a' .. 'z' | '0' .. '9' | '-' | '_' | '.' | '~' | '!' | '$' | '&'
+    | '\'' | '(' | ')' | '*' | ',' | ';' | '=' | ':' | '@' -> String.of_char c
+    | c -> sprintf "%%%02X" (Char.to_int c))
+;;
+
+let encode_path (path : string) : string =
+  String.split path ~on:'/' |> List.map ~f:encode_segment |> String.concat ~sep:"/"
+;;
+
+(** URL path of note [rel_path]. *)
+let note_url_path (t : t) (rel_path : string) : string =
+  let base = String.chop_suffix_exn rel_path ~suffix:".md" in
+  match t.style with
+  | Config.Url_style_def.Pretty ->
+    if String.is_suffix base ~suffix:"/index"
+    then "/" ^ String.chop_suffix_exn base ~suffix:"/index" ^ "/"
+    else "/" ^ base ^ "/"
+  | Config.Url_style_def.Obsidian_publish ->
+    "/" ^ encode_path (Option.value (Map.find t.permalinks rel_path) ~default:base)
+;;
+
+(** URL path for any file: notes get page URLs, others their literal path. *)
+let file_url_path (t : t) (path : string) : string =
+  if String.is_suffix path ~suffix:".md"
+  then note_url_path t path
+  else (
+    match t.style with
+    | Config.Url_style_def.Pretty -> "/" ^ path
+    | Config.Url_style_def.Obsidian_publish -> "/" ^ encode_path path)
+;;
+
+(** URL path of the page of folder [dir], given as it appears in URLs. *)
+let folder_url_path (t : t) (dir : string) : string =
+  match t.style with
+  | Config.Url_style_def.Pretty -> "/" ^ dir ^ "/"
+  | Config.Url_style_def.Obsidian_publish -> "/" ^ dir ^ "/index"
+;;
+
+(** Display text of URL path segment [seg]. *)
+let segment_text (t : t) (seg : string) : string =
+  match t.style with
+  | Config.Url_style_def.Pretty -> seg
+  | Config.Url_style_def.Obsidian_publish ->
+    Vault.Link_ref.percent_decode (String.tr seg ~target:'+' ~replacement:' ')
+;;
+
+(** Text fragment ([#:~:text=...]) pointing at [text], a paragraph's plain
+    text: the whole text when short, else its first and last few words as a
+    [start,end] range. Empty for blank [text]. *)
+let text_fragment (text : string) : string =
+  (* Fragment directives reserve [-], [,] and [&] besides the usual URL
+     characters; words keep only unreserved ones. *)
+  let encode (s : string) : string =
+    String.concat_map s ~f:(fun c ->
+      match c with
+      | 'A' .. 'Z' | 'a' .. 'z' | '0' .. '9' | '_' | '.' | '~' -> String.of_char c
+      | c -> sprintf "%%%02X" (Char.to_int c))
+  in
+  let words =
+    String.split_on_chars text ~on:[ ' '; '\t'; '\n'; '\r' ]
+    |> List.filter ~f:(Fn.non String.is_empty)
+  in
+  let phrase words = encode (String.concat ~sep:" " words) in
+  let edge = 4 in
+  match words with
+  | [] -> ""
+  | words when List.length words <= 2 * edge -> "#:~:text=" ^ phrase words
+  | words ->
+    let n = List.length words in
+    sprintf
+      "#:~:text=%s,%s"
+      (phrase (List.take words edge))
+      (phrase (List.drop words (n - edge)))
+;;
+
+(** Output file serving [url]: the [index.html] of its folder, since static
+    hosts serve [/a/b] and [/a/b/] from [a/b/index.html], or the file itself
+    for an [.html] URL. Percent-escapes are decoded, as hosts do before
+    looking a path up; [+] is kept. [None] for a URL with a [..] segment,
+    which could lead out of the output directory. *)
+let output_path_of_url (url : string) : string option =
+  let path = Vault.Link_ref.percent_decode (String.strip url ~drop:(Char.equal '/')) in
+  if List.mem (String.split path ~on:'/') ".." ~equal:String.equal
+  then None
+  else if String.is_empty path
+  then Some "index.html"
+  else if String.is_suffix path ~suffix:".html" || String.is_suffix path ~suffix:".htm"
+  then Some path
+  else Some (path ^ "/index.html")
+;;
+
+(** Output file for note [rel_path]. *)
+let note_output_path (t : t) (rel_path : string) : string =
+  match output_path_of_url (note_url_path t rel_path) with
+  | Some path -> path
+  | None -> failwithf "%s: URL leads out of the output directory" rel_path ()
+;;
+
+(** Output file of the hover preview of note [rel_path], mirroring its page
+    under [_previews/]; see {!Config.Page_previews}. *)
+let preview_output_path (t : t) (rel_path : string) : string =
+  "_previews/" ^ note_output_path t rel_path
+;;
+
+(** URL of {!preview_output_path}. *)
+let preview_url_path (t : t) (rel_path : string) : string =
+  match t.style with
+  | Config.Url_style_def.Pretty -> "/" ^ preview_output_path t rel_path
+  | Config.Url_style_def.Obsidian_publish ->
+    "/" ^ encode_path (preview_output_path t rel_path)
+;;
+
+(* Strings of frontmatter field [key]: one string or a list of them. *)
+let strings_field (doc : Cmarkit.Doc.t) (key : string) : string list =
+  match Parse.Frontmatter.field doc key with
+  | Some (Parse.Value.String s) -> [ s ]
+  | Some (Parse.Value.List vs) ->
+    List.filter_map vs ~f:(function
+      | Parse.Value.String s -> Some s
+      | _ -> None)
+  | _ -> []
+;;
+
+(** URLs that should lead to note [path] besides its own: its [aliases]
+    (as sibling notes of that name) and the URL paths listed under
+    [redirect_from]; with [obsidian_publish], also the path a permalink
+    replaced. *)
+let old_urls (t : t) ((path, doc) : string * Cmarkit.Doc.t) : string list =
+  let dir = Filename.dirname path in
+  let sibling name = if String.equal dir "." then name else dir ^ "/" ^ name in
+  let unlinked = { t with permalinks = String.Map.empty } in
+  List.concat
+    [ (if Map.mem t.permalinks path then [ note_url_path unlinked path ] else [])
+    ; List.filter_map (strings_field doc "aliases") ~f:(fun alias ->
+        let alias = String.strip alias in
+        if String.is_empty alias || String.mem alias '/'
+        then None
+        else Some (note_url_path unlinked (sibling alias ^ ".md")))
+    ; List.filter_map (strings_field doc "redirect_from") ~f:(fun from ->
+        match String.strip from with
+        | "" -> None
+        | from when String.is_prefix from ~prefix:"/" -> Some from
+        | from -> Some ("/" ^ from))
+    ]
+;;
+
+type redirect_problem =
+  | Escapes (** a [..] segment would lead out of the site *)
+  | Shadows_page (** the URL is a page's own *)
+
+(** A redirect from one of a note's {!old_urls} that {!redirects} leaves out. *)
+type redirect_issue =
+  { note : string
+  ; from : string
+  ; problem : redirect_problem
+  }
+
+let redirect_issue_to_string_hum (i : redirect_issue) : string =
+  match i.problem with
+  | Escapes -> sprintf "%s: redirect from %s leads out of the site; skipped" i.note i.from
+  | Shadows_page ->
+    sprintf "%s: redirect from %s would replace a page there; skipped" i.note i.from
+;;
+
+(* Every redirect of [docs]' notes as [(note, from, target_url)], [explicit]
+   when the note asked for it rather than it being a lowercase form. *)
+let candidate_redirects (t : t) (docs : (string * Cmarkit.Doc.t) list)
+  : (string * string * string * bool) list
+  =
+  let notes = List.filter docs ~f:(fun (p, _) -> String.is_suffix p ~suffix:".md") in
+  List.concat_map notes ~f:(fun ((p, _) as note) ->
+    let url = note_url_path t p in
+    let froms = old_urls t note in
+    let lowercase =
+      match t.style with
+      | Config.Url_style_def.Pretty -> []
+      | Config.Url_style_def.Obsidian_publish ->
+        List.map (url :: froms) ~f:String.lowercase
+    in
+    List.map froms ~f:(fun from -> p, from, url, true)
+    @ List.map lowercase ~f:(fun from -> p, from, url, false))
+;;
+
+let page_urls (t : t) (docs : (string * Cmarkit.Doc.t) list) : String.Set.t =
+  List.filter_map docs ~f:(fun (p, _) ->
+    Option.some_if (String.is_suffix p ~suffix:".md") (note_url_path t p))
+  |> String.Set.of_list
+;;
+
+(** Redirects to serve for notes [docs], as [(url, target_url)]: from each
+    note's {!old_urls} and, with [obsidian_publish], from the lowercase form
+    of those and of every page URL, since Publish matches URLs
+    case-insensitively. Page URLs are never shadowed, and URLs leading out of
+    the site are dropped (see {!redirect_issues}); the first of several
+    redirects from one URL wins. *)
+let redirects (t : t) (docs : (string * Cmarkit.Doc.t) list) : (string * string) list =
+  let page_urls = page_urls t docs in
+  candidate_redirects t docs
+  |> List.filter_map ~f:(fun (_, from, url, _) ->
+    Option.some_if
+      ((not (Set.mem page_urls from)) && Option.is_some (output_path_of_url from))
+      (from, url))
+  |> List.stable_dedup ~compare:(fun (a, _) (b, _) -> String.compare a b)
+;;
+
+(** The redirects notes [docs] ask for that {!redirects} drops. Lowercase
+    forms that happen to be a page's URL are not reported. *)
+let redirect_issues (t : t) (docs : (string * Cmarkit.Doc.t) list)
+  : redirect_issue list
+  =
+  let page_urls = page_urls t docs in
+  List.filter_map (candidate_redirects t docs) ~f:(fun (note, from, _, explicit) ->
+    if Option.is_none (output_path_of_url from)
+    then Some { note; from; problem = Escapes }
+    else if explicit && Set.mem page_urls from
+    then Some { note; from; problem = Shadows_page }
+    else None)
+  |> List.stable_dedup ~compare:(fun a b -> String.compare a.from b.from)
+;;
+
+(** A [_redirects] file (Netlify, Cloudflare Pages) with [redirects], all
+    permanent. *)
+let redirects_file (redirects : (string * string) list) : string =
+  let encode url = String.substr_replace_all url ~pattern:" " ~with_:"%20" in
+  List.map redirects ~f:(fun (from, url) ->
+    sprintf "%s %s 301\n" (encode from) (encode url))
+  |> String.concat
+;;
+
+(** Page sending the browser on to [url]. *)
+let redirect_html (url : string) : string =
+  let url =
+    String.concat_map url ~f:(function
+      | '&' -> "&amp;"
+      | '"' -> "&quot;"
+      | '<' -> "&lt;"
+      | '>' -> "&gt;"
+      | c -> String.of_char c)
+  in
+  sprintf
+    {|<!DOCTYPE html>
+<html><head><meta charset="utf-8"><link rel="canonical" href="%s">
+<meta http-equiv="refresh" content="0; url=%s"></head>
+<body><a href="%s">%s</a></body></html>
+|}
+    url
+    url
+    url
+    url
+;;
+
+let%expect_test "obsidian_publish" =
+  let docs =
+    List.map
+      [ "Notes/My Note.md", ""
+      ; "Notes/C# & F#.md", ""
+      ; "about.md", "---\npermalink: /About-me/\naliases: [Me]\n---\nHi.\n"
+      ; "notes/lower.md", ""
+      ]
+      ~f:(fun (path, src) -> path, Parse.of_string ~locs:false src)
+  in
+  let t = of_docs ~style:Config.Url_style_def.Obsidian_publish docs in
+  List.iter docs ~f:(fun (p, _) ->
+    printf "%s -> %s (%s)\n" p (note_url_path t p) (note_output_path t p));
+  printf "%s\n" (file_url_path t "img/a b.png");
+  [%expect
+    {|
+    Notes/My Note.md -> /Notes/My+Note (Notes/My+Note/index.html)
+    Notes/C# & F#.md -> /Notes/C%23+&+F%23 (Notes/C#+&+F#/index.html)
+    about.md -> /About-me (About-me/index.html)
+    notes/lower.md -> /notes/lower (notes/lower/index.html)
+    /img/a+b.png
+    |}];
+  List.iter (redirects t docs) ~f:(fun (from, url) -> printf "%s -> %s\n" from url);
+  [%expect
+    {|
+    /notes/my+note -> /Notes/My+Note
+    /notes/c%23+&+f%23 -> /Notes/C%23+&+F%23
+    /about-me -> /About-me
+    /about -> /About-me
+    /Me -> /About-me
+    /me -> /About-me
+    |}]
+;;
+
+let%expect_test "permalink_errors" =
+  let docs =
+    List.map
+      [ "a.md", "---\npermalink: /shared/\n---\n"
+      ; "b.md", "---\npermalink: shared\n---\n"
+      ; "c.md", "---\npermalink: ../../etc\n---\n"
+      ; "d.md", "---\npermalink: https://example.com/d\n---\n"
+      ; "e.md", "---\npermalink: fine/path\n---\n"
+      ]
+      ~f:(fun (path, src) -> path, Parse.of_string ~locs:false src)
+  in
+  let style = Config.Url_style_def.Obsidian_publish in
+  List.iter (permalink_errors ~style docs) ~f:print_endline;
+  [%expect
+    {|
+    c.md: permalink ../../etc leads out of the site
+    d.md: permalink https://example.com/d is not a path within the site
+    permalink shared is shared by a.md, b.md
+    |}];
+  let t = of_docs ~style docs in
+  List.iter [ "c.md"; "d.md"; "e.md" ] ~f:(fun p -> print_endline (note_url_path t p));
+  [%expect
+    {|
+    /c
+    /d
+    /fine/path
+    |}];
+  let pretty_errors = permalink_errors ~style:Config.Url_style_def.Pretty docs in
+  print_s [%sexp (pretty_errors : string list)];
+  [%expect {| () |}]
+;;
+
+let%expect_test "text_fragment" =
+  List.iter
+    [ "Short, sweet & to-the-point."
+    ; "Static hosts serve [/a/b] and [/a/b/] from the same index.html file."
+    ; "  "
+    ]
+    ~f:(fun text -> print_endline (text_fragment text));
+  [%expect
+    {|
+    #:~:text=Short%2C%20sweet%20%26%20to%2Dthe%2Dpoint.
+    #:~:text=Static%20hosts%20serve%20%5B%2Fa%2Fb%5D,the%20same%20index.html%20file.
+
+    |}]
+;;
+
+let%expect_test "redirects" =
+  let docs =
+    List.map
+      [ ( "guides/Setup.md"
+        , "---\naliases: [Install, Getting started]\n\
+           redirect_from:\n  - /old/setup/\n  - setup.html\n  - ../escape/\n---\n" )
+      ; "guides/Install.md", ""
+      ]
+      ~f:(fun (path, src) -> path, Parse.of_string ~locs:false src)
+  in
+  let redirects = redirects pretty docs in
+  List.iter redirects ~f:(fun (from, url) ->
+    printf "%s -> %s\n" (Option.value_exn (output_path_of_url from)) url);
+  [%expect
+    {|
+    guides/Getting started/index.html -> /guides/Setup/
+    old/setup/index.html -> /guides/Setup/
+    setup.html -> /guides/Setup/
+    |}];
+  List.iter (redirect_issues pretty docs) ~f:(fun i ->
+    print_endline (redirect_issue_to_string_hum i));
+  [%expect
+    {|
+    guides/Setup.md: redirect from /guides/Install/ would replace a page there; skipped
+    guides/Setup.md: redirect from /../escape/ leads out of the site; skipped
+    |}];
+  print_string (redirects_file redirects);
+  [%expect
+    {|
+    /guides/Getting%20started/ /guides/Setup/ 301
+    /old/setup/ /guides/Setup/ 301
+    /setup.html /guides/Setup/ 301
+    |}]
+;;
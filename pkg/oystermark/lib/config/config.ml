@@ -92,6 +92,703 @@ module Ext_struct = struct
   let t_of_yojson j = or_default ~default t_of_yojson j
 end
 
+module Slug_style_def = struct
+  type t =
+    | Github
+    | Obsidian
+
+  let table = [ "github", Github, [ "gfm" ]; "obsidian", Obsidian, [] ]
+  let default = Github
+end
+
+(** How heading anchors are derived from heading text. See
+    {!Parse.Heading_slug.slugify}. *)
+module Slug_style = Make_string_enum (Slug_style_def)
+
+(** Markdown syntax recognized when parsing notes. The build and [check]
+    parse through [Parse.of_string ~config], so they agree; commands taking no
+    config ([ast], [outline-diagram], [links convert]) and the LSP's lookups in
+    other notes parse with the defaults. Extensions are all on by default;
+    raw-text fidelity is opt-in. *)
+module Parse_options = struct
+  type t =
+    { extensions : bool [@default true]
+      (** tables, footnotes, math, strikethrough and task items *)
+    ; wikilink : bool [@default true] (** [[[note]]] and [![[embed]]] *)
+    ; block_id : bool [@default true] (** [^id] block references *)
+    ; div : bool [@default true] (** [:::] fenced divs *)
+    ; attributes : bool [@default true] (** [{#id .class}] on inlines and blocks *)
+    ; callout : bool [@default true]
+      (** [> [!note]] callouts, GitHub's [> [!NOTE]] alerts included *)
+    ; definition_list : bool [@default true] (** [Term] then [: definition] lines *)
+    ; smart_punctuation : bool [@default true]
+      (** when off, text keeps its source bytes ([don't], not [don’t]) *)
+    ; coalesce_text : bool [@default false]
+      (** merge adjacent text inlines into one *)
+    ; slug_style : Slug_style.t [@default Slug_style.default]
+      (** heading anchors: [github] or [obsidian] *)
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default =
+    { extensions = true
+    ; wikilink = true
+    ; block_id = true
+    ; div = true
+    ; attributes = true
+    ; callout = true
+    ; definition_list = true
+    ; smart_punctuation = true
+    ; coalesce_text = false
+    ; slug_style = Slug_style.default
+    }
+  ;;
+
+  let t_of_yojson j = or_default ~default t_of_yojson j
+
+  (** Turn off the option named like its JSON field; an error for any other
+      name. *)
+  let disable (t : t) (name : string) : (t, string) result =
+    match name with
+    | "extensions" -> Ok { t with extensions = false }
+    | "wikilink" -> Ok { t with wikilink = false }
+    | "block_id" -> Ok { t with block_id = false }
+    | "div" -> Ok { t with div = false }
+    | "attributes" -> Ok { t with attributes = false }
+    | "callout" -> Ok { t with callout = false }
+    | "definition_list" -> Ok { t with definition_list = false }
+    | "smart_punctuation" -> Ok { t with smart_punctuation = false }
+    | _ -> Error ("invalid parse option: " ^ name)
+  ;;
+end
+
+module Raw_html_mode_def = struct
+  type t =
+    | Allow
+    | Sanitize
+    | Strip
+
+  let table = [ "allow", Allow, []; "sanitize", Sanitize, []; "strip", Strip, [] ]
+  let default = Allow
+end
+
+module Raw_html_mode = Make_string_enum (Raw_html_mode_def)
+
+(** What the HTML export does with raw HTML written in notes (HTML blocks and
+    inline tags): pass it through ([allow]), keep only allowlisted tags and
+    attributes ([sanitize]), or drop it ([strip]). [extra_tags] extends the
+    built-in allowlist of {!Parse.Html_fragment}. *)
+module Raw_html = struct
+  type t =
+    { mode : Raw_html_mode.t [@default Raw_html_mode.default]
+    ; extra_tags : string list [@default []]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { mode = Raw_html_mode.default; extra_tags = [] }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Link_preference_def = struct
+  type t =
+    | First
+    | Shortest
+    | Nearest
+
+  let table = [ "first", First, []; "shortest", Shortest, []; "nearest", Nearest, [] ]
+  let default = First
+end
+
+module Link_preference = Make_string_enum (Link_preference_def)
+
+(** How link targets are matched to files. *)
+module Link_resolution = struct
+  type t =
+    { prefer : Link_preference.t [@default Link_preference.default]
+      (** how a link naming several files ([[[Ideas]]] with [a/Ideas.md] and
+          [b/Ideas.md]) picks one: the file with the fewest folders
+          ([shortest]), the one sharing the most folders with the linking note
+          ([nearest]), or the first in vault listing order ([first], the
+          default); ties go to the alphabetically first path *)
+    ; relative : bool [@default false]
+      (** resolve Markdown links ([[x](other/note.md)]) relative to the
+          linking note's folder first, as plain Markdown tools do. Markdown
+          link targets starting with [./] or [../] are always relative;
+          wikilink targets are always vault paths. *)
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { prefer = Link_preference.default; relative = false }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Attributes the HTML export adds to links leaving the vault ([http],
+    [https], [mailto], [ftp]). With [new_tab], they open in a new tab
+    ([target="_blank"]) and always get [rel="noopener"]; [rel] adds more
+    values, e.g. [nofollow]. *)
+module External_links = struct
+  type t =
+    { new_tab : bool [@default false]
+    ; rel : string list [@default []]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { new_tab = false; rel = [] }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Note templates: notes under [folder] are not published as pages. A
+    published note with [template: name] in its frontmatter renders as
+    [folder/name.md], its [{{var}}] placeholders filled from the note's
+    frontmatter and [{{content}}] standing for the note's own body. The same
+    templates seed new notes ([oyster new --template]). *)
+module Templates = struct
+  type t = { folder : string [@default "templates"] }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { folder = "templates" }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Site-wide values: [title] fills the [{{site.title}}] placeholders of notes
+    that turn them on (see {!Vault.Variables}). *)
+module Site = struct
+  type t = { title : string [@default ""] }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { title = "" }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Url_style_def = struct
+  type t =
+    | Pretty
+    | Obsidian_publish
+
+  let table =
+    [ "pretty", Pretty, []
+    ; "obsidian_publish", Obsidian_publish, [ "obsidian-publish"; "publish" ]
+    ]
+  ;;
+
+  let default = Pretty
+end
+
+(** Page URLs of the HTML export: [pretty] ([/foo/Bar Baz/]) or Obsidian
+    Publish's ([/foo/Bar+Baz], honouring [permalink] frontmatter). See
+    {!Component.Url}. *)
+module Url_style = Make_string_enum (Url_style_def)
+
+module Redirect_format_def = struct
+  type t =
+    | Html
+    | Redirects_file
+
+  let table =
+    [ "html", Html, [ "stubs" ]
+    ; "redirects_file", Redirects_file, [ "_redirects"; "netlify"; "cloudflare" ]
+    ]
+  ;;
+
+  let default = Html
+end
+
+module Redirect_format = Make_string_enum (Redirect_format_def)
+
+(** Redirects the HTML export writes for notes' [aliases] and [redirect_from]
+    frontmatter (and, with [url_style: obsidian_publish], Publish's
+    case-insensitive URLs): an HTML stub page per old URL ([html]), or one
+    [_redirects] file for Netlify and Cloudflare Pages ([redirects_file]). *)
+module Redirects = struct
+  type t = { format : Redirect_format.t [@default Redirect_format.default] }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { format = Redirect_format.default }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Print output. Every theme prints cleanly (see {!Theme.print_css}); with
+    [variants], each note also gets a [note.print.html] page laid out for
+    paper, next to its source path in the output. *)
+module Print = struct
+  type t = { variants : bool [@default false] }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { variants = false }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Sidebar_order_def = struct
+  type t =
+    | Toc_order
+    | Alphabetical
+    | Frontmatter
+
+  let table =
+    [ "toc_order", Toc_order, []
+    ; "alphabetical", Alphabetical, [ "alpha" ]
+    ; "frontmatter", Frontmatter, [ "order" ]
+    ]
+  ;;
+
+  let default = Toc_order
+end
+
+module Sidebar_order = Make_string_enum (Sidebar_order_def)
+
+(** The sidebar file tree of the HTML export. Entries of a folder are sorted by
+    [order]: by the [toc_order] patterns ([toc_order], the default), by name
+    ([alphabetical]), or by a numeric [order] frontmatter field ([frontmatter];
+    a folder takes the field of its [index.md], entries without one come after,
+    sorted as with [toc_order]). Folders fold open and closed, remembered across
+    pages by the browser. *)
+module Sidebar = struct
+  type t = { order : Sidebar_order.t [@default Sidebar_order.default] }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { order = Sidebar_order.default }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Menu in the header of every page, separate from the sidebar tree. Each
+    entry has a [label] and a [target], a note (vault path, [.md] optional,
+    e.g. ["guides/Intro"]) or a URL; an entry's [children] drop down under it,
+    one level deep. An entry may have children and no target.
+    {v
+    "menu": [
+      { "label": "Guides", "target": "guides/index",
+        "children": [ { "label": "Install", "target": "guides/Install" } ] },
+      { "label": "Source", "target": "https://github.com/hon-gyu/oyster" }
+    ]
+    v} *)
+module Menu = struct
+  module Link = struct
+    type t =
+      { label : string
+      ; target : string
+      }
+    [@@deriving yojson] [@@yojson.allow_extra_fields]
+  end
+
+  module Item = struct
+    type t =
+      { label : string
+      ; target : string [@default ""]
+      ; children : Link.t list [@default []]
+      }
+    [@@deriving yojson] [@@yojson.allow_extra_fields]
+  end
+
+  type t = Item.t list [@@deriving yojson]
+
+  let default : t = []
+  let t_of_yojson j = or_default ~default t_of_yojson j
+
+  (** Menu from [--menu] flags, in order: [LABEL=TARGET] adds an entry,
+      [PARENT>LABEL=TARGET] a child of entry [PARENT] (added without a target
+      if missing). *)
+  let of_flags (flags : string list) : t =
+    let split_at (s : string) (c : char) : string * string option =
+      match String.index_opt s c with
+      | Some i ->
+        ( String.trim (String.sub s 0 i)
+        , Some (String.trim (String.sub s (i + 1) (String.length s - i - 1))) )
+      | None -> String.trim s, None
+    in
+    let add (menu : t) (flag : string) : t =
+      let entry, target = split_at flag '=' in
+      let target = Option.value target ~default:"" in
+      match split_at entry '>' with
+      | label, None -> menu @ [ { Item.label; target; children = [] } ]
+      | parent, Some label ->
+        let child : Link.t = { label; target } in
+        if List.exists (fun (item : Item.t) -> String.equal item.label parent) menu
+        then
+          List.map
+            (fun (item : Item.t) ->
+               if String.equal item.label parent
+               then { item with children = item.children @ [ child ] }
+               else item)
+            menu
+        else menu @ [ { Item.label = parent; target = ""; children = [ child ] } ]
+    in
+    List.fold_left add [] flags
+  ;;
+end
+
+module Frontmatter_render_def = struct
+  type t =
+    | Text
+    | Chips
+    | Date
+    | Link
+
+  let table =
+    [ "text", Text, []
+    ; "chips", Chips, [ "tags" ]
+    ; "date", Date, []
+    ; "link", Link, [ "url" ]
+    ]
+  ;;
+
+  let default = Text
+end
+
+(** How a frontmatter field is shown: as written ([text], the default), one
+    chip per item ([chips], for tags), dates in [date_format] ([date]), or
+    URLs as links ([link]). Values a renderer doesn't fit are shown as
+    [text]. *)
+module Frontmatter_render = Make_string_enum (Frontmatter_render_def)
+
+(** The frontmatter block on HTML pages. With no [fields], every key is shown
+    as written, in source order; otherwise only the listed keys, in the listed
+    order, each under its [label] (the key when empty) and shown by its
+    [render]. [date_format] takes [%Y], [%m], [%d], [%e], [%B] and [%b].
+    A note with [hide_frontmatter: true] shows no frontmatter block. *)
+module Frontmatter_display = struct
+  module Field = struct
+    type t =
+      { key : string
+      ; label : string [@default ""]
+      ; render : Frontmatter_render.t [@default Frontmatter_render.default]
+      }
+    [@@deriving yojson] [@@yojson.allow_extra_fields]
+  end
+
+  type t =
+    { fields : Field.t list [@default []]
+    ; date_format : string [@default "%Y-%m-%d"]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { fields = []; date_format = "%Y-%m-%d" }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+
+  (** Field from a [--frontmatter-field] flag, [KEY[:RENDER][=LABEL]], e.g.
+      [date:date=Published]. *)
+  let field_of_flag (flag : string) : Field.t =
+    let spec, label =
+      match String.index_opt flag '=' with
+      | Some i -> String.sub flag 0 i, String.sub flag (i + 1) (String.length flag - i - 1)
+      | None -> flag, ""
+    in
+    let key, render =
+      match String.index_opt spec ':' with
+      | Some i ->
+        ( String.sub spec 0 i
+        , Frontmatter_render.of_string
+            (String.sub spec (i + 1) (String.length spec - i - 1)) )
+      | None -> spec, Frontmatter_render.default
+    in
+    { key = String.trim key; label = String.trim label; render }
+  ;;
+end
+
+(** Deep links into published notes: with [headings], each heading gets a
+    link to itself; with [paragraphs], each paragraph gets a text-fragment
+    link ([#:~:text=]) that scrolls to and highlights it. Clicking either
+    copies the link, or opens the share sheet on touch devices. *)
+module Share = struct
+  type t =
+    { headings : bool [@default false]
+    ; paragraphs : bool [@default false]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { headings = false; paragraphs = false }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Image_caption_def = struct
+  type t =
+    | Off
+    | Alt
+    | Italic
+
+  let table = [ "off", Off, [ "none" ]; "alt", Alt, []; "italic", Italic, [] ]
+  let default = Off
+end
+
+module Image_caption = Make_string_enum (Image_caption_def)
+
+(** Images on HTML pages. A paragraph holding just an image becomes a figure
+    captioned by [caption]: the image's alt text ([alt]), or the italic line
+    right under it ([italic], as in [![[photo.png]]] then [*Caption*]);
+    [off] (the default) adds no captions. With [lightbox] (the default),
+    clicking an image zooms it. *)
+module Images = struct
+  type t =
+    { caption : Image_caption.t [@default Image_caption.default]
+    ; lightbox : bool [@default true]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { caption = Image_caption.default; lightbox = true }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Tables on HTML pages. Each scrolls sideways on its own when wider than the
+    page, and keeps the column alignment of its delimiter row. With
+    [sticky_header], a tall table scrolls within a box under its header row;
+    with [sortable], tables of at least [sortable_min_rows] rows sort by a
+    column when its header is clicked. *)
+module Tables = struct
+  type t =
+    { sticky_header : bool [@default false]
+    ; sortable : bool [@default false]
+    ; sortable_min_rows : int [@default 10]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { sticky_header = false; sortable = false; sortable_min_rows = 10 }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Link_preview_mode_def = struct
+  type t =
+    | Off
+    | Fetch
+    | Offline
+
+  let table = [ "off", Off, [ "none" ]; "fetch", Fetch, []; "offline", Offline, [] ]
+  let default = Off
+end
+
+module Link_preview_mode = Make_string_enum (Link_preview_mode_def)
+
+module Link_preview_style_def = struct
+  type t =
+    | Hover
+    | Inline
+
+  let table = [ "hover", Hover, []; "inline", Inline, [] ]
+  let default = Hover
+end
+
+module Link_preview_style = Make_string_enum (Link_preview_style_def)
+
+(** Previews of external links: the page title, description and [og:image],
+    read at build time and kept in a cache in the vault's [.oyster/]. With
+    [mode] [fetch], pages missing from the cache are downloaded (waiting at
+    most [timeout] seconds each); [offline] only uses the cache, never the
+    network; [off] (the default) shows no previews. [style] [hover] shows a
+    card while the link is hovered or focused, [inline] a box under it. *)
+module Link_previews = struct
+  type t =
+    { mode : Link_preview_mode.t [@default Link_preview_mode.default]
+    ; style : Link_preview_style.t [@default Link_preview_style.default]
+    ; timeout : int [@default 5]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default =
+    { mode = Link_preview_mode.default; style = Link_preview_style.default; timeout = 5 }
+  ;;
+
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Hover previews of internal links, like Obsidian's Page preview: with
+    [enabled], each note also gets a small preview (its first heading and
+    paragraph) that pops up while a link to it is hovered. *)
+module Page_previews = struct
+  type t = { enabled : bool [@default false] }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { enabled = false }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Glossary_style_def = struct
+  type t =
+    | Abbr
+    | Link
+
+  let table = [ "abbr", Abbr, []; "link", Link, [] ]
+  let default = Abbr
+end
+
+module Glossary_style = Make_string_enum (Glossary_style_def)
+
+(** Glossary: with [note] set (a vault path, e.g. [glossary.md]), each heading
+    of that note is a term, defined by the paragraph under it. The first
+    occurrence of each term on every other page is marked: as an [<abbr>]
+    showing the definition on hover ([abbr], the default), or also as a link
+    to the term's heading ([link]). Notes with [glossary: false] in their
+    frontmatter are left alone. *)
+module Glossary = struct
+  type t =
+    { note : string [@default ""]
+    ; style : Glossary_style.t [@default Glossary_style.default]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { note = ""; style = Glossary_style.default }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Social cards: with [enabled], each note gets a card image (its title and
+    the site title on the theme's colors) under [_cards/], and its page [og:]
+    and [twitter:] meta tags pointing at it, so shared links show a preview.
+    A note's own [image] frontmatter field takes the card's place. Crawlers
+    only fetch absolute image URLs: [base_url] ([https://notes.example.com])
+    is put before the card's path.
+
+    Cards are drawn as SVG, which the major crawlers (Facebook, X, LinkedIn,
+    Slack) don't show. [png_command] turns them into PNG: a shell command
+    reading the SVG on stdin and printing the PNG, such as
+    [rsvg-convert -f png]. Without it the cards stay SVG. *)
+module Social_cards = struct
+  type t =
+    { enabled : bool [@default false]
+    ; base_url : string [@default ""]
+    ; png_command : string [@default ""]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { enabled = false; base_url = ""; png_command = "" }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+module Heading_case_def = struct
+  type t =
+    | Any
+    | Sentence
+    | Title
+
+  let table =
+    [ "any", Any, []
+    ; "sentence", Sentence, [ "sentence_case" ]
+    ; "title", Title, [ "title_case" ]
+    ]
+  ;;
+
+  let default = Any
+end
+
+module Heading_case = Make_string_enum (Heading_case_def)
+
+(** Heading rules checked by [oyster lint --headings]: [case] ([sentence]:
+    only the first word capitalized, [title]: every word but short ones like
+    "of" and "the"), with the words of [case_exceptions] (names, acronyms
+    written in lowercase) kept as they are; at most [max_length] characters
+    ([0] for no limit); no trailing character of [trailing_punctuation]; and,
+    with [unique], no title twice in a note. [oyster lint --fix] fixes case
+    and punctuation. *)
+module Heading_style = struct
+  type t =
+    { case : Heading_case.t [@default Heading_case.default]
+    ; case_exceptions : string list [@default []]
+    ; max_length : int [@default 0]
+    ; trailing_punctuation : string [@default ".,;:!"]
+    ; unique : bool [@default true]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default =
+    { case = Heading_case.default
+    ; case_exceptions = []
+    ; max_length = 0
+    ; trailing_punctuation = ".,;:!"
+    ; unique = true
+    }
+  ;;
+
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Whether notes are published by default: with [default], a note without a
+    [publish] frontmatter field is published; [publish: false] still keeps it
+    off the site. Mostly set per folder (see {!of_yaml}). With [drafts], every
+    note is published, [.draft.md] and [publish: false] ones included — for
+    previewing a site locally (see {!builtin_profiles}). *)
+module Publish = struct
+  type t =
+    { default : bool [@default false]
+    ; drafts : bool [@default false]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { default = false; drafts = false }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Note embeds ([![[note]]]). An embed nests at most [max_depth] deep; deeper
+    ones render as plain links. Embed cycles, deeper chains and embeds of notes
+    that aren't published are reported when building, as warnings or, with
+    [strict], as errors that stop the build. *)
+module Embeds = struct
+  type t =
+    { max_depth : int [@default 5]
+    ; strict : bool [@default false]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { max_depth = 5; strict = false }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** Periodic notes: the daily and weekly notes of a journal, told by their
+    path. [daily] and [weekly] are patterns matched against the end of a
+    note's path without [.md]: [YYYY] reads the year, [MM] the month, [DD]
+    the day and [ww] the ISO week (of [YYYY], then the ISO week-numbering
+    year); other characters match themselves. Only notes under [folder]
+    count ([""]: anywhere). With [pages], the site gets a calendar page per
+    month and per year of them under [page_folder]. *)
+module Journal = struct
+  type t =
+    { daily : string [@default "YYYY-MM-DD"]
+    ; weekly : string [@default "YYYY-Www"]
+    ; folder : string [@default ""]
+    ; pages : bool [@default false]
+    ; page_folder : string [@default "journal"]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default =
+    { daily = "YYYY-MM-DD"
+    ; weekly = "YYYY-Www"
+    ; folder = ""
+    ; pages = false
+    ; page_folder = "journal"
+    }
+  ;;
+
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** A page listing the [limit] most recently updated published notes, newest
+    first, at vault path [page] unless a note is already there. Off unless
+    [enabled], so a build adds no page the vault didn't ask for. *)
+module Recent = struct
+  type t =
+    { enabled : bool [@default false]
+    ; limit : int [@default 20]
+    ; page : string [@default "recent.md"]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { enabled = false; limit = 20; page = "recent.md" }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
+(** External commands a build runs at its stages, in order, each given a JSON
+    payload on stdin and able to print a changed note or page back: [after_parse]
+    per note, [before_write] per page and [after_build] once the site is
+    written. See {!Pipeline.Hook} for the payloads. Only the site config sets
+    them; see {!site_only_keys}. *)
+module Hooks = struct
+  type t =
+    { after_parse : string list [@default []]
+    ; before_write : string list [@default []]
+    ; after_build : string list [@default []]
+    }
+  [@@deriving yojson] [@@yojson.allow_extra_fields]
+
+  let default = { after_parse = []; before_write = []; after_build = [] }
+  let t_of_yojson j = or_default ~default t_of_yojson j
+end
+
 module Theme_def = struct
   type t =
     | Tokyonight
@@ -291,6 +988,33 @@ type t =
   ; home_graph_view : Home_graph_view.t [@default Home_graph_view.default]
   ; home : Home.t [@default Home.default]
   ; toc_order : Toc_order.t [@default Toc_order.default]
+  ; parse : Parse_options.t [@default Parse_options.default]
+  ; raw_html : Raw_html.t [@default Raw_html.default]
+  ; link_resolution : Link_resolution.t [@default Link_resolution.default]
+  ; external_links : External_links.t [@default External_links.default]
+  ; templates : Templates.t [@default Templates.default]
+  ; site : Site.t [@default Site.default]
+  ; url_style : Url_style.t [@default Url_style.default]
+  ; redirects : Redirects.t [@default Redirects.default]
+  ; print : Print.t [@default Print.default]
+  ; sidebar : Sidebar.t [@default Sidebar.default]
+  ; menu : Menu.t [@default Menu.default]
+  ; frontmatter : Frontmatter_display.t [@default Frontmatter_display.default]
+  ; share : Share.t [@default Share.default]
+  ; images : Images.t [@default Images.default]
+  ; tables : Tables.t [@default Tables.default]
+  ; link_previews : Link_previews.t [@default Link_previews.default]
+  ; page_previews : Page_previews.t [@default Page_previews.default]
+  ; social_cards : Social_cards.t [@default Social_cards.default]
+  ; glossary : Glossary.t [@default Glossary.default]
+  ; heading_style : Heading_style.t [@default Heading_style.default]
+  ; publish : Publish.t [@default Publish.default]
+  ; embeds : Embeds.t [@default Embeds.default]
+  ; journal : Journal.t [@default Journal.default]
+  ; recent : Recent.t [@default Recent.default]
+  ; hooks : Hooks.t [@default Hooks.default]
+  ; accent : string [@default ""]
+    (** CSS color replacing the theme's accent color; empty keeps it *)
   }
 [@@deriving yojson] [@@yojson.allow_extra_fields]
 
@@ -302,9 +1026,54 @@ let default : t =
   ; home_graph_view = Home_graph_view.default
   ; home = Home.default
   ; toc_order = Toc_order.default
+  ; parse = Parse_options.default
+  ; raw_html = Raw_html.default
+  ; link_resolution = Link_resolution.default
+  ; external_links = External_links.default
+  ; templates = Templates.default
+  ; site = Site.default
+  ; url_style = Url_style.default
+  ; redirects = Redirects.default
+  ; print = Print.default
+  ; sidebar = Sidebar.default
+  ; menu = Menu.default
+  ; frontmatter = Frontmatter_display.default
+  ; share = Share.default
+  ; images = Images.default
+  ; tables = Tables.default
+  ; link_previews = Link_previews.default
+  ; page_previews = Page_previews.default
+  ; social_cards = Social_cards.default
+  ; glossary = Glossary.default
+  ; heading_style = Heading_style.default
+  ; publish = Publish.default
+  ; embeds = Embeds.default
+  ; journal = Journal.default
+  ; recent = Recent.default
+  ; hooks = Hooks.default
+  ; accent = ""
   }
 ;;
 
+(** {1 Presets} *)
+
+(** [t] laid out as Obsidian Publish would publish the vault, for sites
+    migrating off it: Publish URLs and [permalink]s, and heading anchors
+    that keep the heading's case and punctuation. *)
+let obsidian_publish (t : t) : t =
+  { t with
+    url_style = Url_style_def.Obsidian_publish
+  ; parse = { t.parse with slug_style = Slug_style_def.Obsidian }
+  }
+;;
+
+(** Apply the preset named [name] ([obsidian_publish]) to [t]. *)
+let with_preset (t : t) (name : string) : t =
+  match name with
+  | "obsidian_publish" | "obsidian-publish" | "publish" -> obsidian_publish t
+  | _ -> failwith ("Invalid preset: " ^ name)
+;;
+
 let of_file (path : string) : t =
   let contents : string = In_channel.with_open_text path In_channel.input_all in
   or_default ~default t_of_yojson (J.from_string contents)
@@ -341,8 +1110,8 @@ let merge (base : t) (overlay : t) : t =
 
 (** {1 Per-file config from frontmatter} *)
 
-(** Convert a [Yaml.value] to [Yojson.Safe.t]. *)
-let rec yaml_to_yojson : Yaml.value -> J.t = function
+(** Convert a [Yaml_backend.value] to [Yojson.Safe.t]. *)
+let rec yaml_to_yojson : Yaml_backend.value -> J.t = function
   | `Null -> `Null
   | `Bool b -> `Bool b
   | `Float f -> `Float f
@@ -351,10 +1120,46 @@ let rec yaml_to_yojson : Yaml.value -> J.t = function
   | `O pairs -> `Assoc (List.map (fun (k, v) -> k, yaml_to_yojson v) pairs)
 ;;
 
-(** Extract per-file config from frontmatter YAML, merged over [default].
-    Merges the raw JSON onto [default]'s JSON {e before} parsing into {!t},
- *)
-let of_frontmatter ?(default = default) ?(config_key = "oyster") (fm : Yaml.value option)
+(** [overlay], a partial config such as a folder's [.oyster.toml], merged over
+    [default]: keys it sets win, the rest keep [default]'s values. Merges the
+    raw JSON onto [default]'s JSON {e before} parsing into {!t}. *)
+let of_yaml ?(default = default) (overlay : Yaml_backend.value) : t =
+  let merged = merge_json (yojson_of_t default) (yaml_to_yojson overlay) in
+  or_default ~default t_of_yojson merged
+;;
+
+(** Keys only the site config sets: a folder's [.oyster.toml] or a note's
+    [oyster] frontmatter field setting one is an error, as {!Hooks} run
+    commands for the whole build. *)
+let site_only_keys : string list = [ "hooks" ]
+
+(** The {!site_only_keys} that [overlay], a partial config, sets. *)
+let site_only_keys_in (overlay : Yaml_backend.value) : string list =
+  match overlay with
+  | `O pairs -> List.filter (fun k -> List.mem_assoc k pairs) site_only_keys
+  | _ -> []
+;;
+
+(** Like {!site_only_keys_in}, for the [config_key] field of frontmatter. *)
+let site_only_keys_in_frontmatter
+      ?(config_key = "oyster")
+      (fm : Yaml_backend.value option)
+  : string list
+  =
+  match fm with
+  | Some (`O pairs) ->
+    (match List.assoc_opt config_key pairs with
+     | Some overlay -> site_only_keys_in overlay
+     | None -> [])
+  | _ -> []
+;;
+
+(** Extract per-file config from frontmatter YAML, merged over [default];
+    see {!of_yaml}. *)
+let of_frontmatter
+      ?(default = default)
+      ?(config_key = "oyster")
+      (fm : Yaml_backend.value option)
   : t
   =
   match fm with
@@ -362,13 +1167,73 @@ let of_frontmatter ?(default = default) ?(config_key = "oyster") (fm : Yaml.valu
   | Some (`O pairs) ->
     (match List.assoc_opt config_key pairs with
      | None -> default
-     | Some ov_y ->
-       let base_j = yojson_of_t default in
-       let overlay_j = yaml_to_yojson ov_y in
-       or_default ~default t_of_yojson (merge_json base_j overlay_j))
+     | Some ov_y -> of_yaml ~default ov_y)
   | Some _ -> default
 ;;
 
+(** The settings of [t] that differ from [base], as a partial config in the
+    shape {!of_yaml} reads. [`Null] when none do. *)
+let diff_json ~(base : t) (t : t) : J.t =
+  let rec diff (b : J.t) (v : J.t) : J.t option =
+    match b, v with
+    | `Assoc bs, `Assoc vs ->
+      let fields =
+        List.filter_map
+          (fun (k, v) ->
+             match List.assoc_opt k bs with
+             | None -> Some (k, v)
+             | Some b -> Option.map (fun d -> k, d) (diff b v))
+          vs
+      in
+      if fields = [] then None else Some (`Assoc fields)
+    | b, v -> if b = v then None else Some v
+  in
+  Option.value (diff (yojson_of_t base) (yojson_of_t t)) ~default:`Null
+;;
+
+(** {1 Profiles}
+
+    A profile is a named partial config applied over the site config, so one
+    config file serves both local previews and deploys ([--profile dev] /
+    [--profile prod]). A config file defines them under its [profile] field:
+    [{ "profile": { "dev": { "publish": { "drafts": true } } } }]. *)
+
+(** Profiles available without a config file; a config file's profile of the
+    same name is applied on top. *)
+let builtin_profiles : (string * J.t) list =
+  [ "dev", `Assoc [ "publish", `Assoc [ "drafts", `Bool true ] ]; "prod", `Assoc [] ]
+;;
+
+(** The [profile] field of config JSON [j]: profile name to partial config. *)
+let profiles_of_json (j : J.t) : (string * J.t) list =
+  match j with
+  | `Assoc fields ->
+    (match List.assoc_opt "profile" fields with
+     | Some (`Assoc profiles) -> profiles
+     | _ -> [])
+  | _ -> []
+;;
+
+let profiles_of_file (path : string) : (string * J.t) list =
+  profiles_of_json (J.from_file path)
+;;
+
+(** Apply the profile named [name] to [t]: the built-in one, then the one in
+    [profiles] (see {!profiles_of_json}). An error if neither exists. *)
+let with_profile ?(profiles : (string * J.t) list = []) (t : t) (name : string)
+  : t Core.Or_error.t
+  =
+  match
+    List.filter_map
+      (fun profiles -> List.assoc_opt name profiles)
+      [ builtin_profiles; profiles ]
+  with
+  | [] -> Core.Or_error.error_string ("unknown profile: " ^ name)
+  | overlays ->
+    let j = List.fold_left merge_json (yojson_of_t t) overlays in
+    Ok (or_default ~default:t t_of_yojson j)
+;;
+
 (** {1 Tests} *)
 
 (* Wire-format contract with [static/graph_view/config.d.ts]
@@ -397,7 +1262,7 @@ let%expect_test "Home_graph_view wire format" =
 ;;
 
 let%expect_test "of_frontmatter overrides ext_struct" =
-  let fm : Yaml.value option =
+  let fm : Yaml_backend.value option =
     Some (`O [ "oyster", `O [ "ext_struct", `O [ "struct_style", `String "graph" ] ] ])
   in
   let merged = fm |> of_frontmatter |> fun fm -> merge default fm in
@@ -416,17 +1281,147 @@ let%expect_test "of_frontmatter overrides ext_struct" =
         "default_tag": "none"
       },
       "home": { "path": "home.md" },
-      "toc_order": [ "*" ]
+      "toc_order": [ "*" ],
+      "parse": {
+        "extensions": true,
+        "wikilink": true,
+        "block_id": true,
+        "div": true,
+        "attributes": true,
+        "callout": true,
+        "definition_list": true,
+        "smart_punctuation": true,
+        "coalesce_text": false,
+        "slug_style": "github"
+      },
+      "raw_html": { "mode": "allow", "extra_tags": [] },
+      "link_resolution": { "prefer": "first", "relative": false },
+      "external_links": { "new_tab": false, "rel": [] },
+      "templates": { "folder": "templates" },
+      "site": { "title": "" },
+      "url_style": "pretty",
+      "redirects": { "format": "html" },
+      "print": { "variants": false },
+      "sidebar": { "order": "toc_order" },
+      "menu": [],
+      "frontmatter": { "fields": [], "date_format": "%Y-%m-%d" },
+      "share": { "headings": false, "paragraphs": false },
+      "images": { "caption": "off", "lightbox": true },
+      "tables": {
+        "sticky_header": false,
+        "sortable": false,
+        "sortable_min_rows": 10
+      },
+      "link_previews": { "mode": "off", "style": "hover", "timeout": 5 },
+      "page_previews": { "enabled": false },
+      "social_cards": { "enabled": false, "base_url": "", "png_command": "" },
+      "glossary": { "note": "", "style": "abbr" },
+      "heading_style": {
+        "case": "any",
+        "case_exceptions": [],
+        "max_length": 0,
+        "trailing_punctuation": ".,;:!",
+        "unique": true
+      },
+      "publish": { "default": false, "drafts": false },
+      "embeds": { "max_depth": 5, "strict": false },
+      "journal": {
+        "daily": "YYYY-MM-DD",
+        "weekly": "YYYY-Www",
+        "folder": "",
+        "pages": false,
+        "page_folder": "journal"
+      },
+      "recent": { "enabled": false, "limit": 20, "page": "recent.md" },
+      "hooks": { "after_parse": [], "before_write": [], "after_build": [] },
+      "accent": ""
     }
     |}]
 ;;
 
 let%expect_test "of_frontmatter no oystermark key returns base" =
-  let fm : Yaml.value option = Some (`O [ "title", `String "Hello" ]) in
+  let fm : Yaml_backend.value option = Some (`O [ "title", `String "Hello" ]) in
   let merged = fm |> of_frontmatter |> fun fm -> merge default fm in
   assert (merged = default)
 ;;
 
+let%expect_test "of_yaml layers folder configs" =
+  let folder : Yaml_backend.value =
+    `O [ "publish", `O [ "default", `Bool true ]; "accent", `String "#e06c75" ]
+  in
+  let sub : Yaml_backend.value = `O [ "share", `O [ "headings", `Bool true ] ] in
+  let base = { default with url_style = Url_style_def.Obsidian_publish } in
+  let t = of_yaml ~default:(of_yaml ~default:base folder) sub in
+  diff_json ~base t |> J.pretty_to_string |> print_endline;
+  [%expect
+    {|
+    {
+      "share": { "headings": true },
+      "publish": { "default": true },
+      "accent": "#e06c75"
+    }
+    |}];
+  diff_json ~base base |> J.pretty_to_string |> print_endline;
+  [%expect {| null |}]
+;;
+
+let%expect_test "with_profile" =
+  let profiles =
+    profiles_of_json
+      (J.from_string
+         {|{ "profile": {
+               "dev": { "link_previews": { "mode": "offline" } },
+               "staging": { "url_style": "obsidian_publish" } } }|})
+  in
+  List.iter
+    (fun name ->
+       let t = Core.Or_error.ok_exn (with_profile ~profiles default name) in
+       Printf.printf "%s: %s\n" name (J.to_string (diff_json ~base:default t)))
+    [ "dev"; "prod"; "staging" ];
+  [%expect
+    {|
+    dev: {"link_previews":{"mode":"offline"},"publish":{"drafts":true}}
+    prod: null
+    staging: {"url_style":"obsidian_publish"}
+    |}];
+  (match with_profile default "test" with
+   | Ok _ -> ()
+   | Error e -> print_endline (Core.Error.to_string_hum e));
+  [%expect {| unknown profile: test |}]
+;;
+
+let%expect_test "Menu.of_flags" =
+  Menu.of_flags
+    [ "Guides=guides/index"
+    ; "Guides > Install = guides/Install"
+    ; "Source=https://github.com/hon-gyu/oyster"
+    ; "More>Changelog=changelog"
+    ]
+  |> Menu.yojson_of_t
+  |> J.pretty_to_string
+  |> print_endline;
+  [%expect
+    {|
+    [
+      {
+        "label": "Guides",
+        "target": "guides/index",
+        "children": [ { "label": "Install", "target": "guides/Install" } ]
+      },
+      {
+        "label": "Source",
+        "target": "https://github.com/hon-gyu/oyster",
+        "children": []
+      },
+      {
+        "label": "More",
+        "target": "",
+        "children": [ { "label": "Changelog", "target": "changelog" } ]
+      }
+    ]
+    |}]
+;;
+
 let%expect_test "Toc_order.rank_of" =
   let patterns = [ "intro"; "guides"; "*"; "changelog" ] in
   List.iter
@@ -477,7 +1472,60 @@ let%expect_test "Config default" =
         "default_tag": "none"
       },
       "home": { "path": "home.md" },
-      "toc_order": [ "*" ]
+      "toc_order": [ "*" ],
+      "parse": {
+        "extensions": true,
+        "wikilink": true,
+        "block_id": true,
+        "div": true,
+        "attributes": true,
+        "callout": true,
+        "definition_list": true,
+        "smart_punctuation": true,
+        "coalesce_text": false,
+        "slug_style": "github"
+      },
+      "raw_html": { "mode": "allow", "extra_tags": [] },
+      "link_resolution": { "prefer": "first", "relative": false },
+      "external_links": { "new_tab": false, "rel": [] },
+      "templates": { "folder": "templates" },
+      "site": { "title": "" },
+      "url_style": "pretty",
+      "redirects": { "format": "html" },
+      "print": { "variants": false },
+      "sidebar": { "order": "toc_order" },
+      "menu": [],
+      "frontmatter": { "fields": [], "date_format": "%Y-%m-%d" },
+      "share": { "headings": false, "paragraphs": false },
+      "images": { "caption": "off", "lightbox": true },
+      "tables": {
+        "sticky_header": false,
+        "sortable": false,
+        "sortable_min_rows": 10
+      },
+      "link_previews": { "mode": "off", "style": "hover", "timeout": 5 },
+      "page_previews": { "enabled": false },
+      "social_cards": { "enabled": false, "base_url": "", "png_command": "" },
+      "glossary": { "note": "", "style": "abbr" },
+      "heading_style": {
+        "case": "any",
+        "case_exceptions": [],
+        "max_length": 0,
+        "trailing_punctuation": ".,;:!",
+        "unique": true
+      },
+      "publish": { "default": false, "drafts": false },
+      "embeds": { "max_depth": 5, "strict": false },
+      "journal": {
+        "daily": "YYYY-MM-DD",
+        "weekly": "YYYY-Www",
+        "folder": "",
+        "pages": false,
+        "page_folder": "journal"
+      },
+      "recent": { "enabled": false, "limit": 20, "page": "recent.md" },
+      "hooks": { "after_parse": [], "before_write": [], "after_build": [] },
+      "accent": ""
     }
     |}]
 ;;
@@ -0,0 +1,212 @@
+(** A vault as chunks of text for retrieval pipelines, written as JSONL by
+    [oystermark export corpus]. A chunk is a section (its heading and own body)
+    or a whole note, split further to stay under a token budget.
+
+    Chunk ids are stable while the heading doesn't change: the note path, then
+    [#] and the heading's anchor for a section, then [:k] for the k-th part of
+    a split chunk. *)
+
+open Core
+
+type chunk_by =
+  | Section
+  | Note
+
+let chunk_by_of_string : string -> chunk_by option = function
+  | "section" -> Some Section
+  | "note" -> Some Note
+  | _ -> None
+;;
+
+type chunk =
+  { id : string
+  ; note : string
+  ; section_path : string
+  ; text : string
+  ; links : string list (** destinations as written, in order, without repeats *)
+  ; tags : string list
+  }
+
+let json_of_chunk (c : chunk) : Yojson.Safe.t =
+  let strings l = `List (List.map l ~f:(fun s -> `String s)) in
+  `Assoc
+    [ "id", `String c.id
+    ; "note", `String c.note
+    ; "section_path", `String c.section_path
+    ; "text", `String c.text
+    ; "links", strings c.links
+    ; "tags", strings c.tags
+    ]
+;;
+
+(** A rough token count, about four characters a token. *)
+let estimate_tokens (s : string) : int = (String.length s + 3) / 4
+
+(* [lines] grouped into parts of at most [max_tokens]; a longer line is broken
+   between words. *)
+let split ~(max_tokens : int) (lines : string list) : string list =
+  let pieces =
+    List.concat_map lines ~f:(fun line ->
+      if estimate_tokens line <= max_tokens
+      then [ line ]
+      else
+        String.split line ~on:' '
+        |> List.fold ~init:[] ~f:(fun acc word ->
+          match acc with
+          | cur :: rest when estimate_tokens (cur ^ " " ^ word) <= max_tokens ->
+            (cur ^ " " ^ word) :: rest
+          | _ -> word :: acc)
+        |> List.rev)
+  in
+  List.fold pieces ~init:[] ~f:(fun acc piece ->
+    match acc with
+    | cur :: rest when estimate_tokens (cur ^ "\n" ^ piece) <= max_tokens ->
+      (cur ^ "\n" ^ piece) :: rest
+    | _ -> piece :: acc)
+  |> List.rev
+;;
+
+(* Source lines [first] to [last], 1-based. *)
+let source_range (source_lines : string array) ((first, last) : int * int) : string list =
+  List.range first (last + 1)
+  |> List.filter_map ~f:(fun i ->
+    if i >= 1 && i <= Array.length source_lines then Some source_lines.(i - 1) else None)
+;;
+
+(** The chunks of the note at [path], parsed as [doc] from [source], less
+    encrypted blocks (see {!Parse.Encrypted}). *)
+let of_note
+      ~(chunk_by : chunk_by)
+      ?(max_tokens : int option)
+      (path : string)
+      (doc : Cmarkit.Doc.t)
+      (source : string)
+  : chunk list
+  =
+  let source = Parse.Encrypted.redact ~doc source in
+  let doc = Parse.Encrypted.strip doc in
+  let tags = Query.Search.note_tags (Query.Search.frontmatter_fields doc) in
+  let source_lines = Array.of_list (String.split_lines source) in
+  let root = Query.Section.of_doc doc in
+  let links = Query.Links.of_section root in
+  let links_of (keep : Query.Links.t -> bool) : string list =
+    List.filter_map links ~f:(fun (l : Query.Links.t) -> Option.some_if (keep l) l.dest)
+    |> List.stable_dedup ~compare:String.compare
+  in
+  let slugs = Hashtbl.create (module String) in
+  let sections : (string * string * string list * string list) list =
+    match chunk_by with
+    | Note ->
+      [ ( path
+        , "root"
+        , Option.value_map root.lines ~default:[] ~f:(source_range source_lines)
+        , links_of (Fn.const true) )
+      ]
+    | Section ->
+      List.filter_map (Query.Section.descendants root) ~f:(fun (s : Query.Section.t) ->
+        let section_path = Query.Section.path_to_string s.path in
+        let id =
+          match s.heading with
+          | None -> path
+          | Some _ -> path ^ "#" ^ Parse.Heading_slug.dedup_slug slugs s.title
+        in
+        let lines =
+          List.map (Option.to_list s.heading @ s.blocks) ~f:Query.Section.block_lines
+          |> Query.Section.span
+          |> Option.value_map ~default:[] ~f:(source_range source_lines)
+        in
+        if List.is_empty lines
+        then None
+        else
+          Some
+            ( id
+            , section_path
+            , lines
+            , links_of (fun (l : Query.Links.t) ->
+                String.equal l.section_path section_path) ))
+  in
+  List.concat_map sections ~f:(fun (id, section_path, lines, links) ->
+    let text = String.strip (String.concat ~sep:"\n" lines) in
+    let parts =
+      match max_tokens with
+      | Some max_tokens when estimate_tokens text > max_tokens ->
+        split ~max_tokens (String.split_lines text)
+      | _ -> [ text ]
+    in
+    List.mapi parts ~f:(fun i text ->
+      { id = (if List.length parts > 1 then sprintf "%s:%d" id (i + 1) else id)
+      ; note = path
+      ; section_path
+      ; text
+      ; links
+      ; tags
+      }))
+  |> List.filter ~f:(fun c -> not (String.is_empty c.text))
+;;
+
+(** The chunks of the notes of [vault], whose source [source_of] gives. Only
+    notes [publishes] accepts (all by default) are included, and no
+    {!Vault.is_unlisted} ones. *)
+let of_vault
+      ~(chunk_by : chunk_by)
+      ?(max_tokens : int option)
+      ?(publishes : string -> Cmarkit.Doc.t -> bool = fun _ _ -> true)
+      ~(source_of : string -> string)
+      (vault : Vault.t)
+  : chunk list
+  =
+  List.concat_map (Vault.listed_docs vault) ~f:(fun (path, doc) ->
+    if String.is_suffix path ~suffix:".md" && publishes path doc
+    then of_note ~chunk_by ?max_tokens path doc (source_of path)
+    else [])
+;;
+
+let%expect_test "of_note" =
+  let source =
+    "---\ntags: [ocaml]\n---\n# Setup\n\nSee [[Install]] and [[Install]].\n\n\
+     ## Linux\n\none two three four five six seven eight nine ten\n\n\
+     ## Setup\n\nAgain.\n"
+  in
+  let show ~chunk_by ?max_tokens () =
+    of_note ~chunk_by ?max_tokens "guide.md" (Parse.of_string source) source
+    |> List.iter ~f:(fun c -> print_endline (Yojson.Safe.to_string (json_of_chunk c)))
+  in
+  show ~chunk_by:Section ();
+  [%expect
+    {|
+    {"id":"guide.md#setup","note":"guide.md","section_path":"1","text":"# Setup\n\nSee [[Install]] and [[Install]].","links":["Install"],"tags":["ocaml"]}
+    {"id":"guide.md#linux","note":"guide.md","section_path":"1.1","text":"## Linux\n\none two three four five six seven eight nine ten","links":[],"tags":["ocaml"]}
+    {"id":"guide.md#setup-1","note":"guide.md","section_path":"1.2","text":"## Setup\n\nAgain.","links":[],"tags":["ocaml"]}
+    |}];
+  show ~chunk_by:Section ~max_tokens:8 ();
+  [%expect
+    {|
+    {"id":"guide.md#setup:1","note":"guide.md","section_path":"1","text":"# Setup\n","links":["Install"],"tags":["ocaml"]}
+    {"id":"guide.md#setup:2","note":"guide.md","section_path":"1","text":"See [[Install]] and [[Install]].","links":["Install"],"tags":["ocaml"]}
+    {"id":"guide.md#linux:1","note":"guide.md","section_path":"1.1","text":"## Linux\n","links":[],"tags":["ocaml"]}
+    {"id":"guide.md#linux:2","note":"guide.md","section_path":"1.1","text":"one two three four five six","links":[],"tags":["ocaml"]}
+    {"id":"guide.md#linux:3","note":"guide.md","section_path":"1.1","text":"seven eight nine ten","links":[],"tags":["ocaml"]}
+    {"id":"guide.md#setup-1","note":"guide.md","section_path":"1.2","text":"## Setup\n\nAgain.","links":[],"tags":["ocaml"]}
+    |}];
+  show ~chunk_by:Note ();
+  [%expect
+    {|
+    {"id":"guide.md","note":"guide.md","section_path":"root","text":"# Setup\n\nSee [[Install]] and [[Install]].\n\n## Linux\n\none two three four five six seven eight nine ten\n\n## Setup\n\nAgain.","links":["Install"],"tags":["ocaml"]}
+    |}]
+;;
+
+let%expect_test "of_vault" =
+  let files =
+    [ "a.md", "---\npublish: true\n---\nPublic.\n"
+    ; "b.md", "Not published.\n"
+    ; "c.md", "---\npublish: true\nunlisted: true\n---\nHidden.\n"
+    ]
+  in
+  of_vault
+    ~chunk_by:Note
+    ~publishes:(Pipeline.publishes ~config:Config.default)
+    ~source_of:(List.Assoc.find_exn files ~equal:String.equal)
+    (Vault.of_inmem_files files)
+  |> List.iter ~f:(fun c -> printf "%s: %s\n" c.id c.text);
+  [%expect {| a.md: Public. |}]
+;;
@@ -8,25 +8,55 @@ let layout : string = "layout"
 let sidebar : string = "sidebar"
 let sidebar_handle : string = "sidebar-handle"
 let sidebar_collapsed : string = "sidebar-collapsed"
+let sidebar_toggle : string = "sidebar-toggle"
+let sidebar_open : string = "sidebar-open"
+let active : string = "active"
 let page_title : string = "page-title"
+let skip_link : string = "skip-link"
+
+(* Tables *)
+let table_wrapper : string = "table-wrapper"
+let sticky_header : string = "sticky-header"
+let sortable : string = "sortable"
 
 (* Lightbox *)
 let lightbox : string = "lightbox"
 let lightbox_close : string = "lightbox-close"
+let lightbox_caption : string = "lightbox-caption"
 
 (* Content *)
 let frontmatter : string = "frontmatter"
+let chip : string = "chip"
 let callout : string = "callout"
 let callout_title : string = "callout-title"
 let callout_content : string = "callout-content"
 let embed : string = "embed"
+let transcript : string = "transcript"
+let transcript_time : string = "transcript-time"
 let unresolved : string = "unresolved"
 
 (* Components *)
 let backlinks : string = "backlinks"
 let backlink_context : string = "backlink-context"
 let breadcrumb : string = "breadcrumb"
+let menu : string = "menu"
+let menu_group : string = "menu-group"
 let sep : string = "sep"
+let heading_link : string = "heading-link"
+let fragment_link : string = "fragment-link"
+let link_preview : string = "link-preview"
+let link_preview_card : string = "link-preview-card"
+let link_preview_title : string = "link-preview-title"
+let link_preview_description : string = "link-preview-description"
+let preview_popover : string = "preview-popover"
+let page_preview : string = "page-preview"
+let page_preview_title : string = "page-preview-title"
+let glossary_term : string = "glossary-term"
+let calendar : string = "calendar"
+let kanban_board : string = "kanban-board"
+let kanban_lane : string = "kanban-lane"
+let kanban_lane_title : string = "kanban-lane-title"
+let kanban_count : string = "kanban-count"
 
 (* Struct (keyed blocks) — variant info lives in data-* attributes, not classes. *)
 let keyed : string = "keyed"
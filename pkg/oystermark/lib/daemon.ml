@@ -0,0 +1,401 @@
+(** The vault [oystermark daemon] keeps in memory, and the JSON-RPC 2.0
+    methods it answers, so editors and scripts don't rescan the vault per call.
+    Requests and responses are one JSON object per line. Methods, with their
+    [params]:
+    - [query {path, args}]: query expressions on a note, as
+      {!Query.Json_api.query_value}
+    - [links {path}]: the note's links, as {!Query.Links.t}
+    - [search {text, limit?, tag?, path?, section?}]: sections matching
+      [text], best first, at most [limit] (20), as [oystermark search] ranks
+      and filters them (see {!Query.Search})
+    - [backlinks {path}]: links into the note from other notes, as
+      {!Vault.Links.t}
+    - [render {path}]: the note's HTML body
+    - [reload]: rescan the vault
+
+    [query] and [render] use the note's own config, as the build does: the
+    site config, the [.oyster.toml] of its folders (see
+    {!Vault.Folder_config}), then its frontmatter.
+
+    Vault-wide methods keep what they take from each note in a {!Memo},
+    stamped with the note's hash: [search] its indexed sections and
+    [backlinks] its links. A [reload] reads and parses every note again, but
+    these are only recomputed for the notes that changed. *)
+
+open Core
+
+(** Results computed from one note, kept with the stamp of what they were
+    computed from: the note's hash and, for results that depend on how links
+    resolve, the vault's anchors (see {!anchors_of_index}). *)
+module Memo = struct
+  type stamp =
+    { hash : string
+    ; anchors : string option
+    }
+  [@@deriving equal]
+
+  type 'a t = (string, stamp * 'a) Hashtbl.t
+
+  let create () : 'a t = Hashtbl.create (module String)
+
+  (** The result under [key] if it was computed at [stamp], else [f ()], kept
+      for next time. *)
+  let find_or_add (memo : 'a t) ~(key : string) ~(stamp : stamp) ~(f : unit -> 'a) : 'a =
+    match Hashtbl.find memo key with
+    | Some (s, v) when equal_stamp s stamp -> v
+    | _ ->
+      let v = f () in
+      Hashtbl.set memo ~key ~data:(stamp, v);
+      v
+  ;;
+
+  (** Drop the results of notes whose hash is no longer among [hashes]. *)
+  let prune (memo : 'a t) ~(hashes : String.Set.t) : unit =
+    Hashtbl.filter_inplace memo ~f:(fun (s, _) -> Set.mem hashes s.hash)
+  ;;
+end
+
+type t =
+  { vault_root : string
+  ; config : Config.t
+  ; mutable folders : Vault.Folder_config.t
+  ; mutable sources : (string * string) list
+  (** markdown notes and their source, encrypted blocks blanked (see
+      {!Parse.Encrypted.redact}) *)
+  ; mutable vault : Vault.t
+  ; mutable hashes : string String.Map.t (** hash of each note's source *)
+  ; mutable anchors : string (** see {!anchors_of_index} *)
+  ; search_memo : Query.Search.doc list Memo.t (** each note's sections *)
+  ; links_memo : Vault.Links.t list Memo.t
+  }
+
+(** Digest of everything a link can resolve to: the vault's paths, headings,
+    block ids and attribute ids. A note's resolved links change only with its
+    source or with this. *)
+let anchors_of_index (index : Vault.Index.t) : string =
+  List.map index.files ~f:(fun (f : Vault.Index.file_entry) ->
+    String.concat
+      ~sep:"\x00"
+      (List.concat
+         [ [ f.rel_path ]
+         ; List.map f.headings ~f:(fun (h : Vault.Index.heading_entry) ->
+             h.slug ^ "\x01" ^ h.text)
+         ; List.map f.blocks ~f:(fun (b : Vault.Index.block_entry) -> b.id)
+         ; List.map f.attrs ~f:(fun (a : Vault.Index.attr_entry) -> a.id)
+         ]))
+  @ index.dirs
+  |> List.sort ~compare:String.compare
+  |> String.concat ~sep:"\n"
+  |> Vault.Manifest.hash_content
+;;
+
+(** Index and resolve the notes in [sources] (vault-relative paths to markdown)
+    among [other_files] and [dirs], as the build does. *)
+let build
+      ~(config : Config.t)
+      ~(vault_root : string)
+      ?(folders : Vault.Folder_config.t = Vault.Folder_config.empty)
+      ?(other_files : string list = [])
+      ?(dirs : string list = [])
+      (sources : (string * string) list)
+  : t
+  =
+  let hashes : string String.Map.t =
+    String.Map.of_alist_reduce
+      (List.map sources ~f:(fun (p, src) -> p, Vault.Manifest.hash_content src))
+      ~f:(fun first _ -> first)
+  in
+  (* Encrypted blocks are answered with, as in the site, neither their source
+     nor their content. *)
+  let sources, md_docs =
+    List.map sources ~f:(fun (p, src) ->
+      let doc = Parse.of_string ~config src in
+      (p, Parse.Encrypted.redact ~doc src), (p, Parse.Encrypted.strip doc))
+    |> List.unzip
+  in
+  let index = Vault.build_index ~md_docs ~other_files ~dirs in
+  let docs =
+    Vault.Resolve.resolve_docs
+      ~prefer:config.link_resolution.prefer
+      ~relative:config.link_resolution.relative
+      md_docs
+      index
+    |> Vault.Embed.expand_docs
+  in
+  { vault_root
+  ; config
+  ; folders
+  ; sources
+  ; vault = { vault_root; index; docs; vault_meta = Cmarkit.Meta.none }
+  ; hashes
+  ; anchors = anchors_of_index index
+  ; search_memo = Memo.create ()
+  ; links_memo = Memo.create ()
+  }
+;;
+
+(** Read the vault at [vault_root], with its folders' [.oyster.toml]. *)
+let load ?(config : Config.t = Config.default) (vault_root : string) : t =
+  let entries = Vault.list_entries vault_root in
+  let is_dir (p : string) : bool = String.is_suffix p ~suffix:"/" in
+  let is_md (p : string) : bool = String.is_suffix p ~suffix:".md" in
+  let read_file (p : string) : string =
+    In_channel.read_all (Filename.concat vault_root p)
+  in
+  let sources = List.filter entries ~f:is_md |> List.map ~f:(fun p -> p, read_file p) in
+  let folders =
+    Vault.Folder_config.load
+      ~read_file:(fun p -> Option.try_with (fun () -> read_file p))
+      (List.filter entries ~f:is_dir)
+  in
+  build
+    ~config
+    ~vault_root
+    ~folders
+    ~other_files:(List.filter entries ~f:(fun p -> not (is_dir p || is_md p)))
+    ~dirs:(List.filter entries ~f:is_dir)
+    sources
+;;
+
+(** Take the notes of [fresh], keeping what [t] read of the unchanged ones. *)
+let refresh (t : t) ~(fresh : t) : unit =
+  t.folders <- fresh.folders;
+  t.sources <- fresh.sources;
+  t.vault <- fresh.vault;
+  t.hashes <- fresh.hashes;
+  t.anchors <- fresh.anchors;
+  let hashes = String.Set.of_list (Map.data t.hashes) in
+  Memo.prune t.search_memo ~hashes;
+  Memo.prune t.links_memo ~hashes
+;;
+
+(** Rescan the vault, after its files changed. *)
+let reload (t : t) : unit = refresh t ~fresh:(load ~config:t.config t.vault_root)
+
+(** {1 JSON-RPC} *)
+
+(* A failed call: JSON-RPC error [code] and message. *)
+exception Rpc_error of int * string
+
+let invalid_params (msg : string) = raise (Rpc_error (-32602, msg))
+
+let param (params : (string * Yojson.Safe.t) list) (key : string)
+  : Yojson.Safe.t option
+  =
+  List.Assoc.find params ~equal:String.equal key
+;;
+
+let string_param params key : string =
+  match param params key with
+  | Some (`String s) -> s
+  | _ -> invalid_params (sprintf "%s: expected a string" key)
+;;
+
+let note (t : t) (path : string) : string * Cmarkit.Doc.t =
+  match
+    ( List.Assoc.find t.sources ~equal:String.equal path
+    , List.Assoc.find t.vault.docs ~equal:String.equal path )
+  with
+  | Some src, Some doc -> src, doc
+  | _ -> raise (Rpc_error (-32000, "no such note: " ^ path))
+;;
+
+let stamp ?(anchors : string option) (t : t) (path : string) : Memo.stamp =
+  { Memo.hash = Map.find_exn t.hashes path; anchors }
+;;
+
+(* The config of note [path], parsed as [doc]. *)
+let note_config (t : t) (path : string) (doc : Cmarkit.Doc.t) : Config.t =
+  Vault.Folder_config.note_config ~base:t.config t.folders path doc
+;;
+
+(** Sections matching [text], as {!Query.Search.search} ranks and filters them. *)
+let search
+      ?(tag : string option)
+      ?(path : string option)
+      ?(section : string option)
+      (t : t)
+      ~(text : string)
+      ~(limit : int)
+  : Yojson.Safe.t
+  =
+  let docs =
+    List.concat_map t.sources ~f:(fun (note, src) ->
+      Memo.find_or_add t.search_memo ~key:note ~stamp:(stamp t note) ~f:(fun () ->
+        Query.Search.docs_of_note note (Parse.of_string ~config:t.config src) src))
+  in
+  `List
+    (Query.Search.search ?tag ?path ?section ~limit (Query.Search.of_docs docs) text
+     |> List.map ~f:(fun (r : Query.Search.result) ->
+       `Assoc
+         [ "path", `String r.note
+         ; "section_path", `String r.section_path
+         ; "section", `String r.section_title
+         ; "line", Option.value_map r.line ~default:`Null ~f:(fun l -> `Int l)
+         ; "snippet", `String r.snippet
+         ]))
+;;
+
+(** Links into note [path] from other notes. *)
+let backlinks (t : t) ~(path : string) : Vault.Links.t list =
+  List.concat_map t.vault.docs ~f:(fun (source, doc) ->
+    Memo.find_or_add
+      t.links_memo
+      ~key:source
+      ~stamp:(stamp ~anchors:t.anchors t source)
+      ~f:(fun () -> Vault.Links.of_doc source doc))
+  |> Vault.Links.backlinks ~path
+;;
+
+(* Result of [meth] called with [params]. *)
+let call (t : t) (meth : string) (params : (string * Yojson.Safe.t) list)
+  : Yojson.Safe.t
+  =
+  match meth with
+  | "query" ->
+    let path = string_param params "path" in
+    let src, doc = note t path in
+    let args =
+      match param params "args" with
+      | Some (`List args) ->
+        List.map args ~f:(function
+          | `String a -> a
+          | _ -> invalid_params "args: expected strings")
+      | _ -> invalid_params "args: expected a list"
+    in
+    (match Query.Json_api.query_value ~config:(note_config t path doc) ~path args src with
+     | Ok v -> v
+     | Error msg -> raise (Rpc_error (-32000, msg)))
+  | "links" ->
+    let _, doc = note t (string_param params "path") in
+    `List
+      (Query.Links.of_section (Query.Section.of_doc doc)
+       |> List.map ~f:Query.Links.yojson_of_t)
+  | "search" ->
+    let limit =
+      match param params "limit" with
+      | Some (`Int n) -> n
+      | None -> 20
+      | Some _ -> invalid_params "limit: expected an integer"
+    in
+    let filter (key : string) : string option =
+      Option.map (param params key) ~f:(fun _ -> string_param params key)
+    in
+    search
+      ?tag:(filter "tag")
+      ?path:(filter "path")
+      ?section:(filter "section")
+      t
+      ~text:(string_param params "text")
+      ~limit
+  | "backlinks" ->
+    let path = string_param params "path" in
+    ignore (note t path : string * Cmarkit.Doc.t);
+    `List (List.map (backlinks t ~path) ~f:Vault.Links.yojson_of_t)
+  | "render" ->
+    let path = string_param params "path" in
+    let _, doc = note t path in
+    `String
+      (Component.Html.of_doc
+         ~backend_blocks:true
+         ~safe:false
+         ~config:(note_config t path doc)
+         doc)
+  | "reload" ->
+    reload t;
+    `Null
+  | meth -> raise (Rpc_error (-32601, "method not found: " ^ meth))
+;;
+
+(** The response line to the request line [line]. *)
+let handle (t : t) (line : string) : string =
+  let response ~(id : Yojson.Safe.t) (outcome : (string * Yojson.Safe.t) list) =
+    Yojson.Safe.to_string
+      (`Assoc (("jsonrpc", `String "2.0") :: ("id", id) :: outcome))
+  in
+  let error ~id (code : int) (msg : string) =
+    response ~id [ "error", `Assoc [ "code", `Int code; "message", `String msg ] ]
+  in
+  match Yojson.Safe.from_string line with
+  | exception Yojson.Json_error msg -> error ~id:`Null (-32700) msg
+  | `Assoc fields ->
+    let id = Option.value (param fields "id") ~default:`Null in
+    (match param fields "method", param fields "params" with
+     | Some (`String meth), ((None | Some (`Assoc _)) as params) ->
+       let params =
+         match params with
+         | Some (`Assoc params) -> params
+         | _ -> []
+       in
+       (match call t meth params with
+        | result -> response ~id [ "result", result ]
+        | exception Rpc_error (code, msg) -> error ~id code msg
+        | exception e -> error ~id (-32603) (Exn.to_string e))
+     | _ -> error ~id (-32600) "expected a method and object params")
+  | _ -> error ~id:`Null (-32600) "expected an object"
+;;
+
+let%expect_test "handle" =
+  let t =
+    build
+      ~config:Config.default
+      ~vault_root:"."
+      [ "a.md", "# Alpha\n\nSee [[b#Usage]].\n\n## Setup\n\nInstall it.\n"
+      ; "b.md", "# Beta\n\n## Usage\n\nRun it.\n"
+      ; "sealed.md", "Sealed:\n\n%%🔐β Do it. 🔐%%\n"
+      ]
+  in
+  List.iter
+    [ {|{"jsonrpc":"2.0","id":1,"method":"query","params":{"path":"a.md","args":["--titles"]}}|}
+    ; {|{"jsonrpc":"2.0","id":2,"method":"links","params":{"path":"a.md"}}|}
+    ; {|{"jsonrpc":"2.0","id":3,"method":"search","params":{"text":"it."}}|}
+    ; {|{"jsonrpc":"2.0","id":4,"method":"render","params":{"path":"c.md"}}|}
+    ; {|{"jsonrpc":"2.0","id":5,"method":"rename"}|}
+    ; {|[1]|}
+    ]
+    ~f:(fun line -> print_endline (handle t line));
+  [%expect
+    {|
+    {"jsonrpc":"2.0","id":1,"result":["Alpha","Setup"]}
+    {"jsonrpc":"2.0","id":2,"result":[{"text":"b#Usage","dest":"b#Usage","kind":"internal","style":"wikilink","section_path":"1"}]}
+    {"jsonrpc":"2.0","id":3,"result":[{"path":"a.md","section_path":"1.1","section":"Setup","line":7,"snippet":"Install it."},{"path":"b.md","section_path":"1.1","section":"Usage","line":5,"snippet":"Run it."}]}
+    {"jsonrpc":"2.0","id":4,"error":{"code":-32000,"message":"no such note: c.md"}}
+    {"jsonrpc":"2.0","id":5,"error":{"code":-32601,"message":"method not found: rename"}}
+    {"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"expected an object"}}
+    |}];
+  (* the memo holds each note's sections once, whatever was searched for *)
+  List.iter [ "alpha"; "RUN"; "setup" ] ~f:(fun text ->
+    ignore (search t ~text ~limit:50 : Yojson.Safe.t));
+  print_s [%sexp (Hashtbl.length t.search_memo : int)];
+  [%expect {| 3 |}]
+;;
+
+let%expect_test "memo survives refresh for unchanged notes" =
+  let build files = build ~config:Config.default ~vault_root:"." files in
+  let t =
+    build [ "a.md", "See [[b]].\n"; "b.md", "# Beta\n"; "c.md", "Also [[b]].\n" ]
+  in
+  let sources () =
+    List.map (backlinks t ~path:"b.md") ~f:(fun (l : Vault.Links.t) -> l.source)
+    |> String.concat ~sep:" "
+    |> print_endline
+  in
+  sources ();
+  [%expect {| a.md c.md |}];
+  let memoized (path : string) : Vault.Links.t list =
+    snd (Hashtbl.find_exn t.links_memo path)
+  in
+  let kept = List.map [ "a.md"; "c.md" ] ~f:memoized in
+  refresh
+    t
+    ~fresh:(build [ "a.md", "See [[b]].\n"; "b.md", "# Beta\n"; "c.md", "Not now.\n" ]);
+  sources ();
+  [%expect {| a.md |}];
+  List.iter2_exn [ "a.md"; "c.md" ] kept ~f:(fun p links ->
+    printf "%s reused: %b\n" p (phys_equal links (memoized p)));
+  [%expect
+    {|
+    a.md reused: true
+    c.md reused: false
+    |}]
+;;
@@ -0,0 +1,346 @@
+(** Semantic search for [oystermark search --semantic]: note sections embedded
+    as vectors by a {!provider}, kept in the SQLite database of {!Sql_export}
+    and ranked by cosine similarity to the embedded query. {!update} only
+    embeds sections whose text or model changed since the last run.
+
+    {v
+    embeddings (note, section, hash, model, vector)
+    v}
+
+    See {!schema}. *)
+
+open Core
+
+(** Something that turns texts into vectors, one per text. *)
+type provider =
+  { model : string (** stored with each vector; another model re-embeds everything *)
+  ; embed : string list -> float array list Or_error.t
+  }
+
+let vector_of_json : Yojson.Safe.t -> float array Or_error.t = function
+  | `List xs ->
+    Or_error.try_with (fun () ->
+      Array.of_list_map xs ~f:(function
+        | `Float f -> f
+        | `Int i -> Float.of_int i
+        | _ -> failwith "expected a number"))
+  | _ -> Or_error.error_string "expected a list of numbers"
+;;
+
+let json_of_vector (v : float array) : Yojson.Safe.t =
+  `List (Array.to_list v |> List.map ~f:(fun f -> `Float f))
+;;
+
+let parse_json ~(what : string) (s : string) : Yojson.Safe.t Or_error.t =
+  match Yojson.Safe.from_string s with
+  | j -> Ok j
+  | exception Yojson.Json_error msg -> Or_error.errorf "%s: %s" what msg
+;;
+
+(** A local model: [cmd] reads a JSON list of texts on stdin and prints a JSON
+    list of vectors, e.g. a script around candle or ONNX Runtime. *)
+let command ~(model : string) (cmd : string) : provider =
+  let embed (texts : string list) =
+    let open Or_error.Let_syntax in
+    let input = Yojson.Safe.to_string (`List (List.map texts ~f:(fun t -> `String t))) in
+    let%bind output = Code_executor.Process.run cmd ~input in
+    match%bind parse_json ~what:cmd output with
+    | `List vs -> Or_error.all (List.map vs ~f:vector_of_json)
+    | _ -> Or_error.errorf "%s: expected a JSON list of vectors" cmd
+  in
+  { model; embed }
+;;
+
+(** An HTTP endpoint of the OpenAI embeddings API ([POST {model, input}],
+    answered with [{data: [{embedding}]}]), as OpenAI, Ollama and llama.cpp's
+    server offer. Requests go through [curl]. *)
+let http ?(api_key : string option) ~(model : string) (url : string) : provider =
+  let embed (texts : string list) =
+    let open Or_error.Let_syntax in
+    let body =
+      `Assoc
+        [ "model", `String model
+        ; "input", `List (List.map texts ~f:(fun t -> `String t))
+        ]
+    in
+    (* The key goes in a header file so it doesn't show in the process list. *)
+    let headers = Filename_unix.temp_file "oyster-embed" ".headers" in
+    let auth =
+      Option.value_map api_key ~default:"" ~f:(sprintf "Authorization: Bearer %s\n")
+    in
+    Out_channel.write_all headers ~data:("Content-Type: application/json\n" ^ auth);
+    let output =
+      Code_executor.Process.run
+        (sprintf
+           "curl --silent --show-error --fail -H @%s --data-binary @- %s"
+           (Filename.quote headers)
+           (Filename.quote url))
+        ~input:(Yojson.Safe.to_string body)
+    in
+    Core_unix.unlink headers;
+    let%bind output = output in
+    let%bind json = parse_json ~what:url output in
+    match Yojson.Safe.Util.member "data" json with
+    | `List items ->
+      Or_error.all
+        (List.map items ~f:(fun item ->
+           vector_of_json (Yojson.Safe.Util.member "embedding" item)))
+    | _ -> Or_error.errorf "%s: expected a data list in the response" url
+    | exception Yojson.Safe.Util.Type_error (msg, _) -> Or_error.errorf "%s: %s" url msg
+  in
+  { model; embed }
+;;
+
+(** {1 Chunks} *)
+
+(* Map key of a note's section. *)
+let key (note : string) (section : string) : string = note ^ "\n" ^ section
+
+(** A section as embedded: its heading and own body. *)
+type chunk =
+  { doc : Query.Search.doc
+  ; text : string
+  ; hash : string
+  }
+
+let chunks (index : Query.Search.t) : chunk list =
+  List.filter_map index.docs ~f:(fun (d : Query.Search.doc) ->
+    let text =
+      String.strip (String.concat ~sep:"\n" (d.section_title :: List.map d.lines ~f:snd))
+    in
+    if String.is_empty text
+    then None
+    else Some { doc = d; text; hash = Md5.to_hex (Md5.digest_string text) })
+;;
+
+let cosine (a : float array) (b : float array) : float =
+  if Array.length a <> Array.length b
+  then 0.
+  else (
+    let dot x y = Array.fold2_exn x y ~init:0. ~f:(fun acc p q -> acc +. (p *. q)) in
+    let norm = Float.sqrt (dot a a) *. Float.sqrt (dot b b) in
+    if Float.(norm = 0.) then 0. else dot a b /. norm)
+;;
+
+(** The sections of [index] with a vector in [vectors] (keyed by note and
+    section path) that pass {!Query.Search.keep}, nearest [query] first, at most
+    [limit]. The snippet is the section's first line. *)
+let rank
+      ?(tag : string option)
+      ?(path : string option)
+      ?(section : string option)
+      ?(limit : int = 20)
+      ~(vectors : string * string -> float array option)
+      (index : Query.Search.t)
+      (query : float array)
+  : Query.Search.result list
+  =
+  List.filter_map index.docs ~f:(fun (d : Query.Search.doc) ->
+    if not (Query.Search.keep ?tag ?path ?section d)
+    then None
+    else
+      Option.map (vectors (d.note, d.section_path)) ~f:(fun v ->
+        let first =
+          List.find d.lines ~f:(fun (_, l) -> not (String.is_empty (String.strip l)))
+        in
+        ({ note = d.note
+         ; section_path = d.section_path
+         ; section_title = d.section_title
+         ; line = Option.map first ~f:fst
+         ; snippet = Option.value_map first ~default:"" ~f:(fun (_, l) -> String.strip l)
+         ; score = cosine query v
+         }
+         : Query.Search.result)))
+  |> List.stable_sort ~compare:(fun (a : Query.Search.result) b ->
+    Float.descending a.score b.score)
+  |> Fn.flip List.take limit
+;;
+
+(** {1 Storage} *)
+
+let schema : string =
+  {|-- One vector per embedded section; kept by `oystermark index --sqlite`.
+CREATE TABLE IF NOT EXISTS embeddings (
+  note    TEXT NOT NULL,  -- vault-relative path
+  section TEXT NOT NULL,  -- section path, as in sections.path
+  hash    TEXT NOT NULL,  -- MD5 of the embedded text
+  model   TEXT NOT NULL,  -- provider model that made the vector
+  vector  TEXT NOT NULL,  -- JSON array of floats
+  PRIMARY KEY (note, section)
+);
+|}
+;;
+
+(* Texts sent to the provider per request. *)
+let batch_size = 64
+
+let string_field (row : (string * Yojson.Safe.t) list) (key : string) : string =
+  match List.Assoc.find row ~equal:String.equal key with
+  | Some (`String s) -> s
+  | _ -> ""
+;;
+
+(** Bring the vectors in [db] up to date with [index]: embed sections that are
+    new or whose text or model changed, and drop those of removed sections.
+    The number of sections embedded. *)
+let update ~(provider : provider) ~(db : string) (index : Query.Search.t) : int Or_error.t
+  =
+  let open Or_error.Let_syntax in
+  let%bind () = Sql_export.write ~db schema in
+  let%bind rows =
+    Sql_export.select ~db "SELECT note, section, hash, model FROM embeddings"
+  in
+  let stored =
+    List.map rows ~f:(fun row ->
+      let note = string_field row "note"
+      and section = string_field row "section" in
+      let hash = string_field row "hash"
+      and model = string_field row "model" in
+      key note section, (note, section, hash, model))
+    |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+  in
+  let chunks = chunks index in
+  let stale =
+    List.filter chunks ~f:(fun c ->
+      match Map.find stored (key c.doc.note c.doc.section_path) with
+      | Some (_, _, hash, model) ->
+        not (String.equal hash c.hash && String.equal model provider.model)
+      | None -> true)
+  in
+  let%bind vectors =
+    List.chunks_of stale ~length:batch_size
+    |> List.fold_result ~init:[] ~f:(fun acc batch ->
+      let%map vs = provider.embed (List.map batch ~f:(fun c -> c.text)) in
+      List.rev_append vs acc)
+    >>| List.rev
+  in
+  let%bind embedded =
+    match List.zip stale vectors with
+    | Ok pairs -> Ok pairs
+    | Unequal_lengths ->
+      Or_error.errorf
+        "%s: got %d vectors for %d texts"
+        provider.model
+        (List.length vectors)
+        (List.length stale)
+  in
+  let live =
+    String.Set.of_list (List.map chunks ~f:(fun c -> key c.doc.note c.doc.section_path))
+  in
+  let q = Sql_export.quote in
+  let deletes =
+    Map.data stored
+    |> List.filter ~f:(fun (note, section, _, _) -> not (Set.mem live (key note section)))
+    |> List.map ~f:(fun (note, section, _, _) ->
+      sprintf
+        "DELETE FROM embeddings WHERE note = %s AND section = %s;\n"
+        (q note)
+        (q section))
+  in
+  let inserts =
+    List.map embedded ~f:(fun (c, v) ->
+      sprintf
+        "INSERT OR REPLACE INTO embeddings VALUES (%s, %s, %s, %s, %s);\n"
+        (q c.doc.note)
+        (q c.doc.section_path)
+        (q c.hash)
+        (q provider.model)
+        (q (Yojson.Safe.to_string (json_of_vector v))))
+  in
+  let%map () =
+    Sql_export.write
+      ~db
+      (String.concat ([ "BEGIN;\n" ] @ deletes @ inserts @ [ "COMMIT;\n" ]))
+  in
+  List.length embedded
+;;
+
+(** {!rank} the sections of [index] by their vectors in [db] against [query]. *)
+let search
+      ?(tag : string option)
+      ?(path : string option)
+      ?(section : string option)
+      ?(limit : int option)
+      ~(provider : provider)
+      ~(db : string)
+      (index : Query.Search.t)
+      (query : string)
+  : Query.Search.result list Or_error.t
+  =
+  let open Or_error.Let_syntax in
+  let%bind rows =
+    Sql_export.select
+      ~db
+      (sprintf
+         "SELECT note, section, vector FROM embeddings WHERE model = %s"
+         (Sql_export.quote provider.model))
+  in
+  let%bind vectors =
+    List.map rows ~f:(fun row ->
+      let%bind json = parse_json ~what:db (string_field row "vector") in
+      let%map v = vector_of_json json in
+      key (string_field row "note") (string_field row "section"), v)
+    |> Or_error.all
+    >>| String.Map.of_alist_reduce ~f:(fun a _ -> a)
+  in
+  let%bind query =
+    match%bind provider.embed [ query ] with
+    | [ v ] -> Ok v
+    | _ -> Or_error.errorf "%s: expected one vector for the query" provider.model
+  in
+  let vectors (note, section) = Map.find vectors (key note section) in
+  Ok (rank ?tag ?path ?section ?limit ~vectors index query)
+;;
+
+let%expect_test "rank" =
+  let notes =
+    [ "pearl.md", "# Pearl\n\nGrown inside an oyster.\n\n## Shell\n\nThe outer layer.\n"
+    ; "tools.md", "# Install\n\nopam install oyster\n"
+    ]
+    |> List.map ~f:(fun (p, src) -> p, Parse.of_string src, src)
+  in
+  let index = Query.Search.build notes in
+  (* A toy model: how often each of a few words occurs. *)
+  let vocabulary = [ "oyster"; "shell"; "layer"; "install" ] in
+  let provider =
+    { model = "toy"
+    ; embed =
+        (fun texts ->
+          Ok
+            (List.map texts ~f:(fun text ->
+               let words = Query.Search.tokens text in
+               Array.of_list_map vocabulary ~f:(fun w ->
+                 Float.of_int (List.count words ~f:(String.equal w))))))
+    }
+  in
+  let chunks = chunks index in
+  List.iter chunks ~f:(fun c -> printf "%s %s %S\n" c.doc.note c.doc.section_path c.text);
+  [%expect
+    {|
+    pearl.md 1 "Pearl\nGrown inside an oyster."
+    pearl.md 1.1 "Shell\nThe outer layer."
+    tools.md 1 "Install\nopam install oyster"
+    |}];
+  let vectors =
+    List.zip_exn chunks (ok_exn (provider.embed (List.map chunks ~f:(fun c -> c.text))))
+    |> List.map ~f:(fun (c, v) -> (c.doc.note, c.doc.section_path), v)
+  in
+  let show ?path query =
+    rank
+      ?path
+      ~vectors:(List.Assoc.find vectors ~equal:[%equal: string * string])
+      index
+      (List.hd_exn (ok_exn (provider.embed [ query ])))
+    |> List.iter ~f:(fun (r : Query.Search.result) ->
+      printf "%.2f %s %s %s\n" r.score r.note r.section_path r.snippet)
+  in
+  show "the shell's layer";
+  [%expect
+    {|
+    1.00 pearl.md 1.1 The outer layer.
+    0.00 pearl.md 1 Grown inside an oyster.
+    0.00 tools.md 1 opam install oyster
+    |}];
+  show ~path:"tools" "install";
+  [%expect {| 0.89 tools.md 1 opam install oyster |}]
+;;
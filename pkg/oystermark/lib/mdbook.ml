@@ -0,0 +1,521 @@
+(** A folder of a vault as an mdBook, written by [oystermark export mdbook]: a
+    [book.toml], and under [src/] a [SUMMARY.md] and a chapter per note.
+
+    The book follows the folder tree: a folder is a chapter, its [index.md]
+    the chapter's page (a draft chapter without one), and the notes in it its
+    sub-chapters, in sidebar order; the top folder's [index.md] is the
+    introduction. With an index note, the book follows that note's lists
+    instead: an item linking a note is a chapter, items nested under it are
+    its sub-chapters, an item without a link is a draft chapter and a heading
+    starts a part.
+
+    Chapters keep their path below the folder; notes and files from outside
+    it go under [_vault/]. Wikilinks become relative Markdown links to a
+    chapter (or a heading in it) or to a copied file, an image for an embed;
+    links to notes outside the book and unresolved links become their text.
+    Frontmatter is dropped. *)
+
+open Core
+
+type t =
+  { files : (string * string) list (** book-relative path and contents *)
+  ; assets : (string * string) list
+    (** book-relative path and the vault-relative file to copy there *)
+  }
+
+type entry =
+  | Part of string
+  | Chapter of
+      { title : string
+      ; note : string option (** [None] for a draft chapter *)
+      ; children : entry list
+      }
+
+(** Book-relative path, below [src/], of vault file [path]. *)
+let book_path ~(folder : string) (path : string) : string =
+  if String.is_empty folder
+  then path
+  else (
+    match String.chop_prefix path ~prefix:(folder ^ "/") with
+    | Some path -> path
+    | None -> "_vault/" ^ path)
+;;
+
+(* The anchor of a heading titled [heading] in the book: mdBook derives its
+   own, GitHub-style, whatever slug style the vault's site uses. *)
+let mdbook_anchor (heading : string) : string =
+  Parse.Heading_slug.slugify ~style:Parse.Heading_slug.Github heading
+;;
+
+let note_title (path : string) (doc : Cmarkit.Doc.t) : string =
+  match Parse.Frontmatter.field doc "title" with
+  | Some (Parse.Value.String title) when not (String.is_empty (String.strip title)) ->
+    String.strip title
+  | _ -> Component.title_of_path path
+;;
+
+(* Vault path of the note a link resolves to. *)
+let linked_note (meta : Cmarkit.Meta.t) : string option =
+  match Cmarkit.Meta.find Vault.Resolve.resolved_key meta with
+  | Some
+      ( Vault.Resolve.Note { path }
+      | Vault.Resolve.Heading { path; _ }
+      | Vault.Resolve.Block { path; _ }
+      | Vault.Resolve.Attr { path; _ } )
+    when String.is_suffix path ~suffix:".md" -> Some path
+  | _ -> None
+;;
+
+(** The book's entries from the folder tree of [notes], vault paths of the
+    notes below [folder]; also the introduction, the folder's [index.md]. *)
+let entries_of_tree
+      ~(compare_path : string -> string -> int)
+      ~(title_of : string -> string)
+      ~(folder : string)
+      (notes : string list)
+  : string option * entry list
+  =
+  let prefix = if String.is_empty folder then "" else folder ^ "/" in
+  let rec of_toc ~(dir : string) (toc : Component.toc_entry list) : entry list =
+    List.filter_map toc ~f:(function
+      | Component.Leaf { name = "index.md"; _ } -> None
+      | Component.Leaf { path; _ } ->
+        let note = prefix ^ path in
+        Some (Chapter { title = title_of note; note = Some note; children = [] })
+      | Component.Dir { name; children } ->
+        let dir = dir ^ name ^ "/" in
+        let index = prefix ^ dir ^ "index.md" in
+        let has_index =
+          List.exists children ~f:(function
+            | Component.Leaf { name = "index.md"; _ } -> true
+            | _ -> false)
+        in
+        Some
+          (Chapter
+             { title = (if has_index then title_of index else name)
+             ; note = Option.some_if has_index index
+             ; children = of_toc ~dir children
+             }))
+  in
+  let relative = List.map notes ~f:(String.chop_prefix_if_exists ~prefix) in
+  let intro = Option.some_if (List.mem relative "index.md" ~equal:String.equal) in
+  ( intro (prefix ^ "index.md")
+  , of_toc
+      ~dir:""
+      (Component.build_toc_entries ~compare_path ~path_prefix:prefix relative) )
+;;
+
+(** The book's entries from the lists and headings of index note [doc]. *)
+let entries_of_index ~(title_of : string -> string) (doc : Cmarkit.Doc.t) : entry list =
+  (* The text and note of the first link to a note in [b]. *)
+  let first_link (b : Cmarkit.Block.t) : (string * string) option =
+    let folder =
+      Cmarkit.Folder.make
+        ~inline_ext_default:(fun _f acc (i : Cmarkit.Inline.t) ->
+          match acc, i with
+          | None, Cmarkit.Inline.Ext_wikilink (w, meta) ->
+            Option.map (linked_note meta) ~f:(fun note ->
+              ( Option.value
+                  (Cmarkit.Inline.Wikilink.display w)
+                  ~default:(Vault.Link_convert.wikilink_default_display w)
+              , note ))
+          | _ -> acc)
+        ~block_ext_default:(fun _f acc _b -> acc)
+        ~inline:(fun _f acc (i : Cmarkit.Inline.t) ->
+          match acc, i with
+          | None, Cmarkit.Inline.Link (l, meta) ->
+            Cmarkit.Folder.ret
+              (Option.map (linked_note meta) ~f:(fun note ->
+                 ( Parse.Heading_slug.inline_to_plain_text (Cmarkit.Inline.Link.text l)
+                 , note )))
+          | Some _, _ -> Cmarkit.Folder.ret acc
+          | _ -> Cmarkit.Folder.default)
+        ()
+    in
+    Cmarkit.Folder.fold_block folder None b
+  in
+  let rec of_blocks (blocks : Cmarkit.Block.t list) : entry list =
+    List.concat_map blocks ~f:(function
+      | Cmarkit.Block.Heading (h, _) ->
+        [ Part
+            (Parse.Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h))
+        ]
+      | Cmarkit.Block.List (l, _) ->
+        List.map (Cmarkit.Block.List'.items l) ~f:(fun (item, _) ->
+          let blocks =
+            match Cmarkit.Block.List_item.block item with
+            | Cmarkit.Block.Blocks (bs, _) -> bs
+            | b -> [ b ]
+          in
+          let label, nested =
+            List.partition_tf blocks ~f:(function
+              | Cmarkit.Block.List _ -> false
+              | _ -> true)
+          in
+          let link = List.find_map label ~f:first_link in
+          let title =
+            match link with
+            | Some (text, note) -> if String.is_empty text then title_of note else text
+            | None ->
+              List.filter_map label ~f:(function
+                | Cmarkit.Block.Paragraph (p, _) ->
+                  Some
+                    (Parse.Heading_slug.inline_to_plain_text
+                       (Cmarkit.Block.Paragraph.inline p))
+                | _ -> None)
+              |> String.concat ~sep:" "
+          in
+          Chapter
+            { title = String.strip title
+            ; note = Option.map link ~f:snd
+            ; children = of_blocks nested
+            })
+      | Cmarkit.Block.Blocks (bs, _) -> of_blocks bs
+      | _ -> [])
+  in
+  of_blocks [ Cmarkit.Doc.block doc ]
+;;
+
+(* Notes of [entries], in order. *)
+let rec notes_of (entries : entry list) : string list =
+  List.concat_map entries ~f:(function
+    | Part _ -> []
+    | Chapter { note; children; _ } -> Option.to_list note @ notes_of children)
+;;
+
+let summary
+      ~(folder : string)
+      ~(title_of : string -> string)
+      ~(intro : string option)
+      (entries : entry list)
+  : string
+  =
+  let link (title : string) (dest : string) : string =
+    sprintf "[%s](%s)" (Vault.Link_convert.escape_link_text title) dest
+  in
+  let dest (note : string) : string =
+    Vault.Link_rewrite.percent_encode (book_path ~folder note)
+  in
+  let rec lines ~(depth : int) (entries : entry list) : string list =
+    List.concat_map entries ~f:(function
+      | Part title -> [ ""; "# " ^ title; "" ]
+      | Chapter { title; note; children } ->
+        (String.make (2 * depth) ' '
+         ^ "- "
+         ^ link title (Option.value_map note ~default:"" ~f:dest))
+        :: lines ~depth:(depth + 1) children)
+  in
+  let lines =
+    [ "# Summary"; "" ]
+    @ Option.value_map intro ~default:[] ~f:(fun note ->
+      [ link (title_of note) (dest note); "" ])
+    @ lines ~depth:0 entries
+    (* parts bring their own blank lines *)
+    |> List.remove_consecutive_duplicates ~equal:(fun a b ->
+      String.is_empty a && String.is_empty b)
+  in
+  String.concat ~sep:"\n" lines ^ "\n"
+;;
+
+(** The chapter of note [path], with source [source] and resolved [doc]: its
+    wikilinks rewritten (see the top of this module), its frontmatter dropped
+    and its encrypted blocks emptied (see {!Parse.Encrypted}). Also the vault
+    files it links to, less encrypted ones. *)
+let chapter
+      ~(folder : string)
+      ~(in_book : string -> bool)
+      (path : string)
+      (doc : Cmarkit.Doc.t)
+      (source : string)
+  : string * string list
+  =
+  let from = book_path ~folder path in
+  let to_book (target : string) : string =
+    Vault.Resolve.relative_link ~curr_file:from (book_path ~folder target)
+  in
+  let folder_ =
+    Cmarkit.Folder.make
+      ~inline_ext_default:(fun _f acc (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Ext_wikilink (w, meta) ->
+          let embed = Cmarkit.Inline.Wikilink.embed w in
+          (match
+             Vault.Link_convert.range ~src:source ~embed (Cmarkit.Meta.textloc meta)
+           with
+           | None -> acc
+           | Some (first, stop) ->
+             let display =
+               Option.value
+                 (Cmarkit.Inline.Wikilink.display w)
+                 ~default:(Vault.Link_convert.wikilink_default_display w)
+             in
+             let md ?(image = false) (dest : string) : string =
+               sprintf
+                 "%s[%s](%s)"
+                 (if image then "!" else "")
+                 (Vault.Link_convert.escape_link_text display)
+                 (Vault.Link_rewrite.percent_encode dest)
+             in
+             let text, file =
+               match Cmarkit.Meta.find Vault.Resolve.resolved_key meta with
+               | Some (Vault.Resolve.File { path })
+                 when not (Parse.Encrypted.is_encrypted_file path) ->
+                 let image =
+                   embed
+                   &&
+                   match Component.Html.media_type_of_href path with
+                   | `Image -> true
+                   | _ -> false
+                 in
+                 md ~image (to_book path), Some path
+               | Some (Vault.Resolve.Heading { path; heading; _ }) when in_book path ->
+                 md (to_book path ^ "#" ^ mdbook_anchor heading), None
+               | Some (Vault.Resolve.Curr_heading { heading; _ }) ->
+                 md ("#" ^ mdbook_anchor heading), None
+               | Some
+                   ( Vault.Resolve.Note { path }
+                   | Vault.Resolve.Block { path; _ }
+                   | Vault.Resolve.Attr { path; _ } )
+                 when in_book path -> md (to_book path), None
+               | _ -> display, None
+             in
+             (first, stop, text) :: fst acc, Option.to_list file @ snd acc)
+        | _ -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  let edits, files =
+    Cmarkit.Folder.fold_doc folder_ ([], []) (Parse.Encrypted.strip doc)
+  in
+  (* Rewritten links stay on their lines, so redacting after splicing blanks
+     the same lines as before *)
+  let _, body =
+    Parse.Frontmatter.of_string
+      (Parse.Encrypted.redact ~doc (Vault.Link_rewrite.splice source edits))
+  in
+  (if String.is_suffix body ~suffix:"\n" then body else body ^ "\n"), List.rev files
+;;
+
+(* [entries] without notes [publishes] rejects: a chapter of such a note
+   becomes a draft chapter if sub-chapters remain, else goes, as does a draft
+   chapter all of whose sub-chapters went. *)
+let rec published ~(publishes : string -> bool) (entries : entry list) : entry list =
+  List.filter_map entries ~f:(function
+    | Part _ as part -> Some part
+    | Chapter { title; note = written; children = all } ->
+      let note = Option.filter written ~f:publishes in
+      let children = published ~publishes all in
+      Option.some_if
+        (Option.is_some note
+         || (not (List.is_empty children))
+         || (Option.is_none written && List.is_empty all))
+        (Chapter { title; note; children }))
+;;
+
+(** The mdBook of [folder] ([""] for the whole vault) of [vault], resolved
+    with locations, whose sources [source_of] gives. With [index], a note's
+    vault path, the book follows that note's lists. Only notes [publishes]
+    accepts (all by default) are chapters; links to the others become their
+    text. An error if [index] is not a note of [vault]. *)
+let of_vault
+      ?(compare_path : string -> string -> int = String.compare)
+      ?(publishes : string -> Cmarkit.Doc.t -> bool = fun _ _ -> true)
+      ?(index : string option)
+      ~(title : string)
+      ~(folder : string)
+      ~(source_of : string -> string)
+      (vault : Vault.t)
+  : t Or_error.t
+  =
+  let folder = String.strip folder ~drop:(Char.equal '/') in
+  let docs = String.Map.of_alist_reduce vault.docs ~f:(fun first _ -> first) in
+  let title_of (note : string) : string =
+    match Map.find docs note with
+    | Some doc -> note_title note doc
+    | None -> Component.title_of_path note
+  in
+  let is_published (note : string) : bool =
+    match Map.find docs note with
+    | Some doc -> publishes note doc
+    | None -> false
+  in
+  let%map.Or_error intro, entries =
+    match index with
+    | Some index ->
+      (match Map.find docs index with
+       | Some doc -> Ok (None, entries_of_index ~title_of doc)
+       | None -> Or_error.errorf "index note %s not found" index)
+    | None ->
+      let prefix = if String.is_empty folder then "" else folder ^ "/" in
+      Ok
+        (entries_of_tree
+           ~compare_path
+           ~title_of
+           ~folder
+           (List.filter_map vault.docs ~f:(fun (p, _) ->
+              Option.some_if
+                (String.is_suffix p ~suffix:".md" && String.is_prefix p ~prefix)
+                p)))
+  in
+  let intro = Option.filter intro ~f:is_published in
+  let entries = published ~publishes:is_published entries in
+  let notes =
+    List.stable_dedup (Option.to_list intro @ notes_of entries) ~compare:String.compare
+  in
+  let in_book = String.Set.mem (String.Set.of_list notes) in
+  let chapters =
+    List.filter_map notes ~f:(fun note ->
+      Option.map (Map.find docs note) ~f:(fun doc ->
+        let body, files = chapter ~folder ~in_book note doc (source_of note) in
+        ("src/" ^ book_path ~folder note, body), files))
+  in
+  let book_toml =
+    sprintf
+      "[book]\ntitle = \"%s\"\nsrc = \"src\"\n"
+      (String.concat_map title ~f:(function
+         | '"' -> "\\\""
+         | '\\' -> "\\\\"
+         | c -> String.of_char c))
+  in
+  { files =
+      ("book.toml", book_toml)
+      :: ("src/SUMMARY.md", summary ~folder ~title_of ~intro entries)
+      :: List.map chapters ~f:fst
+  ; assets =
+      List.concat_map chapters ~f:snd
+      |> List.dedup_and_sort ~compare:String.compare
+      |> List.map ~f:(fun file -> "src/" ^ book_path ~folder file, file)
+  }
+;;
+
+let%expect_test "of_vault" =
+  let files =
+    [ "guide/index.md", "---\ntitle: The Guide\n---\nStart with [[install]].\n"
+    ; ( "guide/install.md"
+      , "# Install\n\n![[guide/img/shot.png]] then [[guide/usage/index#Run it|run]].\n\n\
+         %%🔐β c2VjcmV0 🔐%%\n\nSee [[guide/keys.mdenc|keys]].\n" )
+    ; "guide/usage/index.md", "## Run it\n\nSee [[other]] and [[#Run it]].\n"
+    ; "guide/usage/flags.md", "Back to [[guide/index|The Guide]].\n"
+    ; "guide/img/shot.png", ""
+    ; "guide/keys.mdenc", ""
+    ; "other.md", "Not in the book.\n"
+    ; "book.md", "# Basics\n\n- [[install]]\n  - [[flags|All flags]]\n- Later\n"
+    ]
+  in
+  let vault =
+    Vault.of_inmem_files
+      (List.filter files ~f:(fun (p, _) -> String.is_suffix p ~suffix:".md"))
+  in
+  let vault =
+    { vault with
+      index =
+        Vault.build_index
+          ~md_docs:vault.docs
+          ~other_files:[ "guide/img/shot.png"; "guide/keys.mdenc" ]
+          ~dirs:[]
+    }
+  in
+  let vault =
+    { vault with docs = Vault.Resolve.resolve_docs vault.docs vault.index }
+  in
+  let source_of p = List.Assoc.find_exn files p ~equal:String.equal in
+  let print (t : t) =
+    List.iter t.files ~f:(fun (path, contents) -> printf "== %s\n%s" path contents);
+    List.iter t.assets ~f:(fun (dst, src) -> printf "== %s <- %s\n" dst src)
+  in
+  print (ok_exn (of_vault ~title:"Guide" ~folder:"guide" ~source_of vault));
+  [%expect
+    {|
+    == book.toml
+    [book]
+    title = "Guide"
+    src = "src"
+    == src/SUMMARY.md
+    # Summary
+
+    [The Guide](index.md)
+
+    - [install](install.md)
+    - [usage](usage/index.md)
+      - [flags](usage/flags.md)
+    == src/index.md
+    Start with [install](install.md).
+    == src/install.md
+    # Install
+
+    ![guide/img/shot.png](img/shot.png) then [run](usage/index.md#run-it).
+
+
+
+    See keys.
+    == src/usage/index.md
+    ## Run it
+
+    See other and [Run it](#run-it).
+    == src/usage/flags.md
+    Back to [The Guide](../index.md).
+    == src/img/shot.png <- guide/img/shot.png
+    |}];
+  print
+    (ok_exn (of_vault ~index:"book.md" ~title:"Guide" ~folder:"guide" ~source_of vault));
+  [%expect
+    {|
+    == book.toml
+    [book]
+    title = "Guide"
+    src = "src"
+    == src/SUMMARY.md
+    # Summary
+
+    # Basics
+
+    - [install](install.md)
+      - [All flags](usage/flags.md)
+    - [Later]()
+    == src/install.md
+    # Install
+
+    ![guide/img/shot.png](img/shot.png) then run.
+
+
+
+    See keys.
+    == src/usage/flags.md
+    Back to The Guide.
+    == src/img/shot.png <- guide/img/shot.png
+    |}];
+  let publishes (p : string) _ = not (String.equal p "guide/usage/index.md") in
+  print (ok_exn (of_vault ~publishes ~title:"Guide" ~folder:"guide" ~source_of vault));
+  [%expect
+    {|
+    == book.toml
+    [book]
+    title = "Guide"
+    src = "src"
+    == src/SUMMARY.md
+    # Summary
+
+    [The Guide](index.md)
+
+    - [install](install.md)
+    - [usage]()
+      - [flags](usage/flags.md)
+    == src/index.md
+    Start with [install](install.md).
+    == src/install.md
+    # Install
+
+    ![guide/img/shot.png](img/shot.png) then run.
+
+
+
+    See keys.
+    == src/usage/flags.md
+    Back to [The Guide](../index.md).
+    == src/img/shot.png <- guide/img/shot.png
+    |}];
+  (match of_vault ~index:"missing.md" ~title:"Guide" ~folder:"" ~source_of vault with
+   | Ok _ -> print_endline "ok"
+   | Error e -> print_endline (Error.to_string_hum e));
+  [%expect {| index note missing.md not found |}]
+;;
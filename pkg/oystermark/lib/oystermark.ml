@@ -10,47 +10,82 @@ open Core
 module Parse = Parse
 module Vault = Vault
 module Html = Component.Html
+module Url = Component.Url
+module Anchors = Component.Anchors
+module A11y = A11y
+module Social_card = Social_card
+module Single_file = Single_file
 module Pipeline = Pipeline
 module Theme = Theme
 module Config = Config
 module Code_executor = Code_executor
 module Vault_graph = Vault_graph
 module Graph_view = Vault_graph.Graph_view
+module Query = Query
+module Daemon = Daemon
+module Sql_export = Sql_export
+module Embedding = Embedding
+module Corpus = Corpus
+module Mdbook = Mdbook
+module Slides = Slides
 
-(** Build and render a vault through the pipeline.
+(** Effective config of the note at [rel_path]: [config], then the
+    [.oyster.toml] files of its folders, then its frontmatter [oyster] field. *)
+let note_config
+      ~(config : Config.t)
+      ~(folders : Vault.Folder_config.t)
+      (rel_path : string)
+      (doc : Cmarkit.Doc.t)
+  : Config.t
+  =
+  Vault.Folder_config.note_config ~base:config folders rel_path doc
+;;
 
-    Stages:
-    1. List files and dirs, apply [on_discover].
-    2. Parse [.md] files only, apply [on_parse].
-    3. Build index, resolve links, apply [on_vault] to all entries
-       (docs + dirs with synthetic empty docs), then render to HTML. *)
-let render_vault
-      ?(pipeline : Pipeline.t = Pipeline.default ())
-      ?(theme : Theme.t = Theme.none)
-      ?(config = Config.default)
-      ~(backend_blocks : bool)
-      ~(safe : bool)
+(* Stages 1 to 4 of {!render_site}, up to the final vault context: [all_entries]
+   are merged-vault relative paths, [read_file] maps one to its contents. Also
+   returns the folders' configs. *)
+let build_entries
+      ?(on_embed_issues : Vault.Embed.issue list -> unit = ignore)
+      ~(pipeline : Pipeline.t)
+      ~(config : Config.t)
+      ~(vault_meta : Cmarkit.Meta.t)
+      ~(read_file : string -> string)
       (vault_root : string)
-  : (string * string) list
+      (all_entries : string list)
+  : Vault.t * Vault.Folder_config.t
   =
-  let all_entries = Vault.list_entries vault_root in
-  (* Stage 1: discover *)
+  (* Stage 1: discover. Encrypted notes are never published, whatever the
+     pipeline. *)
   let discovered =
-    List.filter all_entries ~f:(fun p -> pipeline.on_discover p all_entries)
+    List.filter all_entries ~f:(fun p ->
+      (not (Parse.Encrypted.is_encrypted_file p)) && pipeline.on_discover p all_entries)
   in
   let is_dir (p : string) : bool = String.is_suffix p ~suffix:"/" in
   let dirs : string list = List.filter discovered ~f:is_dir in
-  (* Stage 2: parse — only .md files go through on_parse *)
+  (* Stage 2: parse — only .md files go through on_parse, without their
+     encrypted blocks *)
   let parsed : (string * Cmarkit.Doc.t) list =
     List.concat_map discovered ~f:(fun rel_path ->
       if String.is_suffix rel_path ~suffix:".md"
       then (
-        let full_path = Filename.concat vault_root rel_path in
-        let content = In_channel.read_all full_path in
-        let doc = Parse.of_string content ~config in
+        let content = read_file rel_path in
+        let doc = Parse.Encrypted.strip (Parse.of_string content ~config) in
+        (match Config.site_only_keys_in_frontmatter (Parse.Frontmatter.of_doc doc) with
+         | [] -> ()
+         | keys ->
+           failwithf
+             "%s: %s can only be set in the site config"
+             rel_path
+             (String.concat ~sep:", " keys)
+             ());
         pipeline.on_parse rel_path doc)
       else [])
   in
+  let folders : Vault.Folder_config.t =
+    Vault.Folder_config.load
+      ~read_file:(fun p -> Option.try_with (fun () -> read_file p))
+      dirs
+  in
   (* Stage 3: Build index, resolve links *)
   let md_docs = List.filter parsed ~f:(fun (p, _) -> String.is_suffix p ~suffix:".md") in
   let other_files =
@@ -59,43 +94,354 @@ let render_vault
   in
   let index = Vault.build_index ~md_docs ~other_files ~dirs in
   let resolved : (string * Cmarkit.Doc.t) list =
-    Vault.Resolve.resolve_docs md_docs index
+    Vault.Resolve.resolve_docs
+      ~prefer:config.link_resolution.prefer
+      ~relative:config.link_resolution.relative
+      md_docs
+      index
+  in
+  (* Check, then expand note embeds after resolution; notes [on_parse] dropped
+     are the unpublished ones *)
+  let embed_issues : Vault.Embed.issue list =
+    let kept = String.Set.of_list (List.map md_docs ~f:fst) in
+    Vault.Embed.check
+      ~max_depth:config.embeds.max_depth
+      ~unpublished:
+        (List.filter discovered ~f:(fun p ->
+           String.is_suffix p ~suffix:".md" && not (Set.mem kept p)))
+      resolved
+  in
+  if config.embeds.strict && not (List.is_empty embed_issues)
+  then
+    failwith
+      (String.concat
+         ~sep:"\n"
+         (List.map embed_issues ~f:Vault.Embed.issue_to_string_hum))
+  else on_embed_issues embed_issues;
+  let expanded : (string * Cmarkit.Doc.t) list =
+    Vault.Embed.expand_docs
+      ~slug_style:(Parse.Heading_slug.of_config config.parse.slug_style)
+      ~max_depth:config.embeds.max_depth
+      resolved
   in
-  (* Expand note embeds after resolution *)
-  let expanded : (string * Cmarkit.Doc.t) list = Vault.Embed.expand_docs resolved in
   let vault_ctx : Vault.t =
-    { vault_root; index; docs = expanded; vault_meta = Cmarkit.Meta.none }
+    { vault_root; index; docs = expanded; vault_meta }
   in
-  (* Stage 4: on_vault + Render *)
+  (* Stage 4: on_vault *)
   let final_vault : Vault.t = pipeline.on_vault vault_ctx in
+  (match Component.Url.permalink_errors ~style:config.url_style final_vault.docs with
+   | [] -> ()
+   | errors -> failwith (String.concat ~sep:"\n" errors));
+  final_vault, folders
+;;
+
+(* Shared body of {!render_site}: {!build_entries}, then render. Also returns
+   the final vault context the pages were rendered from. *)
+let render_entries
+      ?(on_embed_issues : Vault.Embed.issue list -> unit = ignore)
+      ?(on_warning : string -> unit = ignore)
+      ~(pipeline : Pipeline.t)
+      ~(theme : Theme.t)
+      ~(config : Config.t)
+      ~(backend_blocks : bool)
+      ~(safe : bool)
+      ~(vault_meta : Cmarkit.Meta.t)
+      ~(read_file : string -> string)
+      (vault_root : string)
+      (all_entries : string list)
+  : Vault.t * (string * string) list
+  =
+  let final_vault, folders =
+    build_entries
+      ~on_embed_issues
+      ~pipeline
+      ~config
+      ~vault_meta
+      ~read_file
+      vault_root
+      all_entries
+  in
   let sidebar_paths : string list =
-    List.filter_map final_vault.docs ~f:(fun (p, _) ->
+    List.filter_map (Vault.listed_docs final_vault) ~f:(fun (p, _) ->
       if String.is_suffix p ~suffix:".md" then Some p else None)
   in
+  let urls : Component.Url.t =
+    Component.Url.of_docs ~style:config.url_style final_vault.docs
+  in
   let sidebar : string =
     Component.toc_html
-      ~dir_href_f:(fun dir -> Some (Html.note_url_path (dir ^ "/index.md")))
-      ~leaf_href_f:Html.file_url_path
+      ~dir_href_f:(fun dir -> Some (Component.Url.note_url_path urls (dir ^ "/index.md")))
+      ~leaf_href_f:(Component.Url.file_url_path urls)
       ~collapsible:true
       ~collapsed_by_default:true
-      ~compare_path:(Pipeline.compare_path_of_toc_order config.toc_order)
+      ~compare_path:
+        (Pipeline.compare_path_of_sidebar_order
+           ~toc_order:config.toc_order
+           config.sidebar.order
+           final_vault.docs)
       sidebar_paths
   in
-  List.filter_map final_vault.docs ~f:(fun (rel_path, final) ->
-    if String.is_suffix rel_path ~suffix:".md"
-    then (
-      let fm = Parse.Frontmatter.of_doc final in
-      let fm_config = Config.of_frontmatter fm in
-      let config = Config.merge config fm_config in
-      let body = Html.of_doc ~backend_blocks ~safe ~config final in
-      let url_path = Html.note_url_path rel_path in
-      let title : string = Component.title_of_path rel_path in
-      let nav : string = Component.nav_of_url_path ~home_path:config.home.path url_path in
-      let sidebar : string =
-        if String.equal rel_path config.home.path then "" else sidebar
-      in
-      let page = Theme.{ title; body; url_path; nav; sidebar } in
-      let html = theme page in
-      Some (Html.note_output_path rel_path, html))
-    else None)
+  let menu : string =
+    Component.menu_html ~urls ~paths:(List.map final_vault.docs ~f:fst) config.menu
+  in
+  let pages : (string * string) list =
+    List.filter_map final_vault.docs ~f:(fun (rel_path, final) ->
+      if String.is_suffix rel_path ~suffix:".md"
+      then (
+        let config = note_config ~config ~folders rel_path final in
+        let body =
+          Theme.accent_style config.accent
+          ^ Html.of_doc ~backend_blocks ~safe ~config ~urls final
+        in
+        let url_path = Component.Url.note_url_path urls rel_path in
+        let title : string = Component.title_of_path rel_path in
+        let nav : string =
+          Component.nav_of_url_path ~home_path:config.home.path ~urls url_path
+        in
+        let sidebar : string =
+          if String.equal rel_path config.home.path then "" else sidebar
+        in
+        let classes : string list = Component.css_classes final in
+        let head, card =
+          if config.social_cards.enabled
+          then
+            Social_card.of_note
+              ~on_warning
+              ~config:config.social_cards
+              ~colors:
+                (Option.value
+                   (Theme.colors_of_name config.theme)
+                   ~default:Theme.bluloco_dark_colors)
+              ~urls
+              ~site_title:config.site.title
+              ~title
+              rel_path
+              final
+          else "", None
+        in
+        let head : string =
+          if Vault.is_unlisted final
+          then {|<meta name="robots" content="noindex">|} ^ "\n" ^ head
+          else head
+        in
+        let page =
+          Theme.{ title; body; url_path; nav; menu; sidebar; classes; head }
+        in
+        let html = theme page in
+        let print_variant =
+          if config.print.variants
+          then
+            [ ( String.chop_suffix_exn rel_path ~suffix:".md" ^ ".print.html"
+              , Theme.print { page with nav = ""; menu = ""; sidebar = "" } )
+            ]
+          else []
+        in
+        let preview =
+          if config.page_previews.enabled
+          then
+            [ ( Component.Url.preview_output_path urls rel_path
+              , Html.preview_html ~backend_blocks ~safe ~config ~urls ~title final )
+            ]
+          else []
+        in
+        Some
+          ((Component.Url.note_output_path urls rel_path, html)
+           :: (print_variant @ preview @ Option.to_list card)))
+      else None)
+    |> List.concat
+  in
+  (* Old URLs of notes, see {!Component.Url.redirects} *)
+  List.iter (Component.Url.redirect_issues urls final_vault.docs) ~f:(fun i ->
+    on_warning (Component.Url.redirect_issue_to_string_hum i));
+  let redirects : (string * string) list =
+    let redirects = Component.Url.redirects urls final_vault.docs in
+    match config.redirects.format with
+    | Config.Redirect_format_def.Html ->
+      List.filter_map redirects ~f:(fun (from, url) ->
+        let%bind.Option out = Component.Url.output_path_of_url from in
+        if List.Assoc.mem pages ~equal:String.equal out
+        then (
+          on_warning
+            (sprintf "redirect from %s to %s would replace %s; skipped" from url out);
+          None)
+        else Some (out, Component.Url.redirect_html url))
+    | Config.Redirect_format_def.Redirects_file ->
+      (match redirects with
+       | [] -> []
+       | redirects -> [ "_redirects", Component.Url.redirects_file redirects ])
+  in
+  final_vault, pages @ redirects
+;;
+
+(* What {!render_site} builds: the vault meta, a reader of merged-vault
+   relative paths, and the entries of [vault_root] alone or of [mounts]. *)
+let site_entries ~(mounts : Vault.mount list) (vault_root : string)
+  : Cmarkit.Meta.t * (string -> string) * string list
+  =
+  match mounts with
+  | [] ->
+    ( Cmarkit.Meta.none
+    , (fun rel_path -> In_channel.read_all (Filename.concat vault_root rel_path))
+    , Vault.list_entries vault_root )
+  | mounts ->
+    let read_file (rel_path : string) : string =
+      match Vault.mount_full_path mounts rel_path with
+      | Some full_path -> In_channel.read_all full_path
+      | None -> failwith (sprintf "%s is not under any mounted vault" rel_path)
+    in
+    ( Cmarkit.Meta.add Vault.mounts_key mounts Cmarkit.Meta.none
+    , read_file
+    , Vault.list_mounted_entries mounts )
+;;
+
+(** Build and render a vault, returning the final vault context alongside the
+    pages — callers use it for post-render work such as copying only the
+    assets pages reference ({!Vault.Assets.referenced_files}).
+
+    With [mounts] empty this renders [vault_root] alone (see {!render_vault});
+    otherwise it renders the merged [mounts] (see {!render_vaults}) and
+    [vault_root] only names the site's base root.
+
+    Embed problems ({!Vault.Embed.check}) fail the build when
+    [config.embeds.strict] is set, and otherwise go to [on_embed_issues].
+    Invalid or shared permalinks ({!Component.Url.permalink_errors}) fail it.
+    Redirects left out (see {!Component.Url.redirect_issues}) go to
+    [on_warning]. *)
+let render_site
+      ?(on_embed_issues : (Vault.Embed.issue list -> unit) option)
+      ?(on_warning : (string -> unit) option)
+      ?(pipeline : Pipeline.t = Pipeline.default ())
+      ?(theme : Theme.t = Theme.none)
+      ?(config = Config.default)
+      ?(mounts : Vault.mount list = [])
+      ~(backend_blocks : bool)
+      ~(safe : bool)
+      (vault_root : string)
+  : Vault.t * (string * string) list
+  =
+  let vault_meta, read_file, entries = site_entries ~mounts vault_root in
+  render_entries
+    ?on_embed_issues
+    ?on_warning
+    ~pipeline
+    ~theme
+    ~config
+    ~backend_blocks
+    ~safe
+    ~vault_meta
+    ~read_file
+    vault_root
+    entries
+;;
+
+(** The published notes of a vault as one page (see
+    {!Single_file}), built as {!render_site} builds the site; also returns
+    the final vault context. Notes come in sidebar order, each under the
+    settings {!note_config} gives it. *)
+let render_single_file
+      ?(on_embed_issues : (Vault.Embed.issue list -> unit) option)
+      ?(pipeline : Pipeline.t = Pipeline.default ())
+      ?(theme : Theme.t = Theme.none)
+      ?(config = Config.default)
+      ?(mounts : Vault.mount list = [])
+      ~(backend_blocks : bool)
+      ~(safe : bool)
+      (vault_root : string)
+  : Vault.t * string
+  =
+  let vault_meta, read_file, entries = site_entries ~mounts vault_root in
+  let final_vault, folders =
+    build_entries
+      ?on_embed_issues
+      ~pipeline
+      ~config
+      ~vault_meta
+      ~read_file
+      vault_root
+      entries
+  in
+  let urls : Component.Url.t =
+    Component.Url.of_docs ~style:config.url_style final_vault.docs
+  in
+  let notes : (string * Cmarkit.Doc.t) list =
+    List.filter (Vault.listed_docs final_vault) ~f:(fun (p, _) ->
+      String.is_suffix p ~suffix:".md")
+  in
+  let compare_path =
+    Pipeline.compare_path_of_sidebar_order
+      ~toc_order:config.toc_order
+      config.sidebar.order
+      final_vault.docs
+  in
+  let notes : Single_file.note list =
+    List.sort notes ~compare:(fun (a, _) (b, _) -> compare_path a b)
+    |> List.map ~f:(fun (path, doc) : Single_file.note ->
+      let config = note_config ~config ~folders path doc in
+      { path
+      ; title = Component.title_of_path path
+      ; body =
+          Theme.accent_style config.accent
+          ^ Html.of_doc ~backend_blocks ~safe ~config ~urls doc
+      })
+  in
+  let html : string =
+    Single_file.to_html
+      ~theme
+      ~title:
+        (if String.is_empty config.site.title
+         then Filename.basename vault_root
+         else config.site.title)
+      ~urls
+      ~compare_path
+      ~read_asset:(fun path ->
+        Option.try_with (fun () ->
+          In_channel.read_all (Vault.full_path final_vault path)))
+      ~assets:(Vault.Assets.referenced_files final_vault.docs)
+      notes
+  in
+  final_vault, html
+;;
+
+(** Build and render a vault through the pipeline.
+
+    Stages:
+    1. List files and dirs, apply [on_discover].
+    2. Parse [.md] files only, apply [on_parse].
+    3. Build index, resolve links, apply [on_vault] to all entries
+       (docs + dirs with synthetic empty docs), then render to HTML. *)
+let render_vault
+      ?(pipeline : Pipeline.t = Pipeline.default ())
+      ?(theme : Theme.t = Theme.none)
+      ?(config = Config.default)
+      ~(backend_blocks : bool)
+      ~(safe : bool)
+      (vault_root : string)
+  : (string * string) list
+  =
+  render_site ~pipeline ~theme ~config ~backend_blocks ~safe vault_root |> snd
+;;
+
+(** Build several vaults into one site. Each {!Vault.mount} contributes its
+    entries under its prefix, so pages get per-vault URL prefixes
+    ([/work/...], [/personal/...]) and, since all mounts share one index,
+    wikilinks resolve across vaults ([\[\[personal/recipes\]\]] from a work
+    note). The mounts are recorded on [vault_meta] under {!Vault.mounts_key}
+    so later stages can map paths back to disk with {!Vault.full_path}. *)
+let render_vaults
+      ?(pipeline : Pipeline.t = Pipeline.default ())
+      ?(theme : Theme.t = Theme.none)
+      ?(config = Config.default)
+      ~(backend_blocks : bool)
+      ~(safe : bool)
+      (mounts : Vault.mount list)
+  : (string * string) list
+  =
+  let vault_root : string =
+    match List.find mounts ~f:(fun (m : Vault.mount) -> String.is_empty m.prefix) with
+    | Some m -> m.root
+    | None ->
+      Option.value_map (List.hd mounts) ~default:"." ~f:(fun (m : Vault.mount) ->
+        m.root)
+  in
+  render_site ~pipeline ~theme ~config ~mounts ~backend_blocks ~safe vault_root |> snd
 ;;
@@ -0,0 +1,448 @@
+(** The parsed document as JSON, for tools that consume oystermark's parse
+    without linking the library ([oystermark ast FILE --format json]).
+
+    Every node is an object with
+    - [kind]: the constructor name, as in {!Parse.sexp_of_doc};
+    - [range]: source position, [{"start": pos, "end": pos}] with
+      [pos = {"byte", "line", "col"}] (line 1-based, byte and column 0-based,
+      end inclusive), or [null] for synthesized nodes;
+    - [attrs]: metadata attached by the parse passes (heading slug, block id,
+      callout, code block attribute, definition list), omitted when empty;
+    - [children]: child nodes, omitted when there are none;
+    - node-specific fields ([text], [level], [info], [code], ...).
+
+    Links and images carry [dest] and [title], taken from the definition for
+    reference links ([label] names it); footnote references carry [footnote]
+    instead. Nodes from syntax extensions this module doesn't know keep their
+    constructor name as [kind], without fields or children. *)
+
+open Core
+
+type json = Yojson.Basic.t
+
+let range (meta : Cmarkit.Meta.t) : json =
+  let tl = Cmarkit.Meta.textloc meta in
+  if Cmarkit.Textloc.is_none tl
+  then `Null
+  else (
+    let pos (byte : int) ((line, line_start) : Cmarkit.Textloc.line_pos) : json =
+      `Assoc [ "byte", `Int byte; "line", `Int line; "col", `Int (byte - line_start) ]
+    in
+    `Assoc
+      [ "start", pos (Cmarkit.Textloc.first_byte tl) (Cmarkit.Textloc.first_line tl)
+      ; "end", pos (Cmarkit.Textloc.last_byte tl) (Cmarkit.Textloc.last_line tl)
+      ])
+;;
+
+let string_opt : string option -> json = function
+  | None -> `Null
+  | Some s -> `String s
+;;
+
+let of_attribute (a : Cmarkit.Attribute.t) : json =
+  `Assoc
+    [ "id", string_opt (Cmarkit.Attribute.id a)
+    ; "classes", `List (List.map (Cmarkit.Attribute.classes a) ~f:(fun c -> `String c))
+    ; ( "key_values"
+      , `Assoc
+          (List.map (Cmarkit.Attribute.key_values a) ~f:(fun (k, v) -> k, `String v)) )
+    ]
+;;
+
+let attrs (meta : Cmarkit.Meta.t) : (string * json) list =
+  List.filter_opt
+    [ Cmarkit.Meta.find Heading_slug.meta_key meta
+      |> Option.map ~f:(fun slug -> "heading_slug", `String slug)
+    ; Cmarkit.Block.Block_id.find meta
+      |> Option.map ~f:(fun bid -> "block_id", `String (Cmarkit.Block.Block_id.id bid))
+    ; Cmarkit.Block.Callout.find meta
+      |> Option.map ~f:(fun c ->
+        ( "callout"
+        , `Assoc
+            [ "kind", `String (Gfm_alert.callout_kind (Cmarkit.Block.Callout.kind c))
+            ; ( "fold"
+              , match Cmarkit.Block.Callout.fold c with
+                | None -> `Null
+                | Some Cmarkit.Block.Callout.Foldable_open -> `String "open"
+                | Some Cmarkit.Block.Callout.Foldable_closed -> `String "closed" )
+            ] ))
+    ; Cmarkit.Meta.find Cb_attribute.meta_key meta
+      |> Option.map ~f:(fun (info : Cb_attribute.code_block_info) ->
+        ( "code_attribute"
+        , `Assoc
+            [ "lang", `String info.lang
+            ; ( "attribute"
+              , match info.attribute with
+                | None -> `Null
+                | Some a ->
+                  `Assoc
+                    [ "id", string_opt a.id
+                    ; "classes", `List (List.map a.classes ~f:(fun c -> `String c))
+                    ; ( "key_values"
+                      , `Assoc (List.map a.kvs ~f:(fun (k, v) -> k, `String v)) )
+                    ] )
+            ] ))
+    ; Option.some_if
+        (Definition_list.is_definition_list meta)
+        ("definition_list", `Bool true)
+    ]
+;;
+
+let node
+      ?(fields : (string * json) list = [])
+      ?(children : json list = [])
+      (kind : string)
+      (meta : Cmarkit.Meta.t)
+  : json
+  =
+  `Assoc
+    (List.concat
+       [ [ "kind", `String kind; "range", range meta ]
+       ; (match attrs meta with
+          | [] -> []
+          | a -> [ "attrs", `Assoc a ])
+       ; fields
+       ; (if List.is_empty children then [] else [ "children", `List children ])
+       ])
+;;
+
+let lines_json (lines : Cmarkit.Block_line.t list) : json =
+  `String (List.map lines ~f:Cmarkit.Block_line.to_string |> String.concat ~sep:"\n")
+;;
+
+(* Name of the constructor of an extensible variant value, without its path. *)
+let constructor_name (v : 'a) : string =
+  let name = Stdlib.Obj.Extension_constructor.(name (of_val v)) in
+  match String.rsplit2 name ~on:'.' with
+  | Some (_, n) -> n
+  | None -> name
+;;
+
+let link_definition_fields (ld : Cmarkit.Link_definition.t) : (string * json) list =
+  let title : json =
+    match Cmarkit.Link_definition.title ld with
+    | None -> `Null
+    | Some lines ->
+      `String
+        (List.map lines ~f:Cmarkit.Block_line.tight_to_string |> String.concat ~sep:"\n")
+  in
+  [ "dest", string_opt (Link_def.dest ld); "title", title ]
+;;
+
+let link_fields ~(defs : Cmarkit.Label.defs) (l : Cmarkit.Inline.Link.t)
+  : (string * json) list
+  =
+  let label : json =
+    match Cmarkit.Inline.Link.reference l with
+    | `Inline _ -> `Null
+    | `Ref (_, label, _) -> `String (Cmarkit.Label.key label)
+  in
+  match Link_def.of_link ~defs l with
+  | Some (Link_def.Footnote (name, _)) -> [ "footnote", `String name ]
+  | Some (Link_def.Link ld) -> ("label", label) :: link_definition_fields ld
+  | None -> [ "label", label; "dest", `Null; "title", `Null ]
+;;
+
+let rec of_inline ~(defs : Cmarkit.Label.defs) (i : Cmarkit.Inline.t) : json =
+  let open Cmarkit.Inline in
+  let of_inline = of_inline ~defs in
+  match i with
+  | Text (s, m) -> node "Text" m ~fields:[ "text", `String s ]
+  | Autolink (a, m) ->
+    node "Autolink" m ~fields:[ "link", `String (fst (Autolink.link a)) ]
+  | Break (b, m) ->
+    let type' =
+      match Break.type' b with
+      | `Hard -> "hard"
+      | `Soft -> "soft"
+    in
+    node "Break" m ~fields:[ "type", `String type' ]
+  | Code_span (cs, m) ->
+    node "Code_span" m ~fields:[ "code", `String (Code_span.code cs) ]
+  | Emphasis (e, m) -> node "Emphasis" m ~children:[ of_inline (Emphasis.inline e) ]
+  | Strong_emphasis (e, m) ->
+    node "Strong_emphasis" m ~children:[ of_inline (Emphasis.inline e) ]
+  | Link (l, m) ->
+    node "Link" m ~fields:(link_fields ~defs l) ~children:[ of_inline (Link.text l) ]
+  | Image (l, m) ->
+    node "Image" m ~fields:(link_fields ~defs l) ~children:[ of_inline (Link.text l) ]
+  | Raw_html (html, m) ->
+    let s = List.map html ~f:Cmarkit.Block_line.tight_to_string |> String.concat in
+    node "Raw_html" m ~fields:[ "html", `String s ]
+  | Inlines (is, m) -> node "Inlines" m ~children:(List.map is ~f:of_inline)
+  | Ext_strikethrough (s, m) ->
+    node "Strikethrough" m ~children:[ of_inline (Strikethrough.inline s) ]
+  | Ext_math_span (ms, m) ->
+    node "Math_span" m ~fields:[ "tex", `String (Math_span.tex ms) ]
+  | Ext_wikilink (wl, m) ->
+    let fragment : json =
+      match Wikilink.fragment wl with
+      | None -> `Null
+      | Some (Wikilink.Heading hs) ->
+        `Assoc [ "heading", `List (List.map hs ~f:(fun h -> `String h)) ]
+      | Some (Wikilink.Block_ref id) -> `Assoc [ "block_ref", `String id ]
+    in
+    node
+      "Wikilink"
+      m
+      ~fields:
+        [ "target", string_opt (Wikilink.target wl)
+        ; "fragment", fragment
+        ; "display", string_opt (Wikilink.display wl)
+        ; "embed", `Bool (Wikilink.embed wl)
+        ]
+  | Ext_attributes (a, m) ->
+    node
+      "Attributes"
+      m
+      ~fields:[ "attributes", of_attribute (Attributes.attributes a) ]
+      ~children:[ of_inline (Attributes.inline a) ]
+  | i ->
+    node (constructor_name i) (Cmarkit.Inline.meta ~ext:(fun _ -> Cmarkit.Meta.none) i)
+;;
+
+let rec of_block ~(defs : Cmarkit.Label.defs) (b : Cmarkit.Block.t) : json =
+  let open Cmarkit.Block in
+  let of_block = of_block ~defs in
+  let of_inline = of_inline ~defs in
+  match b with
+  | Blank_line (_, m) -> node "Blank_line" m
+  | Paragraph (p, m) -> node "Paragraph" m ~children:[ of_inline (Paragraph.inline p) ]
+  | Heading (h, m) ->
+    node
+      "Heading"
+      m
+      ~fields:
+        [ "level", `Int (Heading.level h)
+        ; ( "style"
+          , `String
+              (match Heading_slug.style_of h with
+               | Heading_slug.Atx -> "atx"
+               | Heading_slug.Setext -> "setext") )
+        ]
+      ~children:[ of_inline (Heading.inline h) ]
+  | Code_block (cb, m) ->
+    node
+      "Code_block"
+      m
+      ~fields:
+        [ "info", string_opt (Option.map (Code_block.info_string cb) ~f:fst)
+        ; "code", lines_json (Code_block.code cb)
+        ]
+  | Ext_math_block (cb, m) ->
+    node "Math_block" m ~fields:[ "tex", lines_json (Code_block.code cb) ]
+  | Html_block (lines, m) -> node "Html_block" m ~fields:[ "html", lines_json lines ]
+  | Block_quote (bq, m) ->
+    node "Block_quote" m ~children:[ of_block (Block_quote.block bq) ]
+  | List (l, m) ->
+    let items =
+      List.map (List'.items l) ~f:(fun (item, item_meta) ->
+        let task : json =
+          match List_item.ext_task_marker item with
+          | None -> `Null
+          | Some (mark, _) ->
+            `String
+              (match List_item.task_status_of_task_marker mark with
+               | `Unchecked -> "unchecked"
+               | `Checked -> "checked"
+               | `Cancelled -> "cancelled"
+               | `Other _ -> "other")
+        in
+        node
+          "List_item"
+          item_meta
+          ~fields:[ "task", task ]
+          ~children:[ of_block (List_item.block item) ])
+    in
+    let type' : json =
+      match List'.type' l with
+      | `Unordered c -> `Assoc [ "unordered", `String (Char.to_string c) ]
+      | `Ordered (start, c) ->
+        `Assoc [ "ordered", `String (Char.to_string c); "start", `Int start ]
+      | `Ext_ordered (style, c, start) ->
+        let style =
+          match style with
+          | `Decimal -> "decimal"
+          | `Alpha_lower -> "alpha_lower"
+          | `Alpha_upper -> "alpha_upper"
+          | `Roman_lower -> "roman_lower"
+          | `Roman_upper -> "roman_upper"
+        in
+        `Assoc
+          [ "ordered", `String (Char.to_string c)
+          ; "start", `Int start
+          ; "style", `String style
+          ]
+    in
+    node
+      "List"
+      m
+      ~fields:[ "type", type'; "tight", `Bool (List'.tight l) ]
+      ~children:items
+  | Blocks (bs, m) -> node "Blocks" m ~children:(List.map bs ~f:of_block)
+  | Link_reference_definition (ld, m) ->
+    let label : json =
+      match Cmarkit.Link_definition.defined_label ld with
+      | None -> `Null
+      | Some label -> `String (Cmarkit.Label.key label)
+    in
+    node
+      "Link_reference_definition"
+      m
+      ~fields:(("label", label) :: link_definition_fields ld)
+  | Thematic_break (_, m) -> node "Thematic_break" m
+  | Ext_table (t, m) ->
+    let row (cells : (Cmarkit.Inline.t * _) list) : json =
+      `List (List.map cells ~f:(fun (i, _) -> of_inline i))
+    in
+    let rows =
+      List.filter_map (Table.rows t) ~f:(fun ((r, _), _) ->
+        match r with
+        | `Header cells -> Some (`Assoc [ "header", row cells ])
+        | `Data cells -> Some (`Assoc [ "data", row cells ])
+        | `Sep _ -> None)
+    in
+    node "Table" m ~fields:[ "rows", `List rows ]
+  | Ext_footnote_definition (fn, m) ->
+    node
+      "Footnote_definition"
+      m
+      ~fields:[ "label", `String (Cmarkit.Label.key (Footnote.label fn)) ]
+      ~children:[ of_block (Footnote.block fn) ]
+  | Ext_div (d, m) ->
+    node
+      "Div"
+      m
+      ~fields:[ "class", string_opt (Option.map (Div.class' d) ~f:fst) ]
+      ~children:[ of_block (Div.block d) ]
+  | Ext_attributes (a, m) ->
+    node
+      "Attributes"
+      m
+      ~fields:[ "attributes", of_attribute (Attributes.attributes a) ]
+      ~children:[ of_block (Attributes.block a) ]
+  | Ext_keyed ((label, body), m) ->
+    node "Keyed" m ~fields:[ "label", of_inline label ] ~children:[ of_block body ]
+  | Frontmatter.Frontmatter (y, format) ->
+    node
+      "Frontmatter"
+      Cmarkit.Meta.none
+      ~fields:
+        [ ( "format"
+          , `String
+              (match format with
+               | Frontmatter.Yaml -> "yaml"
+               | Frontmatter.Toml -> "toml"
+               | Frontmatter.Json -> "json") )
+        ; "value", Frontmatter.json_of_yaml y
+        ]
+  | b ->
+    node (constructor_name b) (Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) b)
+;;
+
+let of_doc (doc : Cmarkit.Doc.t) : json =
+  of_block ~defs:(Cmarkit.Doc.defs doc) (Cmarkit.Doc.block doc)
+;;
+
+let%expect_test "of_doc" =
+  let doc = Cmarkit.Doc.of_string ~locs:true "Some *text*." in
+  print_endline (Yojson.Basic.pretty_to_string (of_doc doc));
+  [%expect
+    {|
+    {
+      "kind": "Paragraph",
+      "range": {
+        "start": { "byte": 0, "line": 1, "col": 0 },
+        "end": { "byte": 11, "line": 1, "col": 11 }
+      },
+      "children": [
+        {
+          "kind": "Inlines",
+          "range": {
+            "start": { "byte": 0, "line": 1, "col": 0 },
+            "end": { "byte": 11, "line": 1, "col": 11 }
+          },
+          "children": [
+            {
+              "kind": "Text",
+              "range": {
+                "start": { "byte": 0, "line": 1, "col": 0 },
+                "end": { "byte": 4, "line": 1, "col": 4 }
+              },
+              "text": "Some "
+            },
+            {
+              "kind": "Emphasis",
+              "range": {
+                "start": { "byte": 5, "line": 1, "col": 5 },
+                "end": { "byte": 10, "line": 1, "col": 10 }
+              },
+              "children": [
+                {
+                  "kind": "Text",
+                  "range": {
+                    "start": { "byte": 6, "line": 1, "col": 6 },
+                    "end": { "byte": 9, "line": 1, "col": 9 }
+                  },
+                  "text": "text"
+                }
+              ]
+            },
+            {
+              "kind": "Text",
+              "range": {
+                "start": { "byte": 11, "line": 1, "col": 11 },
+                "end": { "byte": 11, "line": 1, "col": 11 }
+              },
+              "text": "."
+            }
+          ]
+        }
+      ]
+    }
+    |}]
+;;
+
+let%expect_test "node kinds" =
+  let rec drop_ranges : json -> json = function
+    | `Assoc kvs ->
+      `Assoc
+        (List.filter_map kvs ~f:(fun (k, v) ->
+           Option.some_if (not (String.equal k "range")) (k, drop_ranges v)))
+    | `List l -> `List (List.map l ~f:drop_ranges)
+    | j -> j
+  in
+  let print_block ~defs (b : Cmarkit.Block.t) : unit =
+    print_endline (Yojson.Basic.to_string (drop_ranges (of_block ~defs b)))
+  in
+  let show (src : string) : unit =
+    let doc = Cmarkit.Doc.of_string ~strict:false src in
+    let defs = Cmarkit.Doc.defs doc in
+    match Cmarkit.Doc.block doc with
+    | Cmarkit.Block.Blocks (bs, _) -> List.iter bs ~f:(print_block ~defs)
+    | b -> print_block ~defs b
+  in
+  show
+    {|[Home](https://example.com "The home page") ![Logo](logo.png) [ref][r] [^1]
+
+[r]: <https://example.org> 'Ref title'
+[^1]: Note.
+|};
+  [%expect
+    {|
+    {"kind":"Paragraph","children":[{"kind":"Inlines","children":[{"kind":"Link","label":null,"dest":"https://example.com","title":"The home page","children":[{"kind":"Text","text":"Home"}]},{"kind":"Text","text":" "},{"kind":"Image","label":null,"dest":"logo.png","title":null,"children":[{"kind":"Text","text":"Logo"}]},{"kind":"Text","text":" "},{"kind":"Link","label":"r","dest":"https://example.org","title":"Ref title","children":[{"kind":"Text","text":"ref"}]},{"kind":"Text","text":" "},{"kind":"Link","footnote":"1","children":[{"kind":"Text","text":"^1"}]}]}]}
+    {"kind":"Blank_line"}
+    {"kind":"Link_reference_definition","label":"r","dest":"https://example.org","title":"Ref title"}
+    {"kind":"Footnote_definition","label":"^1","children":[{"kind":"Paragraph","children":[{"kind":"Text","text":"Note."}]}]}
+    |}];
+  show "- [ ] todo\n- [x] done\n- plain\n";
+  [%expect
+    {| {"kind":"List","type":{"unordered":"-"},"tight":true,"children":[{"kind":"List_item","task":"unchecked","children":[{"kind":"Paragraph","children":[{"kind":"Text","text":"todo"}]}]},{"kind":"List_item","task":"checked","children":[{"kind":"Paragraph","children":[{"kind":"Text","text":"done"}]}]},{"kind":"List_item","task":null,"children":[{"kind":"Paragraph","children":[{"kind":"Text","text":"plain"}]}]}]} |}];
+  (* Extensions this module doesn't know keep their name *)
+  let module M = struct
+    type Cmarkit.Block.t += Custom of unit
+  end
+  in
+  print_block ~defs:Cmarkit.Label.Map.empty (M.Custom ());
+  [%expect {| {"kind":"Custom"} |}]
+;;
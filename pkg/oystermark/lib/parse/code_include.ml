@@ -0,0 +1,162 @@
+(** Code fences whose code comes from a file, so published samples follow the
+    source: [```rust file=src/lib.rs#L10-L42] (or [{file="src/lib.rs#L10-L42"}]
+    in the attribute block) takes lines 10 to 42 of [src/lib.rs]. [#L10] is a
+    single line; without a range the whole file is taken. What [file] is
+    relative to is up to the caller's [read_file]: the vault root when
+    building. *)
+
+open Core
+open Cmarkit
+
+type t =
+  { file : string
+  ; range : (int * int) option (** 1-based, inclusive *)
+  }
+[@@deriving sexp_of]
+
+(** Parse [path#L10-L42], [path#L10-42], [path#L10] or [path]. *)
+let of_string (s : string) : (t, string) Result.t =
+  let line (n : string) : int option =
+    Option.try_with (fun () -> Int.of_string (String.chop_prefix_if_exists n ~prefix:"L"))
+  in
+  match String.rsplit2 s ~on:'#' with
+  | None -> Ok { file = s; range = None }
+  | Some (file, fragment) ->
+    let range =
+      match String.lsplit2 fragment ~on:'-' with
+      | None -> Option.map (line fragment) ~f:(fun n -> n, n)
+      | Some (first, last) -> Option.both (line first) (line last)
+    in
+    (match range with
+     | Some (first, last) when String.is_prefix fragment ~prefix:"L" ->
+       if first >= 1 && first <= last
+       then Ok { file; range = Some (first, last) }
+       else Error (sprintf "bad line range %s" fragment)
+     | _ -> Error (sprintf "bad line range %s, expected #L10-L42" fragment))
+;;
+
+(* The [file=] spec of a code block, from its attribute block or a bare
+   [file=...] word of its info string. *)
+let spec (cb : Block.Code_block.t) (meta : Meta.t) : string option =
+  let of_attribute =
+    let%bind.Option (info : Cb_attribute.code_block_info) =
+      Meta.find Cb_attribute.meta_key meta
+    in
+    let%bind.Option attribute = info.attribute in
+    List.Assoc.find attribute.kvs ~equal:String.equal "file"
+  in
+  match of_attribute with
+  | Some s -> Some s
+  | None ->
+    let%bind.Option (info, _) = Block.Code_block.info_string cb in
+    String.split info ~on:' '
+    |> List.find_map ~f:(String.chop_prefix ~prefix:"file=")
+    |> Option.map ~f:(String.strip ~drop:(fun c -> Char.equal c '"' || Char.equal c '\''))
+;;
+
+(** The lines of [content] that [t] selects. *)
+let select (t : t) (content : string) : (string, string) Result.t =
+  let lines = String.split_lines content in
+  match t.range with
+  | None -> Ok (String.concat ~sep:"\n" lines)
+  | Some (first, last) when last <= List.length lines ->
+    List.sub lines ~pos:(first - 1) ~len:(last - first + 1)
+    |> String.concat ~sep:"\n"
+    |> Result.return
+  | Some (_, last) ->
+    Error (sprintf "%s has %d lines, not %d" t.file (List.length lines) last)
+;;
+
+(** [doc] with the code of each fence naming a file replaced by the lines it
+    selects, [read_file] giving the file's content from its path as written. A
+    fence whose file can't be read or whose range is out of bounds keeps its
+    own code; the problem goes to [on_error], prefixed by the fence's line. *)
+let expand
+      ?(on_error : string -> unit = ignore)
+      ~(read_file : string -> string option)
+      (doc : Doc.t)
+  : Doc.t
+  =
+  let block _m : Block.t -> Block.t Mapper.result = function
+    | Block.Code_block (cb, meta) ->
+      (match spec cb meta with
+       | None -> Mapper.default
+       | Some spec ->
+         let code =
+           let%bind.Result t = of_string spec in
+           let%bind.Result content =
+             Result.of_option (read_file t.file) ~error:(sprintf "can't read %s" t.file)
+           in
+           select t content
+         in
+         (match code with
+          | Ok code ->
+            let cb =
+              Block.Code_block.make
+                ~layout:(Block.Code_block.layout cb)
+                ?info_string:(Block.Code_block.info_string cb)
+                (Block_line.list_of_string code)
+            in
+            Mapper.ret (Block.Code_block (cb, meta))
+          | Error msg ->
+            let tl = Meta.textloc meta in
+            if Textloc.is_none tl
+            then on_error msg
+            else on_error (sprintf "line %d: %s" (fst (Textloc.first_line tl)) msg);
+            Mapper.default))
+    | _ -> Mapper.default
+  in
+  let mapper =
+    Mapper.make
+      ~inline_ext_default:(fun _m i -> Some i)
+      ~block_ext_default:(fun _m b -> Some b)
+      ~block
+      ()
+  in
+  Mapper.map_doc mapper doc
+;;
+
+let%expect_test "expand" =
+  let files = [ "src/lib.rs", "fn a() {}\n\nfn b() {\n    1\n}\n" ] in
+  let read_file f = List.Assoc.find files ~equal:String.equal f in
+  let src =
+    "```rust file=src/lib.rs#L3-L5\nstale\n```\n\n\
+     ```rust {file=\"src/lib.rs#L1\"}\n```\n\n\
+     ```rust file=src/lib.rs#L4-L9\nkept\n```\n\n\
+     ```rust file=src/nope.rs\nkept\n```\n\n\
+     ```rust file=src/lib.rs#top\nkept\n```\n"
+  in
+  let doc =
+    Doc.of_string ~locs:true src
+    |> Mapper.map_doc (Mapper.make ~block:Cb_attribute.block_map ())
+    |> expand ~on_error:(fun msg -> print_endline ("error: " ^ msg)) ~read_file
+  in
+  print_string (Cmarkit_commonmark.of_doc doc);
+  [%expect
+    {|
+    error: line 8: src/lib.rs has 5 lines, not 9
+    error: line 12: can't read src/nope.rs
+    error: line 16: bad line range top, expected #L10-L42
+    ```rust file=src/lib.rs#L3-L5
+    fn b() {
+        1
+    }
+    ```
+
+    ```rust {file="src/lib.rs#L1"}
+    fn a() {}
+    ```
+
+    ```rust file=src/lib.rs#L4-L9
+    kept
+    ```
+
+    ```rust file=src/nope.rs
+    kept
+    ```
+
+    ```rust file=src/lib.rs#top
+    kept
+    ```
+    |}]
+;;
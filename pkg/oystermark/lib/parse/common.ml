@@ -185,10 +185,11 @@ let sexp_of_block_core : block_sexp =
       with_meta
         meta
         (Sexp.List
-           [ Atom "Heading"
-           ; Atom (Int.to_string (Block.Heading.level h))
-           ; recurse_inline (Block.Heading.inline h)
-           ])
+           ([ Atom "Heading"; Atom (Int.to_string (Block.Heading.level h)) ]
+            @ (match Block.Heading.layout h with
+               | `Atx _ -> []
+               | `Setext _ -> [ Sexp.Atom "setext" ])
+            @ [ recurse_inline (Block.Heading.inline h) ]))
     | Block.Code_block (cb, meta) ->
       let info =
         match Block.Code_block.info_string cb with
@@ -0,0 +1,141 @@
+(** Definition lists, PHP Markdown Extra / Pandoc style:
+
+    {v
+    Term
+    : Definition of the term
+    : Another definition
+    Second term
+    : Its definition
+    v}
+
+    CommonMark reads this as one paragraph, so the paragraph is kept as is and
+    tagged with {!meta_key}. Every pass that rewrites inlines (wikilink
+    resolution, embeds, raw text) still sees it, and rendering back to markdown
+    is unchanged. The HTML renderer splits it into terms and definitions with
+    {!items}.
+
+    A paragraph qualifies when its first line is a term, every line starting
+    with [": "] is a definition, and every term is followed by at least one
+    definition. *)
+
+open Core
+
+let meta_key : unit Cmarkit.Meta.key = Cmarkit.Meta.key ()
+
+type item =
+  { term : Cmarkit.Inline.t
+  ; definitions : Cmarkit.Inline.t list
+  }
+
+(* Inlines of a paragraph, one list per source line. *)
+let lines (inline : Cmarkit.Inline.t) : Cmarkit.Inline.t list list =
+  let inlines =
+    match inline with
+    | Cmarkit.Inline.Inlines (is, _) -> is
+    | i -> [ i ]
+  in
+  let flush cur acc = List.rev cur :: acc in
+  let cur, acc =
+    List.fold inlines ~init:([], []) ~f:(fun (cur, acc) i ->
+      match i with
+      | Cmarkit.Inline.Break (b, _) ->
+        (match Cmarkit.Inline.Break.type' b with
+         | `Soft -> [], flush cur acc
+         | `Hard -> i :: cur, acc)
+      | i -> i :: cur, acc)
+  in
+  List.rev (flush cur acc)
+;;
+
+(* The line without its [": "] marker, when it is a definition. *)
+let definition (line : Cmarkit.Inline.t list) : Cmarkit.Inline.t list option =
+  match line with
+  | Cmarkit.Inline.Text (s, m) :: rest when String.is_prefix s ~prefix:": " ->
+    let s' = String.lstrip (String.drop_prefix s 2) in
+    Some (if String.is_empty s' then rest else Cmarkit.Inline.Text (s', m) :: rest)
+  | _ -> None
+;;
+
+let inline_of_line : Cmarkit.Inline.t list -> Cmarkit.Inline.t = function
+  | [ i ] -> i
+  | is -> Cmarkit.Inline.Inlines (is, Cmarkit.Meta.none)
+;;
+
+(** Terms and their definitions, or [None] when the paragraph inline [inline]
+    is not a definition list. *)
+let items (inline : Cmarkit.Inline.t) : item list option =
+  let rec go (acc : item list) : Cmarkit.Inline.t list list -> item list option
+    = function
+    | [] -> Some (List.rev acc)
+    | term :: rest ->
+      (match definition term with
+       | Some _ -> None
+       | None ->
+         let defs, rest =
+           List.split_while rest ~f:(fun l -> Option.is_some (definition l))
+         in
+         if List.is_empty defs
+         then None
+         else (
+           let definitions =
+             List.map defs ~f:(fun l ->
+               inline_of_line (Option.value_exn (definition l)))
+           in
+           go ({ term = inline_of_line term; definitions } :: acc) rest))
+  in
+  match lines inline with
+  | [] | [ _ ] -> None
+  | ls -> go [] ls
+;;
+
+let is_definition_list (meta : Cmarkit.Meta.t) : bool =
+  Option.is_some (Cmarkit.Meta.find meta_key meta)
+;;
+
+let block_map : Cmarkit.Block.t Cmarkit.Mapper.mapper =
+  fun _m b ->
+  match b with
+  | Cmarkit.Block.Paragraph (p, meta)
+    when Option.is_some (items (Cmarkit.Block.Paragraph.inline p)) ->
+    Cmarkit.Mapper.ret (Cmarkit.Block.Paragraph (p, Cmarkit.Meta.add meta_key () meta))
+  | _ -> Cmarkit.Mapper.default
+;;
+
+let sexp_of_meta : Common.meta_sexp =
+  fun meta ->
+  if is_definition_list meta then Some (Sexp.Atom "definition-list") else None
+;;
+
+let%expect_test "items" =
+  let show src =
+    let doc = Cmarkit.Doc.of_string src in
+    match Cmarkit.Doc.block doc with
+    | Cmarkit.Block.Paragraph (p, _) ->
+      (match items (Cmarkit.Block.Paragraph.inline p) with
+       | None -> print_endline "<none>"
+       | Some items ->
+         List.iter items ~f:(fun { term; definitions } ->
+           let text i =
+             Cmarkit.Inline.to_plain_text ~break_on_soft:false i
+             |> List.concat
+             |> String.concat
+           in
+           printf
+             "%s => %s\n"
+             (text term)
+             (String.concat ~sep:" | " (List.map definitions ~f:text))))
+    | _ -> print_endline "<not a paragraph>"
+  in
+  show "Apple\n: A fruit\n: A company\nOrange\n: *Also* a fruit";
+  [%expect
+    {|
+    Apple => A fruit | A company
+    Orange => Also a fruit
+    |}];
+  show "Just text\nover two lines";
+  [%expect {| <none> |}];
+  show ": no term\n: at all";
+  [%expect {| <none> |}];
+  show "Term\n: def\nDangling term";
+  [%expect {| <none> |}]
+;;
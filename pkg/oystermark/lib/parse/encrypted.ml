@@ -0,0 +1,257 @@
+(** Encrypted content in notes, which must never be published: ciphertext
+    left in a note by an encryption plugin, and whole encrypted notes.
+
+    Recognized:
+    - Meld Encrypt: [%%🔐β ... 🔐%%] (and the older [%%🔐α] and [%%🔐]
+      openings) anywhere in a block's text, and whole notes in [.mdenc] files.
+    - ASCII-armored ciphertext, as the age and PGP plugins write it: a block
+      holding [-----BEGIN AGE ENCRYPTED FILE-----] or
+      [-----BEGIN PGP MESSAGE-----].
+
+    The site export skips encrypted files and {!strip}s encrypted blocks from
+    every note, whatever its pipeline; search and the retrieval exports also
+    {!redact} the source they quote. [oystermark lint --encrypted] flags
+    published notes with encrypted blocks. *)
+
+open Core
+
+type kind =
+  | Meld_encrypt
+  | Armored of string (** the armor's label, e.g. [AGE ENCRYPTED FILE] *)
+[@@deriving sexp_of]
+
+let kind_to_string : kind -> string = function
+  | Meld_encrypt -> "Meld Encrypt"
+  | Armored label -> label
+;;
+
+(** Extensions of whole encrypted notes. *)
+let file_extensions = [ ".mdenc" ]
+
+let is_encrypted_file (path : string) : bool =
+  List.exists file_extensions ~f:(fun suffix -> String.is_suffix path ~suffix)
+;;
+
+let meld_marker = "🔐"
+let armor_labels = [ "AGE ENCRYPTED FILE"; "PGP MESSAGE" ]
+
+(* Whether [s] holds a Meld Encrypt span: [%%🔐] (then [α], [β] or nothing)
+   opening it and [🔐%%] closing it. *)
+let has_meld_span (s : string) : bool =
+  match String.substr_index s ~pattern:("%%" ^ meld_marker) with
+  | None -> false
+  | Some i ->
+    String.is_substring
+      (String.drop_prefix s (i + 2 + String.length meld_marker))
+      ~substring:(meld_marker ^ "%%")
+;;
+
+let kind_of_text (s : string) : kind option =
+  if has_meld_span s
+  then Some Meld_encrypt
+  else
+    List.find_map armor_labels ~f:(fun label ->
+      if String.is_substring s ~substring:(sprintf "-----BEGIN %s-----" label)
+      then Some (Armored label)
+      else None)
+;;
+
+(* Blocks checked as a whole and dropped as a whole: leaf blocks, tables, and
+   attributed blocks and footnote definitions with whatever they hold. *)
+let is_unit : Cmarkit.Block.t -> bool = function
+  | Cmarkit.Block.Paragraph _
+  | Cmarkit.Block.Heading _
+  | Cmarkit.Block.Code_block _
+  | Cmarkit.Block.Html_block _
+  | Cmarkit.Block.Ext_table _
+  | Cmarkit.Block.Ext_attributes _
+  | Cmarkit.Block.Ext_footnote_definition _ -> true
+  | _ -> false
+;;
+
+(** The kind of encrypted content in [b]'s text, code or raw HTML, if any,
+    looking into every block it holds. *)
+let kind_of_block (b : Cmarkit.Block.t) : kind option =
+  let lines (ls : Cmarkit.Block_line.t list) : string list =
+    List.map ls ~f:Cmarkit.Block_line.to_string
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ~inline:(fun f acc (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Text (s, _) -> Cmarkit.Folder.ret (s :: acc)
+        | Cmarkit.Inline.Code_span (cs, _) ->
+          Cmarkit.Folder.ret (Cmarkit.Inline.Code_span.code cs :: acc)
+        | Cmarkit.Inline.Raw_html (ls, _) ->
+          Cmarkit.Folder.ret
+            (List.rev_map ls ~f:Cmarkit.Block_line.tight_to_string @ acc)
+        | Cmarkit.Inline.Ext_attributes (a, _) ->
+          Cmarkit.Folder.ret
+            (Cmarkit.Folder.fold_inline f acc (Cmarkit.Inline.Attributes.inline a))
+        | _ -> Cmarkit.Folder.default)
+      ~block:(fun f acc (b : Cmarkit.Block.t) ->
+        match b with
+        | Cmarkit.Block.Code_block (cb, _) ->
+          Cmarkit.Folder.ret (List.rev (lines (Cmarkit.Block.Code_block.code cb)) @ acc)
+        | Cmarkit.Block.Html_block (ls, _) ->
+          Cmarkit.Folder.ret (List.rev (lines ls) @ acc)
+        | Cmarkit.Block.Ext_attributes (a, _) ->
+          Cmarkit.Folder.ret
+            (Cmarkit.Folder.fold_block f acc (Cmarkit.Block.Attributes.block a))
+        | Cmarkit.Block.Ext_footnote_definition (fn, _) ->
+          Cmarkit.Folder.ret
+            (Cmarkit.Folder.fold_block f acc (Cmarkit.Block.Footnote.block fn))
+        | Cmarkit.Block.Ext_table (t, _) ->
+          Cmarkit.Folder.ret
+            (List.fold (Cmarkit.Block.Table.rows t) ~init:acc ~f:(fun acc ((row, _), _) ->
+               match row with
+               | `Header cells | `Data cells ->
+                 List.fold cells ~init:acc ~f:(fun acc (i, _) ->
+                   Cmarkit.Folder.fold_inline f acc i)
+               | `Sep _ -> acc))
+        | _ -> Cmarkit.Folder.default)
+      ()
+  in
+  (* Pieces are gathered last first; Meld spans are matched in reading order. *)
+  Cmarkit.Folder.fold_block folder [] b
+  |> List.rev
+  |> String.concat ~sep:"\n"
+  |> kind_of_text
+;;
+
+type found =
+  { kind : kind
+  ; lines : (int * int) option (** first and last 1-based line of the block *)
+  }
+[@@deriving sexp_of]
+
+(** The blocks of [doc] with encrypted content, in document order. *)
+let find (doc : Cmarkit.Doc.t) : found list =
+  let folder =
+    Cmarkit.Folder.make
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ~block:(fun _f acc (b : Cmarkit.Block.t) ->
+        if not (is_unit b)
+        then Cmarkit.Folder.default
+        else (
+          match kind_of_block b with
+          | None -> Cmarkit.Folder.ret acc
+          | Some kind ->
+            let meta = Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) b in
+            let loc = Cmarkit.Meta.textloc meta in
+            let lines =
+              if Cmarkit.Textloc.is_none loc
+              then None
+              else
+                Some
+                  ( fst (Cmarkit.Textloc.first_line loc)
+                  , fst (Cmarkit.Textloc.last_line loc) )
+            in
+            Cmarkit.Folder.ret ({ kind; lines } :: acc)))
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_doc folder [] doc)
+;;
+
+(** [doc] without its blocks with encrypted content. *)
+let strip (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
+  let mapper =
+    Cmarkit.Mapper.make
+      ~inline_ext_default:(fun _m i -> Some i)
+      ~block_ext_default:(fun _m b -> Some b)
+      ~block:(fun _m (b : Cmarkit.Block.t) ->
+        if is_unit b && Option.is_some (kind_of_block b)
+        then Cmarkit.Mapper.delete
+        else Cmarkit.Mapper.default)
+      ()
+  in
+  Cmarkit.Mapper.map_doc mapper doc
+;;
+
+(** [source], parsed with locations as [doc], with the lines of its encrypted
+    blocks emptied; the other lines keep their numbers. *)
+let redact ~(doc : Cmarkit.Doc.t) (source : string) : string =
+  let blanked =
+    List.filter_map (find doc) ~f:(fun f -> f.lines)
+    |> List.concat_map ~f:(fun (first, last) -> List.range first (last + 1))
+    |> Int.Set.of_list
+  in
+  if Set.is_empty blanked
+  then source
+  else
+    String.split source ~on:'\n'
+    |> List.mapi ~f:(fun i line -> if Set.mem blanked (i + 1) then "" else line)
+    |> String.concat ~sep:"\n"
+;;
+
+let%expect_test "find, strip and redact" =
+  let source =
+    "# Keys\n\nPublic part.\n\n%%🔐β c2VjcmV0 🔐%%\n\n\
+     ```\n-----BEGIN AGE ENCRYPTED FILE-----\nYWdl\n\
+     -----END AGE ENCRYPTED FILE-----\n```\n\n\
+     - item\n- %%🔐α b2xk 🔐%%\n"
+  in
+  let doc = Cmarkit.Doc.of_string ~locs:true source in
+  print_s [%sexp (find doc : found list)];
+  [%expect
+    {|
+    (((kind Meld_encrypt) (lines ((5 5))))
+     ((kind (Armored "AGE ENCRYPTED FILE")) (lines ((7 11))))
+     ((kind Meld_encrypt) (lines ((14 14)))))
+    |}];
+  print_string (Cmarkit_commonmark.of_doc (strip doc));
+  [%expect
+    {|
+    # Keys
+
+    Public part.
+
+    - item
+    -
+    |}];
+  print_string (redact ~doc source);
+  [%expect
+    {|
+    # Keys
+
+    Public part.
+
+
+
+
+
+
+
+
+
+    - item
+    |}]
+;;
+
+let%expect_test "tables, attributes, footnotes and the bare marker" =
+  let doc =
+    Cmarkit.Doc.of_string
+      ~locs:true
+      ~strict:false
+      ~block_attributes:true
+      ~inline_attributes:true
+      "Locked 🔐 for now, see[^1].\n\n\
+       | key | value |\n|---|---|\n| a | %%🔐β YQ== 🔐%% |\n\n\
+       {.secret}\n%%🔐β Yg== 🔐%%\n\n\
+       Inline [%%🔐α Yw== 🔐%%]{.x} span.\n\n\
+       [^1]: %%🔐β ZA== 🔐%%\n"
+  in
+  List.iter (find doc) ~f:(fun f -> print_s [%sexp (f.kind : kind)]);
+  [%expect
+    {|
+    Meld_encrypt
+    Meld_encrypt
+    Meld_encrypt
+    Meld_encrypt
+    |}];
+  print_string (Cmarkit_commonmark.of_doc (strip doc));
+  [%expect {| Locked 🔐 for now, see[^1]. |}]
+;;
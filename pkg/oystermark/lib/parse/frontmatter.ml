@@ -1,27 +1,76 @@
-(** Strip YAML frontmatter from a markdown string.
+(** Strip frontmatter from a markdown string.
 
-    Frontmatter is delimited by exactly [---] on its own line.
-    The opening delimiter must be the very first line of the file.
-    The closing delimiter is also [---] on its own line.
+    Frontmatter opens on the very first line of the file and is one of
+    - YAML between [---] lines,
+    - TOML between [+++] lines,
+    - a JSON object from a [{] line to a [}] line (the braces are part of it).
 
-    Exact 3 dashes are required in both the opening and closing delimiters.
+    Delimiters must be exact and alone on their line. All three formats are
+    read into the same [Yaml_backend.value]; the {!format} is kept next to it so
+    rendering back to markdown uses the original delimiters.
     *)
 
 open Core
 open Cmarkit
 
-type t = Yaml.value
+type t = Yaml_backend.value
 
-let to_commonmark (fm : Yaml.value) : string = "---\n" ^ Yaml.to_string_exn fm ^ "---\n"
+type format =
+  | Yaml
+  | Toml
+  | Json
+[@@deriving sexp, equal]
 
-type Cmarkit.Block.t += Frontmatter of Yaml.value
+let yaml_of_json (j : Yojson.Basic.t) : Yaml_backend.value =
+  Value.(to_yaml (of_json_basic j))
+;;
+
+let json_of_yaml (v : Yaml_backend.value) : Yojson.Basic.t =
+  Value.(to_json_basic (of_yaml v))
+;;
+
+(** Parse the frontmatter text (between the delimiters, or the whole object for
+    JSON). [None] when it is malformed. *)
+let parse_value (format : format) (text : string) : Yaml_backend.value option =
+  match format with
+  | Yaml -> Result.ok (Yaml_backend.of_string text)
+  | Toml -> Result.ok (Toml_lite.of_string text)
+  | Json ->
+    (match Yojson.Basic.from_string text with
+     | j -> Some (yaml_of_json j)
+     | exception Yojson.Json_error _ -> None)
+;;
+
+let to_commonmark ?(format : format = Yaml) (fm : Yaml_backend.value) : string =
+  match format with
+  | Yaml -> "---\n" ^ Yaml_backend.to_string_exn fm ^ "---\n"
+  | Toml -> "+++\n" ^ Toml_lite.to_string fm ^ "+++\n"
+  | Json ->
+    (* one field per line, so that the braces are alone on theirs as
+       {!of_string} needs; frontmatter that isn't an object can't be JSON
+       frontmatter, and is written as YAML *)
+    (match json_of_yaml fm with
+     | `Assoc fields ->
+       "{\n"
+       ^ String.concat
+           ~sep:",\n"
+           (List.map fields ~f:(fun (k, v) ->
+              sprintf
+                "  %s: %s"
+                (Yojson.Basic.to_string (`String k))
+                (Yojson.Basic.to_string v)))
+       ^ "\n}\n"
+     | _ -> "---\n" ^ Yaml_backend.to_string_exn fm ^ "---\n")
+;;
+
+type Cmarkit.Block.t += Frontmatter of Yaml_backend.value * format
 
 let block_commonmark_renderer : Cmarkit_renderer.block =
   let open Cmarkit_renderer in
   fun (c : context) (b : Block.t) ->
     match b with
-    | Frontmatter y ->
-      Context.string c (to_commonmark y);
+    | Frontmatter (y, format) ->
+      Context.string c (to_commonmark ~format y);
       true
     | _ -> false
 ;;
@@ -29,68 +78,92 @@ let block_commonmark_renderer : Cmarkit_renderer.block =
 let sexp_of_block : Common.block_sexp =
   fun ~recurse_inline:_ ~recurse_block:_ ~with_meta:_ b ->
   match b with
-  | Frontmatter _ -> Some (Sexp.Atom "Frontmatter")
+  | Frontmatter (_, Yaml) -> Some (Sexp.Atom "Frontmatter")
+  | Frontmatter (_, format) ->
+    Some (Sexp.List [ Atom "Frontmatter"; sexp_of_format format ])
   | _ -> None
 ;;
 
-let make_block_mapper (f : Yaml.value -> Yaml.value option)
+let make_block_mapper (f : Yaml_backend.value -> Yaml_backend.value option)
   : Cmarkit.Block.t Cmarkit.Mapper.mapper
   =
   let open Cmarkit in
   fun (_m : Mapper.t) (block : Block.t) ->
     match block with
-    | Frontmatter y ->
+    | Frontmatter (y, format) ->
       (match f y with
-       | Some y -> Mapper.ret (Frontmatter y)
+       | Some y -> Mapper.ret (Frontmatter (y, format))
        | None -> Mapper.ret (Block.Blocks ([], Meta.none)))
     | other -> Mapper.default
 ;;
 
-let delimiter : string = "---"
-let is_delimiter (line : string) : bool = String.equal (String.rstrip line) delimiter
+let format_of_opening (line : string) : format option =
+  match String.rstrip line with
+  | "---" -> Some Yaml
+  | "+++" -> Some Toml
+  | "{" -> Some Json
+  | _ -> None
+;;
+
+let is_closing (format : format) (line : string) : bool =
+  String.equal
+    (String.rstrip line)
+    (match format with
+     | Yaml -> "---"
+     | Toml -> "+++"
+     | Json -> "}")
+;;
+
+(* The frontmatter text given the lines between the delimiters. *)
+let text_of_lines (format : format) (lines : string list) : string =
+  match format with
+  | Yaml | Toml -> String.concat ~sep:"\n" lines
+  | Json -> String.concat ~sep:"\n" (("{" :: lines) @ [ "}" ])
+;;
 
-(** [of_string s] splits [s] into frontmatter YAML and the remaining body.
-    If [s] does not start with [---], returns [{ yaml = None; body = s }]. *)
-let of_string (s : string) : Yaml.value option * string =
+(** Format of the frontmatter [s] opens with, judged by its first line only. *)
+let format_of_string (s : string) : format option =
+  let first_line = Option.value_map (String.lsplit2 s ~on:'\n') ~default:s ~f:fst in
+  format_of_opening first_line
+;;
+
+(** [of_string s] splits [s] into the frontmatter value and the remaining body.
+    If [s] does not start with a frontmatter delimiter, returns [(None, s)]. *)
+let of_string (s : string) : Yaml_backend.value option * string =
   match String.lsplit2 s ~on:'\n' with
   | None -> None, s
   | Some (first_line, rest) ->
-    if not (is_delimiter first_line)
-    then None, s
-    else (
-      (* Find the closing delimiter *)
-      let lines = String.split_lines rest in
-      let rec find_close (acc : string list) (remaining : string list)
-        : Yaml.value option * string
-        =
-        match remaining with
-        | [] ->
-          (* No closing delimiter found — treat everything as body *)
-          None, s
-        | line :: tl ->
-          if is_delimiter line
-          then (
-            let yaml_str = String.concat ~sep:"\n" (List.rev acc) in
-            let yaml =
-              match Yaml.of_string yaml_str with
-              | Ok v -> Some v
-              | Error _ -> None
-            in
-            let body = String.concat ~sep:"\n" tl in
-            yaml, body)
-          else find_close (line :: acc) tl
-      in
-      find_close [] lines)
+    (match format_of_opening first_line with
+     | None -> None, s
+     | Some format ->
+       (* Find the closing delimiter *)
+       let lines = String.split_lines rest in
+       let rec find_close (acc : string list) (remaining : string list)
+         : Yaml_backend.value option * string
+         =
+         match remaining with
+         | [] ->
+           (* No closing delimiter found — treat everything as body *)
+           None, s
+         | line :: tl ->
+           if is_closing format line
+           then (
+             let value = parse_value format (text_of_lines format (List.rev acc)) in
+             let body = String.concat ~sep:"\n" tl in
+             value, body)
+           else find_close (line :: acc) tl
+       in
+       find_close [] lines)
 ;;
 
-(** [blank_frontmatter s] is [(yaml, input)] where [input] is [s] with any
+(** [blank_frontmatter s] is [(value, input)] where [input] is [s] with any
     leading frontmatter block replaced by whitespace — each non-newline byte
     becomes a space, newlines are kept — so every byte and line position is
     preserved. Parsing [input] instead of the stripped {!of_string} body keeps
     the parsed AST's [Cmarkit.Textloc] offsets aligned with the {e original}
     file, which LSP positions depend on. When [s] has no (closed) frontmatter,
     [input] is [s] unchanged. *)
-let blank_frontmatter (s : string) : Yaml.value option * string =
+let blank_frontmatter (s : string) : Yaml_backend.value option * string =
   let n = String.length s in
   let line_end pos =
     match String.index_from s pos '\n' with
@@ -98,35 +171,35 @@ let blank_frontmatter (s : string) : Yaml.value option * string =
     | None -> n
   in
   let first_end = line_end 0 in
-  if first_end >= n || not (is_delimiter (String.sub s ~pos:0 ~len:first_end))
-  then None, s
-  else (
-    let rec find_close pos yaml_lines =
+  let opening : format option =
+    if first_end >= n
+    then None
+    else format_of_opening (String.sub s ~pos:0 ~len:first_end)
+  in
+  match opening with
+  | None -> None, s
+  | Some format ->
+    let rec find_close pos lines =
       if pos >= n
       then None (* reached end without a closing delimiter *)
       else (
         let e = line_end pos in
         let line = String.sub s ~pos ~len:(e - pos) in
-        if is_delimiter line
+        if is_closing format line
         then (
           let body_start = if e < n then e + 1 else n in
-          let yaml_str = String.concat ~sep:"\n" (List.rev yaml_lines) in
-          let yaml =
-            match Yaml.of_string yaml_str with
-            | Ok v -> Some v
-            | Error _ -> None
-          in
-          Some (yaml, body_start))
-        else find_close (e + 1) (line :: yaml_lines))
+          let value = parse_value format (text_of_lines format (List.rev lines)) in
+          Some (value, body_start))
+        else find_close (e + 1) (line :: lines))
     in
-    match find_close (first_end + 1) [] with
-    | None -> None, s (* unclosed: whole string is body, matching {!of_string} *)
-    | Some (yaml, body_start) ->
-      let b = Bytes.of_string s in
-      for i = 0 to body_start - 1 do
-        if not (Char.equal (Bytes.get b i) '\n') then Bytes.set b i ' '
-      done;
-      yaml, Bytes.to_string b)
+    (match find_close (first_end + 1) [] with
+     | None -> None, s (* unclosed: whole string is body, matching {!of_string} *)
+     | Some (value, body_start) ->
+       let b = Bytes.of_string s in
+       for i = 0 to body_start - 1 do
+         if not (Char.equal (Bytes.get b i) '\n') then Bytes.set b i ' '
+       done;
+       value, Bytes.to_string b)
 ;;
 
 let escape_html (s : string) : string =
@@ -136,7 +209,7 @@ let escape_html (s : string) : string =
 ;;
 
 (** Render a YAML value as an HTML fragment. *)
-let rec value_to_html (v : Yaml.value) : string =
+let rec value_to_html (v : Yaml_backend.value) : string =
   match v with
   | `Null -> ""
   | `Bool b -> escape_html (Bool.to_string b)
@@ -158,20 +231,43 @@ let rec value_to_html (v : Yaml.value) : string =
 
 (** Render frontmatter as an HTML table wrapped in a frontmatter div.
     Returns empty string if there is no frontmatter. *)
-let to_html (fm : Yaml.value option) : string =
+let to_html (fm : Yaml_backend.value option) : string =
   match fm with
   | None | Some `Null -> ""
   | Some v -> value_to_html v
 ;;
 
-(** Extract the frontmatter value from a doc's top-level block, if present. *)
-let of_doc (doc : Cmarkit.Doc.t) : Yaml.value option =
+let with_format_of_doc (doc : Cmarkit.Doc.t) : (Yaml_backend.value * format) option =
   match Cmarkit.Doc.block doc with
   | Cmarkit.Block.Blocks (blocks, _) ->
     (match blocks with
-     | Frontmatter y :: _ -> Some y
+     | Frontmatter (y, format) :: _ -> Some (y, format)
      | _ -> None)
-  | Frontmatter y -> Some y
+  | Frontmatter (y, format) -> Some (y, format)
+  | _ -> None
+;;
+
+(** Extract the frontmatter value from a doc's top-level block, if present. *)
+let of_doc (doc : Cmarkit.Doc.t) : Yaml_backend.value option =
+  Option.map (with_format_of_doc doc) ~f:fst
+;;
+
+(** The format the doc's frontmatter was written in, if it has one. *)
+let format_of_doc (doc : Cmarkit.Doc.t) : format option =
+  Option.map (with_format_of_doc doc) ~f:snd
+;;
+
+(** Typed value of the top-level frontmatter field [key] in [doc]. *)
+let field (doc : Cmarkit.Doc.t) (key : string) : Value.t option =
+  match Option.map (of_doc doc) ~f:Value.of_yaml with
+  | Some (Value.Map fields) -> List.Assoc.find fields ~equal:String.equal key
+  | _ -> None
+;;
+
+(** Frontmatter field [key] in [doc] as a date, e.g. [date: 2024-05-01]. *)
+let date_field (doc : Cmarkit.Doc.t) (key : string) : Timestamp.t option =
+  match field doc key with
+  | Some (Value.Date d) -> Some d
   | _ -> None
 ;;
 
@@ -179,7 +275,7 @@ module For_test = struct
   let to_string (v : t option) : string =
     match v with
     | None -> "<none>"
-    | Some v -> Yaml.to_string_exn v
+    | Some v -> Yaml_backend.to_string_exn v
   ;;
 
   let%expect_test "no frontmatter" =
@@ -233,6 +329,72 @@ module For_test = struct
     |}]
   ;;
 
+  let%expect_test "toml and json frontmatter" =
+    let show (s : string) =
+      let value, body = of_string s in
+      Printf.printf "yaml: %sbody: %s\n" (to_string value) body
+    in
+    show "+++\ntitle = \"Hello\"\ntags = [\"a\", \"b\"]\n+++\n# Body";
+    [%expect
+      {|
+    yaml: title: Hello
+    tags:
+    - a
+    - b
+    body: # Body
+    |}];
+    show "{\n  \"title\": \"Hello\",\n  \"draft\": true\n}\n# Body";
+    [%expect
+      {|
+    yaml: title: Hello
+    draft: true
+    body: # Body
+    |}];
+    let value = `O [ "title", `String "Hello"; "weight", `Float 2. ] in
+    List.iter [ Yaml; Toml; Json ] ~f:(fun format ->
+      print_string (to_commonmark ~format value));
+    [%expect
+      {|
+    ---
+    title: Hello
+    weight: 2
+    ---
+    +++
+    title = "Hello"
+    weight = 2
+    +++
+    {
+      "title": "Hello",
+      "weight": 2
+    }
+    |}]
+  ;;
+
+  let%expect_test "to_commonmark round-trips through of_string" =
+    let value : Yaml_backend.value =
+      `O
+        [ "title", `String "[draft] #1 {wip}"
+        ; "tags", `A [ `String "a]"; `String "{b" ]
+        ; "draft", `Bool true
+        ; "weight", `Float 1.5
+        ; "author", `O [ "name", `String "K"; "links", `A [ `String "x" ] ]
+        ]
+    in
+    List.iter [ Yaml; Toml; Json ] ~f:(fun format ->
+      let parsed, body = of_string (to_commonmark ~format value ^ "# Body") in
+      Printf.printf
+        "%s: %b %s\n"
+        (Sexp.to_string (sexp_of_format format))
+        (Poly.equal parsed (Some value))
+        body);
+    [%expect
+      {|
+    Yaml: true # Body
+    Toml: true # Body
+    Json: true # Body
+    |}]
+  ;;
+
   (* [blank_frontmatter] preserves byte and line positions: the frontmatter
      region becomes whitespace (newlines kept), the body is byte-identical, and
      the total length is unchanged. Shown with [|] markers around the result. *)
@@ -0,0 +1,25 @@
+(** GitHub alerts: [> [!NOTE]], [> [!TIP]], [> [!IMPORTANT]], [> [!WARNING]]
+    and [> [!CAUTION]] on their own first line, body below.
+
+    They share the callout syntax, so the parser already reads them as
+    callouts; only the kind is written upper case. Callout kinds are
+    case-insensitive (as in Obsidian), and {!callout_kind} is the one spelling
+    that rendering and queries use, which is also what the theme's
+    [data-callout] rules match. All five alert kinds have a callout style. *)
+
+open Core
+
+(** Canonical callout kind for a kind as written in [[!kind]]. *)
+let callout_kind (kind : string) : string = String.lowercase kind
+
+let%expect_test "callout_kind" =
+  List.iter [ "NOTE"; "Tip"; "caution"; "faq" ] ~f:(fun k ->
+    printf "%s -> %s\n" k (callout_kind k));
+  [%expect
+    {|
+    NOTE -> note
+    Tip -> tip
+    caution -> caution
+    faq -> faq
+    |}]
+;;
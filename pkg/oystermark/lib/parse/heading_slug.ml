@@ -9,14 +9,43 @@ open Core
 
 let meta_key : string Cmarkit.Meta.key = Cmarkit.Meta.key ()
 
+(** How a heading is written: [# Title] or a title underlined with [===]/[---].
+    Carried by the heading's [Cmarkit.Block.Heading.layout], which every
+    rewrite of a heading must keep so rendering back to markdown preserves it. *)
+type style =
+  | Atx
+  | Setext
+[@@deriving sexp, equal]
+
+let style_of (h : Cmarkit.Block.Heading.t) : style =
+  match Cmarkit.Block.Heading.layout h with
+  | `Atx _ -> Atx
+  | `Setext _ -> Setext
+;;
+
 let sexp_of_meta : Common.meta_sexp =
   fun meta ->
   Cmarkit.Meta.find meta_key meta
   |> Option.map ~f:(fun slug -> Sexp.List [ Atom "heading-slug"; Atom slug ])
 ;;
 
-(** GitHub-style slug: lowercase, non-alphanum to [-], collapse runs, strip edges. *)
-let slugify (s : string) : string =
+(** How anchors are derived from heading text. *)
+type slug_style =
+  | Github
+  (** lowercase; anything but letters, digits, [-] and [_] becomes [-]; runs of
+      [-] collapse and edges are stripped: [Hello, World!] is [hello-world] *)
+  | Obsidian
+  (** case and punctuation kept, except characters a wikilink heading can't
+      contain ([#], [^], [[], []], [|]); whitespace runs become one [-]:
+      [Hello, World!] is [Hello,-World!] *)
+  | Custom of (string -> string)
+
+let of_config : Config.Slug_style.t -> slug_style = function
+  | Config.Slug_style_def.Github -> Github
+  | Config.Slug_style_def.Obsidian -> Obsidian
+;;
+
+let github_slug (s : string) : string =
   s
   |> String.lowercase
   |> String.map ~f:(fun c ->
@@ -26,32 +55,79 @@ let slugify (s : string) : string =
   |> String.concat ~sep:"-"
 ;;
 
-(** Compute a deduplicated slug. [seen] tracks base slug -> count. *)
-let dedup_slug (seen : (string, int) Hashtbl.t) (text : string) : string =
-  let base : string = slugify text in
+let obsidian_slug (s : string) : string =
+  s
+  |> String.filter ~f:(fun c -> not (String.mem "#^[]|" c))
+  |> String.split_on_chars ~on:[ ' '; '\t'; '\n'; '\r' ]
+  |> List.filter ~f:(fun s -> not (String.is_empty s))
+  |> String.concat ~sep:"-"
+;;
+
+(** Slug of heading text [s] under [style] (default {!Github}), without
+    deduplication. Every subsystem that needs an anchor (HTML export, TOC,
+    LSP, query) goes through this or the slug stamped by {!mk_block_map}. *)
+let slugify ?(style : slug_style = Github) (s : string) : string =
+  match style with
+  | Github -> github_slug s
+  | Obsidian -> obsidian_slug s
+  | Custom f -> f s
+;;
+
+(** Compute a deduplicated slug. [seen] tracks base slug -> count; the second
+    heading with the same base gets [-1], the third [-2], and so on. *)
+let dedup_slug
+      ?(style : slug_style option)
+      (seen : (string, int) Hashtbl.t)
+      (text : string)
+  : string
+  =
+  let base : string = slugify ?style text in
   let count : int = Hashtbl.find seen base |> Option.value ~default:0 in
   Hashtbl.set seen ~key:base ~data:(count + 1);
   if count = 0 then base else sprintf "%s-%d" base count
 ;;
 
+(** The slug stamped on a heading with [meta] by {!mk_block_map}; for a
+    heading of a doc not parsed by [Parse.of_string], the slug of its [text]
+    under [style], undeduplicated. *)
+let of_meta ?(style : slug_style option) (meta : Cmarkit.Meta.t) (text : string)
+  : string
+  =
+  match Cmarkit.Meta.find meta_key meta with
+  | Some slug -> slug
+  | None -> slugify ?style text
+;;
+
+(** Deduplicated slugs of [texts], in order, as headings of one document. *)
+let slugify_all ?(style : slug_style option) (texts : string list) : string list =
+  let seen = Hashtbl.create (module String) in
+  List.map texts ~f:(dedup_slug ?style seen)
+;;
+
+(** [~ext] of {!Cmarkit.Inline.to_plain_text} for oystermark's inline
+    extensions: a wikilink gives its display text, an attributed span its
+    content, and any other extension nothing. It never hands an extension node
+    back, which [to_plain_text] would pass to it again, forever. *)
+let plain_text_ext ~break_on_soft:(_ : bool) (inline : Cmarkit.Inline.t)
+  : Cmarkit.Inline.t
+  =
+  match inline with
+  | Cmarkit.Inline.Ext_wikilink (wl, _meta) ->
+    Cmarkit.Inline.Text (Cmarkit.Inline.Wikilink.to_plain_text wl, Cmarkit.Meta.none)
+  | Cmarkit.Inline.Ext_attributes (a, _meta) -> Cmarkit.Inline.Attributes.inline a
+  | _ -> Cmarkit.Inline.Text ("", Cmarkit.Meta.none)
+;;
+
 (** Render inlines to plain text, losing their markdown syntax. Used in rendering
     heading to plain text. *)
 let inline_to_plain_text (inline : Cmarkit.Inline.t) : string =
   let lines =
-    Cmarkit.Inline.to_plain_text
-      ~ext:(fun ~break_on_soft inline ->
-        match inline with
-        | Cmarkit.Inline.Ext_wikilink (wl, _meta) ->
-          let text = Cmarkit.Inline.Wikilink.to_plain_text wl in
-          Cmarkit.Inline.Text (text, Cmarkit.Meta.none)
-        | other -> other)
-      ~break_on_soft:false
-      inline
+    Cmarkit.Inline.to_plain_text ~ext:plain_text_ext ~break_on_soft:false inline
   in
   String.concat ~sep:"\n" (List.map lines ~f:(String.concat ~sep:""))
 ;;
 
-let mk_block_map () : Cmarkit.Block.t Cmarkit.Mapper.mapper =
+let mk_block_map ?(style : slug_style option) () : Cmarkit.Block.t Cmarkit.Mapper.mapper =
   let open Cmarkit.Mapper in
   let slug_seen = Hashtbl.create (module String) in
   fun (m : t) (b : Cmarkit.Block.t) ->
@@ -62,7 +138,7 @@ let mk_block_map () : Cmarkit.Block.t Cmarkit.Mapper.mapper =
         Cmarkit.Mapper.map_inline m orig_inline |> Option.value ~default:orig_inline
       in
       let text = inline_to_plain_text mapped_inline in
-      let slug = dedup_slug slug_seen text in
+      let slug = dedup_slug ?style slug_seen text in
       let meta' = Cmarkit.Meta.add meta_key slug meta in
       let h' =
         Cmarkit.Block.Heading.make
@@ -74,3 +150,15 @@ let mk_block_map () : Cmarkit.Block.t Cmarkit.Mapper.mapper =
       ret (Cmarkit.Block.Heading (h', meta'))
     | _ -> Cmarkit.Mapper.default
 ;;
+
+let%expect_test "slugify" =
+  let texts = [ "Hello, World!"; "Hello, World!"; "API [v2] #notes"; "Ünïcode" ] in
+  List.iter [ Github; Obsidian; Custom String.uppercase ] ~f:(fun style ->
+    print_endline (String.concat ~sep:" | " (slugify_all ~style texts)));
+  [%expect
+    {|
+    hello-world | hello-world-1 | api-v2-notes | n-code
+    Hello,-World! | Hello,-World!-1 | API-v2-notes | Ünïcode
+    HELLO, WORLD! | HELLO, WORLD!-1 | API [V2] #NOTES | ÜNïCODE
+    |}]
+;;
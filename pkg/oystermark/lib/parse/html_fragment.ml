@@ -0,0 +1,314 @@
+(** Raw HTML as a sequence of tags, comments and text.
+
+    Cmarkit keeps raw HTML as opaque source lines: a whole HTML block, or a
+    single tag or comment for inline HTML. This reads that text into pieces so
+    the export can apply a policy ({!Config.Raw_html}) without a full HTML
+    parser. It is lenient: anything that does not read as a tag is text. *)
+
+open Core
+
+type attr =
+  { name : string (** lowercased *)
+  ; value : string option
+  }
+[@@deriving sexp_of]
+
+type tag =
+  { name : string (** lowercased *)
+  ; attrs : attr list
+  ; closing : bool (** [</name>] *)
+  ; self_closing : bool (** [<name ... />] *)
+  }
+[@@deriving sexp_of]
+
+type piece =
+  | Tag of tag
+  | Comment of string (** [<!-- ... -->], also doctypes and CDATA *)
+  | Text of string
+[@@deriving sexp_of]
+
+type t = piece list [@@deriving sexp_of]
+
+let is_name_char (c : char) : bool = Char.is_alphanum c || Char.equal c '-'
+
+let is_attr_name_char (c : char) : bool =
+  Char.is_alphanum c || List.mem [ '-'; '_'; ':'; '.' ] c ~equal:Char.equal
+;;
+
+(* Read a tag starting at [pos] (on its [<]); the tag and the position after
+   its [>]. *)
+let read_tag (s : string) (pos : int) : (tag * int) option =
+  let n = String.length s in
+  let rec skip_ws i = if i < n && Char.is_whitespace s.[i] then skip_ws (i + 1) else i in
+  let rec take_while i ~f = if i < n && f s.[i] then take_while (i + 1) ~f else i in
+  let closing = pos + 1 < n && Char.equal s.[pos + 1] '/' in
+  let name_start = if closing then pos + 2 else pos + 1 in
+  let name_end = take_while name_start ~f:is_name_char in
+  if name_end = name_start || not (Char.is_alpha s.[name_start])
+  then None
+  else (
+    let name =
+      String.lowercase (String.sub s ~pos:name_start ~len:(name_end - name_start))
+    in
+    let rec attrs i acc =
+      let i = skip_ws i in
+      if i >= n
+      then None
+      else if Char.equal s.[i] '>'
+      then Some (List.rev acc, false, i + 1)
+      else if Char.equal s.[i] '/' && i + 1 < n && Char.equal s.[i + 1] '>'
+      then Some (List.rev acc, true, i + 2)
+      else (
+        let a_end = take_while i ~f:is_attr_name_char in
+        if a_end = i
+        then None
+        else (
+          let a_name = String.lowercase (String.sub s ~pos:i ~len:(a_end - i)) in
+          let j = skip_ws a_end in
+          if j < n && Char.equal s.[j] '='
+          then (
+            let k = skip_ws (j + 1) in
+            if k >= n
+            then None
+            else (
+              match s.[k] with
+              | ('"' | '\'') as q ->
+                (match String.index_from s (k + 1) q with
+                 | None -> None
+                 | Some close ->
+                   let value = String.sub s ~pos:(k + 1) ~len:(close - k - 1) in
+                   attrs (close + 1) ({ name = a_name; value = Some value } :: acc))
+              | _ ->
+                let v_end =
+                  take_while k ~f:(fun c ->
+                    not (Char.is_whitespace c || Char.equal c '>'))
+                in
+                let value = String.sub s ~pos:k ~len:(v_end - k) in
+                attrs v_end ({ name = a_name; value = Some value } :: acc)))
+          else attrs a_end ({ name = a_name; value = None } :: acc)))
+    in
+    match attrs name_end [] with
+    | None -> None
+    | Some (attrs, self_closing, next) ->
+      if closing && not (List.is_empty attrs)
+      then None
+      else Some ({ name; attrs; closing; self_closing }, next))
+;;
+
+(* [<!--...-->], [<!...>] or [<?...?>], starting at [pos]. *)
+let read_comment (s : string) (pos : int) : (string * int) option =
+  let close_at (pattern : string) =
+    String.substr_index s ~pos ~pattern
+    |> Option.map ~f:(fun i ->
+      let next = i + String.length pattern in
+      String.sub s ~pos ~len:(next - pos), next)
+  in
+  if String.is_substring_at s ~pos ~substring:"<!--"
+  then close_at "-->"
+  else if String.is_substring_at s ~pos ~substring:"<!"
+          || String.is_substring_at s ~pos ~substring:"<?"
+  then close_at ">"
+  else None
+;;
+
+let of_string (s : string) : t =
+  let n = String.length s in
+  let text = Buffer.create 16 in
+  let flush acc =
+    if Buffer.length text = 0
+    then acc
+    else (
+      let t = Buffer.contents text in
+      Buffer.clear text;
+      Text t :: acc)
+  in
+  let rec go pos acc =
+    if pos >= n
+    then List.rev (flush acc)
+    else if Char.equal s.[pos] '<'
+    then (
+      match read_comment s pos with
+      | Some (c, next) -> go next (Comment c :: flush acc)
+      | None ->
+        (match read_tag s pos with
+         | Some (tag, next) -> go next (Tag tag :: flush acc)
+         | None ->
+           Buffer.add_char text '<';
+           go (pos + 1) acc))
+    else (
+      Buffer.add_char text s.[pos];
+      go (pos + 1) acc)
+  in
+  go 0 []
+;;
+
+let escape_attr_value (v : string) : string =
+  String.concat_map v ~f:(function
+    | '"' -> "&quot;"
+    | '&' -> "&amp;"
+    | '<' -> "&lt;"
+    | c -> String.of_char c)
+;;
+
+let tag_to_string (tag : tag) : string =
+  if tag.closing
+  then [%string "</%{tag.name}>"]
+  else (
+    let attrs =
+      List.map tag.attrs ~f:(fun a ->
+        match a.value with
+        | None -> " " ^ a.name
+        | Some v -> [%string " %{a.name}=\"%{escape_attr_value v}\""])
+      |> String.concat
+    in
+    let close = if tag.self_closing then " />" else ">" in
+    [%string "<%{tag.name}%{attrs}%{close}"])
+;;
+
+let to_string (t : t) : string =
+  List.map t ~f:(function
+    | Tag tag -> tag_to_string tag
+    | Comment c -> c
+    | Text s -> s)
+  |> String.concat
+;;
+
+(** {1 Sanitizing} *)
+
+(** Tags kept by {!sanitize} unless the caller passes its own list. *)
+let default_allowed_tags : string list =
+  [ "a"; "abbr"; "b"; "br"; "caption"; "cite"; "code"; "dd"; "del"; "details"; "div"
+  ; "dl"; "dt"; "em"; "figcaption"; "figure"; "h1"; "h2"; "h3"; "h4"; "h5"; "h6"
+  ; "hr"; "i"; "img"; "ins"; "kbd"; "li"; "mark"; "ol"; "p"; "pre"; "q"; "s"
+  ; "samp"; "small"; "span"; "strong"; "sub"; "summary"; "sup"; "table"; "tbody"
+  ; "td"; "tfoot"; "th"; "thead"; "tr"; "u"; "ul"
+  ]
+;;
+
+(** Attributes kept on allowed tags. Event handlers ([on*]) and [style] never
+    are. *)
+let allowed_attributes : string list =
+  [ "alt"; "class"; "colspan"; "height"; "href"; "id"; "lang"; "open"; "rowspan"
+  ; "src"; "title"; "width"
+  ]
+;;
+
+(* Tags whose content goes with them rather than surviving as text. *)
+let dropped_with_content : string list =
+  [ "script"; "style"; "iframe"; "object"; "embed"; "template"; "noscript" ]
+;;
+
+(* Schemes kept on links and images; URLs without one are relative. *)
+let safe_schemes : string list = [ "http"; "https"; "mailto"; "tel" ]
+
+(* Whether URL [v], as written in an attribute, is relative or has a scheme in
+   [safe_schemes], or is an inline image. Browsers skip whitespace and control
+   characters anywhere in the scheme and decode character references before
+   reading it, so those characters are dropped first and a [&] before the
+   scheme ends makes the URL unsafe. *)
+let is_safe_url (v : string) : bool =
+  let v =
+    String.filter v ~f:(fun c -> Char.to_int c > 0x20 && Char.to_int c <> 0x7f)
+    |> String.lowercase
+  in
+  let scheme_end =
+    String.lfindi v ~f:(fun _ c ->
+      Char.equal c ':' || Char.equal c '/' || Char.equal c '?' || Char.equal c '#')
+  in
+  let head = String.prefix v (Option.value scheme_end ~default:(String.length v)) in
+  if String.mem head '&'
+  then false
+  else (
+    match scheme_end with
+    | Some i when Char.equal v.[i] ':' ->
+      List.mem safe_schemes head ~equal:String.equal
+      || String.is_prefix v ~prefix:"data:image/"
+    | _ -> true)
+;;
+
+let keep_attr (a : attr) : bool =
+  List.mem allowed_attributes a.name ~equal:String.equal
+  &&
+  match a.name, a.value with
+  | ("href" | "src"), Some v -> is_safe_url v
+  | _ -> true
+;;
+
+(** Keep tags in [allowed_tags] with their allowed attributes, and drop
+    comments and every other tag. Text inside dropped tags survives, except
+    for [script], [style] and similar tags whose content is not prose. *)
+let sanitize ?(allowed_tags : string list = default_allowed_tags) (t : t) : t =
+  let rec go (pieces : t) ~(skipping : string option) (acc : t) : t =
+    match pieces, skipping with
+    | [], _ -> List.rev acc
+    | Tag { name; closing = true; _ } :: rest, Some skipped
+      when String.equal name skipped -> go rest ~skipping:None acc
+    | _ :: rest, Some _ -> go rest ~skipping acc
+    | Comment _ :: rest, None -> go rest ~skipping acc
+    | Text s :: rest, None -> go rest ~skipping (Text s :: acc)
+    | Tag tag :: rest, None ->
+      if List.mem allowed_tags tag.name ~equal:String.equal
+      then (
+        let tag = { tag with attrs = List.filter tag.attrs ~f:keep_attr } in
+        go rest ~skipping (Tag tag :: acc))
+      else if List.mem dropped_with_content tag.name ~equal:String.equal
+              && not (tag.closing || tag.self_closing)
+      then go rest ~skipping:(Some tag.name) acc
+      else go rest ~skipping acc
+  in
+  go t ~skipping:None []
+;;
+
+let%expect_test "of_string and sanitize" =
+  let html =
+    {|<div class="x" onclick="evil()"><img src="a.png" alt=b style="c"><!-- note -->
+<script>alert(1)</script><a href="javascript:x()">link</a><font>kept</font></div>|}
+  in
+  let t = of_string html in
+  print_endline (to_string t);
+  [%expect
+    {|
+    <div class="x" onclick="evil()"><img src="a.png" alt="b" style="c"><!-- note -->
+    <script>alert(1)</script><a href="javascript:x()">link</a><font>kept</font></div>
+    |}];
+  print_endline (to_string (sanitize t));
+  [%expect
+    {|
+    <div class="x"><img src="a.png" alt="b">
+    <a>link</a>kept</div>
+    |}];
+  List.iter
+    [ "https://example.com/a?b&c"
+    ; "../a.html#x"
+    ; "mailto:me@example.com"
+    ; "data:image/png;base64,AAAA"
+    ; "java\tscript:x()"
+    ; "\x01javascript:x()"
+    ; "JavaScript:x()"
+    ; "javascript&colon;x()"
+    ; "&#106;avascript:x()"
+    ; "data:text/html,<b>"
+    ; "file:///etc/passwd"
+    ]
+    ~f:(fun url -> printf "%b %S\n" (is_safe_url url) url);
+  [%expect
+    {|
+    true "https://example.com/a?b&c"
+    true "../a.html#x"
+    true "mailto:me@example.com"
+    true "data:image/png;base64,AAAA"
+    false "java\tscript:x()"
+    false "\001javascript:x()"
+    false "JavaScript:x()"
+    false "javascript&colon;x()"
+    false "&#106;avascript:x()"
+    false "data:text/html,<b>"
+    false "file:///etc/passwd"
+    |}];
+  print_s [%sexp (of_string "a < b <br/>" : t)];
+  [%expect
+    {|
+    ((Text "a < b ")
+     (Tag ((name br) (attrs ()) (closing false) (self_closing true))))
+    |}]
+;;
@@ -0,0 +1,283 @@
+(** Incremental reparsing for editors: keep data derived from each top-level
+    block of a document, and after a small edit reparse only the block it
+    falls in.
+
+    The derived data (links, diagnostics, ...) carries absolute byte offsets,
+    so the caller supplies [extract] to compute it from one top-level block of
+    a document and [shift] to move it by a byte delta. Blocks after the edit
+    are only shifted. The LSP's diagnostics are kept this way between edits.
+
+    An edit is handled incrementally only when it cannot change block
+    boundaries: it stays within one line of one top-level block, neither
+    removes nor inserts a newline, the block is set off by blank lines (or the
+    document edges), the block is not a heading (whose slug is deduplicated
+    against the ones before it, and changes theirs after it), and reparsing
+    the block alone gives back one block of the same kind. The document must
+    also define no link labels or footnotes, since a lone block cannot see
+    them. Anything else is a full reparse. *)
+
+open Core
+
+type 'a chunk =
+  { first_byte : int (** start of the block's first line *)
+  ; last_byte : int (** last byte of the block, before its final newline *)
+  ; kind : string
+  ; data : 'a
+  }
+
+type 'a t =
+  { source : string
+  ; chunks : 'a chunk list
+  ; link_defs : bool (** the document defines link labels or footnotes *)
+  }
+
+let source (t : 'a t) : string = t.source
+let data (t : 'a t) : 'a list = List.map t.chunks ~f:(fun c -> c.data)
+
+let kind_of_block (b : Cmarkit.Block.t) : string =
+  match b with
+  | Cmarkit.Block.Blank_line _ -> "blank"
+  | Block_quote _ -> "block_quote"
+  | Blocks _ -> "blocks"
+  | Code_block _ -> "code_block"
+  | Heading _ -> "heading"
+  | Html_block _ -> "html_block"
+  | Link_reference_definition _ -> "link_reference_definition"
+  | List _ -> "list"
+  | Paragraph _ -> "paragraph"
+  | Thematic_break _ -> "thematic_break"
+  | _ -> "ext"
+;;
+
+let top_level_blocks (doc : Cmarkit.Doc.t) : Cmarkit.Block.t list =
+  match Cmarkit.Doc.block doc with
+  | Cmarkit.Block.Blocks (bs, _) -> bs
+  | b -> [ b ]
+;;
+
+let line_start (s : string) (byte : int) : int =
+  if byte <= 0
+  then 0
+  else (
+    match String.rindex_from s (byte - 1) '\n' with
+    | Some i -> i + 1
+    | None -> 0)
+;;
+
+(* Blocks with a location, as chunks whose offsets are relative to [base]. *)
+let chunks_of_doc
+      ~(extract : Cmarkit.Doc.t -> Cmarkit.Block.t -> 'a)
+      ~(source : string)
+      ?(base : int = 0)
+      (doc : Cmarkit.Doc.t)
+  : 'a chunk list
+  =
+  List.filter_map (top_level_blocks doc) ~f:(fun b ->
+    let meta = Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) b in
+    let tl = Cmarkit.Meta.textloc meta in
+    match b with
+    | Cmarkit.Block.Blank_line _ -> None
+    | _ when Cmarkit.Textloc.is_none tl -> None
+    | _ ->
+      let first = Cmarkit.Textloc.first_byte tl in
+      Some
+        { first_byte = base + line_start source first
+        ; last_byte = base + Cmarkit.Textloc.last_byte tl
+        ; kind = kind_of_block b
+        ; data = extract doc b
+        })
+;;
+
+(** Parse [source] in full with [parse] and derive each block's data with
+    [extract doc block]. *)
+let build
+      ~(parse : string -> Cmarkit.Doc.t)
+      ~(extract : Cmarkit.Doc.t -> Cmarkit.Block.t -> 'a)
+      (source : string)
+  : 'a t
+  =
+  let doc = parse source in
+  let link_defs = not (Cmarkit.Label.Map.is_empty (Cmarkit.Doc.defs doc)) in
+  { source; chunks = chunks_of_doc ~extract ~source doc; link_defs }
+;;
+
+(** The single splice turning [old_source] into [new_source]: the bytes between
+    their common prefix and common suffix. *)
+let edit_of_diff (old_source : string) (new_source : string) : Source_edit.splice =
+  let n_old = String.length old_source
+  and n_new = String.length new_source in
+  let rec prefix i =
+    if i < n_old && i < n_new && Char.equal old_source.[i] new_source.[i]
+    then prefix (i + 1)
+    else i
+  in
+  let p = prefix 0 in
+  let rec suffix i =
+    if i < n_old - p
+       && i < n_new - p
+       && Char.equal old_source.[n_old - 1 - i] new_source.[n_new - 1 - i]
+    then suffix (i + 1)
+    else i
+  in
+  let s = suffix 0 in
+  { first_byte = p
+  ; last_byte = n_old - s - 1
+  ; text = String.sub new_source ~pos:p ~len:(n_new - s - p)
+  }
+;;
+
+let is_blank_line_before (s : string) (first_byte : int) : bool =
+  first_byte = 0
+  ||
+  let prev_end = first_byte - 1 in
+  let prev_start = line_start s prev_end in
+  String.for_all
+    (String.sub s ~pos:prev_start ~len:(prev_end - prev_start))
+    ~f:Char.is_whitespace
+;;
+
+let is_blank_line_after (s : string) (last_byte : int) : bool =
+  let n = String.length s in
+  match String.index_from s (last_byte + 1) '\n' with
+  | None -> true
+  | Some nl ->
+    let next_start = nl + 1 in
+    next_start >= n
+    ||
+    let next_end = Option.value (String.index_from s next_start '\n') ~default:n in
+    String.for_all
+      (String.sub s ~pos:next_start ~len:(next_end - next_start))
+      ~f:Char.is_whitespace
+;;
+
+type how =
+  | Incremental of { reparsed_bytes : int }
+  | Full
+[@@deriving sexp_of]
+
+(** Apply [edit] (a splice on [t]'s source) and bring the derived data up to
+    date, reparsing as little as the rules above allow. *)
+let update
+      (t : 'a t)
+      ~(parse : string -> Cmarkit.Doc.t)
+      ~(extract : Cmarkit.Doc.t -> Cmarkit.Block.t -> 'a)
+      ~(shift : int -> 'a -> 'a)
+      (edit : Source_edit.splice)
+  : 'a t * how
+  =
+  let new_source : string =
+    String.prefix t.source edit.first_byte
+    ^ edit.text
+    ^ String.drop_prefix t.source (edit.last_byte + 1)
+  in
+  let delta : int = String.length edit.text - (edit.last_byte - edit.first_byte + 1) in
+  let full () = build ~parse ~extract new_source, Full in
+  let removed : string =
+    String.sub t.source ~pos:edit.first_byte ~len:(edit.last_byte - edit.first_byte + 1)
+  in
+  let target =
+    List.find t.chunks ~f:(fun c ->
+      (* Typing right after the block's last byte still extends it *)
+      c.first_byte <= edit.first_byte && edit.last_byte <= c.last_byte)
+  in
+  match target with
+  | None -> full ()
+  | Some c
+    when t.link_defs
+         || String.equal c.kind "heading"
+         || String.mem removed '\n'
+         || String.mem edit.text '\n' -> full ()
+  | Some c
+    when not
+           (is_blank_line_before t.source c.first_byte
+            && is_blank_line_after t.source c.last_byte) -> full ()
+  | Some c ->
+    let last_byte = c.last_byte + delta in
+    let text =
+      String.sub new_source ~pos:c.first_byte ~len:(last_byte - c.first_byte + 1)
+    in
+    if Option.is_some (Frontmatter.format_of_string text)
+    then full ()
+    else (
+      match chunks_of_doc ~extract ~source:text ~base:c.first_byte (parse text) with
+      | [ fresh ]
+        when String.equal fresh.kind c.kind
+             && fresh.first_byte = c.first_byte
+             && fresh.last_byte = last_byte ->
+        let chunks =
+          List.map t.chunks ~f:(fun other ->
+            if other.first_byte < c.first_byte
+            then other
+            else if other.first_byte = c.first_byte
+            then { fresh with data = shift c.first_byte fresh.data }
+            else
+              { other with
+                first_byte = other.first_byte + delta
+              ; last_byte = other.last_byte + delta
+              ; data = shift delta other.data
+              })
+        in
+        ( { t with source = new_source; chunks }
+        , Incremental { reparsed_bytes = String.length text } )
+      | _ -> full ())
+;;
+
+let%expect_test "update" =
+  let parse (s : string) = Cmarkit.Doc.of_string ~locs:true s in
+  (* Derived data: the block's absolute byte range *)
+  let extract (_ : Cmarkit.Doc.t) (b : Cmarkit.Block.t) : int * int =
+    let meta = Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) b in
+    let tl = Cmarkit.Meta.textloc meta in
+    Cmarkit.Textloc.first_byte tl, Cmarkit.Textloc.last_byte tl
+  in
+  let shift (d : int) ((a, b) : int * int) = a + d, b + d in
+  let filler = String.concat ~sep:"\n" (List.init 200 ~f:(fun _ -> "Filler text.")) in
+  let src = String.concat ~sep:"\n\n" [ "# Title"; "A paragraph."; filler ] ^ "\n" in
+  let t = build ~parse ~extract src in
+  let check (t : (int * int) t) (edit : Source_edit.splice) =
+    let t', how = update t ~parse ~extract ~shift edit in
+    let full = build ~parse ~extract (source t') in
+    print_s [%sexp (how : how)];
+    let same : bool = [%equal: (int * int) list] (data t') (data full) in
+    printf "same as full reparse: %b\n" same;
+    t'
+  in
+  (* Typing one character in the paragraph: only its 13 bytes are reparsed *)
+  let t = check t { first_byte = 10; last_byte = 9; text = "n" } in
+  [%expect
+    {|
+    (Incremental (reparsed_bytes 13))
+    same as full reparse: true
+    |}];
+  (* A newline may split the block *)
+  let (_ : _ t) = check t { first_byte = 12; last_byte = 11; text = "\n\n" } in
+  [%expect
+    {|
+    Full
+    same as full reparse: true
+    |}];
+  (* Deleting [# ] turns the heading into a paragraph *)
+  let (_ : _ t) = check t { first_byte = 0; last_byte = 1; text = "" } in
+  [%expect
+    {|
+    Full
+    same as full reparse: true
+    |}];
+  (* A heading's slug depends on the headings before it *)
+  let (_ : _ t) = check t { first_byte = 7; last_byte = 6; text = "s" } in
+  [%expect
+    {|
+    Full
+    same as full reparse: true
+    |}];
+  (* A reference only parses as one next to its definition *)
+  let t = build ~parse ~extract "Text.[^1]\n\n[^1]: Note.\n" in
+  let (_ : _ t) = check t { first_byte = 0; last_byte = 0; text = "t" } in
+  [%expect
+    {|
+    Full
+    same as full reparse: true
+    |}];
+  print_s [%sexp (edit_of_diff "abcdef" "abXdef" : Source_edit.splice)];
+  [%expect {| ((first_byte 2) (last_byte 2) (text X)) |}]
+;;
@@ -0,0 +1,185 @@
+(** Dataview-style inline fields: per-note metadata written in the body instead
+    of the frontmatter.
+
+    - A whole line [key:: value], e.g. [Rating:: 9].
+    - Bracketed within a line, [\[key:: value\]] or [(key:: value)], e.g.
+      [I read it (pages:: 320) last week].
+
+    Keys are letters, digits, spaces, [-], [_] and [/]; both key and value are
+    trimmed. Fields are read from paragraph text, so code blocks and headings
+    never contribute.
+
+    Queries reach them through [oystermark query --meta] and the [fields]
+    table of the SQLite index ([oystermark index --sqlite]). The site
+    exporter has no query blocks that could list them in a page. *)
+
+open Core
+
+type t =
+  { key : string
+  ; value : string
+  ; line : int option (** 1-based source line *)
+  }
+[@@deriving sexp_of]
+
+let is_key (k : string) : bool =
+  (not (String.is_empty k))
+  && String.for_all k ~f:(fun c ->
+    Char.is_alphanum c || List.mem [ ' '; '-'; '_'; '/' ] c ~equal:Char.equal)
+;;
+
+(* [key:: value] split at the first [::]. *)
+let split_field (s : string) : (string * string) option =
+  match String.substr_index s ~pattern:"::" with
+  | None -> None
+  | Some i ->
+    let key = String.strip (String.prefix s i) in
+    let value = String.strip (String.drop_prefix s (i + 2)) in
+    if is_key key then Some (key, value) else None
+;;
+
+(* Bracketed fields in [line], left to right. Brackets nest, so a value may
+   itself hold [[wikilinks]] or (parentheses). *)
+let bracketed (line : string) : (string * string) list =
+  let n = String.length line in
+  let rec close_of (pos : int) (stack : char list) : int option =
+    if pos >= n
+    then None
+    else (
+      match line.[pos], stack with
+      | (('[' | '(') as c), _ -> close_of (pos + 1) (c :: stack)
+      | ']', [ '[' ] | ')', [ '(' ] -> Some pos
+      | (']' | ')'), _ :: rest -> close_of (pos + 1) rest
+      | _ -> close_of (pos + 1) stack)
+  in
+  let rec scan (pos : int) (acc : (string * string) list) =
+    let is_open (_ : int) (c : char) : bool = Char.equal c '[' || Char.equal c '(' in
+    match String.lfindi line ~pos ~f:is_open with
+    | None -> List.rev acc
+    | Some start ->
+      (match close_of (start + 1) [ line.[start] ] with
+       | None -> List.rev acc
+       | Some stop ->
+         let inner = String.sub line ~pos:(start + 1) ~len:(stop - start - 1) in
+         (match split_field inner with
+          | Some field -> scan (stop + 1) (field :: acc)
+          | None -> scan (start + 1) acc))
+  in
+  scan 0 []
+;;
+
+(** Fields on one line of text: the bracketed ones if there are any, else the
+    whole line as a field. *)
+let of_line (line : string) : (string * string) list =
+  match bracketed line with
+  | [] -> Option.to_list (split_field line)
+  | fields -> fields
+;;
+
+(** Every inline field of [doc], in document order. *)
+let of_doc (doc : Cmarkit.Doc.t) : t list =
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun _f acc b ->
+        match b with
+        | Cmarkit.Block.Paragraph (p, meta) ->
+          let tl = Cmarkit.Meta.textloc meta in
+          let first : int option =
+            if Cmarkit.Textloc.is_none tl
+            then None
+            else Some (fst (Cmarkit.Textloc.first_line tl))
+          in
+          let lines : string list =
+            Cmarkit.Inline.to_plain_text
+              ~ext:Heading_slug.plain_text_ext
+              ~break_on_soft:true
+              (Cmarkit.Block.Paragraph.inline p)
+            |> List.map ~f:(String.concat ~sep:"")
+          in
+          let fields =
+            List.concat_mapi lines ~f:(fun i text ->
+              List.map (of_line text) ~f:(fun (key, value) ->
+                { key; value; line = Option.map first ~f:(fun l -> l + i) }))
+          in
+          Cmarkit.Folder.ret (List.rev_append fields acc)
+        | Cmarkit.Block.Code_block _ | Cmarkit.Block.Heading _ -> Cmarkit.Folder.ret acc
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_doc folder [] doc)
+;;
+
+(** Fields as a mapping in first-seen key order. A key given more than once
+    maps to the list of its values. *)
+let to_yaml (fields : t list) : Yaml_backend.value =
+  let keys : string list =
+    List.fold fields ~init:[] ~f:(fun acc f ->
+      if List.mem acc f.key ~equal:String.equal then acc else f.key :: acc)
+    |> List.rev
+  in
+  `O
+    (List.map keys ~f:(fun k ->
+       match List.filter fields ~f:(fun f -> String.equal f.key k) with
+       | [ f ] -> k, `String f.value
+       | fs -> k, `A (List.map fs ~f:(fun f -> `String f.value))))
+;;
+
+(** Like {!to_yaml}, with each value typed by {!Value.of_markdown}, so
+    [Rating:: 9] is the number [9]. *)
+let to_value (fields : t list) : Value.t =
+  match Value.of_yaml (to_yaml fields) with
+  | Value.Map kvs ->
+    let rec typed : Value.t -> Value.t = function
+      | Value.String s -> Value.of_markdown s
+      | Value.List vs -> Value.List (List.map vs ~f:typed)
+      | v -> v
+    in
+    Value.Map (List.map kvs ~f:(fun (k, v) -> k, typed v))
+  | v -> v
+;;
+
+let%expect_test "of_doc" =
+  let doc =
+    Cmarkit.Doc.of_string
+      ~locs:true
+      ~wikilink:true
+      {|# Book
+
+Rating:: 9
+Author:: [[Ursula]]
+
+I read it (pages:: 320) last week [mood:: calm], see [the site](https://x.y).
+
+- genre:: fiction
+- genre:: fantasy
+
+```
+ignored:: here
+```
+|}
+  in
+  let fields = of_doc doc in
+  List.iter fields ~f:(fun f -> print_s [%sexp (f : t)]);
+  [%expect
+    {|
+    ((key Rating) (value 9) (line (3)))
+    ((key Author) (value Ursula) (line (4)))
+    ((key pages) (value 320) (line (6)))
+    ((key mood) (value calm) (line (6)))
+    ((key genre) (value fiction) (line (8)))
+    ((key genre) (value fantasy) (line (9)))
+    |}];
+  print_string (Yaml_backend.to_string_exn (to_yaml fields));
+  [%expect
+    {|
+    Rating: "9"
+    Author: Ursula
+    pages: "320"
+    mood: calm
+    genre:
+    - fiction
+    - fantasy
+    |}]
+;;
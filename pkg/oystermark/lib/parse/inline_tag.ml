@@ -0,0 +1,68 @@
+(** Obsidian's inline tags, [#tag] in a note's text. A tag starts a text run
+    or follows a blank, and holds letters, digits, [_], [-] and [/] (nested
+    tags such as [#project/oyster]) with at least one non-digit, so [#1] is
+    not a tag. Tags in code spans and code blocks don't count. *)
+
+open Core
+
+let is_tag_char (c : char) : bool =
+  Char.is_alphanum c
+  || Char.equal c '_'
+  || Char.equal c '-'
+  || Char.equal c '/'
+  || Char.to_int c >= 128
+;;
+
+(** The tags in [text], without [#], in order. *)
+let of_text (text : string) : string list =
+  let n = String.length text in
+  let rec stop (j : int) : int =
+    if j < n && is_tag_char text.[j] then stop (j + 1) else j
+  in
+  let rec go (i : int) (acc : string list) : string list =
+    if i >= n
+    then List.rev acc
+    else if Char.equal text.[i] '#' && (i = 0 || Char.is_whitespace text.[i - 1])
+    then (
+      let j = stop (i + 1) in
+      let tag = String.sub text ~pos:(i + 1) ~len:(j - i - 1) in
+      go j (if String.exists tag ~f:(Fn.non Char.is_digit) then tag :: acc else acc))
+    else go (i + 1) acc
+  in
+  go 0 []
+;;
+
+(** The tags in [doc]'s text, once each, in order of first appearance. *)
+let of_doc (doc : Cmarkit.Doc.t) : string list =
+  let folder =
+    Cmarkit.Folder.make
+      ~inline:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Text (s, _) ->
+          Cmarkit.Folder.ret (List.rev_append (of_text s) acc)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_doc folder [] doc)
+  |> List.fold ~init:(String.Set.empty, []) ~f:(fun (seen, acc) tag ->
+    if Set.mem seen tag then seen, acc else Set.add seen tag, tag :: acc)
+  |> snd
+  |> List.rev
+;;
+
+let%expect_test "of_doc" =
+  Cmarkit.Doc.of_string
+    "# Notes #h1\n\n#todo see #project/oyster, #1 and a#b.\n\n\
+     `#code` and *#em* #todo\n\n    #indented\n"
+  |> of_doc
+  |> List.iter ~f:print_endline;
+  [%expect
+    {|
+    h1
+    todo
+    project/oyster
+    em
+    |}]
+;;
@@ -0,0 +1,117 @@
+(** Boards of the Obsidian Kanban plugin: notes with a [kanban-plugin]
+    frontmatter field, written as a [## Lane] heading per column, each
+    followed by a task list of its cards.
+
+    Under a lane heading, a [**Complete**] paragraph marks the lane that
+    finished cards move to. A thematic break ([***]) starts the archive, whose
+    cards the board doesn't show, and the [%% kanban:settings] comment the
+    plugin ends a board with holds its settings, which are not content. *)
+
+open Core
+
+type card = Cmarkit.Block.List_item.t Cmarkit.node
+
+type lane =
+  { title : string (** the heading's plain text *)
+  ; complete : bool
+  ; cards : card list
+  }
+
+type t =
+  { lanes : lane list
+  ; archive : card list
+  }
+
+let frontmatter_key = "kanban-plugin"
+let settings_marker = "%% kanban:settings"
+
+let is_board (doc : Cmarkit.Doc.t) : bool =
+  Option.is_some (Frontmatter.field doc frontmatter_key)
+;;
+
+let paragraph_text (b : Cmarkit.Block.t) : string option =
+  match b with
+  | Cmarkit.Block.Paragraph (p, _) ->
+    let inline = Cmarkit.Block.Paragraph.inline p in
+    Some (String.strip (Heading_slug.inline_to_plain_text inline))
+  | _ -> None
+;;
+
+let is_complete_marker (b : Cmarkit.Block.t) : bool =
+  match b with
+  | Cmarkit.Block.Paragraph (p, _) ->
+    (match Cmarkit.Block.Paragraph.inline p with
+     | Cmarkit.Inline.Strong_emphasis _ ->
+       Option.equal String.equal (paragraph_text b) (Some "Complete")
+     | _ -> false)
+  | _ -> false
+;;
+
+(** The board [doc] holds, if it is one. *)
+let of_doc (doc : Cmarkit.Doc.t) : t option =
+  let blocks : Cmarkit.Block.t list =
+    match Cmarkit.Doc.block doc with
+    | Cmarkit.Block.Blocks (blocks, _) -> blocks
+    | b -> [ b ]
+  in
+  (* [lanes] in reverse, the current one first *)
+  let rec go (lanes : lane list) (archive : card list option) blocks : t =
+    let finish () : t =
+      { lanes = List.rev lanes; archive = Option.value archive ~default:[] }
+    in
+    match blocks, lanes, archive with
+    | [], _, _ -> finish ()
+    | b :: _, _, _
+      when Option.value_map (paragraph_text b) ~default:false ~f:(fun s ->
+             String.is_prefix s ~prefix:settings_marker) -> finish ()
+    | Cmarkit.Block.Thematic_break _ :: rest, _, None -> go lanes (Some []) rest
+    | Cmarkit.Block.Heading (h, _) :: rest, _, None
+      when Cmarkit.Block.Heading.level h = 2 ->
+      let title = Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h) in
+      go ({ title; complete = false; cards = [] } :: lanes) None rest
+    | b :: rest, lane :: lanes', None when is_complete_marker b ->
+      go ({ lane with complete = true } :: lanes') None rest
+    | Cmarkit.Block.List (l, _) :: rest, lane :: lanes', None ->
+      let cards = lane.cards @ Cmarkit.Block.List'.items l in
+      go ({ lane with cards } :: lanes') None rest
+    | Cmarkit.Block.List (l, _) :: rest, _, Some archive ->
+      go lanes (Some (archive @ Cmarkit.Block.List'.items l)) rest
+    | _ :: rest, _, _ -> go lanes archive rest
+  in
+  if is_board doc then Some (go [] None blocks) else None
+;;
+
+let%expect_test "of_doc" =
+  let of_string ?(frontmatter : string option) (body : string) : Cmarkit.Doc.t =
+    let doc = Cmarkit.Doc.of_string ~strict:false body in
+    match frontmatter, Cmarkit.Doc.block doc with
+    | None, _ -> doc
+    | Some fm, Cmarkit.Block.Blocks (blocks, meta) ->
+      let fm =
+        Frontmatter.Frontmatter (Yaml_backend.of_string_exn fm, Frontmatter.Yaml)
+      in
+      Cmarkit.Doc.make (Cmarkit.Block.Blocks (fm :: blocks, meta))
+    | Some _, _ -> doc
+  in
+  let board =
+    of_string
+      ~frontmatter:"kanban-plugin: basic"
+      "## To do\n\n- [ ] Write the plan\n- [ ] Review\n\n\
+       ## Done\n\n**Complete**\n- [x] Ship\n\n\
+       ***\n\n## Archive\n\n- [x] Old\n\n\
+       %% kanban:settings\n```\n{\"kanban-plugin\":\"basic\"}\n```\n%%\n"
+    |> of_doc
+    |> Option.value_exn
+  in
+  List.iter board.lanes ~f:(fun lane ->
+    printf "%s complete=%b cards=%d\n" lane.title lane.complete (List.length lane.cards));
+  printf "archive=%d\n" (List.length board.archive);
+  [%expect
+    {|
+    To do complete=false cards=2
+    Done complete=true cards=1
+    archive=1
+    |}];
+  print_s [%sexp (Option.is_some (of_doc (of_string "## To do\n\n- a\n")) : bool)];
+  [%expect {| false |}]
+;;
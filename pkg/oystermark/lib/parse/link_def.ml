@@ -0,0 +1,121 @@
+(** Definitions behind reference-style links and footnote references.
+
+    A reference link ([[text][label]], [[label][]] or [[label]]) and a footnote
+    reference ([[^label]]) both parse as a {!Cmarkit.Inline.Link} whose
+    reference is [`Ref]; what it stands for lives in a separate
+    [[label]: dest "title"] or [[^label]: text] block, collected into the
+    document's label definitions ({!Cmarkit.Doc.defs}). *)
+
+open Core
+
+type t =
+  | Link of Cmarkit.Link_definition.t (** [[label]: dest "title"] *)
+  | Footnote of string * Cmarkit.Textloc.t
+  (** label of a [[^label]: ...] definition, without [^], and where it is *)
+
+(** Definition [label] (the defining label of a [`Ref]) stands for in [defs]. *)
+let find ~(defs : Cmarkit.Label.defs) (label : Cmarkit.Label.t) : t option =
+  match Cmarkit.Label.Map.find_opt (Cmarkit.Label.key label) defs with
+  | Some (Cmarkit.Link_definition.Def (ld, _)) -> Some (Link ld)
+  | Some (Cmarkit.Block.Footnote.Def (fn, meta)) ->
+    let key = Cmarkit.Label.key (Cmarkit.Block.Footnote.label fn) in
+    let label = Option.value (String.chop_prefix key ~prefix:"^") ~default:key in
+    Some (Footnote (label, Cmarkit.Meta.textloc meta))
+  | _ -> None
+;;
+
+(** Definition a link points at: its own for inline links, the one in [defs]
+    for reference links and footnote references. *)
+let of_link ~(defs : Cmarkit.Label.defs) (l : Cmarkit.Inline.Link.t) : t option =
+  match Cmarkit.Inline.Link.reference l with
+  | `Inline (ld, _) -> Some (Link ld)
+  | `Ref (_, _, def) -> find ~defs def
+;;
+
+(** Destination of a link definition, or [None] when it is empty. *)
+let dest (ld : Cmarkit.Link_definition.t) : string option =
+  Option.map (Cmarkit.Link_definition.dest ld) ~f:fst
+;;
+
+(** Label definitions of the [[label]: dest] and [[^label]: text] blocks in [b].
+    For documents rebuilt from blocks (e.g. a query section), whose
+    {!Cmarkit.Doc.defs} is empty. The first definition of a label wins, as in
+    CommonMark. *)
+let of_block (b : Cmarkit.Block.t) : Cmarkit.Label.defs =
+  let add (defs : Cmarkit.Label.defs) (label : Cmarkit.Label.t option) def =
+    match label with
+    | Some label when not (Cmarkit.Label.Map.mem (Cmarkit.Label.key label) defs) ->
+      Cmarkit.Label.Map.add (Cmarkit.Label.key label) def defs
+    | _ -> defs
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun f defs b ->
+        match b with
+        | Cmarkit.Block.Link_reference_definition ((ld, _) as node) ->
+          Cmarkit.Folder.ret
+            (add
+               defs
+               (Cmarkit.Link_definition.defined_label ld)
+               (Cmarkit.Link_definition.Def node))
+        | Cmarkit.Block.Ext_footnote_definition ((fn, _) as node) ->
+          (* Footnote bodies may hold further definitions. *)
+          let defs =
+            add
+              defs
+              (Cmarkit.Block.Footnote.defined_label fn)
+              (Cmarkit.Block.Footnote.Def node)
+          in
+          Cmarkit.Folder.ret
+            (Cmarkit.Folder.fold_block f defs (Cmarkit.Block.Footnote.block fn))
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f defs _i -> defs)
+      ~block_ext_default:(fun _f defs _b -> defs)
+      ()
+  in
+  Cmarkit.Folder.fold_block folder Cmarkit.Label.Map.empty b
+;;
+
+let%expect_test "find" =
+  let doc =
+    Cmarkit.Doc.of_string
+      ~strict:false
+      "See [the docs][docs], [Docs] and a note[^1].\n\n\
+       [docs]: https://ocaml.org \"OCaml\"\n\n\
+       [^1]: A footnote.\n"
+  in
+  let show (defs : Cmarkit.Label.defs) =
+    let folder =
+      Cmarkit.Folder.make
+        ~inline:(fun _f () i ->
+          match i with
+          | Cmarkit.Inline.Link (l, _) ->
+            (match of_link ~defs l with
+             | Some (Link ld) ->
+               print_endline ("link " ^ Option.value (dest ld) ~default:"<empty>")
+             | Some (Footnote (label, _)) -> print_endline ("footnote " ^ label)
+             | None -> print_endline "<undefined>");
+            Cmarkit.Folder.ret ()
+          | _ -> Cmarkit.Folder.default)
+        ~inline_ext_default:(fun _f () _i -> ())
+        ~block_ext_default:(fun _f () _b -> ())
+        ()
+    in
+    Cmarkit.Folder.fold_doc folder () doc
+  in
+  show (Cmarkit.Doc.defs doc);
+  [%expect
+    {|
+    link https://ocaml.org
+    link https://ocaml.org
+    footnote 1
+    |}];
+  (* A document rebuilt from its blocks finds the same definitions. *)
+  show (of_block (Cmarkit.Doc.block doc));
+  [%expect
+    {|
+    link https://ocaml.org
+    link https://ocaml.org
+    footnote 1
+    |}]
+;;
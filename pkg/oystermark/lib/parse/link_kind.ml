@@ -0,0 +1,49 @@
+(** Whether a link destination stays inside the vault or leaves it.
+
+    Exporters mark external links (e.g. open them in a new tab), the resolver
+    skips them, and [oyster query --links] filters on the kind. *)
+
+open Core
+
+type t =
+  | Internal (** a note, heading, block or asset of the vault *)
+  | External (** [http], [https], [mailto] or [ftp] URL *)
+[@@deriving sexp, equal, compare]
+
+let to_string : t -> string = function
+  | Internal -> "internal"
+  | External -> "external"
+;;
+
+let of_string : string -> t option = function
+  | "internal" -> Some Internal
+  | "external" -> Some External
+  | _ -> None
+;;
+
+let external_prefixes = [ "http://"; "https://"; "mailto:"; "ftp://" ]
+
+(** Kind of a link destination as written, e.g. [https://ocaml.org] or
+    [notes/todo.md]. Scheme matching ignores case. *)
+let of_dest (dest : string) : t =
+  let dest = String.lowercase (String.strip dest) in
+  if List.exists external_prefixes ~f:(fun prefix -> String.is_prefix dest ~prefix)
+  then External
+  else Internal
+;;
+
+let is_external (dest : string) : bool = equal (of_dest dest) External
+
+let%expect_test "of_dest" =
+  List.iter
+    [ "https://ocaml.org"; "HTTP://x.y"; "mailto:a@b.c"; "notes/todo.md"; "#heading" ]
+    ~f:(fun d -> printf "%s %s\n" d (to_string (of_dest d)));
+  [%expect
+    {|
+    https://ocaml.org external
+    HTTP://x.y external
+    mailto:a@b.c external
+    notes/todo.md internal
+    #heading internal
+    |}]
+;;
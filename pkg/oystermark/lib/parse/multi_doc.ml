@@ -0,0 +1,67 @@
+(** Several documents in one file, separated by thematic breaks at the top
+    level, as in slide decks and concatenated exports. Cmarkit keeps no layout
+    by default, so [***] and [___] separate documents as [---] does; breaks
+    inside lists, quotes or [:::] divs don't. The frontmatter stays with the
+    first document, and separators with only blank lines around them yield
+    no document. *)
+
+open Core
+
+let is_blank : Cmarkit.Block.t -> bool = function
+  | Cmarkit.Block.Blank_line _ -> true
+  | _ -> false
+;;
+
+(** The documents of [doc], in order; [[doc]] if it has no separator. *)
+let split (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t list =
+  match Cmarkit.Doc.block doc with
+  | Cmarkit.Block.Blocks (blocks, meta) ->
+    let groups : Cmarkit.Block.t list list =
+      List.group blocks ~break:(fun _ b ->
+        match b with
+        | Cmarkit.Block.Thematic_break _ -> true
+        | _ -> false)
+      |> List.map ~f:(function
+        | Cmarkit.Block.Thematic_break _ :: rest -> rest
+        | group -> group)
+      |> List.filter ~f:(fun group -> not (List.for_all group ~f:is_blank))
+    in
+    (match groups with
+     | [] | [ _ ] -> [ doc ]
+     | groups ->
+       List.map groups ~f:(fun group ->
+         Cmarkit.Doc.make
+           ~defs:(Cmarkit.Doc.defs doc)
+           (Cmarkit.Block.Blocks (group, meta))))
+  | _ -> [ doc ]
+;;
+
+let%expect_test "split" =
+  let show (src : string) : unit =
+    Cmarkit.Doc.of_string ~strict:false src
+    |> split
+    |> List.iter ~f:(fun d ->
+      print_endline (String.strip (Cmarkit_commonmark.of_doc d));
+      print_endline "==")
+  in
+  show "# One\n\nIntro.\n\n---\n\n# Two\n\n- a\n\n  ***\n\n***\n\n---\n";
+  [%expect
+    {|
+    # One
+
+    Intro.
+    ==
+    # Two
+
+    - a
+
+      ***
+    ==
+    |}];
+  show "No separator.\n";
+  [%expect
+    {|
+    No separator.
+    ==
+    |}]
+;;
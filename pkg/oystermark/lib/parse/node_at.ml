@@ -0,0 +1,142 @@
+(** Position to node lookup: the deepest AST node whose source range covers a
+    position, with the chain of nodes enclosing it.
+
+    Ranges come from [Cmarkit.Meta.textloc], so the document must be parsed
+    with [~locs:true] (as {!Parse.of_string} does). Nodes without a location,
+    such as the [Inlines] wrapper the mappers build, are looked through: their
+    children are still searched, but they are never the result. Ranges are
+    inclusive, so a cursor on the last byte of a node is inside it. *)
+
+open Core
+
+type node =
+  | Block of Cmarkit.Block.t
+  | Inline of Cmarkit.Inline.t
+
+type t =
+  { node : node
+  ; ancestors : node list (** innermost first, ending at the document block *)
+  }
+
+let meta : node -> Cmarkit.Meta.t = function
+  | Block b -> Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) b
+  | Inline i -> Cmarkit.Inline.meta ~ext:(fun _ -> Cmarkit.Meta.none) i
+;;
+
+let inline_children (i : Cmarkit.Inline.t) : node list =
+  let open Cmarkit.Inline in
+  match i with
+  | Emphasis (e, _) | Strong_emphasis (e, _) -> [ Inline (Emphasis.inline e) ]
+  | Link (l, _) | Image (l, _) -> [ Inline (Link.text l) ]
+  | Inlines (is, _) -> List.map is ~f:(fun i -> Inline i)
+  | Ext_strikethrough (s, _) -> [ Inline (Strikethrough.inline s) ]
+  | Ext_attributes (a, _) -> [ Inline (Attributes.inline a) ]
+  | _ -> []
+;;
+
+let block_children (b : Cmarkit.Block.t) : node list =
+  let open Cmarkit.Block in
+  match b with
+  | Paragraph (p, _) -> [ Inline (Paragraph.inline p) ]
+  | Heading (h, _) -> [ Inline (Heading.inline h) ]
+  | Block_quote (bq, _) -> [ Block (Block_quote.block bq) ]
+  | List (l, _) ->
+    List.map (List'.items l) ~f:(fun (item, _) -> Block (List_item.block item))
+  | Blocks (bs, _) -> List.map bs ~f:(fun b -> Block b)
+  | Ext_footnote_definition (fn, _) -> [ Block (Footnote.block fn) ]
+  | Ext_table (t, _) ->
+    List.concat_map (Table.rows t) ~f:(fun ((row, _), _) ->
+      match row with
+      | `Header cells | `Data cells -> List.map cells ~f:(fun (i, _) -> Inline i)
+      | `Sep _ -> [])
+  | Ext_div (d, _) -> [ Block (Div.block d) ]
+  | Ext_attributes (a, _) -> [ Block (Attributes.block a) ]
+  | Ext_keyed ((label, body), _) -> [ Inline label; Block body ]
+  | _ -> []
+;;
+
+let children : node -> node list = function
+  | Block b -> block_children b
+  | Inline i -> inline_children i
+;;
+
+(** The deepest node covering byte [offset] of the source [doc] was parsed
+    from, or [None] when no located node covers it (e.g. a blank line). *)
+let node_at (doc : Cmarkit.Doc.t) (offset : int) : t option =
+  let rec go (ancestors : node list) (n : node) : t option =
+    let tl = Cmarkit.Meta.textloc (meta n) in
+    let located = not (Cmarkit.Textloc.is_none tl) in
+    let covers =
+      located
+      && Cmarkit.Textloc.first_byte tl <= offset
+      && offset <= Cmarkit.Textloc.last_byte tl
+    in
+    if located && not covers
+    then None
+    else (
+      match List.find_map (children n) ~f:(go (n :: ancestors)) with
+      | Some found -> Some found
+      | None -> if covers then Some { node = n; ancestors } else None)
+  in
+  go [] (Block (Cmarkit.Doc.block doc))
+;;
+
+(** Like {!node_at}, for the 0-based [row] and byte [col] of [source], the
+    string [doc] was parsed from. A [col] past the end of the row clamps to
+    its last byte. *)
+let node_at_point (source : string) (doc : Cmarkit.Doc.t) ~(row : int) ~(col : int)
+  : t option
+  =
+  let lines = String.split source ~on:'\n' in
+  match List.split_n lines row with
+  | _, [] -> None
+  | before, line :: _ ->
+    let line_start = List.sum (module Int) before ~f:(fun l -> String.length l + 1) in
+    node_at doc (line_start + min col (max 0 (String.length line - 1)))
+;;
+
+let kind : node -> string = function
+  | Block b ->
+    (match b with
+     | Cmarkit.Block.Paragraph _ -> "Paragraph"
+     | Heading _ -> "Heading"
+     | Block_quote _ -> "Block_quote"
+     | List _ -> "List"
+     | Blocks _ -> "Blocks"
+     | Code_block _ -> "Code_block"
+     | Ext_div _ -> "Div"
+     | Ext_attributes _ -> "Attributes"
+     | Ext_keyed _ -> "Keyed"
+     | _ -> "Block")
+  | Inline i ->
+    (match i with
+     | Cmarkit.Inline.Text _ -> "Text"
+     | Emphasis _ -> "Emphasis"
+     | Strong_emphasis _ -> "Strong_emphasis"
+     | Link _ -> "Link"
+     | Image _ -> "Image"
+     | Code_span _ -> "Code_span"
+     | Inlines _ -> "Inlines"
+     | Ext_wikilink _ -> "Wikilink"
+     | _ -> "Inline")
+;;
+
+let%expect_test "node_at" =
+  let source = "# Title\n\nSee *the [[Note]] here* now.\n" in
+  let doc = Cmarkit.Doc.of_string ~locs:true ~wikilink:true source in
+  let show t =
+    match t with
+    | None -> print_endline "<none>"
+    | Some { node; ancestors } ->
+      print_endline
+        (String.concat ~sep:" < " (kind node :: List.map ancestors ~f:kind))
+  in
+  show (node_at doc 3);
+  [%expect {| Text < Heading < Blocks |}];
+  show (node_at doc 17);
+  [%expect {| Wikilink < Inlines < Emphasis < Inlines < Paragraph < Blocks |}];
+  show (node_at_point source doc ~row:2 ~col:1);
+  [%expect {| Text < Inlines < Paragraph < Blocks |}];
+  show (node_at_point source doc ~row:1 ~col:0);
+  [%expect {| Blocks |}]
+;;
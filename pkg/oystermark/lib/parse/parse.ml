@@ -19,9 +19,28 @@ open Common
 module Common = Common
 module Frontmatter = Frontmatter
 module Heading_slug = Heading_slug
+module Section_id = Section_id
+module Encrypted = Encrypted
+module Kanban = Kanban
+module Multi_doc = Multi_doc
 module Cb_attribute = Cb_attribute
+module Code_include = Code_include
 module Textloc_conv = Textloc_conv
 module Struct = Struct
+module Timestamp = Timestamp
+module Value = Value
+module Inline_field = Inline_field
+module Source_edit = Source_edit
+module Incremental = Incremental
+module Raw_text = Raw_text
+module Html_fragment = Html_fragment
+module Definition_list = Definition_list
+module Gfm_alert = Gfm_alert
+module Ast_json = Ast_json
+module Node_at = Node_at
+module Link_kind = Link_kind
+module Link_def = Link_def
+module Inline_tag = Inline_tag
 
 (** Does not provide a mapper  *)
 module Extract = Extract
@@ -30,18 +49,21 @@ type block_id =
   | Caret of Cmarkit.Block.Block_id.t
   | Heading of string
 
-let mk_mapper () : Cmarkit.Mapper.t =
+let mk_mapper ?(definition_list = true) ?slug_style () : Cmarkit.Mapper.t =
   Cmarkit.Mapper.make
     ~inline_ext_default:(fun _m i -> Some i)
     ~block:
-      (compose_all_block_maps [ Heading_slug.mk_block_map (); Cb_attribute.block_map ])
+      (compose_all_block_maps
+         ([ Heading_slug.mk_block_map ?style:slug_style (); Cb_attribute.block_map ]
+          @ if definition_list then [ Definition_list.block_map ] else []))
     ()
 ;;
 
 (** [of_string ?strict ?layout s] parses markdown string [s] into a
     [Cmarkit.Doc.t] with frontmatter embedded as a {!Frontmatter.Frontmatter}
     block and wikilinks/block IDs parsed. Heading slugs are stamped onto
-    heading block metadata. *)
+    heading block metadata. Which syntax extensions are recognized is set by
+    [config.parse] ({!Config.Parse_options}). *)
 let of_string
       (* Cmarkit config *)
       ?(strict = false)
@@ -53,24 +75,40 @@ let of_string
   : Cmarkit.Doc.t
   =
   let enable_struct = config.ext_struct.enable in
+  let opts : Config.Parse_options.t = config.parse in
   let open Cmarkit in
   (* Blank (not strip) the frontmatter so parsed [Textloc]s stay aligned with
      the original file's byte/line positions. See {!Frontmatter.blank_frontmatter}. *)
   let yaml_opt, body = Frontmatter.blank_frontmatter s in
   let cmarkit_doc =
     Doc.of_string
-      ~strict
+      ~strict:(strict || not opts.extensions)
       ~layout
       ~locs:true
-      ~block_id:true
-      ~div:true
-      ~wikilink:true
-      ~inline_attributes:true
-      ~block_attributes:true
-      ~callout:(Block.Callout.Config.make ())
+      ~block_id:opts.block_id
+      ~div:opts.div
+      ~wikilink:opts.wikilink
+      ~inline_attributes:opts.attributes
+      ~block_attributes:opts.attributes
+      ?callout:(if opts.callout then Some (Block.Callout.Config.make ()) else None)
       body
   in
-  let body_doc = Mapper.map_doc (mk_mapper ()) cmarkit_doc in
+  (* Before slugging, so heading slugs see the raw text *)
+  let cmarkit_doc =
+    Raw_text.map_doc
+      ~source:body
+      ~restore:(not opts.smart_punctuation)
+      ~coalesce:opts.coalesce_text
+      cmarkit_doc
+  in
+  let body_doc =
+    Mapper.map_doc
+      (mk_mapper
+         ~definition_list:opts.definition_list
+         ~slug_style:(Heading_slug.of_config opts.slug_style)
+         ())
+      cmarkit_doc
+  in
   let body_doc = if enable_struct then Struct.rewrite_doc body_doc else body_doc in
   (* The frontmatter region was blanked (not stripped) to keep [Textloc]s
      aligned with the original file, so the parsed body begins with blank lines
@@ -81,13 +119,17 @@ let of_string
     | Cmarkit.Block.Blank_line _ :: rest -> drop_leading_blanks rest
     | bs -> bs
   in
+  let format : Frontmatter.format =
+    Option.value (Frontmatter.format_of_string s) ~default:Frontmatter.Yaml
+  in
   match yaml_opt, Doc.block body_doc with
   | None, _ -> body_doc
   | Some yaml, Block.Blocks (blocks, meta) ->
-    let blocks' = Frontmatter.Frontmatter yaml :: drop_leading_blanks blocks in
+    let blocks' = Frontmatter.Frontmatter (yaml, format) :: drop_leading_blanks blocks in
     Doc.make (Block.Blocks (blocks', meta))
   | Some yaml, other ->
-    Doc.make (Block.Blocks ([ Frontmatter.Frontmatter yaml; other ], Meta.none))
+    let blocks = [ Frontmatter.Frontmatter (yaml, format); other ] in
+    Doc.make (Block.Blocks (blocks, Meta.none))
 ;;
 
 let commonmark_of_doc (doc : Cmarkit.Doc.t) : string =
@@ -213,6 +255,7 @@ let sexp_of_ =
       ; block_id_sexp_of_meta
       ; callout_sexp_of_meta
       ; Cb_attribute.sexp_of_meta
+      ; Definition_list.sexp_of_meta
       ]
     ()
 ;;
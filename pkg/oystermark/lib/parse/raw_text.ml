@@ -0,0 +1,109 @@
+(** Raw-text fidelity for text inlines.
+
+    The parser may substitute typographic punctuation ([don't] becomes
+    [don’t]) and splits text around such characters, so plain text extracted
+    from a heading or paragraph no longer matches the source. Two passes undo
+    that, both driven by {!Config.Parse_options}:
+
+    - {!restore}: a text inline takes back the exact bytes of its source range.
+      Ranges holding a backslash escape, an entity or a line break are left
+      alone, since there the parsed text is meant to differ.
+    - {!coalesce}: adjacent text inlines are merged into one, spanning both
+      source ranges. *)
+
+open Core
+
+(* The source bytes of [meta]'s range, when they can stand for the text. *)
+let raw_slice (source : string) (meta : Cmarkit.Meta.t) : string option =
+  let tl = Cmarkit.Meta.textloc meta in
+  if Cmarkit.Textloc.is_none tl
+  then None
+  else (
+    let first = Cmarkit.Textloc.first_byte tl
+    and last = Cmarkit.Textloc.last_byte tl in
+    if first < 0 || last >= String.length source || last < first
+    then None
+    else (
+      let raw = String.sub source ~pos:first ~len:(last - first + 1) in
+      if String.exists raw ~f:(fun c ->
+           Char.equal c '\\' || Char.equal c '&' || Char.equal c '\n')
+      then None
+      else Some raw))
+;;
+
+let merge_meta (a : Cmarkit.Meta.t) (b : Cmarkit.Meta.t) : Cmarkit.Meta.t =
+  let ta = Cmarkit.Meta.textloc a
+  and tb = Cmarkit.Meta.textloc b in
+  if Cmarkit.Textloc.is_none ta || Cmarkit.Textloc.is_none tb
+  then Cmarkit.Meta.with_textloc ~keep_id:true a Cmarkit.Textloc.none
+  else Cmarkit.Meta.with_textloc ~keep_id:true a (Cmarkit.Textloc.span ta tb)
+;;
+
+let coalesce_inlines (is : Cmarkit.Inline.t list) : Cmarkit.Inline.t list =
+  List.fold is ~init:[] ~f:(fun acc i ->
+    match acc, i with
+    | Cmarkit.Inline.Text (prev, pm) :: rest, Cmarkit.Inline.Text (s, m) ->
+      Cmarkit.Inline.Text (prev ^ s, merge_meta pm m) :: rest
+    | _ -> i :: acc)
+  |> List.rev
+;;
+
+(** Map every text inline of a document parsed from [source] (same byte
+    offsets) back to its raw bytes ([restore]) and merge neighbouring text
+    inlines ([coalesce]). *)
+let mk_mapper ~(source : string) ~(restore : bool) ~(coalesce : bool)
+  : Cmarkit.Mapper.t
+  =
+  Cmarkit.Mapper.make
+    ~inline_ext_default:(fun _m i -> Some i)
+    ~block_ext_default:(fun _m b -> Some b)
+    ~inline:(fun m i ->
+      match i with
+      | Cmarkit.Inline.Text (s, meta) when restore ->
+        (match raw_slice source meta with
+         | Some raw when not (String.equal raw s) ->
+           Cmarkit.Mapper.ret (Cmarkit.Inline.Text (raw, meta))
+         | _ -> Cmarkit.Mapper.default)
+      | Cmarkit.Inline.Inlines (is, meta) when coalesce ->
+        let is = List.filter_map is ~f:(Cmarkit.Mapper.map_inline m) in
+        (match coalesce_inlines is with
+         | [ single ] -> Cmarkit.Mapper.ret single
+         | is -> Cmarkit.Mapper.ret (Cmarkit.Inline.Inlines (is, meta)))
+      | _ -> Cmarkit.Mapper.default)
+    ()
+;;
+
+let map_doc ~(source : string) ~(restore : bool) ~(coalesce : bool) (doc : Cmarkit.Doc.t)
+  : Cmarkit.Doc.t
+  =
+  if restore || coalesce
+  then Cmarkit.Mapper.map_doc (mk_mapper ~source ~restore ~coalesce) doc
+  else doc
+;;
+
+let%expect_test "restore and coalesce" =
+  let source = "# Don't *stop* me\n\nIt's \"fine\", isn't it?\n" in
+  let doc = Cmarkit.Doc.of_string ~locs:true source in
+  let texts (doc : Cmarkit.Doc.t) : string list =
+    let folder =
+      Cmarkit.Folder.make
+        ~inline:(fun _f acc i ->
+          match i with
+          | Cmarkit.Inline.Text (s, _) -> Cmarkit.Folder.ret (s :: acc)
+          | _ -> Cmarkit.Folder.default)
+        ~inline_ext_default:(fun _f acc _i -> acc)
+        ~block_ext_default:(fun _f acc _b -> acc)
+        ()
+    in
+    List.rev (Cmarkit.Folder.fold_doc folder [] doc)
+  in
+  let doc = map_doc ~source ~restore:true ~coalesce:true doc in
+  List.iter (texts doc) ~f:print_endline;
+  [%expect
+    {|
+    Don't
+    stop
+     me
+    It's "fine", isn't it?
+    |}]
+;;
@@ -0,0 +1,107 @@
+(** Stable section identifiers, for tools that track the sections of a note
+    across edits: [id] in the JSON of [oystermark query] and the
+    [data-section-id] of headings in the exported HTML.
+
+    A section's id hashes the note's path with the titles of its heading and
+    the headings enclosing it. Unlike its dot path ([1.2]), it survives
+    sections being added, removed or reordered around it; it changes when the
+    note, the heading or an enclosing heading is renamed. A heading whose
+    titles repeat an earlier heading's also hashes its occurrence count, so
+    ids stay unique within a note. *)
+
+open Core
+
+(** Stamped on top-level heading blocks by {!stamp}. *)
+let meta_key : string Cmarkit.Meta.key = Cmarkit.Meta.key ()
+
+(** The id of the section of note [path] whose heading and enclosing headings
+    are titled [titles], outermost first; [[]] is the whole note.
+    [occurrence] counts the earlier sections with the same [titles]. *)
+let make ?(occurrence : int = 0) ~(path : string) (titles : string list) : string =
+  let key = String.concat ~sep:"\n" (path :: titles) in
+  let key = if occurrence = 0 then key else key ^ "\n" ^ Int.to_string occurrence in
+  "s" ^ String.prefix (Md5.to_hex (Md5.digest_string key)) 12
+;;
+
+(** [doc], the note at [path], with the top-level headings (those that open
+    sections; see [Query.Section]) carrying their id under {!meta_key}. *)
+let stamp ~(path : string) (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
+  (* Enclosing headings as (level, title), innermost first. *)
+  let enclosing : (int * string) list ref = ref [] in
+  let seen : (string list, int) Hashtbl.t = Hashtbl.Poly.create () in
+  let id_of (h : Cmarkit.Block.Heading.t) : string =
+    let level = Cmarkit.Block.Heading.level h in
+    let title = Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h) in
+    enclosing
+    := (level, title) :: List.drop_while !enclosing ~f:(fun (l, _) -> l >= level);
+    let titles = List.rev_map !enclosing ~f:snd in
+    let occurrence = Option.value (Hashtbl.find seen titles) ~default:0 in
+    Hashtbl.set seen ~key:titles ~data:(occurrence + 1);
+    make ~occurrence ~path titles
+  in
+  let mapper =
+    Cmarkit.Mapper.make
+      ~inline_ext_default:(fun _m i -> Some i)
+      ~block_ext_default:(fun _m b -> Some b)
+      ~block:(fun _m (b : Cmarkit.Block.t) ->
+        match b with
+        | Cmarkit.Block.Heading (h, meta) ->
+          Cmarkit.Mapper.ret
+            (Cmarkit.Block.Heading (h, Cmarkit.Meta.add meta_key (id_of h) meta))
+        | Cmarkit.Block.Blocks _ -> Cmarkit.Mapper.default
+        | Cmarkit.Block.Ext_attributes (a, _) ->
+          (* Only a heading's attribute line; headings inside an attributed
+             quote or list open no section, and don't enclose any. *)
+          (match Cmarkit.Block.Attributes.block a with
+           | Cmarkit.Block.Heading _ -> Cmarkit.Mapper.default
+           | _ -> Cmarkit.Mapper.ret b)
+        | _ -> Cmarkit.Mapper.ret b)
+      ()
+  in
+  Cmarkit.Mapper.map_doc mapper doc
+;;
+
+let%expect_test "stamp" =
+  let doc =
+    Cmarkit.Doc.of_string
+      ~strict:false
+      ~block_attributes:true
+      "# Guide\n\n\
+       ## Install\n\n\
+       > ## Quoted\n\n\
+       {.aside}\n\
+       > ## Aside\n\n\
+       ## Usage\n\n\
+       {#setup}\n\
+       ### Install\n\n\
+       ## Install\n"
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ~block:(fun _f acc (b : Cmarkit.Block.t) ->
+        match b with
+        | Cmarkit.Block.Heading (h, meta) ->
+          let title =
+            Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h)
+          in
+          Cmarkit.Folder.ret ((title, Cmarkit.Meta.find meta_key meta) :: acc)
+        | _ -> Cmarkit.Folder.default)
+      ()
+  in
+  Cmarkit.Folder.fold_doc folder [] (stamp ~path:"guide.md" doc)
+  |> List.rev
+  |> List.iter ~f:(fun (title, id) ->
+    printf "%s %s\n" title (Option.value id ~default:"-"));
+  [%expect
+    {|
+    Guide se29218233e02
+    Install s35389f845e65
+    Quoted -
+    Aside -
+    Usage s2bfae2e870f2
+    Install sfb57c6d75477
+    Install sc8d17f29340c
+    |}]
+;;
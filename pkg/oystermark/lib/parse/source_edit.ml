@@ -0,0 +1,202 @@
+(** Node-level edits to a markdown source, applied as byte-range splices on the
+    original text rather than by re-rendering the AST.
+
+    Ranges are given by the [Cmarkit.Textloc.t] of nodes parsed from that
+    source (so {!Parse.of_string} with [~locs:true], the default). Everything
+    outside the edited ranges is kept byte for byte, and {!map_byte} moves an
+    offset of the original to where it ends up, so ranges of untouched nodes
+    stay usable after the edits. *)
+
+open Core
+
+type splice =
+  { first_byte : int
+  ; last_byte : int (** inclusive; [first_byte - 1] for a pure insertion *)
+  ; text : string
+  }
+[@@deriving sexp_of]
+
+type t =
+  { source : string
+  ; splices : splice list (** in the order they were added *)
+  }
+
+let create (source : string) : t = { source; splices = [] }
+let source (t : t) : string = t.source
+
+let range_of_textloc (tl : Cmarkit.Textloc.t) : (int * int) Or_error.t =
+  if Cmarkit.Textloc.is_none tl
+  then Or_error.error_s [%message "node has no source location; parse with ~locs:true"]
+  else Ok (Cmarkit.Textloc.first_byte tl, Cmarkit.Textloc.last_byte tl)
+;;
+
+let length (s : splice) : int = s.last_byte - s.first_byte + 1
+
+(* Two splices conflict if their replaced ranges share a byte, or if one
+   inserts strictly inside the other's range. Insertions at the same point
+   are kept in the order they were added. *)
+let overlaps (a : splice) (b : splice) : bool =
+  let inside (ins : splice) (r : splice) =
+    length ins = 0 && ins.first_byte > r.first_byte && ins.first_byte <= r.last_byte
+  in
+  (length a > 0
+   && length b > 0
+   && a.first_byte <= b.last_byte
+   && b.first_byte <= a.last_byte)
+  || inside a b
+  || inside b a
+;;
+
+let add (t : t) (splice : splice) : t Or_error.t =
+  let n = String.length t.source in
+  (* A pure insertion may sit at [n], appending *)
+  if splice.first_byte < 0 || splice.last_byte >= n || length splice < 0
+  then
+    Or_error.error_s
+      [%message
+        "edit out of range" (splice.first_byte : int) (splice.last_byte : int) (n : int)]
+  else (
+    match List.find t.splices ~f:(overlaps splice) with
+    | Some other ->
+      Or_error.error_s
+        [%message "overlapping edits" (splice : splice) ~with_:(other : splice)]
+    | None -> Ok { t with splices = t.splices @ [ splice ] })
+;;
+
+(** Replace the bytes [first_byte..last_byte] (inclusive) with [text]. *)
+let replace_range (t : t) ~(first_byte : int) ~(last_byte : int) (text : string)
+  : t Or_error.t
+  =
+  add t { first_byte; last_byte; text }
+;;
+
+(** Replace the whole source text of the node at [loc]. *)
+let replace (t : t) (loc : Cmarkit.Textloc.t) (text : string) : t Or_error.t =
+  let%bind.Or_error first_byte, last_byte = range_of_textloc loc in
+  replace_range t ~first_byte ~last_byte text
+;;
+
+(* End of the line holding [byte], past its newline if it has one. *)
+let line_end (s : string) (byte : int) : int =
+  match String.index_from s byte '\n' with
+  | Some i -> i
+  | None -> String.length s - 1
+;;
+
+let line_start (s : string) (byte : int) : int =
+  match String.rindex_from s byte '\n' with
+  | Some i -> i + 1
+  | None -> 0
+;;
+
+(** Remove the node at [loc] together with the rest of the lines it sits on,
+    so no empty line or stray indentation takes its place. Surrounding blank
+    lines are kept. *)
+let remove (t : t) (loc : Cmarkit.Textloc.t) : t Or_error.t =
+  let%bind.Or_error first_byte, last_byte = range_of_textloc loc in
+  replace_range
+    t
+    ~first_byte:(line_start t.source first_byte)
+    ~last_byte:(line_end t.source last_byte)
+    ""
+;;
+
+let insert_at (t : t) (byte : int) (text : string) : t Or_error.t =
+  replace_range t ~first_byte:byte ~last_byte:(byte - 1) text
+;;
+
+(** Insert [text] as a sibling before the node at [loc]: at the start of its
+    first line, followed by a blank line. *)
+let insert_before (t : t) (loc : Cmarkit.Textloc.t) (text : string) : t Or_error.t =
+  let%bind.Or_error first_byte, _ = range_of_textloc loc in
+  insert_at t (line_start t.source first_byte) (text ^ "\n\n")
+;;
+
+(** Insert [text] as a sibling after the node at [loc], separated by a blank
+    line. *)
+let insert_after (t : t) (loc : Cmarkit.Textloc.t) (text : string) : t Or_error.t =
+  let%bind.Or_error _, last_byte = range_of_textloc loc in
+  let stop = line_end t.source last_byte in
+  if stop < String.length t.source && Char.equal t.source.[stop] '\n'
+  then insert_at t (stop + 1) ("\n" ^ text ^ "\n")
+  else insert_at t (stop + 1) ("\n\n" ^ text ^ "\n")
+;;
+
+let sorted (t : t) : splice list =
+  List.stable_sort t.splices ~compare:(fun a b -> Int.compare a.first_byte b.first_byte)
+;;
+
+(** The source with every edit applied. *)
+let apply (t : t) : string =
+  let buf = Buffer.create (String.length t.source) in
+  let pos =
+    List.fold (sorted t) ~init:0 ~f:(fun pos s ->
+      Buffer.add_substring buf t.source ~pos ~len:(s.first_byte - pos);
+      Buffer.add_string buf s.text;
+      s.last_byte + 1)
+  in
+  Buffer.add_substring buf t.source ~pos ~len:(String.length t.source - pos);
+  Buffer.contents buf
+;;
+
+(** Where byte [b] of the original source is in {!apply}'s result; [None] if an
+    edit replaced it. *)
+let map_byte (t : t) (b : int) : int option =
+  List.fold_until
+    (sorted t)
+    ~init:0
+    ~f:(fun shift s ->
+      if b < s.first_byte
+      then Stop (Some (b + shift))
+      else if b <= s.last_byte
+      then Stop None
+      else Continue (shift + String.length s.text - length s))
+    ~finish:(fun shift -> Some (b + shift))
+;;
+
+let%expect_test "edits" =
+  let src = "# Title\n\nFirst para.\n\nSecond para.\n\n## Sub\n" in
+  let doc = Cmarkit.Doc.of_string ~locs:true src in
+  let blocks =
+    match Cmarkit.Doc.block doc with
+    | Cmarkit.Block.Blocks (bs, _) ->
+      List.filter bs ~f:(function
+        | Cmarkit.Block.Blank_line _ -> false
+        | _ -> true)
+    | b -> [ b ]
+  in
+  let loc (i : int) : Cmarkit.Textloc.t =
+    Cmarkit.Meta.textloc
+      (Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) (List.nth_exn blocks i))
+  in
+  let edits =
+    let open Or_error.Let_syntax in
+    let t = create src in
+    let%bind t = replace t (loc 0) "# Renamed" in
+    let%bind t = remove t (loc 2) in
+    let%bind t = insert_after t (loc 1) "Inserted." in
+    return t
+  in
+  let t = Or_error.ok_exn edits in
+  print_string (apply t);
+  [%expect
+    {|
+    # Renamed
+
+    First para.
+
+    Inserted.
+
+
+    ## Sub
+    |}];
+  (* [## Sub] moved but is still found through the mapping *)
+  let sub = Cmarkit.Textloc.first_byte (loc 3) in
+  let mapped = Option.value_exn (map_byte t sub) in
+  print_endline (String.sub (apply t) ~pos:mapped ~len:6);
+  [%expect {| ## Sub |}];
+  print_s [%sexp (map_byte t 2 : int option)];
+  [%expect {| () |}];
+  print_s [%sexp (Or_error.is_error (replace t (loc 0) "# Again") : bool)];
+  [%expect {| true |}]
+;;
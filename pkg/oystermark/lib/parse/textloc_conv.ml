@@ -1,4 +1,4 @@
-(** Sexp and compare conversions for [Cmarkit.Textloc.t]. *)
+(** Sexp, JSON and compare conversions for [Cmarkit.Textloc.t]. *)
 
 open Core
 
@@ -26,10 +26,14 @@ let t_of_sexp (sexp : Sexp.t) : Cmarkit.Textloc.t =
   match sexp with
   | Sexp.List fields ->
     let get name =
-      List.find_map_exn fields ~f:(fun field ->
-        match field with
-        | Sexp.List [ Atom n; v ] when String.equal n name -> Some v
-        | _ -> None)
+      match
+        List.find_map fields ~f:(fun field ->
+          match field with
+          | Sexp.List [ Atom n; v ] when String.equal n name -> Some v
+          | _ -> None)
+      with
+      | Some v -> v
+      | None -> of_sexp_error ("missing field " ^ name) sexp
     in
     let first_byte =
       match get "first_byte" with
@@ -58,3 +62,80 @@ let compare (a : Cmarkit.Textloc.t) (b : Cmarkit.Textloc.t) : int =
   then c
   else Int.compare (Cmarkit.Textloc.last_byte a) (Cmarkit.Textloc.last_byte b)
 ;;
+
+(** JSON form: [{"first_byte", "last_byte", "first_line", "last_line"}], lines
+    as [[line, byte]] pairs like the sexp. *)
+let yojson_of_t (tl : Cmarkit.Textloc.t) : Yojson.Safe.t =
+  let line_pos ((line, byte) : int * int) : Yojson.Safe.t =
+    `List [ `Int line; `Int byte ]
+  in
+  `Assoc
+    [ "first_byte", `Int (Cmarkit.Textloc.first_byte tl)
+    ; "last_byte", `Int (Cmarkit.Textloc.last_byte tl)
+    ; "first_line", line_pos (Cmarkit.Textloc.first_line tl)
+    ; "last_line", line_pos (Cmarkit.Textloc.last_line tl)
+    ]
+;;
+
+(** The textloc [json] gives in {!yojson_of_t}'s form, or what is wrong with it. *)
+let of_yojson (json : Yojson.Safe.t) : (Cmarkit.Textloc.t, string) Result.t =
+  let open Result.Let_syntax in
+  let get name =
+    match json with
+    | `Assoc fields -> List.Assoc.find fields ~equal:String.equal name
+    | _ -> None
+  in
+  let int name =
+    match get name with
+    | Some (`Int i) -> Ok i
+    | _ -> Error (sprintf "textloc: expected int field %s" name)
+  in
+  let line_pos name =
+    match get name with
+    | Some (`List [ `Int line; `Int byte ]) -> Ok (line, byte)
+    | _ -> Error (sprintf "textloc: expected [line, byte] field %s" name)
+  in
+  let%map first_byte = int "first_byte"
+  and last_byte = int "last_byte"
+  and first_line = line_pos "first_line"
+  and last_line = line_pos "last_line" in
+  Cmarkit.Textloc.v
+    ~file:Cmarkit.Textloc.file_none
+    ~first_byte
+    ~last_byte
+    ~first_line
+    ~last_line
+;;
+
+(** {!of_yojson} for [[@@deriving yojson]]: raises the error derived
+    converters report, naming the offending JSON. *)
+let t_of_yojson (json : Yojson.Safe.t) : Cmarkit.Textloc.t =
+  match of_yojson json with
+  | Ok tl -> tl
+  | Error msg -> Ppx_yojson_conv_lib.Yojson_conv.of_yojson_error msg json
+;;
+
+let%expect_test "of_yojson" =
+  let tl =
+    Cmarkit.Textloc.v
+      ~file:Cmarkit.Textloc.file_none
+      ~first_byte:3
+      ~last_byte:9
+      ~first_line:(1, 0)
+      ~last_line:(2, 5)
+  in
+  let show (json : Yojson.Safe.t) : unit =
+    match of_yojson json with
+    | Ok tl -> print_endline (Yojson.Safe.to_string (yojson_of_t tl))
+    | Error msg -> print_endline msg
+  in
+  show (yojson_of_t tl);
+  show (`Assoc [ "first_byte", `Int 3 ]);
+  show (`String "3-9");
+  [%expect
+    {|
+    {"first_byte":3,"last_byte":9,"first_line":[1,0],"last_line":[2,5]}
+    textloc: expected int field last_byte
+    textloc: expected int field first_byte
+    |}]
+;;
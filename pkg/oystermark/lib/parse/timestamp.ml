@@ -0,0 +1,174 @@
+(** Dates and date-times as written in frontmatter and inline fields:
+    [2024-05-01], [2024-05-01T09:30], [2024-05-01 09:30:15+02:00],
+    [2024-05-01T09:30:15.5Z].
+
+    The source text is kept, so printing a parsed value gives back what was
+    written. Comparison goes through the instant instead: a date is midnight,
+    and a time without an offset is read as UTC. *)
+
+open Core
+
+type t =
+  { text : string
+  ; date : Date.t
+  ; seconds : int option (** seconds since midnight, when a time is given *)
+  ; utc_offset : int option (** minutes east of UTC *)
+  }
+
+let to_string (t : t) : string = t.text
+
+(** [YYYY-MM-DD] part of the value. *)
+let date_string (t : t) : string = Date.to_string t.date
+
+let epoch : Date.t = Date.create_exn ~y:1970 ~m:Jan ~d:1
+
+(* Seconds since the Unix epoch, ignoring fractions of a second. *)
+let instant (t : t) : int =
+  (Date.diff t.date epoch * 86_400)
+  + Option.value t.seconds ~default:0
+  - (Option.value t.utc_offset ~default:0 * 60)
+;;
+
+let compare (a : t) (b : t) : int =
+  match Int.compare (instant a) (instant b) with
+  | 0 -> String.compare a.text b.text
+  | c -> c
+;;
+
+let equal (a : t) (b : t) : bool = String.equal a.text b.text
+
+(* Digits of [s] between [pos] and [pos + len], when all are digits. *)
+let int_at (s : string) ~(pos : int) ~(len : int) : int option =
+  if pos + len > String.length s
+  then None
+  else (
+    let sub = String.sub s ~pos ~len in
+    if String.for_all sub ~f:Char.is_digit then Some (Int.of_string sub) else None)
+;;
+
+let char_at (s : string) (pos : int) : char option =
+  if pos < String.length s then Some s.[pos] else None
+;;
+
+(* [HH:MM[:SS[.fff]]], returning seconds since midnight and the next position. *)
+let parse_time (s : string) ~(pos : int) : (int * int) option =
+  let open Option.Let_syntax in
+  let%bind h = int_at s ~pos ~len:2 in
+  let%bind () =
+    Option.some_if ([%equal: char option] (char_at s (pos + 2)) (Some ':')) ()
+  in
+  let%bind m = int_at s ~pos:(pos + 3) ~len:2 in
+  let sec, pos =
+    match char_at s (pos + 5), int_at s ~pos:(pos + 6) ~len:2 with
+    | Some ':', Some sec ->
+      let pos = pos + 8 in
+      (match char_at s pos with
+       | Some '.' ->
+         let frac =
+           String.drop_prefix s (pos + 1) |> String.take_while ~f:Char.is_digit
+         in
+         sec, pos + 1 + String.length frac
+       | _ -> sec, pos)
+    | _ -> 0, pos + 5
+  in
+  if h < 24 && m < 60 && sec < 61 then Some ((h * 3600) + (m * 60) + sec, pos) else None
+;;
+
+(* [Z], [+HH:MM], [+HHMM] or [+HH] at the end of [s]; [Some None] if absent. *)
+let parse_offset (s : string) ~(pos : int) : int option option =
+  let rest = String.drop_prefix s pos in
+  match rest with
+  | "" -> Some None
+  | "Z" | "z" -> Some (Some 0)
+  | _ ->
+    let sign =
+      match rest.[0] with
+      | '+' -> Some 1
+      | '-' -> Some (-1)
+      | _ -> None
+    in
+    let hm = String.drop_prefix rest 1 |> String.filter ~f:(fun c -> Char.( <> ) c ':') in
+    (match sign, String.length hm, int_at hm ~pos:0 ~len:2 with
+     | Some sign, 2, Some h -> Some (Some (sign * h * 60))
+     | Some sign, 4, Some h ->
+       Option.map (int_at hm ~pos:2 ~len:2) ~f:(fun m -> Some (sign * ((h * 60) + m)))
+     | _ -> None)
+;;
+
+(** Parse a date or date-time, or [None] when [s] is not one. *)
+let of_string (s : string) : t option =
+  let text = String.strip s in
+  let open Option.Let_syntax in
+  let%bind y = int_at text ~pos:0 ~len:4 in
+  let%bind m = int_at text ~pos:5 ~len:2 in
+  let%bind d = int_at text ~pos:8 ~len:2 in
+  let%bind () =
+    Option.some_if
+      (Char.equal text.[4] '-' && Char.equal text.[7] '-' && m >= 1 && m <= 12)
+      ()
+  in
+  let%bind date =
+    Option.try_with (fun () -> Date.create_exn ~y ~m:(Month.of_int_exn m) ~d)
+  in
+  match char_at text 10 with
+  | None -> Some { text; date; seconds = None; utc_offset = None }
+  | Some ('T' | 't' | ' ') ->
+    let%bind seconds, pos = parse_time text ~pos:11 in
+    let%bind utc_offset = parse_offset text ~pos in
+    Some { text; date; seconds = Some seconds; utc_offset }
+  | Some _ -> None
+;;
+
+(** Compare two strings as timestamps when both parse, as text otherwise. *)
+let compare_text (a : string) (b : string) : int =
+  match of_string a, of_string b with
+  | Some a, Some b -> compare a b
+  | _ -> String.compare a b
+;;
+
+let sexp_of_t (t : t) : Sexp.t = Sexp.Atom t.text
+
+let t_of_sexp (sexp : Sexp.t) : t =
+  match of_string (String.t_of_sexp sexp) with
+  | Some t -> t
+  | None -> of_sexp_error "Timestamp.t_of_sexp: not a date" sexp
+;;
+
+let%expect_test "of_string" =
+  List.iter
+    [ "2024-05-01"
+    ; "2024-05-01T09:30"
+    ; "2024-05-01 09:30:15+02:00"
+    ; "2024-05-01T09:30:15.123456Z"
+    ; "2024-02-30"
+    ; "2024-05-01 and more"
+    ; "May 1"
+    ]
+    ~f:(fun s ->
+      match of_string s with
+      | None -> printf "%-30s <none>\n" s
+      | Some t ->
+        printf
+          "%-30s %s %s\n"
+          s
+          (date_string t)
+          (Option.value_map t.seconds ~default:"-" ~f:Int.to_string));
+  [%expect
+    {|
+    2024-05-01                     2024-05-01 -
+    2024-05-01T09:30               2024-05-01 34200
+    2024-05-01 09:30:15+02:00      2024-05-01 34215
+    2024-05-01T09:30:15.123456Z    2024-05-01 34215
+    2024-02-30                     <none>
+    2024-05-01 and more            <none>
+    May 1                          <none>
+    |}];
+  (* 09:30+02:00 is 07:30 UTC, before 08:00 UTC; a later day wins over text order *)
+  print_s
+    [%sexp
+      (compare_text "2024-05-01T09:30+02:00" "2024-05-01T08:00Z" < 0
+       , compare_text "2024-05-10" "2024-05-09T23:59" > 0
+       , compare_text "T1" "T2" < 0
+       : bool * bool * bool)];
+  [%expect {| (true true true) |}]
+;;
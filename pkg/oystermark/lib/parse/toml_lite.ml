@@ -0,0 +1,430 @@
+(** The subset of TOML found in frontmatter, read into and written from the
+    same [Yaml_backend.value] as YAML frontmatter.
+
+    Supported: [key = value] pairs with bare, quoted or dotted keys; [[table]]
+    and [[[array.of.tables]]] headers; basic and literal strings; integers,
+    floats and booleans; arrays (possibly spanning lines) and inline tables.
+    Dates and times are kept as strings. *)
+
+open Core
+
+exception Parse_error of string
+
+let fail fmt = Printf.ksprintf (fun s -> raise (Parse_error s)) fmt
+
+(* Cursor over one logical line. *)
+type cursor =
+  { s : string
+  ; mutable pos : int
+  }
+
+let peek (c : cursor) : char option =
+  if c.pos < String.length c.s then Some c.s.[c.pos] else None
+;;
+
+let rec skip_ws (c : cursor) : unit =
+  match peek c with
+  | Some (' ' | '\t' | '\n' | '\r') ->
+    c.pos <- c.pos + 1;
+    skip_ws c
+  | Some '#' ->
+    (match String.index_from c.s c.pos '\n' with
+     | Some i -> c.pos <- i
+     | None -> c.pos <- String.length c.s);
+    skip_ws c
+  | _ -> ()
+;;
+
+let expect (c : cursor) (ch : char) : unit =
+  skip_ws c;
+  match peek c with
+  | Some x when Char.equal x ch -> c.pos <- c.pos + 1
+  | _ -> fail "expected %C at %d in %S" ch c.pos c.s
+;;
+
+let basic_string (c : cursor) : string =
+  let buf = Buffer.create 16 in
+  c.pos <- c.pos + 1;
+  let rec loop () =
+    match peek c with
+    | None -> fail "unterminated string in %S" c.s
+    | Some '"' -> c.pos <- c.pos + 1
+    | Some '\\' ->
+      let esc = if c.pos + 1 < String.length c.s then c.s.[c.pos + 1] else '\\' in
+      Buffer.add_char
+        buf
+        (match esc with
+         | 'n' -> '\n'
+         | 't' -> '\t'
+         | 'r' -> '\r'
+         | other -> other);
+      c.pos <- c.pos + 2;
+      loop ()
+    | Some ch ->
+      Buffer.add_char buf ch;
+      c.pos <- c.pos + 1;
+      loop ()
+  in
+  loop ();
+  Buffer.contents buf
+;;
+
+let literal_string (c : cursor) : string =
+  match String.index_from c.s (c.pos + 1) '\'' with
+  | None -> fail "unterminated string in %S" c.s
+  | Some i ->
+    let v = String.sub c.s ~pos:(c.pos + 1) ~len:(i - c.pos - 1) in
+    c.pos <- i + 1;
+    v
+;;
+
+let is_bare_char (ch : char) : bool =
+  Char.is_alphanum ch || Char.equal ch '_' || Char.equal ch '-'
+;;
+
+let key_segment (c : cursor) : string =
+  skip_ws c;
+  match peek c with
+  | Some '"' -> basic_string c
+  | Some '\'' -> literal_string c
+  | _ ->
+    let start = c.pos in
+    while Option.value_map (peek c) ~default:false ~f:is_bare_char do
+      c.pos <- c.pos + 1
+    done;
+    if c.pos = start then fail "expected a key at %d in %S" start c.s;
+    String.sub c.s ~pos:start ~len:(c.pos - start)
+;;
+
+(* A possibly dotted key. *)
+let key (c : cursor) : string list =
+  let rec loop acc =
+    let acc = key_segment c :: acc in
+    skip_ws c;
+    match peek c with
+    | Some '.' ->
+      c.pos <- c.pos + 1;
+      loop acc
+    | _ -> List.rev acc
+  in
+  loop []
+;;
+
+(* Scalars without quotes: booleans, numbers, and dates kept as strings. *)
+let bare_scalar (c : cursor) : Yaml_backend.value =
+  let start = c.pos in
+  while
+    match peek c with
+    | Some (',' | ']' | '}' | '\n' | '#') | None -> false
+    | Some _ -> true
+  do
+    c.pos <- c.pos + 1
+  done;
+  let raw = String.strip (String.sub c.s ~pos:start ~len:(c.pos - start)) in
+  let number = String.filter raw ~f:(fun ch -> not (Char.equal ch '_')) in
+  match raw with
+  | "true" -> `Bool true
+  | "false" -> `Bool false
+  | "" -> fail "expected a value at %d in %S" start c.s
+  | _ ->
+    (match Float.of_string_opt number with
+     | Some f when not (String.mem raw ':') -> `Float f
+     | _ -> `String raw)
+;;
+
+(* Bind [k] in [pairs], in place if present so key order follows the source. *)
+let replace
+      (pairs : (string * Yaml_backend.value) list)
+      (k : string)
+      (v : Yaml_backend.value)
+  : (string * Yaml_backend.value) list
+  =
+  if List.Assoc.mem pairs ~equal:String.equal k
+  then List.map pairs ~f:(fun (k', v') -> if String.equal k k' then k', v else k', v')
+  else pairs @ [ k, v ]
+;;
+
+let rec value (c : cursor) : Yaml_backend.value =
+  skip_ws c;
+  match peek c with
+  | Some '"' -> `String (basic_string c)
+  | Some '\'' -> `String (literal_string c)
+  | Some '[' ->
+    c.pos <- c.pos + 1;
+    `A (items c ~close:']' ~item:value)
+  | Some '{' ->
+    c.pos <- c.pos + 1;
+    let pairs =
+      items c ~close:'}' ~item:(fun c ->
+        let k = key c in
+        expect c '=';
+        k, value c)
+    in
+    List.fold pairs ~init:(`O []) ~f:(fun acc (k, v) -> set acc k v)
+  | _ -> bare_scalar c
+
+(* Comma separated [item]s up to [close]; a trailing comma is allowed. *)
+and items : 'a. cursor -> close:char -> item:(cursor -> 'a) -> 'a list =
+  fun c ~close ~item ->
+  let rec loop acc =
+    skip_ws c;
+    match peek c with
+    | Some ch when Char.equal ch close ->
+      c.pos <- c.pos + 1;
+      List.rev acc
+    | None -> fail "unterminated %C in %S" close c.s
+    | Some _ ->
+      let acc = item c :: acc in
+      skip_ws c;
+      (match peek c with
+       | Some ',' -> c.pos <- c.pos + 1
+       | _ -> ());
+      loop acc
+  in
+  loop []
+
+(* [set obj path v] binds [path] in the table [obj], creating tables on the way
+   and appending to the last element of an array of tables. *)
+and set (obj : Yaml_backend.value) (path : string list) (v : Yaml_backend.value)
+  : Yaml_backend.value
+  =
+  match obj, path with
+  | `O pairs, [ k ] -> `O (replace pairs k v)
+  | `O pairs, k :: rest ->
+    let child =
+      match List.Assoc.find pairs ~equal:String.equal k with
+      | Some (`A (_ :: _ as tables)) ->
+        let last = List.last_exn tables in
+        `A (List.drop_last_exn tables @ [ set last rest v ])
+      | Some child -> set child rest v
+      | None -> set (`O []) rest v
+    in
+    `O (replace pairs k child)
+  | _, [] -> v
+  | _, _ -> fail "key %s is not a table" (String.concat ~sep:"." path)
+;;
+
+let rec get (obj : Yaml_backend.value) (path : string list) : Yaml_backend.value option =
+  match obj, path with
+  | v, [] -> Some v
+  | `O pairs, k :: rest ->
+    Option.bind (List.Assoc.find pairs ~equal:String.equal k) ~f:(fun v -> get v rest)
+  | _ -> None
+;;
+
+(* Join physical lines into logical ones: a value may continue over lines while
+   brackets or braces are open. Brackets in strings and comments don't count. *)
+let logical_lines (s : string) : string list =
+  let depth (line : string) : int =
+    let n = String.length line in
+    let rec go (i : int) (depth : int) (quote : char option) : int =
+      if i >= n
+      then depth
+      else (
+        match quote, line.[i] with
+        | None, '#' -> depth
+        | None, (('"' | '\'') as q) -> go (i + 1) depth (Some q)
+        | None, ('[' | '{') -> go (i + 1) (depth + 1) None
+        | None, (']' | '}') -> go (i + 1) (depth - 1) None
+        | Some '"', '\\' -> go (i + 2) depth quote
+        | Some q, ch when Char.equal ch q -> go (i + 1) depth None
+        | _ -> go (i + 1) depth quote)
+    in
+    go 0 0 None
+  in
+  let rec loop acc pending open_ = function
+    | [] -> List.rev (if String.is_empty pending then acc else pending :: acc)
+    | line :: rest ->
+      let pending = if String.is_empty pending then line else pending ^ "\n" ^ line in
+      let is_header = String.is_prefix (String.lstrip line) ~prefix:"[" && open_ = 0 in
+      let open_ = if is_header then 0 else open_ + depth line in
+      if open_ > 0 then loop acc pending open_ rest else loop (pending :: acc) "" 0 rest
+  in
+  loop [] "" 0 (String.split_lines s)
+;;
+
+(** Parse a TOML document into a [`O] value. *)
+let of_string (s : string) : (Yaml_backend.value, string) Result.t =
+  let step (doc, table) (line : string) =
+    let c = { s = line; pos = 0 } in
+    skip_ws c;
+    match peek c with
+    | None -> doc, table
+    | Some '[' when String.is_prefix (String.drop_prefix line c.pos) ~prefix:"[[" ->
+      c.pos <- c.pos + 2;
+      let path = key c in
+      expect c ']';
+      expect c ']';
+      let tables =
+        match get doc path with
+        | Some (`A tables) -> tables
+        | _ -> []
+      in
+      set doc path (`A (tables @ [ `O [] ])), path
+    | Some '[' ->
+      c.pos <- c.pos + 1;
+      let path = key c in
+      expect c ']';
+      let doc = if Option.is_some (get doc path) then doc else set doc path (`O []) in
+      doc, path
+    | Some _ ->
+      let k = key c in
+      expect c '=';
+      let v = value c in
+      skip_ws c;
+      if Option.is_some (peek c) then fail "trailing characters in %S" line;
+      set doc (table @ k) v, table
+  in
+  match List.fold (logical_lines s) ~init:(`O [], []) ~f:step with
+  | doc, _ -> Ok doc
+  | exception Parse_error msg -> Error msg
+;;
+
+let is_bare_key (k : string) : bool =
+  (not (String.is_empty k)) && String.for_all k ~f:is_bare_char
+;;
+
+let quote (s : string) : string =
+  let buf = Buffer.create (String.length s + 2) in
+  Buffer.add_char buf '"';
+  String.iter s ~f:(function
+    | '"' -> Buffer.add_string buf "\\\""
+    | '\\' -> Buffer.add_string buf "\\\\"
+    | '\n' -> Buffer.add_string buf "\\n"
+    | '\t' -> Buffer.add_string buf "\\t"
+    | ch -> Buffer.add_char buf ch);
+  Buffer.add_char buf '"';
+  Buffer.contents buf
+;;
+
+let key_to_string (k : string) : string = if is_bare_key k then k else quote k
+
+let rec inline_value (v : Yaml_backend.value) : string =
+  match v with
+  | `Null -> "\"\""
+  | `Bool b -> Bool.to_string b
+  | `Float f when Float.is_integer f -> Int.to_string (Float.to_int f)
+  | `Float f -> Float.to_string f
+  | `String s -> quote s
+  | `A items -> "[" ^ String.concat ~sep:", " (List.map items ~f:inline_value) ^ "]"
+  | `O pairs ->
+    "{ "
+    ^ String.concat
+        ~sep:", "
+        (List.map pairs ~f:(fun (k, v) -> key_to_string k ^ " = " ^ inline_value v))
+    ^ " }"
+;;
+
+(** Render a [`O] value as TOML: scalars and arrays as [key = value] pairs,
+    nested objects as [[table]] sections. Other values render inline. *)
+let to_string (v : Yaml_backend.value) : string =
+  let buf = Buffer.create 64 in
+  let rec table (path : string list) (pairs : (string * Yaml_backend.value) list) =
+    let nested, flat =
+      List.partition_tf pairs ~f:(fun (_, v) ->
+        match v with
+        | `O _ -> true
+        | _ -> false)
+    in
+    if (not (List.is_empty path)) && not (List.is_empty flat)
+    then (
+      if Buffer.length buf > 0 then Buffer.add_char buf '\n';
+      Buffer.add_string
+        buf
+        ("[" ^ String.concat ~sep:"." (List.map path ~f:key_to_string) ^ "]\n"));
+    List.iter flat ~f:(fun (k, v) ->
+      Buffer.add_string buf (key_to_string k ^ " = " ^ inline_value v ^ "\n"));
+    List.iter nested ~f:(fun (k, v) ->
+      match v with
+      | `O pairs -> table (path @ [ k ]) pairs
+      | _ -> ())
+  in
+  (match v with
+   | `O pairs -> table [] pairs
+   | other -> Buffer.add_string buf (inline_value other ^ "\n"));
+  Buffer.contents buf
+;;
+
+let%expect_test "of_string and to_string" =
+  let src =
+    {|title = "Hello \"world\""
+draft = false
+weight = 10
+tags = [
+  "a",
+  'b',
+]
+date = 2024-01-02
+author.name = "K" # comment
+
+[params]
+ratio = 1.5
+inline = { x = 1, y = "z" }
+
+[[links]]
+url = "u1"
+
+[[links]]
+url = "u2"
+|}
+  in
+  let v = Result.ok_or_failwith (of_string src) in
+  print_string (Yaml_backend.to_string_exn v);
+  [%expect
+    {|
+    title: Hello "world"
+    draft: false
+    weight: 10
+    tags:
+    - a
+    - b
+    date: 2024-01-02
+    author:
+      name: K
+    params:
+      ratio: 1.5
+      inline:
+        x: 1
+        y: z
+    links:
+    - url: u1
+    - url: u2
+    |}];
+  print_string (to_string v);
+  [%expect
+    {|
+    title = "Hello \"world\""
+    draft = false
+    weight = 10
+    tags = ["a", "b"]
+    date = "2024-01-02"
+    links = [{ url = "u1" }, { url = "u2" }]
+
+    [author]
+    name = "K"
+
+    [params]
+    ratio = 1.5
+
+    [params.inline]
+    x = 1
+    y = "z"
+    |}];
+  (match of_string "a = [1, 2" with
+   | Ok _ -> print_endline "ok"
+   | Error msg -> print_endline msg);
+  [%expect {| unterminated ']' in "a = [1, 2" |}];
+  (* brackets in strings and comments don't hold the line open *)
+  print_string
+    (Yaml_backend.to_string_exn
+       (Result.ok_or_failwith
+          (of_string "a = \"[draft\" # {\nb = '{'\nc = [\"]\", # ]\n  1]\n")));
+  [%expect
+    {|
+    a: '[draft'
+    b: '{'
+    c:
+    - ']'
+    - 1
+    |}]
+;;
@@ -0,0 +1,171 @@
+(** Typed values shared by frontmatter (YAML, TOML, JSON), inline fields
+    ([key:: value]) and query output.
+
+    Each source has its own raw representation: [Yaml_backend.value] stores every
+    number as a float, JSON distinguishes ints from floats, and Markdown
+    scalars are plain text. Converting through {!t} keeps them consistent:
+    - YAML floats that are whole numbers become {!Int}, so [count: 3] prints as
+      [3] in JSON, not [3.];
+    - {!of_markdown} reads [true], [false], numbers and an empty string the way
+      YAML would read the same unquoted scalar;
+    - strings that parse as a {!Timestamp} become {!Date} from every source,
+      and are written back as the same text;
+    - round trips [of_yaml (to_yaml v)] and [of_json (to_json v)] give back
+      [v]. *)
+
+open Core
+
+type t =
+  | Null
+  | Bool of bool
+  | Int of int
+  | Float of float
+  | String of string
+  | Date of Timestamp.t
+  | List of t list
+  | Map of (string * t) list (** keys in source order *)
+[@@deriving sexp, equal, compare]
+
+(* A string, or a {!Date} when it reads as one. *)
+let of_string (s : string) : t =
+  match Timestamp.of_string s with
+  | Some d -> Date d
+  | None -> String s
+;;
+
+(** {1 YAML} *)
+
+let rec of_yaml (v : Yaml_backend.value) : t =
+  match v with
+  | `Null -> Null
+  | `Bool b -> Bool b
+  | `Float f when Float.is_integer f && Float.(abs f < 2. ** 53.) ->
+    Int (Float.to_int f)
+  | `Float f -> Float f
+  | `String s -> of_string s
+  | `A vs -> List (List.map vs ~f:of_yaml)
+  | `O kvs -> Map (List.map kvs ~f:(fun (k, v) -> k, of_yaml v))
+;;
+
+let rec to_yaml (v : t) : Yaml_backend.value =
+  match v with
+  | Null -> `Null
+  | Bool b -> `Bool b
+  | Int i -> `Float (Float.of_int i)
+  | Float f -> `Float f
+  | String s -> `String s
+  | Date d -> `String (Timestamp.to_string d)
+  | List vs -> `A (List.map vs ~f:to_yaml)
+  | Map kvs -> `O (List.map kvs ~f:(fun (k, v) -> k, to_yaml v))
+;;
+
+(** {1 JSON} *)
+
+let rec of_json (j : Yojson.Safe.t) : t =
+  match j with
+  | `Null -> Null
+  | `Bool b -> Bool b
+  | `Int i -> Int i
+  | `Intlit s -> String s
+  | `Float f -> Float f
+  | `String s -> of_string s
+  | `List js | `Tuple js -> List (List.map js ~f:of_json)
+  | `Assoc kvs -> Map (List.map kvs ~f:(fun (k, v) -> k, of_json v))
+  | `Variant (name, arg) ->
+    List (String name :: Option.to_list (Option.map arg ~f:of_json))
+;;
+
+let rec to_json (v : t) : Yojson.Safe.t =
+  match v with
+  | Null -> `Null
+  | Bool b -> `Bool b
+  | Int i -> `Int i
+  | Float f -> `Float f
+  | String s -> `String s
+  | Date d -> `String (Timestamp.to_string d)
+  | List vs -> `List (List.map vs ~f:to_json)
+  | Map kvs -> `Assoc (List.map kvs ~f:(fun (k, v) -> k, to_json v))
+;;
+
+let rec to_json_basic (v : t) : Yojson.Basic.t =
+  match v with
+  | Null -> `Null
+  | Bool b -> `Bool b
+  | Int i -> `Int i
+  | Float f -> `Float f
+  | String s -> `String s
+  | Date d -> `String (Timestamp.to_string d)
+  | List vs -> `List (List.map vs ~f:to_json_basic)
+  | Map kvs -> `Assoc (List.map kvs ~f:(fun (k, v) -> k, to_json_basic v))
+;;
+
+let of_json_basic (j : Yojson.Basic.t) : t = of_json (j :> Yojson.Safe.t)
+
+(** {1 Markdown scalars} *)
+
+(** Type a scalar written as Markdown text, e.g. an inline field value.
+    Surrounding whitespace is ignored; anything that is not a boolean or a
+    number stays a {!String}. *)
+let of_markdown (s : string) : t =
+  match String.strip s with
+  | "" -> Null
+  | "true" | "True" | "TRUE" -> Bool true
+  | "false" | "False" | "FALSE" -> Bool false
+  | s ->
+    (match Int.of_string_opt s with
+     | Some i -> Int i
+     | None ->
+       (match Float.of_string_opt s with
+        | Some f when Float.is_finite f && String.exists s ~f:Char.is_digit -> Float f
+        | _ -> of_string s))
+;;
+
+(** Text for a scalar; lists and maps as compact JSON. *)
+let to_markdown (v : t) : string =
+  match v with
+  | Null -> ""
+  | Bool b -> Bool.to_string b
+  | Int i -> Int.to_string i
+  | Float f -> Float.to_string f
+  | String s -> s
+  | Date d -> Timestamp.to_string d
+  | List _ | Map _ -> Yojson.Safe.to_string (to_json v)
+;;
+
+(** Whether [v], or an element of a list [v], equals the scalar [expected]
+    typed with {!of_markdown}, so [3] matches both [3] and [3.0]. *)
+let rec matches (v : t) (expected : string) : bool =
+  match v, of_markdown expected with
+  | List vs, _ -> List.exists vs ~f:(fun v -> matches v expected)
+  | Int i, Float f | Float f, Int i -> Float.equal (Float.of_int i) f
+  | Null, String "null" -> true
+  | v, e -> equal v e
+;;
+
+let%expect_test "conversions" =
+  let yaml = "count: 3\nratio: 0.5\ndraft: false\ntags: [a, 1]\nnone:\n" in
+  let v = of_yaml (Yaml_backend.of_string_exn yaml) in
+  print_endline (Yojson.Safe.to_string (to_json v));
+  [%expect {| {"count":3,"ratio":0.5,"draft":false,"tags":["a",1],"none":null} |}];
+  print_s [%sexp (equal v (of_yaml (to_yaml v)) && equal v (of_json (to_json v)) : bool)];
+  [%expect {| true |}];
+  List.iter
+    [ "9"; " 2.5 "; "true"; ""; "1e3"; "inf"; "2024-05-01"; "Ursula" ]
+    ~f:(fun s -> print_s [%sexp (of_markdown s : t)]);
+  [%expect
+    {|
+    (Int 9)
+    (Float 2.5)
+    (Bool true)
+    Null
+    (Float 1000)
+    (String inf)
+    (Date 2024-05-01)
+    (String Ursula)
+    |}];
+  print_s
+    [%sexp
+      (matches (Float 3.) "3", matches (List [ String "a"; Int 1 ]) "1", matches Null "x"
+       : bool * bool * bool)];
+  [%expect {| (true true false) |}]
+;;
@@ -56,7 +56,7 @@ let transclude_code_files : t =
                  (match lang_of_path file_path with
                   | None -> Cmarkit.Mapper.default
                   | Some lang ->
-                    let full_path = Filename.concat ctx.vault_root file_path in
+                    let full_path = Vault.full_path ctx file_path in
                     let content = In_channel.read_all full_path in
                     let cb =
                       Cmarkit.Block.Code_block.make
@@ -73,6 +73,27 @@ let transclude_code_files : t =
   make ~on_vault ()
 ;;
 
+(** Fill code fences naming a [file=] (see {!Parse.Code_include}) from that
+    file, its path taken from the vault root. Paths leading out of the vault,
+    absolute or through [..], are not read. Fences that can't be filled keep
+    their own code and are reported to [on_warning]. *)
+let include_code_lines ?(on_warning : string -> unit = ignore) () : t =
+  let on_vault : Vault.t -> Vault.t =
+    map_each_doc (fun (ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+      let read_file (file : string) : string option =
+        let%bind.Option file = Vault.confined_path file in
+        Option.try_with (fun () -> In_channel.read_all (Vault.full_path ctx file))
+      in
+      [ ( path
+        , Parse.Code_include.expand
+            ~on_error:(fun msg -> on_warning (sprintf "%s: %s" path msg))
+            ~read_file
+            doc )
+      ])
+  in
+  make ~on_vault ()
+;;
+
 include struct
   let fm_has_pyproject_in_oyster (fm_opt : Parse.Frontmatter.t option) : bool =
     match fm_opt with
@@ -184,14 +205,24 @@ let traced_code_exec
     inline SVG.  The layout engine defaults to ["dot"] but can be overridden
     via the Pandoc attribute [layout], e.g. [```dot {layout=neato}].
 
+    @param colors the site theme's colors, for diagrams that match it.
     @param on_error controls what is rendered when [dot] fails.
     [`Keep_original] (default) leaves the code block unchanged.
     [`Show_error] replaces it with an [=html] block showing the stderr. *)
-let dot_render ?(on_error : [ `Keep_original | `Show_error ] = `Keep_original) () : t =
+let dot_render
+      ?(colors : Code_executor.diagram_colors option)
+      ?(on_error : [ `Keep_original | `Show_error ] = `Keep_original)
+      ()
+  : t
+  =
+  let salt =
+    Option.map colors ~f:(fun c ->
+      Sexp.to_string [%sexp (c : Code_executor.diagram_colors)])
+  in
   code_exec
     ~fm_filter:(fun _ -> true)
     ~loc_map:(fun _ -> `Replace)
-    ~executor:(Code_executor.dot_executor ~on_error)
-    ~hash_fn:(Code_executor.hash_fn_of_lang "dot")
+    ~executor:(Code_executor.dot_executor ?colors ~on_error ())
+    ~hash_fn:(Code_executor.hash_fn_of_lang ?salt "dot")
     ()
 ;;
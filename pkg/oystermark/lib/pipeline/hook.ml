@@ -0,0 +1,131 @@
+(** External-command hooks, set in the config's [hooks] field (see
+    {!Config.Hooks}). A build runs each command through the shell with a JSON
+    payload on stdin, and reads back what it prints:
+
+    {ul
+    {- [after_parse], per note: [{"stage", "path", "markdown"}]; printing
+       [{"markdown": ...}] replaces the note, which is parsed again.}
+    {- [before_write], per page: [{"stage", "path", "html"}], [path] relative
+       to the output directory; printing [{"html": ...}] replaces the page.}
+    {- [after_build], once: [{"stage", "output_dir", "pages"}]; what it
+       prints is ignored.}}
+
+    Commands of a stage run in order, each seeing what the one before printed.
+    Printing nothing leaves the content as is; a failing command, or one
+    printing anything else, is a warning and changes nothing. *)
+
+open Core
+
+(** What [cmd] prints for [payload], as JSON; [None] if it prints nothing. *)
+let run (cmd : string) (payload : Yojson.Safe.t) : Yojson.Safe.t option Or_error.t =
+  let%bind.Or_error output =
+    Code_executor.Process.run cmd ~input:(Yojson.Safe.to_string payload)
+  in
+  if String.is_empty (String.strip output)
+  then Ok None
+  else (
+    match Yojson.Safe.from_string output with
+    | json -> Ok (Some json)
+    | exception Yojson.Json_error msg -> Or_error.errorf "%s: %s" cmd msg)
+;;
+
+(* [content] through [commands] in order, the string field [key] of what each
+   prints replacing it. *)
+let fold_content
+      ~(on_warning : string -> unit)
+      ~(commands : string list)
+      ~(key : string)
+      ~(payload : string -> Yojson.Safe.t)
+      (content : string)
+  : string
+  =
+  List.fold commands ~init:content ~f:(fun content cmd ->
+    let unexpected (json : Yojson.Safe.t) : string =
+      on_warning
+        (sprintf
+           "hook %s: expected {\"%s\": string}, got %s"
+           cmd
+           key
+           (Yojson.Safe.to_string json));
+      content
+    in
+    match run cmd (payload content) with
+    | Ok None -> content
+    | Ok (Some (`Assoc fields as json)) ->
+      (match List.Assoc.find fields ~equal:String.equal key with
+       | Some (`String s) -> s
+       | Some _ | None -> unexpected json)
+    | Ok (Some json) -> unexpected json
+    | Error e ->
+      on_warning (sprintf "hook %s" (Error.to_string_hum e));
+      content)
+;;
+
+(** The markdown of note [path] after the [after_parse] [commands]. *)
+let after_parse
+      ?(on_warning : string -> unit = ignore)
+      ~(commands : string list)
+      ~(path : string)
+      (markdown : string)
+  : string
+  =
+  fold_content ~on_warning ~commands ~key:"markdown" markdown ~payload:(fun markdown ->
+    `Assoc
+      [ "stage", `String "after_parse"
+      ; "path", `String path
+      ; "markdown", `String markdown
+      ])
+;;
+
+(** The HTML of page [path] after the [before_write] [commands]. *)
+let before_write
+      ?(on_warning : string -> unit = ignore)
+      ~(commands : string list)
+      ~(path : string)
+      (html : string)
+  : string
+  =
+  fold_content ~on_warning ~commands ~key:"html" html ~payload:(fun html ->
+    `Assoc
+      [ "stage", `String "before_write"; "path", `String path; "html", `String html ])
+;;
+
+(** Run the [after_build] [commands] on the site written to [output_dir]. *)
+let after_build
+      ?(on_warning : string -> unit = ignore)
+      ~(commands : string list)
+      ~(output_dir : string)
+      (pages : string list)
+  : unit
+  =
+  let payload : Yojson.Safe.t =
+    `Assoc
+      [ "stage", `String "after_build"
+      ; "output_dir", `String output_dir
+      ; "pages", `List (List.map pages ~f:(fun p -> `String p))
+      ]
+  in
+  List.iter commands ~f:(fun cmd ->
+    match run cmd payload with
+    | Ok _ -> ()
+    | Error e -> on_warning (sprintf "hook %s" (Error.to_string_hum e)))
+;;
+
+let%expect_test "after_parse" =
+  let commands =
+    [ {|sed 's/world/hooks/'|}
+    ; "cat > /dev/null"
+    ; {|cat > /dev/null; echo '{"markdown": 1}'|}
+    ; "cat > /dev/null; exit 1"
+    ]
+  in
+  (* [sed] prints the payload back edited, [markdown] field included *)
+  after_parse ~on_warning:print_endline ~commands ~path:"a.md" "Hello, world!"
+  |> print_endline;
+  [%expect
+    {|
+    hook cat > /dev/null; echo '{"markdown": 1}': expected {"markdown": string}, got {"markdown":1}
+    hook cat > /dev/null; exit 1 failed
+    Hello, hooks!
+    |}]
+;;
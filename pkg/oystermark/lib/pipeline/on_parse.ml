@@ -14,16 +14,60 @@ let exclude_drafts : t =
     ()
 ;;
 
-(** Exclude files without [publish: true] frontmatter. Apply on parse stage. *)
-let exclude_unpublish : t =
+(** Exclude files without [publish: true] frontmatter, or, for paths
+    [published_by_default], with [publish: false]. Apply on parse stage. *)
+let exclude_unpublish ?(published_by_default : string -> bool = fun _ -> false) () : t =
   make
     ~on_parse:(fun path doc ->
-      match Parse.Frontmatter.of_doc doc with
-      | Some (`O fields) ->
-        (match List.Assoc.find fields ~equal:String.equal "publish" with
-         | Some (`Bool true) -> [ path, doc ]
-         | _ -> [])
-      | _ -> [])
+      let publish : bool =
+        match Parse.Frontmatter.of_doc doc with
+        | Some (`O fields) ->
+          (match List.Assoc.find fields ~equal:String.equal "publish" with
+           | Some (`Bool b) -> b
+           | _ -> published_by_default path)
+        | _ -> published_by_default path
+      in
+      if publish then [ path, doc ] else [])
+    ()
+;;
+
+(** Stamp headings with their {!Parse.Section_id}, for the HTML export's
+    [data-section-id]. Apply on parse stage, once templates are in. *)
+let section_ids : t =
+  make ~on_parse:(fun path doc -> [ path, Parse.Section_id.stamp ~path doc ]) ()
+;;
+
+(** Keep heading levels in order under the page title, which the theme
+    renders as the [<h1>]. Sections (see {!Query.Section}) keep their nesting:
+    a section directly under the note is at most an [<h2>], and every other
+    one a level below its parent, so a [###] right after the title renders as
+    [<h2>] and a [####] under a [#] as [<h2>]. Only the headings that open
+    sections move; those in quotes, lists and callouts are left as written.
+    Apply on parse stage. *)
+let heading_order : t =
+  make
+    ~on_parse:(fun path doc ->
+      let rec relevel ~(level : int) (s : Query.Section.t) : Query.Section.t =
+        { s with
+          level
+        ; heading = Option.map s.heading ~f:(Query.Section.relevel_heading ~level)
+        ; children = List.map s.children ~f:(relevel ~level:(level + 1))
+        }
+      in
+      let root = Query.Section.of_doc doc in
+      let top (c : Query.Section.t) = relevel ~level:(Int.min c.level 2) c in
+      let root' = { root with children = List.map root.children ~f:top } in
+      let levels (t : Query.Section.t) : int list =
+        List.map (Query.Section.descendants t) ~f:(fun s -> s.level)
+      in
+      if List.equal Int.equal (levels root) (levels root')
+      then [ path, doc ]
+      else
+        [ ( path
+          , Cmarkit.Doc.make
+              ~defs:(Cmarkit.Doc.defs doc)
+              (Cmarkit.Block.Blocks (Query.Section.to_blocks root', Cmarkit.Meta.none)) )
+        ])
     ()
 ;;
 
@@ -69,3 +113,58 @@ let drop_emtpy_frontmatter : t =
       [ path, Cmarkit.Mapper.map_doc mapper doc ])
     ()
 ;;
+
+(** Lay out Obsidian Kanban boards ({!Parse.Kanban}) as a row of lanes, each a
+    column of its cards, instead of headings and lists. The archive and the
+    plugin's settings are left out. Apply on parse stage. *)
+let kanban_boards : t =
+  make
+    ~on_parse:(fun path doc ->
+      match Parse.Kanban.of_doc doc with
+      | None -> [ path, doc ]
+      | Some board ->
+        let m : Cmarkit.Meta.t = Cmarkit.Meta.none in
+        let html (content : string) : Cmarkit.Block.t =
+          Cmarkit.Block.Code_block
+            ( Cmarkit.Block.Code_block.make
+                ~info_string:("=html", m)
+                (Cmarkit.Block_line.list_of_string content)
+            , m )
+        in
+        let lane (lane : Parse.Kanban.lane) : Cmarkit.Block.t list =
+          let open_lane : string =
+            sprintf
+              "<section class=\"kanban-lane\"%s>\n\
+               <h2 class=\"kanban-lane-title\">%s \
+               <span class=\"kanban-count\">%d</span></h2>"
+              (if lane.complete then " data-complete" else "")
+              (Parse.Frontmatter.escape_html lane.title)
+              (List.length lane.cards)
+          in
+          let cards : Cmarkit.Block.t list =
+            match lane.cards with
+            | [] -> []
+            | cards ->
+              [ Cmarkit.Block.List (Cmarkit.Block.List'.make (`Unordered '-') cards, m) ]
+          in
+          (html open_lane :: cards) @ [ html "</section>" ]
+        in
+        let frontmatter : Cmarkit.Block.t list =
+          match Cmarkit.Doc.block doc with
+          | Cmarkit.Block.Blocks ((Parse.Frontmatter.Frontmatter _ as fm) :: _, _) ->
+            [ fm ]
+          | _ -> []
+        in
+        let blocks : Cmarkit.Block.t list =
+          frontmatter
+          @ [ html "<div class=\"kanban-board\">" ]
+          @ List.concat_map board.lanes ~f:lane
+          @ [ html "</div>" ]
+        in
+        [ ( path
+          , Cmarkit.Doc.make
+              ~defs:(Cmarkit.Doc.defs doc)
+              (Cmarkit.Block.Blocks (blocks, m)) )
+        ])
+    ()
+;;
@@ -13,6 +13,7 @@ include Common
 include On_discover
 include On_parse
 include Code_exec_
+module Hook = Hook
 
 (** Full-path comparator for TOC entries from a {!Config.Toc_order.t}.
     Ranks each path by [toc_order], tiebreaking alphabetically. Strips [.md]
@@ -30,12 +31,66 @@ let compare_path_of_toc_order (toc_order : Config.Toc_order.t) : string -> strin
     if ra <> rb then Int.compare ra rb else String.compare a b
 ;;
 
+(** Full-path comparator for sidebar entries from a {!Config.Sidebar_order.t}.
+    With [Frontmatter], notes carrying a numeric [order] field in [docs] come
+    first, lowest first; a folder takes the field of its [index.md]. Ties and
+    entries without one fall back to [toc_order]. *)
+let compare_path_of_sidebar_order
+      ?(toc_order : Config.Toc_order.t = Config.Toc_order.default)
+      (order : Config.Sidebar_order.t)
+      (docs : (string * Cmarkit.Doc.t) list)
+  : string -> string -> int
+  =
+  match order with
+  | Config.Sidebar_order_def.Alphabetical -> String.compare
+  | Config.Sidebar_order_def.Toc_order -> compare_path_of_toc_order toc_order
+  | Config.Sidebar_order_def.Frontmatter ->
+    let orders : float String.Map.t =
+      List.filter_map docs ~f:(fun (path, doc) ->
+        match Parse.Frontmatter.field doc "order" with
+        | Some (Parse.Value.Int n) -> Some (path, Float.of_int n)
+        | Some (Parse.Value.Float f) -> Some (path, f)
+        | _ -> None)
+      |> String.Map.of_alist_reduce ~f:(fun first _ -> first)
+    in
+    let order_of (path : string) : float option =
+      match Map.find orders path with
+      | Some _ as o -> o
+      | None -> Map.find orders (path ^ "/index.md")
+    in
+    let fallback = compare_path_of_toc_order toc_order in
+    fun a b ->
+      match order_of a, order_of b with
+      | Some oa, Some ob when not (Float.equal oa ob) -> Float.compare oa ob
+      | Some _, None -> -1
+      | None, Some _ -> 1
+      | _ -> fallback a b
+;;
+
+let%expect_test "compare_path_of_sidebar_order frontmatter" =
+  let docs =
+    List.map
+      [ "a.md", ""
+      ; "b.md", "---\norder: 2\n---\n"
+      ; "c.md", "---\norder: 1\n---\n"
+      ; "z/index.md", "---\norder: 1.5\n---\n"
+      ]
+      ~f:(fun (path, src) -> path, Parse.of_string ~locs:false src)
+  in
+  let compare = compare_path_of_sidebar_order Config.Sidebar_order_def.Frontmatter docs in
+  List.sort [ "a.md"; "z"; "b.md"; "c.md" ] ~compare
+  |> String.concat ~sep:" "
+  |> print_endline;
+  [%expect {| c.md z b.md a.md |}]
+;;
+
 (** Add TOC to the home page.
     @param dir_link controls whether directory entries in the TOC are rendered as
     wikilinks (to [dir/index]) or plain text.  Set to [true] when [dir_index]
     is also in the pipeline.
     @param toc_order orders top-level TOC entries; see {!Config.Toc_order}.
-    @param home_path path of the home page; see {!Config.Home}. *)
+    @param home_path path of the home page; see {!Config.Home}.
+    Unlisted notes ({!Vault.is_unlisted}) are left out. *)
 let home_toc
       ?(dir_link : bool = false)
       ?(toc_order : Config.Toc_order.t = Config.Toc_order.default)
@@ -49,8 +104,9 @@ let home_toc
       if not (String.equal path home_path)
       then [ path, doc ]
       else (
+        let listed : Vault.t = { ctx with docs = Vault.listed_docs ctx } in
         let toc_paths : string list =
-          List.filter_map (Vault.all_entry_paths ctx) ~f:(fun p ->
+          List.filter_map (Vault.all_entry_paths listed) ~f:(fun p ->
             if String.is_suffix p ~suffix:"/" then None else Some p)
         in
         let toc_cmark_list = Component.toc_cmark_list ~dir_link ~compare_path toc_paths in
@@ -69,6 +125,7 @@ let home_toc
     @param immediate_only when [true] lists only direct children (files and subdirs);
       when [false] lists all descendants as a nested tree.
     @param toc_order the order to use for TOC entries.
+    Unlisted notes ({!Vault.is_unlisted}) are left out of the listings.
      *)
 let dir_index
       ?(immediate_only : bool = false)
@@ -78,10 +135,11 @@ let dir_index
   =
   let compare_path = compare_path_of_toc_order toc_order in
   let on_vault (ctx : Vault.t) : Vault.t =
-    let doc_paths : string list = List.map ctx.docs ~f:fst in
+    let listed : (string * Cmarkit.Doc.t) list = Vault.listed_docs ctx in
+    let doc_paths : string list = List.map listed ~f:fst in
     let non_empty_dirs : string list =
       List.filter ctx.index.dirs ~f:(fun (dir_path : string) ->
-        List.exists ctx.docs ~f:(fun (p, _) ->
+        List.exists listed ~f:(fun (p, _) ->
           String.is_prefix p ~prefix:dir_path && not (String.equal p dir_path)))
     in
     let all_paths : string list = doc_paths @ non_empty_dirs in
@@ -94,7 +152,7 @@ let dir_index
         else (
           (* Skip directories that contain no notes *)
           let has_notes : bool =
-            List.exists ctx.docs ~f:(fun (p, _) ->
+            List.exists listed ~f:(fun (p, _) ->
               String.is_prefix p ~prefix:dir_path && not (String.equal p dir_path))
           in
           if not has_notes
@@ -135,11 +193,293 @@ let dir_index
   make ~on_vault ()
 ;;
 
-(** Append backlink component to every note's last block. *)
-let backlinks : t =
+(** Put a collapsible transcript under each paragraph embedding audio or
+    video that has one next to it (see {!Vault.Transcript}): the cues of a
+    [.vtt]/[.srt] file, or the body of a [.transcript.md] note, which is kept
+    off the site as a page of its own. *)
+let media_transcripts ?(config : Config.t option) () : t =
+  let on_discover (path : string) (_paths : string list) : bool =
+    not (Vault.Transcript.is_transcript_note path)
+  in
+  let embedded_media (inline : Cmarkit.Inline.t) : string list =
+    let folder =
+      Cmarkit.Folder.make
+        ~inline_ext_default:(fun _f acc (i : Cmarkit.Inline.t) ->
+          match i with
+          | Cmarkit.Inline.Ext_wikilink (w, meta) when Cmarkit.Inline.Wikilink.embed w ->
+            (match Cmarkit.Meta.find Vault.Resolve.resolved_key meta with
+             | Some (Vault.Resolve.File { path }) ->
+               (match Component.Html.media_type_of_href path with
+                | `Audio | `Video -> path :: acc
+                | _ -> acc)
+             | _ -> acc)
+          | _ -> acc)
+        ~block_ext_default:(fun _f acc _b -> acc)
+        ()
+    in
+    List.rev (Cmarkit.Folder.fold_inline folder [] inline)
+  in
   let on_vault : Vault.t -> Vault.t =
     map_each_doc (fun (ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
-      let html : string = Component.backlinks path ctx in
+      let transcript (transcript_path : string) : Cmarkit.Block.t =
+        let src = In_channel.read_all (Vault.full_path ctx transcript_path) in
+        let html (s : string) : Cmarkit.Block.t =
+          Cmarkit.Block.Code_block
+            ( Cmarkit.Block.Code_block.make
+                ~info_string:("=html", Cmarkit.Meta.none)
+                (Cmarkit.Block_line.list_of_string s)
+            , Cmarkit.Meta.none )
+        in
+        let body : Cmarkit.Block.t =
+          if Vault.Transcript.is_transcript_note transcript_path
+          then
+            Parse.of_string ?config src
+            |> Cmarkit.Mapper.map_doc
+                 (Vault.Resolve.resolution_cmarkit_mapper
+                    ~index:ctx.index
+                    ~curr_file:transcript_path
+                    ())
+            |> Cmarkit.Doc.block
+            |> (function
+             | Cmarkit.Block.Blocks (Parse.Frontmatter.Frontmatter _ :: blocks, meta) ->
+               Cmarkit.Block.Blocks (blocks, meta)
+             | b -> b)
+          else html (Vault.Transcript.cues_to_html (Vault.Transcript.cues_of_string src))
+        in
+        Cmarkit.Block.Blocks
+          ( [ html
+                {|<details class="transcript"><summary>Transcript</summary>|}
+            ; body
+            ; html "</details>"
+            ]
+          , Cmarkit.Meta.none )
+      in
+      let exists (p : string) : bool =
+        Sys_unix.file_exists_exn (Vault.full_path ctx p)
+      in
+      let mapper =
+        Cmarkit.Mapper.make
+          ~inline_ext_default:(fun _m i -> Some i)
+          ~block_ext_default:(fun _m b -> Some b)
+          ~block:(fun _m block ->
+            match block with
+            | Cmarkit.Block.Paragraph (p, _) ->
+              (match
+                 List.filter_map
+                   (embedded_media (Cmarkit.Block.Paragraph.inline p))
+                   ~f:(Vault.Transcript.find ~exists)
+               with
+               | [] -> Cmarkit.Mapper.default
+               | transcripts ->
+                 Cmarkit.Mapper.ret
+                   (Cmarkit.Block.Blocks
+                      (block :: List.map transcripts ~f:transcript, Cmarkit.Meta.none)))
+            | _ -> Cmarkit.Mapper.default)
+          ()
+      in
+      [ path, Cmarkit.Mapper.map_doc mapper doc ])
+  in
+  make ~on_discover ~on_vault ()
+;;
+
+(** Attach a {!Vault.Link_preview.t} to each external link, for the HTML
+    export to show. Previews come from [cache]; with [Fetch], pages missing
+    from it are downloaded and added. Does nothing with [Off] or no [cache]. *)
+let link_previews
+      ?(config : Config.Link_previews.t = Config.Link_previews.default)
+      ?(cache : Vault.Link_preview.cache option)
+      ()
+  : t
+  =
+  match config.mode, cache with
+  | Config.Link_preview_mode_def.Off, _ | _, None -> id
+  | mode, Some cache ->
+    let fetch : (string -> Vault.Link_preview.t option) option =
+      match mode with
+      | Config.Link_preview_mode_def.Fetch ->
+        Some (Vault.Link_preview.fetch ~timeout:config.timeout)
+      | Config.Link_preview_mode_def.Offline | Config.Link_preview_mode_def.Off -> None
+    in
+    let with_preview (url : string) (meta : Cmarkit.Meta.t) : Cmarkit.Meta.t option =
+      if Parse.Link_kind.is_external url
+         && not (String.Caseless.is_prefix url ~prefix:"mailto:")
+      then
+        Vault.Link_preview.lookup ?fetch cache url
+        |> Option.map ~f:(fun p -> Cmarkit.Meta.add Vault.Link_preview.meta_key p meta)
+      else None
+    in
+    let on_vault : Vault.t -> Vault.t =
+      map_each_doc (fun (_ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+        let defs = Cmarkit.Doc.defs doc in
+        let mapper =
+          Cmarkit.Mapper.make
+            ~inline_ext_default:(fun _m i -> Some i)
+            ~block_ext_default:(fun _m b -> Some b)
+            ~inline:(fun _m (i : Cmarkit.Inline.t) ->
+              match i with
+              | Cmarkit.Inline.Link (l, meta) ->
+                (match Parse.Link_def.of_link ~defs l with
+                 | Some (Parse.Link_def.Link ld) ->
+                   (match
+                      Option.bind (Parse.Link_def.dest ld) ~f:(fun dest ->
+                        with_preview dest meta)
+                    with
+                    | Some meta -> Cmarkit.Mapper.ret (Cmarkit.Inline.Link (l, meta))
+                    | None -> Cmarkit.Mapper.default)
+                 | Some (Parse.Link_def.Footnote _) | None -> Cmarkit.Mapper.default)
+              | Cmarkit.Inline.Autolink (a, meta) ->
+                (match with_preview (fst (Cmarkit.Inline.Autolink.link a)) meta with
+                 | Some meta -> Cmarkit.Mapper.ret (Cmarkit.Inline.Autolink (a, meta))
+                 | None -> Cmarkit.Mapper.default)
+              | _ -> Cmarkit.Mapper.default)
+            ()
+        in
+        [ path, Cmarkit.Mapper.map_doc mapper doc ])
+    in
+    make ~on_vault ()
+;;
+
+(** Mark the first occurrence of each term of the glossary note in every
+    other note, for the HTML export to annotate; see {!Config.Glossary}. Does
+    nothing without a glossary [note]. [slug_style] is as for
+    {!Vault.Glossary.entries}. *)
+let glossary
+      ?(slug_style : Parse.Heading_slug.slug_style option)
+      ?(config : Config.Glossary.t = Config.Glossary.default)
+      ()
+  : t
+  =
+  if String.is_empty config.note
+  then id
+  else (
+    let link =
+      match config.style with
+      | Config.Glossary_style_def.Link -> true
+      | Config.Glossary_style_def.Abbr -> false
+    in
+    let on_vault (ctx : Vault.t) : Vault.t =
+      match List.Assoc.find ctx.docs ~equal:String.equal config.note with
+      | None -> ctx
+      | Some glossary ->
+        let entries = Vault.Glossary.entries ?slug_style ~path:config.note glossary in
+        map_each_doc
+          (fun (_ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+            if String.equal path config.note || not (Vault.Glossary.enabled doc)
+            then [ path, doc ]
+            else [ path, Vault.Glossary.mark ~link entries doc ])
+          ctx
+    in
+    make ~on_vault ())
+;;
+
+(** Point links to byte-identical assets at one canonical copy, so an image
+    pasted into several folders is exported once. The copy under Obsidian's
+    attachment folder wins; see {!Vault.Assets.duplicates}. *)
+let dedup_assets : t =
+  let on_vault (ctx : Vault.t) : Vault.t =
+    let assets : string list =
+      List.filter_map ctx.index.files ~f:(fun (f : Vault.Index.file_entry) ->
+        if String.is_suffix f.rel_path ~suffix:".md" then None else Some f.rel_path)
+    in
+    let canonical : string String.Map.t =
+      Vault.Assets.duplicates
+        ?attachment_folder:(Vault.Assets.attachment_folder ctx.vault_root)
+        ~read_file:(fun p -> In_channel.read_all (Vault.full_path ctx p))
+        assets
+      |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+    in
+    if Map.is_empty canonical
+    then ctx
+    else
+      { ctx with
+        docs =
+          List.map ctx.docs ~f:(fun (p, doc) -> p, Vault.Assets.retarget canonical doc)
+      }
+  in
+  make ~on_vault ()
+;;
+
+(** Replace [outline-diagram] code fences with a {!Vault.Outline_diagram} of
+    their note: its sections, or with [view=links] its link neighborhood. The
+    diagram is Mermaid, drawn in the browser, or with [format=dot] Graphviz,
+    drawn here like {!dot_render} draws [dot] fences. *)
+let outline_diagrams ?(colors : Code_executor.diagram_colors option) () : t =
+  let module D = Vault.Outline_diagram in
+  let on_vault (ctx : Vault.t) : Vault.t =
+    let links : Vault.Links.t list Lazy.t = lazy (Vault.Links.of_docs ctx.docs) in
+    map_each_doc
+      (fun (_ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+        let has_dot = ref false in
+        let diagram (attribute : Parse.Cb_attribute.t option) : Cmarkit.Block.t =
+          let kvs =
+            Option.value_map attribute ~default:[] ~f:(fun (a : Parse.Cb_attribute.t) ->
+              a.kvs)
+          in
+          let option (type a) key ~(of_string : string -> a option) ~(default : a) : a =
+            List.Assoc.find kvs ~equal:String.equal key
+            |> Option.bind ~f:of_string
+            |> Option.value ~default
+          in
+          let view = option "view" ~of_string:D.view_of_string ~default:D.Sections in
+          let format = option "format" ~of_string:D.format_of_string ~default:D.Mermaid in
+          let t =
+            match view with
+            | D.Sections -> D.of_sections ~path doc
+            | D.Links -> D.of_links ~path (force links)
+          in
+          let lang =
+            match format with
+            | D.Mermaid -> "mermaid"
+            | D.Dot ->
+              has_dot := true;
+              "dot"
+          in
+          let cb =
+            Cmarkit.Block.Code_block.make
+              ~info_string:(lang, Cmarkit.Meta.none)
+              (Cmarkit.Block_line.list_of_string
+                 (String.chop_suffix_if_exists (D.render format t) ~suffix:"\n"))
+          in
+          let meta =
+            Cmarkit.Meta.add
+              Parse.Cb_attribute.meta_key
+              { Parse.Cb_attribute.lang; attribute = None }
+              Cmarkit.Meta.none
+          in
+          Cmarkit.Block.Code_block (cb, meta)
+        in
+        let mapper =
+          Cmarkit.Mapper.make
+            ~inline_ext_default:(fun _m i -> Some i)
+            ~block_ext_default:(fun _m b -> Some b)
+            ~block:(fun _m (b : Cmarkit.Block.t) ->
+              match b with
+              | Cmarkit.Block.Code_block (_, meta) ->
+                (match Cmarkit.Meta.find Parse.Cb_attribute.meta_key meta with
+                 | Some { Parse.Cb_attribute.lang; attribute }
+                   when String.equal lang D.fence_lang ->
+                   Cmarkit.Mapper.ret (diagram attribute)
+                 | _ -> Cmarkit.Mapper.default)
+              | _ -> Cmarkit.Mapper.default)
+            ()
+        in
+        let doc = Cmarkit.Mapper.map_doc mapper doc in
+        if !has_dot then (dot_render ?colors ()).on_parse path doc else [ path, doc ])
+      ctx
+  in
+  make ~on_vault ()
+;;
+
+(** Append backlink component to every note's last block, linking to notes
+    by their [url_style] URLs. Unlisted notes ({!Vault.is_unlisted}) are left
+    out of the backlinks. *)
+let backlinks ?(url_style : Config.Url_style.t = Config.Url_style.default) () : t =
+  let on_vault (ctx : Vault.t) : Vault.t =
+    let urls = Component.Url.of_docs ~style:url_style ctx.docs in
+    let listed : Vault.t = { ctx with docs = Vault.listed_docs ctx } in
+    ctx
+    |> map_each_doc (fun (_ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+      let html : string = Component.backlinks ~urls path listed in
       match html with
       | "" -> [ path, doc ]
       | content ->
@@ -156,9 +496,264 @@ let backlinks : t =
   make ~on_vault ()
 ;;
 
+(** Add a [page_path] page listing the [limit] most recently updated published
+    notes, newest first, each with the date it last changed; unlisted notes
+    are left out. [manifest] is this build's {!Vault.Manifest.t}, already
+    updated against the previous build. A note's [updated] frontmatter date,
+    when set, takes precedence over the time its content last changed. *)
+let recent_changes
+      ?(limit : int = 20)
+      ?(page_path : string = "recent.md")
+      ~(manifest : Vault.Manifest.t)
+      ()
+  : t
+  =
+  let on_vault (ctx : Vault.t) : Vault.t =
+    let m : Cmarkit.Meta.t = Cmarkit.Meta.none in
+    let updated (path : string) (e : Vault.Manifest.entry) : string =
+      match List.Assoc.find ctx.docs ~equal:String.equal path with
+      | Some doc ->
+        (match Parse.Frontmatter.date_field doc "updated" with
+         | Some d -> Parse.Timestamp.to_string d
+         | None -> e.updated)
+      | None -> e.updated
+    in
+    let published : (string * string) list =
+      let listed = Vault.listed_docs ctx in
+      Vault.Manifest.recent manifest
+      |> List.filter_map ~f:(fun (path, e) ->
+        if List.Assoc.mem listed ~equal:String.equal path
+        then Some (path, updated path e)
+        else None)
+      |> List.stable_sort ~compare:(fun (_, a) (_, b) -> Parse.Timestamp.compare_text b a)
+      |> Fn.flip List.take limit
+    in
+    let item ((path : string), (updated : string))
+      : Cmarkit.Block.List_item.t Cmarkit.node
+      =
+      let link : Cmarkit.Inline.t =
+        Vault.Resolve.make_wikilink
+          ~target:(Some (String.chop_suffix_if_exists path ~suffix:".md"))
+          ~fragment:None
+          ~display:None
+          ~embed:false
+          ~resolved_target:(Note { path })
+      in
+      let date : Cmarkit.Inline.t =
+        Cmarkit.Inline.Text (" — " ^ String.prefix updated 10, m)
+      in
+      let para : Cmarkit.Block.Paragraph.t =
+        Cmarkit.Block.Paragraph.make (Cmarkit.Inline.Inlines ([ link; date ], m))
+      in
+      Cmarkit.Block.List_item.make (Cmarkit.Block.Paragraph (para, m)), m
+    in
+    let heading : Cmarkit.Block.t =
+      let title : Cmarkit.Inline.t = Cmarkit.Inline.Text ("Recently updated", m) in
+      Cmarkit.Block.Heading (Cmarkit.Block.Heading.make ~level:1 title, m)
+    in
+    let list : Cmarkit.Block.t =
+      Cmarkit.Block.List
+        (Cmarkit.Block.List'.make (`Unordered '-') (List.map published ~f:item), m)
+    in
+    if List.Assoc.mem ctx.docs ~equal:String.equal page_path
+    then ctx
+    else
+      (let page : Cmarkit.Doc.t =
+         Cmarkit.Doc.make (Cmarkit.Block.Blocks ([ heading; list ], m))
+       in
+       { ctx with docs = ctx.docs @ [ page_path, page ] })
+  in
+  make ~on_vault ()
+;;
+
+(** Add calendar pages for the daily and weekly notes of {!Config.Journal}, in
+    its [page_folder]: a [YYYY-MM.md] page per month with a note, a [YYYY.md]
+    page per year with its months' calendars, and an [index.md] listing the
+    years. Notes already at those paths are kept. Unlisted notes are left out. *)
+let journal_pages
+      ?(config : Config.Journal.t = Config.Journal.default)
+      ?(url_style : Config.Url_style.t = Config.Url_style.default)
+      ()
+  : t
+  =
+  let on_vault (ctx : Vault.t) : Vault.t =
+    let m : Cmarkit.Meta.t = Cmarkit.Meta.none in
+    let urls = Component.Url.of_docs ~style:url_style ctx.docs in
+    let entries : Vault.Journal.entry list =
+      Vault.Journal.of_paths ~config (List.map (Vault.listed_docs ctx) ~f:fst)
+    in
+    let folder = String.strip config.page_folder ~drop:(Char.equal '/') in
+    let page_path (name : string) : string =
+      if String.is_empty folder then name ^ ".md" else folder ^ "/" ^ name ^ ".md"
+    in
+    let month_path (year : int) (month : Month.t) : string =
+      page_path (sprintf "%04d-%02d" year (Month.to_int month))
+    in
+    let year_path (year : int) : string = page_path (sprintf "%04d" year) in
+    let heading (title : string) : Cmarkit.Block.t =
+      Cmarkit.Block.Heading
+        (Cmarkit.Block.Heading.make ~level:1 (Cmarkit.Inline.Text (title, m)), m)
+    in
+    let html (content : string) : Cmarkit.Block.t =
+      Cmarkit.Block.Code_block
+        ( Cmarkit.Block.Code_block.make
+            ~info_string:("=html", m)
+            (Cmarkit.Block_line.list_of_string content)
+        , m )
+    in
+    let page (blocks : Cmarkit.Block.t list) : Cmarkit.Doc.t =
+      Cmarkit.Doc.make (Cmarkit.Block.Blocks (blocks, m))
+    in
+    (* A week shows in the calendars of both months it spans. *)
+    let months : (int * Month.t) list =
+      List.concat_map entries ~f:(fun (e : Vault.Journal.entry) ->
+        [ e.date; Vault.Journal.last_day e ])
+      |> List.map ~f:(fun d -> Date.year d, Date.month d)
+      |> List.dedup_and_sort ~compare:[%compare: int * Month.t]
+    in
+    let years : int list =
+      List.map months ~f:fst |> List.dedup_and_sort ~compare:Int.compare
+    in
+    let month_pages : (string * Cmarkit.Doc.t) list =
+      List.map months ~f:(fun (year, month) ->
+        ( month_path year month
+        , page
+            [ heading (sprintf "%s %d" (Vault.Journal.month_name month) year)
+            ; html (Component.calendar_html ~urls ~year ~month entries)
+            ] ))
+    in
+    let year_pages : (string * Cmarkit.Doc.t) list =
+      List.map years ~f:(fun year ->
+        let calendars : Cmarkit.Block.t list =
+          List.filter_map months ~f:(fun (y, month) ->
+            if y <> year
+            then None
+            else
+              Some
+                (html
+                   (Component.calendar_html
+                      ~urls
+                      ~caption:(Vault.Journal.month_name month)
+                      ~caption_href:
+                        (Component.Url.note_url_path urls (month_path year month))
+                      ~year
+                      ~month
+                      entries)))
+        in
+        year_path year, page (heading (Int.to_string year) :: calendars))
+    in
+    let index : string * Cmarkit.Doc.t =
+      let item (year : int) : Cmarkit.Block.List_item.t Cmarkit.node =
+        let path = year_path year in
+        let link : Cmarkit.Inline.t =
+          Vault.Resolve.make_wikilink
+            ~target:(Some (String.chop_suffix_if_exists path ~suffix:".md"))
+            ~fragment:None
+            ~display:(Some (Int.to_string year))
+            ~embed:false
+            ~resolved_target:(Note { path })
+        in
+        ( Cmarkit.Block.List_item.make
+            (Cmarkit.Block.Paragraph (Cmarkit.Block.Paragraph.make link, m))
+        , m )
+      in
+      ( page_path "index"
+      , page
+          [ heading "Journal"
+          ; Cmarkit.Block.List
+              ( Cmarkit.Block.List'.make (`Unordered '-') (List.rev_map years ~f:item)
+              , m )
+          ] )
+    in
+    let pages : (string * Cmarkit.Doc.t) list =
+      if List.is_empty entries
+      then []
+      else
+        (index :: year_pages) @ month_pages
+        |> List.filter ~f:(fun (path, _) ->
+          not (List.Assoc.mem ctx.docs ~equal:String.equal path))
+    in
+    { ctx with docs = ctx.docs @ pages }
+  in
+  make ~on_vault ()
+;;
+
+(** Keep notes under the templates [folder] out of the site, and lay out each
+    note with a [template: name] frontmatter field by [folder/name.md]: its
+    [{{var}}] placeholders take the note's frontmatter values, [{{title}}]
+    defaulting to the note's file name. Names leading out of [folder] are
+    reported to [on_warning] and ignored. See {!Vault.Template}. *)
+let apply_templates
+      ?(on_warning : string -> unit = ignore)
+      ?(config : Config.t option)
+      ?(folder : string = Config.Templates.default.folder)
+      ()
+  : t
+  =
+  let on_discover (path : string) (_paths : string list) : bool =
+    not (Vault.Template.is_template ~folder path)
+  in
+  let on_vault : Vault.t -> Vault.t =
+    map_each_doc (fun (ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+      let template_src : string option =
+        match Parse.Frontmatter.field doc "template" with
+        | Some (Parse.Value.String name) ->
+          (match Vault.Template.path ~folder name with
+           | Some template ->
+             Option.try_with (fun () ->
+               In_channel.read_all (Vault.full_path ctx template))
+           | None ->
+             on_warning
+               (sprintf "%s: template %s is outside the templates folder" path name);
+             None)
+        | _ -> None
+      in
+      match template_src with
+      | None -> [ path, doc ]
+      | Some src ->
+        let vars : (string * string) list =
+          Vault.Template.vars_of_frontmatter (Parse.Frontmatter.of_doc doc)
+          @ [ "title", Filename.chop_extension (Filename.basename path) ]
+        in
+        let template : Cmarkit.Doc.t =
+          Parse.of_string ?config (Vault.Template.substitute ~vars src)
+          |> Cmarkit.Mapper.map_doc
+               (Vault.Resolve.resolution_cmarkit_mapper
+                  ~index:ctx.index
+                  ~curr_file:path
+                  ())
+        in
+        [ path, Vault.Template.apply ~template doc ])
+  in
+  make ~on_discover ~on_vault ()
+;;
+
+(** Fill the [{{...}}] placeholders of notes with [variables: true] in their
+    frontmatter; see {!Vault.Variables}. [today] defaults to the build's local
+    date. *)
+let substitute_variables ?(site_title : string = "") ?(today : Date.t option) () : t =
+  let on_vault (ctx : Vault.t) : Vault.t =
+    let today : Date.t =
+      match today with
+      | Some today -> today
+      | None ->
+        Time_float.to_date
+          (Time_float_unix.now ())
+          ~zone:(force Time_float_unix.Zone.local)
+    in
+    map_each_doc
+      (fun (_ctx : Vault.t) (path : string) (doc : Cmarkit.Doc.t) ->
+        [ path, Vault.Variables.substitute ~site_title ~today ~path doc ])
+      ctx
+  in
+  make ~on_vault ()
+;;
+
 (** Append an interactive graph widget to the home page.
     [view] controls which dir/tag clusters appear and which are selected by
-    default. See {!Config.Home} for [home_path]. *)
+    default. See {!Config.Home} for [home_path]. Unlisted notes
+    ({!Vault.is_unlisted}) are left out of the graph, and so are links to
+    them. *)
 let home_graph
       ?(config : Config.Home_graph_view.t = Config.Home_graph_view.default)
       ?(home_path : string = Config.Home.default.path)
@@ -168,7 +763,21 @@ let home_graph
   let open Vault_graph in
   let open Graph_view in
   let on_vault (ctx : Vault.t) : Vault.t =
-    let g = of_vault ctx in
+    let listed = Vault.listed_docs ctx in
+    let is_listed : string -> bool =
+      let paths = String.Set.of_list (List.map listed ~f:fst) in
+      Set.mem paths
+    in
+    let g = of_vault { ctx with docs = listed } in
+    let g =
+      { g with
+        graph =
+          G.fold_vertex
+            (fun v graph -> if is_listed v.path then graph else G.remove_vertex graph v)
+            g.graph
+            g.graph
+      }
+    in
     let html = to_widget_html ~config g in
     let docs =
       List.map ctx.docs ~f:(fun (path, doc) ->
@@ -190,29 +799,148 @@ let home_graph
   make ~on_vault ()
 ;;
 
-let default ?(cache : Cache.cache option) ?(config : Config.t = Config.default) () : t =
+(** Run the config's [after_parse] hook [commands] on each note's markdown,
+    parsing again the notes they change; see {!Hook}. *)
+let after_parse_hooks
+      ?(on_warning : string -> unit = ignore)
+      ?(config : Config.t option)
+      (commands : string list)
+  : t
+  =
+  if List.is_empty commands
+  then id
+  else
+    make
+      ~on_parse:(fun path doc ->
+        let markdown = Parse.commonmark_of_doc doc in
+        let markdown' = Hook.after_parse ~on_warning ~commands ~path markdown in
+        if String.equal markdown markdown'
+        then [ path, doc ]
+        else [ path, Parse.of_string ?config markdown' ])
+      ()
+;;
+
+(* The stages of {!default} that leave out drafts and unpublished notes. *)
+let publish_filter ~(folders : Vault.Folder_config.t) ~(config : Config.t) : t =
+  let published_by_default (path : string) : bool =
+    (Vault.Folder_config.config ~base:config folders path).publish.default
+  in
+  if config.publish.drafts
+  then id
+  else exclude_draft_by_note_name >> exclude_unpublish ~published_by_default ()
+;;
+
+(** Whether {!default} publishes note [path], parsed as [doc]: every note with
+    [publish.drafts], else notes not named [*.draft.md] whose [publish] field,
+    or failing that their folder's [publish.default], says so. *)
+let publishes
+      ?(folders : Vault.Folder_config.t = Vault.Folder_config.empty)
+      ~(config : Config.t)
+      (path : string)
+      (doc : Cmarkit.Doc.t)
+  : bool
+  =
+  let filter = publish_filter ~folders ~config in
+  filter.on_discover path [ path ] && not (List.is_empty (filter.on_parse path doc))
+;;
+
+let default
+      ?(on_warning : (string -> unit) option)
+      ?(cache : Cache.cache option)
+      ?(preview_cache : Vault.Link_preview.cache option)
+      ?(folders : Vault.Folder_config.t = Vault.Folder_config.empty)
+      ?(manifest : Vault.Manifest.t option)
+      ?(diagram_colors : Code_executor.diagram_colors option)
+      ?(config : Config.t = Config.default)
+      ()
+  : t
+  =
+  let recent : t =
+    match manifest with
+    | Some manifest when config.recent.enabled ->
+      recent_changes ~limit:config.recent.limit ~page_path:config.recent.page ~manifest ()
+    | Some _ | None -> id
+  in
+  let journal : t =
+    if config.journal.pages
+    then journal_pages ~config:config.journal ~url_style:config.url_style ()
+    else id
+  in
   id
-  >> exclude_draft_by_note_name
-  >> exclude_unpublish
+  >> publish_filter ~folders ~config
   >> validate_no_duplicates
   >> drop_keys_in_frontmatter [ "publish"; "draft" ]
   >> drop_emtpy_frontmatter
+  >> apply_templates ?on_warning ~config ~folder:config.templates.folder ()
+  >> substitute_variables ~site_title:config.site.title ()
+  >> heading_order
+  >> section_ids
+  >> kanban_boards
   >> transclude_code_files
+  >> include_code_lines ?on_warning ()
   >> py_executor ?cache ()
-  >> dot_render ()
-  >> backlinks
+  >> dot_render ?colors:diagram_colors ()
+  >> media_transcripts ~config ()
+  >> dedup_assets
+  >> link_previews ~config:config.link_previews ?cache:preview_cache ()
+  >> glossary
+       ~slug_style:(Parse.Heading_slug.of_config config.parse.slug_style)
+       ~config:config.glossary
+       ()
+  >> outline_diagrams ?colors:diagram_colors ()
+  >> backlinks ~url_style:config.url_style ()
+  >> recent
+  >> journal
   >> home_graph ~config:config.home_graph_view ~home_path:config.home.path ()
   >> home_toc ~dir_link:true ~toc_order:config.toc_order ~home_path:config.home.path ()
   >> dir_index ~toc_order:config.toc_order ()
 ;;
 
-let basic : t = id >> backlinks
+let basic
+      ?(preview_cache : Vault.Link_preview.cache option)
+      ?(config : Config.t = Config.default)
+      ()
+  : t
+  =
+  id
+  >> heading_order
+  >> section_ids
+  >> link_previews ~config:config.link_previews ?cache:preview_cache ()
+  >> glossary
+       ~slug_style:(Parse.Heading_slug.of_config config.parse.slug_style)
+       ~config:config.glossary
+       ()
+  >> backlinks ~url_style:config.url_style ()
+;;
 
-(** Build a pipeline from a {!Config.t}, dispatching on [pipeline_profile]. *)
-let of_config ?(cache : Cache.cache option) ~(config : Config.t) () : t =
+(** Build a pipeline from a {!Config.t}, dispatching on [pipeline_profile],
+    after the config's [after_parse] hooks. Problems that don't stop the build
+    go to [on_warning]. *)
+let of_config
+      ?(on_warning : (string -> unit) option)
+      ?(cache : Cache.cache option)
+      ?(preview_cache : Vault.Link_preview.cache option)
+      ?(folders : Vault.Folder_config.t option)
+      ?(manifest : Vault.Manifest.t option)
+      ?(diagram_colors : Code_executor.diagram_colors option)
+      ~(config : Config.t)
+      ()
+  : t
+  =
+  after_parse_hooks ?on_warning ~config config.hooks.after_parse
+  >>
   match config.pipeline_profile with
-  | Config.Pipeline_profile_def.Default -> default ?cache ~config ()
-  | Basic -> basic
+  | Config.Pipeline_profile_def.Default ->
+    default
+      ?on_warning
+      ?cache
+      ?preview_cache
+      ?folders
+      ?manifest
+      ?diagram_colors
+      ~config
+      ()
+  | Basic -> basic ?preview_cache ~config ()
   | None_profile -> id
 ;;
 
@@ -368,3 +1096,75 @@ hello
     ;;
   end)
 ;;
+
+let%expect_test "recent_changes: updated frontmatter overrides the manifest time" =
+  let docs : (string * Cmarkit.Doc.t) list =
+    [ "a.md", Parse.of_string "---\nupdated: 2024-06-01\n---\n# A\n"
+    ; "b.md", Parse.of_string "# B\n"
+    ; "c.md", Parse.of_string "---\nunlisted: true\n---\n# C\n"
+    ]
+  in
+  let manifest : Vault.Manifest.t =
+    String.Map.of_alist_exn
+      [ "a.md", { Vault.Manifest.hash = "a"; updated = "2024-01-01T00:00:00Z" }
+      ; "b.md", { Vault.Manifest.hash = "b"; updated = "2024-03-01T00:00:00Z" }
+      ; "c.md", { Vault.Manifest.hash = "c"; updated = "2024-09-01T00:00:00Z" }
+      ]
+  in
+  let vault : Vault.t =
+    { Vault.vault_root = "."
+    ; index = { Vault.Index.files = []; dirs = [] }
+    ; docs
+    ; vault_meta = Cmarkit.Meta.none
+    }
+  in
+  let vault = (recent_changes ~manifest ()).on_vault vault in
+  List.Assoc.find_exn vault.docs ~equal:String.equal "recent.md"
+  |> Parse.commonmark_of_doc
+  |> print_endline;
+  [%expect
+    {|
+    # Recently updated
+
+    - [[a]] — 2024-06-01
+    - [[b]] — 2024-03-01
+    |}]
+;;
+
+let%expect_test "heading_order" =
+  let show (src : string) : unit =
+    heading_order.on_parse "a.md" (Parse.of_string src)
+    |> List.iter ~f:(fun (_, doc) -> print_string (Parse.commonmark_of_doc doc))
+  in
+  show "### A\n\n#### B\n\n# C\n\n### D\n";
+  [%expect
+    {|
+    ## A
+
+    ### B
+
+    # C
+
+    ## D
+    |}];
+  (* C stays B's sibling *)
+  show "# A\n\n#### B\n\n### C\n";
+  [%expect
+    {|
+    # A
+
+    ## B
+
+    ## C
+    |}];
+  (* A quoted heading is neither moved nor counted *)
+  show "# A\n\n> ###### E\n\n#### B\n";
+  [%expect
+    {|
+    # A
+
+    > ###### E
+
+    ## B
+    |}]
+;;
@@ -48,7 +48,15 @@ let () =
       [ section "plain" `Plain; section "basic" `Basic; section "graph" `Graph ]
   in
   let page : Theme.page =
-    { title = "Struct"; body; url_path = ""; nav = ""; sidebar = "" }
+    { title = "Struct"
+    ; body
+    ; url_path = ""
+    ; nav = ""
+    ; menu = ""
+    ; sidebar = ""
+    ; classes = []
+    ; head = ""
+    }
   in
   Theme.default page |> print_string
 ;;
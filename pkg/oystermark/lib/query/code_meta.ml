@@ -0,0 +1,134 @@
+(** Code block metadata: the structured view of a fenced code block that
+    [--codemeta] prints and that exporters (e.g. [md2script]) read code from. *)
+
+open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
+
+type attrs = (string * string) list [@@deriving sexp_of]
+
+let yojson_of_attrs (kvs : attrs) : Yojson.Safe.t =
+  `Assoc (List.map kvs ~f:(fun (k, v) -> k, `String v))
+;;
+
+type t =
+  { lang : string option
+  ; info : string option (** raw fence info string *)
+  ; id : string option (** [{#id}] from the Pandoc attribute block *)
+  ; classes : string list
+  ; attrs : attrs
+  ; content : string
+  ; first_line : int option (** 1-based, fence line included *)
+  ; last_line : int option
+  ; section_path : string (** see {!Section.path_to_string} *)
+  ; section_title : string
+  ; lang_index : int (** 0-based index among blocks of the same [lang] in scope *)
+  }
+[@@deriving sexp_of, yojson_of]
+
+(** Metadata of a code block, or [None] if [b] is not one. The section fields
+    and [lang_index] default to a lone block at the root. *)
+let of_block
+      ?(section_path : string = "root")
+      ?(section_title : string = "")
+      ?(lang_index : int = 0)
+      (b : Cmarkit.Block.t)
+  : t option
+  =
+  match b with
+  | Cmarkit.Block.Code_block (cb, meta) ->
+    let cb_info : Parse.Cb_attribute.code_block_info option =
+      Cmarkit.Meta.find Parse.Cb_attribute.meta_key meta
+    in
+    let attribute : Parse.Cb_attribute.t =
+      Option.bind cb_info ~f:(fun i -> i.attribute)
+      |> Option.value ~default:Parse.Cb_attribute.empty
+    in
+    let content : string =
+      List.map (Cmarkit.Block.Code_block.code cb) ~f:Cmarkit.Block_line.to_string
+      |> String.concat ~sep:"\n"
+    in
+    let tl : Cmarkit.Textloc.t = Cmarkit.Meta.textloc meta in
+    let line (f : Cmarkit.Textloc.t -> Cmarkit.Textloc.line_pos) : int option =
+      if Cmarkit.Textloc.is_none tl then None else Some (fst (f tl))
+    in
+    Some
+      { lang = Option.map cb_info ~f:(fun i -> i.lang)
+      ; info = Option.map (Cmarkit.Block.Code_block.info_string cb) ~f:fst
+      ; id = attribute.id
+      ; classes = attribute.classes
+      ; attrs = attribute.kvs
+      ; content
+      ; first_line = line Cmarkit.Textloc.first_line
+      ; last_line = line Cmarkit.Textloc.last_line
+      ; section_path
+      ; section_title
+      ; lang_index
+      }
+  | _ -> None
+;;
+
+(* Code blocks nested anywhere in [b] (lists, quotes, ...), in order. *)
+let code_blocks_in (b : Cmarkit.Block.t) : Cmarkit.Block.t list =
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun _f acc b ->
+        match b with
+        | Cmarkit.Block.Code_block _ -> Cmarkit.Folder.ret (b :: acc)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_block folder [] b)
+;;
+
+(** Every code block in [section] and its descendants, in document order, each
+    tagged with its enclosing section. *)
+let of_section (section : Section.t) : t list =
+  let seen : int String.Table.t = String.Table.create () in
+  List.concat_map (Section.descendants section) ~f:(fun (s : Section.t) ->
+    List.concat_map s.blocks ~f:code_blocks_in
+    |> List.filter_map ~f:(fun b ->
+      let lang_key : string =
+        match of_block b with
+        | Some { lang = Some lang; _ } -> lang
+        | _ -> ""
+      in
+      let lang_index : int = Hashtbl.find seen lang_key |> Option.value ~default:0 in
+      Hashtbl.set seen ~key:lang_key ~data:(lang_index + 1);
+      of_block
+        ~section_path:(Section.path_to_string s.path)
+        ~section_title:s.title
+        ~lang_index
+        b))
+;;
+
+let%expect_test "of_section" =
+  let doc =
+    Parse.of_string
+      {|# Setup
+
+```sh
+make
+```
+
+## Run
+
+> ```python {#main .cell timeout=5}
+> print(1)
+> ```
+
+```python
+print(2)
+```
+|}
+  in
+  List.iter (of_section (Section.of_doc doc)) ~f:(fun t ->
+    print_endline (Yojson.Safe.to_string (yojson_of_t t)));
+  [%expect
+    {|
+    {"lang":"sh","info":"sh","id":null,"classes":[],"attrs":{},"content":"make","first_line":3,"last_line":5,"section_path":"1","section_title":"Setup","lang_index":0}
+    {"lang":"python","info":"python {#main .cell timeout=5}","id":"main","classes":["cell"],"attrs":{"timeout":"5"},"content":"print(1)","first_line":9,"last_line":11,"section_path":"1.1","section_title":"Run","lang_index":0}
+    {"lang":"python","info":"python","id":null,"classes":[],"attrs":{},"content":"print(2)","first_line":13,"last_line":15,"section_path":"1.1","section_title":"Run","lang_index":1}
+    |}]
+;;
@@ -0,0 +1,957 @@
+(** Query expressions.
+
+    A query is a pipeline of expressions applied left to right, starting from
+    the whole document (the root {!Section.t}). On the command line each
+    expression is a flag: [--field Usage --codemeta] selects the "Usage"
+    section and lists its code blocks. A [,] starts another pipeline from the
+    document, and the query yields the values of all of them:
+    [--field Install , --field Usage] selects both sections. *)
+
+open Core
+
+(** How [--field] compares its string with section titles, which are plain
+    text (see {!Section.t}). *)
+type title_match =
+  | Loose
+  (** ignoring case, runs of whitespace and emphasis or code marks ([*], [_],
+      [`]) on either side *)
+  | Prefix (** as {!Loose}, against the start of the title *)
+  | Exact (** the title as is *)
+[@@deriving sexp_of]
+
+type t =
+  | Field of string * title_match
+  (** first descendant section whose title matches the string *)
+  | Anchor of string
+  (** first descendant section whose heading slug is the string, as in
+      [note#anchor] links; see {!Parse.Heading_slug} *)
+  | Id of string
+  (** first descendant section whose heading has the id ([{#id}]); see
+      {!Section.attributes} *)
+  | Class of string
+  (** every descendant section whose heading has the class ([{.class}]), as
+      children of the scope; a match nested in another stays with it *)
+  | Attr of string * string option
+  (** every descendant section whose heading has the attribute ([{key=value}]),
+      with the value when given, as children of the scope; a match nested in
+      another stays with it *)
+  | Index of int (** nth child section, 0-based; negative counts from the end *)
+  | Slice of int option * int option (** keep children [\[start, stop)] *)
+  | Frontmatter (** the document's frontmatter as JSON *)
+  | Code_meta (** {!Code_meta.t} of every code block in scope, as JSON *)
+  | Quote of int (** nth blockquote (callouts included) in scope, as {!Quote.t} JSON *)
+  | Callout of int (** nth callout in scope *)
+  | Callouts (** every callout in scope *)
+  | Links of Parse.Link_kind.t option
+  (** every link in scope as {!Links.t} JSON, only of the kind when given *)
+  | Outline of Outline.format (** heading hierarchy of the scope *)
+  | Path of int list (** the section at a {!Section.path_to_string} path *)
+  | Parent (** the enclosing section *)
+  | Next (** the following sibling section *)
+  | Prev (** the preceding sibling section *)
+  | First (** first child section, or first element of a JSON list *)
+  | Last (** last child section, or last element of a JSON list *)
+  | Flatten_children (** every descendant as a direct child, in document order *)
+  | Root_level of int
+  (** the scope with its heading at the level (1-6), its descendants' headings
+      following, an error if that pushes one past 6; see {!Section.reroot} *)
+  | Titles (** titles of every descendant section, as a JSON list *)
+  | Summary_json of int option (** {!Summary.t} of the scope, to an optional depth *)
+  | Meta of string
+  (** an inline [key:: value] field in scope, as JSON; a list if repeated *)
+  | Fm_keys (** top-level frontmatter keys, as a JSON list *)
+  | Fm_has of string (** keep the current value only if the frontmatter has the key *)
+  | Fm_filter of string * string
+  (** keep the current value only if the frontmatter field equals the value, or
+      is a list containing it *)
+  | Custom of string * string
+  (** the custom expression registered under the name, given the argument; see
+      {!register} *)
+  | Comma
+  (** ends a pipeline; the expressions after it start again from the root, and
+      the query yields {!Many} values *)
+[@@deriving sexp_of]
+
+type value =
+  | Section of Section.t
+  | Json of Yojson.Safe.t
+  | Text of string
+  | Many of value list (** the value of each pipeline of a query with [,] *)
+  | Empty
+  (** nothing was selected: a frontmatter filter rejected the document, or a
+      selection missed in {!Lenient} mode *)
+
+(** A custom expression: given the document's root, the current value and
+    its argument, the new value or why there is none. *)
+type custom = root:Section.t -> value -> string -> (value, string) Result.t
+
+let customs : custom String.Table.t = String.Table.create ()
+
+(** Make [--x name:ARG] run [f] with [ARG]; registering [name] again replaces
+    the earlier one. *)
+let register ~(name : string) (f : custom) : unit = Hashtbl.set customs ~key:name ~data:f
+
+(** Names of the registered custom expressions, sorted. *)
+let registered () : string list = List.sort (Hashtbl.keys customs) ~compare:String.compare
+
+(** What a missing selection (unknown [--field], out-of-range [--index] or
+    [--slice] bound) means. *)
+type mode =
+  | Strict (** an error *)
+  | Lenient (** an {!Empty} result; later expressions pass it through *)
+[@@deriving sexp_of]
+
+(** One line per expression flag, for [--help]. *)
+let help : string =
+  String.concat
+    ~sep:"\n"
+    [ "  --field TITLE          first section in scope with this title, ignoring case"
+    ; "                         and markup; add --prefix to match its start, or"
+    ; "                         --exact to match it as is"
+    ; "  --anchor SLUG          first section in scope with this heading anchor"
+    ; "  --id ID                first section in scope whose heading has {#ID}"
+    ; "  --class CLASS          sections in scope whose heading has {.CLASS}"
+    ; "  --attr KEY[=VALUE]     sections in scope whose heading has {KEY=VALUE}"
+    ; "  --index N              nth child section (negative counts from the end)"
+    ; "  --slice START:STOP     keep child sections [START, STOP)"
+    ; "  --path PATH            section at a path like 1.2.3, or root"
+    ; "  --parent               enclosing section"
+    ; "  --next, --prev         following / preceding sibling section"
+    ; "  --first, --last        first / last child section or JSON list element"
+    ; "  --flatten-children     every descendant as a direct child"
+    ; "  --root-level N         move the scope's headings so it starts at level N"
+    ; "  --titles               titles of every section in scope"
+    ; "  --summary-json [--depth N]  titles, paths and word counts of the scope"
+    ; "  --frontmatter          frontmatter as JSON"
+    ; "  --meta KEY             inline KEY:: value field(s) in scope"
+    ; "  --fm-keys              frontmatter keys"
+    ; "  --fm-has KEY           continue only if the frontmatter has KEY"
+    ; "  --fm-filter KEY=VALUE  continue only if frontmatter KEY is (or contains) VALUE"
+    ; "  --codemeta             code blocks in scope, as JSON"
+    ; "  --quote N              nth blockquote in scope, as JSON"
+    ; "  --callout N            nth callout in scope, as JSON"
+    ; "  --callouts             every callout in scope, as JSON"
+    ; "  --links [internal|external]  links in scope, as JSON"
+    ; "  --outline [md|opml]    heading outline of the scope"
+    ; "  --x NAME[:ARG]         custom expression NAME, given ARG"
+    ; "  ,                      start again from the document; yield every result"
+    ]
+;;
+
+let json_of_yaml (v : Yaml_backend.value) : Yojson.Safe.t =
+  Parse.Value.(to_json (of_yaml v))
+;;
+
+(* [start:stop], either side optional, e.g. [1:], [:2], [-2:] *)
+let slice_of_string (spec : string) : (int option * int option) Or_error.t =
+  let bound (s : string) : int option Or_error.t =
+    if String.is_empty s
+    then Ok None
+    else (
+      match Int.of_string_opt s with
+      | Some n -> Ok (Some n)
+      | None -> Or_error.error_s [%message "invalid slice bound" (s : string)])
+  in
+  match String.lsplit2 spec ~on:':' with
+  | Some (a, b) -> Or_error.both (bound a) (bound b)
+  | None -> Or_error.error_s [%message "--slice expects START:STOP" (spec : string)]
+;;
+
+(* Whether a [,] lacks an expression on one of its sides, as in [--titles ,]. *)
+let has_empty_pipeline (exprs : t list) : bool =
+  let rec go (after_comma : bool) : t list -> bool = function
+    | [] -> after_comma
+    | Comma :: rest -> after_comma || go true rest
+    | _ :: rest -> go false rest
+  in
+  (not (List.is_empty exprs)) && go true exprs
+;;
+
+(** Parse a flag-style expression list, e.g. [["--field"; "Usage"; "--codemeta"]]. *)
+let of_args (args : string list) : t list Or_error.t =
+  let rec go (acc : t list) : string list -> t list Or_error.t = function
+    | [] when has_empty_pipeline acc ->
+      Or_error.error_s [%message "',' needs an expression on each side"]
+    | [] -> Ok (List.rev acc)
+    | "--field" :: title :: "--exact" :: rest -> go (Field (title, Exact) :: acc) rest
+    | "--field" :: title :: "--prefix" :: rest -> go (Field (title, Prefix) :: acc) rest
+    | "--field" :: title :: rest -> go (Field (title, Loose) :: acc) rest
+    | "--anchor" :: slug :: rest -> go (Anchor slug :: acc) rest
+    | "--id" :: id :: rest -> go (Id id :: acc) rest
+    | "--class" :: cls :: rest -> go (Class cls :: acc) rest
+    | "--attr" :: spec :: rest ->
+      (match String.lsplit2 spec ~on:'=' with
+       | Some (key, value) ->
+         go (Attr (String.strip key, Some (String.strip value)) :: acc) rest
+       | None -> go (Attr (String.strip spec, None) :: acc) rest)
+    | "--index" :: n :: rest ->
+      (match Int.of_string_opt n with
+       | Some n -> go (Index n :: acc) rest
+       | None -> Or_error.error_s [%message "--index expects an integer" (n : string)])
+    | "--slice" :: spec :: rest ->
+      let%bind.Or_error start, stop = slice_of_string spec in
+      go (Slice (start, stop) :: acc) rest
+    | "--frontmatter" :: rest -> go (Frontmatter :: acc) rest
+    | "--codemeta" :: rest -> go (Code_meta :: acc) rest
+    | "--quote" :: n :: rest ->
+      (match Int.of_string_opt n with
+       | Some n -> go (Quote n :: acc) rest
+       | None -> Or_error.error_s [%message "--quote expects an integer" (n : string)])
+    | "--callout" :: n :: rest ->
+      (match Int.of_string_opt n with
+       | Some n -> go (Callout n :: acc) rest
+       | None -> Or_error.error_s [%message "--callout expects an integer" (n : string)])
+    | "--callouts" :: rest -> go (Callouts :: acc) rest
+    | "--links" :: kind :: rest when Option.is_some (Parse.Link_kind.of_string kind) ->
+      go (Links (Parse.Link_kind.of_string kind) :: acc) rest
+    | "--links" :: rest -> go (Links None :: acc) rest
+    | "--outline" :: fmt :: rest when Option.is_some (Outline.format_of_string fmt) ->
+      go (Outline (Option.value_exn (Outline.format_of_string fmt)) :: acc) rest
+    | "--outline" :: rest -> go (Outline Markdown :: acc) rest
+    | "--path" :: p :: rest ->
+      (match Section.path_of_string p with
+       | Some path -> go (Path path :: acc) rest
+       | None -> Or_error.error_s [%message "--path expects root or 1.2.3" (p : string)])
+    | "--parent" :: rest -> go (Parent :: acc) rest
+    | "--next" :: rest -> go (Next :: acc) rest
+    | "--prev" :: rest -> go (Prev :: acc) rest
+    | "--first" :: rest -> go (First :: acc) rest
+    | "--last" :: rest -> go (Last :: acc) rest
+    | "--flatten-children" :: rest -> go (Flatten_children :: acc) rest
+    | "--root-level" :: n :: rest ->
+      (match Int.of_string_opt n with
+       | Some n when n >= 1 && n <= 6 -> go (Root_level n :: acc) rest
+       | _ -> Or_error.error_s [%message "--root-level expects 1 to 6" (n : string)])
+    | "--titles" :: rest -> go (Titles :: acc) rest
+    | "--summary-json" :: "--depth" :: n :: rest ->
+      (match Int.of_string_opt n with
+       | Some n -> go (Summary_json (Some n) :: acc) rest
+       | None -> Or_error.error_s [%message "--depth expects an integer" (n : string)])
+    | "--summary-json" :: rest -> go (Summary_json None :: acc) rest
+    | "--meta" :: key :: rest -> go (Meta key :: acc) rest
+    | "--fm-keys" :: rest -> go (Fm_keys :: acc) rest
+    | "--fm-has" :: key :: rest -> go (Fm_has key :: acc) rest
+    | "--fm-filter" :: spec :: rest ->
+      (match String.lsplit2 spec ~on:'=' with
+       | Some (key, value) ->
+         go (Fm_filter (String.strip key, String.strip value) :: acc) rest
+       | None ->
+         Or_error.error_s [%message "--fm-filter expects KEY=VALUE" (spec : string)])
+    | "," :: rest -> go (Comma :: acc) rest
+    | "--x" :: spec :: rest ->
+      (match String.lsplit2 spec ~on:':' with
+       | Some (name, arg) -> go (Custom (name, arg) :: acc) rest
+       | None -> go (Custom (spec, "") :: acc) rest)
+    | [ (( "--field" | "--anchor" | "--id" | "--class" | "--attr" | "--index" | "--slice"
+         | "--quote" | "--callout" | "--path" | "--meta" | "--fm-has" | "--fm-filter"
+         | "--root-level" | "--x" ) as flag)
+      ] ->
+      Or_error.error_s [%message "missing argument" (flag : string)]
+    | arg :: _ -> Or_error.error_s [%message "unknown expression" (arg : string)]
+  in
+  go [] args
+;;
+
+let is_callout (q : Quote.t) : bool = Option.is_some q.callout
+
+(* The frontmatter expressions work on the current value when it is a
+   frontmatter map (after [--frontmatter]), else on the document's. *)
+let frontmatter_of ~(root : Section.t) (v : value) : (string * Yojson.Safe.t) list =
+  match v with
+  | Json (`Assoc kvs) -> kvs
+  | _ ->
+    (match Parse.Frontmatter.of_doc (Section.to_doc root) with
+     | Some fm ->
+       (match json_of_yaml fm with
+        | `Assoc kvs -> kvs
+        | _ -> [])
+     | None -> [])
+;;
+
+let json_matches (expected : string) (j : Yojson.Safe.t) : bool =
+  Parse.Value.matches (Parse.Value.of_json j) expected
+;;
+
+(* Levenshtein distance, for did-you-mean suggestions. *)
+let edit_distance (a : string) (b : string) : int =
+  let m = String.length a
+  and n = String.length b in
+  let prev = Array.init (n + 1) ~f:Fn.id in
+  let curr = Array.create ~len:(n + 1) 0 in
+  for i = 1 to m do
+    curr.(0) <- i;
+    for j = 1 to n do
+      let cost = if Char.equal a.[i - 1] b.[j - 1] then 0 else 1 in
+      let best = Int.min (prev.(j) + 1) (curr.(j - 1) + 1) in
+      curr.(j) <- Int.min best (prev.(j - 1) + cost)
+    done;
+    Array.blit ~src:curr ~src_pos:0 ~dst:prev ~dst_pos:0 ~len:(n + 1)
+  done;
+  prev.(n)
+;;
+
+(** The candidate closest to [target], if any is close enough to be a likely
+    typo: equal ignoring case, or within a third of [target]'s length. *)
+let suggest (target : string) (candidates : string list) : string option =
+  let target' = String.lowercase target in
+  List.map candidates ~f:(fun c -> edit_distance target' (String.lowercase c), c)
+  |> List.min_elt ~compare:(fun (a, _) (b, _) -> Int.compare a b)
+  |> Option.bind ~f:(fun (d, c) ->
+    if d <= Int.max 1 (String.length target / 3) then Some c else None)
+;;
+
+(* Lowercase, without emphasis or code marks, whitespace runs as one space. *)
+let normalize_title (s : string) : string =
+  String.filter s ~f:(fun c -> not (String.mem "*_`" c))
+  |> String.split_on_chars ~on:[ ' '; '\t'; '\n' ]
+  |> List.filter ~f:(Fn.non String.is_empty)
+  |> String.concat ~sep:" "
+  |> String.lowercase
+;;
+
+let title_matches (how : title_match) ~(pattern : string) (title : string) : bool =
+  match how with
+  | Exact -> String.equal title pattern
+  | Loose -> String.equal (normalize_title title) (normalize_title pattern)
+  | Prefix -> String.is_prefix (normalize_title title) ~prefix:(normalize_title pattern)
+;;
+
+type expr = t [@@deriving sexp_of]
+
+(** Why an expression failed. *)
+module Eval_error = struct
+  type kind =
+    | No_such_field of
+        { title : string
+        ; candidates : string list (** titles that were in scope *)
+        ; suggestion : string option
+        }
+    | Index_out_of_bounds of
+        { what : string (** ["section"], ["quote"] or ["callout"] *)
+        ; index : int
+        ; len : int
+        }
+    | No_such_anchor of
+        { anchor : string
+        ; candidates : string list (** heading slugs that were in scope *)
+        ; suggestion : string option
+        }
+    | No_such_heading_attr of
+        { attr : string (** as written in braces: [#id], [.class], [key=value] *)
+        ; candidates : string list (** heading attributes that were in scope *)
+        ; suggestion : string option
+        }
+    | No_such_meta of
+        { key : string
+        ; candidates : string list (** inline field keys that were in scope *)
+        ; suggestion : string option
+        }
+    | No_such_path of { path : int list }
+    | No_parent (** [--parent] at the root *)
+    | No_sibling of { direction : [ `Next | `Prev ] }
+    | Expects_section of { got : string }
+    | Heading_too_deep of
+        { title : string
+        ; level : int (** where {!Root_level} would put its heading *)
+        }
+    | No_such_custom of
+        { name : string
+        ; registered : string list
+        ; suggestion : string option
+        }
+    | Custom_failed of
+        { name : string
+        ; message : string
+        }
+  [@@deriving sexp_of]
+
+  type nonrec t =
+    { stage : int (** 0-based position of the failing expression *)
+    ; expr : expr
+    ; kind : kind
+    }
+  [@@deriving sexp_of]
+
+  (** Whether the error is a selection that found nothing, which {!Lenient}
+      mode turns into {!Empty}. *)
+  let is_missing : kind -> bool = function
+    | No_such_field _
+    | No_such_anchor _
+    | No_such_heading_attr _
+    | No_such_meta _
+    | Index_out_of_bounds _
+    | No_such_path _
+    | No_parent
+    | No_sibling _ -> true
+    | Expects_section _ | Heading_too_deep _ | No_such_custom _ | Custom_failed _ ->
+      false
+  ;;
+
+  let range_hum ~(what : string) ~(len : int) : string =
+    (* Slice bounds may point one past the end *)
+    let last : int = if String.equal what "slice" then len else len - 1 in
+    if len = 0
+    then "there are none"
+    else [%string "valid range is %{-len#Int}..%{last#Int}"]
+  ;;
+
+  let to_string_hum ({ stage; expr; kind } : t) : string =
+    let what_failed : string =
+      [%string "stage %{stage#Int} %{Sexp.to_string (sexp_of_expr expr)}"]
+    in
+    let reason : string =
+      match kind with
+      | No_such_field { title; candidates; suggestion } ->
+        let hint : string =
+          match suggestion, candidates with
+          | Some s, _ -> [%string "; did you mean %{s}?"]
+          | None, [] -> "; there are no sections in scope"
+          | None, cs -> "; candidates: " ^ String.concat ~sep:", " cs
+        in
+        [%string "no section titled %{title}%{hint}"]
+      | No_such_anchor { anchor; candidates; suggestion } ->
+        let hint : string =
+          match suggestion, candidates with
+          | Some s, _ -> [%string "; did you mean %{s}?"]
+          | None, [] -> "; there are no sections in scope"
+          | None, cs -> "; anchors: " ^ String.concat ~sep:", " cs
+        in
+        [%string "no section with anchor %{anchor}%{hint}"]
+      | No_such_heading_attr { attr; candidates; suggestion } ->
+        let hint : string =
+          match suggestion, candidates with
+          | Some s, _ -> [%string "; did you mean %{s}?"]
+          | None, [] -> "; there are no heading attributes in scope"
+          | None, cs -> "; in scope: " ^ String.concat ~sep:", " cs
+        in
+        [%string "no section with heading attribute %{attr}%{hint}"]
+      | No_such_meta { key; candidates; suggestion } ->
+        let hint : string =
+          match suggestion, candidates with
+          | Some s, _ -> [%string "; did you mean %{s}?"]
+          | None, [] -> "; there are no inline fields in scope"
+          | None, cs -> "; keys: " ^ String.concat ~sep:", " cs
+        in
+        [%string "no inline field %{key}%{hint}"]
+      | Index_out_of_bounds { what; index; len } ->
+        [%string "%{what} index %{index#Int} out of bounds; %{range_hum ~what ~len}"]
+      | No_such_path { path } ->
+        [%string "no section at path %{Section.path_to_string path}"]
+      | No_parent -> "the root section has no parent"
+      | No_sibling { direction = `Next } -> "no following sibling section"
+      | No_sibling { direction = `Prev } -> "no preceding sibling section"
+      | Expects_section { got } -> [%string "expects a section, got %{got}"]
+      | Heading_too_deep { title; level } ->
+        [%string "would put the heading of %{title} at level %{level#Int}, past 6"]
+      | No_such_custom { name; registered; suggestion } ->
+        let hint : string =
+          match suggestion, registered with
+          | Some s, _ -> [%string "; did you mean %{s}?"]
+          | None, [] -> "; none are registered"
+          | None, names -> "; registered: " ^ String.concat ~sep:", " names
+        in
+        [%string "no custom expression %{name}%{hint}"]
+      | Custom_failed { name; message } -> [%string "%{name}: %{message}"]
+    in
+    [%string "%{what_failed}: %{reason}"]
+  ;;
+end
+
+let nth_or_error ~(what : string) (xs : 'a list) (index : int)
+  : ('a, Eval_error.kind) Result.t
+  =
+  let len : int = List.length xs in
+  match List.nth xs (if index < 0 then len + index else index) with
+  | Some x -> Ok x
+  | None -> Error (Index_out_of_bounds { what; index; len })
+;;
+
+(* [attrs] as written in braces, one string per id, class and key/value. *)
+let heading_attrs_hum (attrs : Section.attributes) : string list =
+  Option.value_map attrs.id ~default:[] ~f:(fun id -> [ "#" ^ id ])
+  @ List.map attrs.classes ~f:(fun c -> "." ^ c)
+  @ List.map attrs.key_values ~f:(fun (k, v) -> k ^ "=" ^ v)
+;;
+
+let eval_step ~(mode : mode) ~(root : Section.t) (v : value) (e : t)
+  : (value, Eval_error.kind) Result.t
+  =
+  let open Result.Let_syntax in
+  match e, v with
+  | _, Empty -> Ok Empty
+  | Field (title, how), Section s ->
+    let in_scope : Section.t list = List.tl_exn (Section.descendants s) in
+    (match
+       List.find in_scope ~f:(fun (c : Section.t) ->
+         title_matches how ~pattern:title c.title)
+     with
+     | Some c -> Ok (Section c)
+     | None ->
+       let candidates : string list = List.map in_scope ~f:(fun c -> c.title) in
+       let suggestion : string option = suggest title candidates in
+       Error (No_such_field { title; candidates; suggestion }))
+  | Anchor anchor, Section s ->
+    let slug_of (c : Section.t) : string option =
+      match Option.map c.heading ~f:Section.unwrap_heading with
+      | Some (Cmarkit.Block.Heading (_, meta)) ->
+        Cmarkit.Meta.find Parse.Heading_slug.meta_key meta
+      | _ -> None
+    in
+    let in_scope : (string * Section.t) list =
+      List.tl_exn (Section.descendants s)
+      |> List.filter_map ~f:(fun c -> Option.map (slug_of c) ~f:(fun slug -> slug, c))
+    in
+    (match List.Assoc.find in_scope ~equal:String.equal anchor with
+     | Some c -> Ok (Section c)
+     | None ->
+       let candidates : string list = List.map in_scope ~f:fst in
+       let suggestion : string option = suggest anchor candidates in
+       Error (No_such_anchor { anchor; candidates; suggestion }))
+  | ((Id _ | Class _ | Attr _) as e), Section s ->
+    let in_scope : Section.t list = List.tl_exn (Section.descendants s) in
+    let has (c : Section.t) : bool =
+      let attrs : Section.attributes = Section.attributes c in
+      match e with
+      | Id id -> [%equal: string option] attrs.id (Some id)
+      | Class cls -> List.mem attrs.classes cls ~equal:String.equal
+      | Attr (key, expected) ->
+        (match List.Assoc.find attrs.key_values ~equal:String.equal key, expected with
+         | Some value, Some expected -> String.equal value expected
+         | Some _, None -> true
+         | None, _ -> false)
+      | _ -> false
+    in
+    (* Matches in document order, not looking inside one already taken, so
+       no section comes back twice. *)
+    let rec outermost (cs : Section.t list) : Section.t list =
+      List.concat_map cs ~f:(fun (c : Section.t) ->
+        if has c then [ c ] else outermost c.children)
+    in
+    (match e, outermost s.children with
+     | Id _, c :: _ -> Ok (Section c)
+     | _, (_ :: _ as children) ->
+       Ok (Section { s with heading = None; blocks = []; children })
+     | _, [] ->
+       let attr : string =
+         match e with
+         | Id id -> "#" ^ id
+         | Class cls -> "." ^ cls
+         | Attr (key, Some value) -> key ^ "=" ^ value
+         | Attr (key, None) -> key
+         | _ -> ""
+       in
+       let candidates : string list =
+         List.concat_map in_scope ~f:(fun c -> heading_attrs_hum (Section.attributes c))
+         |> List.dedup_and_sort ~compare:String.compare
+       in
+       let suggestion : string option = suggest attr candidates in
+       Error (No_such_heading_attr { attr; candidates; suggestion }))
+  | Index i, Section s ->
+    let%map c = nth_or_error ~what:"section" s.children i in
+    Section c
+  | Slice (start, stop), Section s ->
+    let n : int = List.length s.children in
+    let%bind () =
+      (* In lenient mode out-of-range bounds clamp, like Python slices *)
+      let out_of_range (i : int) : bool = i < -n || i > n in
+      match mode, List.find (List.filter_opt [ start; stop ]) ~f:out_of_range with
+      | Strict, Some index ->
+        Error (Index_out_of_bounds { what = "slice"; index; len = n })
+      | Strict, None | Lenient, _ -> Ok ()
+    in
+    let clamp (i : int) : int =
+      Int.clamp_exn (if i < 0 then n + i else i) ~min:0 ~max:n
+    in
+    let start : int = Option.value_map start ~default:0 ~f:clamp in
+    let stop : int = Option.value_map stop ~default:n ~f:clamp in
+    let children : Section.t list =
+      List.sub s.children ~pos:start ~len:(Int.max 0 (stop - start))
+    in
+    Ok (Section { s with children })
+  | Frontmatter, _ ->
+    Ok
+      (Json
+         (Parse.Frontmatter.of_doc (Section.to_doc root)
+          |> Option.value_map ~default:`Null ~f:json_of_yaml))
+  | Code_meta, Section s ->
+    Ok (Json (`List (List.map (Code_meta.of_section s) ~f:Code_meta.yojson_of_t)))
+  | Quote i, Section s ->
+    let%map q = nth_or_error ~what:"quote" (Quote.of_section s) i in
+    Json (Quote.yojson_of_t q)
+  | Callout i, Section s ->
+    let callouts : Quote.t list = List.filter (Quote.of_section s) ~f:is_callout in
+    let%map q = nth_or_error ~what:"callout" callouts i in
+    Json (Quote.yojson_of_t q)
+  | Callouts, Section s ->
+    let callouts : Quote.t list = List.filter (Quote.of_section s) ~f:is_callout in
+    Ok (Json (`List (List.map callouts ~f:Quote.yojson_of_t)))
+  | Links kind, Section s ->
+    (* Definitions anywhere in the note count, not just those in scope. *)
+    let defs = Parse.Link_def.of_block (Cmarkit.Doc.block (Section.to_doc root)) in
+    Ok (Json (`List (List.map (Links.of_section ?kind ~defs s) ~f:Links.yojson_of_t)))
+  | Outline format, Section s -> Ok (Text (Outline.render format s))
+  | Path path, Section _ ->
+    (match Section.find_path root path with
+     | Some c -> Ok (Section c)
+     | None -> Error (No_such_path { path }))
+  | Parent, Section s ->
+    (match List.drop_last s.path with
+     | None -> Error No_parent
+     | Some path -> Ok (Section (Option.value_exn (Section.find_path root path))))
+  | ((Next | Prev) as e), Section s ->
+    let direction, step =
+      match e with
+      | Prev -> `Prev, -1
+      | _ -> `Next, 1
+    in
+    (match List.split_n s.path (List.length s.path - 1) with
+     | parent, [ i ] when i + step >= 1 ->
+       (match Section.find_path root (parent @ [ i + step ]) with
+        | Some c -> Ok (Section c)
+        | None -> Error (No_sibling { direction }))
+     | _ -> Error (No_sibling { direction }))
+  | First, Section s ->
+    let%map c = nth_or_error ~what:"section" s.children 0 in
+    Section c
+  | Last, Section s ->
+    let%map c = nth_or_error ~what:"section" s.children (-1) in
+    Section c
+  | First, Json (`List js) ->
+    let%map j = nth_or_error ~what:"element" js 0 in
+    Json j
+  | Last, Json (`List js) ->
+    let%map j = nth_or_error ~what:"element" js (-1) in
+    Json j
+  | Flatten_children, Section s ->
+    let children : Section.t list =
+      List.tl_exn (Section.descendants s)
+      |> List.map ~f:(fun (c : Section.t) -> { c with children = [] })
+    in
+    Ok (Section { s with children })
+  | Root_level level, Section s ->
+    (match Section.reroot ~level s with
+     | Ok s -> Ok (Section s)
+     | Error ((d : Section.t), level) ->
+       Error (Heading_too_deep { title = d.title; level }))
+  | Titles, Section s ->
+    let titles : string list =
+      List.tl_exn (Section.descendants s) |> List.map ~f:(fun (c : Section.t) -> c.title)
+    in
+    Ok (Json (`List (List.map titles ~f:(fun t -> `String t))))
+  | Summary_json depth, Section s ->
+    Ok (Json (Summary.yojson_of_t (Summary.of_section ?depth s)))
+  | Meta key, Section s ->
+    (match Parse.Inline_field.(to_value (of_doc (Section.to_doc s))) with
+     | Parse.Value.Map fields ->
+       (match List.Assoc.find fields ~equal:String.equal key with
+        | Some v -> Ok (Json (Parse.Value.to_json v))
+        | None ->
+          let candidates : string list = List.map fields ~f:fst in
+          let suggestion : string option = suggest key candidates in
+          Error (No_such_meta { key; candidates; suggestion }))
+     | _ -> Error (No_such_meta { key; candidates = []; suggestion = None }))
+  | Fm_keys, _ ->
+    let keys : string list = List.map (frontmatter_of ~root v) ~f:fst in
+    Ok (Json (`List (List.map keys ~f:(fun k -> `String k))))
+  | Fm_has key, _ ->
+    let has : bool = List.Assoc.mem (frontmatter_of ~root v) ~equal:String.equal key in
+    Ok (if has then v else Empty)
+  | Fm_filter (key, expected), _ ->
+    (match List.Assoc.find (frontmatter_of ~root v) ~equal:String.equal key with
+     | Some j when json_matches expected j -> Ok v
+     | Some _ | None -> Ok Empty)
+  | Custom (name, arg), _ ->
+    (match Hashtbl.find customs name with
+     | Some f ->
+       f ~root v arg
+       |> Result.map_error ~f:(fun message -> Eval_error.Custom_failed { name; message })
+     | None ->
+       let registered : string list = registered () in
+       Error
+         (No_such_custom { name; registered; suggestion = suggest name registered }))
+  | Comma, _ -> Ok v
+  | _, Json _ -> Error (Expects_section { got = "JSON" })
+  | _, Text _ -> Error (Expects_section { got = "text" })
+  | _, Many _ -> Error (Expects_section { got = "several values" })
+;;
+
+(** Run [exprs] left to right over the document [root]; with [,]s, each
+    pipeline between them, giving {!Many} values. See {!mode} for how missing
+    selections are handled; the default is {!Strict}. *)
+let eval ?(mode : mode = Strict) (exprs : t list) (root : Section.t)
+  : (value, Eval_error.t) Result.t
+  =
+  (* [exprs] from stage [start] on, as one pipeline *)
+  let eval_pipeline ((start, exprs) : int * t list) : (value, Eval_error.t) Result.t =
+    List.foldi exprs ~init:(Ok (Section root)) ~f:(fun i acc expr ->
+      Result.bind acc ~f:(fun v ->
+        match mode, eval_step ~mode ~root v expr with
+        | _, Ok v -> Ok v
+        | Lenient, Error kind when Eval_error.is_missing kind -> Ok Empty
+        | (Strict | Lenient), Error kind ->
+          Error { Eval_error.stage = start + i; expr; kind }))
+  in
+  (* the pipelines between commas, each with the stage it starts at *)
+  let pipelines : (int * t list) list =
+    let start, current, done_ =
+      List.foldi exprs ~init:(0, [], []) ~f:(fun i (start, current, done_) e ->
+        match e with
+        | Comma -> i + 1, [], (start, List.rev current) :: done_
+        | _ -> start, e :: current, done_)
+    in
+    List.rev ((start, List.rev current) :: done_)
+  in
+  match pipelines with
+  | [ pipeline ] -> eval_pipeline pipeline
+  | pipelines ->
+    let%map.Result values = Result.all (List.map pipelines ~f:eval_pipeline) in
+    Many values
+;;
+
+(** As JSON, a section being its title, path, markdown and {!Section.id} if
+    stamped. *)
+let rec json_of_value : value -> Yojson.Safe.t = function
+  | Section s ->
+    `Assoc
+      ([ "title", `String s.title
+       ; "path", `String (Section.path_to_string s.path)
+       ; "markdown", `String (Section.to_markdown s)
+       ]
+       @ Option.value_map (Section.id s) ~default:[] ~f:(fun id -> [ "id", `String id ]))
+  | Json j -> j
+  | Text s -> `String s
+  | Many vs -> `List (List.map vs ~f:json_of_value)
+  | Empty -> `Null
+;;
+
+(** Sections print as markdown, text as is, everything else as JSON. Several
+    values print one after the other if all are sections or text, else as a
+    JSON list. *)
+let rec value_to_string : value -> string = function
+  | Section s -> Section.to_markdown s
+  | Json j -> Yojson.Safe.pretty_to_string j
+  | Text s -> s
+  | Many vs
+    when List.for_all vs ~f:(function
+           | Section _ | Text _ | Empty -> true
+           | Json _ | Many _ -> false) ->
+    List.filter vs ~f:(function
+      | Empty -> false
+      | _ -> true)
+    |> List.map ~f:value_to_string
+    |> String.concat ~sep:"\n"
+  | Many _ as v -> Yojson.Safe.pretty_to_string (json_of_value v)
+  | Empty -> ""
+;;
+
+let%expect_test "field matching ignores markup" =
+  let root =
+    Section.of_doc (Parse.of_string "# Using `--field` with *emphasis*\n\nBody.\n")
+  in
+  List.iter
+    [ Field ("using --field with emphasis", Loose)
+    ; Field ("Using `--field` with *emphasis*", Loose)
+    ; Field ("using `--field`", Prefix)
+    ; Field ("Using --field with emphasis", Exact)
+    ; Field ("Using `--field` with *emphasis*", Exact)
+    ]
+    ~f:(fun e ->
+      match eval [ e ] root with
+      | Ok (Section s) -> printf "%s\n" s.title
+      | Ok _ -> ()
+      | Error e -> print_endline (Eval_error.to_string_hum e));
+  [%expect
+    {|
+    Using --field with emphasis
+    Using --field with emphasis
+    Using --field with emphasis
+    Using --field with emphasis
+    stage 0 (Field ("Using `--field` with *emphasis*" Exact)): no section titled Using `--field` with *emphasis*; did you mean Using --field with emphasis?
+    |}]
+;;
+
+let%expect_test "custom expressions" =
+  let root = Section.of_doc (Parse.of_string "# Install\n\n# Usage\n") in
+  register ~name:"title-case" (fun ~root:_ v arg ->
+    match v, arg with
+    | Section s, "upper" -> Ok (Text (String.uppercase s.title))
+    | Section s, "lower" -> Ok (Text (String.lowercase s.title))
+    | Section _, _ -> Error "expects upper or lower"
+    | _ -> Error "expects a section");
+  let run (args : string list) =
+    match eval (Or_error.ok_exn (of_args args)) root with
+    | Ok v -> print_endline (value_to_string v)
+    | Error e -> print_endline (Eval_error.to_string_hum e)
+  in
+  run [ "--field"; "usage"; "--x"; "title-case:upper" ];
+  [%expect {| USAGE |}];
+  run [ "--first"; "--x"; "title-case" ];
+  [%expect {| stage 1 (Custom title-case ""): title-case: expects upper or lower |}];
+  run [ "--x"; "title-cas:upper" ];
+  [%expect
+    {| stage 0 (Custom title-cas upper): no custom expression title-cas; did you mean title-case? |}]
+;;
+
+let%expect_test "eval" =
+  let root =
+    Section.of_doc
+      (Parse.of_string
+         {|---
+tags: [a, b]
+---
+# Install
+
+## Linux
+
+## macOS
+
+# Usage
+|})
+  in
+  let run ?mode (args : string list) =
+    match eval ?mode (Or_error.ok_exn (of_args args)) root with
+    | Ok v -> print_endline (String.strip (value_to_string v))
+    | Error e -> print_endline (Eval_error.to_string_hum e)
+  in
+  run [ "--field"; "Install"; "--index"; "-1" ];
+  [%expect {| ## macOS |}];
+  run [ "--frontmatter" ];
+  [%expect {| { "tags": [ "a", "b" ] } |}];
+  run [ "--field"; "Install"; "--index"; "2" ];
+  [%expect {| stage 1 (Index 2): section index 2 out of bounds; valid range is -2..1 |}];
+  run [ "--field"; "Instal" ];
+  [%expect
+    {| stage 0 (Field (Instal Loose)): no section titled Instal; did you mean Install? |}];
+  run [ "--field"; "Linux"; ","; "--field"; "Usage" ];
+  [%expect
+    {|
+    ## Linux
+
+    # Usage
+    |}];
+  run [ "--fm-keys"; ","; "--titles" ];
+  [%expect {| [ [ "tags" ], [ "Install", "Linux", "macOS", "Usage" ] ] |}];
+  run [ "--titles"; ","; "--field"; "Nope" ];
+  [%expect
+    {| stage 2 (Field (Nope Loose)): no section titled Nope; candidates: Install, Linux, macOS, Usage |}];
+  List.iter
+    [ [ "--titles"; "," ]; [ ","; "--titles" ]; [ "--titles"; ","; ","; "--fm-keys" ] ]
+    ~f:(fun args ->
+      match of_args args with
+      | Ok _ -> print_endline "ok"
+      | Error e -> print_endline (Error.to_string_hum e));
+  [%expect
+    {|
+    "',' needs an expression on each side"
+    "',' needs an expression on each side"
+    "',' needs an expression on each side"
+    |}];
+  run [ "--field"; "  LINUX " ];
+  [%expect {| ## Linux |}];
+  run [ "--field"; "mac"; "--prefix" ];
+  [%expect {| ## macOS |}];
+  run [ "--field"; "linux"; "--exact" ];
+  [%expect
+    {| stage 0 (Field (linux Exact)): no section titled linux; did you mean Linux? |}];
+  run [ "--anchor"; "macos" ];
+  [%expect {| ## macOS |}];
+  run [ "--anchor"; "instal" ];
+  [%expect
+    {| stage 0 (Anchor instal): no section with anchor instal; did you mean install? |}];
+  run [ "--field"; "Linux"; "--field"; "Usage" ];
+  [%expect
+    {| stage 1 (Field (Usage Loose)): no section titled Usage; there are no sections in scope
+    |}];
+  run ~mode:Lenient [ "--field"; "Instal"; "--codemeta" ];
+  [%expect {| |}];
+  run [ "--field"; "Install"; "--slice"; "1:5" ];
+  [%expect {| stage 1 (Slice (1) (5)): slice index 5 out of bounds; valid range is -2..2 |}];
+  run [ "--path"; "1.2"; "--parent"; "--index"; "0" ];
+  [%expect {| ## Linux |}];
+  run [ "--field"; "Install"; "--next" ];
+  [%expect {| # Usage |}];
+  run [ "--field"; "Linux"; "--prev" ];
+  [%expect {| stage 1 Prev: no preceding sibling section |}];
+  run [ "--titles" ];
+  [%expect {| [ "Install", "Linux", "macOS", "Usage" ] |}];
+  run [ "--field"; "Install"; "--last" ];
+  [%expect {| ## macOS |}];
+  run [ "--field"; "Install"; "--root-level"; "2" ];
+  [%expect
+    {|
+    ## Install
+
+    ### Linux
+
+    ### macOS
+    |}];
+  run [ "--field"; "Install"; "--root-level"; "6" ];
+  [%expect
+    {| stage 1 (Root_level 6): would put the heading of Linux at level 7, past 6 |}];
+  run [ "--path"; "3" ];
+  [%expect {| stage 0 (Path (3)): no section at path 3 |}];
+  run [ "--fm-keys" ];
+  [%expect {| [ "tags" ] |}];
+  run [ "--fm-filter"; "tags=b"; "--field"; "Usage" ];
+  [%expect {| # Usage |}];
+  run [ "--fm-has"; "status"; "--field"; "Usage" ];
+  [%expect {| |}];
+  run ~mode:Lenient [ "--field"; "Install"; "--slice"; "1:5" ];
+  [%expect
+    {|
+    # Install
+
+    ## macOS
+    |}]
+;;
+
+let%expect_test "meta" =
+  let root =
+    Section.of_doc
+      (Parse.of_string "# Book\n\nRating:: 9\n\n## Notes\n\ntag:: a\ntag:: b\n")
+  in
+  let run (args : string list) =
+    match eval (Or_error.ok_exn (of_args args)) root with
+    | Ok v -> print_endline (String.strip (value_to_string v))
+    | Error e -> print_endline (Eval_error.to_string_hum e)
+  in
+  run [ "--meta"; "Rating" ];
+  [%expect {| 9 |}];
+  run [ "--field"; "Notes"; "--meta"; "tag" ];
+  [%expect {| [ "a", "b" ] |}];
+  run [ "--field"; "Notes"; "--meta"; "rating" ];
+  [%expect {| stage 1 (Meta rating): no inline field rating; keys: tag |}];
+  run [ "--meta"; "ratin" ];
+  [%expect {| stage 0 (Meta ratin): no inline field ratin; did you mean Rating? |}]
+;;
+
+let%expect_test "heading attributes" =
+  let root =
+    Section.of_doc
+      (Parse.of_string
+         "# Setup\n\n\
+          {#linux .os status=done}\n## Linux\n\n{.os}\n### Debian\n\n\
+          {.os status=draft}\n## macOS\n\n\
+          {.appendix}\n# Glossary\n")
+  in
+  let run (args : string list) =
+    match eval (Or_error.ok_exn (of_args args)) root with
+    | Ok v -> print_endline (String.strip (value_to_string v))
+    | Error e -> print_endline (Eval_error.to_string_hum e)
+  in
+  run [ "--id"; "linux"; "--titles" ];
+  [%expect {| [ "Debian" ] |}];
+  (* Debian, inside Linux, is not a match of its own *)
+  run [ "--class"; "os"; "--titles" ];
+  [%expect {| [ "Linux", "Debian", "macOS" ] |}];
+  run [ "--class"; "os"; "--index"; "2" ];
+  [%expect {| stage 1 (Index 2): section index 2 out of bounds; valid range is -2..1 |}];
+  run [ "--attr"; "status=draft"; "--titles" ];
+  [%expect {| [ "macOS" ] |}];
+  run [ "--class"; "appendx" ];
+  [%expect
+    {| stage 0 (Class appendx): no section with heading attribute .appendx; did you mean .appendix? |}];
+  run [ "--field"; "Glossary"; "--attr"; "status" ];
+  [%expect
+    {| stage 1 (Attr status ()): no section with heading attribute status; there are no heading attributes in scope |}]
+;;
@@ -0,0 +1,86 @@
+(** Heading level normalization: close the gaps {!Section} detects by lifting
+    every heading that skips a level ([#] then [###]) to one below its
+    enclosing heading. Headings directly under the document keep their level. *)
+
+open Core
+
+type change =
+  { line : int (** 1-based line of the heading *)
+  ; title : string
+  ; from_level : int
+  ; to_level : int
+  }
+[@@deriving sexp_of]
+
+let change_to_string_hum (c : change) : string =
+  [%string "line %{c.line#Int}: %{c.title} (H%{c.from_level#Int} -> H%{c.to_level#Int})"]
+;;
+
+(** Level changes needed for [root], in document order. *)
+let changes (root : Section.t) : change list =
+  let rec go ~(parent_level : int) (s : Section.t) : change list =
+    let level : int =
+      if parent_level = 0 then s.level else Int.min s.level (parent_level + 1)
+    in
+    let own : change list =
+      match Section.heading_line s with
+      | Some line when level <> s.level ->
+        [ { line; title = s.title; from_level = s.level; to_level = level } ]
+      | _ -> []
+    in
+    own @ List.concat_map s.children ~f:(go ~parent_level:level)
+  in
+  List.concat_map root.children ~f:(go ~parent_level:0)
+;;
+
+(* Re-level the ATX heading on [line]. A gap always lands on H3 or deeper, so
+   it is never a setext heading. *)
+let relevel_line (line : string) ~(level : int) : string =
+  let indent : int = String.length line - String.length (String.lstrip line) in
+  let rest : string = String.drop_prefix line indent in
+  let hashes : int =
+    String.lfindi rest ~f:(fun _ c -> not (Char.equal c '#'))
+    |> Option.value ~default:(String.length rest)
+  in
+  String.prefix line indent ^ String.make level '#' ^ String.drop_prefix rest hashes
+;;
+
+(** Apply [changes] to the source [content] they were computed from. *)
+let apply (changes : change list) (content : string) : string =
+  let by_line : int Int.Map.t =
+    Int.Map.of_alist_reduce
+      (List.map changes ~f:(fun c -> c.line, c.to_level))
+      ~f:(fun _ l -> l)
+  in
+  String.split content ~on:'\n'
+  |> List.mapi ~f:(fun i line ->
+    match Map.find by_line (i + 1) with
+    | Some level -> relevel_line line ~level
+    | None -> line)
+  |> String.concat ~sep:"\n"
+;;
+
+let%expect_test "changes and apply" =
+  let content = "# A\n\n### B\n\n#### C\n\n## D\n\n#### E\n" in
+  let cs = changes (Section.of_doc (Parse.of_string content)) in
+  List.iter cs ~f:(fun c -> print_endline (change_to_string_hum c));
+  [%expect
+    {|
+    line 3: B (H3 -> H2)
+    line 5: C (H4 -> H3)
+    line 9: E (H4 -> H3)
+    |}];
+  print_string (apply cs content);
+  [%expect
+    {|
+    # A
+
+    ## B
+
+    ### C
+
+    ## D
+
+    ### E
+    |}]
+;;
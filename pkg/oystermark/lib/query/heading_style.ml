@@ -0,0 +1,252 @@
+(** Heading style rules for [oyster lint --headings] (see
+    {!Config.Heading_style}): letter case, length, trailing punctuation and
+    titles repeated within a note. Case and punctuation issues come with the
+    fixed title, which {!apply} writes back to the source the way
+    {!Fix_levels.apply} does levels. *)
+
+open Core
+
+type rule =
+  | Case
+  | Max_length
+  | Trailing_punctuation
+  | Duplicate
+[@@deriving sexp_of]
+
+type issue =
+  { line : int (** 1-based line of the heading *)
+  ; title : string
+  ; rule : rule
+  ; message : string
+  ; fix : string option
+    (** the heading's text once fixed; [None] when the rule has no fix or the
+        heading's source has markup *)
+  }
+[@@deriving sexp_of]
+
+let issue_to_string_hum (i : issue) : string = sprintf "line %d: %s" i.line i.message
+
+(* Lowercase words a title-cased heading keeps lowercase, unless first or last. *)
+let minor_words =
+  String.Set.of_list
+    [ "a"; "an"; "the"; "and"; "but"; "or"; "nor"; "for"; "so"; "yet"; "as"; "at"
+    ; "by"; "in"; "of"; "on"; "per"; "to"; "up"; "via"; "vs"
+    ] [@ocamlformat "disable"]
+;;
+
+(* [title] in [case]: words of letters only (apostrophes aside) are recased,
+   unless they are in [exceptions], one letter long, or have a capital past
+   their first letter (acronyms, camelCase). *)
+let recase ~(case : Config.Heading_case.t) ~(exceptions : String.Set.t) (title : string)
+  : string
+  =
+  let tokens = String.split title ~on:' ' in
+  let words =
+    List.filter_mapi tokens ~f:(fun i token ->
+      if String.is_empty token then None else Some i)
+  in
+  let first = List.hd words in
+  let last = List.last words in
+  List.mapi tokens ~f:(fun i token ->
+    let word =
+      String.strip token ~drop:(fun c -> not (Char.is_alpha c || Char.equal c '\''))
+    in
+    let recasable =
+      String.length word > 1
+      && String.for_all word ~f:(fun c -> Char.is_alpha c || Char.equal c '\'')
+      && (not (String.exists (String.drop_prefix word 1) ~f:Char.is_uppercase))
+      && not (Set.mem exceptions word)
+    in
+    let is_first = [%equal: int option] (Some i) first in
+    let is_last = [%equal: int option] (Some i) last in
+    let capitalized =
+      match case with
+      | Config.Heading_case_def.Any -> None
+      | Config.Heading_case_def.Sentence -> Some is_first
+      | Config.Heading_case_def.Title ->
+        Some (is_first || is_last || not (Set.mem minor_words (String.lowercase word)))
+    in
+    match capitalized with
+    | Some capitalized when recasable ->
+      let fixed = if capitalized then String.capitalize word else String.lowercase word in
+      String.substr_replace_first token ~pattern:word ~with_:fixed
+    | _ -> token)
+  |> String.concat ~sep:" "
+;;
+
+(* Characters, not bytes, of UTF-8 [s]. *)
+let length (s : string) : int =
+  String.count s ~f:(fun c -> Char.to_int c land 0xC0 <> 0x80)
+;;
+
+(* The heading source [line] as its marker ([## ]), text and closing
+   sequence ([ ##]); a setext heading's line is all text. *)
+let split_line (line : string) : string * string * string =
+  let indent = String.length line - String.length (String.lstrip line) in
+  let rest = String.drop_prefix line indent in
+  let hashes =
+    String.lfindi rest ~f:(fun _ c -> not (Char.equal c '#'))
+    |> Option.value ~default:(String.length rest)
+  in
+  let text =
+    if hashes = 0 then rest else String.lstrip (String.drop_prefix rest hashes)
+  in
+  let body =
+    let trimmed = String.rstrip text in
+    let unclosed = String.rstrip trimmed ~drop:(Char.equal '#') in
+    (* closing hashes follow a space, else they are text ([C#]) *)
+    if hashes > 0
+       && String.length unclosed < String.length trimmed
+       && (String.is_empty unclosed || String.is_suffix unclosed ~suffix:" ")
+    then String.rstrip unclosed
+    else trimmed
+  in
+  ( String.prefix line (String.length line - String.length text)
+  , body
+  , String.drop_prefix text (String.length body) )
+;;
+
+(** The issues of the headings of [root], whose source is [content], in
+    document order. *)
+let check ~(config : Config.Heading_style.t) ~(content : string) (root : Section.t)
+  : issue list
+  =
+  let lines = Array.of_list (String.split content ~on:'\n') in
+  let exceptions = String.Set.of_list config.case_exceptions in
+  let rec headings (s : Section.t) : (int * string) list =
+    let own =
+      match Section.heading_line s with
+      | Some line -> [ line, s.title ]
+      | None -> []
+    in
+    own @ List.concat_map s.children ~f:headings
+  in
+  let seen = String.Table.create () in
+  List.concat_map (headings root) ~f:(fun (line, title) ->
+    let cased = recase ~case:config.case ~exceptions title in
+    let fixed =
+      String.rstrip cased ~drop:(fun c -> String.mem config.trailing_punctuation c)
+    in
+    (* Fixes rewrite the source text, so only a heading written as its
+       title (no emphasis, links or code) gets one. *)
+    let fix =
+      let source = if line <= Array.length lines then lines.(line - 1) else "" in
+      let _, text, _ = split_line source in
+      if String.equal text title then Some fixed else None
+    in
+    let issue rule message = { line; title; rule; message; fix = None } in
+    let case =
+      if String.equal cased title
+      then []
+      else
+        [ { (issue
+               Case
+               (sprintf
+                  "heading case: %S should be %S (%s case)"
+                  title
+                  cased
+                  (Config.Heading_case.to_string config.case)))
+            with
+            fix
+          }
+        ]
+    in
+    let max_length =
+      if config.max_length > 0 && length title > config.max_length
+      then
+        [ issue
+            Max_length
+            (sprintf
+               "heading too long: %S is %d characters (max %d)"
+               title
+               (length title)
+               config.max_length)
+        ]
+      else []
+    in
+    let trailing =
+      match String.to_list_rev title with
+      | c :: _ when String.mem config.trailing_punctuation c ->
+        [ { (issue Trailing_punctuation (sprintf "heading ends with %C: %S" c title)) with
+            fix
+          }
+        ]
+      | _ -> []
+    in
+    let duplicate =
+      match Hashtbl.find seen title with
+      | Some first when config.unique ->
+        [ issue Duplicate (sprintf "duplicate heading: %S (first on line %d)" title first)
+        ]
+      | Some _ -> []
+      | None ->
+        Hashtbl.set seen ~key:title ~data:line;
+        []
+    in
+    case @ max_length @ trailing @ duplicate)
+;;
+
+(** Apply the fixes of [issues] to the source [content] they were computed
+    from. *)
+let apply (issues : issue list) (content : string) : string =
+  let by_line : string Int.Map.t =
+    List.filter_map issues ~f:(fun i -> Option.map i.fix ~f:(fun fix -> i.line, fix))
+    |> Int.Map.of_alist_reduce ~f:(fun fix _ -> fix)
+  in
+  String.split content ~on:'\n'
+  |> List.mapi ~f:(fun i line ->
+    match Map.find by_line (i + 1) with
+    | Some fix ->
+      let marker, _, closing = split_line line in
+      marker ^ fix ^ closing
+    | None -> line)
+  |> String.concat ~sep:"\n"
+;;
+
+let%expect_test "check and apply" =
+  let content =
+    "# Getting Started With Oyster\n\n\
+     ## Install the CLI.\n\n\
+     ## Using *emphasis* Here\n\n\
+     ## what iPhone users see ##\n\n\
+     ## Install the CLI.\n"
+  in
+  let config : Config.Heading_style.t =
+    { Config.Heading_style.default with
+      case = Config.Heading_case_def.Sentence
+    ; case_exceptions = [ "Oyster" ]
+    ; max_length = 24
+    }
+  in
+  let issues = check ~config ~content (Section.of_doc (Parse.of_string content)) in
+  List.iter issues ~f:(fun i -> print_endline (issue_to_string_hum i));
+  [%expect
+    {|
+    line 1: heading case: "Getting Started With Oyster" should be "Getting started with Oyster" (sentence case)
+    line 1: heading too long: "Getting Started With Oyster" is 27 characters (max 24)
+    line 3: heading ends with '.': "Install the CLI."
+    line 5: heading case: "Using emphasis Here" should be "Using emphasis here" (sentence case)
+    line 7: heading case: "what iPhone users see" should be "What iPhone users see" (sentence case)
+    line 9: heading ends with '.': "Install the CLI."
+    line 9: duplicate heading: "Install the CLI." (first on line 3)
+    |}];
+  print_string (apply issues content);
+  [%expect
+    {|
+    # Getting started with Oyster
+
+    ## Install the CLI
+
+    ## Using *emphasis* Here
+
+    ## What iPhone users see ##
+
+    ## Install the CLI
+    |}];
+  recase
+    ~case:Config.Heading_case_def.Title
+    ~exceptions:String.Set.empty
+    "notes on the state of the art"
+  |> print_endline;
+  [%expect {| Notes on the State of the Art |}]
+;;
@@ -0,0 +1,77 @@
+(** Parsing and querying as string in, JSON string out, for bindings from other
+    languages (the browser build in [js/]). A failure is returned as
+    [{"error": message}] rather than raised. *)
+
+open Core
+
+let error_json (msg : string) : Yojson.Safe.t = `Assoc [ "error", `String msg ]
+
+let guard (f : unit -> Yojson.Safe.t) : string =
+  Yojson.Safe.to_string
+    (match f () with
+     | j -> j
+     | exception e -> error_json (Exn.to_string e))
+;;
+
+(** The parsed document, as {!Parse.Ast_json.of_doc}. *)
+let parse ?(config : Config.t option) (content : string) : string =
+  guard (fun () ->
+    Yojson.Basic.to_string (Parse.Ast_json.of_doc (Parse.of_string ?config content))
+    |> Yojson.Safe.from_string)
+;;
+
+let json_of_value : Expr.value -> Yojson.Safe.t = Expr.json_of_value
+
+(** Run the query expressions [args], written as on the command line
+    ([["--field"; "Usage"; "--codemeta"]]), over [content]: the value, with a
+    section given as its title, path and markdown, or an error message. With
+    the note's [path], a section also has its {!Section.id}. *)
+let query_value
+      ?(config : Config.t option)
+      ?(path : string option)
+      ?(mode : Expr.mode = Strict)
+      (args : string list)
+      (content : string)
+  : (Yojson.Safe.t, string) Result.t
+  =
+  match Expr.of_args args with
+  | Error e -> Error (Error.to_string_hum e)
+  | Ok exprs ->
+    let doc = Parse.of_string ?config content in
+    let doc =
+      Option.value_map path ~default:doc ~f:(fun path -> Parse.Section_id.stamp ~path doc)
+    in
+    (match Expr.eval ~mode exprs (Section.of_doc doc) with
+     | Ok v -> Ok (json_of_value v)
+     | Error e -> Error (Expr.Eval_error.to_string_hum e))
+;;
+
+(** {!query_value} as [{"result": value}] or [{"error": message}]. *)
+let query
+      ?(config : Config.t option)
+      ?(path : string option)
+      ?(mode : Expr.mode option)
+      args
+      content
+  : string
+  =
+  guard (fun () ->
+    match query_value ?config ?path ?mode args content with
+    | Ok v -> `Assoc [ "result", v ]
+    | Error msg -> error_json msg)
+;;
+
+let%expect_test "query" =
+  let content = "# Install\n\n## Linux\n\napt install oyster\n\n# Usage\n" in
+  print_endline (query [ "--field"; "Linux" ] content);
+  [%expect
+    {| {"result":{"title":"Linux","path":"1.1","markdown":"## Linux\n\napt install oyster\n"}} |}];
+  print_endline (query [ "--titles" ] content);
+  [%expect {| {"result":["Install","Linux","Usage"]} |}];
+  print_endline (query [ "--field"; "Linx" ] content);
+  [%expect
+    {| {"error":"stage 0 (Field (Linx Loose)): no section titled Linx; did you mean Linux?"} |}];
+  print_endline (query ~path:"install.md" [ "--field"; "Linux" ] content);
+  [%expect
+    {| {"result":{"title":"Linux","path":"1.1","markdown":"## Linux\n\napt install oyster\n","id":"s88129aa4a0e9"}} |}]
+;;
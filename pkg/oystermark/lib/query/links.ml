@@ -0,0 +1,155 @@
+(** Links of a section as structured data, for [--links]. *)
+
+open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
+
+type t =
+  { text : string (** link text as plain text; the destination for autolinks *)
+  ; dest : string
+    (** destination as written, e.g. [https://ocaml.org] or [Note#Usage]; the
+        definition's for reference links, [^label] for footnotes *)
+  ; kind : string (** {!Parse.Link_kind.to_string} *)
+  ; style : string
+    (** ["inline"], ["reference"] ([[text][label]]), ["autolink"],
+        ["wikilink"] or ["footnote"] *)
+  ; section_path : string
+  }
+[@@deriving sexp_of, yojson_of]
+
+let wikilink_dest (w : Cmarkit.Inline.Wikilink.t) : string =
+  let target = Option.value (Cmarkit.Inline.Wikilink.target w) ~default:"" in
+  match Cmarkit.Inline.Wikilink.fragment w with
+  | None -> target
+  | Some (Cmarkit.Inline.Wikilink.Heading hs) -> target ^ "#" ^ String.concat ~sep:"#" hs
+  | Some (Cmarkit.Inline.Wikilink.Block_ref b) -> target ^ "#^" ^ b
+;;
+
+(* [(text, dest, style)] of every link in [b], in document order, reference
+   links and footnotes looked up in [defs]. Images and embeds are left out. *)
+let links_in ~(defs : Cmarkit.Label.defs) (b : Cmarkit.Block.t)
+  : (string * string * string) list
+  =
+  let folder =
+    Cmarkit.Folder.make
+      ~inline:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Link (l, _) ->
+          let text =
+            Parse.Heading_slug.inline_to_plain_text (Cmarkit.Inline.Link.text l)
+          in
+          let style =
+            match Cmarkit.Inline.Link.reference l with
+            | `Inline _ -> "inline"
+            | `Ref _ -> "reference"
+          in
+          (match Parse.Link_def.of_link ~defs l with
+           | Some (Parse.Link_def.Link ld) ->
+             let dest = Option.value (Parse.Link_def.dest ld) ~default:"" in
+             Cmarkit.Folder.ret ((text, dest, style) :: acc)
+           | Some (Parse.Link_def.Footnote (label, _)) ->
+             Cmarkit.Folder.ret ((text, "^" ^ label, "footnote") :: acc)
+           | None -> Cmarkit.Folder.ret acc)
+        | Cmarkit.Inline.Autolink (a, _) ->
+          let link, _ = Cmarkit.Inline.Autolink.link a in
+          let dest =
+            if Cmarkit.Inline.Autolink.is_email a then "mailto:" ^ link else link
+          in
+          Cmarkit.Folder.ret ((link, dest, "autolink") :: acc)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Ext_wikilink (w, _) when not (Cmarkit.Inline.Wikilink.embed w) ->
+          let dest = wikilink_dest w in
+          let text = Option.value (Cmarkit.Inline.Wikilink.display w) ~default:dest in
+          (text, dest, "wikilink") :: acc
+        | _ -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_block folder [] b)
+;;
+
+(** Every link in [section] and its descendants, in document order, keeping
+    only those of [kind] when given. Reference links and footnotes are looked
+    up in [defs] (see {!Parse.Link_def.of_block}), defaulting to the
+    definitions within [section]. *)
+let of_section
+      ?(kind : Parse.Link_kind.t option)
+      ?(defs : Cmarkit.Label.defs option)
+      (section : Section.t)
+  : t list
+  =
+  let defs =
+    match defs with
+    | Some defs -> defs
+    | None -> Parse.Link_def.of_block (Cmarkit.Doc.block (Section.to_doc section))
+  in
+  List.concat_map (Section.descendants section) ~f:(fun (s : Section.t) ->
+    List.concat_map s.blocks ~f:(links_in ~defs)
+    |> List.map ~f:(fun (text, dest, style) ->
+      { text
+      ; dest
+      ; kind = Parse.Link_kind.(to_string (of_dest dest))
+      ; style
+      ; section_path = Section.path_to_string s.path
+      }))
+  |> List.filter ~f:(fun l ->
+    match kind with
+    | None -> true
+    | Some k -> String.equal l.kind (Parse.Link_kind.to_string k))
+;;
+
+let%expect_test "of_section" =
+  let doc =
+    Parse.of_string
+      {|# Links
+
+See [OCaml](https://ocaml.org), [[Setup#Install|setup]] and <mailto:a@b.c>.
+
+## Local
+
+Read [the *todo*](notes/todo.md) and [[Ideas]]; not ![[logo.png]].
+The [manual][man] says so[^1].
+
+[man]: https://ocaml.org/manual
+
+[^1]: Chapter 2.
+|}
+  in
+  let print ?kind () =
+    List.iter (of_section ?kind (Section.of_doc doc)) ~f:(fun t ->
+      print_endline (Yojson.Safe.to_string (yojson_of_t t)))
+  in
+  print ();
+  [%expect
+    {|
+    {"text":"OCaml","dest":"https://ocaml.org","kind":"external","style":"inline","section_path":"1"}
+    {"text":"setup","dest":"Setup#Install","kind":"internal","style":"wikilink","section_path":"1"}
+    {"text":"a@b.c","dest":"mailto:a@b.c","kind":"external","style":"autolink","section_path":"1"}
+    {"text":"the todo","dest":"notes/todo.md","kind":"internal","style":"inline","section_path":"1.1"}
+    {"text":"Ideas","dest":"Ideas","kind":"internal","style":"wikilink","section_path":"1.1"}
+    {"text":"manual","dest":"https://ocaml.org/manual","kind":"external","style":"reference","section_path":"1.1"}
+    {"text":"^1","dest":"^1","kind":"internal","style":"footnote","section_path":"1.1"}
+    |}];
+  print ~kind:External ();
+  [%expect
+    {|
+    {"text":"OCaml","dest":"https://ocaml.org","kind":"external","style":"inline","section_path":"1"}
+    {"text":"a@b.c","dest":"mailto:a@b.c","kind":"external","style":"autolink","section_path":"1"}
+    {"text":"manual","dest":"https://ocaml.org/manual","kind":"external","style":"reference","section_path":"1.1"}
+    |}]
+;;
+
+let%expect_test "of_section: markup in link text" =
+  let doc =
+    Parse.of_string
+      "See [**Bold** ![logo](x.png) word{.x} `code`](page.md) and [[Note|*n*]].\n"
+  in
+  List.iter (of_section (Section.of_doc doc)) ~f:(fun t ->
+    printf "%s -> %s\n" t.text t.dest);
+  [%expect
+    {|
+    Bold logo word code -> page.md
+    *n* -> Note
+    |}]
+;;
@@ -0,0 +1,102 @@
+(** Heading outline of a section, for [--outline]: an indented Markdown list,
+    or OPML for mind-mapping and outliner tools. The section's own title is
+    the outline's title; its descendants are the items. *)
+
+open Core
+
+type format =
+  | Markdown
+  | Opml
+[@@deriving sexp_of]
+
+let format_of_string : string -> format option = function
+  | "md" | "markdown" -> Some Markdown
+  | "opml" -> Some Opml
+  | _ -> None
+;;
+
+let to_markdown (section : Section.t) : string =
+  let buf = Buffer.create 256 in
+  let rec go (depth : int) (s : Section.t) =
+    Buffer.add_string buf (String.make (2 * depth) ' ');
+    Buffer.add_string buf "- ";
+    Buffer.add_string buf s.title;
+    Buffer.add_char buf '\n';
+    List.iter s.children ~f:(go (depth + 1))
+  in
+  List.iter section.children ~f:(go 0);
+  Buffer.contents buf
+;;
+
+let xml_escape (s : string) : string =
+  String.concat_map s ~f:(function
+    | '&' -> "&amp;"
+    | '<' -> "&lt;"
+    | '>' -> "&gt;"
+    | '"' -> "&quot;"
+    | c -> String.of_char c)
+;;
+
+let to_opml (section : Section.t) : string =
+  let buf = Buffer.create 256 in
+  let title : string =
+    if String.is_empty section.title then "Outline" else section.title
+  in
+  Buffer.add_string buf "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+  Buffer.add_string buf "<opml version=\"2.0\">\n";
+  Buffer.add_string buf [%string "  <head><title>%{xml_escape title}</title></head>\n"];
+  Buffer.add_string buf "  <body>\n";
+  (* Outline items nest two levels below <opml> and <body> *)
+  let indent (s : Section.t) : string =
+    String.make (2 * (List.length s.path - List.length section.path + 1)) ' '
+  in
+  List.iter section.children ~f:(fun child ->
+    Section.fold
+      child
+      ~init:()
+      ~enter:(fun () s ->
+        let text : string = xml_escape s.title in
+        let close : string = if List.is_empty s.children then "/" else "" in
+        Buffer.add_string buf [%string "%{indent s}<outline text=\"%{text}\"%{close}>\n"])
+      ~exit:(fun () s ->
+        if not (List.is_empty s.children)
+        then Buffer.add_string buf [%string "%{indent s}</outline>\n"]));
+  Buffer.add_string buf "  </body>\n</opml>\n";
+  Buffer.contents buf
+;;
+
+let render (format : format) (section : Section.t) : string =
+  match format with
+  | Markdown -> to_markdown section
+  | Opml -> to_opml section
+;;
+
+let%expect_test "outline" =
+  let section =
+    Section.of_doc (Parse.of_string "# A & B\n\n## A.1\n\n### A.1.a\n\n# C\n")
+  in
+  print_string (to_markdown section);
+  [%expect
+    {|
+    - A & B
+      - A.1
+        - A.1.a
+    - C
+    |}];
+  print_string (to_opml section);
+  [%expect
+    {|
+    <?xml version="1.0" encoding="UTF-8"?>
+    <opml version="2.0">
+      <head><title>Outline</title></head>
+      <body>
+        <outline text="A &amp; B">
+          <outline text="A.1">
+            <outline text="A.1.a"/>
+          </outline>
+        </outline>
+        <outline text="C"/>
+      </body>
+    </opml>
+    |}]
+;;
@@ -0,0 +1,72 @@
+(** Structural queries over a single markdown document: select sections by
+    title or position and extract data (frontmatter, code blocks, callouts) from them.
+    Backs the [oystermark query] command; see {!Expr}. *)
+
+module Section = Section
+module Code_meta = Code_meta
+module Quote = Quote
+module Links = Links
+module Outline = Outline
+module Summary = Summary
+module Fix_levels = Fix_levels
+module Heading_style = Heading_style
+module Expr = Expr
+module Json_api = Json_api
+module Search = Search
+
+(* [content] parsed, with code fences naming a [file=] filled through
+   [read_file] if given; see {!Parse.Code_include}. *)
+let parse ?(config : Config.t option) ?read_file (content : string) : Cmarkit.Doc.t =
+  let doc = Parse.of_string ?config content in
+  match read_file with
+  | None -> doc
+  | Some read_file -> Parse.Code_include.expand ~read_file doc
+;;
+
+(** Section tree of [content]; fails under {!Section.Reject}, or when there is
+    no document [doc]. With [read_file], code fences naming a [file=] hold that
+    file's lines; with the note's [path], sections have their {!Section.id};
+    with [doc], only the [doc]th (0-based, negative counting from the end) of
+    the documents {!Parse.Multi_doc.split} finds is queried. *)
+let sections
+      ?(config : Config.t option)
+      ?(read_file : (string -> string option) option)
+      ?(path : string option)
+      ?(gap_policy : Section.gap_policy = Attach)
+      ?(doc : int option)
+      (content : string)
+  : Section.t Or_error.t
+  =
+  let%bind.Or_error doc =
+    let whole = parse ?config ?read_file content in
+    match doc with
+    | None -> Ok whole
+    | Some i ->
+      let docs = Parse.Multi_doc.split whole in
+      let n = List.length docs in
+      (match List.nth docs (if i < 0 then n + i else i) with
+       | Some d -> Ok d
+       | None ->
+         Or_error.errorf
+           "document %d out of bounds; the file has %d document%s"
+           i
+           n
+           (if n = 1 then "" else "s"))
+  in
+  let doc =
+    Option.value_map path ~default:doc ~f:(fun path -> Parse.Section_id.stamp ~path doc)
+  in
+  Section.of_doc_with ~gap_policy doc
+;;
+
+(** Parse [content] and run [exprs] over it; [read_file] as for {!sections}. *)
+let run
+      ?(config : Config.t option)
+      ?(read_file : (string -> string option) option)
+      ?(mode : Expr.mode option)
+      (exprs : Expr.t list)
+      (content : string)
+  : (Expr.value, Expr.Eval_error.t) Result.t
+  =
+  Expr.eval ?mode exprs (Section.of_doc (parse ?config ?read_file content))
+;;
@@ -0,0 +1,98 @@
+(** Blockquotes and callouts as structured data, for [--quote], [--callout]
+    and [--callouts]. *)
+
+open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
+
+type t =
+  { callout : string option (** callout kind, e.g. ["tip"]; [None] for a plain quote *)
+  ; title : string option (** explicit title, else the capitalized kind *)
+  ; fold : string option (** ["open"] or ["closed"] for foldable callouts *)
+  ; content : string (** markdown body, callout header stripped *)
+  ; first_line : int option
+  ; last_line : int option
+  ; section_path : string
+  }
+[@@deriving sexp_of, yojson_of]
+
+let of_block ?(section_path : string = "root") (b : Cmarkit.Block.t) : t option =
+  match b with
+  | Cmarkit.Block.Block_quote (bq, meta) ->
+    let inner : Cmarkit.Block.t = Cmarkit.Block.Block_quote.block bq in
+    let callout : Cmarkit.Block.Callout.t option = Cmarkit.Block.Callout.find meta in
+    let kind (c : Cmarkit.Block.Callout.t) : string =
+      Parse.Gfm_alert.callout_kind (Cmarkit.Block.Callout.kind c)
+    in
+    let body : Cmarkit.Block.t =
+      if Option.is_some callout then Cmarkit.Block.Callout.strip_header inner else inner
+    in
+    let tl : Cmarkit.Textloc.t = Cmarkit.Meta.textloc meta in
+    let line (f : Cmarkit.Textloc.t -> Cmarkit.Textloc.line_pos) : int option =
+      if Cmarkit.Textloc.is_none tl then None else Some (fst (f tl))
+    in
+    Some
+      { callout = Option.map callout ~f:kind
+      ; title =
+          Option.map callout ~f:(fun c ->
+            match Cmarkit.Block.Callout.title c inner with
+            | Some title -> Parse.Heading_slug.inline_to_plain_text title
+            | None -> String.capitalize (kind c))
+      ; fold =
+          Option.bind callout ~f:Cmarkit.Block.Callout.fold
+          |> Option.map ~f:(function
+            | Cmarkit.Block.Callout.Foldable_open -> "open"
+            | Cmarkit.Block.Callout.Foldable_closed -> "closed")
+      ; content = String.rstrip (Parse.commonmark_of_doc (Cmarkit.Doc.make body))
+      ; first_line = line Cmarkit.Textloc.first_line
+      ; last_line = line Cmarkit.Textloc.last_line
+      ; section_path
+      }
+  | _ -> None
+;;
+
+(* Outermost blockquotes in [b]; quotes nested in a quote belong to it. *)
+let quotes_in (b : Cmarkit.Block.t) : Cmarkit.Block.t list =
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun _f acc b ->
+        match b with
+        | Cmarkit.Block.Block_quote _ -> Cmarkit.Folder.ret (b :: acc)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_block folder [] b)
+;;
+
+(** Every blockquote (callouts included) in [section] and its descendants,
+    in document order. *)
+let of_section (section : Section.t) : t list =
+  List.concat_map (Section.descendants section) ~f:(fun (s : Section.t) ->
+    List.concat_map s.blocks ~f:quotes_in
+    |> List.filter_map ~f:(of_block ~section_path:(Section.path_to_string s.path)))
+;;
+
+let%expect_test "of_section" =
+  let doc =
+    Parse.of_string
+      {|# Notes
+
+> plain quote
+
+> [!warning]- Mind the gap
+> Between train and platform.
+
+> [!tip]
+> Short one.
+|}
+  in
+  List.iter (of_section (Section.of_doc doc)) ~f:(fun t ->
+    print_endline (Yojson.Safe.to_string (yojson_of_t t)));
+  [%expect
+    {|
+    {"callout":null,"title":null,"fold":null,"content":"plain quote","first_line":3,"last_line":3,"section_path":"1"}
+    {"callout":"warning","title":"Mind the gap","fold":"closed","content":"Between train and platform.","first_line":5,"last_line":6,"section_path":"1"}
+    {"callout":"tip","title":"Tip","fold":null,"content":"Short one.","first_line":8,"last_line":9,"section_path":"1"}
+    |}]
+;;
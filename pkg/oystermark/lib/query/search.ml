@@ -0,0 +1,299 @@
+(** Full-text search over a vault's notes, for [oystermark search]. Every
+    section is a document, ranked by BM25 over the words of its own body, with
+    words of its heading counted several times over; the root section of a
+    note also gets the note's title and tags. *)
+
+open Core
+
+(** One indexed section. *)
+type doc =
+  { note : string (** vault-relative path *)
+  ; note_title : string (** frontmatter [title], else the file name *)
+  ; tags : string list
+  ; section_path : string
+  ; section_title : string (** [""] for the root *)
+  ; lines : (int * string) list (** the section's own body, by 1-based line *)
+  ; tf : int String.Map.t (** weighted term frequencies *)
+  ; length : int (** words in the body *)
+  }
+
+type t =
+  { docs : doc list
+  ; df : int String.Map.t (** documents containing each term *)
+  ; avg_length : float
+  }
+
+type result =
+  { note : string
+  ; section_path : string
+  ; section_title : string
+  ; line : int option (** line of the snippet *)
+  ; snippet : string
+  ; score : float
+  }
+[@@deriving sexp_of]
+
+(* Letters and digits, and any byte of a non-ASCII character. *)
+let is_word_char (c : char) : bool = Char.is_alphanum c || Char.to_int c >= 128
+
+(** Lowercased runs of {!is_word_char}. *)
+let tokens (s : string) : string list =
+  String.map s ~f:(fun c -> if is_word_char c then Char.lowercase c else ' ')
+  |> String.split ~on:' '
+  |> List.filter ~f:(Fn.non String.is_empty)
+;;
+
+(* How many times a word in each field counts, against once in the body. *)
+let title_weight = 3
+let heading_weight = 2
+let tag_weight = 2
+
+let add_terms (tf : int String.Map.t) ~(weight : int) (words : string list) =
+  List.fold words ~init:tf ~f:(fun tf w ->
+    Map.update tf w ~f:(fun n -> Option.value n ~default:0 + weight))
+;;
+
+let frontmatter_fields (doc : Cmarkit.Doc.t) : (string * Yaml_backend.value) list =
+  match Parse.Frontmatter.of_doc doc with
+  | Some (`O fields) -> fields
+  | _ -> []
+;;
+
+let note_tags (fields : (string * Yaml_backend.value) list) : string list =
+  match List.Assoc.find fields ~equal:String.equal "tags" with
+  | Some (`A tags) ->
+    List.filter_map tags ~f:(function
+      | `String t -> Some t
+      | _ -> None)
+  | Some (`String t) -> [ t ]
+  | _ -> []
+;;
+
+(** The sections of the note at [path], parsed as [doc] from [source]; its
+    encrypted blocks (see {!Parse.Encrypted}) are left out. *)
+let docs_of_note (path : string) (doc : Cmarkit.Doc.t) (source : string) : doc list =
+  let source = Parse.Encrypted.redact ~doc source in
+  let doc = Parse.Encrypted.strip doc in
+  let fields = frontmatter_fields doc in
+  let note_title =
+    match List.Assoc.find fields ~equal:String.equal "title" with
+    | Some (`String t) -> t
+    | _ -> Filename.chop_suffix_if_exists (Filename.basename path) ~suffix:".md"
+  in
+  let tags = note_tags fields in
+  let source_lines = Array.of_list (String.split_lines source) in
+  let line_text (i : int) : string =
+    if i >= 1 && i <= Array.length source_lines then source_lines.(i - 1) else ""
+  in
+  List.map
+    (Section.descendants (Section.of_doc doc))
+    ~f:(fun (s : Section.t) ->
+      let lines =
+        List.concat_map s.blocks ~f:(fun b ->
+          match Section.block_lines b with
+          | Some (first, last) ->
+            List.range first (last + 1) |> List.map ~f:(fun i -> i, line_text i)
+          | None -> [])
+      in
+      let body = List.concat_map lines ~f:(fun (_, l) -> tokens l) in
+      let tf = add_terms String.Map.empty ~weight:1 body in
+      let tf = add_terms tf ~weight:heading_weight (tokens s.title) in
+      let tf =
+        if List.is_empty s.path
+        then
+          add_terms tf ~weight:title_weight (tokens note_title)
+          |> add_terms ~weight:tag_weight (List.concat_map tags ~f:tokens)
+        else tf
+      in
+      { note = path
+      ; note_title
+      ; tags
+      ; section_path = Section.path_to_string s.path
+      ; section_title = s.title
+      ; lines
+      ; tf
+      ; length = List.length body
+      })
+;;
+
+(** Index of sections taken from notes by {!docs_of_note}. *)
+let of_docs (docs : doc list) : t =
+  let df =
+    List.fold docs ~init:String.Map.empty ~f:(fun df (d : doc) ->
+      Map.fold d.tf ~init:df ~f:(fun ~key ~data:_ df ->
+        Map.update df key ~f:(fun n -> Option.value n ~default:0 + 1)))
+  in
+  let avg_length =
+    if List.is_empty docs
+    then 0.
+    else
+      Float.of_int (List.sum (module Int) docs ~f:(fun d -> d.length))
+      /. Float.of_int (List.length docs)
+  in
+  { docs; df; avg_length }
+;;
+
+(** Index [notes], given as vault-relative path, parsed document and source. *)
+let build (notes : (string * Cmarkit.Doc.t * string) list) : t =
+  of_docs
+    (List.concat_map notes ~f:(fun (path, doc, source) -> docs_of_note path doc source))
+;;
+
+(* BM25 parameters. *)
+let k1 = 1.2
+let b = 0.75
+
+let score (t : t) (terms : string list) (d : doc) : float =
+  let n = Float.of_int (List.length t.docs) in
+  let norm =
+    if Float.(t.avg_length = 0.)
+    then 1.
+    else 1. -. b +. (b *. Float.of_int d.length /. t.avg_length)
+  in
+  List.sum
+    (module Float)
+    terms
+    ~f:(fun term ->
+      match Map.find d.tf term with
+      | None -> 0.
+      | Some tf ->
+        let df = Float.of_int (Option.value (Map.find t.df term) ~default:0) in
+        let idf = Float.log (1. +. ((n -. df +. 0.5) /. (df +. 0.5))) in
+        let tf = Float.of_int tf in
+        idf *. tf *. (k1 +. 1.) /. (tf +. (k1 *. norm)))
+;;
+
+(** [line] with the words among [terms] passed through [highlight]. *)
+let highlight_line ~(highlight : string -> string) (terms : string list) (line : string)
+  : string
+  =
+  let buf = Buffer.create (String.length line) in
+  let flush_word (word : string) =
+    if List.mem terms (String.lowercase word) ~equal:String.equal
+    then Buffer.add_string buf (highlight word)
+    else Buffer.add_string buf word
+  in
+  let word =
+    String.fold line ~init:"" ~f:(fun word c ->
+      if is_word_char c
+      then word ^ String.of_char c
+      else (
+        flush_word word;
+        Buffer.add_char buf c;
+        ""))
+  in
+  flush_word word;
+  Buffer.contents buf
+;;
+
+let max_snippet = 160
+
+(* The body line with the most matching words, highlighted. *)
+let snippet ~highlight (terms : string list) (d : doc) : int option * string =
+  let hits (line : string) : int =
+    List.count (tokens line) ~f:(List.mem terms ~equal:String.equal)
+  in
+  match
+    List.max_elt d.lines ~compare:(fun (_, a) (_, b) -> Int.compare (hits a) (hits b))
+  with
+  | Some (i, line) when hits line > 0 ->
+    let line = String.strip line in
+    let line =
+      if String.length line > max_snippet
+      then String.prefix line max_snippet ^ "..."
+      else line
+    in
+    Some i, highlight_line ~highlight terms line
+  | _ -> None, ""
+;;
+
+(** Whether [d] passes the filters: [tag] keeps notes with that tag, [path]
+    notes under that path prefix and [section] sections whose heading contains
+    it (ignoring case). *)
+let keep
+      ?(tag : string option)
+      ?(path : string option)
+      ?(section : string option)
+      (d : doc)
+  : bool
+  =
+  Option.for_all tag ~f:(fun tag -> List.mem d.tags tag ~equal:String.Caseless.equal)
+  && Option.for_all path ~f:(fun prefix -> String.is_prefix d.note ~prefix)
+  && Option.for_all section ~f:(fun substring ->
+    String.Caseless.is_substring d.section_title ~substring)
+;;
+
+(** The sections matching [query] and the {!keep} filters, best first, at most
+    [limit]. Snippet words matching the query go through [highlight]. *)
+let search
+      ?(tag : string option)
+      ?(path : string option)
+      ?(section : string option)
+      ?(limit : int = 20)
+      ?(highlight : string -> string = Fn.id)
+      (t : t)
+      (query : string)
+  : result list
+  =
+  let terms = List.dedup_and_sort (tokens query) ~compare:String.compare in
+  List.filter_map t.docs ~f:(fun d ->
+    if not (keep ?tag ?path ?section d)
+    then None
+    else (
+      let score = score t terms d in
+      if Float.(score <= 0.)
+      then None
+      else (
+        let line, snippet = snippet ~highlight terms d in
+        Some
+          { note = d.note
+          ; section_path = d.section_path
+          ; section_title = d.section_title
+          ; line
+          ; snippet
+          ; score
+          })))
+  |> List.stable_sort ~compare:(fun a b -> Float.descending a.score b.score)
+  |> Fn.flip List.take limit
+;;
+
+let%expect_test "search" =
+  let notes =
+    [ ( "tools/oyster.md"
+      , "---\ntags: [tools]\n---\n# Oyster\n\nA vault renderer.\n\n## Install\n\n\
+         Run opam install oyster.\n" )
+    ; "notes/pearl.md", "# Pearl\n\nGrown inside an oyster, slowly.\n"
+    ; "notes/shell.md", "# Shell\n\nNothing to see here.\n"
+    ]
+    |> List.map ~f:(fun (p, src) -> p, Parse.of_string src, src)
+  in
+  let t = build notes in
+  let show ?tag ?path ?section query =
+    List.iter
+      (search ?tag ?path ?section ~highlight:(sprintf "[%s]") t query)
+      ~f:(fun r ->
+        [ r.note
+        ; r.section_path
+        ; sprintf "%S" r.section_title
+        ; Option.value_map r.line ~default:"-" ~f:Int.to_string
+        ; r.snippet
+        ]
+        |> List.filter ~f:(Fn.non String.is_empty)
+        |> String.concat ~sep:" "
+        |> print_endline)
+  in
+  show "oyster";
+  [%expect
+    {|
+    tools/oyster.md root "" -
+    tools/oyster.md 1 "Oyster" -
+    tools/oyster.md 1.1 "Install" 10 Run opam install [oyster].
+    notes/pearl.md 1 "Pearl" 3 Grown inside an [oyster], slowly.
+    |}];
+  show ~path:"notes/" "oyster";
+  [%expect {| notes/pearl.md 1 "Pearl" 3 Grown inside an [oyster], slowly. |}];
+  show ~tag:"tools" ~section:"inst" "install";
+  [%expect {| tools/oyster.md 1.1 "Install" 10 Run opam [install] oyster. |}];
+  show "nowhere";
+  [%expect {| |}]
+;;
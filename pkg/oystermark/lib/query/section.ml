@@ -0,0 +1,557 @@
+(** Heading hierarchy of a document.
+
+    Each heading opens a section that owns the blocks up to the next heading,
+    and nests the following headings of a deeper level. The document itself is
+    the root section (level 0). How a heading that skips levels ([#] then
+    [###]) nests is set by a {!gap_policy}.
+
+    Sections hold the document's own blocks rather than copies of its text,
+    so a section tree costs little beyond the document it is built from. *)
+
+open Core
+
+type t =
+  { title : string
+    (** plain text of the heading, markup stripped and code spans kept as their
+        code; [""] for the root. [--field] matches it and the summary shows it;
+        [heading] keeps the text as written, for {!to_markdown}. *)
+  ; level : int (** heading level; [0] for the root *)
+  ; path : int list (** 1-based child indices from the root; [[]] for the root *)
+  ; heading : Cmarkit.Block.t option (** [None] for the root *)
+  ; blocks : Cmarkit.Block.t list (** own body, before the first child heading *)
+  ; children : t list
+  ; implicit : bool (** padding for a skipped level; see {!Pad} *)
+  ; lines : (int * int) option
+    (** first and last 1-based line of the whole section; an implicit section
+        spans its children *)
+  }
+
+(** What to do with a heading more than one level deeper than its enclosing
+    heading. Headings directly under the document may start at any level. *)
+type gap_policy =
+  | Attach (** nest it directly under the enclosing heading *)
+  | Pad (** insert untitled {!implicit} sections for the skipped levels *)
+  | Reject (** fail; see {!of_doc_with} *)
+[@@deriving sexp_of]
+
+let gap_policy_of_string : string -> gap_policy option = function
+  | "attach" -> Some Attach
+  | "pad" -> Some Pad
+  | "error" -> Some Reject
+  | _ -> None
+;;
+
+(** [1.2.3] style rendering of a path; the root is ["root"]. *)
+let path_to_string (path : int list) : string =
+  match path with
+  | [] -> "root"
+  | path -> String.concat ~sep:"." (List.map path ~f:Int.to_string)
+;;
+
+(** Inverse of {!path_to_string}. *)
+let path_of_string (s : string) : int list option =
+  match s with
+  | "root" -> Some []
+  | s ->
+    String.split s ~on:'.'
+    |> List.map ~f:(fun seg ->
+      match Int.of_string_opt seg with
+      | Some i when i >= 1 -> Some i
+      | _ -> None)
+    |> Option.all
+;;
+
+(** [b] without the [{...}] attribute line a heading may carry above it. *)
+let unwrap_heading (b : Cmarkit.Block.t) : Cmarkit.Block.t =
+  match b with
+  | Cmarkit.Block.Ext_attributes (a, _) ->
+    (match Cmarkit.Block.Attributes.block a with
+     | Cmarkit.Block.Heading _ as heading -> heading
+     | _ -> b)
+  | _ -> b
+;;
+
+let heading_level (b : Cmarkit.Block.t) : int option =
+  match unwrap_heading b with
+  | Cmarkit.Block.Heading (h, _) -> Some (Cmarkit.Block.Heading.level h)
+  | _ -> None
+;;
+
+(* Leading non-heading blocks, and the rest starting at the first heading. *)
+let split_body (blocks : Cmarkit.Block.t list)
+  : Cmarkit.Block.t list * Cmarkit.Block.t list
+  =
+  List.split_while blocks ~f:(fun b -> Option.is_none (heading_level b))
+;;
+
+let block_lines (b : Cmarkit.Block.t) : (int * int) option =
+  let tl : Cmarkit.Textloc.t =
+    Cmarkit.Meta.textloc (Cmarkit.Block.meta ~ext:(fun _ -> Cmarkit.Meta.none) b)
+  in
+  if Cmarkit.Textloc.is_none tl
+  then None
+  else Some (fst (Cmarkit.Textloc.first_line tl), fst (Cmarkit.Textloc.last_line tl))
+;;
+
+let span (ranges : (int * int) option list) : (int * int) option =
+  match List.filter_opt ranges with
+  | [] -> None
+  | (first, last) :: rest ->
+    Some
+      (List.fold rest ~init:(first, last) ~f:(fun (f, l) (f', l') ->
+         Int.min f f', Int.max l l'))
+;;
+
+exception Level_gap of { heading : string; level : int; parent_level : int }
+
+(* Sections nested under a heading of [parent_level], and the blocks left once
+   a heading at [parent_level] or shallower ends them. *)
+let rec parse_children
+          ~(gap_policy : gap_policy)
+          ~(parent_level : int)
+          ~(parent_path : int list)
+          (blocks : Cmarkit.Block.t list)
+  : t list * Cmarkit.Block.t list
+  =
+  let rec loop (i : int) (acc : t list) (blocks : Cmarkit.Block.t list) =
+    match blocks with
+    | heading :: rest ->
+      (match unwrap_heading heading with
+       | Cmarkit.Block.Heading (h, _) when Cmarkit.Block.Heading.level h > parent_level
+         ->
+         let level : int = Cmarkit.Block.Heading.level h in
+         let path : int list = parent_path @ [ i ] in
+         let title : string =
+           Parse.Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h)
+         in
+         let is_gap : bool = parent_level > 0 && level > parent_level + 1 in
+         (match gap_policy with
+          | Pad when is_gap ->
+            let level : int = parent_level + 1 in
+            let children, rest =
+              parse_children ~gap_policy ~parent_level:level ~parent_path:path blocks
+            in
+            let lines = span (List.map children ~f:(fun c -> c.lines)) in
+            let section =
+              { title = ""
+              ; level
+              ; path
+              ; heading = None
+              ; blocks = []
+              ; children
+              ; implicit = true
+              ; lines
+              }
+            in
+            loop (i + 1) (section :: acc) rest
+          | Reject when is_gap ->
+           raise (Level_gap { heading = title; level; parent_level })
+          | Attach | Pad | Reject ->
+            let body, rest = split_body rest in
+            let children, rest =
+              parse_children ~gap_policy ~parent_level:level ~parent_path:path rest
+            in
+            let lines =
+              span
+                (List.map (heading :: body) ~f:block_lines
+                 @ List.map children ~f:(fun c -> c.lines))
+            in
+            let section =
+              { title
+              ; level
+              ; path
+              ; heading = Some heading
+              ; blocks = body
+              ; children
+              ; implicit = false
+              ; lines
+              }
+            in
+            loop (i + 1) (section :: acc) rest)
+       | _ -> List.rev acc, blocks)
+    | [] -> List.rev acc, []
+  in
+  loop 1 [] blocks
+;;
+
+(** Build the section tree of [doc]. Fails only under {!Reject}, naming the
+    first heading that skips a level. *)
+let of_doc_with ~(gap_policy : gap_policy) (doc : Cmarkit.Doc.t) : t Or_error.t =
+  let blocks : Cmarkit.Block.t list = Parse.Extract.flatten [ Cmarkit.Doc.block doc ] in
+  let body, rest = split_body blocks in
+  match parse_children ~gap_policy ~parent_level:0 ~parent_path:[] rest with
+  | children, _ ->
+    let lines =
+      span (List.map body ~f:block_lines @ List.map children ~f:(fun c -> c.lines))
+    in
+    Ok
+      { title = ""
+      ; level = 0
+      ; path = []
+      ; heading = None
+      ; blocks = body
+      ; children
+      ; implicit = false
+      ; lines
+      }
+  | exception Level_gap { heading; level; parent_level } ->
+    Or_error.error_s
+      [%message
+        "heading skips a level" (heading : string) (level : int) (parent_level : int)]
+;;
+
+(** {!of_doc_with} under {!Attach}, which never fails. *)
+let of_doc (doc : Cmarkit.Doc.t) : t =
+  Or_error.ok_exn (of_doc_with ~gap_policy:Attach doc)
+;;
+
+(** All blocks of the section in document order: heading, body, children. *)
+let rec to_blocks (t : t) : Cmarkit.Block.t list =
+  Option.to_list t.heading @ t.blocks @ List.concat_map t.children ~f:to_blocks
+;;
+
+let to_doc (t : t) : Cmarkit.Doc.t =
+  Cmarkit.Doc.make (Cmarkit.Block.Blocks (to_blocks t, Cmarkit.Meta.none))
+;;
+
+let to_markdown (t : t) : string = Parse.commonmark_of_doc (to_doc t)
+
+(** 1-based line of [t]'s heading, below its attribute line if any. *)
+let heading_line (t : t) : int option =
+  Option.bind t.heading ~f:(fun b -> Option.map (block_lines (unwrap_heading b)) ~f:fst)
+;;
+
+(** The stable id of [t]'s heading, once {!Parse.Section_id.stamp}ed. *)
+let id (t : t) : string option =
+  Option.bind t.heading ~f:(fun b ->
+    match unwrap_heading b with
+    | Cmarkit.Block.Heading (_, meta) -> Cmarkit.Meta.find Parse.Section_id.meta_key meta
+    | _ -> None)
+;;
+
+(** Djot attributes of a heading: from a [{#id .class key=value}] line above
+    it, the id also from a [{#id}] after its text. *)
+type attributes =
+  { id : string option
+  ; classes : string list
+  ; key_values : (string * string) list
+  }
+
+let attributes (t : t) : attributes =
+  let attr : Cmarkit.Attribute.t option =
+    match t.heading with
+    | Some (Cmarkit.Block.Ext_attributes (a, _)) ->
+      Some (Cmarkit.Block.Attributes.attributes a)
+    | _ -> None
+  in
+  let explicit_id : string option =
+    match Option.map t.heading ~f:unwrap_heading with
+    | Some (Cmarkit.Block.Heading (h, _)) ->
+      (match Cmarkit.Block.Heading.id h with
+       | Some (`Id id) -> Some id
+       | Some (`Auto _) | None -> None)
+    | _ -> None
+  in
+  { id = Option.first_some (Option.bind attr ~f:Cmarkit.Attribute.id) explicit_id
+  ; classes = Option.value_map attr ~default:[] ~f:Cmarkit.Attribute.classes
+  ; key_values = Option.value_map attr ~default:[] ~f:Cmarkit.Attribute.key_values
+  }
+;;
+
+(** Depth-first walk of [t] threading an accumulator: [enter] sees each section
+    before its children and [exit] after them. Both default to passing the
+    accumulator through. *)
+let rec fold
+          ?(enter : 'acc -> t -> 'acc = fun acc _ -> acc)
+          ?(exit : 'acc -> t -> 'acc = fun acc _ -> acc)
+          (t : t)
+          ~(init : 'acc)
+  : 'acc
+  =
+  let acc = enter init t in
+  let acc =
+    List.fold t.children ~init:acc ~f:(fun acc c -> fold ~enter ~exit c ~init:acc)
+  in
+  exit acc t
+;;
+
+(** Rebuild [t] bottom-up: [f] gets each section with its children already
+    transformed. Paths are left as they were; see {!renumber}. *)
+let rec map (t : t) ~(f : t -> t) : t =
+  f { t with children = List.map t.children ~f:(map ~f) }
+;;
+
+(** The section and all its descendants, in document order. *)
+let descendants (t : t) : t list =
+  List.rev (fold t ~init:[] ~enter:(fun acc s -> s :: acc))
+;;
+
+(** Recompute paths from positions, after a {!map} added or dropped sections. *)
+let renumber (t : t) : t =
+  let rec go (path : int list) (t : t) : t =
+    let children = List.mapi t.children ~f:(fun i c -> go (path @ [ i + 1 ]) c) in
+    { t with path; children }
+  in
+  go t.path t
+;;
+
+(* [b], a section heading, at [level]. A setext heading past level 2 turns
+   ATX, as setext has no deeper levels. The [{...}] line of an attributed
+   heading stays on it; nothing else is entered. *)
+let relevel_heading ~(level : int) (b : Cmarkit.Block.t) : Cmarkit.Block.t =
+  let mapper =
+    Cmarkit.Mapper.make
+      ~inline_ext_default:(fun _m i -> Some i)
+      ~block_ext_default:(fun _m b -> Some b)
+      ~block:(fun _m (b : Cmarkit.Block.t) ->
+        match b with
+        | Cmarkit.Block.Ext_attributes (a, _) ->
+          (* The mapper maps the wrapped heading and rewraps it. *)
+          (match Cmarkit.Block.Attributes.block a with
+           | Cmarkit.Block.Heading _ -> Cmarkit.Mapper.default
+           | _ -> Cmarkit.Mapper.ret b)
+        | Cmarkit.Block.Heading (h, meta) ->
+          let layout =
+            match Cmarkit.Block.Heading.layout h with
+            | `Setext _ when level > 2 -> None
+            | layout -> Some layout
+          in
+          Cmarkit.Mapper.ret
+            (Cmarkit.Block.Heading
+               ( Cmarkit.Block.Heading.make
+                   ?id:(Cmarkit.Block.Heading.id h)
+                   ?layout
+                   ~level
+                   (Cmarkit.Block.Heading.inline h)
+               , meta ))
+        | _ -> Cmarkit.Mapper.ret b)
+      ()
+  in
+  Option.value (Cmarkit.Mapper.map_block mapper b) ~default:b
+;;
+
+(** [t] with its heading at [level] (1-6) and its descendants' headings moved
+    as much, so a [###] section and its [####] subsections print as [#] and
+    [##]. The root, having no heading, moves its shallowest children to
+    [level]. [Error (s, l)] if that would put the heading of [s], the first
+    such section, at level [l], past 6. *)
+let reroot ~(level : int) (t : t) : (t, t * int) Result.t =
+  let top : int option =
+    if t.level > 0
+    then Some t.level
+    else List.map t.children ~f:(fun c -> c.level) |> List.min_elt ~compare:Int.compare
+  in
+  match top with
+  | None -> Ok t
+  | Some top ->
+    let moved (s : t) : int = s.level + level - top in
+    (match List.find (descendants t) ~f:(fun s -> s.level > 0 && moved s > 6) with
+     | Some s -> Error (s, moved s)
+     | None ->
+       Ok
+         (map t ~f:(fun s ->
+            if s.level = 0
+            then s
+            else (
+              let level = moved s in
+              let heading = Option.map s.heading ~f:(relevel_heading ~level) in
+              { s with level; heading }))))
+;;
+
+(** The section at [path] below [root]. *)
+let rec find_path (root : t) (path : int list) : t option =
+  match path with
+  | [] -> Some root
+  | i :: rest ->
+    Option.bind (List.nth root.children (i - 1)) ~f:(fun c -> find_path c rest)
+;;
+
+let%expect_test "of_doc" =
+  let doc =
+    Parse.of_string
+      {|Intro
+
+# A
+
+## A.1
+
+### A.1.a
+
+## A.2
+
+# B
+
+### B.deep
+|}
+  in
+  let rec show (t : t) =
+    printf
+      "%s%s %s\n"
+      (String.make (2 * List.length t.path) ' ')
+      (path_to_string t.path)
+      t.title;
+    List.iter t.children ~f:show
+  in
+  show (of_doc doc);
+  [%expect
+    {|
+    root
+      1 A
+        1.1 A.1
+          1.1.1 A.1.a
+        1.2 A.2
+      2 B
+        2.1 B.deep
+    |}]
+;;
+
+let%expect_test "title" =
+  let root =
+    of_doc
+      (Parse.of_string
+         "# Using `--field` with *emphasis*\n\n\
+          Setext [heading](x.md)\n------\n")
+  in
+  List.iter (descendants root) ~f:(fun t -> printf "%S\n" t.title);
+  [%expect
+    {|
+    ""
+    "Using --field with emphasis"
+    "Setext heading"
+    |}]
+;;
+
+let%expect_test "gap policies" =
+  let doc = Parse.of_string "# A\n\n### deep\n\ntext\n\n## B\n" in
+  let show (t : t) =
+    List.iter (descendants t) ~f:(fun s ->
+      let lines =
+        Option.value_map s.lines ~default:"-" ~f:(fun (f, l) -> sprintf "%d-%d" f l)
+      in
+      printf
+        "%s %S%s lines %s\n"
+        (path_to_string s.path)
+        s.title
+        (if s.implicit then " (implicit)" else "")
+        lines)
+  in
+  show (Or_error.ok_exn (of_doc_with ~gap_policy:Pad doc));
+  [%expect
+    {|
+    root "" lines 1-7
+    1 "A" lines 1-7
+    1.1 "" (implicit) lines 3-5
+    1.1.1 "deep" lines 3-5
+    1.2 "B" lines 7-7
+    |}];
+  Or_error.iter_error (of_doc_with ~gap_policy:Reject doc) ~f:(fun e ->
+    print_s [%sexp (e : Error.t)]);
+  [%expect {| ("heading skips a level" (heading deep) (level 3) (parent_level 1)) |}]
+;;
+
+let%expect_test "setext headings round-trip" =
+  let doc = Parse.of_string "Title\n=====\n\n## A\n\nSub\n---\n\nbody\n" in
+  let root = of_doc doc in
+  List.iter (descendants root) ~f:(fun s ->
+    printf "%s %S level %d\n" (path_to_string s.path) s.title s.level);
+  [%expect
+    {|
+    root "" level 0
+    1 "Title" level 1
+    1.1 "A" level 2
+    1.2 "Sub" level 2
+    |}];
+  print_string (to_markdown (Option.value_exn (find_path root [ 1; 2 ])));
+  [%expect
+    {|
+    Sub
+    ---
+
+    body
+    |}]
+;;
+
+let%expect_test "fold and map" =
+  let root = of_doc (Parse.of_string "# A\n\n## A.1\n\n## draft\n\n# B\n") in
+  let trace : string list =
+    fold
+      root
+      ~init:[]
+      ~enter:(fun acc s -> ("+" ^ path_to_string s.path) :: acc)
+      ~exit:(fun acc s -> ("-" ^ path_to_string s.path) :: acc)
+  in
+  print_endline (String.concat ~sep:" " (List.rev trace));
+  [%expect {| +root +1 +1.1 -1.1 +1.2 -1.2 -1 +2 -2 -root |}];
+  let pruned =
+    map root ~f:(fun s ->
+      { s with
+        children = List.filter s.children ~f:(fun c -> not (String.equal c.title "draft"))
+      })
+    |> renumber
+  in
+  List.iter (descendants pruned) ~f:(fun s ->
+    printf "%s %s\n" (path_to_string s.path) s.title);
+  [%expect
+    {|
+    root
+    1 A
+    1.1 A.1
+    2 B
+    |}]
+;;
+
+let%expect_test "reroot" =
+  let root = of_doc (Parse.of_string "# A
+
+## B
+
+### C
+
+Text
+
+{#d}
+#### D
+
+## E
+") in
+  let show (t : t) : unit =
+    List.iter (descendants t) ~f:(fun s ->
+      let heading : int option = Option.bind s.heading ~f:heading_level in
+      printf
+        "%s %s level %d heading %s\n"
+        (path_to_string s.path)
+        s.title
+        s.level
+        (Option.value_map heading ~default:"-" ~f:Int.to_string))
+  in
+  let c = Option.value_exn (find_path root [ 1; 1; 1 ]) in
+  (match reroot ~level:1 c with
+   | Ok c ->
+     print_string (to_markdown { c with children = [] });
+     show c
+   | Error _ -> print_endline "too deep");
+  [%expect
+    {|
+    # C
+
+    Text
+    1.1.1 C level 1 heading 1
+    1.1.1.1 D level 2 heading 2
+    |}];
+  (match reroot ~level:3 root with
+   | Ok root -> show root
+   | Error _ -> print_endline "too deep");
+  [%expect
+    {|
+    root  level 0 heading -
+    1 A level 3 heading 3
+    1.1 B level 4 heading 4
+    1.1.1 C level 5 heading 5
+    1.1.1.1 D level 6 heading 6
+    1.2 E level 4 heading 4
+    |}];
+  (match reroot ~level:4 root with
+   | Ok _ -> print_endline "rerooted"
+   | Error (s, level) -> printf "%s at level %d\n" s.title level);
+  [%expect {| D at level 7 |}]
+;;
@@ -0,0 +1,62 @@
+(** Content-free summary of a section tree, for [--summary-json]: titles,
+    paths, ids and sizes, trimmed to a depth. *)
+
+open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
+
+type t =
+  { title : string
+  ; path : string
+  ; id : string option [@yojson.option]
+    (** {!Section.id}; absent for the root and when the note's path is unknown *)
+  ; level : int
+  ; child_count : int (** direct children, including any trimmed by depth *)
+  ; word_count : int (** words in the section body and all descendants *)
+  ; children : t list
+  }
+[@@deriving sexp_of, yojson_of]
+
+(* Whitespace-separated tokens with at least one letter or digit, so list
+   markers and fences don't count. *)
+let count_words (blocks : Cmarkit.Block.t list) : int =
+  let doc : Cmarkit.Doc.t =
+    Cmarkit.Doc.make (Cmarkit.Block.Blocks (blocks, Cmarkit.Meta.none))
+  in
+  let text : string = Parse.commonmark_of_doc doc in
+  String.split_on_chars text ~on:[ ' '; '\n'; '\t'; '\r' ]
+  |> List.count ~f:(String.exists ~f:Char.is_alphanum)
+;;
+
+(** Summarize [section]; [depth] levels of children are kept (all when
+    [None]). *)
+let rec of_section ?(depth : int option) (section : Section.t) : t =
+  let child_depth : int option = Option.map depth ~f:(fun d -> d - 1) in
+  let all_children : t list =
+    List.map section.children ~f:(of_section ?depth:child_depth)
+  in
+  let word_count : int =
+    count_words section.blocks
+    + List.sum (module Int) all_children ~f:(fun c -> c.word_count)
+  in
+  { title = section.title
+  ; path = Section.path_to_string section.path
+  ; id = Section.id section
+  ; level = section.level
+  ; child_count = List.length section.children
+  ; word_count
+  ; children =
+      (match depth with
+       | Some d when d <= 0 -> []
+       | _ -> all_children)
+  }
+;;
+
+let%expect_test "of_section" =
+  let section =
+    Section.of_doc
+      (Parse.of_string "# A\n\none two three\n\n## A.1\n\nfour five\n\n### deep\n\nsix\n")
+  in
+  print_endline (Yojson.Safe.to_string (yojson_of_t (of_section ~depth:1 section)));
+  [%expect
+    {| {"title":"","path":"root","level":0,"child_count":1,"word_count":6,"children":[{"title":"A","path":"1","level":1,"child_count":1,"word_count":6,"children":[]}]} |}]
+;;
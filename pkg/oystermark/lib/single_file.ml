@@ -0,0 +1,227 @@
+(** A whole vault as one HTML file ([oystermark vault
+    --single-file]), for emailing or archiving small vaults.
+
+    Every published note becomes a section of the page, listed in an in-page
+    nav in the sidebar's order. The ids in a note's body take its section's
+    id as prefix, so two notes' [#usage] headings don't clash; links to a
+    note jump to its section, and links to a heading or block in it to that
+    heading or block. The images notes show are inlined as data URIs. The
+    theme's CSS and scripts are inline already, but highlight.js and KaTeX
+    are not: they load from their CDNs when online, and offline code blocks
+    go uncolored and math shows as its TeX source. *)
+
+open Core
+
+type note =
+  { path : string
+  ; title : string
+  ; body : string (** rendered HTML, with site URLs *)
+  }
+
+let base64_alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+
+let base64 (s : string) : string =
+  let n = String.length s in
+  let buf = Buffer.create ((n + 2) / 3 * 4) in
+  let byte (i : int) : int = if i < n then Char.to_int s.[i] else 0 in
+  let rec go (i : int) : unit =
+    if i < n
+    then (
+      let triple = (byte i lsl 16) lor (byte (i + 1) lsl 8) lor byte (i + 2) in
+      List.iteri [ 18; 12; 6; 0 ] ~f:(fun k shift ->
+        (* a group of [r] input bytes gives [r + 1] characters, then padding *)
+        Buffer.add_char
+          buf
+          (if k <= n - i then base64_alphabet.[(triple lsr shift) land 63] else '='));
+      go (i + 3))
+  in
+  go 0;
+  Buffer.contents buf
+;;
+
+(** MIME type of the image at [path], from its extension; [None] for other
+    files, which stay links. *)
+let image_mime (path : string) : string option =
+  match Filename.split_extension path with
+  | _, Some ext ->
+    (match String.lowercase ext with
+     | "png" -> Some "image/png"
+     | "jpg" | "jpeg" -> Some "image/jpeg"
+     | "gif" -> Some "image/gif"
+     | "svg" -> Some "image/svg+xml"
+     | "webp" -> Some "image/webp"
+     | _ -> None)
+  | _, None -> None
+;;
+
+let data_uri ~(mime : string) (content : string) : string =
+  sprintf "data:%s;base64,%s" mime (base64 content)
+;;
+
+(* [html] with the double-quoted values of attribute [name] that [f] maps,
+   given unescaped, replaced by what it maps them to. *)
+let map_attr (name : string) ~(f : string -> string option) (html : string) : string =
+  let needle = " " ^ name ^ "=\"" in
+  let buf = Buffer.create (String.length html) in
+  let rec go (pos : int) : unit =
+    match String.substr_index html ~pattern:needle ~pos with
+    | None -> Buffer.add_substring buf html ~pos ~len:(String.length html - pos)
+    | Some i ->
+      let start = i + String.length needle in
+      (match String.index_from html start '"' with
+       | None -> Buffer.add_substring buf html ~pos ~len:(String.length html - pos)
+       | Some stop ->
+         Buffer.add_substring buf html ~pos ~len:(start - pos);
+         let value = String.sub html ~pos:start ~len:(stop - start) in
+         (match f (Component.Anchors.unescape value) with
+          | Some value -> Buffer.add_string buf (Parse.Frontmatter.escape_html value)
+          | None -> Buffer.add_string buf value);
+         go stop)
+  in
+  go 0;
+  Buffer.contents buf
+;;
+
+(** The page of [notes], titled [title] and laid out by [theme]. [urls] are
+    the URLs the notes' bodies were rendered with; [assets] are the vault's
+    referenced files, read with [read_asset] and inlined when they are
+    images. *)
+let to_html
+      ~(theme : Theme.t)
+      ~(title : string)
+      ~(urls : Component.Url.t)
+      ~(compare_path : string -> string -> int)
+      ~(read_asset : string -> string option)
+      ~(assets : string list)
+      (notes : note list)
+  : string
+  =
+  let ids : string list =
+    Parse.Heading_slug.slugify_all
+      (List.map notes ~f:(fun n ->
+         "note " ^ String.chop_suffix_if_exists n.path ~suffix:".md"))
+  in
+  let id_of_path : string String.Map.t =
+    List.zip_exn (List.map notes ~f:(fun n -> n.path)) ids |> String.Map.of_alist_exn
+  in
+  let id_of_url : string String.Map.t =
+    Map.to_alist id_of_path
+    |> List.map ~f:(fun (path, id) -> Component.Url.note_url_path urls path, id)
+    |> String.Map.of_alist_reduce ~f:(fun first _ -> first)
+  in
+  let images : string String.Map.t =
+    List.filter_map assets ~f:(fun path ->
+      let%bind.Option mime = image_mime path in
+      let%map.Option content = read_asset path in
+      Component.Url.file_url_path urls path, data_uri ~mime content)
+    |> String.Map.of_alist_reduce ~f:(fun first _ -> first)
+  in
+  (* the id in the page of id [frag] in the note of section id [note] *)
+  let scoped ~(note : string) (frag : string) : string = note ^ "--" ^ frag in
+  let rewrite ~(note : string) (html : string) : string =
+    html
+    |> map_attr "id" ~f:(fun id -> Some (scoped ~note id))
+    |> map_attr "href" ~f:(fun href ->
+      match String.lsplit2 href ~on:'#' with
+      | Some ("", frag) -> Some ("#" ^ scoped ~note frag)
+      | Some (url, frag) ->
+        Option.map (Map.find id_of_url url) ~f:(fun id ->
+          if String.is_empty frag then "#" ^ id else "#" ^ scoped ~note:id frag)
+      | None -> Option.map (Map.find id_of_url href) ~f:(fun id -> "#" ^ id))
+    |> map_attr "src" ~f:(Map.find images)
+  in
+  let nav : string =
+    Component.toc_html
+      ~dir_href_f:(fun _ -> None)
+      ~leaf_href_f:(fun path -> "#" ^ Map.find_exn id_of_path path)
+      ~compare_path
+      (List.map notes ~f:(fun n -> n.path))
+    ^ "\n"
+  in
+  let body : string =
+    List.map2_exn notes ids ~f:(fun n id ->
+      sprintf
+        "<section class=\"single-file-note\" id=\"%s\">\n\
+         <h1 class=\"page-title\">%s</h1>\n\
+         %s</section>\n"
+        id
+        (Parse.Frontmatter.escape_html n.title)
+        (rewrite ~note:id n.body))
+    |> String.concat
+  in
+  theme
+    { title
+    ; body
+    ; url_path = "/"
+    ; nav = ""
+    ; menu = ""
+    ; sidebar = nav
+    ; classes = [ "single-file" ]
+    ; head = ""
+    }
+;;
+
+let%expect_test "base64" =
+  List.iter [ "M"; "Ma"; "Man"; "Many" ] ~f:(fun s -> print_endline (base64 s));
+  [%expect
+    {|
+    TQ==
+    TWE=
+    TWFu
+    TWFueQ==
+    |}]
+;;
+
+let%expect_test "to_html" =
+  let notes : note list =
+    [ { path = "a.md"
+      ; title = "a"
+      ; body =
+          {|<h2 id="usage">Usage</h2>|}
+          ^ "\n"
+          ^ {|<p><a href="/dir/b/#usage">b</a> <img src="/img/dot.png" alt="dot"/> |}
+          ^ {|<a href="https://x.org/">x</a> <a href="#usage">up</a></p>|}
+          ^ "\n"
+      }
+    ; { path = "dir/b.md"
+      ; title = "b"
+      ; body = "<h2 id=\"usage\">Usage</h2>\n<p><a href=\"/a/\">a</a></p>\n"
+      }
+    ]
+  in
+  to_html
+    ~theme:Theme.none
+    ~title:"Vault"
+    ~urls:Component.Url.pretty
+    ~compare_path:String.compare
+    ~read_asset:(fun _ -> Some "PNG")
+    ~assets:[ "img/dot.png"; "doc.pdf" ]
+    notes
+  |> print_string;
+  [%expect
+    {|
+    <!DOCTYPE html>
+    <html>
+    <head><meta charset="UTF-8"></head>
+    <body class="single-file">
+    <ul>
+    <li><a href="#note-a">a</a></li>
+    <li>dir
+    <ul>
+    <li><a href="#note-dir-b">b</a></li>
+    </ul></li>
+    </ul>
+    <section class="single-file-note" id="note-a">
+    <h1 class="page-title">a</h1>
+    <h2 id="note-a--usage">Usage</h2>
+    <p><a href="#note-dir-b--usage">b</a> <img src="data:image/png;base64,UE5H" alt="dot"/> <a href="https://x.org/">x</a> <a href="#note-a--usage">up</a></p>
+    </section>
+    <section class="single-file-note" id="note-dir-b">
+    <h1 class="page-title">b</h1>
+    <h2 id="note-dir-b--usage">Usage</h2>
+    <p><a href="#note-a">a</a></p>
+    </section>
+    </body>
+    </html>
+    |}]
+;;
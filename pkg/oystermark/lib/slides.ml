@@ -0,0 +1,389 @@
+(** A note as a slide deck, written by [oystermark export slides]: a reveal.js
+    page, or Marp markdown for [marp] to build its HTML deck from.
+
+    The note splits into slides at its top-level thematic breaks (see
+    {!Parse.Multi_doc}), or before each [##] heading. A [> [!notes]] callout
+    on a slide holds its speaker notes, shown in reveal.js's speaker view and
+    as Marp's presenter notes rather than on the slide. Frontmatter and
+    top-level thematic breaks are dropped.
+
+    The reveal.js page takes its colors and its callout styles from the site
+    theme; reveal.js's highlight plugin colors the code. The Marp deck takes
+    the theme's background and text colors; Marp knows no wikilinks, so as
+    in {!Mdbook} embedded images become Markdown images and links to vault
+    files Markdown links, relative to the note (write the deck next to it),
+    and links to notes their text. *)
+
+open Core
+
+type split =
+  | Rule (** at top-level thematic breaks *)
+  | H2 (** before each level-2 heading *)
+
+let split_of_string : string -> split option = function
+  | "rule" -> Some Rule
+  | "h2" -> Some H2
+  | _ -> None
+;;
+
+type format =
+  | Reveal
+  | Marp
+
+let format_of_string : string -> format option = function
+  | "reveal" -> Some Reveal
+  | "marp" -> Some Marp
+  | _ -> None
+;;
+
+(** Kind of the callouts holding speaker notes. *)
+let notes_kind = "notes"
+
+type slide =
+  { body : Cmarkit.Doc.t
+  ; notes : Cmarkit.Doc.t option (** the bodies of the slide's notes callouts *)
+  }
+
+let top_blocks (doc : Cmarkit.Doc.t) : Cmarkit.Block.t list =
+  match Cmarkit.Doc.block doc with
+  | Cmarkit.Block.Blocks (blocks, _) -> blocks
+  | b -> [ b ]
+;;
+
+(* The body of [b] if it is a notes callout. *)
+let notes_of (b : Cmarkit.Block.t) : Cmarkit.Block.t option =
+  match b with
+  | Cmarkit.Block.Block_quote (bq, meta) ->
+    (match Cmarkit.Block.Callout.find meta with
+     | Some c
+       when String.equal
+              (Parse.Gfm_alert.callout_kind (Cmarkit.Block.Callout.kind c))
+              notes_kind ->
+       Some (Cmarkit.Block.Callout.strip_header (Cmarkit.Block.Block_quote.block bq))
+     | _ -> None)
+  | _ -> None
+;;
+
+(** The slides of [doc], empty ones left out. *)
+let of_doc ~(split : split) (doc : Cmarkit.Doc.t) : slide list =
+  let groups : Cmarkit.Block.t list list =
+    match split with
+    | Rule -> List.map (Parse.Multi_doc.split doc) ~f:top_blocks
+    | H2 ->
+      List.group (top_blocks doc) ~break:(fun _ b ->
+        match b with
+        | Cmarkit.Block.Heading (h, _) -> Cmarkit.Block.Heading.level h = 2
+        | _ -> false)
+  in
+  let make (blocks : Cmarkit.Block.t list) : Cmarkit.Doc.t =
+    Cmarkit.Doc.make
+      ~defs:(Cmarkit.Doc.defs doc)
+      (Cmarkit.Block.Blocks (blocks, Cmarkit.Meta.none))
+  in
+  List.filter_map groups ~f:(fun blocks ->
+    let blocks : Cmarkit.Block.t list =
+      List.filter blocks ~f:(function
+        | Parse.Frontmatter.Frontmatter _
+        | Cmarkit.Block.Blank_line _
+        | Cmarkit.Block.Thematic_break _ -> false
+        | _ -> true)
+    in
+    let notes, body =
+      List.partition_map blocks ~f:(fun b ->
+        match notes_of b with
+        | Some n -> First n
+        | None -> Second b)
+    in
+    match body, notes with
+    | [], [] -> None
+    | body, [] -> Some { body = make body; notes = None }
+    | body, notes -> Some { body = make body; notes = Some (make notes) })
+;;
+
+let reveal_cdn = "https://cdn.jsdelivr.net/npm/reveal.js@5.1.0"
+
+(* Reveal.js in the site theme's colors; the theme's own layout is for
+   pages, not slides. *)
+let reveal_theme_css =
+  {|.reveal-viewport {
+    background: var(--bg);
+}
+.reveal {
+    color: var(--fg);
+    font-size: 32px;
+}
+.reveal a {
+    color: var(--accent);
+}
+.reveal pre {
+    width: 100%;
+    font-size: 0.6em;
+}
+|}
+;;
+
+(** The reveal.js page of [slides], titled [title], in [colors] (reveal.js's
+    white theme without). [urls] are as for {!Component.Html.of_doc}. *)
+let to_reveal
+      ?(colors : Theme.colors option)
+      ?(urls : Component.Url.t option)
+      ~(title : string)
+      (slides : slide list)
+  : string
+  =
+  let html (doc : Cmarkit.Doc.t) : string =
+    Component.Html.of_doc ~backend_blocks:true ~safe:false ?urls doc
+  in
+  let sections : string =
+    List.map slides ~f:(fun s ->
+      let notes : string =
+        Option.value_map s.notes ~default:"" ~f:(fun n ->
+          sprintf "<aside class=\"notes\">\n%s</aside>\n" (html n))
+      in
+      sprintf "<section>\n%s%s</section>\n" (html s.body) notes)
+    |> String.concat
+  in
+  let style : string =
+    match colors with
+    | Some c ->
+      sprintf
+        "<style>\n%s\n%s\n%s</style>\n"
+        (Theme.root_css c)
+        (Callout_css.expand Theme.callout_css)
+        reveal_theme_css
+    | None ->
+      sprintf "<link rel=\"stylesheet\" href=\"%s/dist/theme/white.css\">\n" reveal_cdn
+  in
+  let title = Parse.Frontmatter.escape_html title in
+  {%string|<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<title>%{title}</title>
+<link rel="stylesheet" href="%{reveal_cdn}/dist/reveal.css">
+<link rel="stylesheet" href="https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/styles/github-dark.min.css">
+%{style}</head>
+<body class="slides">
+<div class="reveal"><div class="slides">
+%{sections}</div></div>
+<script src="%{reveal_cdn}/dist/reveal.js"></script>
+<script src="%{reveal_cdn}/plugin/notes/notes.js"></script>
+<script src="%{reveal_cdn}/plugin/highlight/highlight.js"></script>
+<script>Reveal.initialize({ hash: true, plugins: [RevealNotes, RevealHighlight] });</script>
+</body>
+</html>
+|}
+;;
+
+(* Wikilinks of note [path] as Marp reads them; see the top of this module. *)
+let wikilink_renderer ~(path : string) : Cmarkit_renderer.t =
+  let inline c : Cmarkit.Inline.t -> bool = function
+    | Cmarkit.Inline.Ext_wikilink (w, meta) ->
+      let display =
+        Option.value
+          (Cmarkit.Inline.Wikilink.display w)
+          ~default:(Vault.Link_convert.wikilink_default_display w)
+      in
+      (match Cmarkit.Meta.find Vault.Resolve.resolved_key meta with
+       | Some (Vault.Resolve.File { path = file }) ->
+         let image =
+           Cmarkit.Inline.Wikilink.embed w
+           &&
+           match Component.Html.media_type_of_href file with
+           | `Image -> true
+           | _ -> false
+         in
+         Cmarkit_renderer.Context.string
+           c
+           (sprintf
+              "%s[%s](%s)"
+              (if image then "!" else "")
+              (Vault.Link_convert.escape_link_text display)
+              (Vault.Link_rewrite.percent_encode
+                 (Vault.Resolve.relative_link ~curr_file:path file)))
+       | _ -> Cmarkit_renderer.Context.string c display);
+      true
+    | _ -> false
+  in
+  Cmarkit_renderer.make ~inline ()
+;;
+
+(** The Marp markdown of [slides], from note [path], with [colors]'
+    background and text. *)
+let to_marp ?(colors : Theme.colors option) ~(path : string) (slides : slide list)
+  : string
+  =
+  let renderer =
+    List.fold
+      ~f:Cmarkit_renderer.compose
+      ~init:(Cmarkit_commonmark.renderer ())
+      [ Cmarkit_renderer.make ~block:Parse.Frontmatter.block_commonmark_renderer ()
+      ; wikilink_renderer ~path
+      ]
+  in
+  let markdown (doc : Cmarkit.Doc.t) : string =
+    String.strip (Cmarkit_renderer.doc_to_string renderer doc)
+  in
+  let directives : string list =
+    "marp: true"
+    :: Option.value_map colors ~default:[] ~f:(fun (c : Theme.colors) ->
+      [ sprintf "backgroundColor: \"%s\"" c.bg; sprintf "color: \"%s\"" c.fg ])
+  in
+  let slide (s : slide) : string =
+    let notes : string list =
+      Option.value_map s.notes ~default:[] ~f:(fun n ->
+        (* an HTML comment ends at the first [-->] *)
+        let text = String.substr_replace_all (markdown n) ~pattern:"-->" ~with_:"- ->" in
+        [ "<!--\n" ^ text ^ "\n-->" ])
+    in
+    String.concat ~sep:"\n\n" (markdown s.body :: notes)
+  in
+  sprintf
+    "---\n%s\n---\n\n%s\n"
+    (String.concat ~sep:"\n" directives)
+    (String.concat ~sep:"\n\n---\n\n" (List.map slides ~f:slide))
+;;
+
+let%expect_test "of_doc and to_marp" =
+  let doc =
+    Parse.of_string
+      "---\n\
+       title: Deck\n\
+       ---\n\
+       # Deck\n\n\
+       ## One\n\n\
+       Hello.\n\n\
+       > [!notes]\n\
+       > Say hi.\n\n\
+       ## Two\n\n\
+       ```ocaml\n\
+       let x = 1\n\
+       ```\n\n\
+       ---\n\n\
+       ## Three\n"
+  in
+  List.iter [ Rule; H2 ] ~f:(fun split ->
+    printf "%d slides\n" (List.length (of_doc ~split doc)));
+  [%expect
+    {|
+    2 slides
+    4 slides
+    |}];
+  print_string (to_marp ~path:"Deck.md" (of_doc ~split:H2 doc));
+  [%expect
+    {|
+    ---
+    marp: true
+    ---
+
+    # Deck
+
+    ---
+
+    ## One
+
+    Hello.
+
+    <!--
+    Say hi.
+    -->
+
+    ---
+
+    ## Two
+
+    ```ocaml
+    let x = 1
+    ```
+
+    ---
+
+    ## Three
+    |}]
+;;
+
+let%expect_test "to_marp converts wikilinks" =
+  let entries =
+    [ "other.md"; "refs/paper.pdf"; "talks/deck.md"; "talks/img/chart.png" ]
+  in
+  let index : Vault.Index.t =
+    { files =
+        List.map entries ~f:(fun rel_path : Vault.Index.file_entry ->
+          { rel_path; headings = []; blocks = []; attrs = [] })
+    ; dirs = []
+    }
+  in
+  let doc =
+    Parse.of_string
+      "# Results\n\n![[chart.png]]\n\nSee [[paper.pdf|the paper]] and [[other]].\n"
+  in
+  let doc =
+    List.Assoc.find_exn
+      (Vault.Resolve.resolve_docs [ "talks/deck.md", doc ] index)
+      ~equal:String.equal
+      "talks/deck.md"
+  in
+  print_string (to_marp ~path:"talks/deck.md" (of_doc ~split:Rule doc));
+  [%expect
+    {|
+    ---
+    marp: true
+    ---
+
+    # Results
+
+    ![chart.png](img/chart.png)
+
+    See [the paper](../refs/paper.pdf) and other.
+    |}]
+;;
+
+let%expect_test "to_reveal" =
+  let doc = Parse.of_string "# Hi\n\n> [!notes]\n> Slowly.\n\n---\n\nBye.\n" in
+  print_string (to_reveal ~title:"A & B" (of_doc ~split:Rule doc));
+  [%expect
+    {|
+    <!DOCTYPE html>
+    <html>
+    <head>
+    <meta charset="UTF-8">
+    <title>A &amp; B</title>
+    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/reveal.js@5.1.0/dist/reveal.css">
+    <link rel="stylesheet" href="https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/styles/github-dark.min.css">
+    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/reveal.js@5.1.0/dist/theme/white.css">
+    </head>
+    <body class="slides">
+    <div class="reveal"><div class="slides">
+    <section>
+    <h1 id="hi">Hi</h1>
+    <aside class="notes">
+    <p>Slowly.</p>
+    </aside>
+    </section>
+    <section>
+    <p>Bye.</p>
+    </section>
+    </div></div>
+    <script src="https://cdn.jsdelivr.net/npm/reveal.js@5.1.0/dist/reveal.js"></script>
+    <script src="https://cdn.jsdelivr.net/npm/reveal.js@5.1.0/plugin/notes/notes.js"></script>
+    <script src="https://cdn.jsdelivr.net/npm/reveal.js@5.1.0/plugin/highlight/highlight.js"></script>
+    <script>Reveal.initialize({ hash: true, plugins: [RevealNotes, RevealHighlight] });</script>
+    </body>
+    </html>
+    |}];
+  (* in the theme's colors, the page takes its [:root] and callout rules only *)
+  let page =
+    to_reveal ~colors:Theme.tokyonight_colors ~title:"Deck" (of_doc ~split:Rule doc)
+  in
+  List.iter
+    [ "--bg: #1a1b26;"; ".callout {"; "mask-image"; ".sidebar {"; "nav.breadcrumb {" ]
+    ~f:(fun css -> printf "%s %b\n" css (String.is_substring page ~substring:css));
+  [%expect
+    {|
+    --bg: #1a1b26; true
+    .callout { true
+    mask-image true
+    .sidebar { false
+    nav.breadcrumb { false
+    |}]
+;;
@@ -0,0 +1,336 @@
+(** Social cards (see {!Config.Social_cards}): a 1200×630 SVG per note with
+    its title and the site's on the theme's colors, converted to PNG when the
+    config names a command for it, and the Open Graph and Twitter meta tags
+    that point link previews at it. *)
+
+open Core
+
+let width = 1200
+let height = 630
+
+(* Longest title line, in columns at the title's font size: a code point
+   takes one, or two when it is wide (CJK and full-width forms). *)
+let line_chars = 28
+let max_lines = 3
+
+let escape = Parse.Frontmatter.escape_html
+
+(* [s]'s code points, each as its UTF-8 bytes and its width in columns; a
+   byte that isn't UTF-8 is a code point of its own. *)
+let code_points (s : string) : (string * int) list =
+  let is_wide (u : Uchar.t) : bool =
+    let c = Stdlib.Uchar.to_int u in
+    (c >= 0x1100 && c <= 0x115F)
+    || (c >= 0x2E80 && c <= 0xA4CF)
+    || (c >= 0xAC00 && c <= 0xD7A3)
+    || (c >= 0xF900 && c <= 0xFAFF)
+    || (c >= 0xFE30 && c <= 0xFE4F)
+    || (c >= 0xFF00 && c <= 0xFF60)
+    || (c >= 0xFFE0 && c <= 0xFFE6)
+    || (c >= 0x20000 && c <= 0x3FFFD)
+  in
+  let rec go (i : int) acc =
+    if i >= String.length s
+    then List.rev acc
+    else (
+      let d = Stdlib.String.get_utf_8_uchar s i in
+      let len = Stdlib.Uchar.utf_decode_length d in
+      let width = if is_wide (Stdlib.Uchar.utf_decode_uchar d) then 2 else 1 in
+      go (i + len) ((String.sub s ~pos:i ~len, width) :: acc))
+  in
+  go 0 []
+;;
+
+(** [title] broken into at most [max_lines] lines, the last one cut with an
+    ellipsis when it doesn't all fit. Lines break between words, and also
+    between wide characters, as CJK text has no spaces to break at. *)
+let title_lines (title : string) : string list =
+  (* pieces of the title that stay on one line, each with the glue before
+     it: a space between words, nothing next to a wide character *)
+  let pieces : (string * string * int) list =
+    (* [word] holds the code points of the piece being read, reversed, and
+       [glue] goes before it *)
+    let rec go cps ~glue ~word ~width acc =
+      let flush acc =
+        if width = 0 then acc else (glue, String.concat (List.rev word), width) :: acc
+      in
+      match cps with
+      | [] -> List.rev (flush acc)
+      | ((" " | "\t" | "\n"), _) :: rest ->
+        go rest ~glue:" " ~word:[] ~width:0 (flush acc)
+      | (cp, 2) :: rest ->
+        let piece = (if width = 0 then glue else ""), cp, 2 in
+        go rest ~glue:"" ~word:[] ~width:0 (piece :: flush acc)
+      | (cp, w) :: rest -> go rest ~glue ~word:(cp :: word) ~width:(width + w) acc
+    in
+    go (code_points title) ~glue:"" ~word:[] ~width:0 []
+  in
+  let lines : (string * int) list =
+    List.fold pieces ~init:[] ~f:(fun acc (glue, text, width) ->
+      match acc with
+      | (cur, cur_width) :: rest
+        when cur_width + String.length glue + width <= line_chars ->
+        (cur ^ glue ^ text, cur_width + String.length glue + width) :: rest
+      | _ -> (text, width) :: acc)
+    |> List.rev
+  in
+  if List.length lines <= max_lines
+  then List.map lines ~f:fst
+  else (
+    let kept = List.take lines max_lines in
+    let last, last_width = List.last_exn kept in
+    let last =
+      if last_width + 1 <= line_chars
+      then last
+      else
+        List.folding_map (code_points last) ~init:0 ~f:(fun total (cp, w) ->
+          total + w, (cp, total + w))
+        |> List.take_while ~f:(fun (_, total) -> total <= line_chars - 1)
+        |> List.map ~f:fst
+        |> String.concat
+    in
+    List.map (List.take kept (max_lines - 1)) ~f:fst @ [ last ^ "…" ])
+;;
+
+(** The card of a page titled [title] on site [site_title]. *)
+let svg ~(colors : Theme.colors) ~(site_title : string) ~(title : string) : string =
+  let lines = title_lines title in
+  let line_height = 84 in
+  (* the title block is centred, whatever its line count *)
+  let top = (height / 2) - (List.length lines * line_height / 2) in
+  let tspans =
+    List.mapi lines ~f:(fun i line ->
+      sprintf
+        {|<tspan x="96" y="%d">%s</tspan>|}
+        (top + ((i + 1) * line_height))
+        (escape line))
+    |> String.concat
+  in
+  String.concat
+    ~sep:"\n"
+    [ sprintf
+        {|<svg xmlns="http://www.w3.org/2000/svg" width="%d" height="%d" viewBox="0 0 %d %d">|}
+        width
+        height
+        width
+        height
+    ; sprintf {|<rect width="%d" height="%d" fill="%s"/>|} width height colors.bg
+    ; sprintf {|<rect width="24" height="%d" fill="%s"/>|} height colors.accent
+    ; sprintf
+        {|<text font-family="system-ui, sans-serif" font-size="68" font-weight="700" fill="%s">%s</text>|}
+        colors.fg
+        tspans
+    ; sprintf
+        {|<text x="96" y="%d" font-family="system-ui, sans-serif" font-size="36" fill="%s">%s</text>|}
+        (height - 72)
+        colors.fg_dim
+        (escape site_title)
+    ; "</svg>"
+    ; ""
+    ]
+;;
+
+(** Output file of the card of note [rel_path], mirroring its page under
+    [_cards/], with extension [ext]. *)
+let output_path ~(ext : string) (urls : Component.Url.t) (rel_path : string) : string =
+  "_cards/"
+  ^ String.chop_suffix_if_exists
+      (Component.Url.note_output_path urls rel_path)
+      ~suffix:".html"
+  ^ "."
+  ^ ext
+;;
+
+(** The card of a page titled [title] as written: PNG through the config's
+    [png_command] when set, SVG otherwise or when the command fails (a
+    warning), with its file extension. *)
+let render
+      ~(on_warning : string -> unit)
+      ~(config : Config.Social_cards.t)
+      ~(colors : Theme.colors)
+      ~(site_title : string)
+      ~(title : string)
+  : string * string
+  =
+  let svg = svg ~colors ~site_title ~title in
+  match config.png_command with
+  | "" -> "svg", svg
+  | cmd ->
+    (match Code_executor.Process.run cmd ~input:svg with
+     | Ok png when not (String.is_empty png) -> "png", png
+     | Ok _ ->
+       on_warning (sprintf "social card %S: %s printed nothing" title cmd);
+       "svg", svg
+     | Error e ->
+       on_warning (sprintf "social card %S: %s" title (Error.to_string_hum e));
+       "svg", svg)
+;;
+
+(* Absolute URL of site file [path], percent-encoded as hosts expect. *)
+let site_url ~(base_url : string) (path : string) : string =
+  let encode_segment (s : string) : string =
+    String.concat_map s ~f:(fun c ->
+      match c with
+      | 'A' .. 'Z' | 'a' .. 'z' | '0' .. '9' | '-' | '_' | '.' | '~' -> String.of_char c
+      | c -> sprintf "%%%02X" (Char.to_int c))
+  in
+  String.rstrip base_url ~drop:(Char.equal '/')
+  ^ "/"
+  ^ (String.split (String.lstrip path ~drop:(Char.equal '/')) ~on:'/'
+     |> List.map ~f:encode_segment
+     |> String.concat ~sep:"/")
+;;
+
+(** The meta tags of a page titled [title] previewed by [image]. *)
+let meta_tags ~(site_title : string) ~(title : string) ~(image : string) : string =
+  let tag attr name content =
+    sprintf {|<meta %s="%s" content="%s">|} attr name (escape content) ^ "\n"
+  in
+  String.concat
+    [ tag "property" "og:title" title
+    ; tag "property" "og:type" "article"
+    ; (if String.is_empty site_title
+       then ""
+       else tag "property" "og:site_name" site_title)
+    ; tag "property" "og:image" image
+    ; tag "name" "twitter:card" "summary_large_image"
+    ]
+;;
+
+(** For note [rel_path], parsed as [doc] and titled [title]: its page's meta
+    tags and the card to write, as output path and content (see {!render}).
+    A note with an [image] frontmatter field (a URL, or a path from the vault
+    root) gets no card; the tags point at its image. *)
+let of_note
+      ?(on_warning : string -> unit = ignore)
+      ~(config : Config.Social_cards.t)
+      ~(colors : Theme.colors)
+      ~(urls : Component.Url.t)
+      ~(site_title : string)
+      ~(title : string)
+      (rel_path : string)
+      (doc : Cmarkit.Doc.t)
+  : string * (string * string) option
+  =
+  let image, card =
+    match Parse.Frontmatter.field doc "image" with
+    | Some (Parse.Value.String image) when Parse.Link_kind.is_external image ->
+      image, None
+    | Some (Parse.Value.String image) -> site_url ~base_url:config.base_url image, None
+    | _ ->
+      let ext, card = render ~on_warning ~config ~colors ~site_title ~title in
+      let path = output_path ~ext urls rel_path in
+      site_url ~base_url:config.base_url path, Some (path, card)
+  in
+  meta_tags ~site_title ~title ~image, card
+;;
+
+let%expect_test "of_note" =
+  let config : Config.Social_cards.t =
+    { enabled = true; base_url = "https://notes.example.com/"; png_command = "" }
+  in
+  let show rel_path src =
+    let head, card =
+      of_note
+        ~config
+        ~colors:Theme.tokyonight_colors
+        ~urls:Component.Url.pretty
+        ~site_title:"Oyster & co"
+        ~title:(Component.title_of_path rel_path)
+        rel_path
+        (Parse.of_string src)
+    in
+    print_string head;
+    Option.iter card ~f:(fun (path, svg) -> print_string (path ^ "\n" ^ svg))
+  in
+  show "Field notes/Tides.md" "# Tides\n";
+  [%expect
+    {|
+    <meta property="og:title" content="Tides">
+    <meta property="og:type" content="article">
+    <meta property="og:site_name" content="Oyster &amp; co">
+    <meta property="og:image" content="https://notes.example.com/_cards/Field%20notes/Tides/index.svg">
+    <meta name="twitter:card" content="summary_large_image">
+    _cards/Field notes/Tides/index.svg
+    <svg xmlns="http://www.w3.org/2000/svg" width="1200" height="630" viewBox="0 0 1200 630">
+    <rect width="1200" height="630" fill="#1a1b26"/>
+    <rect width="24" height="630" fill="#7aa2f7"/>
+    <text font-family="system-ui, sans-serif" font-size="68" font-weight="700" fill="#c0caf5"><tspan x="96" y="357">Tides</tspan></text>
+    <text x="96" y="558" font-family="system-ui, sans-serif" font-size="36" fill="#565f89">Oyster &amp; co</text>
+    </svg>
+    |}];
+  show "Reef.md" "---\nimage: img/reef.png\n---\n";
+  [%expect
+    {|
+    <meta property="og:title" content="Reef">
+    <meta property="og:type" content="article">
+    <meta property="og:site_name" content="Oyster &amp; co">
+    <meta property="og:image" content="https://notes.example.com/img/reef.png">
+    <meta name="twitter:card" content="summary_large_image">
+    |}];
+  List.iter
+    [ "Short title"
+    ; "A much longer title that has to be broken over several lines before it \
+       fits on the card at all"
+    ; "東京の海と牡蠣の養殖についての長い記録をまとめたノートです。\
+       続きはまた今度書きますので、しばらくお待ちください"
+    ; "OCaml로 만든 정적 사이트 생성기"
+    ]
+    ~f:(fun title -> List.iter (title_lines title) ~f:(printf "| %s\n"));
+  [%expect
+    {|
+    | Short title
+    | A much longer title that has
+    | to be broken over several
+    | lines before it fits on the…
+    | 東京の海と牡蠣の養殖について
+    | の長い記録をまとめたノートで
+    | す。続きはまた今度書きます…
+    | OCaml로 만든 정적 사이트 생
+    | 성기
+    |}]
+;;
+
+let%expect_test "render through png_command" =
+  let config : Config.Social_cards.t =
+    { enabled = true
+    ; base_url = "https://notes.example.com"
+    ; png_command = "cat > /dev/null; printf PNG"
+    }
+  in
+  let show (config : Config.Social_cards.t) =
+    let head, card =
+      of_note
+        ~on_warning:print_endline
+        ~config
+        ~colors:Theme.tokyonight_colors
+        ~urls:Component.Url.pretty
+        ~site_title:""
+        ~title:"Tides"
+        "Tides.md"
+        (Parse.of_string "# Tides\n")
+    in
+    print_string head;
+    Option.iter card ~f:(fun (path, content) ->
+      printf "%s: %s\n" path (String.prefix content 4))
+  in
+  show config;
+  [%expect
+    {|
+    <meta property="og:title" content="Tides">
+    <meta property="og:type" content="article">
+    <meta property="og:image" content="https://notes.example.com/_cards/Tides/index.png">
+    <meta name="twitter:card" content="summary_large_image">
+    _cards/Tides/index.png: PNG
+    |}];
+  show { config with png_command = "cat > /dev/null; exit 1" };
+  [%expect
+    {|
+    social card "Tides": cat > /dev/null; exit 1 failed
+    <meta property="og:title" content="Tides">
+    <meta property="og:type" content="article">
+    <meta property="og:image" content="https://notes.example.com/_cards/Tides/index.svg">
+    <meta name="twitter:card" content="summary_large_image">
+    _cards/Tides/index.svg: <svg
+    |}]
+;;
@@ -0,0 +1,301 @@
+(** The vault index as a SQLite database, for ad-hoc SQL and other tools
+    ([oystermark index VAULT --sqlite vault.db]). {!to_sql} gives a script
+    that (re)creates the tables below and fills them; {!write} runs it with the
+    [sqlite3] command. {!Embedding} keeps its vectors in the same database.
+
+    {v
+    notes       (path, title, words)
+    sections    (note, path, title, level, first_line, last_line)
+    frontmatter (note, key, value)
+    tags        (note, tag)
+    fields      (note, key, value, line)
+    links       (note, section, text, dest, kind, style, target)
+    blocks      (note, id, line)
+    notes_fts   FTS5 over (path, title, body)
+    v}
+
+    Column meanings are in {!schema}'s comments. *)
+
+open Core
+
+let schema : string =
+  {|-- One row per markdown note.
+CREATE TABLE notes (
+  path  TEXT PRIMARY KEY,  -- vault-relative, e.g. 'notes/idea.md'
+  title TEXT NOT NULL,     -- frontmatter title, else the file name
+  words INTEGER NOT NULL   -- words in the body
+);
+
+-- The heading hierarchy of each note; the note itself is path 'root'.
+CREATE TABLE sections (
+  note       TEXT NOT NULL REFERENCES notes (path),
+  path       TEXT NOT NULL,     -- '1.2.3' as `oystermark query --path` takes
+  title      TEXT NOT NULL,     -- plain heading text; '' for the root
+  level      INTEGER NOT NULL,  -- heading level; 0 for the root
+  first_line INTEGER,           -- 1-based lines the section spans
+  last_line  INTEGER,
+  PRIMARY KEY (note, path)
+);
+
+-- Top-level frontmatter fields; of a repeated key, the first.
+CREATE TABLE frontmatter (
+  note  TEXT NOT NULL REFERENCES notes (path),
+  key   TEXT NOT NULL,
+  value TEXT NOT NULL,  -- JSON; query with json_extract
+  PRIMARY KEY (note, key)
+);
+
+-- Tags from the frontmatter and inline #tags, without '#', once per note.
+CREATE TABLE tags (
+  note TEXT NOT NULL REFERENCES notes (path),
+  tag  TEXT NOT NULL,
+  PRIMARY KEY (note, tag)
+);
+
+-- Inline fields (key:: value), as `oystermark query --meta` reads them.
+CREATE TABLE fields (
+  note  TEXT NOT NULL REFERENCES notes (path),
+  key   TEXT NOT NULL,
+  value TEXT NOT NULL,  -- as written
+  line  INTEGER         -- 1-based
+);
+
+-- Links, as `oystermark query -- --links` reports them.
+CREATE TABLE links (
+  note    TEXT NOT NULL REFERENCES notes (path),
+  section TEXT NOT NULL,  -- path of the section holding the link
+  text    TEXT NOT NULL,
+  dest    TEXT NOT NULL,  -- as written, e.g. 'Setup#Install' or a URL
+  kind    TEXT NOT NULL,  -- 'internal', 'external', ...
+  style   TEXT NOT NULL,  -- 'wikilink', 'inline', 'reference', 'autolink', 'footnote'
+  target  TEXT            -- resolved vault path; NULL if external or unresolved
+);
+
+-- Block ids (^id).
+CREATE TABLE blocks (
+  note TEXT NOT NULL REFERENCES notes (path),
+  id   TEXT NOT NULL,
+  line INTEGER  -- 1-based
+);
+
+-- Full-text search over notes: SELECT path FROM notes_fts WHERE notes_fts MATCH '...'.
+CREATE VIRTUAL TABLE notes_fts USING fts5 (path UNINDEXED, title, body);
+|}
+;;
+
+let tables : string list =
+  [ "notes"; "sections"; "frontmatter"; "tags"; "fields"; "links"; "blocks"; "notes_fts" ]
+;;
+
+(* A SQL string literal. *)
+let quote (s : string) : string =
+  "'" ^ String.substr_replace_all s ~pattern:"'" ~with_:"''" ^ "'"
+;;
+
+type cell =
+  | S of string
+  | I of int
+  | Null
+
+let sql_of_cell : cell -> string = function
+  | S s -> quote s
+  | I i -> Int.to_string i
+  | Null -> "NULL"
+;;
+
+let insert (table : string) (row : cell list) : string =
+  sprintf
+    "INSERT INTO %s VALUES (%s);\n"
+    table
+    (String.concat ~sep:", " (List.map row ~f:sql_of_cell))
+;;
+
+let line_cells (lines : (int * int) option) : cell list =
+  match lines with
+  | Some (first, last) -> [ I first; I last ]
+  | None -> [ Null; Null ]
+;;
+
+(* The first of [xs] with each [key], in order. Lookups see a repeated
+   frontmatter key's first value, so that is the one kept. *)
+let unique (xs : 'a list) ~(key : 'a -> string) : 'a list =
+  List.fold xs ~init:(String.Set.empty, []) ~f:(fun (seen, acc) x ->
+    if Set.mem seen (key x) then seen, acc else Set.add seen (key x), x :: acc)
+  |> snd
+  |> List.rev
+;;
+
+(* Rows of the note at [path] with [source], parsed and resolved as [doc],
+   less encrypted blocks (see {!Parse.Encrypted}). *)
+let note_rows ~(index : Vault.Index.t) (path : string) (source : string) doc : string list
+  =
+  let source = Parse.Encrypted.redact ~doc source in
+  let doc = Parse.Encrypted.strip doc in
+  let fields : (string * Yaml.value) list =
+    match Parse.Frontmatter.of_doc doc with
+    | Some (`O fields) -> fields
+    | _ -> []
+  in
+  let title =
+    match List.Assoc.find fields ~equal:String.equal "title" with
+    | Some (`String t) -> t
+    | _ -> Filename.chop_suffix_if_exists (Filename.basename path) ~suffix:".md"
+  in
+  let root = Query.Section.of_doc doc in
+  let words = (Query.Summary.of_section root).word_count in
+  let sections =
+    List.map (Query.Section.descendants root) ~f:(fun (s : Query.Section.t) ->
+      insert
+        "sections"
+        ([ S path; S (Query.Section.path_to_string s.path); S s.title; I s.level ]
+         @ line_cells s.lines))
+  in
+  let frontmatter =
+    List.map (unique fields ~key:fst) ~f:(fun (key, v) ->
+      insert
+        "frontmatter"
+        [ S path; S key; S (Yojson.Safe.to_string (Query.Expr.json_of_yaml v)) ])
+  in
+  let tags =
+    unique (Query.Search.note_tags fields @ Parse.Inline_tag.of_doc doc) ~key:Fn.id
+    |> List.map ~f:(fun tag -> insert "tags" [ S path; S tag ])
+  in
+  let inline_fields =
+    List.map (Parse.Inline_field.of_doc doc) ~f:(fun (f : Parse.Inline_field.t) ->
+      let line = Option.value_map f.line ~default:Null ~f:(fun l -> I l) in
+      insert "fields" [ S path; S f.key; S f.value; line ])
+  in
+  let links =
+    List.map (Query.Links.of_section root) ~f:(fun (l : Query.Links.t) ->
+      let target =
+        match Vault.Link_ref.of_cmark_dest l.dest with
+        | Some link_ref when String.equal l.kind "internal" ->
+          (match
+             Vault.Resolve.resolve
+               ~markdown:(not (String.equal l.style "wikilink"))
+               link_ref
+               path
+               index
+           with
+           | Note { path }
+           | File { path }
+           | Heading { path; _ }
+           | Block { path; _ }
+           | Attr { path; _ } -> S path
+           | Curr_file | Curr_heading _ | Curr_block _ | Curr_attr _ -> S path
+           | Unresolved -> Null)
+        | _ -> Null
+      in
+      insert
+        "links"
+        [ S path; S l.section_path; S l.text; S l.dest; S l.kind; S l.style; target ])
+  in
+  let blocks =
+    List.find index.files ~f:(fun (f : Vault.Index.file_entry) ->
+      String.equal f.rel_path path)
+    |> Option.value_map ~default:[] ~f:(fun (f : Vault.Index.file_entry) -> f.blocks)
+    |> List.map ~f:(fun (b : Vault.Index.block_entry) ->
+      let line =
+        Option.value_map b.loc ~default:Null ~f:(fun tl ->
+          I (fst (Cmarkit.Textloc.first_line tl)))
+      in
+      insert "blocks" [ S path; S b.id; line ])
+  in
+  (insert "notes" [ S path; S title; I words ] :: sections)
+  @ frontmatter
+  @ tags
+  @ inline_fields
+  @ links
+  @ blocks
+  @ [ insert "notes_fts" [ S path; S title; S source ] ]
+;;
+
+(** A script creating the tables, dropping any earlier ones, and filling them
+    from [vault]'s notes, whose source [source_of] gives. *)
+let to_sql ~(source_of : string -> string) (vault : Vault.t) : string =
+  String.concat
+    ([ "BEGIN;\n" ]
+     @ List.map tables ~f:(sprintf "DROP TABLE IF EXISTS %s;\n")
+     @ [ schema ]
+     @ List.concat_map vault.docs ~f:(fun (path, doc) ->
+       if String.is_suffix path ~suffix:".md"
+       then note_rows ~index:vault.index path (source_of path) doc
+       else [])
+     @ [ "COMMIT;\n" ])
+;;
+
+(** Run [sql] on the database at [db] with the [sqlite3] command. *)
+let write ~(db : string) (sql : string) : unit Or_error.t =
+  let oc = Core_unix.open_process_out ("sqlite3 -bail " ^ Filename.quote db) in
+  Out_channel.output_string oc sql;
+  match Core_unix.close_process_out oc with
+  | Ok () -> Ok ()
+  | Error _ -> Or_error.errorf "sqlite3 failed writing %s" db
+;;
+
+(** Rows [sql] selects from the database at [db], as column name and value. *)
+let select ~(db : string) (sql : string) : (string * Yojson.Safe.t) list list Or_error.t =
+  let cmd =
+    sprintf "sqlite3 -bail -json %s %s" (Filename.quote db) (Filename.quote sql)
+  in
+  match
+    let ic = Core_unix.open_process_in cmd in
+    let out = In_channel.input_all ic in
+    out, Core_unix.close_process_in ic
+  with
+  | _, Error _ -> Or_error.errorf "sqlite3 failed reading %s" db
+  | out, Ok () when String.is_empty (String.strip out) -> Ok []
+  | out, Ok () ->
+    (match Yojson.Safe.from_string out with
+     | `List rows ->
+       Ok
+         (List.filter_map rows ~f:(function
+            | `Assoc row -> Some row
+            | _ -> None))
+     | _ -> Or_error.errorf "sqlite3: unexpected output reading %s" db
+     | exception Yojson.Json_error msg -> Or_error.errorf "sqlite3: %s" msg)
+;;
+
+let%expect_test "to_sql" =
+  let sources =
+    [ ( "a.md"
+      , "---\ntitle: Alpha\ntags: [x, y]\ntitle: Again\n---\n"
+        ^ "# A\n\nSee [[b#Use]]. ^p1\n" )
+    ; "b.md", "# B\n\n## Use\n\nIt's [here](https://example.com) (rating:: 9). #y #z/w\n"
+    ]
+  in
+  let md_docs = List.map sources ~f:(fun (p, src) -> p, Parse.of_string src) in
+  let index = Vault.build_index ~md_docs ~other_files:[] ~dirs:[] in
+  let vault : Vault.t =
+    { vault_root = "."
+    ; index
+    ; docs = Vault.Resolve.resolve_docs md_docs index
+    ; vault_meta = Cmarkit.Meta.none
+    }
+  in
+  let sql = to_sql ~source_of:(List.Assoc.find_exn sources ~equal:String.equal) vault in
+  String.split_lines sql
+  |> List.filter ~f:(fun l ->
+    String.is_prefix l ~prefix:"INSERT" && not (String.is_substring l ~substring:"fts"))
+  |> List.iter ~f:print_endline;
+  [%expect
+    {|
+    INSERT INTO notes VALUES ('a.md', 'Alpha', 2);
+    INSERT INTO sections VALUES ('a.md', 'root', '', 0, 6, 8);
+    INSERT INTO sections VALUES ('a.md', '1', 'A', 1, 6, 8);
+    INSERT INTO frontmatter VALUES ('a.md', 'title', '"Alpha"');
+    INSERT INTO frontmatter VALUES ('a.md', 'tags', '["x","y"]');
+    INSERT INTO tags VALUES ('a.md', 'x');
+    INSERT INTO tags VALUES ('a.md', 'y');
+    INSERT INTO links VALUES ('a.md', '1', 'b#Use', 'b#Use', 'internal', 'wikilink', 'b.md');
+    INSERT INTO blocks VALUES ('a.md', 'p1', 8);
+    INSERT INTO notes VALUES ('b.md', 'B', 6);
+    INSERT INTO sections VALUES ('b.md', 'root', '', 0, 1, 5);
+    INSERT INTO sections VALUES ('b.md', '1', 'B', 1, 1, 5);
+    INSERT INTO sections VALUES ('b.md', '1.1', 'Use', 2, 3, 5);
+    INSERT INTO tags VALUES ('b.md', 'y');
+    INSERT INTO tags VALUES ('b.md', 'z/w');
+    INSERT INTO fields VALUES ('b.md', 'rating', '9', 5);
+    INSERT INTO links VALUES ('b.md', '1.1', 'here', 'https://example.com', 'external', 'inline', NULL);
+    |}]
+;;
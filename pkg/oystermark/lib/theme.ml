@@ -7,11 +7,47 @@ type page =
   ; body : string
   ; url_path : string
   ; nav : string
+  ; menu : string (** site menu, see {!Component.menu_html} *)
   ; sidebar : string
+  ; classes : string list
+    (** on [<body>], from the note's [cssclasses]; see {!body_tag} *)
+  ; head : string (** extra [<head>] tags, each on its own line *)
   }
 
 type t = page -> string
 
+(* Classes the page's own script toggles on [<body>]; a note can't set them,
+   or its page would open with the sidebar in the wrong state. *)
+let script_body_classes : string list = [ "sidebar-open"; "sidebar-collapsed" ]
+
+(** [<body>] with [page]'s classes, less {!script_body_classes}. *)
+let body_tag (page : page) : string =
+  match
+    List.filter page.classes ~f:(fun c ->
+      not (List.mem script_body_classes c ~equal:String.equal))
+  with
+  | [] -> "<body>"
+  | classes -> sprintf {|<body class="%s">|} (String.concat ~sep:" " classes)
+;;
+
+let%expect_test "body_tag" =
+  let page : page =
+    { title = ""
+    ; body = ""
+    ; url_path = ""
+    ; nav = ""
+    ; menu = ""
+    ; sidebar = ""
+    ; classes = [ "wide-page"; "sidebar-collapsed"; "cards" ]
+    ; head = ""
+    }
+  in
+  print_endline (body_tag page);
+  [%expect {| <body class="wide-page cards"> |}];
+  print_endline (body_tag { page with classes = [ "sidebar-open" ] });
+  [%expect {| <body> |}]
+;;
+
 type colors =
   { bg : string
   ; bg_alt : string
@@ -29,7 +65,12 @@ type colors =
 
 let theme_css : string = [%blob "static/theme.css"]
 
-let css_of_colors (c : colors) : string =
+(** Paper layout: no chrome, black on white, callouts expanded, external link
+    URLs spelled out. Themes apply it when printing; {!print} always. *)
+let print_css : string = [%blob "static/print.css"]
+
+(** The [:root] custom properties of [colors], which {!theme_css} draws with. *)
+let root_css (c : colors) : string =
   {%string|:root {
   --bg: %{c.bg};
   --bg-alt: %{c.bg_alt};
@@ -44,18 +85,82 @@ let css_of_colors (c : colors) : string =
   --blockquote-accent: %{c.blockquote_accent};
   --frontmatter-label: %{c.frontmatter_label};
 }|}
-  ^ "\n"
-  ^ theme_css
 ;;
 
-let wrap ~(css : string) (page : page) : string =
+let css_of_colors (c : colors) : string = root_css c ^ "\n" ^ theme_css
+
+(** The callout rules of {!theme_css}, from its [Callouts] comment to the
+    next section's; {!Callout_css.expand} still has to run over them. *)
+let callout_css : string =
+  let start = String.substr_index_exn theme_css ~pattern:"/* Callouts */" in
+  let stop = String.substr_index_exn theme_css ~pattern:"/* =====" ~pos:start in
+  String.sub theme_css ~pos:start ~len:(stop - start)
+;;
+
+(** A [<style>] overriding [--accent] for one page, e.g. from a folder's
+    [accent] setting; empty when [accent] is, or holds anything beyond a CSS
+    color's characters. *)
+let accent_style (accent : string) : string =
+  let color_char (c : char) : bool =
+    Char.is_alphanum c || String.mem "#(),.% -" c
+  in
+  let accent = String.strip accent in
+  if String.is_empty accent || not (String.for_all accent ~f:color_char)
+  then ""
+  else sprintf "<style>:root { --accent: %s; }</style>\n" accent
+;;
+
+(** Client-side mermaid for [pre.mermaid] blocks, loaded only on pages that
+    have one and drawn in [colors]. *)
+let mermaid_script (c : colors) : string =
+  let dark =
+    (* Judged by the red channel of the background, enough for the themes here *)
+    match String.chop_prefix c.bg ~prefix:"#" with
+    | Some hex when String.length hex = 6 -> Char.(hex.[0] < '8')
+    | _ -> true
+  in
+  let theme_variables : string =
+    Yojson.Safe.to_string
+      (`Assoc
+          [ "darkMode", `Bool dark
+          ; "background", `String c.bg
+          ; "primaryColor", `String c.bg_alt
+          ; "primaryTextColor", `String c.fg
+          ; "primaryBorderColor", `String c.accent
+          ; "secondaryColor", `String c.bg_alt
+          ; "tertiaryColor", `String c.bg
+          ; "lineColor", `String c.fg_dim
+          ; "textColor", `String c.fg
+          ; "noteBkgColor", `String c.bg_alt
+          ; "noteTextColor", `String c.fg
+          ; "fontFamily", `String "inherit"
+          ])
+  in
+  {%string|<script type="module">
+if (document.querySelector("pre.mermaid")) {
+  const { default: mermaid } =
+    await import("https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs");
+  mermaid.initialize({
+    startOnLoad: false,
+    theme: "base",
+    themeVariables: %{theme_variables}
+  });
+  await mermaid.run({ querySelector: "pre.mermaid" });
+}
+</script>
+|}
+;;
+
+let wrap ?(colors : colors option) ~(css : string) (page : page) : string =
+  let mermaid : string = Option.value_map colors ~default:"" ~f:mermaid_script in
+  let body_tag : string = body_tag page in
   {%string|<!DOCTYPE html>
 <html>
 <head>
 <meta charset="UTF-8">
 <link rel="icon" href="/favicon.png">
 <title>%{page.title}</title>
-<link rel="stylesheet" href="https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/styles/github-dark.min.css">
+%{page.head}<link rel="stylesheet" href="https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/styles/github-dark.min.css">
 <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16.11/dist/katex.min.css">
 <style>
 %{css}
@@ -64,14 +169,16 @@ let wrap ~(css : string) (page : page) : string =
 <script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.11/dist/katex.min.js"></script>
 <script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.11/dist/contrib/auto-render.min.js"></script>
 </head>
-<body>
+%{body_tag}
+<a class="skip-link" href="#content">Skip to content</a>
 <header>
-%{page.nav}</header>
+<button class="sidebar-toggle" onclick="this.setAttribute('aria-expanded', document.body.classList.toggle('sidebar-open'))" aria-label="Toggle sidebar" aria-controls="sidebar" aria-expanded="false">&#9776;</button>
+%{page.nav}%{page.menu}</header>
 <div class="layout">
-<aside class="sidebar">
-%{page.sidebar}</aside>
-<div class="sidebar-handle" onclick="document.body.classList.toggle('sidebar-collapsed')" aria-label="Toggle sidebar"></div>
-<main>
+<nav class="sidebar" id="sidebar" aria-label="Notes">
+%{page.sidebar}</nav>
+<button class="sidebar-handle" onclick="this.setAttribute('aria-expanded', !document.body.classList.toggle('sidebar-collapsed'))" aria-label="Toggle sidebar" aria-controls="sidebar" aria-expanded="true"></button>
+<main id="content">
 <h1 class="page-title">%{page.title}</h1>
 %{page.body}</main>
 </div>
@@ -79,12 +186,14 @@ let wrap ~(css : string) (page : page) : string =
 <div class="lightbox" id="lightbox">
 <button class="lightbox-close" aria-label="Close">&times;</button>
 <img id="lightbox-img" src="" alt="">
+<p class="lightbox-caption" id="lightbox-caption"></p>
 </div>
 <script>
 document.addEventListener("DOMContentLoaded", function() {
   /* Lightbox — runs first so it's not blocked by CDN script failures */
   var lb = document.getElementById("lightbox");
   var lbImg = document.getElementById("lightbox-img");
+  var lbCaption = document.getElementById("lightbox-caption");
   document.querySelectorAll("main a > img, main a > video").forEach(function(img) {
     var link = img.closest("a");
     if (!link) return;
@@ -93,6 +202,9 @@ document.addEventListener("DOMContentLoaded", function() {
       e.stopPropagation();
       lbImg.src = img.src || link.href;
       lbImg.alt = img.alt || "";
+      var figure = img.closest("figure");
+      var caption = figure && figure.querySelector("figcaption");
+      lbCaption.textContent = caption ? caption.textContent : "";
       lb.classList.add("active");
     });
   });
@@ -102,6 +214,150 @@ document.addEventListener("DOMContentLoaded", function() {
   document.addEventListener("keydown", function(e) {
     if (e.key === "Escape") lb.classList.remove("active");
   });
+  /* Sidebar: folders stay open or closed across pages; the current note is
+     marked and its folders opened. Without localStorage (private
+     windows, some file:// pages) the state just isn't kept. */
+  var sidebar = document.querySelector(".sidebar");
+  var folders = sidebar ? Array.from(sidebar.querySelectorAll("details[data-path]")) : [];
+  var openKey = "oyster-sidebar-open";
+  var stored = [];
+  try { stored = JSON.parse(localStorage.getItem(openKey)) || []; } catch (e) {}
+  folders.forEach(function(d) {
+    if (stored.indexOf(d.dataset.path) !== -1) d.open = true;
+  });
+  if (sidebar) {
+    var here = decodeURI(location.pathname).replace(/index\.html$/, "");
+    sidebar.querySelectorAll("a[href]").forEach(function(a) {
+      if (decodeURI(a.pathname).replace(/index\.html$/, "") !== here) return;
+      a.classList.add("active");
+      a.setAttribute("aria-current", "page");
+      for (var d = a.closest("details"); d; d = d.parentElement.closest("details")) {
+        d.open = true;
+      }
+    });
+  }
+  folders.forEach(function(d) {
+    d.addEventListener("toggle", function() {
+      var open = folders.filter(function(f) { return f.open; })
+        .map(function(f) { return f.dataset.path; });
+      try { localStorage.setItem(openKey, JSON.stringify(open)); } catch (e) {}
+    });
+  });
+  /* Sidebar drawer (narrow screens): close on Escape or a click outside it */
+  var toggle = document.querySelector(".sidebar-toggle");
+  function closeDrawer() {
+    document.body.classList.remove("sidebar-open");
+    if (toggle) toggle.setAttribute("aria-expanded", "false");
+  }
+  document.addEventListener("keydown", function(e) {
+    if (e.key === "Escape") closeDrawer();
+  });
+  document.querySelector("main").addEventListener("click", closeDrawer);
+  /* Share links: copy the full URL, or hand it to the share sheet on touch
+     devices */
+  document.querySelectorAll(".heading-link, .fragment-link").forEach(function(a) {
+    a.addEventListener("click", function(e) {
+      e.preventDefault();
+      var url = location.origin + location.pathname + a.getAttribute("href");
+      if (navigator.share && matchMedia("(pointer: coarse)").matches) {
+        navigator.share({ url: url }).catch(function() {});
+        return;
+      }
+      if (a.classList.contains("heading-link")) history.replaceState(null, "", a.hash);
+      if (!navigator.clipboard) return;
+      navigator.clipboard.writeText(url).then(function() {
+        a.classList.add("copied");
+        setTimeout(function() { a.classList.remove("copied"); }, 1500);
+      });
+    });
+  });
+  /* Page previews: hovering a link to a note pops up its first heading and
+     paragraph, fetched once per note */
+  var previews = {};
+  var popover = null;
+  var hovered = null;
+  var showTimer = null;
+  var hideTimer = null;
+  function hidePreview() {
+    hovered = null;
+    clearTimeout(showTimer);
+    hideTimer = setTimeout(function() {
+      if (popover) popover.remove();
+      popover = null;
+    }, 200);
+  }
+  function showPreview(a, html) {
+    if (popover) popover.remove();
+    popover = document.createElement("div");
+    popover.className = "preview-popover";
+    popover.innerHTML = html;
+    popover.addEventListener("mouseenter", function() { clearTimeout(hideTimer); });
+    popover.addEventListener("mouseleave", hidePreview);
+    document.body.appendChild(popover);
+    var r = a.getBoundingClientRect();
+    var maxLeft = scrollX + document.documentElement.clientWidth - popover.offsetWidth - 8;
+    popover.style.left = Math.max(scrollX + 8, Math.min(r.left + scrollX, maxLeft)) + "px";
+    popover.style.top = (r.bottom + scrollY + 6) + "px";
+  }
+  document.querySelectorAll("main a[data-preview]").forEach(function(a) {
+    a.addEventListener("mouseenter", function() {
+      if (matchMedia("(pointer: coarse)").matches) return;
+      hovered = a;
+      clearTimeout(hideTimer);
+      clearTimeout(showTimer);
+      showTimer = setTimeout(function() {
+        var url = a.dataset.preview;
+        if (!(url in previews)) {
+          previews[url] = fetch(url)
+            .then(function(r) { return r.ok ? r.text() : ""; })
+            .catch(function() { return ""; });
+        }
+        previews[url].then(function(html) {
+          if (html && hovered === a) showPreview(a, html);
+        });
+      }, 300);
+    });
+    a.addEventListener("mouseleave", hidePreview);
+  });
+  /* Sortable tables: a header click sorts by its column, numbers by value,
+     and a second click reverses */
+  document.querySelectorAll("table.sortable").forEach(function(table) {
+    var body = table.tBodies[0];
+    if (!body) return;
+    var heads = Array.from(table.querySelectorAll("thead th"));
+    heads.forEach(function(th, col) {
+      th.tabIndex = 0;
+      function sort() {
+        var asc = th.getAttribute("aria-sort") !== "ascending";
+        heads.forEach(function(h) { h.removeAttribute("aria-sort"); });
+        th.setAttribute("aria-sort", asc ? "ascending" : "descending");
+        var text = function(row) {
+          var cell = row.cells[col];
+          return cell ? cell.textContent.trim() : "";
+        };
+        Array.from(body.rows)
+          .sort(function(a, b) {
+            var c = text(a).localeCompare(text(b), undefined, { numeric: true });
+            return asc ? c : -c;
+          })
+          .forEach(function(row) { body.appendChild(row); });
+      }
+      th.addEventListener("click", sort);
+      th.addEventListener("keydown", function(e) {
+        if (e.key === "Enter" || e.key === " ") { e.preventDefault(); sort(); }
+      });
+    });
+  });
+  /* Print collapsed callouts expanded, then fold them back */
+  var folded = [];
+  window.addEventListener("beforeprint", function() {
+    folded = Array.from(document.querySelectorAll("main details:not([open])"));
+    folded.forEach(function(d) { d.open = true; });
+  });
+  window.addEventListener("afterprint", function() {
+    folded.forEach(function(d) { d.open = false; });
+    folded = [];
+  });
   /* Syntax highlighting & math */
   if (typeof hljs !== "undefined") hljs.highlightAll();
   if (typeof renderMathInElement !== "undefined") renderMathInElement(document.body, {
@@ -115,22 +371,23 @@ document.addEventListener("DOMContentLoaded", function() {
   });
 });
 </script>
-</body>
+%{mermaid}</body>
 </html>
 |}
 ;;
 
 let of_colors ?(extra_css : string = "") (colors : colors) : t =
-  wrap ~css:(Callout_css.expand (css_of_colors colors ^ extra_css))
+  let print = "\n@media print {\n" ^ print_css ^ "}\n" in
+  wrap ~colors ~css:(Callout_css.expand (css_of_colors colors ^ print ^ extra_css))
 ;;
 
 let none : t =
   fun page ->
   {%string|<!DOCTYPE html>
 <html>
-<head><meta charset="UTF-8"></head>
-<body>
-%{page.nav}%{page.sidebar}%{page.body}</body>
+<head><meta charset="UTF-8">%{page.head}</head>
+%{body_tag page}
+%{page.nav}%{page.menu}%{page.sidebar}%{page.body}</body>
 </html>
 |}
 ;;
@@ -233,15 +490,34 @@ let bluloco_light_colors : colors =
 
 let default : t = of_colors bluloco_dark_colors
 
+(** Print-friendly variant of a page ([note.print.html]), whatever the site's
+    theme: {!print_css} on screen too, so the browser's print preview or
+    "Save as PDF" shows what the page looks like. *)
+let print : t =
+  let colors = bluloco_light_colors in
+  wrap ~colors ~css:(Callout_css.expand (css_of_colors colors ^ "\n" ^ print_css))
+;;
+
+let colors_of_name : Config.Theme.t -> colors option = function
+  | Config.Theme_def.Tokyonight -> Some tokyonight_colors
+  | Gruvbox -> Some gruvbox_colors
+  | Atom_one_light -> Some atom_one_light_colors
+  | Atom_one_dark -> Some atom_one_dark_colors
+  | Bluloco_light -> Some bluloco_light_colors
+  | Bluloco_dark -> Some bluloco_dark_colors
+  | No_theme -> None
+;;
+
 let of_name ?(css_snippets : string list = []) (theme : Config.Theme.t) =
   let extra_css = String.concat css_snippets in
-  let of_colors' = of_colors ~extra_css in
-  match theme with
-  | Config.Theme_def.Tokyonight -> of_colors' tokyonight_colors
-  | Gruvbox -> of_colors' gruvbox_colors
-  | Atom_one_light -> of_colors' atom_one_light_colors
-  | Atom_one_dark -> of_colors' atom_one_dark_colors
-  | Bluloco_light -> of_colors' bluloco_light_colors
-  | Bluloco_dark -> of_colors' bluloco_dark_colors
-  | No_theme -> none
+  match colors_of_name theme with
+  | Some colors -> of_colors ~extra_css colors
+  | None -> none
+;;
+
+(** Colors for build-time diagrams ([dot] blocks) matching [theme]; [None]
+    leaves them in Graphviz's defaults. *)
+let diagram_colors (theme : Config.Theme.t) : Code_executor.diagram_colors option =
+  Option.map (colors_of_name theme) ~f:(fun (c : colors) : Code_executor.diagram_colors ->
+    { fg = c.fg; bg = c.bg_alt; accent = c.accent })
 ;;
@@ -0,0 +1,282 @@
+(** Vault assets: the non-markdown files (images, PDFs, ...) notes link to. *)
+
+open Core
+
+(** Obsidian's configured attachment folder ([attachmentFolderPath] in
+    [.obsidian/app.json]) as a vault-relative path. [None] when unset, when set
+    to the vault root, or when note-relative (["./..."]). *)
+let attachment_folder (vault_root : string) : string option =
+  let app_json : string = Filename.concat vault_root ".obsidian/app.json" in
+  match Yojson.Safe.from_file app_json with
+  | exception _ -> None
+  | `Assoc fields ->
+    (match List.Assoc.find fields ~equal:String.equal "attachmentFolderPath" with
+     | Some (`String p) ->
+       let p : string = String.strip ~drop:(Char.equal '/') p in
+       if String.is_empty p || String.is_prefix p ~prefix:"." then None else Some p
+     | _ -> None)
+  | _ -> None
+;;
+
+(** Files linked or embedded from [docs], each with the notes referencing it,
+    both sorted. Only {!Resolve.File} targets count; notes are not assets. *)
+let references (docs : (string * Cmarkit.Doc.t) list) : (string * string list) list =
+  let add acc meta =
+    match Cmarkit.Meta.find Resolve.resolved_key meta with
+    | Some (Resolve.File { path }) -> path :: acc
+    | _ -> acc
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline:(fun _f acc (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Link (_, meta) | Cmarkit.Inline.Image (_, meta) ->
+          Cmarkit.Folder.ret (add acc meta)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Ext_wikilink (_, meta) -> add acc meta
+        | _ -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.concat_map docs ~f:(fun (note, doc) ->
+    Cmarkit.Folder.fold_doc folder [] doc |> List.map ~f:(fun asset -> asset, note))
+  |> String.Map.of_alist_multi
+  |> Map.map ~f:(List.dedup_and_sort ~compare:String.compare)
+  |> Map.to_alist
+;;
+
+(** Vault-relative paths of the files linked or embedded from [docs], deduped and
+    sorted; the keys of {!references}. *)
+let referenced_files (docs : (string * Cmarkit.Doc.t) list) : string list =
+  List.map (references docs) ~f:fst
+;;
+
+(* Attribute values of raw HTML that point at files. *)
+let html_file_attrs : string list = [ "src"; "href"; "poster"; "data" ]
+
+(* What the raw HTML of [doc] and the string values of its frontmatter
+   mention, as written: [<img src=..>] values, and frontmatter strings with
+   any [[[..]]] around them dropped. *)
+let mentions (doc : Cmarkit.Doc.t) : string list =
+  let of_html (lines : string list) : string list =
+    Parse.Html_fragment.of_string (String.concat ~sep:"\n" lines)
+    |> List.concat_map ~f:(function
+      | Parse.Html_fragment.Tag { attrs; _ } ->
+        List.filter_map attrs ~f:(fun (a : Parse.Html_fragment.attr) ->
+          if List.mem html_file_attrs a.name ~equal:String.equal then a.value else None)
+      | Parse.Html_fragment.Comment _ | Parse.Html_fragment.Text _ -> [])
+  in
+  let rec of_yaml (v : Yaml.value) : string list =
+    match v with
+    | `String s ->
+      let s = String.strip s in
+      let s = String.chop_prefix_if_exists s ~prefix:"!" in
+      let s = String.chop_prefix_if_exists s ~prefix:"[[" in
+      let s = String.chop_suffix_if_exists s ~suffix:"]]" in
+      [ List.hd_exn (String.split s ~on:'|') ]
+    | `A vs -> List.concat_map vs ~f:of_yaml
+    | `O fields -> List.concat_map fields ~f:(fun (_, v) -> of_yaml v)
+    | `Null | `Bool _ | `Float _ -> []
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline:(fun _f acc (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Raw_html (lines, _) ->
+          Cmarkit.Folder.ret
+            (of_html (List.map lines ~f:Cmarkit.Block_line.tight_to_string) @ acc)
+        | _ -> Cmarkit.Folder.default)
+      ~block:(fun _f acc (b : Cmarkit.Block.t) ->
+        match b with
+        | Cmarkit.Block.Html_block (lines, _) ->
+          let lines = List.map lines ~f:Cmarkit.Block_line.to_string in
+          Cmarkit.Folder.ret (of_html lines @ acc)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  Cmarkit.Folder.fold_doc folder [] doc
+  @ Option.value_map (Parse.Frontmatter.of_doc doc) ~default:[] ~f:of_yaml
+;;
+
+(* Entries a build or Obsidian reads without a note linking them: canvases,
+   bases, stylesheets, scripts, config files and anything under a dot
+   folder such as [.obsidian/]. *)
+let is_kept (path : string) : bool =
+  List.exists (String.split path ~on:'/') ~f:(String.is_prefix ~prefix:".")
+  ||
+  match snd (Filename.split_extension path) with
+  | Some ("canvas" | "base" | "css" | "js" | "json" | "toml" | "yaml" | "yml") -> true
+  | _ -> false
+;;
+
+(** Files among [entries] (as from {!Vault.list_entries}) that are neither
+    notes nor referenced from [docs], sorted. Besides links and embeds, a
+    file counts as referenced when the raw HTML of a note points at it
+    ([<img src=..>], relative to the note or to the vault root) or a
+    frontmatter string names it (by path, or by name as a wikilink does).
+    Canvases, bases, stylesheets, scripts and config files are never
+    unused. *)
+let unused ~(entries : string list) (docs : (string * Cmarkit.Doc.t) list) : string list =
+  let referenced = String.Set.of_list (referenced_files docs) in
+  let mentioned : String.Set.t =
+    List.concat_map docs ~f:(fun (note, doc) ->
+      List.concat_map (mentions doc) ~f:(fun m ->
+        let m = List.hd_exn (String.split_on_chars m ~on:[ '#'; '?' ]) in
+        m
+        :: Option.to_list (Resolve.relative_path ~curr_file:note m)
+        @ [ String.chop_prefix_if_exists m ~prefix:"/" ]))
+    |> String.Set.of_list
+  in
+  List.filter entries ~f:(fun p ->
+    (not (String.is_suffix p ~suffix:"/"))
+    && (not (String.is_suffix p ~suffix:".md"))
+    && (not (is_kept p))
+    && (not (Set.mem referenced p))
+    && (not (Set.mem mentioned p))
+    && not (Set.mem mentioned (Filename.basename p)))
+  |> List.sort ~compare:String.compare
+;;
+
+(** Group [paths] by content hash and pick one canonical copy per group.
+    Returns [(duplicate, canonical)] for every non-canonical path. Within a
+    group a copy under [attachment_folder] wins, then the smallest path. *)
+let duplicates
+      ?(attachment_folder : string option)
+      ~(read_file : string -> string)
+      (paths : string list)
+  : (string * string) list
+  =
+  let rank (p : string) : int * string =
+    let in_attachments : bool =
+      match attachment_folder with
+      | Some dir -> String.is_prefix p ~prefix:(dir ^ "/")
+      | None -> false
+    in
+    (if in_attachments then 0 else 1), p
+  in
+  List.map paths ~f:(fun p -> Md5.to_hex (Md5.digest_string (read_file p)), p)
+  |> String.Map.of_alist_multi
+  |> Map.data
+  |> List.concat_map ~f:(fun group ->
+    match
+      List.sort group ~compare:(fun a b -> [%compare: int * string] (rank a) (rank b))
+    with
+    | [] -> []
+    | canonical :: rest -> List.map rest ~f:(fun p -> p, canonical))
+;;
+
+(** Point every resolved link to a key of [canonical] at its value. *)
+let retarget (canonical : string String.Map.t) (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
+  let remap meta : Cmarkit.Meta.t option =
+    match Cmarkit.Meta.find Resolve.resolved_key meta with
+    | Some (Resolve.File { path }) ->
+      Map.find canonical path
+      |> Option.map ~f:(fun path ->
+        Cmarkit.Meta.add Resolve.resolved_key (Resolve.File { path }) meta)
+    | _ -> None
+  in
+  let mapper =
+    Cmarkit.Mapper.make
+      ~inline:(fun _m (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Link (l, meta) ->
+          (match remap meta with
+           | Some meta -> Cmarkit.Mapper.ret (Cmarkit.Inline.Link (l, meta))
+           | None -> Cmarkit.Mapper.default)
+        | Cmarkit.Inline.Image (l, meta) ->
+          (match remap meta with
+           | Some meta -> Cmarkit.Mapper.ret (Cmarkit.Inline.Image (l, meta))
+           | None -> Cmarkit.Mapper.default)
+        | _ -> Cmarkit.Mapper.default)
+      ~inline_ext_default:(fun _m i ->
+        match i with
+        | Cmarkit.Inline.Ext_wikilink (w, meta) ->
+          Some (Cmarkit.Inline.Ext_wikilink (w, Option.value (remap meta) ~default:meta))
+        | other -> Some other)
+      ~block_ext_default:(fun _m b -> Some b)
+      ()
+  in
+  Cmarkit.Mapper.map_doc mapper doc
+;;
+
+let%expect_test "duplicates prefers the attachment folder" =
+  let contents =
+    String.Map.of_alist_exn
+      [ "a/logo.png", "PNG1"
+      ; "attachments/logo.png", "PNG1"
+      ; "b/logo copy.png", "PNG1"
+      ; "c/other.png", "PNG2"
+      ]
+  in
+  duplicates
+    ~attachment_folder:"attachments"
+    ~read_file:(Map.find_exn contents)
+    (Map.keys contents)
+  |> List.iter ~f:(fun (dup, canonical) -> printf "%s -> %s\n" dup canonical);
+  [%expect
+    {|
+    a/logo.png -> attachments/logo.png
+    b/logo copy.png -> attachments/logo.png
+    |}]
+;;
+
+let%expect_test "references and unused" =
+  let entries =
+    [ ".obsidian/app.json"
+    ; "a.md"
+    ; "b.md"
+    ; "board.canvas"
+    ; "c.md"
+    ; "doc.pdf"
+    ; "img/"
+    ; "img/banner.png"
+    ; "img/cover.png"
+    ; "img/diagram.svg"
+    ; "img/logo.png"
+    ; "img/old.png"
+    ; "img/photo.jpg"
+    ; "notes.txt"
+    ; "snippets/wide.css"
+    ]
+  in
+  let docs =
+    [ "a.md", Parse.of_string "![[logo.png]] and [the doc](doc.pdf)\n"
+    ; "b.md", Parse.of_string "![[img/logo.png]], [[a]]\n"
+    ; ( "c.md"
+      , Parse.of_string
+          "---\n\
+           cover: img/cover.png\n\
+           banner: \"[[banner.png]]\"\n\
+           ---\n\
+           <img src=\"img/diagram.svg\">\n\n\
+           A <img src=\"/img/photo.jpg\" alt=\"\"> inline.\n" )
+    ]
+  in
+  let index : Index.t =
+    { files =
+        List.filter_map entries ~f:(fun rel_path ->
+          Option.some_if
+            (not (String.is_suffix rel_path ~suffix:"/"))
+            ({ rel_path; headings = []; blocks = []; attrs = [] } : Index.file_entry))
+    ; dirs = []
+    }
+  in
+  let docs = Resolve.resolve_docs docs index in
+  List.iter (references docs) ~f:(fun (asset, notes) ->
+    printf "%s <- %s\n" asset (String.concat ~sep:", " notes));
+  [%expect
+    {|
+    doc.pdf <- a.md
+    img/logo.png <- a.md, b.md
+    |}];
+  List.iter (unused ~entries docs) ~f:print_endline;
+  [%expect
+    {|
+    img/old.png
+    notes.txt
+    |}]
+;;
@@ -0,0 +1,157 @@
+(** Vault health checks for [oyster doctor]: names and contents that make
+    links ambiguous or waste space.
+
+    A bare [[[name]]] resolves to the first note whose path ends in
+    [name.md] (see {!Resolve.resolve_file}), so two notes sharing a file name
+    in different folders make the link depend on listing order. The same goes
+    for [![[image.png]]] and assets. *)
+
+open Core
+
+type kind =
+  | Duplicate_title of string
+  (** notes with the same file name, e.g. [a/Ideas.md] and [b/Ideas.md] *)
+  | Case_conflict
+  (** paths equal up to case; they collide on case-insensitive file systems *)
+  | Identical_content (** files with byte-identical content *)
+  | Asset_name_clash of string (** assets with the same file name *)
+[@@deriving sexp_of]
+
+type finding =
+  { kind : kind
+  ; paths : string list (** sorted *)
+  ; suggestion : string
+  }
+[@@deriving sexp_of]
+
+let is_note (path : string) : bool = String.is_suffix path ~suffix:".md"
+
+(* Groups of two or more [paths] sharing a key, each sorted. *)
+let groups (paths : string list) ~(key : string -> string) : (string * string list) list =
+  List.map paths ~f:(fun p -> key p, p)
+  |> String.Map.of_alist_multi
+  |> Map.to_alist
+  |> List.filter_map ~f:(fun (k, ps) ->
+    match ps with
+    | _ :: _ :: _ -> Some (k, List.sort ps ~compare:String.compare)
+    | _ -> None)
+;;
+
+let duplicate_titles (notes : string list) : finding list =
+  groups notes ~key:(fun p -> Filename.basename p |> String.chop_suffix_exn ~suffix:".md")
+  |> List.map ~f:(fun (title, paths) ->
+    { kind = Duplicate_title title
+    ; paths
+    ; suggestion =
+        sprintf
+          "[[%s]] is ambiguous; rename all but one, or link with a folder \
+           ([[%s]])"
+          title
+          (String.chop_suffix_exn (List.hd_exn paths) ~suffix:".md")
+    })
+;;
+
+let case_conflicts (paths : string list) : finding list =
+  groups paths ~key:String.lowercase
+  |> List.map ~f:(fun (_, paths) ->
+    { kind = Case_conflict
+    ; paths
+    ; suggestion = "rename so the paths differ by more than letter case"
+    })
+;;
+
+let identical_content ~(digest_of : string -> string) (paths : string list)
+  : finding list
+  =
+  groups paths ~key:digest_of
+  |> List.map ~f:(fun (_, paths) ->
+    { kind = Identical_content
+    ; paths
+    ; suggestion = "keep one copy and point links at it"
+    })
+;;
+
+let asset_name_clashes (assets : string list) : finding list =
+  groups assets ~key:Filename.basename
+  |> List.map ~f:(fun (name, paths) ->
+    { kind = Asset_name_clash name
+    ; paths
+    ; suggestion =
+        sprintf "![[%s]] is ambiguous; rename the assets or embed with a folder" name
+    })
+;;
+
+(** Hex MD5 of the file at [path], read in blocks rather than whole. *)
+let digest_file (path : string) : string = Md5.to_hex (Md5.digest_file_blocking path)
+
+(** Every finding for the vault-relative file [paths] (directories excluded),
+    whose contents are compared by [digest_of], a {!Manifest.hash_content} of
+    each (see {!digest_file}), called once per path. Empty files are not
+    compared. *)
+let check ~(digest_of : string -> string) (paths : string list) : finding list =
+  let digest_of = Memo.general ~hashable:String.hashable digest_of in
+  let empty = Manifest.hash_content "" in
+  let notes, assets = List.partition_tf paths ~f:is_note in
+  let non_empty =
+    List.filter paths ~f:(fun p -> not (String.equal (digest_of p) empty))
+  in
+  List.concat
+    [ duplicate_titles notes
+    ; case_conflicts paths
+    ; identical_content ~digest_of non_empty
+    ; asset_name_clashes assets
+    ]
+;;
+
+let kind_to_string_hum : kind -> string = function
+  | Duplicate_title title -> sprintf "duplicate note title %S" title
+  | Case_conflict -> "paths differ only by case"
+  | Identical_content -> "identical content"
+  | Asset_name_clash name -> sprintf "assets named %S" name
+;;
+
+let to_string_hum (f : finding) : string =
+  sprintf
+    "%s:\n%s  fix: %s\n"
+    (kind_to_string_hum f.kind)
+    (String.concat (List.map f.paths ~f:(fun p -> "  " ^ p ^ "\n")))
+    f.suggestion
+;;
+
+let%expect_test "check" =
+  let files =
+    [ "a/Ideas.md", "one"
+    ; "b/Ideas.md", "two"
+    ; "Notes.md", "same"
+    ; "notes.md", "same"
+    ; "img/logo.png", "PNG"
+    ; "assets/logo.png", "PNG2"
+    ; "empty1.md", ""
+    ; "empty2.md", ""
+    ]
+  in
+  let digest_of p =
+    Manifest.hash_content (List.Assoc.find_exn files ~equal:String.equal p)
+  in
+  List.iter (check ~digest_of (List.map files ~f:fst)) ~f:(fun f ->
+    print_string (to_string_hum f));
+  [%expect
+    {|
+    duplicate note title "Ideas":
+      a/Ideas.md
+      b/Ideas.md
+      fix: [[Ideas]] is ambiguous; rename all but one, or link with a folder ([[a/Ideas]])
+    paths differ only by case:
+      Notes.md
+      notes.md
+      fix: rename so the paths differ by more than letter case
+    identical content:
+      Notes.md
+      notes.md
+      fix: keep one copy and point links at it
+    assets named "logo.png":
+      assets/logo.png
+      img/logo.png
+      fix: ![[logo.png]] is ambiguous; rename the assets or embed with a folder
+    |}]
+;;
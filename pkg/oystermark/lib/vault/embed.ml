@@ -22,6 +22,11 @@
       the moment we have no way to specify whether an embed is Inline.t or
       Block.t
 
+    An embed wikilink can carry an [as] attribute choosing its {!view}:
+    [!\[\[NOTE#Section\]\]{as=summary}] pulls in just the section's heading and
+    first paragraph, [{as=list}] a list of links to its headings, so a "map of
+    content" note can gather titles or summaries rather than whole notes.
+
     Depth limiting: embedding is allowed up to [max_depth] levels deep.
     When [embed_depth >= max_depth] the wikilink is replaced with a plain
     fallback link instead; image embeds are left as-is. *)
@@ -38,6 +43,31 @@ module type Spec = sig
   val reverse_embed : unit
 end
 
+(** What an embed shows of the blocks it pulls in, set by its [as] attribute. *)
+type view =
+  | Full (** the blocks themselves; the default *)
+  | Summary (** the leading heading, if any, and the first paragraph *)
+  | List
+  (** a list of links to the headings, those of the top level within, below the
+      leading heading *)
+[@@deriving sexp_of]
+
+(** The embed attribute choosing the {!view}: [{as=full|summary|list}]. *)
+let view_key = "as"
+
+let view_of_string : string -> view option = function
+  | "full" -> Some Full
+  | "summary" -> Some Summary
+  | "list" -> Some List
+  | _ -> None
+;;
+
+let view_to_string : view -> string = function
+  | Full -> "full"
+  | Summary -> "summary"
+  | List -> "list"
+;;
+
 (** Metadata attached to the [Cmarkit.Block.Blocks] node that wraps
     transcluded content. Consumers (e.g. the HTML renderer) can use this to
     style embedded blocks differently, and {!reverse_embed_doc} uses it to
@@ -51,6 +81,7 @@ type embed_meta =
   ; fragment : Cmarkit.Inline.Wikilink.fragment option
     (** The heading or block-ref fragment, if the embed targeted a sub-section
         rather than the full note. *)
+  ; view : view
   }
 
 let embed_meta_key : embed_meta Cmarkit.Meta.key = Cmarkit.Meta.key ()
@@ -79,12 +110,18 @@ type embed_source =
 
 (** If [inline] is a single embed source (embed wikilink or image link
     pointing to a note), return the classified source.  cmarkit wraps a
-    paragraph's inline content in [Inlines(\[...\], _)] — we unwrap that. *)
+    paragraph's inline content in [Inlines(\[...\], _)] — we unwrap that,
+    and the [Ext_attributes] of an embed wikilink with attributes. *)
 let extract_embed_source (inline : Cmarkit.Inline.t) : embed_source option =
   let check_one (i : Cmarkit.Inline.t) : embed_source option =
     match i with
     | Cmarkit.Inline.Ext_wikilink (w, meta) when Cmarkit.Inline.Wikilink.embed w ->
       Some (Wikilink_embed (w, meta))
+    | Cmarkit.Inline.Ext_attributes (a, _) ->
+      (match Cmarkit.Inline.Attributes.inline a with
+       | Cmarkit.Inline.Ext_wikilink (w, meta) when Cmarkit.Inline.Wikilink.embed w ->
+         Some (Wikilink_embed (w, meta))
+       | _ -> None)
     | Cmarkit.Inline.Image (_, meta) ->
       (match Cmarkit.Meta.find Resolve.resolved_key meta with
        | Some
@@ -102,6 +139,99 @@ let extract_embed_source (inline : Cmarkit.Inline.t) : embed_source option =
   | i -> check_one i
 ;;
 
+(** The {!view} of the embed source [inline] (as for {!extract_embed_source}):
+    its [as] attribute, {!Full} when absent or not a view. *)
+let view_of_inline (inline : Cmarkit.Inline.t) : view =
+  let key_values =
+    match inline with
+    | Cmarkit.Inline.Inlines ([ Cmarkit.Inline.Ext_attributes (a, _) ], _)
+    | Cmarkit.Inline.Ext_attributes (a, _) ->
+      Cmarkit.Attribute.key_values (Cmarkit.Inline.Attributes.attributes a)
+    | _ -> []
+  in
+  List.Assoc.find key_values view_key ~equal:String.equal
+  |> Option.bind ~f:view_of_string
+  |> Option.value ~default:Full
+;;
+
+(* [blocks] with [Blocks] spliced in, except those of nested embeds. *)
+let rec own_blocks (blocks : Cmarkit.Block.t list) : Cmarkit.Block.t list =
+  List.concat_map blocks ~f:(fun block ->
+    match block with
+    | Cmarkit.Block.Blocks (children, meta)
+      when not (Cmarkit.Meta.mem embed_meta_key meta) -> own_blocks children
+    | other -> [ other ])
+;;
+
+let heading_of_block (block : Cmarkit.Block.t)
+  : (Cmarkit.Block.Heading.t * Cmarkit.Meta.t) option
+  =
+  match block with
+  | Cmarkit.Block.Heading (h, meta) -> Some (h, meta)
+  | Cmarkit.Block.Ext_attributes (a, _) ->
+    (match Cmarkit.Block.Attributes.block a with
+     | Cmarkit.Block.Heading (h, meta) -> Some (h, meta)
+     | _ -> None)
+  | _ -> None
+;;
+
+(** [blocks], extracted from note [path] for an embed, as [view] shows them.
+    The links of {!List} point to the headings in [path]. *)
+let apply_view
+      ?(slug_style : Parse.Heading_slug.slug_style option)
+      (view : view)
+      ~(path : string)
+      (blocks : Cmarkit.Block.t list)
+  : Cmarkit.Block.t list
+  =
+  let m = Cmarkit.Meta.none in
+  let leading, rest =
+    match own_blocks blocks with
+    | b :: rest when Option.is_some (heading_of_block b) -> [ b ], rest
+    | bs -> [], bs
+  in
+  match view with
+  | Full -> blocks
+  | Summary ->
+    let paragraph =
+      List.find rest ~f:(function
+        | Cmarkit.Block.Paragraph _ -> true
+        | _ -> false)
+    in
+    leading @ Option.to_list paragraph
+  | List ->
+    let headings = List.filter_map rest ~f:heading_of_block in
+    let level (h, _) = Cmarkit.Block.Heading.level h in
+    (match List.min_elt (List.map headings ~f:level) ~compare:Int.compare with
+     | None -> []
+     | Some top ->
+       let item ((h : Cmarkit.Block.Heading.t), (meta : Cmarkit.Meta.t))
+         : Cmarkit.Block.List_item.t Cmarkit.node
+         =
+         let heading =
+           String.strip
+             (Parse.Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h))
+         in
+         let slug = Parse.Heading_slug.of_meta ?style:slug_style meta heading in
+         let link : Cmarkit.Inline.t =
+           Resolve.make_wikilink
+             ~target:(Some (String.chop_suffix_if_exists path ~suffix:".md"))
+             ~fragment:(Some (Cmarkit.Inline.Wikilink.Heading [ heading ]))
+             ~display:(Some heading)
+             ~embed:false
+             ~resolved_target:
+               (Resolve.Heading { path; heading; level = top; slug; loc = None })
+         in
+         ( Cmarkit.Block.List_item.make
+             (Cmarkit.Block.Paragraph (Cmarkit.Block.Paragraph.make link, m))
+         , m )
+       in
+       let items = List.filter headings ~f:(fun h -> level h = top) in
+       [ Cmarkit.Block.List
+           (Cmarkit.Block.List'.make (`Unordered '-') (List.map items ~f:item), m)
+       ])
+;;
+
 (** Test whether a block is an embed-expandable paragraph: a paragraph
     containing a single embed source (wikilink or image), where every sibling
     in [siblings] is either a blank line or absent.  An embed can only replace
@@ -162,7 +292,8 @@ let fallback_block (wl : Cmarkit.Inline.Wikilink.t) (meta : Cmarkit.Meta.t)
 
     5. {b Wrapping} — the extracted blocks are wrapped in a [Block.Blocks]
        node tagged with {!embed_meta} (depth + source path), which the HTML
-       renderer uses to emit [<div class="embed" data-embed-depth="N">].
+       renderer uses to emit [<div class="embed" data-embed-depth="N">],
+       after {!apply_view} narrows them to the embed's [view].
 
     @param embed_depth Current transclusion nesting level. 0 for the root
       document, incremented by 1 each time we descend into an embed.
@@ -173,6 +304,7 @@ let fallback_block (wl : Cmarkit.Inline.Wikilink.t) (meta : Cmarkit.Meta.t)
       image embeds this is the original paragraph (keeping the image as-is).
     @param docs_tbl All parsed vault documents keyed by vault-relative path
       (e.g. ["notes/foo.md"]). Shared across the entire expansion pass.
+    @param view What of the extracted blocks to show; see {!apply_view}.
     @param path Vault-relative path of the target note to embed.
     @param extract A selector that narrows the target's blocks to the
       desired subset. Called on the target's top-level blocks after
@@ -180,10 +312,12 @@ let fallback_block (wl : Cmarkit.Inline.Wikilink.t) (meta : Cmarkit.Meta.t)
     @return [Some block] with the wrapped transclusion, or [None] if [path]
     was not found in [docs_tbl]. *)
 let rec embed_note
+          ?(slug_style : Parse.Heading_slug.slug_style option)
           ~(embed_depth : int)
           ~(max_depth : int)
           ~(depth_fallback : Cmarkit.Block.t)
           ~(fragment : Cmarkit.Inline.Wikilink.fragment option)
+          ~(view : view)
           (docs_tbl : (string, Cmarkit.Doc.t) Hashtbl.t)
           (path : string)
           (extract : Cmarkit.Block.t list -> Cmarkit.Block.t list)
@@ -197,13 +331,19 @@ let rec embed_note
     | Some target_doc ->
       let new_depth = embed_depth + 1 in
       let expanded =
-        expand_doc ~embed_depth:new_depth ~max_depth ~curr_path:path docs_tbl target_doc
+        expand_doc
+          ?slug_style
+          ~embed_depth:new_depth
+          ~max_depth
+          ~curr_path:path
+          docs_tbl
+          target_doc
       in
-      let blocks = extract (non_fm_blocks expanded) in
+      let blocks = apply_view ?slug_style view ~path (extract (non_fm_blocks expanded)) in
       let block_meta =
         Cmarkit.Meta.add
           embed_meta_key
-          { depth = new_depth; source_path = path; fragment }
+          { depth = new_depth; source_path = path; fragment; view }
           Cmarkit.Meta.none
       in
       Some (Cmarkit.Block.Blocks (blocks, block_meta)))
@@ -251,6 +391,7 @@ let rec embed_note
     @param doc The document whose embed wikilinks should be expanded. For
       self-references, this is also the source of the extracted blocks. *)
 and expand_doc
+      ?(slug_style : Parse.Heading_slug.slug_style option)
       ~(embed_depth : int)
       ~(max_depth : int)
       ~(curr_path : string)
@@ -264,6 +405,7 @@ and expand_doc
      original paragraph. *)
   let try_embed
         (meta : Cmarkit.Meta.t)
+        ~(view : view)
         ~(depth_fallback : Cmarkit.Block.t)
         ~(curr_doc : Cmarkit.Doc.t)
     : Cmarkit.Block.t option
@@ -281,11 +423,13 @@ and expand_doc
       then Some depth_fallback
       else (
         let new_depth = embed_depth + 1 in
-        let blocks_to_embed = extract (non_fm_blocks curr_doc) in
+        let blocks_to_embed =
+          apply_view ?slug_style view ~path:curr_path (extract (non_fm_blocks curr_doc))
+        in
         let block_meta =
           Cmarkit.Meta.add
             embed_meta_key
-            { depth = new_depth; source_path = curr_path; fragment }
+            { depth = new_depth; source_path = curr_path; fragment; view }
             Cmarkit.Meta.none
         in
         Some (Cmarkit.Block.Blocks (blocks_to_embed, block_meta)))
@@ -297,7 +441,16 @@ and expand_doc
           (extract : Cmarkit.Block.t list -> Cmarkit.Block.t list)
       : Cmarkit.Block.t option
       =
-      embed_note ~embed_depth ~max_depth ~depth_fallback ~fragment docs_tbl path extract
+      embed_note
+        ?slug_style
+        ~embed_depth
+        ~max_depth
+        ~depth_fallback
+        ~fragment
+        ~view
+        docs_tbl
+        path
+        extract
     in
     match Cmarkit.Meta.find Resolve.resolved_key meta with
     (* Non-embeddable: no target, unresolved, or non-markdown file. *)
@@ -344,7 +497,8 @@ and expand_doc
       ~block:(fun _mapper block ->
         match block with
         | Cmarkit.Block.Paragraph (p, _) ->
-          (match extract_embed_source (Cmarkit.Block.Paragraph.inline p) with
+          let inline = Cmarkit.Block.Paragraph.inline p in
+          (match extract_embed_source inline with
            | None -> Cmarkit.Mapper.default
            | Some source ->
              let meta, depth_fallback =
@@ -352,7 +506,8 @@ and expand_doc
                | Wikilink_embed (wl, wl_meta) -> wl_meta, fallback_block wl wl_meta
                | Image_embed meta -> meta, block
              in
-             (match try_embed meta ~depth_fallback ~curr_doc:doc with
+             let view = view_of_inline inline in
+             (match try_embed meta ~view ~depth_fallback ~curr_doc:doc with
               | Some spliced -> Cmarkit.Mapper.ret spliced
               | None -> Cmarkit.Mapper.default))
         | _ -> Cmarkit.Mapper.default)
@@ -364,13 +519,225 @@ and expand_doc
 (** Expand all embed wikilinks and image links in a list of resolved docs.
     [max_depth] (default 5) controls how many transclusion levels are allowed
     before falling back to a plain link (wikilinks) or keeping the original
-    image (image links). *)
-let expand_docs ?(max_depth = 5) (docs : (string * Cmarkit.Doc.t) list)
+    image (image links). [slug_style] slugs the headings of docs not parsed by
+    [Parse.of_string] for the links of a {!List} view; see
+    {!Parse.Heading_slug.of_meta}. *)
+let expand_docs
+      ?(slug_style : Parse.Heading_slug.slug_style option)
+      ?(max_depth = 5)
+      (docs : (string * Cmarkit.Doc.t) list)
   : (string * Cmarkit.Doc.t) list
   =
   let docs_tbl = Hashtbl.of_alist_exn (module String) docs in
   List.map docs ~f:(fun (rel_path, doc) ->
-    rel_path, expand_doc ~embed_depth:0 ~max_depth ~curr_path:rel_path docs_tbl doc)
+    ( rel_path
+    , expand_doc ?slug_style ~embed_depth:0 ~max_depth ~curr_path:rel_path docs_tbl doc ))
+;;
+
+(** {1 Checks}
+
+    Problems with embeds that expansion papers over: a cycle ([![[b]]] in [a],
+    [![[a]]] in [b]) or a chain deeper than [max_depth] expands until the
+    depth limit and ends in a plain link, and an embed of a note that isn't
+    published silently renders as a link to nowhere. {!check} reports them so
+    the build can warn or fail (see [Config.Embeds]). *)
+
+type problem =
+  | Cycle of string list
+  (** notes around the cycle, starting from the reported one and back to it *)
+  | Too_deep of string list (** the longest chain of embeds from the note *)
+  | Unpublished of string (** the embedded note, which is left off the site *)
+[@@deriving sexp_of]
+
+type issue =
+  { path : string (** the embedding note *)
+  ; line : int option
+  ; problem : problem
+  }
+[@@deriving sexp_of]
+
+(* Embeds of [doc] that expansion would act on: their 1-based line and
+   resolved target, with the wikilink for wikilink embeds. *)
+let embeds_of_doc (doc : Cmarkit.Doc.t)
+  : (int option * Resolve.target * Cmarkit.Inline.Wikilink.t option) list
+  =
+  let line_of (meta : Cmarkit.Meta.t) : int option =
+    let tl = Cmarkit.Meta.textloc meta in
+    if Cmarkit.Textloc.is_none tl
+    then None
+    else Some (fst (Cmarkit.Textloc.first_line tl))
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun _f acc block ->
+        match block with
+        | Cmarkit.Block.Paragraph (p, _) ->
+          (match extract_embed_source (Cmarkit.Block.Paragraph.inline p) with
+           | None -> Cmarkit.Folder.default
+           | Some source ->
+             let meta, wikilink =
+               match source with
+               | Wikilink_embed (wl, meta) -> meta, Some wl
+               | Image_embed meta -> meta, None
+             in
+             let target =
+               Cmarkit.Meta.find Resolve.resolved_key meta
+               |> Option.value ~default:Resolve.Unresolved
+             in
+             Cmarkit.Folder.ret ((line_of meta, target, wikilink) :: acc))
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  List.rev (Cmarkit.Folder.fold_doc folder [] doc)
+;;
+
+let target_path : Resolve.target -> string option = function
+  | Resolve.Note { path }
+  | Resolve.Heading { path; _ }
+  | Resolve.Block { path; _ }
+  | Resolve.Attr { path; _ } -> Some path
+  | Resolve.File _
+  | Resolve.Curr_file
+  | Resolve.Curr_heading _
+  | Resolve.Curr_block _
+  | Resolve.Curr_attr _
+  | Resolve.Unresolved -> None
+;;
+
+(** Problems with the embeds of resolved [docs]: cycles, chains of more than
+    [max_depth] embeds, and embeds of the notes in [unpublished] (those dropped
+    before resolution, so their embeds are unresolved). *)
+let check
+      ?(max_depth : int = 5)
+      ?(unpublished : string list = [])
+      (docs : (string * Cmarkit.Doc.t) list)
+  : issue list
+  =
+  let unpublished_index : Index.t =
+    { files =
+        List.map unpublished ~f:(fun rel_path : Index.file_entry ->
+          { rel_path; headings = []; blocks = []; attrs = [] })
+    ; dirs = []
+    }
+  in
+  (* Edges of the embed graph, by embedding note: line and embedded note. *)
+  let edges : (int option * string) list String.Map.t =
+    List.map docs ~f:(fun (path, doc) ->
+      ( path
+      , List.filter_map (embeds_of_doc doc) ~f:(fun (line, target, _) ->
+          Option.map (target_path target) ~f:(fun t -> line, t)) ))
+    |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+  in
+  let next (path : string) = Option.value (Map.find edges path) ~default:[] in
+  let line_to (path : string) (target : string) : int option =
+    List.find_map (next path) ~f:(fun (line, t) ->
+      if String.equal t target then Some line else None)
+    |> Option.join
+  in
+  (* Cycles, each reported once from its alphabetically first note. A note
+     is walked once; a cycle through it is found on that walk. *)
+  let cycles =
+    let found = Hash_set.create (module String) in
+    let walked = Hash_set.create (module String) in
+    let rec walk (stack : string list) (path : string) : string list list =
+      match List.findi stack ~f:(fun _ p -> String.equal p path) with
+      | Some (i, _) ->
+        let cycle = List.rev (List.take stack (i + 1)) in
+        let start =
+          List.min_elt cycle ~compare:String.compare |> Option.value ~default:path
+        in
+        let rotated =
+          let before, after =
+            List.split_while cycle ~f:(fun p -> not (String.equal p start))
+          in
+          after @ before
+        in
+        let key = String.concat ~sep:"\n" rotated in
+        if Hash_set.mem found key
+        then []
+        else (
+          Hash_set.add found key;
+          [ rotated @ [ start ] ])
+      | None when Hash_set.mem walked path -> []
+      | None ->
+        Hash_set.add walked path;
+        List.concat_map (next path) ~f:(fun (_, t) -> walk (path :: stack) t)
+    in
+    List.concat_map docs ~f:(fun (path, _) -> walk [] path)
+  in
+  let on_cycle =
+    String.Set.of_list (List.concat_map cycles ~f:(fun c -> List.drop_last_exn c))
+  in
+  (* Longest chain of embeds from each note off cycles, the note included. *)
+  let chains = String.Table.create () in
+  let rec chain (path : string) : string list =
+    match Hashtbl.find chains path with
+    | Some c -> c
+    | None ->
+      let longest =
+        List.filter_map (next path) ~f:(fun (_, t) ->
+          if Set.mem on_cycle t then None else Some (chain t))
+        |> List.max_elt ~compare:(fun a b -> Int.compare (List.length a) (List.length b))
+        |> Option.value ~default:[]
+      in
+      let c = path :: longest in
+      Hashtbl.set chains ~key:path ~data:c;
+      c
+  in
+  let too_deep =
+    List.filter_map docs ~f:(fun (path, _) ->
+      if Set.mem on_cycle path
+      then None
+      else (
+        let c = chain path in
+        if List.length c - 1 > max_depth
+        then (
+          let second = List.nth_exn c 1 in
+          Some { path; line = line_to path second; problem = Too_deep c })
+        else None))
+  in
+  let unpublished_embeds =
+    List.concat_map docs ~f:(fun (path, doc) ->
+      List.filter_map (embeds_of_doc doc) ~f:(fun (line, target, wikilink) ->
+        let embedded =
+          match target, wikilink with
+          | Resolve.Unresolved, Some wl ->
+            (match
+               Resolve.resolve
+                 { (Link_ref.of_wikilink wl) with fragment = None }
+                 path
+                 unpublished_index
+             with
+             | Resolve.Note { path } -> Some path
+             | _ -> None)
+          | _ ->
+            Option.filter (target_path target) ~f:(fun t ->
+              List.mem unpublished t ~equal:String.equal)
+        in
+        Option.map embedded ~f:(fun t -> { path; line; problem = Unpublished t })))
+  in
+  List.map cycles ~f:(fun c ->
+    let path = List.hd_exn c in
+    { path; line = line_to path (List.nth_exn c 1); problem = Cycle c })
+  @ too_deep
+  @ unpublished_embeds
+;;
+
+let issue_to_string_hum (i : issue) : string =
+  let where =
+    i.path ^ Option.value_map i.line ~default:"" ~f:(fun l -> ":" ^ Int.to_string l)
+  in
+  match i.problem with
+  | Cycle c -> sprintf "%s: embed cycle %s" where (String.concat ~sep:" -> " c)
+  | Too_deep c ->
+    sprintf
+      "%s: embeds nest %d deep (%s), past the limit; the deepest render as links"
+      where
+      (List.length c - 1)
+      (String.concat ~sep:" -> " c)
+  | Unpublished t -> sprintf "%s: embeds %s, which is not published" where t
 ;;
 
 (** Reverse transclusion: replace each [Block.Blocks] carrying {!embed_meta}
@@ -380,7 +747,8 @@ let expand_docs ?(max_depth = 5) (docs : (string * Cmarkit.Doc.t) list)
     wikilink and commonmark inline link, as noted in {!Spec.reverse_embed}).
 
     The [.md] extension is stripped from [source_path] to produce idiomatic
-    wikilink targets.  Nested embeds are reversed recursively — innermost
+    wikilink targets, and a {!view} other than {!Full} is written back as
+    [{as=VIEW}] after the wikilink.  Nested embeds are reversed recursively — innermost
     first, since the mapper walks depth-first. *)
 let reverse_embed_doc (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
   let strip_md (path : string) : string =
@@ -397,7 +765,7 @@ let reverse_embed_doc (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
         | Cmarkit.Block.Blocks (_, meta) ->
           (match Cmarkit.Meta.find embed_meta_key meta with
            | None -> Cmarkit.Mapper.default
-           | Some { source_path; fragment; _ } ->
+           | Some { source_path; fragment; view; _ } ->
              let target =
                if String.is_empty source_path then None else Some (strip_md source_path)
              in
@@ -405,9 +773,18 @@ let reverse_embed_doc (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
                Parse.Common.wikilink_of_fields ~target ~fragment ~display:None ~embed:true
              in
              let inline = Cmarkit.Inline.Ext_wikilink (wl, Cmarkit.Meta.none) in
+             let attribute =
+               match view with
+               | Full -> []
+               | Summary | List ->
+                 [ Cmarkit.Inline.Text
+                     ( sprintf "{%s=%s}" view_key (view_to_string view)
+                     , Cmarkit.Meta.none )
+                 ]
+             in
              let p =
                Cmarkit.Block.Paragraph.make
-                 (Cmarkit.Inline.Inlines ([ inline ], Cmarkit.Meta.none))
+                 (Cmarkit.Inline.Inlines (inline :: attribute, Cmarkit.Meta.none))
              in
              Cmarkit.Mapper.ret (Cmarkit.Block.Paragraph (p, Cmarkit.Meta.none)))
         | _ -> Cmarkit.Mapper.default)
@@ -475,3 +852,33 @@ let%expect_test "is_expandable_embed_paragraph: embed among other blocks" =
   printf "%b\n" (Option.is_some result);
   [%expect {| false |}]
 ;;
+
+let%expect_test "check" =
+  let docs =
+    [ "a.md", "![[b]]"
+    ; "b.md", "Intro.\n\n![[a]]"
+    ; "c.md", "![[d]]"
+    ; "d.md", "![[e]]"
+    ; "e.md", "![[f]]"
+    ; "f.md", "End."
+    ; "g.md", "![[private]]"
+    ; "h.md", "![[g]]"
+    ]
+    |> List.map ~f:(fun (p, src) -> p, Parse.of_string src)
+  in
+  let index : Index.t =
+    { files =
+        List.map docs ~f:(fun (rel_path, _) : Index.file_entry ->
+          { rel_path; headings = []; blocks = []; attrs = [] })
+    ; dirs = []
+    }
+  in
+  check ~max_depth:2 ~unpublished:[ "notes/private.md" ] (Resolve.resolve_docs docs index)
+  |> List.iter ~f:(fun i -> print_endline (issue_to_string_hum i));
+  [%expect
+    {|
+    a.md:1: embed cycle a.md -> b.md -> a.md
+    c.md:1: embeds nest 3 deep (c.md -> d.md -> e.md -> f.md), past the limit; the deepest render as links
+    g.md:1: embeds notes/private.md, which is not published
+    |}]
+;;
@@ -0,0 +1,91 @@
+(** Per-folder configuration: an [.oyster.toml] in a vault folder overrides
+    the site config for the notes in that folder and below. Its keys are
+    those of the site config (and of a note's [oyster] frontmatter field),
+    e.g. [publish.default = true] or [accent = "#e06c75"]. A note's settings
+    come from the site config, then each folder's file from the vault root
+    down, then its own frontmatter, later ones winning. *)
+
+open Core
+
+let file_name = ".oyster.toml"
+
+(** Folder (vault-relative, no trailing [/], [""] for the root) to the
+    overrides its [.oyster.toml] holds, outermost folders first. *)
+type t = (string * Yaml.value) list
+
+let empty : t = []
+
+(** Read the [.oyster.toml] of the root and of each of [dirs] (as in
+    {!Index.t.dirs}, with a trailing [/]) that has one; [read_file] gives a
+    vault-relative file's contents, [None] when it doesn't exist. Raises on a
+    file that isn't valid TOML or sets a {!Config.site_only_keys} key. *)
+let load ~(read_file : string -> string option) (dirs : string list) : t =
+  ""
+  :: List.map dirs ~f:(fun d -> String.chop_suffix_if_exists d ~suffix:"/")
+  |> List.dedup_and_sort ~compare:String.compare
+  |> List.filter_map ~f:(fun dir ->
+    let path = if String.is_empty dir then file_name else dir ^ "/" ^ file_name in
+    Option.map (read_file path) ~f:(fun src ->
+      match Parse.Toml_lite.of_string src with
+      | Ok overrides ->
+        (match Config.site_only_keys_in overrides with
+         | [] -> dir, overrides
+         | keys ->
+           failwithf
+             "%s: %s can only be set in the site config"
+             path
+             (String.concat ~sep:", " keys)
+             ())
+      | Error msg -> failwithf "%s: %s" path msg ()))
+;;
+
+(** Overrides applying to the note at [path], outermost first. *)
+let overrides (t : t) (path : string) : Yaml.value list =
+  List.filter_map t ~f:(fun (dir, overrides) ->
+    if String.is_empty dir || String.is_prefix path ~prefix:(dir ^ "/")
+    then Some overrides
+    else None)
+;;
+
+(** [base] with the folder overrides of the note at [path] applied. *)
+let config ~(base : Config.t) (t : t) (path : string) : Config.t =
+  List.fold (overrides t path) ~init:base ~f:(fun default overrides ->
+    Config.of_yaml ~default overrides)
+;;
+
+(** Effective config of the note at [path], parsed as [doc]: [base], then the
+    folder overrides, then the note's frontmatter [oyster] field. *)
+let note_config ~(base : Config.t) (t : t) (path : string) (doc : Cmarkit.Doc.t)
+  : Config.t
+  =
+  Config.of_frontmatter ~default:(config ~base t path) (Parse.Frontmatter.of_doc doc)
+;;
+
+let%expect_test "config cascade" =
+  let files =
+    [ ".oyster.toml", "accent = \"#e06c75\"\n"
+    ; "blog/.oyster.toml", "[publish]\ndefault = true\n"
+    ; "blog/drafts/.oyster.toml", "publish.default = false\naccent = \"teal\"\n"
+    ]
+  in
+  let t =
+    load
+      ~read_file:(List.Assoc.find files ~equal:String.equal)
+      [ "blog/"; "blog/drafts/"; "notes/" ]
+  in
+  List.iter [ "a.md"; "blog/post.md"; "blog/drafts/wip.md"; "blogroll/b.md" ] ~f:(fun p ->
+    let c = config ~base:Config.default t p in
+    printf "%s: accent=%s publish=%b\n" p c.accent c.publish.default);
+  [%expect
+    {|
+    a.md: accent=#e06c75 publish=false
+    blog/post.md: accent=#e06c75 publish=true
+    blog/drafts/wip.md: accent=teal publish=false
+    blogroll/b.md: accent=#e06c75 publish=false
+    |}];
+  let files = [ "blog/.oyster.toml", "[hooks]\nafter_build = [\"./deploy.sh\"]\n" ] in
+  (match load ~read_file:(List.Assoc.find files ~equal:String.equal) [ "blog/" ] with
+   | _ -> print_endline "loaded"
+   | exception Failure msg -> print_endline msg);
+  [%expect {| blog/.oyster.toml: hooks can only be set in the site config |}]
+;;
@@ -0,0 +1,151 @@
+(** A glossary note (see {!Config.Glossary}) and the marking of its terms in
+    other notes.
+
+    Each heading of the glossary note followed by a paragraph is a term, the
+    paragraph its definition. In a note, the first occurrence of each term
+    in text (as written, as a whole word; not in headings, links or code)
+    carries a {!mark} in its meta, for the HTML renderer to annotate. *)
+
+open Core
+
+type entry =
+  { term : string
+  ; definition : string
+  ; path : string (** of the glossary note *)
+  ; slug : string (** of the term's heading *)
+  }
+
+type mark =
+  { entry : entry
+  ; link : bool (** link the term to its heading, besides the [<abbr>] *)
+  }
+
+let meta_key : mark Cmarkit.Meta.key = Cmarkit.Meta.key ()
+
+(** Frontmatter field that, set to [false], keeps a note's terms unmarked. *)
+let key = "glossary"
+
+let enabled (doc : Cmarkit.Doc.t) : bool =
+  match Parse.Frontmatter.field doc key with
+  | Some (Parse.Value.Bool false) -> false
+  | _ -> true
+;;
+
+(** The terms of the glossary note [path], parsed as [doc]. *)
+let entries
+      ?(slug_style : Parse.Heading_slug.slug_style option)
+      ~(path : string)
+      (doc : Cmarkit.Doc.t)
+  : entry list
+  =
+  let blocks =
+    match Cmarkit.Doc.block doc with
+    | Cmarkit.Block.Blocks (blocks, _) -> blocks
+    | b -> [ b ]
+  in
+  let plain = Parse.Heading_slug.inline_to_plain_text in
+  let rec go : Cmarkit.Block.t list -> entry list = function
+    | Cmarkit.Block.Heading (h, meta) :: Cmarkit.Block.Paragraph (p, _) :: rest ->
+      let term = String.strip (plain (Cmarkit.Block.Heading.inline h)) in
+      let slug = Parse.Heading_slug.of_meta ?style:slug_style meta term in
+      let definition = String.strip (plain (Cmarkit.Block.Paragraph.inline p)) in
+      if String.is_empty term
+      then go rest
+      else { term; definition; path; slug } :: go rest
+    | _ :: rest -> go rest
+    | [] -> []
+  in
+  go blocks
+;;
+
+(* First whole-word occurrence of [term] in [s]. *)
+let find_word (s : string) (term : string) : int option =
+  let boundary (i : int) : bool =
+    i < 0 || i >= String.length s || not (Char.is_alphanum s.[i])
+  in
+  String.substr_index_all s ~may_overlap:false ~pattern:term
+  |> List.find ~f:(fun i -> boundary (i - 1) && boundary (i + String.length term))
+;;
+
+(** [doc] with the first occurrence of each of [entries] marked; see {!mark}. *)
+let mark ~(link : bool) (entries : entry list) (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
+  let pending = ref entries in
+  (* [s] split around its earliest term (the longest one, among those starting
+     there), then the rest likewise. *)
+  let rec split (s : string) (meta : Cmarkit.Meta.t) : Cmarkit.Inline.t list =
+    let first =
+      List.filter_map !pending ~f:(fun e ->
+        Option.map (find_word s e.term) ~f:(fun i -> i, e))
+      |> List.min_elt ~compare:(fun (i, a) (j, b) ->
+        match Int.compare i j with
+        | 0 -> Int.compare (String.length b.term) (String.length a.term)
+        | c -> c)
+    in
+    match first with
+    | None -> [ Cmarkit.Inline.Text (s, meta) ]
+    | Some (i, entry) ->
+      pending := List.filter !pending ~f:(fun e -> not (String.equal e.term entry.term));
+      let stop = i + String.length entry.term in
+      (if i > 0 then [ Cmarkit.Inline.Text (String.prefix s i, meta) ] else [])
+      @ [ Cmarkit.Inline.Text
+            (entry.term, Cmarkit.Meta.add meta_key { entry; link } meta)
+        ]
+      @ if stop < String.length s then split (String.drop_prefix s stop) meta else []
+  in
+  let mapper =
+    Cmarkit.Mapper.make
+      ~inline_ext_default:(fun _m i -> Some i)
+      ~block_ext_default:(fun _m b -> Some b)
+      ~block:(fun _m (b : Cmarkit.Block.t) ->
+        match b with
+        | Cmarkit.Block.Heading _ -> Cmarkit.Mapper.ret b
+        | _ -> Cmarkit.Mapper.default)
+      ~inline:(fun _m (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Text (s, meta) when not (List.is_empty !pending) ->
+          (match split s meta with
+           | [ _ ] -> Cmarkit.Mapper.default
+           | parts ->
+             Cmarkit.Mapper.ret (Cmarkit.Inline.Inlines (parts, Cmarkit.Meta.none)))
+        | Cmarkit.Inline.Link _ | Cmarkit.Inline.Image _ | Cmarkit.Inline.Ext_wikilink _
+          -> Cmarkit.Mapper.ret i
+        | _ -> Cmarkit.Mapper.default)
+      ()
+  in
+  Cmarkit.Mapper.map_doc mapper doc
+;;
+
+let%expect_test "mark" =
+  let glossary =
+    Parse.of_string
+      "# Glossary\n\n## API\n\nApplication programming interface.\n\n\
+       ## Oyster card\n\nA London travel card.\n\n## Empty\n\n- no definition\n"
+  in
+  let entries = entries ~path:"glossary.md" glossary in
+  List.iter entries ~f:(fun e ->
+    printf "%s#%s %s: %s\n" e.path e.slug e.term e.definition);
+  [%expect
+    {|
+    glossary.md#api API: Application programming interface.
+    glossary.md#oyster-card Oyster card: A London travel card.
+    |}];
+  let doc =
+    Parse.of_string
+      "## API notes\n\nAn API and an Oyster card; the API again.\n\n\
+       [the API](x) and RAPID APIs, `API` in code.\n"
+    |> mark ~link:false entries
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ~inline:(fun _f acc (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Text (s, meta) when Cmarkit.Meta.mem meta_key meta ->
+          Cmarkit.Folder.ret (s :: acc)
+        | _ -> Cmarkit.Folder.default)
+      ()
+  in
+  print_s [%sexp (List.rev (Cmarkit.Folder.fold_doc folder [] doc) : string list)];
+  [%expect {| (API "Oyster card") |}]
+;;
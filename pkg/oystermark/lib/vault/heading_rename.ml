@@ -0,0 +1,156 @@
+(** Rename a heading and the links pointing at it.
+
+    Links are matched by what they resolve to ({!Resolve.resolve}) against the
+    index from {e before} the edit, so every spelling of a reference is found:
+    [[[Note#Old]]], nested forms such as [[[Note#Parent#Old]]] and
+    [[Note.md#Parent#Old]], fragment-only links inside the note ([[[#Old]]])
+    and [[label]: Note.md#Old] definitions. Only the last fragment component,
+    the one naming the heading, is replaced; parent components, aliases and
+    link text stay as written. *)
+
+open Core
+
+(** Heading of note [path] that [query] ([Old] or a nested [Parent#Old]) names,
+    as links resolve it. *)
+let find_heading (index : Index.t) ~(path : string) (query : string)
+  : Index.heading_entry option
+  =
+  let open Option.Let_syntax in
+  let%bind file =
+    List.find index.files ~f:(fun (f : Index.file_entry) -> String.equal f.rel_path path)
+  in
+  Resolve.resolve_headings file.headings (String.split query ~on:'#')
+;;
+
+(* Whether [target] is heading [heading] of note [path], seen from [curr_file].
+   Slugs are unique within a note. *)
+let targets
+      ~(curr_file : string)
+      ~(path : string)
+      ~(heading : Index.heading_entry)
+      (target : Resolve.target)
+  : bool
+  =
+  match target with
+  | Resolve.Heading { path = p; slug; _ } ->
+    String.equal p path && String.equal slug heading.slug
+  | Resolve.Curr_heading { slug; _ } ->
+    String.equal curr_file path && String.equal slug heading.slug
+  | _ -> false
+;;
+
+(* Edit replacing the text of [heading] in its source line, when it is written
+   there verbatim (not through inline markup). *)
+let definition_edit (src : string) ~(heading : Index.heading_entry) ~(new_text : string)
+  : (int * int * string) option
+  =
+  let open Option.Let_syntax in
+  let%bind loc = heading.loc in
+  let first = Cmarkit.Textloc.first_byte loc in
+  let stop =
+    String.index_from src first '\n' |> Option.value ~default:(String.length src)
+  in
+  let line = String.sub src ~pos:first ~len:(stop - first) in
+  let%map pos = String.substr_index line ~pattern:heading.text in
+  first + pos, first + pos + String.length heading.text, new_text
+;;
+
+(** [src] of note [curr_file] with every link to [heading] of note [path]
+    pointing at [new_text] instead, and how many links changed. When
+    [curr_file] is [path] the heading itself is renamed too. [index] is the
+    vault index before the rename; [lookup], [prefer] and [relative] are as
+    for {!Resolve.resolve}; [config] sets the parse options. *)
+let rename
+      ?(config : Config.t option)
+      ?(lookup : Index.Lookup.t option)
+      ?(prefer : Config.Link_preference.t option)
+      ?(relative : bool option)
+      (index : Index.t)
+      ~(curr_file : string)
+      ~(path : string)
+      ~(heading : Index.heading_entry)
+      ~(new_text : string)
+      (src : string)
+  : string * int
+  =
+  let refs =
+    Link_rewrite.links ?config src
+    |> List.filter_map ~f:(fun (l : Link_rewrite.link) ->
+      match l.link_ref with
+      | Some ({ fragment = Some (Link_ref.Heading _); _ } as link_ref) ->
+        let markdown =
+          match l.style with
+          | `Markdown -> true
+          | `Wikilink -> false
+        in
+        let target =
+          Resolve.resolve ?lookup ?prefer ~markdown ?relative link_ref curr_file index
+        in
+        if targets ~curr_file ~path ~heading target
+        then (
+          let prefix =
+            String.rsplit2 l.dest ~on:'#' |> Option.value_map ~default:"" ~f:fst
+          in
+          let dest = prefix ^ "#" ^ new_text in
+          let text =
+            match l.style with
+            | `Wikilink -> dest
+            | `Markdown -> Link_rewrite.percent_encode dest
+          in
+          Some (l.first_byte, l.stop_byte, text))
+        else None
+      | _ -> None)
+  in
+  let definition =
+    if String.equal curr_file path
+    then Option.to_list (definition_edit src ~heading ~new_text)
+    else []
+  in
+  Link_rewrite.splice src (definition @ refs), List.length refs
+;;
+
+let%expect_test "rename" =
+  let files =
+    [ "Note.md", "# Guide\n\n## Usage\n\n## Setup\n\n### Usage\n\nSee [[#Setup#Usage]].\n"
+    ; ( "other.md"
+      , "[[Note#Usage]], [[Note#Setup#Usage|how]], [x](Note.md#Guide#Setup#Usage) \
+         and [ref][r].\n\n\
+         [r]: Note.md#Usage\n" )
+    ]
+  in
+  let index : Index.t =
+    { files =
+        List.map files ~f:(fun (rel_path, src) : Index.file_entry ->
+          { rel_path
+          ; headings = Index.extract_headings (Parse.of_string ~locs:true src)
+          ; blocks = []
+          ; attrs = []
+          })
+    ; dirs = []
+    }
+  in
+  (* The nested [### Usage], not the first [## Usage]. *)
+  let heading = Option.value_exn (find_heading index ~path:"Note.md" "Setup#Usage") in
+  List.iter files ~f:(fun (curr_file, src) ->
+    let src, n =
+      rename index ~curr_file ~path:"Note.md" ~heading ~new_text:"First steps" src
+    in
+    printf "== %s: %d\n%s" curr_file n src);
+  [%expect
+    {|
+    == Note.md: 1
+    # Guide
+
+    ## Usage
+
+    ## Setup
+
+    ### First steps
+
+    See [[#Setup#First steps]].
+    == other.md: 2
+    [[Note#Usage]], [[Note#Setup#First steps|how]], [x](Note.md#Guide#Setup#First%20steps) and [ref][r].
+
+    [r]: Note.md#Usage
+    |}]
+;;
@@ -1,4 +1,10 @@
-(** Vault index: walks a vault directory and indexes files, headings, and block IDs. *)
+(** Vault index: walks a vault directory and indexes files, headings, and block IDs.
+
+    There is no borrowed or rope-backed form of the index or of {!Query.Section}:
+    OCaml strings are immutable and shared, so neither copies note text. An
+    entry holds only what it derives, e.g. a heading's plain [text]. A loaded
+    vault costs its parsed documents, each note's source being dropped once
+    parsed (see {!Vault.of_root_path}). *)
 
 open Core
 open Parse
@@ -8,7 +14,9 @@ type heading_entry =
   ; level : int
   ; slug : string
     (** GitHub-style anchor: lowercase, punctuation stripped, deduped with [-1], [-2], etc. *)
+  ; style : Heading_slug.style
   ; loc : Cmarkit.Textloc.t option
+    (** For a setext heading, spans the text and its underline. *)
   }
 
 type block_entry =
@@ -41,7 +49,9 @@ type t =
 
 (* Use Cmarkit.Folder to extract headings from a document.
    Reads slugs from heading block meta (stamped during parsing). *)
-let extract_headings (doc : Cmarkit.Doc.t) : heading_entry list =
+let extract_headings ?(slug_style : Heading_slug.slug_style option) (doc : Cmarkit.Doc.t)
+  : heading_entry list
+  =
   let folder =
     Cmarkit.Folder.make
       ~block:(fun _f acc block ->
@@ -49,23 +59,19 @@ let extract_headings (doc : Cmarkit.Doc.t) : heading_entry list =
         | Cmarkit.Block.Heading (h, meta) ->
           let level = Cmarkit.Block.Heading.level h in
           let text = Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h) in
-          let slug =
-            Cmarkit.Meta.find Heading_slug.meta_key meta
-            |> Option.value_exn
-                 ~message:
-                   "heading missing slug meta — doc must be parsed via Parse.of_string"
-          in
+          let slug = Heading_slug.of_meta ?style:slug_style meta text in
           let loc =
             let tl = Cmarkit.Meta.textloc meta in
             if Cmarkit.Textloc.is_none tl then None else Some tl
           in
-          Cmarkit.Folder.ret (acc @ [ { text; level; slug; loc } ])
+          let style = Heading_slug.style_of h in
+          Cmarkit.Folder.ret ({ text; level; slug; style; loc } :: acc)
         | _ -> Cmarkit.Folder.default)
       ~inline_ext_default:(fun _f acc _i -> acc)
       ~block_ext_default:(fun _f acc _b -> acc)
       ()
   in
-  Cmarkit.Folder.fold_doc folder [] doc
+  List.rev (Cmarkit.Folder.fold_doc folder [] doc)
 ;;
 
 (* Use Cmarkit.Folder to extract block IDs from a document. *)
@@ -86,14 +92,14 @@ let extract_block_ids (doc : Cmarkit.Doc.t) : block_entry list =
                if Cmarkit.Textloc.is_none tl then None else Some tl
              in
              Cmarkit.Folder.ret
-               (acc @ [ ({ id = Cmarkit.Block.Block_id.id bid; loc } : block_entry) ])
+               (({ id = Cmarkit.Block.Block_id.id bid; loc } : block_entry) :: acc)
            | None -> Cmarkit.Folder.default)
         | _ -> Cmarkit.Folder.default)
       ~inline_ext_default:(fun _f acc _i -> acc)
       ~block_ext_default:(fun _f acc _b -> acc)
       ()
   in
-  Cmarkit.Folder.fold_doc folder [] doc
+  List.rev (Cmarkit.Folder.fold_doc folder [] doc)
 ;;
 
 (* Extract explicit djot attribute ids ([{#id}]) from a document.
@@ -166,6 +172,39 @@ let rec list_entries_recursive ~(root : string) ~(rel_prefix : string) : string
       | _ -> [ rel_path ]))
 ;;
 
+(** Hash lookups over {!t.files} for link resolution, which otherwise scans
+    every file per link. Build once per index and pass it along. *)
+module Lookup = struct
+  type t =
+    { by_path : file_entry String.Table.t
+    ; by_component : file_entry list String.Table.t
+      (** lowercase path component (folder or file name) to the files having
+          it, in index order *)
+    }
+
+  let create (files : file_entry list) : t =
+    let by_path = String.Table.create () in
+    let by_component = String.Table.create () in
+    List.iter (List.rev files) ~f:(fun (f : file_entry) ->
+      Hashtbl.set by_path ~key:f.rel_path ~data:f;
+      String.split f.rel_path ~on:'/'
+      |> List.map ~f:String.lowercase
+      |> List.dedup_and_sort ~compare:String.compare
+      |> List.iter ~f:(fun key -> Hashtbl.add_multi by_component ~key ~data:f));
+    { by_path; by_component }
+  ;;
+
+  let find_path (t : t) (rel_path : string) : file_entry option =
+    Hashtbl.find t.by_path rel_path
+  ;;
+
+  (** Files with a path component equal to [name] ignoring case, in index
+      order: a superset of those a link ending in [name] can resolve to. *)
+  let with_component (t : t) (name : string) : file_entry list =
+    Hashtbl.find_multi t.by_component (String.lowercase name)
+  ;;
+end
+
 let%expect_test "extract_headings" =
   let md =
     {|
@@ -194,6 +233,42 @@ let%expect_test "extract_headings" =
     |}]
 ;;
 
+let%expect_test "extract_headings: doc not from Parse.of_string" =
+  let doc = Cmarkit.Doc.of_string "# Plain *doc*\n\n## Plain doc\n" in
+  List.iter (extract_headings doc) ~f:(fun (h : heading_entry) ->
+    printf "H%d: %s [%s]\n" h.level h.text h.slug);
+  [%expect
+    {|
+    H1: Plain doc [plain-doc]
+    H2: Plain doc [plain-doc]
+    |}]
+;;
+
+let%expect_test "extract_headings: setext" =
+  let md = "Title\n=====\n\ntext\n\nChapter\n-------\n\n### Atx\n" in
+  let headings = extract_headings (Parse.of_string md) in
+  List.iter headings ~f:(fun (h : heading_entry) ->
+    let lines =
+      Option.value_map h.loc ~default:"-" ~f:(fun tl ->
+        sprintf
+          "%d-%d"
+          (fst (Cmarkit.Textloc.first_line tl))
+          (fst (Cmarkit.Textloc.last_line tl)))
+    in
+    printf
+      "H%d %s: %s [%s]\n"
+      h.level
+      (Sexp.to_string (Heading_slug.sexp_of_style h.style))
+      h.text
+      lines);
+  [%expect
+    {|
+    H1 Setext: Title [1-2]
+    H2 Setext: Chapter [6-7]
+    H3 Atx: Atx [9-9]
+    |}]
+;;
+
 let%expect_test "extract_block_ids" =
   let md =
     {|
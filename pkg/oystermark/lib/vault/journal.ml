@@ -0,0 +1,213 @@
+(** Periodic notes: the daily and weekly notes of a journal, told by their
+    path under the patterns of {!Config.Journal}. [oystermark query --journal]
+    lists those in a date range, and the journal pages of the site lay them
+    out as calendars. *)
+
+open Core
+
+type period =
+  | Day
+  | Week
+[@@deriving sexp_of, equal]
+
+type entry =
+  { path : string
+  ; period : period
+  ; date : Date.t (** the day, or the Monday of the week *)
+  }
+[@@deriving sexp_of]
+
+(** Last day [e] covers. *)
+let last_day (e : entry) : Date.t =
+  match e.period with
+  | Day -> e.date
+  | Week -> Date.add_days e.date 6
+;;
+
+let month_name (m : Month.t) : string =
+  match m with
+  | Jan -> "January"
+  | Feb -> "February"
+  | Mar -> "March"
+  | Apr -> "April"
+  | May -> "May"
+  | Jun -> "June"
+  | Jul -> "July"
+  | Aug -> "August"
+  | Sep -> "September"
+  | Oct -> "October"
+  | Nov -> "November"
+  | Dec -> "December"
+;;
+
+type fields =
+  { year : int option
+  ; month : int option
+  ; day : int option
+  ; week : int option
+  }
+
+let tokens = [ "YYYY", 4; "MM", 2; "DD", 2; "ww", 2 ]
+
+(* [s] read by [pattern]: a token takes as many digits as it is long, other
+   characters match themselves. *)
+let read ~(pattern : string) (s : string) : fields option =
+  let rec go (p : int) (i : int) (f : fields) : fields option =
+    if p = String.length pattern
+    then Option.some_if (i = String.length s) f
+    else (
+      match
+        List.find tokens ~f:(fun (token, _) ->
+          String.is_substring_at pattern ~pos:p ~substring:token)
+      with
+      | Some (token, width) ->
+        if i + width <= String.length s
+           && String.for_all (String.sub s ~pos:i ~len:width) ~f:Char.is_digit
+        then (
+          let n = Some (Int.of_string (String.sub s ~pos:i ~len:width)) in
+          let f =
+            match token with
+            | "YYYY" -> { f with year = n }
+            | "MM" -> { f with month = n }
+            | "DD" -> { f with day = n }
+            | _ -> { f with week = n }
+          in
+          go (p + String.length token) (i + width) f)
+        else None
+      | None ->
+        if i < String.length s && Char.equal pattern.[p] s.[i]
+        then go (p + 1) (i + 1) f
+        else None)
+  in
+  go 0 0 { year = None; month = None; day = None; week = None }
+;;
+
+(* The end of note [path], without [.md], that [pattern] covers: as many path
+   segments as [pattern] has. *)
+let path_end ~(pattern : string) (path : string) : string =
+  let segments = 1 + String.count pattern ~f:(Char.equal '/') in
+  let parts = String.split (String.chop_suffix_if_exists path ~suffix:".md") ~on:'/' in
+  List.drop parts (List.length parts - segments) |> String.concat ~sep:"/"
+;;
+
+(** Monday of ISO week [week] of ISO week-numbering year [year]. *)
+let week_start ~(year : int) ~(week : int) : Date.t option =
+  let%bind.Option jan4 =
+    Option.try_with (fun () -> Date.create_exn ~y:year ~m:Month.Jan ~d:4)
+  in
+  let monday =
+    Date.add_days
+      jan4
+      (1 - Day_of_week.iso_8601_weekday_number (Date.day_of_week jan4))
+  in
+  let start = Date.add_days monday (7 * (week - 1)) in
+  Option.some_if (week >= 1 && snd (Date.week_number_and_year start) = year) start
+;;
+
+(** The entry of note [path], if it is a daily or weekly note. *)
+let of_path ~(config : Config.Journal.t) (path : string) : entry option =
+  let folder = String.strip config.folder ~drop:(Char.equal '/') in
+  if (not (String.is_suffix path ~suffix:".md"))
+     || not (String.is_empty folder || String.is_prefix path ~prefix:(folder ^ "/"))
+  then None
+  else (
+    let read_with (pattern : string) : fields option =
+      if String.is_empty pattern then None else read ~pattern (path_end ~pattern path)
+    in
+    let daily =
+      match read_with config.daily with
+      | Some { year = Some y; month = Some m; day = Some d; _ } ->
+        Option.try_with (fun () -> Date.create_exn ~y ~m:(Month.of_int_exn m) ~d)
+        |> Option.map ~f:(fun date -> { path; period = Day; date })
+      | _ -> None
+    in
+    match daily with
+    | Some _ -> daily
+    | None ->
+      (match read_with config.weekly with
+       | Some { year = Some year; week = Some week; _ } ->
+         week_start ~year ~week
+         |> Option.map ~f:(fun date -> { path; period = Week; date })
+       | _ -> None))
+;;
+
+(** The entries among note [paths], by date; on the same date, days before
+    weeks. *)
+let of_paths ~(config : Config.Journal.t) (paths : string list) : entry list =
+  List.filter_map paths ~f:(of_path ~config)
+  |> List.sort ~compare:(fun a b ->
+    match Date.compare a.date b.date with
+    | 0 ->
+      (match a.period, b.period with
+       | Day, Week -> -1
+       | Week, Day -> 1
+       | _ -> String.compare a.path b.path)
+    | c -> c)
+;;
+
+(** The entries of [entries] covering a day from [from] to [until], both
+    included; a missing end is open. *)
+let in_range ?(from : Date.t option) ?(until : Date.t option) (entries : entry list)
+  : entry list
+  =
+  List.filter entries ~f:(fun e ->
+    Option.for_all from ~f:(fun from -> Date.( >= ) (last_day e) from)
+    && Option.for_all until ~f:(fun until -> Date.( <= ) e.date until))
+;;
+
+(** A date range as [FROM..TO], either end possibly empty, or a single
+    [DATE]; dates as [YYYY-MM-DD]. *)
+let range_of_string (s : string) : (Date.t option * Date.t option) Or_error.t =
+  let date (s : string) : Date.t option Or_error.t =
+    match String.strip s with
+    | "" -> Ok None
+    | s ->
+      Or_error.try_with (fun () -> Some (Date.of_string s))
+      |> Or_error.tag ~tag:(sprintf "bad date %S, expected YYYY-MM-DD" s)
+  in
+  match String.substr_index s ~pattern:".." with
+  | Some i ->
+    let%bind.Or_error from = date (String.prefix s i) in
+    let%map.Or_error until = date (String.drop_prefix s (i + 2)) in
+    from, until
+  | None ->
+    let%map.Or_error d = date s in
+    d, d
+;;
+
+let%expect_test "of_paths and in_range" =
+  let config = Config.Journal.default in
+  let entries =
+    of_paths
+      ~config
+      [ "daily/2024-05-02.md"
+      ; "2024-W18.md"
+      ; "2024-05-01.md"
+      ; "2024-02-30.md"
+      ; "notes/2024-05-01 plan.md"
+      ; "2025-W01.md"
+      ]
+  in
+  List.iter entries ~f:(fun e ->
+    printf !"%{Date} %{Sexp} %s\n" e.date (sexp_of_period e.period) e.path);
+  [%expect
+    {|
+    2024-04-29 Week 2024-W18.md
+    2024-05-01 Day 2024-05-01.md
+    2024-05-02 Day daily/2024-05-02.md
+    2024-12-30 Week 2025-W01.md
+    |}];
+  let range = Or_error.ok_exn (range_of_string "2024-05-02..") in
+  in_range ?from:(fst range) ?until:(snd range) entries
+  |> List.iter ~f:(fun e -> print_endline e.path);
+  [%expect
+    {|
+    2024-W18.md
+    daily/2024-05-02.md
+    2025-W01.md
+    |}];
+  let config = { config with daily = "YYYY/MM/DD"; folder = "journal" } in
+  of_paths ~config [ "journal/2024/05/01.md"; "2024/05/02.md" ]
+  |> List.iter ~f:(fun e -> print_endline e.path);
+  [%expect {| journal/2024/05/01.md |}]
+;;
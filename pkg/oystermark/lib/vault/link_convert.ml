@@ -0,0 +1,332 @@
+(** Convert links between wikilink and Markdown syntax, for [oyster links
+    convert].
+
+    Display text, aliases, embed markers and heading/block fragments carry over:
+    - [[[Note 2#Usage|setup]]] and [[setup](Note%202.md#Usage)] convert into
+      each other;
+    - a wikilink without an alias becomes a Markdown link whose text is what
+      the wikilink displays ([Note 2#Usage]), and converts back to the bare
+      wikilink;
+    - [![[diagram.png|300]]] and [![300](diagram.png)] convert into each other;
+      both read the [300] as a width.
+
+    Targets go through the vault index, so a converted link names the file the
+    original resolved to: wikilinks are matched vault-wide and become Markdown
+    links relative to the note ([[[Ideas]]] in [daily/today.md] becomes
+    [[Ideas](../projects/Ideas.md)]), and a Markdown link becomes the shortest
+    wikilink naming its file (its name alone when that resolves back to it).
+    Unresolved links keep their target as written, Markdown ones starting
+    with [./] or [../] joined to the note's folder first.
+
+    Markdown links that have no wikilink form are left as they are: external
+    and reference-style links ([[text][label]]), links with a title, links
+    leading out of the vault, and links whose text contains [|], [\]\]] or a
+    line break. *)
+
+open Core
+
+type syntax =
+  | Wiki
+  | Markdown
+[@@deriving sexp, equal, compare]
+
+let syntax_to_string : syntax -> string = function
+  | Wiki -> "wiki"
+  | Markdown -> "markdown"
+;;
+
+let syntax_of_string : string -> syntax option = function
+  | "wiki" | "wikilink" -> Some Wiki
+  | "markdown" | "md" -> Some Markdown
+  | _ -> None
+;;
+
+(** Text a wikilink displays when it has no alias. *)
+let wikilink_default_display (w : Cmarkit.Inline.Wikilink.t) : string =
+  let fragment =
+    match Cmarkit.Inline.Wikilink.fragment w with
+    | None -> ""
+    | Some (Cmarkit.Inline.Wikilink.Heading hs) -> "#" ^ String.concat ~sep:"#" hs
+    | Some (Cmarkit.Inline.Wikilink.Block_ref b) -> "#^" ^ b
+  in
+  let fragment =
+    match Cmarkit.Inline.Wikilink.target w with
+    | None -> String.chop_prefix_if_exists fragment ~prefix:"#"
+    | Some _ -> fragment
+  in
+  Option.value (Cmarkit.Inline.Wikilink.target w) ~default:"" ^ fragment
+;;
+
+(* Backslash-escape what would end the link text or, leading, turn it into a
+   footnote reference ([[^label]]). *)
+let escape_link_text (s : string) : string =
+  String.concat_mapi s ~f:(fun i c ->
+    match c with
+    | '[' | ']' | '\\' -> "\\" ^ String.of_char c
+    | '^' when i = 0 -> "\\^"
+    | c -> String.of_char c)
+;;
+
+let unescape_link_text (s : string) : string =
+  let buf = Buffer.create (String.length s) in
+  let rec go i =
+    if i < String.length s
+    then
+      if Char.equal s.[i] '\\'
+         && i + 1 < String.length s
+         && Char.is_punctuation s.[i + 1]
+      then (
+        Buffer.add_char buf s.[i + 1];
+        go (i + 2))
+      else (
+        Buffer.add_char buf s.[i];
+        go (i + 1))
+  in
+  go 0;
+  Buffer.contents buf
+;;
+
+(** Markdown form of wikilink [w], linking to [dest] (the file it resolves
+    to, relative to the note) when given. Otherwise notes get their [.md]
+    extension back. *)
+let markdown_of_wikilink ?(dest : string option) (w : Cmarkit.Inline.Wikilink.t)
+  : string
+  =
+  let target =
+    match dest, Cmarkit.Inline.Wikilink.target w with
+    | Some dest, _ -> Link_rewrite.percent_encode dest
+    | None, None -> ""
+    | None, Some t ->
+      let t =
+        if Option.is_none (snd (Filename.split_extension (Filename.basename t)))
+        then t ^ ".md"
+        else t
+      in
+      Link_rewrite.percent_encode t
+  in
+  let fragment =
+    match Cmarkit.Inline.Wikilink.fragment w with
+    | None -> ""
+    | Some (Cmarkit.Inline.Wikilink.Heading hs) ->
+      "#" ^ String.concat ~sep:"#" (List.map hs ~f:Link_rewrite.percent_encode)
+    | Some (Cmarkit.Inline.Wikilink.Block_ref b) -> "#^" ^ Link_rewrite.percent_encode b
+  in
+  let display =
+    Option.value (Cmarkit.Inline.Wikilink.display w) ~default:(wikilink_default_display w)
+  in
+  sprintf
+    "%s[%s](%s%s)"
+    (if Cmarkit.Inline.Wikilink.embed w then "!" else "")
+    (escape_link_text display)
+    target
+    fragment
+;;
+
+(** Wikilink form of the Markdown link [slice] (its full source text) to
+    wikilink target [target] (no [.md]) and percent-decoded [fragment] (with
+    its [#], or empty), or [None] when there is none. *)
+let wikilink_of_markdown
+      ~(embed : bool)
+      ~(target : string)
+      ~(fragment : string)
+      (slice : string)
+  : string option
+  =
+  let open Option.Let_syntax in
+  let%bind text_stop =
+    List.last (String.substr_index_all slice ~may_overlap:false ~pattern:"](")
+  in
+  let%bind text_start = Option.map (String.index slice '[') ~f:(fun i -> i + 1) in
+  let text =
+    unescape_link_text (String.sub slice ~pos:text_start ~len:(text_stop - text_start))
+  in
+  let%bind () =
+    Option.some_if
+      (not
+         (String.is_substring text ~substring:"|"
+          || String.is_substring text ~substring:"]]"
+          || String.mem text '\n'))
+      ()
+  in
+  let inner = target ^ fragment in
+  (* What the wikilink displays without an alias. *)
+  let display =
+    if String.is_empty target
+    then String.chop_prefix_if_exists fragment ~prefix:"#"
+    else inner
+  in
+  let alias =
+    if String.is_empty text || String.equal text display then "" else "|" ^ text
+  in
+  Some (sprintf "%s[[%s%s]]" (if embed then "!" else "") inner alias)
+;;
+
+(* Source range [\[first, stop)] of the node at [loc], widened to the [!] of an
+   embed when the location starts after it. *)
+let range ~(src : string) ~(embed : bool) (loc : Cmarkit.Textloc.t) : (int * int) option =
+  if Cmarkit.Textloc.is_none loc
+  then None
+  else (
+    let first = Cmarkit.Textloc.first_byte loc in
+    let first =
+      if embed && first > 0 && Char.(src.[first] <> '!') && Char.(src.[first - 1] = '!')
+      then first - 1
+      else first
+    in
+    Some (first, Cmarkit.Textloc.last_byte loc + 1))
+;;
+
+let chop_md (path : string) : string =
+  Option.value (String.chop_suffix path ~suffix:".md") ~default:path
+;;
+
+(** [src], the note at [path], with every link converted [~to_] the given
+    syntax, and how many links changed. Targets resolve through [index], with
+    [prefer] and [relative] as in {!Resolve.resolve_file}; [config] sets the
+    parse options. *)
+let convert
+      ?(config : Config.t option)
+      ?(prefer : Config.Link_preference.t option)
+      ?(relative : bool option)
+      ~(index : Index.t)
+      ~(path : string)
+      ~(to_ : syntax)
+      (src : string)
+  : string * int
+  =
+  let doc = Parse.of_string ~locs:true ?config src in
+  let lookup = Index.Lookup.create index.files in
+  let resolve ?markdown ?relative (target : string) : Index.file_entry option =
+    Resolve.resolve_file
+      ~lookup
+      ?prefer
+      ?markdown
+      ?relative
+      ~curr_file:path
+      index.files
+      target
+  in
+  (* The shortest wikilink target naming [file]. *)
+  let wiki_target (file : Index.file_entry) : string =
+    let name = chop_md (Filename.basename file.rel_path) in
+    match resolve name with
+    | Some f when String.equal f.rel_path file.rel_path -> name
+    | _ -> chop_md file.rel_path
+  in
+  (* The wikilink target for Markdown destination [dest], percent-decoded and
+     without its fragment. *)
+  let wiki_target_of_dest (dest : string) : string option =
+    if String.is_empty dest
+    then Some ""
+    else (
+      match resolve ~markdown:true ?relative dest with
+      | Some file -> Some (wiki_target file)
+      | None when Resolve.is_explicitly_relative dest ->
+        Option.map (Resolve.relative_path ~curr_file:path dest) ~f:chop_md
+      | None -> Some (chop_md dest))
+  in
+  let edit acc ~embed meta ~(f : string -> string option) =
+    match range ~src ~embed (Cmarkit.Meta.textloc meta) with
+    | None -> acc
+    | Some (first, stop) ->
+      (match f (String.sub src ~pos:first ~len:(stop - first)) with
+       | Some text -> (first, stop, text) :: acc
+       | None -> acc)
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline:(fun _f acc i ->
+        match to_, i with
+        | Wiki, (Cmarkit.Inline.Link (l, meta) | Cmarkit.Inline.Image (l, meta)) ->
+          let embed =
+            match i with
+            | Cmarkit.Inline.Image _ -> true
+            | _ -> false
+          in
+          (match Cmarkit.Inline.Link.reference l with
+           | `Inline (ld, _) ->
+             (match Cmarkit.Link_definition.dest ld, Cmarkit.Link_definition.title ld with
+              | Some (dest, _), None when not (Parse.Link_kind.is_external dest) ->
+                let dest = Link_ref.percent_decode dest in
+                let dest, fragment =
+                  match String.lsplit2 dest ~on:'#' with
+                  | Some (d, f) -> d, "#" ^ f
+                  | None -> dest, ""
+                in
+                Cmarkit.Folder.ret
+                  (edit acc ~embed meta ~f:(fun slice ->
+                     Option.bind (wiki_target_of_dest dest) ~f:(fun target ->
+                       wikilink_of_markdown ~embed ~target ~fragment slice)))
+              | _ -> Cmarkit.Folder.ret acc)
+           | `Ref _ -> Cmarkit.Folder.ret acc)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc i ->
+        match to_, i with
+        | Markdown, Cmarkit.Inline.Ext_wikilink (w, meta) ->
+          let embed = Cmarkit.Inline.Wikilink.embed w in
+          let dest : string option =
+            Option.bind (Cmarkit.Inline.Wikilink.target w) ~f:(fun t -> resolve t)
+            |> Option.map ~f:(fun (file : Index.file_entry) ->
+              Resolve.relative_link ~curr_file:path file.rel_path)
+          in
+          edit acc ~embed meta ~f:(fun _ -> Some (markdown_of_wikilink ?dest w))
+        | _ -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  let edits = Cmarkit.Folder.fold_doc folder [] doc in
+  Link_rewrite.splice src edits, List.length edits
+;;
+
+let%expect_test "convert" =
+  let index : Index.t =
+    { files =
+        List.map
+          [ "Note 2.md"
+          ; "assets/diagram.png"
+          ; "projects/Ideas.md"
+          ; "projects/old/Ideas.md"
+          ; "daily/today.md"
+          ; "x.md"
+          ]
+          ~f:(fun p ->
+            ({ rel_path = p; headings = []; blocks = []; attrs = [] } : Index.file_entry))
+    ; dirs = []
+    }
+  in
+  let convert = convert ~index ~path:"daily/today.md" in
+  let src =
+    "[[Note 2#Usage|setup]], [[Note 2#Some heading]], [[#^para1]] and \
+     ![[diagram.png|300]].\n\
+     [docs](https://ocaml.org), [t](a.md \"title\"), [[Ideas]] and [[Missing]].\n"
+  in
+  let md, n = convert ~to_:Markdown src in
+  printf "%d\n%s" n md;
+  [%expect
+    {|
+    6
+    [setup](../Note%202.md#Usage), [Note 2#Some heading](../Note%202.md#Some%20heading), [\^para1](#^para1) and ![300](../assets/diagram.png).
+    [docs](https://ocaml.org), [t](a.md "title"), [Ideas](../projects/Ideas.md) and [Missing](Missing.md).
+    |}];
+  let wiki, n = convert ~to_:Wiki md in
+  printf "%d\n%s" n wiki;
+  [%expect
+    {|
+    6
+    [[Note 2#Usage|setup]], [[Note 2#Some heading]], [[#^para1]] and ![[diagram.png|300]].
+    [docs](https://ocaml.org), [t](a.md "title"), [[Ideas]] and [[Missing]].
+    |}];
+  (* Relative paths resolve from the note's folder; a name shared by two
+     notes keeps its folders *)
+  let wiki, n =
+    convert
+      ~to_:Wiki
+      "[x](../x.md), [y](../y.md), [i](../projects/old/Ideas.md), [z](../../z.md)\n"
+  in
+  printf "%d\n%s" n wiki;
+  [%expect
+    {|
+    3
+    [[x]], [[y]], [[projects/old/Ideas|i]], [z](../../z.md)
+    |}]
+;;
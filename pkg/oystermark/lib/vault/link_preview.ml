@@ -0,0 +1,226 @@
+(** Previews of external links — a page's title, description and image — read
+    at build time and cached in the vault's [.oyster/] between builds, so only
+    new links hit the network. See {!Config.Link_previews}. *)
+
+open Core
+
+let cache_file = "link_previews.json"
+
+type t =
+  { title : string option [@yojson.option]
+  ; description : string option [@yojson.option]
+  ; image : string option [@yojson.option]
+  }
+[@@deriving yojson] [@@yojson.allow_extra_fields]
+
+let is_empty (t : t) : bool =
+  Option.is_none t.title && Option.is_none t.description && Option.is_none t.image
+;;
+
+(** Meta key on an external link carrying its preview. *)
+let meta_key : t Cmarkit.Meta.key = Cmarkit.Meta.key ()
+
+let unescape (s : string) : string =
+  List.fold
+    [ "&quot;", "\""
+    ; "&#39;", "'"
+    ; "&#x27;", "'"
+    ; "&lt;", "<"
+    ; "&gt;", ">"
+    ; "&amp;", "&"
+    ]
+    ~init:s
+    ~f:(fun s (entity, c) -> String.substr_replace_all s ~pattern:entity ~with_:c)
+;;
+
+(** The preview [page] (HTML) gives of itself: the Open Graph [og:title],
+    [og:description] and [og:image], falling back to [<title>] and
+    [<meta name="description">]. A relative image is resolved against [url]. *)
+let of_html ~(url : string) (page : string) : t =
+  let pieces : Parse.Html_fragment.t = Parse.Html_fragment.of_string page in
+  let attr (tag : Parse.Html_fragment.tag) (name : string) : string option =
+    List.find_map tag.attrs ~f:(fun (a : Parse.Html_fragment.attr) ->
+      if String.equal a.name name then a.value else None)
+  in
+  let meta (key : string) : string option =
+    List.find_map pieces ~f:(function
+      | Parse.Html_fragment.Tag ({ name = "meta"; closing = false; _ } as tag) ->
+        (match Option.first_some (attr tag "property") (attr tag "name") with
+         | Some k when String.Caseless.equal k key -> attr tag "content"
+         | _ -> None)
+      | _ -> None)
+  in
+  let rec title_text = function
+    | Parse.Html_fragment.Tag { name = "title"; closing = false; _ }
+      :: Parse.Html_fragment.Text text
+      :: _ -> Some text
+    | _ :: rest -> title_text rest
+    | [] -> None
+  in
+  let clean (s : string option) : string option =
+    Option.map s ~f:(fun s ->
+      String.split_on_chars (unescape s) ~on:[ ' '; '\n'; '\t'; '\r' ]
+      |> List.filter ~f:(Fn.non String.is_empty)
+      |> String.concat ~sep:" ")
+    |> Option.filter ~f:(Fn.non String.is_empty)
+  in
+  let absolute (src : string) : string =
+    if Parse.Link_kind.is_external src
+    then src
+    else (
+      match String.substr_index url ~pattern:"://" with
+      | None -> src
+      | Some i ->
+        let origin_end =
+          String.index_from url (i + 3) '/' |> Option.value ~default:(String.length url)
+        in
+        let origin = String.prefix url origin_end in
+        if String.is_prefix src ~prefix:"//"
+        then String.prefix url i ^ ":" ^ src
+        else if String.is_prefix src ~prefix:"/"
+        then origin ^ src
+        else (
+          let base =
+            match String.rindex url '/' with
+            | Some j when j >= origin_end -> String.prefix url (j + 1)
+            | _ -> origin ^ "/"
+          in
+          base ^ src))
+  in
+  { title = clean (Option.first_some (meta "og:title") (title_text pieces))
+  ; description = clean (Option.first_some (meta "og:description") (meta "description"))
+  ; image = clean (meta "og:image") |> Option.map ~f:absolute
+  }
+;;
+
+(** Download [url] with [curl], giving up after [timeout] seconds. [None] on
+    any failure. *)
+let fetch ~(timeout : int) (url : string) : t option =
+  let cmd =
+    sprintf
+      "curl --silent --show-error --fail --location --max-time %d --max-filesize \
+       2000000 %s"
+      timeout
+      (Filename.quote url)
+  in
+  match
+    let ic = Core_unix.open_process_in cmd in
+    let page = In_channel.input_all ic in
+    page, Core_unix.close_process_in ic
+  with
+  | page, Ok () -> Some (of_html ~url page) |> Option.filter ~f:(Fn.non is_empty)
+  | _, Error _ -> None
+  | exception _ -> None
+;;
+
+(** {1 Cache} *)
+
+(** Mutable map from URL to its preview; [None] for a URL whose download
+    failed, so that it isn't tried again on every build. Delete the cache
+    file to retry those. *)
+type cache = t option String.Map.t ref
+
+let empty_cache () : cache = ref String.Map.empty
+
+(** Load the cache from [link_previews.json] in [dir]. Returns an empty cache
+    if the file is missing or malformed. *)
+let load_cache ~(dir : string) : cache =
+  let path = Filename.concat dir cache_file in
+  match Yojson.Safe.from_file path with
+  | `Assoc entries ->
+    ref
+      (List.filter_map entries ~f:(fun (url, j) ->
+         match j with
+         | `Null -> Some (url, None)
+         | j -> Option.try_with (fun () -> url, Some (t_of_yojson j)))
+       |> String.Map.of_alist_reduce ~f:(fun a _ -> a))
+  | _ -> empty_cache ()
+  | exception _ -> empty_cache ()
+;;
+
+(** Persist [cache] to [link_previews.json] in [dir], failed URLs as [null]. *)
+let save_cache (c : cache) ~(dir : string) : unit =
+  Core_unix.mkdir_p dir;
+  `Assoc
+    (Map.to_alist !c
+     |> List.map ~f:(fun (url, t) ->
+       url, Option.value_map t ~default:`Null ~f:yojson_of_t))
+  |> Yojson.Safe.to_file (Filename.concat dir cache_file)
+;;
+
+(** The preview of [url]: from [cache], else — only when [fetch] — downloaded
+    and remembered in [cache], as is a failed download. *)
+let lookup ?(fetch : (string -> t option) option) (c : cache) (url : string)
+  : t option
+  =
+  match Map.find !c url, fetch with
+  | Some cached, _ -> cached
+  | None, None -> None
+  | None, Some fetch ->
+    let t = fetch url in
+    c := Map.set !c ~key:url ~data:t;
+    t
+;;
+
+let%expect_test "lookup" =
+  let c = empty_cache () in
+  let fetch (url : string) : t option =
+    printf "fetch %s\n" url;
+    Option.some_if
+      (String.equal url "https://a.example/")
+      { title = Some "A"; description = None; image = None }
+  in
+  let show (url : string) : unit =
+    match lookup ~fetch c url with
+    | Some t -> printf "%s: %s\n" url (Option.value t.title ~default:"")
+    | None -> printf "%s: -\n" url
+  in
+  (* The second time round, neither URL is downloaded again *)
+  List.iter [ "https://a.example/"; "https://down.example/" ] ~f:show;
+  List.iter [ "https://a.example/"; "https://down.example/" ] ~f:show;
+  [%expect
+    {|
+    fetch https://a.example/
+    https://a.example/: A
+    fetch https://down.example/
+    https://down.example/: -
+    https://a.example/: A
+    https://down.example/: -
+    |}]
+;;
+
+let%expect_test "of_html" =
+  let print (t : t) =
+    List.iter
+      [ "title", t.title; "description", t.description; "image", t.image ]
+      ~f:(fun (k, v) -> printf "%s: %s\n" k (Option.value v ~default:"-"))
+  in
+  let page =
+    {|<!doctype html>
+<html><head>
+<title>
+  Oysters &amp; Pearls
+</title>
+<meta name="description" content="All about bivalves.">
+<meta property="og:image" content="/img/card.png">
+</head><body></body></html>|}
+  in
+  print (of_html ~url:"https://example.com/a/b" page);
+  [%expect
+    {|
+    title: Oysters & Pearls
+    description: All about bivalves.
+    image: https://example.com/img/card.png
+    |}];
+  let page =
+    {|<meta property="og:title" content="OG title">
+<meta property="og:image" content="c.png">|}
+  in
+  print (of_html ~url:"https://example.com/a/b" page);
+  [%expect
+    {|
+    title: OG title
+    description: -
+    image: https://example.com/a/c.png
+    |}]
+;;
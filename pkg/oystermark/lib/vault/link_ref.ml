@@ -1,25 +1,21 @@
 (** Unified internal link reference extracted from both wikilinks and markdown links. *)
 
 open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
 open Parse
 
 type fragment =
   | Heading of string list (** Headings *)
   | Block_ref of string (** Block id *)
-[@@deriving sexp]
+[@@deriving sexp, yojson]
 
 type t =
   { target : string option (** Target file path *)
   ; fragment : fragment option
   }
-[@@deriving sexp]
+[@@deriving sexp, yojson]
 
-let is_external (s : string) : bool =
-  String.is_prefix s ~prefix:"http://"
-  || String.is_prefix s ~prefix:"https://"
-  || String.is_prefix s ~prefix:"mailto:"
-  || String.is_prefix s ~prefix:"ftp://"
-;;
+let is_external : string -> bool = Link_kind.is_external
 
 let of_wikilink (w : Cmarkit.Inline.Wikilink.t) : t =
   let fragment =
@@ -64,13 +60,36 @@ let of_cmark_dest (dest : string) : t option =
     Some (of_wikilink wikilink))
 ;;
 
-let of_cmark_reference (ref : Cmarkit.Inline.Link.reference) : t option =
+(** Link definition a Markdown link's destination comes from: its own for
+    inline links, the [[label]: dest] block in [defs] for reference links.
+    [None] for footnote references and undefined labels. *)
+let link_definition ~(defs : Cmarkit.Label.defs) (ref : Cmarkit.Inline.Link.reference)
+  : Cmarkit.Link_definition.t option
+  =
   match ref with
-  | `Ref _ -> None
-  | `Inline (ld, _ld_meta) ->
-    (match Cmarkit.Link_definition.dest ld with
-     | None ->
-       (* When destination is empty, Obsidian resolves it to a file named "().md". *)
-       Some { target = Some "().md"; fragment = None }
-     | Some (dest, dest_meta) -> of_cmark_dest dest)
+  | `Inline (ld, _) -> Some ld
+  | `Ref (_, _, def) ->
+    (match Link_def.find ~defs def with
+     | Some (Link_def.Link ld) -> Some ld
+     | Some (Link_def.Footnote _) | None -> None)
+;;
+
+(** Vault link of a link definition, or [None] for external destinations. *)
+let of_link_definition (ld : Cmarkit.Link_definition.t) : t option =
+  match Cmarkit.Link_definition.dest ld with
+  | None ->
+    (* When destination is empty, Obsidian resolves it to a file named "().md". *)
+    Some { target = Some "().md"; fragment = None }
+  | Some (dest, _) -> of_cmark_dest dest
+;;
+
+(** Vault link of a Markdown link. Reference links ([[text][label]],
+    [[label]]) are looked up in [defs], the document's {!Cmarkit.Doc.defs};
+    without it they give [None], as do footnote references. *)
+let of_cmark_reference
+      ?(defs : Cmarkit.Label.defs = Cmarkit.Label.Map.empty)
+      (ref : Cmarkit.Inline.Link.reference)
+  : t option
+  =
+  Option.bind (link_definition ~defs ref) ~f:of_link_definition
 ;;
@@ -0,0 +1,220 @@
+(** Rewrite link destinations in note source.
+
+    {!rewrite} hands every link of a note to a mapping and splices the
+    destinations it returns back into the source text. Only the destination is
+    replaced: link text, wikilink aliases ([[[note|alias]]]), embed markers and
+    Markdown link titles stay byte-for-byte as written. Renames, exports and
+    vault migrations build on this instead of editing source by hand. *)
+
+open Core
+
+type style =
+  [ `Wikilink
+  | `Markdown
+  ]
+[@@deriving sexp, equal, compare]
+
+(** A link destination in note source. *)
+type link =
+  { dest : string
+    (** destination as a wikilink would spell it: percent-decoded for Markdown
+        links, e.g. [Note 2#Some heading] *)
+  ; link_ref : Link_ref.t option (** [None] for external destinations *)
+  ; style : style
+  ; first_byte : int (** start of the destination in the source *)
+  ; stop_byte : int (** end of the destination, exclusive *)
+  }
+[@@deriving sexp_of]
+
+(** Bounds of a link's destination within [slice] (the link's full source text):
+    [(style, dest_start, dest_stop)], both {e slice-relative}, with [dest_stop]
+    exclusive. The destination excludes a wikilink's [|alias] and a Markdown
+    link's title and [<...>] brackets. A [[label]: dest] definition has the
+    bounds of its [dest]; a reference link ([[text][label]]) has none. *)
+let destination_bounds (slice : string) : (style * int * int) option =
+  let len = String.length slice in
+  (* End of a Markdown destination starting at [i]. *)
+  let rec finish i =
+    if i >= len
+    then i
+    else if
+      Char.equal slice.[i] ')'
+      || Char.equal slice.[i] '>'
+      || Char.is_whitespace slice.[i]
+    then i
+    else finish (i + 1)
+  in
+  let rec skip_blank i =
+    if i < len && Char.is_whitespace slice.[i] then skip_blank (i + 1) else i
+  in
+  let markdown start =
+    let start = skip_blank start in
+    let start =
+      if start < len && Char.equal slice.[start] '<' then start + 1 else start
+    in
+    `Markdown, start, finish start
+  in
+  match String.substr_index slice ~pattern:"[[" with
+  | Some open_pos ->
+    let start = open_pos + 2 in
+    let finish =
+      String.substr_index ~pos:start slice ~pattern:"]]"
+      |> Option.value ~default:(String.length slice)
+    in
+    let finish =
+      String.index_from slice start '|'
+      |> Option.filter ~f:(fun p -> p < finish)
+      |> Option.value ~default:finish
+    in
+    Some (`Wikilink, start, finish)
+  | None ->
+    (match String.substr_index slice ~pattern:"](" with
+     | Some open_pos -> Some (markdown (open_pos + 2))
+     | None ->
+       String.substr_index slice ~pattern:"]:"
+       |> Option.map ~f:(fun colon -> markdown (colon + 2)))
+;;
+
+(** Percent-encode the characters that would end or break a Markdown link
+    destination: whitespace, [%], [(], [)], [<] and [>]. *)
+let percent_encode (s : string) : string =
+  String.concat_map s ~f:(fun c ->
+    match c with
+    | ' ' | '\t' | '\n' | '%' | '(' | ')' | '<' | '>' ->
+      sprintf "%%%02X" (Char.to_int c)
+    | c -> String.of_char c)
+;;
+
+(* The link at [loc] in [src], when its destination can be located. *)
+let link_at ~(src : string) ~(link_ref : Link_ref.t option) (loc : Cmarkit.Textloc.t)
+  : link option
+  =
+  if Cmarkit.Textloc.is_none loc
+  then None
+  else (
+    let first = Cmarkit.Textloc.first_byte loc in
+    let len = Cmarkit.Textloc.last_byte loc - first + 1 in
+    let slice = String.sub src ~pos:first ~len in
+    Option.map (destination_bounds slice) ~f:(fun (style, start, stop) ->
+      let raw = String.sub slice ~pos:start ~len:(stop - start) in
+      { dest =
+          (match style with
+           | `Wikilink -> raw
+           | `Markdown -> Link_ref.percent_decode raw)
+      ; link_ref
+      ; style
+      ; first_byte = first + start
+      ; stop_byte = first + stop
+      }))
+;;
+
+(** Every link destination in note source [src], in source order: wikilinks
+    and embeds, Markdown links and images, and [[label]: dest] definitions
+    (which reference links share). [config] sets the parse options. *)
+let links ?(config : Config.t option) (src : string) : link list =
+  let doc = Parse.of_string ~locs:true ?config src in
+  let defs = Cmarkit.Doc.defs doc in
+  let add acc link_ref meta =
+    match link_at ~src ~link_ref (Cmarkit.Meta.textloc meta) with
+    | Some l -> l :: acc
+    | None -> acc
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun _f acc b ->
+        match b with
+        | Cmarkit.Block.Link_reference_definition (ld, meta) ->
+          Cmarkit.Folder.ret (add acc (Link_ref.of_link_definition ld) meta)
+        | _ -> Cmarkit.Folder.default)
+      ~inline:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Link (l, meta) | Cmarkit.Inline.Image (l, meta) ->
+          (match Cmarkit.Inline.Link.reference l with
+           | `Inline _ ->
+             let link_ref =
+               Link_ref.of_cmark_reference ~defs (Cmarkit.Inline.Link.reference l)
+             in
+             Cmarkit.Folder.ret (add acc link_ref meta)
+           | `Ref _ -> Cmarkit.Folder.default)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Ext_wikilink (w, meta) ->
+          add acc (Some (Link_ref.of_wikilink w)) meta
+        | _ -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  Cmarkit.Folder.fold_doc folder [] doc
+  |> List.sort ~compare:(fun a b -> Int.compare a.first_byte b.first_byte)
+;;
+
+(** Replace the bytes [\[first, stop)] of [src] for each [(first, stop, text)],
+    the ranges not overlapping. *)
+let splice (src : string) (edits : (int * int * string) list) : string =
+  let buf = Buffer.create (String.length src) in
+  let pos =
+    List.sort edits ~compare:(fun (a, _, _) (b, _, _) -> Int.compare a b)
+    |> List.fold ~init:0 ~f:(fun pos (first, stop, text) ->
+      Buffer.add_string buf (String.sub src ~pos ~len:(first - pos));
+      Buffer.add_string buf text;
+      stop)
+  in
+  Buffer.add_string buf (String.drop_prefix src pos);
+  Buffer.contents buf
+;;
+
+(** [src] with the destination of every link [f] maps to [Some dest] replaced
+    by [dest], spelled as {!link.dest} (percent-encoded here for Markdown
+    links). Links mapped to [None] are left as they are. *)
+let rewrite (src : string) ~(f : link -> string option) : string =
+  links src
+  |> List.filter_map ~f:(fun l ->
+    Option.map (f l) ~f:(fun dest ->
+      let text =
+        match l.style with
+        | `Wikilink -> dest
+        | `Markdown -> percent_encode dest
+      in
+      l.first_byte, l.stop_byte, text))
+  |> splice src
+;;
+
+let%expect_test "rewrite" =
+  let src =
+    "See [[Old note#Intro|the intro]], ![[Old note]] and [x](Old%20note.md \"t\").\n\
+     Also [ref][r], [[Other]] and [web](https://old.example).\n\n\
+     [r]: <Old%20note.md#Usage>\n"
+  in
+  List.iter (links src) ~f:(fun l ->
+    printf
+      "%d-%d %s %s\n"
+      l.first_byte
+      l.stop_byte
+      (Sexp.to_string (sexp_of_style l.style))
+      l.dest);
+  [%expect
+    {|
+    6-20 Wikilink Old note#Intro
+    37-45 Wikilink Old note
+    56-69 Markdown Old note.md
+    93-98 Wikilink Other
+    111-130 Markdown https://old.example
+    140-159 Markdown Old note.md#Usage
+    |}];
+  let renamed =
+    rewrite src ~f:(fun l ->
+      match l.link_ref with
+      | Some { Link_ref.target = Some ("Old note" | "Old note.md"); _ } ->
+        Some (String.substr_replace_first l.dest ~pattern:"Old note" ~with_:"New (draft)")
+      | _ -> None)
+  in
+  print_string renamed;
+  [%expect
+    {|
+    See [[New (draft)#Intro|the intro]], ![[New (draft)]] and [x](New%20%28draft%29.md "t").
+    Also [ref][r], [[Other]] and [web](https://old.example).
+
+    [r]: <New%20%28draft%29.md#Usage>
+    |}]
+;;
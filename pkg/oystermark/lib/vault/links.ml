@@ -0,0 +1,269 @@
+(** Every link of a resolved vault as data, for tools outside this library:
+    [oyster links list] prints it as JSON, backlinks filter it by target.
+
+    The JSON schema is versioned by {!schema_version}; fields may be added
+    within a version but are never renamed or removed:
+    {v
+    { "version": 1,
+      "links":
+        [ { "source": "notes/today.md",
+            "loc": { "first_byte": .., "last_byte": ..,
+                     "first_line": [line, byte], "last_line": [..] },
+            "link_ref": { "target": "Ideas", "fragment": ["Heading", ["Usage"]] },
+            "target": ["Heading", { "path": "Ideas.md", "heading": "Usage", .. }],
+            "section": ["Today", "Reading"],
+            "context": "Read the Ideas usage notes first." } ] }
+    v}
+    [loc] is left out when the vault was parsed without locations; [target] is
+    {!Resolve.yojson_of_target}. [section] and [context] let backlinks panels,
+    hover previews and reports quote the link instead of showing a bare range. *)
+
+open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
+
+type textloc = Cmarkit.Textloc.t
+
+let sexp_of_textloc = Parse.Textloc_conv.sexp_of_t
+let yojson_of_textloc = Parse.Textloc_conv.yojson_of_t
+let textloc_of_yojson = Parse.Textloc_conv.t_of_yojson
+
+type t =
+  { source : string (** vault-relative path of the linking note *)
+  ; loc : textloc option [@sexp.option] [@yojson.option]
+  ; link_ref : Link_ref.t (** the link as written *)
+  ; target : Resolve.target (** what it resolves to *)
+  ; section : string list [@yojson.default []]
+    (** texts of the headings the link is under, outermost first *)
+  ; context : string [@yojson.default ""]
+    (** the sentence around the link; a list item's or heading's whole text *)
+  }
+[@@deriving sexp_of, yojson]
+
+let schema_version = 1
+
+(** Path of the note or file [t] points at; the source itself for links within
+    it, [None] when unresolved. *)
+let target_path (t : t) : string option =
+  match t.target with
+  | Resolve.Note { path }
+  | Resolve.File { path }
+  | Resolve.Heading { path; _ }
+  | Resolve.Block { path; _ }
+  | Resolve.Attr { path; _ } -> Some path
+  | Resolve.Curr_file
+  | Resolve.Curr_heading _
+  | Resolve.Curr_block _
+  | Resolve.Curr_attr _ -> Some t.source
+  | Resolve.Unresolved -> None
+;;
+
+(* Plain text of [inline] into [buf], with [(link_ref, meta, start, stop)] for
+   each link in it, [start] and [stop] its text's offsets in [buf]. *)
+let rec flatten
+          ~(defs : Cmarkit.Label.defs)
+          (buf : Buffer.t)
+          (acc : (Link_ref.t option * Cmarkit.Meta.t * int * int) list)
+          (inline : Cmarkit.Inline.t)
+  =
+  match inline with
+  | Cmarkit.Inline.Text (s, _) ->
+    Buffer.add_string buf s;
+    acc
+  | Cmarkit.Inline.Code_span (c, _) ->
+    Buffer.add_string buf (Cmarkit.Inline.Code_span.code c);
+    acc
+  | Cmarkit.Inline.Break _ ->
+    Buffer.add_char buf ' ';
+    acc
+  | Cmarkit.Inline.Emphasis (e, _) | Cmarkit.Inline.Strong_emphasis (e, _) ->
+    flatten ~defs buf acc (Cmarkit.Inline.Emphasis.inline e)
+  | Cmarkit.Inline.Inlines (is, _) -> List.fold is ~init:acc ~f:(flatten ~defs buf)
+  | Cmarkit.Inline.Link (l, meta) | Cmarkit.Inline.Image (l, meta) ->
+    let start = Buffer.length buf in
+    let acc = flatten ~defs buf acc (Cmarkit.Inline.Link.text l) in
+    let link_ref = Link_ref.of_cmark_reference ~defs (Cmarkit.Inline.Link.reference l) in
+    (link_ref, meta, start, Buffer.length buf) :: acc
+  | Cmarkit.Inline.Ext_wikilink (w, meta) ->
+    let start = Buffer.length buf in
+    Buffer.add_string
+      buf
+      (Option.value
+         (Cmarkit.Inline.Wikilink.display w)
+         ~default:(Link_convert.wikilink_default_display w));
+    (Some (Link_ref.of_wikilink w), meta, start, Buffer.length buf) :: acc
+  | Cmarkit.Inline.Raw_html _ -> acc
+  | other ->
+    Buffer.add_string buf (Parse.Heading_slug.inline_to_plain_text other);
+    acc
+;;
+
+(* Sentence of [text] around [\[start, stop)]: from after the previous [.],
+   [!] or [?] followed by a space to the next one, inclusive. *)
+let sentence (text : string) ~(start : int) ~(stop : int) : string =
+  let len = String.length text in
+  let ends_sentence i =
+    (match text.[i] with
+     | '.' | '!' | '?' -> true
+     | _ -> false)
+    && (i + 1 >= len || Char.is_whitespace text.[i + 1])
+  in
+  let rec back i =
+    if i <= 0 then 0 else if ends_sentence (i - 1) then i else back (i - 1)
+  in
+  let rec forward i =
+    if i >= len then len else if ends_sentence i then i + 1 else forward (i + 1)
+  in
+  let first = back start in
+  String.sub text ~pos:first ~len:(forward (max start stop) - first)
+;;
+
+let collapse_whitespace (s : string) : string =
+  String.split_on_chars s ~on:[ ' '; '\t'; '\n'; '\r' ]
+  |> List.filter ~f:(Fn.non String.is_empty)
+  |> String.concat ~sep:" "
+;;
+
+(** Internal links of [doc], a note at [source] already resolved (see
+    {!Resolve.resolve_docs}), in document order. External links carry no
+    resolution and are left out. *)
+let of_doc (source : string) (doc : Cmarkit.Doc.t) : t list =
+  let defs = Cmarkit.Doc.defs doc in
+  (* [stack] holds the enclosing headings as [(level, text)], innermost first. *)
+  let add_inline (links, stack, in_item) ~(whole : bool) inline =
+    let buf = Buffer.create 256 in
+    let found = List.rev (flatten ~defs buf [] inline) in
+    let text = Buffer.contents buf in
+    let section = List.rev_map stack ~f:snd in
+    let links =
+      List.fold found ~init:links ~f:(fun links (link_ref, meta, start, stop) ->
+        match link_ref, Cmarkit.Meta.find Resolve.resolved_key meta with
+        | Some link_ref, Some target ->
+          let loc =
+            let tl = Cmarkit.Meta.textloc meta in
+            if Cmarkit.Textloc.is_none tl then None else Some tl
+          in
+          let context =
+            collapse_whitespace (if whole then text else sentence text ~start ~stop)
+          in
+          { source; loc; link_ref; target; section; context } :: links
+        | _ -> links)
+    in
+    links, stack, in_item
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~block:(fun f ((links, stack, in_item) as acc) b ->
+        match b with
+        | Cmarkit.Block.Heading (h, _) ->
+          let level = Cmarkit.Block.Heading.level h in
+          let inline = Cmarkit.Block.Heading.inline h in
+          let text =
+            let buf = Buffer.create 64 in
+            ignore (flatten ~defs buf [] inline : _ list);
+            collapse_whitespace (Buffer.contents buf)
+          in
+          let stack =
+            (level, text) :: List.drop_while stack ~f:(fun (l, _) -> l >= level)
+          in
+          Cmarkit.Folder.ret (add_inline (links, stack, in_item) ~whole:true inline)
+        | Cmarkit.Block.Paragraph (p, _) ->
+          Cmarkit.Folder.ret
+            (add_inline acc ~whole:in_item (Cmarkit.Block.Paragraph.inline p))
+        | Cmarkit.Block.List (l, _) ->
+          let links, stack, _ =
+            List.fold
+              (Cmarkit.Block.List'.items l)
+              ~init:(links, stack, true)
+              ~f:(fun acc (item, _) ->
+                Cmarkit.Folder.fold_block f acc (Cmarkit.Block.List_item.block item))
+          in
+          Cmarkit.Folder.ret (links, stack, in_item)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  let links, _, _ = Cmarkit.Folder.fold_doc folder ([], [], false) doc in
+  List.rev links
+;;
+
+(** Links of all [docs], resolved, in the order given. *)
+let of_docs (docs : (string * Cmarkit.Doc.t) list) : t list =
+  List.concat_map docs ~f:(fun (source, doc) -> of_doc source doc)
+;;
+
+(** Links into [path] from other notes. *)
+let backlinks (links : t list) ~(path : string) : t list =
+  List.filter links ~f:(fun t ->
+    (not (String.equal t.source path))
+    && Option.equal String.equal (target_path t) (Some path))
+;;
+
+let to_json (links : t list) : Yojson.Safe.t =
+  `Assoc
+    [ "version", `Int schema_version; "links", `List (List.map links ~f:yojson_of_t) ]
+;;
+
+let%expect_test "to_json" =
+  let files =
+    [ "a.md", "See [[b#Intro|intro]], [b](b.md) and [[missing]].\n"
+    ; "b.md", "# Intro\n\nBack to [[a]] and [[#Intro]].\n"
+    ; ( "c.md"
+      , "# Reading\n\n## On *[[b]]*\n\nFirst of all. Then [[b|the b note]] again!\n\
+         Not this one.\n\n\
+         - an item with [[b#Intro]]. More\n\
+        \  text\n" )
+    ]
+  in
+  let docs =
+    List.map files ~f:(fun (path, src) -> path, Parse.of_string ~locs:false src)
+  in
+  let index : Index.t =
+    { files =
+        List.map docs ~f:(fun (rel_path, doc) : Index.file_entry ->
+          { rel_path
+          ; headings = Index.extract_headings doc
+          ; blocks = []
+          ; attrs = []
+          })
+    ; dirs = []
+    }
+  in
+  let links = of_docs (Resolve.resolve_docs docs index) in
+  List.iter links ~f:(fun t ->
+    if not (String.equal t.source "c.md")
+    then print_endline (Yojson.Safe.to_string (yojson_of_t t)));
+  [%expect
+    {|
+    {"source":"a.md","link_ref":{"target":"b","fragment":["Heading",["Intro"]]},"target":["Heading",{"path":"b.md","heading":"Intro","level":1,"slug":"intro"}],"section":[],"context":"See intro, b and missing."}
+    {"source":"a.md","link_ref":{"target":"b.md","fragment":null},"target":["Note",{"path":"b.md"}],"section":[],"context":"See intro, b and missing."}
+    {"source":"a.md","link_ref":{"target":"missing","fragment":null},"target":["Unresolved"],"section":[],"context":"See intro, b and missing."}
+    {"source":"b.md","link_ref":{"target":"a","fragment":null},"target":["Note",{"path":"a.md"}],"section":["Intro"],"context":"Back to a and Intro."}
+    {"source":"b.md","link_ref":{"target":null,"fragment":["Heading",["Intro"]]},"target":["Curr_heading",{"heading":"Intro","level":1,"slug":"intro"}],"section":["Intro"],"context":"Back to a and Intro."}
+    |}];
+  List.iter links ~f:(fun t ->
+    if String.equal t.source "c.md"
+    then printf "%s | %s\n" (String.concat ~sep:" > " t.section) t.context);
+  [%expect
+    {|
+    Reading > On b | On b
+    Reading > On b | Then the b note again!
+    Reading > On b | an item with b#Intro. More text
+    |}];
+  (* JSON round-trips. *)
+  let round_trips =
+    List.for_all links ~f:(fun t ->
+      Sexp.equal (sexp_of_t t) (sexp_of_t (t_of_yojson (yojson_of_t t))))
+  in
+  printf "%b\n" round_trips;
+  [%expect {| true |}];
+  List.iter (backlinks links ~path:"b.md") ~f:(fun t -> print_endline t.source);
+  [%expect
+    {|
+    a.md
+    a.md
+    c.md
+    c.md
+    c.md
+    |}]
+;;
@@ -0,0 +1,131 @@
+(** Build manifest: a content hash per source note, persisted in the output
+    directory between builds so a build can tell which notes changed since the
+    previous one. *)
+
+open Core
+
+let manifest_file = "_manifest.json"
+
+type entry =
+  { hash : string
+  ; updated : string (** ISO 8601 time of the build that first saw [hash] *)
+  }
+
+(** Map from vault-relative note path to its entry. *)
+type t = entry String.Map.t
+
+let empty : t = String.Map.empty
+let hash_content (content : string) : string = Md5.to_hex (Md5.digest_string content)
+
+(** Record this build's [sources] ([(rel_path, content)]) built at [now].
+    Unchanged notes keep their [updated] time, new or edited notes get [now],
+    and notes no longer present are dropped. *)
+let update (prev : t) ~(now : string) (sources : (string * string) list) : t =
+  List.map sources ~f:(fun (path, content) ->
+    let hash : string = hash_content content in
+    match Map.find prev path with
+    | Some e when String.equal e.hash hash -> path, e
+    | _ -> path, { hash; updated = now })
+  |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+;;
+
+(** Paths in [curr] that are new or whose hash differs from [prev], sorted. *)
+let changed ~(prev : t) (curr : t) : string list =
+  Map.to_alist curr
+  |> List.filter_map ~f:(fun (path, e) ->
+    match Map.find prev path with
+    | Some e' when String.equal e'.hash e.hash -> None
+    | _ -> Some path)
+;;
+
+(** Entries most recently updated first, ties broken by path. Times are
+    compared as {!Parse.Timestamp}s, so offsets and precision don't skew the
+    order. *)
+let recent ?(limit : int option) (t : t) : (string * entry) list =
+  let sorted =
+    Map.to_alist t
+    |> List.stable_sort ~compare:(fun (_, a) (_, b) ->
+      Parse.Timestamp.compare_text b.updated a.updated)
+  in
+  match limit with
+  | Some n -> List.take sorted n
+  | None -> sorted
+;;
+
+(** The [changes.json] feed: notes changed since the previous build, and all
+    notes newest first. Only paths satisfying [include_] are listed;
+    [url_of] maps a note path to its page URL. *)
+let changes_json
+      ~(prev : t)
+      ~(include_ : string -> bool)
+      ~(url_of : string -> string)
+      (curr : t)
+  : Yojson.Basic.t
+  =
+  let item (path : string) (e : entry) : Yojson.Basic.t =
+    `Assoc
+      [ "path", `String path
+      ; "url", `String (url_of path)
+      ; "updated", `String e.updated
+      ]
+  in
+  let changed : Yojson.Basic.t list =
+    changed ~prev curr
+    |> List.filter ~f:include_
+    |> List.map ~f:(fun path -> item path (Map.find_exn curr path))
+  in
+  let recent : Yojson.Basic.t list =
+    recent curr
+    |> List.filter ~f:(fun (path, _) -> include_ path)
+    |> List.map ~f:(fun (path, e) -> item path e)
+  in
+  `Assoc [ "changed", `List changed; "recent", `List recent ]
+;;
+
+(** Load the manifest from [_manifest.json] in [dir]. Returns {!empty} if the
+    file is missing or malformed. *)
+let load ~(dir : string) : t =
+  let path = Filename.concat dir manifest_file in
+  if not (Sys_unix.file_exists_exn path)
+  then empty
+  else (
+    try
+      let open Yojson.Basic.Util in
+      Yojson.Basic.from_file path
+      |> to_assoc
+      |> List.map ~f:(fun (file_path, v) ->
+        ( file_path
+        , { hash = v |> member "hash" |> to_string
+          ; updated = v |> member "updated" |> to_string
+          } ))
+      |> String.Map.of_alist_exn
+    with
+    | _ -> empty)
+;;
+
+(** Persist [t] to [_manifest.json] in [dir]. *)
+let save (t : t) ~(dir : string) : unit =
+  Core_unix.mkdir_p dir;
+  let path = Filename.concat dir manifest_file in
+  let json =
+    `Assoc
+      (Map.to_alist t
+       |> List.map ~f:(fun (file_path, e) ->
+         file_path, `Assoc [ "hash", `String e.hash; "updated", `String e.updated ]))
+  in
+  Yojson.Basic.to_file path json
+;;
+
+let%expect_test "update keeps times of unchanged notes" =
+  let prev = update empty ~now:"T1" [ "a.md", "A"; "b.md", "B"; "gone.md", "G" ] in
+  let curr = update prev ~now:"T2" [ "a.md", "A"; "b.md", "B2"; "c.md", "C" ] in
+  List.iter (recent curr) ~f:(fun (path, e) -> printf "%s %s\n" path e.updated);
+  print_s [%sexp (changed ~prev curr : string list)];
+  [%expect
+    {|
+    b.md T2
+    c.md T2
+    a.md T1
+    (b.md c.md)
+    |}]
+;;
@@ -0,0 +1,204 @@
+(** Diagrams of a note, as Mermaid or Graphviz definitions: the hierarchy of
+    its sections, or its local link neighborhood, the notes it links to and
+    those linking to it. [oystermark outline-diagram] prints them, and an
+    [outline-diagram] code fence in a note is replaced by one at build time,
+    its attributes choosing the view and format, e.g.
+    {v ```outline-diagram {view=links format=dot} v} *)
+
+open Core
+
+type format =
+  | Mermaid
+  | Dot
+[@@deriving sexp_of]
+
+let format_of_string : string -> format option = function
+  | "mermaid" -> Some Mermaid
+  | "dot" -> Some Dot
+  | _ -> None
+;;
+
+type view =
+  | Sections
+  | Links
+[@@deriving sexp_of]
+
+let view_of_string : string -> view option = function
+  | "sections" -> Some Sections
+  | "links" -> Some Links
+  | _ -> None
+;;
+
+(** Language of the code fences replaced by a diagram. *)
+let fence_lang = "outline-diagram"
+
+type t =
+  { nodes : (string * string) list (** id and label, in order *)
+  ; edges : (string * string) list (** ids of the two ends *)
+  }
+
+let title_of_path (path : string) : string =
+  String.chop_suffix_if_exists (Filename.basename path) ~suffix:".md"
+;;
+
+(** The sections of note [path], nested as {!Query.Section.of_doc} nests
+    them: the note, then a node per section under its parent. *)
+let of_sections ~(path : string) (doc : Cmarkit.Doc.t) : t =
+  (* The root first, its path [[]] *)
+  let sections : Query.Section.t list =
+    Query.Section.descendants (Query.Section.of_doc doc)
+  in
+  let ids : (int list * string) list =
+    List.mapi sections ~f:(fun i (s : Query.Section.t) -> s.path, sprintf "n%d" i)
+  in
+  let id_of (p : int list) : string =
+    List.Assoc.find_exn ids ~equal:[%equal: int list] p
+  in
+  { nodes =
+      List.mapi sections ~f:(fun i (s : Query.Section.t) ->
+        id_of s.path, if i = 0 then title_of_path path else s.title)
+  ; edges =
+      List.filter_map sections ~f:(fun (s : Query.Section.t) ->
+        Option.map (List.drop_last s.path) ~f:(fun parent -> id_of parent, id_of s.path))
+  }
+;;
+
+(** The neighborhood of note [path] among resolved [links]: the note, the
+    notes and files it links to, and the notes linking to it. *)
+let of_links ~(path : string) (links : Links.t list) : t =
+  let outgoing : (string * string) list =
+    List.filter_map links ~f:(fun (l : Links.t) ->
+      if not (String.equal l.source path)
+      then None
+      else (
+        match Links.target_path l with
+        | Some target when not (String.equal target path) -> Some (path, target)
+        | _ -> None))
+  in
+  let incoming : (string * string) list =
+    List.map (Links.backlinks links ~path) ~f:(fun (l : Links.t) -> l.source, path)
+  in
+  (* first occurrences, in order *)
+  let dedup (type a) ~(equal : a -> a -> bool) (l : a list) : a list =
+    List.fold l ~init:[] ~f:(fun acc x -> if List.mem acc x ~equal then acc else x :: acc)
+    |> List.rev
+  in
+  let edges = dedup ~equal:[%equal: string * string] (outgoing @ incoming) in
+  let paths =
+    dedup ~equal:String.equal (path :: List.concat_map edges ~f:(fun (a, b) -> [ a; b ]))
+  in
+  let id_of : string String.Map.t =
+    List.mapi paths ~f:(fun i p -> p, sprintf "n%d" i) |> String.Map.of_alist_exn
+  in
+  { nodes = List.map paths ~f:(fun p -> Map.find_exn id_of p, title_of_path p)
+  ; edges = List.map edges ~f:(fun (a, b) -> Map.find_exn id_of a, Map.find_exn id_of b)
+  }
+;;
+
+let to_mermaid (t : t) : string =
+  let label (s : string) : string =
+    String.substr_replace_all s ~pattern:"\"" ~with_:"#quot;"
+  in
+  List.concat
+    [ [ "flowchart TD" ]
+    ; List.map t.nodes ~f:(fun (id, text) -> sprintf "  %s[\"%s\"]" id (label text))
+    ; List.map t.edges ~f:(fun (a, b) -> sprintf "  %s --> %s" a b)
+    ]
+  |> String.concat_map ~f:(fun line -> line ^ "\n")
+;;
+
+let to_dot (t : t) : string =
+  let label (s : string) : string =
+    String.concat_map s ~f:(function
+      | '"' -> "\\\""
+      | '\\' -> "\\\\"
+      | c -> String.of_char c)
+  in
+  List.concat
+    [ [ "digraph outline {"; "  node [shape=box];" ]
+    ; List.map t.nodes ~f:(fun (id, text) ->
+        sprintf "  %s [label=\"%s\"];" id (label text))
+    ; List.map t.edges ~f:(fun (a, b) -> sprintf "  %s -> %s;" a b)
+    ; [ "}" ]
+    ]
+  |> String.concat_map ~f:(fun line -> line ^ "\n")
+;;
+
+let render (format : format) (t : t) : string =
+  match format with
+  | Mermaid -> to_mermaid t
+  | Dot -> to_dot t
+;;
+
+let%expect_test "of_sections" =
+  let doc =
+    Parse.of_string "# Intro\n\n## Why\n\n> ## Quoted\n\n# Usage\n\n### Flags\n\n## Env\n"
+  in
+  let t = of_sections ~path:"dir/say \"hi\".md" doc in
+  print_string (to_mermaid t);
+  [%expect
+    {|
+    flowchart TD
+      n0["say #quot;hi#quot;"]
+      n1["Intro"]
+      n2["Why"]
+      n3["Usage"]
+      n4["Flags"]
+      n5["Env"]
+      n0 --> n1
+      n1 --> n2
+      n0 --> n3
+      n3 --> n4
+      n3 --> n5
+    |}];
+  print_string (to_dot t);
+  [%expect
+    {|
+    digraph outline {
+      node [shape=box];
+      n0 [label="say \"hi\""];
+      n1 [label="Intro"];
+      n2 [label="Why"];
+      n3 [label="Usage"];
+      n4 [label="Flags"];
+      n5 [label="Env"];
+      n0 -> n1;
+      n1 -> n2;
+      n0 -> n3;
+      n3 -> n4;
+      n3 -> n5;
+    }
+    |}]
+;;
+
+let%expect_test "of_links" =
+  let docs =
+    List.map
+      [ "a.md", "See [[b]], [[b#Intro]] and [[#Top]].\n"
+      ; "b.md", "# Intro\n\nBack to [[a]].\n"
+      ; "c.md", "Also [[a]].\n"
+      ]
+      ~f:(fun (path, src) -> path, Parse.of_string ~locs:false src)
+  in
+  let index : Index.t =
+    { files =
+        List.map docs ~f:(fun (rel_path, doc) : Index.file_entry ->
+          { rel_path; headings = Index.extract_headings doc; blocks = []; attrs = [] })
+    ; dirs = []
+    }
+  in
+  Links.of_docs (Resolve.resolve_docs docs index)
+  |> of_links ~path:"a.md"
+  |> to_mermaid
+  |> print_string;
+  [%expect
+    {|
+    flowchart TD
+      n0["a"]
+      n1["b"]
+      n2["c"]
+      n0 --> n1
+      n1 --> n0
+      n2 --> n0
+    |}]
+;;
@@ -0,0 +1,278 @@
+(** Prose lint for [oyster lint --prose] and the LSP: spelling against a
+    dictionary, and a few style checks, over the text of a note.
+
+    Only text is checked: code spans and blocks, links (their text too),
+    wikilinks, images, autolinks and raw HTML are skipped. The dictionary is
+    one or more word lists, one word per line, such as the system's
+    {!system_dictionary}, plus the vault's own {!wordlist_path} for names
+    and jargon. Without a dictionary, only style is checked. *)
+
+open Core
+
+type kind =
+  | Spelling
+  | Repeated_word
+  | Wordy of string (** the shorter wording to use instead *)
+  | Space_before_punctuation
+[@@deriving sexp_of]
+
+type issue =
+  { first_byte : int
+  ; last_byte : int
+  ; kind : kind
+  ; message : string
+  }
+[@@deriving sexp_of]
+
+let system_dictionary = "/usr/share/dict/words"
+
+(** The vault's word list, from the vault root. *)
+let wordlist_path = ".oyster/wordlist.txt"
+
+module Dictionary = struct
+  (* lowercase *)
+  type t = String.Hash_set.t
+
+  let empty () : t = String.Hash_set.create ()
+  let is_empty (t : t) : bool = Hash_set.is_empty t
+
+  (** Adds the words of a word list: one per line, blank lines and lines
+      starting with [#] ignored. *)
+  let add_list (t : t) (content : string) : unit =
+    String.split_lines content
+    |> List.iter ~f:(fun line ->
+      match String.strip line with
+      | "" -> ()
+      | w when String.is_prefix w ~prefix:"#" -> ()
+      | w -> Hash_set.add t (String.lowercase w))
+  ;;
+
+  let of_lists (contents : string list) : t =
+    let t = empty () in
+    List.iter contents ~f:(add_list t);
+    t
+  ;;
+
+  let mem (t : t) (word : string) : bool = Hash_set.mem t (String.lowercase word)
+end
+
+(** The dictionary of the vault at [vault_root]: [dictionary] (default
+    {!system_dictionary}) and the vault's {!wordlist_path}, those that exist. *)
+let load_dictionary ?(dictionary = system_dictionary) (vault_root : string)
+  : Dictionary.t
+  =
+  [ dictionary; Filename.concat vault_root wordlist_path ]
+  |> List.filter ~f:(fun path -> Sys_unix.file_exists_exn path)
+  |> List.map ~f:In_channel.read_all
+  |> Dictionary.of_lists
+;;
+
+(* Wordy phrase, lowercase, and what to write instead. *)
+let wordy_phrases : (string * string) list =
+  [ "in order to", "to"
+  ; "due to the fact that", "because"
+  ; "in spite of the fact that", "although"
+  ; "at this point in time", "now"
+  ; "for the purpose of", "for"
+  ; "a large number of", "many"
+  ; "is able to", "can"
+  ; "in the event that", "if"
+  ]
+;;
+
+(* Words of [s] with their byte offsets: runs of letters and digits, with
+   apostrophes between letters (don't) included. Non-ASCII bytes count as
+   letters, so accented words stay whole. *)
+let words (s : string) : (int * string) list =
+  let len = String.length s in
+  let is_word_char (c : char) : bool = Char.is_alphanum c || Char.to_int c >= 128 in
+  let rec go (i : int) (acc : (int * string) list) =
+    if i >= len
+    then List.rev acc
+    else if not (is_word_char s.[i])
+    then go (i + 1) acc
+    else (
+      let rec stop (j : int) =
+        if j < len && is_word_char s.[j]
+        then stop (j + 1)
+        else if j + 1 < len && Char.equal s.[j] '\'' && is_word_char s.[j + 1]
+        then stop (j + 2)
+        else j
+      in
+      let j = stop i in
+      go j ((i, String.sub s ~pos:i ~len:(j - i)) :: acc))
+  in
+  go 0 []
+;;
+
+(* Whether [word], at [pos] in [s], is worth spelling: not a number, an
+   acronym, camelCase, a single letter or non-ASCII, nor part of a file
+   name, path or address (next to [.], [/], [@] or [_] within a word). *)
+let is_checkable (s : string) (pos : int) (word : string) : bool =
+  let joined (i : int) (j : int) : bool =
+    i >= 0
+    && j >= 0
+    && j < String.length s
+    && i < String.length s
+    && List.mem [ '.'; '/'; '@'; '_' ] s.[i] ~equal:Char.equal
+    && Char.is_alphanum s.[j]
+  in
+  String.length word > 1
+  && String.for_all word ~f:(fun c -> Char.is_alpha c || Char.equal c '\'')
+  && not (String.exists (String.drop_prefix word 1) ~f:Char.is_uppercase)
+  && (not (joined (pos - 1) (pos - 2)))
+  && not (joined (pos + String.length word) (pos + String.length word + 1))
+;;
+
+let is_known (dictionary : Dictionary.t) (word : string) : bool =
+  Dictionary.mem dictionary word
+  ||
+  match String.chop_suffix word ~suffix:"'s" with
+  | Some stem -> Dictionary.mem dictionary stem
+  | None -> false
+;;
+
+(* Issues in text [s], as offsets into [s]. *)
+let check_text ?(dictionary : Dictionary.t option) (s : string)
+  : (int * int * kind * string) list
+  =
+  let words = words s in
+  let spelling =
+    match dictionary with
+    | Some d when not (Dictionary.is_empty d) ->
+      List.filter_map words ~f:(fun (pos, w) ->
+        if is_checkable s pos w && not (is_known d w)
+        then Some (pos, pos + String.length w, Spelling, sprintf "unknown word: %S" w)
+        else None)
+    | _ -> []
+  in
+  let repeated =
+    let rec go = function
+      | (i, a) :: ((j, b) :: _ as rest) ->
+        let stop = i + String.length a in
+        let between = String.sub s ~pos:stop ~len:(j - stop) in
+        if String.Caseless.equal a b
+           && (not (String.is_empty between))
+           && String.for_all between ~f:Char.is_whitespace
+        then
+          (i, j + String.length b, Repeated_word, sprintf "repeated word: %S" b)
+          :: go rest
+        else go rest
+      | _ -> []
+    in
+    go words
+  in
+  let wordy =
+    let lower = String.lowercase s in
+    List.concat_map wordy_phrases ~f:(fun (phrase, instead) ->
+      String.substr_index_all lower ~may_overlap:false ~pattern:phrase
+      |> List.filter ~f:(fun i ->
+        let stop = i + String.length phrase in
+        (i = 0 || not (Char.is_alphanum lower.[i - 1]))
+        && (stop >= String.length lower || not (Char.is_alphanum lower.[stop])))
+      |> List.map ~f:(fun i ->
+        let stop = i + String.length phrase in
+        ( i
+        , stop
+        , Wordy instead
+        , sprintf "wordy: %S; consider %S" (String.sub s ~pos:i ~len:(stop - i)) instead
+        )))
+  in
+  let space_before_punctuation =
+    List.init (String.length s) ~f:Fn.id
+    |> List.filter_map ~f:(fun i ->
+      let next = if i + 1 < String.length s then Some s.[i + 1] else None in
+      if i > 0
+         && List.mem [ ','; ';'; '.' ] s.[i] ~equal:Char.equal
+         && Char.equal s.[i - 1] ' '
+         && (i < 2 || not (Char.is_whitespace s.[i - 2]))
+         && not
+              (Option.value_map next ~default:false ~f:(fun c ->
+                 Char.equal c '.' || Char.is_alphanum c))
+      then
+        Some
+          ( i - 1
+          , i + 1
+          , Space_before_punctuation
+          , sprintf "space before %C" s.[i] )
+      else None)
+  in
+  spelling @ repeated @ wordy @ space_before_punctuation
+;;
+
+(** The issues in the text of [doc], parsed with locations, sorted by
+    position. *)
+let check ?(dictionary : Dictionary.t option) (doc : Cmarkit.Doc.t) : issue list =
+  let of_text (s : string) (meta : Cmarkit.Meta.t) : issue list =
+    let loc = Cmarkit.Meta.textloc meta in
+    if Cmarkit.Textloc.is_none loc
+    then []
+    else (
+      let first = Cmarkit.Textloc.first_byte loc in
+      let last = Cmarkit.Textloc.last_byte loc in
+      (* Escapes and entities make the text differ from its source; then an
+         issue covers the whole text. *)
+      let exact = String.length s = last - first + 1 in
+      check_text ?dictionary s
+      |> List.map ~f:(fun (start, stop, kind, message) ->
+        if exact
+        then { first_byte = first + start; last_byte = first + stop - 1; kind; message }
+        else { first_byte = first; last_byte = last; kind; message }))
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline_ext_default:(fun _f acc _i -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ~inline:(fun _f acc (i : Cmarkit.Inline.t) ->
+        match i with
+        | Cmarkit.Inline.Text (s, meta) -> Cmarkit.Folder.ret (of_text s meta @ acc)
+        | Cmarkit.Inline.Link _
+        | Cmarkit.Inline.Image _
+        | Cmarkit.Inline.Autolink _
+        | Cmarkit.Inline.Raw_html _
+        | Cmarkit.Inline.Ext_wikilink _ -> Cmarkit.Folder.ret acc
+        | _ -> Cmarkit.Folder.default)
+      ()
+  in
+  Cmarkit.Folder.fold_doc folder [] doc
+  |> List.sort ~compare:(fun a b ->
+    match Int.compare a.first_byte b.first_byte with
+    | 0 -> Int.compare a.last_byte b.last_byte
+    | c -> c)
+;;
+
+(** [issue] in note [path] with source [content], as
+    [PATH:LINE:COLUMN: MESSAGE], both 1-based. *)
+let to_string_hum ~(path : string) ~(content : string) (issue : issue) : string =
+  let before = String.prefix content issue.first_byte in
+  let line = 1 + String.count before ~f:(Char.equal '\n') in
+  let column =
+    match String.rindex before '\n' with
+    | Some i -> issue.first_byte - i
+    | None -> issue.first_byte + 1
+  in
+  sprintf "%s:%d:%d: %s" path line column issue.message
+;;
+
+let%expect_test "check" =
+  let dictionary =
+    Dictionary.of_lists
+      [ "a\nthe\nnote\nis\nin\norder\nto\nsee\ntide\nwe\nread\nit\nand\nlist\nsaid\n"
+      ; "# vault words\nOyster\n"
+      ]
+  in
+  let content =
+    "---\ntags: [x]\n---\n# The tide\n\n\
+     We read teh note in order to see it , and the the list.\n\n\
+     `teh` and [teh](x.md), [[Teh]], notes.md, said Oyster's API.\n"
+  in
+  check ~dictionary (Parse.of_string content)
+  |> List.iter ~f:(fun i -> print_endline (to_string_hum ~path:"n.md" ~content i));
+  [%expect
+    {|
+    n.md:6:9: unknown word: "teh"
+    n.md:6:18: wordy: "in order to"; consider "to"
+    n.md:6:36: space before ','
+    n.md:6:43: repeated word: "the"
+    |}]
+;;
@@ -1,11 +1,14 @@
 (** Link resolution algorithm: resolves link references against a vault index. *)
 
 open Core
+open Ppx_yojson_conv_lib.Yojson_conv.Primitives
 
 type textloc = Cmarkit.Textloc.t
 
 let sexp_of_textloc = Parse.Textloc_conv.sexp_of_t
 let textloc_of_sexp = Parse.Textloc_conv.t_of_sexp
+let yojson_of_textloc = Parse.Textloc_conv.yojson_of_t
+let textloc_of_yojson = Parse.Textloc_conv.t_of_yojson
 
 type target =
   | Note of { path : string }
@@ -15,35 +18,35 @@ type target =
       ; heading : string
       ; level : int
       ; slug : string
-      ; loc : textloc option [@sexp.option]
+      ; loc : textloc option [@sexp.option] [@yojson.option]
       }
   | Block of
       { path : string
       ; block_id : string
-      ; loc : textloc option [@sexp.option]
+      ; loc : textloc option [@sexp.option] [@yojson.option]
       }
   | Attr of
       { path : string
       ; id : string
-      ; loc : textloc option [@sexp.option]
+      ; loc : textloc option [@sexp.option] [@yojson.option]
       }
   | Curr_file
   | Curr_heading of
       { heading : string
       ; level : int
       ; slug : string
-      ; loc : textloc option [@sexp.option]
+      ; loc : textloc option [@sexp.option] [@yojson.option]
       }
   | Curr_block of
       { block_id : string
-      ; loc : textloc option [@sexp.option]
+      ; loc : textloc option [@sexp.option] [@yojson.option]
       }
   | Curr_attr of
       { id : string
-      ; loc : textloc option [@sexp.option]
+      ; loc : textloc option [@sexp.option] [@yojson.option]
       }
   | Unresolved
-[@@deriving sexp]
+[@@deriving sexp, yojson]
 
 let resolved_key : target Cmarkit.Meta.key = Cmarkit.Meta.key ()
 
@@ -94,23 +97,253 @@ let%expect_test "is_path_subsequence" =
     |}]
 ;;
 
-(** Resolve a target string to a file entry. Exact match first, then subsequence. *)
-let resolve_file (files : Index.file_entry list) (target_str : string)
-  : Index.file_entry option
+let normalize_target (s : string) : string = if String.mem s '.' then s else s ^ ".md"
+
+let find_exact (files : Index.file_entry list) (path : string) : Index.file_entry option =
+  List.find files ~f:(fun f -> String.equal f.rel_path path)
+;;
+
+(* [find_exact] through [lookup] when given, which must be built from [files]. *)
+let find_path ?(lookup : Index.Lookup.t option) files path : Index.file_entry option =
+  match lookup with
+  | Some lookup -> Index.Lookup.find_path lookup path
+  | None -> find_exact files path
+;;
+
+(** Every file [target_str] could name: the exact path when one exists,
+    otherwise every subsequence match, in index order. With [lookup] (built
+    from [files]) only files sharing the target's last path component are
+    tried, instead of all of them. *)
+let candidates
+      ?(lookup : Index.Lookup.t option)
+      (files : Index.file_entry list)
+      (target_str : string)
+  : Index.file_entry list
   =
-  let normalize_target s = if String.mem s '.' then s else s ^ ".md" in
   let normalized = normalize_target target_str in
   (* Exact match *)
-  match List.find files ~f:(fun f -> String.equal f.rel_path normalized) with
-  | Some _ as result -> result
+  match find_path ?lookup files normalized with
+  | Some exact -> [ exact ]
   | None ->
     (* Subsequence match: split needle into path components *)
     let needle = String.split normalized ~on:'/' in
-    List.find files ~f:(fun f ->
+    let pool : Index.file_entry list =
+      match lookup, List.last needle with
+      | Some lookup, Some name -> Index.Lookup.with_component lookup name
+      | _ -> files
+    in
+    List.filter pool ~f:(fun f ->
       let haystack = String.split f.rel_path ~on:'/' in
       is_path_subsequence ~haystack ~needle)
 ;;
 
+(* Folders of a vault-relative path, outermost first. *)
+let dirs_of (path : string) : string list =
+  String.split path ~on:'/' |> List.drop_last |> Option.value ~default:[]
+;;
+
+let shared_dirs (a : string) (b : string) : int =
+  List.zip_with_remainder (dirs_of a) (dirs_of b)
+  |> fst
+  |> List.take_while ~f:(fun (x, y) -> String.equal x y)
+  |> List.length
+;;
+
+(** Pick one of [candidates] for a link written in [curr_file], by [prefer]
+    (see {!Config.Link_resolution}). *)
+let choose
+      ~(prefer : Config.Link_preference.t)
+      ~(curr_file : string)
+      (candidates : Index.file_entry list)
+  : Index.file_entry option
+  =
+  let depth (f : Index.file_entry) : int = List.length (dirs_of f.rel_path) in
+  let by_path (a : Index.file_entry) (b : Index.file_entry) : int =
+    String.compare a.rel_path b.rel_path
+  in
+  match prefer, candidates with
+  | _, ([] | [ _ ]) | Config.Link_preference_def.First, _ -> List.hd candidates
+  | Config.Link_preference_def.Shortest, _ ->
+    List.min_elt candidates ~compare:(fun a b ->
+      match Int.compare (depth a) (depth b) with
+      | 0 -> by_path a b
+      | c -> c)
+  | Config.Link_preference_def.Nearest, _ ->
+    let shared (f : Index.file_entry) : int = shared_dirs f.rel_path curr_file in
+    List.min_elt candidates ~compare:(fun a b ->
+      match Int.compare (shared b) (shared a) with
+      | 0 ->
+        (match Int.compare (depth a) (depth b) with
+         | 0 -> by_path a b
+         | c -> c)
+      | c -> c)
+;;
+
+(** [target_str] joined to the folder of [curr_file], with [.] and [..]
+    segments removed; [None] when it climbs out of the vault root. *)
+let relative_path ~(curr_file : string) (target_str : string) : string option =
+  List.fold
+    (String.split target_str ~on:'/')
+    ~init:(Some (List.rev (dirs_of curr_file)))
+    ~f:(fun acc seg ->
+      match acc, seg with
+      | None, _ -> None
+      | Some dirs, ("." | "") -> Some dirs
+      | Some [], ".." -> None
+      | Some (_ :: dirs), ".." -> Some dirs
+      | Some dirs, seg -> Some (seg :: dirs))
+  |> Option.map ~f:(fun rev_segs -> String.concat ~sep:"/" (List.rev rev_segs))
+;;
+
+(** The relative link from [curr_file] to [path], both vault-relative: the
+    inverse of {!relative_path}. *)
+let relative_link ~(curr_file : string) (path : string) : string =
+  let rec drop_common (a : string list) (b : string list) =
+    match a, b with
+    | x :: a', y :: (_ :: _ as b') when String.equal x y -> drop_common a' b'
+    | _ -> a, b
+  in
+  let up, down = drop_common (dirs_of curr_file) (String.split path ~on:'/') in
+  String.concat ~sep:"/" (List.map up ~f:(Fn.const "..") @ down)
+;;
+
+let is_explicitly_relative (target_str : string) : bool =
+  String.is_prefix target_str ~prefix:"./" || String.is_prefix target_str ~prefix:"../"
+;;
+
+(* The file [target_str], of a Markdown link when [markdown], names relative
+   to the folder of [curr_file], when it is explicitly relative or [relative]
+   is set; wikilinks never resolve relative to the note, as in Obsidian. *)
+let relative_match
+      ?(lookup : Index.Lookup.t option)
+      ~(markdown : bool)
+      ~(relative : bool)
+      ~(curr_file : string)
+      (files : Index.file_entry list)
+      (target_str : string)
+  : Index.file_entry option
+  =
+  if markdown && (is_explicitly_relative target_str || relative)
+  then
+    Option.bind (relative_path ~curr_file target_str) ~f:(fun path ->
+      find_path ?lookup files (normalize_target path))
+  else None
+;;
+
+(** Resolve a target string to a file entry. Exact match first, then
+    subsequence; several subsequence matches are settled by [prefer].
+
+    For a Markdown link or image ([markdown]), a target starting with [./] or
+    [../] is relative to the folder of [curr_file] and resolves only there.
+    With [relative], any other target is first tried relative to that folder
+    too, then matched vault-wide. Wikilink targets are always matched
+    vault-wide. [lookup] speeds up the search; see {!candidates}. *)
+let resolve_file
+      ?(lookup : Index.Lookup.t option)
+      ?(prefer : Config.Link_preference.t = Config.Link_preference.default)
+      ?(markdown : bool = false)
+      ?(relative : bool = false)
+      ?(curr_file : string = "")
+      (files : Index.file_entry list)
+      (target_str : string)
+  : Index.file_entry option
+  =
+  let explicit : bool = markdown && is_explicitly_relative target_str in
+  match relative_match ?lookup ~markdown ~relative ~curr_file files target_str with
+  | Some _ as found -> found
+  | None when explicit -> None
+  | None -> choose ~prefer ~curr_file (candidates ?lookup files target_str)
+;;
+
+let%expect_test "resolve_file preference" =
+  let files : Index.file_entry list =
+    List.map [ "work/deep/Ideas.md"; "notes/Ideas.md"; "archive/Ideas.md" ] ~f:(fun p ->
+      ({ rel_path = p; headings = []; blocks = []; attrs = [] } : Index.file_entry))
+  in
+  List.iter
+    Config.Link_preference_def.[ First; Shortest; Nearest ]
+    ~f:(fun prefer ->
+      resolve_file ~prefer ~curr_file:"work/deep/today.md" files "Ideas"
+      |> Option.iter ~f:(fun (f : Index.file_entry) ->
+        printf "%s: %s\n" (Config.Link_preference.to_string prefer) f.rel_path));
+  [%expect
+    {|
+    first: work/deep/Ideas.md
+    shortest: archive/Ideas.md
+    nearest: work/deep/Ideas.md
+    |}]
+;;
+
+let%expect_test "resolve_file lookup" =
+  let files : Index.file_entry list =
+    List.map
+      [ "work/Ideas.md"; "Ideas.md"; "ideas/Other.md"; "notes/IDEAS.md"; "img/a.png" ]
+      ~f:(fun p ->
+        ({ rel_path = p; headings = []; blocks = []; attrs = [] } : Index.file_entry))
+  in
+  let lookup = Index.Lookup.create files in
+  List.iter
+    [ "Ideas"; "work/Ideas"; "ideas/Other"; "IDEAS"; "a.png"; "Other"; "missing" ]
+    ~f:(fun target ->
+      let show found =
+        Option.value_map found ~default:"<none>" ~f:(fun (f : Index.file_entry) ->
+          f.rel_path)
+      in
+      let scan = show (resolve_file files target)
+      and looked_up = show (resolve_file ~lookup files target) in
+      let mismatch = if String.equal scan looked_up then "" else " !" in
+      printf "%-12s %s%s\n" target scan mismatch);
+  [%expect
+    {|
+    Ideas        Ideas.md
+    work/Ideas   work/Ideas.md
+    ideas/Other  ideas/Other.md
+    IDEAS        notes/IDEAS.md
+    a.png        img/a.png
+    Other        ideas/Other.md
+    missing      <none>
+    |}]
+;;
+
+let%expect_test "resolve_file relative" =
+  let files : Index.file_entry list =
+    List.map
+      [ "guide/intro.md"; "guide/setup.md"; "setup.md"; "other/note.md" ]
+      ~f:(fun p ->
+        ({ rel_path = p; headings = []; blocks = []; attrs = [] } : Index.file_entry))
+  in
+  let show ?(markdown = true) ?relative target =
+    printf
+      "%-18s %s\n"
+      target
+      (match
+         resolve_file ~markdown ?relative ~curr_file:"guide/intro.md" files target
+       with
+       | Some f -> f.rel_path
+       | None -> "<none>")
+  in
+  show "../other/note.md";
+  show "./setup.md";
+  show "setup.md";
+  show ~relative:true "setup.md";
+  show ~relative:true "note.md";
+  show "../../escape.md";
+  (* Wikilink targets are vault paths, whatever [relative] says *)
+  show ~markdown:false "./setup.md";
+  show ~markdown:false ~relative:true "setup.md";
+  [%expect
+    {|
+    ../other/note.md   other/note.md
+    ./setup.md         guide/setup.md
+    setup.md           setup.md
+    setup.md           guide/setup.md
+    note.md            other/note.md
+    ../../escape.md    <none>
+    ./setup.md         <none>
+    setup.md           setup.md
+    |}]
+;;
+
 (** Resolve a heading query (list of heading texts) against document headings.
     Finds a subsequence where levels strictly increase (backtracking). *)
 let resolve_headings (headings : Index.heading_entry list) (query : string list)
@@ -165,11 +398,18 @@ let resolve_attr_of_heading_query (attrs : Index.attr_entry list) (hs : string l
 ;;
 
 (** Resolve a link reference against the vault index. *)
-let resolve (link_ref : Link_ref.t) (curr_file : string) (index : Index.t) : target =
+let resolve
+      ?(lookup : Index.Lookup.t option)
+      ?(prefer : Config.Link_preference.t option)
+      ?(markdown : bool option)
+      ?(relative : bool option)
+      (link_ref : Link_ref.t)
+      (curr_file : string)
+      (index : Index.t)
+  : target
+  =
   (* TODO(refactor): the matches be re-written to use Let_syntax? *)
-  let current_entry =
-    List.find index.files ~f:(fun f -> String.equal f.rel_path curr_file)
-  in
+  let current_entry = find_path ?lookup index.files curr_file in
   match link_ref.target with
   | None ->
     (* Self-reference: fragment only *)
@@ -198,7 +438,9 @@ let resolve (link_ref : Link_ref.t) (curr_file : string) (index : Index.t) : tar
               | None -> Curr_file))
         | None -> Curr_file))
   | Some target_str ->
-    (match resolve_file index.files target_str with
+    (match
+       resolve_file ?lookup ?prefer ?markdown ?relative ~curr_file index.files target_str
+     with
      | None -> Unresolved
      | Some file ->
        let file_or_note (path : string) : target =
@@ -229,15 +471,72 @@ let resolve (link_ref : Link_ref.t) (curr_file : string) (index : Index.t) : tar
               | None -> file_or_note file.rel_path))))
 ;;
 
-(** Build a [Cmarkit.Mapper.t] that resolves links against the vault index. *)
-let resolution_cmarkit_mapper ~(index : Index.t) ~(curr_file : string) : Cmarkit.Mapper.t =
+(** Paths of every file [link_ref], written in [curr_file], could name when
+    there is more than one, so the link is ambiguous; [[]] otherwise. A target
+    resolved relative to [curr_file] (see {!resolve_file}) is never
+    ambiguous. *)
+let ambiguous_candidates
+      ?(lookup : Index.Lookup.t option)
+      ?(markdown : bool = false)
+      ?(relative : bool = false)
+      ?(curr_file : string = "")
+      (link_ref : Link_ref.t)
+      (index : Index.t)
+  : string list
+  =
+  match link_ref.target with
+  | None -> []
+  | Some target_str
+    when (markdown && is_explicitly_relative target_str)
+         || Option.is_some
+              (relative_match
+                 ?lookup
+                 ~markdown
+                 ~relative
+                 ~curr_file
+                 index.files
+                 target_str) -> []
+  | Some target_str ->
+    (match candidates ?lookup index.files target_str with
+     | [] | [ _ ] -> []
+     | cs -> List.map cs ~f:(fun (f : Index.file_entry) -> f.rel_path))
+;;
+
+(** Build a [Cmarkit.Mapper.t] that resolves links against the vault index.
+    Markdown links and images resolve as {!resolve} with [markdown]; [relative]
+    applies to them, not to wikilinks. Reference
+    links ([[text][label]]) are resolved through [defs], the document's
+    {!Cmarkit.Doc.defs}; the [[label]: dest] blocks themselves are stamped too,
+    so a rename can rewrite the one destination they share. *)
+let resolution_cmarkit_mapper
+      ?(lookup : Index.Lookup.t option)
+      ?(prefer : Config.Link_preference.t option)
+      ?(relative : bool option)
+      ?(defs : Cmarkit.Label.defs option)
+      ~(index : Index.t)
+      ~(curr_file : string)
+      ()
+  : Cmarkit.Mapper.t
+  =
   Cmarkit.Mapper.make
+    ~block:(fun _m b ->
+      match b with
+      | Cmarkit.Block.Link_reference_definition (ld, meta) ->
+        (match Link_ref.of_link_definition ld with
+         | Some link_ref ->
+           let target =
+             resolve ?lookup ?prefer ~markdown:true ?relative link_ref curr_file index
+           in
+           let meta' = Cmarkit.Meta.add resolved_key target meta in
+           Cmarkit.Mapper.ret (Cmarkit.Block.Link_reference_definition (ld, meta'))
+         | None -> Cmarkit.Mapper.default)
+      | _ -> Cmarkit.Mapper.default)
     ~block_ext_default:(fun _m b -> Some b)
     ~inline_ext_default:(fun _m i ->
       match i with
       | Cmarkit.Inline.Ext_wikilink (w, meta) ->
         let link_ref = Link_ref.of_wikilink w in
-        let target = resolve link_ref curr_file index in
+        let target = resolve ?lookup ?prefer link_ref curr_file index in
         let meta' = Cmarkit.Meta.add resolved_key target meta in
         Some (Cmarkit.Inline.Ext_wikilink (w, meta'))
       | other -> Some other)
@@ -246,17 +545,21 @@ let resolution_cmarkit_mapper ~(index : Index.t) ~(curr_file : string) : Cmarkit
       (* TODO(code-duplication) *)
       | Cmarkit.Inline.Link (link, meta) ->
         let ref_ = Cmarkit.Inline.Link.reference link in
-        (match Link_ref.of_cmark_reference ref_ with
+        (match Link_ref.of_cmark_reference ?defs ref_ with
          | Some link_ref ->
-           let target = resolve link_ref curr_file index in
+           let target =
+             resolve ?lookup ?prefer ~markdown:true ?relative link_ref curr_file index
+           in
            let meta' = Cmarkit.Meta.add resolved_key target meta in
            Cmarkit.Mapper.ret (Cmarkit.Inline.Link (link, meta'))
          | None -> Cmarkit.Mapper.default)
       | Cmarkit.Inline.Image (link, meta) ->
         let ref_ = Cmarkit.Inline.Link.reference link in
-        (match Link_ref.of_cmark_reference ref_ with
+        (match Link_ref.of_cmark_reference ?defs ref_ with
          | Some link_ref ->
-           let target = resolve link_ref curr_file index in
+           let target =
+             resolve ?lookup ?prefer ~markdown:true ?relative link_ref curr_file index
+           in
            let meta' = Cmarkit.Meta.add resolved_key target meta in
            Cmarkit.Mapper.ret (Cmarkit.Inline.Image (link, meta'))
          | None -> Cmarkit.Mapper.default)
@@ -265,10 +568,24 @@ let resolution_cmarkit_mapper ~(index : Index.t) ~(curr_file : string) : Cmarkit
 ;;
 
 (** Resolve links in a list of parsed docs against the vault index. *)
-let resolve_docs (docs : (string * Cmarkit.Doc.t) list) (index : Index.t)
+let resolve_docs
+      ?(prefer : Config.Link_preference.t option)
+      ?(relative : bool option)
+      (docs : (string * Cmarkit.Doc.t) list)
+      (index : Index.t)
   : (string * Cmarkit.Doc.t) list
   =
+  let lookup = Index.Lookup.create index.files in
   List.map docs ~f:(fun (rel_path, doc) ->
-    let mapper = resolution_cmarkit_mapper ~index ~curr_file:rel_path in
+    let mapper =
+      resolution_cmarkit_mapper
+        ~lookup
+        ?prefer
+        ?relative
+        ~defs:(Cmarkit.Doc.defs doc)
+        ~index
+        ~curr_file:rel_path
+        ()
+    in
     rel_path, Cmarkit.Mapper.map_doc mapper doc)
 ;;
@@ -40,6 +40,12 @@ type target =
 val sexp_of_target : target -> Sexp.t
 val target_of_sexp : Sexp.t -> target
 
+(** JSON as derived by [ppx_yojson_conv]: [["Heading", {"path": ..}]],
+    [["Unresolved"]]; a missing [loc] is left out. *)
+val yojson_of_target : target -> Yojson.Safe.t
+
+val target_of_yojson : Yojson.Safe.t -> target
+
 (** Meta key for storing resolved targets in the target *)
 val resolved_key : target Cmarkit.Meta.key
 
@@ -52,13 +58,87 @@ val make_wikilink
   -> resolved_target:target
   -> Cmarkit.Inline.t
 
-(** Resolve a link reference against the vault index. *)
-val resolve : Link_ref.t -> string -> Index.t -> target
+(** Last heading of a heading query ([["Parent"; "Child"]]) matched in order
+    with strictly increasing levels, as a [#Parent#Child] fragment resolves. *)
+val resolve_headings
+  :  Index.heading_entry list
+  -> string list
+  -> Index.heading_entry option
+
+(** [target_str] joined to the folder of [curr_file], with [.] and [..]
+    segments removed; [None] when it climbs out of the vault root. *)
+val relative_path : curr_file:string -> string -> string option
+
+(** The relative link from [curr_file] to [path], both vault-relative: the
+    inverse of {!relative_path}. *)
+val relative_link : curr_file:string -> string -> string
+
+(** Whether [target_str] starts with [./] or [../]. *)
+val is_explicitly_relative : string -> bool
+
+(** The file a link target names, as {!resolve} finds it. *)
+val resolve_file
+  :  ?lookup:Index.Lookup.t
+  -> ?prefer:Config.Link_preference.t
+  -> ?markdown:bool
+  -> ?relative:bool
+  -> ?curr_file:string
+  -> Index.file_entry list
+  -> string
+  -> Index.file_entry option
 
-(** Build a [Cmarkit.Mapper.t] that resolves links against the vault index. *)
-val resolution_cmarkit_mapper : index:Index.t -> curr_file:string -> Cmarkit.Mapper.t
+(** Resolve a link reference against the vault index. A link naming several
+    files picks one by [prefer] (default {!Config.Link_preference.default}).
+    For a Markdown link or image ([markdown], default [false]), a target
+    starting with [./] or [../] is resolved relative to the linking note's
+    folder only, and with [relative] (default [false]) other targets are
+    tried there first; a wikilink's target is always a vault path. See
+    {!Config.Link_resolution}.
+
+    [lookup] must be built from [index]; pass it when resolving many links,
+    as {!resolve_docs} does, to avoid scanning every file per link. *)
+val resolve
+  :  ?lookup:Index.Lookup.t
+  -> ?prefer:Config.Link_preference.t
+  -> ?markdown:bool
+  -> ?relative:bool
+  -> Link_ref.t
+  -> string
+  -> Index.t
+  -> target
+
+(** Paths of every file the link's target could name, when there is more than
+    one; [[]] for a link that is unambiguous, unresolved or to the current
+    file, and for one that resolves relative to [curr_file] (explicitly, or
+    with [relative]) as {!resolve} does given [markdown]. *)
+val ambiguous_candidates
+  :  ?lookup:Index.Lookup.t
+  -> ?markdown:bool
+  -> ?relative:bool
+  -> ?curr_file:string
+  -> Link_ref.t
+  -> Index.t
+  -> string list
+
+(** Build a [Cmarkit.Mapper.t] that resolves links against the vault index.
+    Markdown links and images resolve as {!resolve} with [markdown]; [relative]
+    applies to them, not to wikilinks. Reference
+    links ([[text][label]]) are resolved through [defs], the document's
+    {!Cmarkit.Doc.defs}; the [[label]: dest] blocks themselves are stamped too,
+    so a rename can rewrite the one destination they share. *)
+val resolution_cmarkit_mapper
+  :  ?lookup:Index.Lookup.t
+  -> ?prefer:Config.Link_preference.t
+  -> ?relative:bool
+  -> ?defs:Cmarkit.Label.defs
+  -> index:Index.t
+  -> curr_file:string
+  -> unit
+  -> Cmarkit.Mapper.t
 
 val resolve_docs
-  :  (string * Cmarkit.Doc.t) list
+  :  ?prefer:Config.Link_preference.t
+  -> ?relative:bool
+  -> (string * Cmarkit.Doc.t) list
   -> Index.t
   -> (string * Cmarkit.Doc.t) list
@@ -0,0 +1,238 @@
+(** Vault snapshots: a compact record of a vault's state at one point in time
+    (per note: content hash, heading slugs and outgoing links), saved as JSON so
+    two states can be compared later with {!diff}.
+
+    Unlike the build {!Manifest}, which only tracks hashes between consecutive
+    builds, a snapshot is taken on demand and keeps enough structure to report
+    section and link-graph changes. *)
+
+open Core
+
+type note =
+  { hash : string
+  ; sections : string list (** heading slugs, in document order *)
+  ; links : string list
+    (** resolved link targets ([path], [path#slug], [path#^id] or [path#id]),
+        sorted and deduplicated; links within the note and unresolved links are
+        left out *)
+  }
+[@@deriving sexp_of]
+
+type t =
+  { created : string (** ISO 8601 time the snapshot was taken *)
+  ; notes : note String.Map.t (** keyed by vault-relative path *)
+  }
+
+let link_of_target : Resolve.target -> string option = function
+  | Resolve.Note { path } | File { path } -> Some path
+  | Heading { path; slug; _ } -> Some (path ^ "#" ^ slug)
+  | Block { path; block_id; _ } -> Some (path ^ "#^" ^ block_id)
+  | Attr { path; id; _ } -> Some (path ^ "#" ^ id)
+  | Curr_file | Curr_heading _ | Curr_block _ | Curr_attr _ | Unresolved -> None
+;;
+
+(* Resolved targets of every link in [doc], as stamped by {!Resolve}. *)
+let links_of_doc (doc : Cmarkit.Doc.t) : string list =
+  let target acc (meta : Cmarkit.Meta.t) =
+    match Option.bind (Cmarkit.Meta.find Resolve.resolved_key meta) ~f:link_of_target with
+    | Some link -> link :: acc
+    | None -> acc
+  in
+  let folder =
+    Cmarkit.Folder.make
+      ~inline:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Link (_, meta) | Cmarkit.Inline.Image (_, meta) ->
+          Cmarkit.Folder.ret (target acc meta)
+        | _ -> Cmarkit.Folder.default)
+      ~inline_ext_default:(fun _f acc i ->
+        match i with
+        | Cmarkit.Inline.Ext_wikilink (_, meta) -> target acc meta
+        | _ -> acc)
+      ~block_ext_default:(fun _f acc _b -> acc)
+      ()
+  in
+  Cmarkit.Folder.fold_doc folder [] doc |> List.dedup_and_sort ~compare:String.compare
+;;
+
+(** Snapshot of resolved [docs] ([(rel_path, doc)], as in {!Vault.t}) whose
+    source text is given by [content_of]. *)
+let create
+      ~(now : string)
+      ~(content_of : string -> string)
+      (docs : (string * Cmarkit.Doc.t) list)
+  : t
+  =
+  let notes =
+    List.map docs ~f:(fun (path, doc) ->
+      ( path
+      , { hash = Manifest.hash_content (content_of path)
+        ; sections =
+            List.map (Index.extract_headings doc) ~f:(fun (h : Index.heading_entry) ->
+              h.slug)
+        ; links = links_of_doc doc
+        } ))
+    |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+  in
+  { created = now; notes }
+;;
+
+(** {1 Diff} *)
+
+type note_change =
+  { path : string
+  ; sections_added : string list
+  ; sections_removed : string list
+  }
+[@@deriving sexp_of]
+
+type diff =
+  { added : string list
+  ; removed : string list
+  ; changed : note_change list (** content hash differs *)
+  ; links_added : (string * string) list (** [(source note, target)] *)
+  ; links_removed : (string * string) list
+  }
+[@@deriving sexp_of]
+
+let minus (a : string list) (b : string list) : string list =
+  List.filter a ~f:(fun x -> not (List.mem b x ~equal:String.equal))
+;;
+
+let edges (t : t) : (string * string) list =
+  Map.to_alist t.notes
+  |> List.concat_map ~f:(fun (path, n) -> List.map n.links ~f:(fun l -> path, l))
+;;
+
+(** What changed from snapshot [old] to [new_]. *)
+let diff ~(old : t) (new_ : t) : diff =
+  let paths (t : t) = Map.keys t.notes in
+  let changed =
+    Map.to_alist new_.notes
+    |> List.filter_map ~f:(fun (path, n) ->
+      match Map.find old.notes path with
+      | Some o when not (String.equal o.hash n.hash) ->
+        Some
+          { path
+          ; sections_added = minus n.sections o.sections
+          ; sections_removed = minus o.sections n.sections
+          }
+      | _ -> None)
+  in
+  let edge_mem es e = List.mem es e ~equal:[%equal: string * string] in
+  let old_edges = edges old
+  and new_edges = edges new_ in
+  { added = minus (paths new_) (paths old)
+  ; removed = minus (paths old) (paths new_)
+  ; changed
+  ; links_added = List.filter new_edges ~f:(fun e -> not (edge_mem old_edges e))
+  ; links_removed = List.filter old_edges ~f:(fun e -> not (edge_mem new_edges e))
+  }
+;;
+
+let is_empty_diff (d : diff) : bool =
+  List.is_empty d.added
+  && List.is_empty d.removed
+  && List.is_empty d.changed
+  && List.is_empty d.links_added
+  && List.is_empty d.links_removed
+;;
+
+(** One line per change: [+]/[-]/[~] for notes, [link +]/[link -] for edges. *)
+let diff_to_string_hum (d : diff) : string =
+  let sections (c : note_change) : string =
+    match
+      List.map c.sections_added ~f:(fun s -> "+#" ^ s)
+      @ List.map c.sections_removed ~f:(fun s -> "-#" ^ s)
+    with
+    | [] -> ""
+    | parts -> " (" ^ String.concat ~sep:" " parts ^ ")"
+  in
+  List.concat
+    [ List.map d.added ~f:(fun p -> "+ " ^ p)
+    ; List.map d.removed ~f:(fun p -> "- " ^ p)
+    ; List.map d.changed ~f:(fun c -> "~ " ^ c.path ^ sections c)
+    ; List.map d.links_added ~f:(fun (s, t) -> sprintf "link + %s -> %s" s t)
+    ; List.map d.links_removed ~f:(fun (s, t) -> sprintf "link - %s -> %s" s t)
+    ]
+  |> List.map ~f:(fun l -> l ^ "\n")
+  |> String.concat
+;;
+
+(** {1 Persistence} *)
+
+let to_json (t : t) : Yojson.Basic.t =
+  let strings l = `List (List.map l ~f:(fun s -> `String s)) in
+  `Assoc
+    [ "created", `String t.created
+    ; ( "notes"
+      , `Assoc
+          (Map.to_alist t.notes
+           |> List.map ~f:(fun (path, n) ->
+             ( path
+             , `Assoc
+                 [ "hash", `String n.hash
+                 ; "sections", strings n.sections
+                 ; "links", strings n.links
+                 ] ))) )
+    ]
+;;
+
+let of_json (j : Yojson.Basic.t) : t =
+  let open Yojson.Basic.Util in
+  let strings v = v |> to_list |> List.map ~f:to_string in
+  { created = j |> member "created" |> to_string
+  ; notes =
+      j
+      |> member "notes"
+      |> to_assoc
+      |> List.map ~f:(fun (path, v) ->
+        ( path
+        , { hash = v |> member "hash" |> to_string
+          ; sections = v |> member "sections" |> strings
+          ; links = v |> member "links" |> strings
+          } ))
+      |> String.Map.of_alist_reduce ~f:(fun a _ -> a)
+  }
+;;
+
+let save (t : t) ~(path : string) : unit =
+  Core_unix.mkdir_p (Filename.dirname path);
+  Yojson.Basic.to_file path (to_json t)
+;;
+
+(** Read a snapshot saved by {!save}. Raises on a missing or malformed file. *)
+let load (path : string) : t = of_json (Yojson.Basic.from_file path)
+
+let%expect_test "diff" =
+  let note ?(sections = []) ?(links = []) hash = { hash; sections; links } in
+  let snapshot notes = { created = "T"; notes = String.Map.of_alist_exn notes } in
+  let old =
+    snapshot
+      [ "a.md", note "1" ~sections:[ "intro"; "usage" ] ~links:[ "b.md" ]
+      ; "b.md", note "2"
+      ; "gone.md", note "3" ~links:[ "a.md" ]
+      ]
+  in
+  let new_ =
+    snapshot
+      [ "a.md", note "1b" ~sections:[ "intro"; "faq" ] ~links:[ "c.md#^x" ]
+      ; "b.md", note "2"
+      ; "c.md", note "4" ~links:[ "b.md" ]
+      ]
+  in
+  let d = diff ~old new_ in
+  print_string (diff_to_string_hum d);
+  [%expect
+    {|
+    + c.md
+    - gone.md
+    ~ a.md (+#faq -#usage)
+    link + a.md -> c.md#^x
+    link + c.md -> b.md
+    link - a.md -> b.md
+    link - gone.md -> a.md
+    |}];
+  print_s [%sexp (is_empty_diff (diff ~old:new_ (of_json (to_json new_))) : bool)];
+  [%expect {| true |}]
+;;
@@ -0,0 +1,175 @@
+(** Note templates: notes under a templates folder (see {!Config.Templates})
+    with [{{var}}] placeholders.
+
+    A template seeds a new note ([oyster new --template]) or, named by a
+    published note's [template:] frontmatter field, lays the note out at
+    export: placeholders take the note's frontmatter values and a paragraph
+    holding just [{{content}}] takes the note's own body. *)
+
+open Core
+
+(** Whether [path] lies in the templates [folder]. *)
+let is_template ~(folder : string) (path : string) : bool =
+  String.is_prefix path ~prefix:(String.rstrip folder ~drop:(Char.equal '/') ^ "/")
+;;
+
+(** Vault-relative path of template [name], e.g. [templates/project.md];
+    [None] if [name] is absolute or has a [..] segment, which could lead out
+    of the templates folder. *)
+let path ~(folder : string) (name : string) : string option =
+  if (not (Filename.is_relative name))
+     || List.mem (String.split name ~on:'/') ".." ~equal:String.equal
+  then None
+  else (
+    let name = if String.is_suffix name ~suffix:".md" then name else name ^ ".md" in
+    Some (String.rstrip folder ~drop:(Char.equal '/') ^ "/" ^ name))
+;;
+
+(** Placeholder values from frontmatter fields: scalars as written, lists
+    joined with [", "]. Nested maps are left out. *)
+let vars_of_frontmatter (fm : Yaml.value option) : (string * string) list =
+  match Option.map fm ~f:Parse.Value.of_yaml with
+  | Some (Parse.Value.Map fields) ->
+    List.filter_map fields ~f:(fun (key, v) ->
+      match v with
+      | Parse.Value.Map _ -> None
+      | Parse.Value.List vs ->
+        Some (key, String.concat ~sep:", " (List.map vs ~f:Parse.Value.to_markdown))
+      | v -> Some (key, Parse.Value.to_markdown v))
+  | _ -> []
+;;
+
+(** [text] with each [{{name}}] (spaces around [name] allowed) replaced by
+    [lookup name]. Names it has no value for are left as written. *)
+let substitute_with ~(lookup : string -> string option) (text : string) : string =
+  let buf = Buffer.create (String.length text) in
+  let rec go pos =
+    match String.substr_index text ~pos ~pattern:"{{" with
+    | None -> Buffer.add_string buf (String.drop_prefix text pos)
+    | Some open_ ->
+      Buffer.add_string buf (String.sub text ~pos ~len:(open_ - pos));
+      (match String.substr_index text ~pos:(open_ + 2) ~pattern:"}}" with
+       | None -> Buffer.add_string buf (String.drop_prefix text open_)
+       | Some close ->
+         let name =
+           String.strip (String.sub text ~pos:(open_ + 2) ~len:(close - open_ - 2))
+         in
+         Buffer.add_string
+           buf
+           (match lookup name with
+            | Some value -> value
+            | None -> String.sub text ~pos:open_ ~len:(close + 2 - open_));
+         go (close + 2))
+  in
+  go 0;
+  Buffer.contents buf
+;;
+
+(** {!substitute_with} taking values from [vars]. *)
+let substitute ~(vars : (string * string) list) (text : string) : string =
+  substitute_with ~lookup:(List.Assoc.find vars ~equal:String.equal) text
+;;
+
+(* Frontmatter block of [doc], if any, and its other top-level blocks. *)
+let split_frontmatter (doc : Cmarkit.Doc.t)
+  : Cmarkit.Block.t option * Cmarkit.Block.t list
+  =
+  match Cmarkit.Doc.block doc with
+  | Cmarkit.Block.Blocks ((Parse.Frontmatter.Frontmatter _ as fm) :: rest, _) ->
+    Some fm, rest
+  | Cmarkit.Block.Blocks (blocks, _) -> None, blocks
+  | Parse.Frontmatter.Frontmatter _ as fm -> Some fm, []
+  | b -> None, [ b ]
+;;
+
+let is_content_placeholder (b : Cmarkit.Block.t) : bool =
+  match b with
+  | Cmarkit.Block.Paragraph (p, _) ->
+    String.equal
+      (String.strip
+         (Parse.Heading_slug.inline_to_plain_text (Cmarkit.Block.Paragraph.inline p)))
+      "{{content}}"
+  | _ -> false
+;;
+
+(** [doc] laid out by [template], a template already parsed with its
+    placeholders substituted (see {!substitute}): [doc]'s frontmatter, then
+    the template's blocks with [doc]'s body in place of the [{{content}}]
+    paragraph, or after them when there is none. *)
+let apply ~(template : Cmarkit.Doc.t) (doc : Cmarkit.Doc.t) : Cmarkit.Doc.t =
+  let fm, body = split_frontmatter doc in
+  let _, layout = split_frontmatter template in
+  let blocks =
+    if List.exists layout ~f:is_content_placeholder
+    then
+      List.concat_map layout ~f:(fun b ->
+        if is_content_placeholder b then body else [ b ])
+    else layout @ body
+  in
+  Cmarkit.Doc.make (Cmarkit.Block.Blocks (Option.to_list fm @ blocks, Cmarkit.Meta.none))
+;;
+
+let%expect_test "path" =
+  List.iter [ "project"; "work/meeting.md"; "../secret"; "/etc/passwd" ] ~f:(fun name ->
+    let path = Option.value (path ~folder:"templates/" name) ~default:"-" in
+    printf "%s -> %s\n" name path);
+  [%expect
+    {|
+    project -> templates/project.md
+    work/meeting.md -> templates/work/meeting.md
+    ../secret -> -
+    /etc/passwd -> -
+    |}]
+;;
+
+let%expect_test "substitute" =
+  let vars = [ "title", "Oyster"; "status", "active" ] in
+  print_endline
+    (substitute ~vars "# {{title}}\n\nStatus: {{ status }}, owner: {{owner}}. {{");
+  [%expect
+    {|
+    # Oyster
+
+    Status: active, owner: {{owner}}. {{
+    |}]
+;;
+
+let%expect_test "apply" =
+  let doc = Parse.of_string "---\nstatus: active\ntags: [a, b]\n---\nNotes here.\n" in
+  let vars = vars_of_frontmatter (Parse.Frontmatter.of_doc doc) in
+  List.iter vars ~f:(fun (k, v) -> printf "%s = %s\n" k v);
+  [%expect
+    {|
+    status = active
+    tags = a, b
+    |}];
+  let template =
+    Parse.of_string
+      (substitute
+         ~vars
+         "---\nkind: project\n---\n**{{status}}** ({{tags}})\n\n{{content}}\n\n## Log\n")
+  in
+  let applied = apply ~template doc in
+  List.iter (vars_of_frontmatter (Parse.Frontmatter.of_doc applied)) ~f:(fun (k, v) ->
+    printf "frontmatter: %s = %s\n" k v);
+  let _, blocks = split_frontmatter applied in
+  List.iter blocks ~f:(fun b ->
+    match b with
+    | Cmarkit.Block.Paragraph (p, _) ->
+      print_endline
+        ("paragraph: "
+         ^ Parse.Heading_slug.inline_to_plain_text (Cmarkit.Block.Paragraph.inline p))
+    | Cmarkit.Block.Heading (h, _) ->
+      print_endline
+        ("heading: "
+         ^ Parse.Heading_slug.inline_to_plain_text (Cmarkit.Block.Heading.inline h))
+    | _ -> ());
+  [%expect
+    {|
+    frontmatter: status = active
+    frontmatter: tags = a, b
+    paragraph: active (a, b)
+    paragraph: Notes here.
+    heading: Log
+    |}]
+;;
@@ -0,0 +1,106 @@
+(** Transcripts of audio and video files, kept next to them as WebVTT
+    ([talk.vtt]), SubRip ([talk.srt]) or a markdown note ([talk.transcript.md])
+    for [talk.mp3]. *)
+
+open Core
+
+(** Suffix of a markdown transcript note. *)
+let md_suffix = ".transcript.md"
+
+let is_transcript_note (path : string) : bool = String.is_suffix path ~suffix:md_suffix
+
+(** Vault-relative paths a transcript of [media] may have, in order of
+    preference. *)
+let candidates (media : string) : string list =
+  let stem : string =
+    match String.rsplit2 media ~on:'.' with
+    | Some (stem, ext) when not (String.mem ext '/') -> stem
+    | _ -> media
+  in
+  [ stem ^ ".vtt"; stem ^ ".srt"; stem ^ md_suffix ]
+;;
+
+(** The first of {!candidates} for [media] that [exists]. *)
+let find ~(exists : string -> bool) (media : string) : string option =
+  List.find (candidates media) ~f:exists
+;;
+
+(** One timed line of a caption file. *)
+type cue =
+  { start : string (** as written, without the fraction of a second *)
+  ; text : string
+  }
+
+(* "00:01:02,500" (SubRip) or "01:02.500" (WebVTT) to "00:01:02" / "01:02". *)
+let timestamp (s : string) : string =
+  String.take_while (String.strip s) ~f:(fun c -> Char.(c <> ',' && c <> '.'))
+;;
+
+(* Drop WebVTT markup such as [<v Speaker>] and [<i>]. *)
+let strip_tags (s : string) : string =
+  let buf = Buffer.create (String.length s) in
+  let in_tag = ref false in
+  String.iter s ~f:(fun c ->
+    match c with
+    | '<' -> in_tag := true
+    | '>' when !in_tag -> in_tag := false
+    | c -> if not !in_tag then Buffer.add_char buf c);
+  Buffer.contents buf
+;;
+
+(** Cues of a WebVTT or SubRip file, in order. Blocks without a timing line
+    (the [WEBVTT] header, [NOTE] and [STYLE] blocks) are skipped. *)
+let cues_of_string (src : string) : cue list =
+  String.split_lines src
+  |> List.map ~f:String.rstrip
+  |> List.group ~break:(fun a b -> String.is_empty a <> String.is_empty b)
+  |> List.filter_map ~f:(fun lines ->
+    match
+      List.split_while lines ~f:(fun l -> not (String.is_substring l ~substring:"-->"))
+    with
+    | _, timing :: text ->
+      let start = fst (String.lsplit2_exn timing ~on:'-') |> timestamp in
+      let text =
+        List.map text ~f:(fun l -> String.strip (strip_tags l))
+        |> List.filter ~f:(Fn.non String.is_empty)
+        |> String.concat ~sep:" "
+      in
+      if String.is_empty text then None else Some { start; text }
+    | _, [] -> None)
+;;
+
+(** [cues] as HTML paragraphs, each led by its start time. *)
+let cues_to_html (cues : cue list) : string =
+  List.map cues ~f:(fun { start; text } ->
+    sprintf
+      "<p><span class=\"transcript-time\">%s</span> %s</p>\n"
+      (Parse.Frontmatter.escape_html start)
+      (Parse.Frontmatter.escape_html text))
+  |> String.concat
+;;
+
+let%expect_test "cues_of_string" =
+  let srt =
+    "1\n\
+     00:00:01,000 --> 00:00:03,500\n\
+     Hello and welcome.\n\n\
+     2\n\
+     00:00:04,000 --> 00:00:06,000\n\
+     Today: <i>oysters</i>\n\
+     & pearls.\n"
+  in
+  print_string (cues_to_html (cues_of_string srt));
+  [%expect
+    {|
+    <p><span class="transcript-time">00:00:01</span> Hello and welcome.</p>
+    <p><span class="transcript-time">00:00:04</span> Today: oysters &amp; pearls.</p>
+    |}];
+  let vtt =
+    "WEBVTT\n\n\
+     NOTE recorded live\n\n\
+     00:01.000 --> 00:02.000 align:start\n\
+     <v Ann>Hi there\n"
+  in
+  print_string (cues_to_html (cues_of_string vtt));
+  [%expect {| <p><span class="transcript-time">00:01</span> Hi there</p> |}]
+;;
@@ -0,0 +1,157 @@
+(** Placeholders filled at export in the text of notes that opt in with
+    [variables: true] in their frontmatter, so a footer or a snippet shared
+    across notes can name the page it ends up on:
+
+    - [{{site.title}}]: the site's title (see {!Config.Site})
+    - [{{note.title}}]: the note's [title] field, else its file name
+    - [{{note.FIELD}}]: any other frontmatter field, as {!Template} fills it
+    - [{{date}}], [{{date:FORMAT}}]: the build date; [FORMAT] takes [YYYY],
+      [YY], [MMMM] (January), [MMM] (Jan), [MM], [M], [DD], [D], [dddd]
+      (Monday) and [ddd] (Mon), anything else as written
+
+    Only text is filled: code spans and code blocks keep their placeholders,
+    so a note can still show the syntax. Unknown placeholders are left as
+    written. *)
+
+open Core
+
+(** Frontmatter field that, set to [true], turns placeholders on. *)
+let key = "variables"
+
+let enabled (doc : Cmarkit.Doc.t) : bool =
+  match Parse.Frontmatter.field doc key with
+  | Some (Parse.Value.Bool b) -> b
+  | _ -> false
+;;
+
+let month_names =
+  [| "January"; "February"; "March"; "April"; "May"; "June"; "July"; "August"
+   ; "September"; "October"; "November"; "December"
+  |] [@ocamlformat "disable"]
+;;
+
+let day_names =
+  [| "Sunday"; "Monday"; "Tuesday"; "Wednesday"; "Thursday"; "Friday"; "Saturday" |]
+;;
+
+(** [date] laid out by [fmt], e.g. [YYYY-MM-DD] or [D MMMM YYYY]. *)
+let format_date ~(fmt : string) (date : Date.t) : string =
+  let month = Month.to_int (Date.month date) in
+  let day_name = day_names.(Day_of_week.to_int (Date.day_of_week date)) in
+  (* longest first, so [MM] isn't read as two [M]s *)
+  let tokens : (string * string) list =
+    [ "YYYY", sprintf "%04d" (Date.year date)
+    ; "YY", sprintf "%02d" (Date.year date % 100)
+    ; "MMMM", month_names.(month - 1)
+    ; "MMM", String.prefix month_names.(month - 1) 3
+    ; "MM", sprintf "%02d" month
+    ; "M", Int.to_string month
+    ; "DD", sprintf "%02d" (Date.day date)
+    ; "D", Int.to_string (Date.day date)
+    ; "dddd", day_name
+    ; "ddd", String.prefix day_name 3
+    ]
+  in
+  let buf = Buffer.create (String.length fmt) in
+  let rec go (pos : int) =
+    if pos < String.length fmt
+    then (
+      match
+        List.find tokens ~f:(fun (token, _) ->
+          String.is_substring_at fmt ~pos ~substring:token)
+      with
+      | Some (token, value) ->
+        Buffer.add_string buf value;
+        go (pos + String.length token)
+      | None ->
+        Buffer.add_char buf fmt.[pos];
+        go (pos + 1))
+  in
+  go 0;
+  Buffer.contents buf
+;;
+
+(** The value of placeholder [name] in note [path], parsed as [doc]. *)
+let lookup
+      ~(site_title : string)
+      ~(today : Date.t)
+      ~(path : string)
+      (doc : Cmarkit.Doc.t)
+      (name : string)
+  : string option
+  =
+  match name with
+  | "site.title" -> Some site_title
+  | "note.title" ->
+    (match Parse.Frontmatter.field doc "title" with
+     | Some (Parse.Value.String title) -> Some title
+     | _ -> Some (fst (Filename.split_extension (Filename.basename path))))
+  | "date" -> Some (format_date ~fmt:"YYYY-MM-DD" today)
+  | name ->
+    (match String.chop_prefix name ~prefix:"date:" with
+     | Some fmt -> Some (format_date ~fmt today)
+     | None ->
+       let%bind.Option field = String.chop_prefix name ~prefix:"note." in
+       Template.vars_of_frontmatter (Parse.Frontmatter.of_doc doc)
+       |> List.Assoc.find ~equal:String.equal field)
+;;
+
+(** [doc] with the placeholders of its text filled, when it opts in. *)
+let substitute
+      ~(site_title : string)
+      ~(today : Date.t)
+      ~(path : string)
+      (doc : Cmarkit.Doc.t)
+  : Cmarkit.Doc.t
+  =
+  if not (enabled doc)
+  then doc
+  else (
+    let lookup = lookup ~site_title ~today ~path doc in
+    let mapper =
+      Cmarkit.Mapper.make
+        ~inline_ext_default:(fun _m i -> Some i)
+        ~block_ext_default:(fun _m b -> Some b)
+        ~inline:(fun _m (i : Cmarkit.Inline.t) ->
+          match i with
+          | Cmarkit.Inline.Text (s, meta) when String.is_substring s ~substring:"{{" ->
+            Cmarkit.Mapper.ret
+              (Cmarkit.Inline.Text (Template.substitute_with ~lookup s, meta))
+          | _ -> Cmarkit.Mapper.default)
+        ()
+    in
+    Cmarkit.Mapper.map_doc mapper doc)
+;;
+
+let%expect_test "substitute" =
+  let today = Date.create_exn ~y:2024 ~m:Month.May ~d:3 in
+  let show path src =
+    Parse.of_string src
+    |> substitute ~site_title:"Oyster" ~today ~path
+    |> Parse.commonmark_of_doc
+    |> print_string
+  in
+  show
+    "notes/Setup.md"
+    "---\nvariables: true\nstatus: draft\n---\n\
+     {{note.title}} on {{site.title}}, {{note.status}}, {{note.owner}}.\n\n\
+     Built {{date}} ({{date:dddd D MMMM YY}}, {{ date:MMM M/D }}). `{{date}}`\n";
+  [%expect
+    {|
+    ---
+    variables: true
+    status: draft
+    ---
+    Setup on Oyster, draft, {{note.owner}}.
+
+    Built 2024-05-03 (Friday 3 May 24, May 5/3). `{{date}}`
+    |}];
+  show "a.md" "---\ntitle: Custom\n---\n{{note.title}}\n";
+  [%expect
+    {|
+    ---
+    title: Custom
+    ---
+    {{note.title}}
+    |}]
+;;
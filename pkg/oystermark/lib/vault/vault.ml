@@ -1,7 +1,24 @@
 module Index = Index
 module Link_ref = Link_ref
+module Link_rewrite = Link_rewrite
+module Link_convert = Link_convert
+module Heading_rename = Heading_rename
+module Links = Links
+module Template = Template
+module Variables = Variables
+module Glossary = Glossary
 module Resolve = Resolve
 module Embed = Embed
+module Assets = Assets
+module Transcript = Transcript
+module Link_preview = Link_preview
+module Folder_config = Folder_config
+module Manifest = Manifest
+module Snapshot = Snapshot
+module Doctor = Doctor
+module Prose_lint = Prose_lint
+module Journal = Journal
+module Outline_diagram = Outline_diagram
 open Core
 
 type t =
@@ -11,17 +28,125 @@ type t =
   ; vault_meta : Cmarkit.Meta.t
   }
 
+(** A vault root mounted under a path prefix in a multi-vault build.
+    Entries of [root] appear in the merged vault as [prefix ^ "/" ^ rel_path];
+    an empty [prefix] mounts the root at the top level. *)
+type mount =
+  { prefix : string
+  ; root : string
+  }
+
+(** Meta key on {!t.vault_meta} recording the mounts a merged vault was built
+    from. Absent for single-root vaults. *)
+let mounts_key : mount list Cmarkit.Meta.key = Cmarkit.Meta.key ()
+
+let mounted_rel_path (m : mount) (rel_path : string) : string =
+  if String.is_empty m.prefix then rel_path else m.prefix ^ "/" ^ rel_path
+;;
+
+(** Map a merged-vault relative path back to the on-disk path. The longest
+    matching prefix wins, so nested mounts ([work] and [work/archive]) resolve
+    to the innermost root. *)
+let mount_full_path (mounts : mount list) (rel_path : string) : string option =
+  List.filter_map mounts ~f:(fun m ->
+    if String.is_empty m.prefix
+    then Some (0, Filename.concat m.root rel_path)
+    else
+      String.chop_prefix rel_path ~prefix:(m.prefix ^ "/")
+      |> Option.map ~f:(fun rest -> String.length m.prefix, Filename.concat m.root rest))
+  |> List.max_elt ~compare:(fun (a, _) (b, _) -> Int.compare a b)
+  |> Option.map ~f:snd
+;;
+
+(** On-disk path of a vault-relative path, honouring {!mounts_key}. *)
+let full_path (vault : t) (rel_path : string) : string =
+  match Cmarkit.Meta.find mounts_key vault.vault_meta with
+  | None -> Filename.concat vault.vault_root rel_path
+  | Some mounts ->
+    Option.value
+      (mount_full_path mounts rel_path)
+      ~default:(Filename.concat vault.vault_root rel_path)
+;;
+
+(** [rel_path] with [.] and [..] segments folded away, or [None] if it is
+    absolute or leads out of the directory it is relative to. *)
+let confined_path (rel_path : string) : string option =
+  if (not (Filename.is_relative rel_path)) || String.is_empty rel_path
+  then None
+  else
+    List.fold_result
+      (String.split rel_path ~on:'/')
+      ~init:[]
+      ~f:(fun acc seg ->
+        match seg, acc with
+        | ("" | "."), acc -> Ok acc
+        | "..", [] -> Error ()
+        | "..", _ :: rest -> Ok rest
+        | seg, acc -> Ok (seg :: acc))
+    |> Result.ok
+    |> Option.bind ~f:(function
+      | [] -> None
+      | segs -> Some (String.concat ~sep:"/" (List.rev segs)))
+;;
+
+let%expect_test "confined_path" =
+  List.iter
+    [ "src/lib.rs"; "./a//b/../c.md"; "../x.rs"; "a/../../x"; "/etc/passwd"; "a/.." ]
+    ~f:(fun p -> printf "%s -> %s\n" p (Option.value (confined_path p) ~default:"-"));
+  [%expect
+    {|
+    src/lib.rs -> src/lib.rs
+    ./a//b/../c.md -> a/c.md
+    ../x.rs -> -
+    a/../../x -> -
+    /etc/passwd -> -
+    a/.. -> -
+    |}]
+;;
+
 let all_entry_paths (vault : t) : string list =
   let doc_paths : string list = List.map vault.docs ~f:fst in
   doc_paths @ vault.index.dirs
 ;;
 
+(** Whether [doc] is unlisted ([unlisted: true] in its frontmatter): its page
+    is published and reachable by URL, but left out of the site's listings
+    (the sidebar, tables of contents, folder indexes and recent changes) and
+    marked [noindex] for search engines. *)
+let is_unlisted (doc : Cmarkit.Doc.t) : bool =
+  match Parse.Frontmatter.field doc "unlisted" with
+  | Some (Parse.Value.Bool b) -> b
+  | _ -> false
+;;
+
+(** [vault]'s docs that aren't {!is_unlisted}. *)
+let listed_docs (vault : t) : (string * Cmarkit.Doc.t) list =
+  List.filter vault.docs ~f:(fun (_, doc) -> not (is_unlisted doc))
+;;
+
 (** List all entries in the vault (files and directories, relative paths).
     Directories have a trailing [/].  Hidden entries are excluded. *)
 let list_entries (vault_root : string) : string list =
   Index.list_entries_recursive ~root:vault_root ~rel_prefix:""
 ;;
 
+(** List all entries of several mounted roots as one merged vault. Each
+    non-empty prefix also contributes its directory entries ([work/], and
+    [a/] and [a/b/] for a nested [a/b] prefix), so mounts show up as folders. *)
+let list_mounted_entries (mounts : mount list) : string list =
+  List.concat_map mounts ~f:(fun m ->
+    let prefix_dirs : string list =
+      if String.is_empty m.prefix
+      then []
+      else (
+        let segs = String.split m.prefix ~on:'/' in
+        List.mapi segs ~f:(fun i _ ->
+          String.concat ~sep:"/" (List.take segs (i + 1)) ^ "/"))
+    in
+    prefix_dirs @ List.map (list_entries m.root) ~f:(mounted_rel_path m))
+  |> List.dedup_and_sort ~compare:String.compare
+;;
+
 (** Build an index from a list of [(rel_path, doc)] pairs
     plus a list of non-md relative paths. *)
 let build_index
@@ -46,7 +171,11 @@ let build_index
 
 (** Simple build: read all .md files, optionally filter, build index.
     For pipeline-aware builds, use the lower-level functions directly. *)
-let of_root_path ?(skip_expand : bool = false) ?(locs : bool = true) (vault_root : string)
+let of_root_path
+      ?(skip_expand : bool = false)
+      ?(locs : bool = true)
+      ?(config : Config.t option)
+      (vault_root : string)
   : t
   =
   (* Scan files *)
@@ -60,7 +189,7 @@ let of_root_path ?(skip_expand : bool = false) ?(locs : bool = true) (vault_root
       then (
         let full_path = Filename.concat vault_root rel_path in
         let content = In_channel.read_all full_path in
-        let parsed = Parse.of_string ~locs content in
+        let parsed = Parse.of_string ~locs ?config content in
         Some (rel_path, parsed))
       else None)
   in
@@ -70,22 +199,44 @@ let of_root_path ?(skip_expand : bool = false) ?(locs : bool = true) (vault_root
   (* Build index *)
   let index = build_index ~md_docs:docs ~other_files ~dirs:[] in
   (* Resolve *)
-  let resolved_docs : (string * Cmarkit.Doc.t) list = Resolve.resolve_docs docs index in
+  let prefer = Option.map config ~f:(fun (c : Config.t) -> c.link_resolution.prefer)
+  and relative =
+    Option.map config ~f:(fun (c : Config.t) -> c.link_resolution.relative)
+  in
+  let resolved_docs : (string * Cmarkit.Doc.t) list =
+    Resolve.resolve_docs ?prefer ?relative docs index
+  in
   if skip_expand
   then { vault_root; index; docs = resolved_docs; vault_meta = Cmarkit.Meta.none }
   else (
-    let expanded_docs : (string * Cmarkit.Doc.t) list = Embed.expand_docs resolved_docs in
+    let slug_style : Parse.Heading_slug.slug_style option =
+      Option.map config ~f:(fun (c : Config.t) ->
+        Parse.Heading_slug.of_config c.parse.slug_style)
+    in
+    let expanded_docs : (string * Cmarkit.Doc.t) list =
+      Embed.expand_docs ?slug_style resolved_docs
+    in
     { vault_root; index; docs = expanded_docs; vault_meta = Cmarkit.Meta.none })
 ;;
 
 (** [of_inmem_files] creates a vault from a list of in-memory files.
   @param files A list of (path, content) pairs representing the files to include in the vault.
 *)
-let of_inmem_files ?(vault_root = "/tmp_vault") (files : (string * string) list) : t =
+let of_inmem_files
+      ?(vault_root = "/tmp_vault")
+      ?(config : Config.t option)
+      (files : (string * string) list)
+  : t
+  =
   let docs =
-    List.map files ~f:(fun (path, content) -> path, Parse.of_string ~locs:true content)
+    List.map files ~f:(fun (path, content) ->
+      path, Parse.of_string ~locs:true ?config content)
   in
   let index = build_index ~md_docs:docs ~other_files:[] ~dirs:[] in
-  let resolved_docs = Resolve.resolve_docs docs index in
+  let prefer = Option.map config ~f:(fun (c : Config.t) -> c.link_resolution.prefer)
+  and relative =
+    Option.map config ~f:(fun (c : Config.t) -> c.link_resolution.relative)
+  in
+  let resolved_docs = Resolve.resolve_docs ?prefer ?relative docs index in
   { vault_root; index; docs = resolved_docs; vault_meta = Cmarkit.Meta.none }
 ;;
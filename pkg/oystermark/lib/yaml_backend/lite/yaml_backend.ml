@@ -0,0 +1,5 @@
+type value = Yaml_lite.value
+
+let of_string = Yaml_lite.of_string
+let of_string_exn = Yaml_lite.of_string_exn
+let to_string_exn = Yaml_lite.to_string_exn
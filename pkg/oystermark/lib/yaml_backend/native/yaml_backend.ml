@@ -0,0 +1,5 @@
+type value = Yaml.value
+
+let of_string (s : string) : (value, [ `Msg of string ]) result = Yaml.of_string s
+let of_string_exn (s : string) : value = Yaml.of_string_exn s
+let to_string_exn (v : value) : string = Yaml.to_string_exn v
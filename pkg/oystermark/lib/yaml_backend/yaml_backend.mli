@@ -0,0 +1,16 @@
+(** Reading and writing frontmatter YAML: ocaml-yaml in native builds,
+    {!Yaml_lite} in the browser build. *)
+
+(** The same type as ocaml-yaml's [Yaml.value]. *)
+type value =
+  [ `Null
+  | `Bool of bool
+  | `Float of float
+  | `String of string
+  | `A of value list
+  | `O of (string * value) list
+  ]
+
+val of_string : string -> (value, [ `Msg of string ]) result
+val of_string_exn : string -> value
+val to_string_exn : value -> string
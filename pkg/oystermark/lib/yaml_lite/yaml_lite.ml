@@ -0,0 +1,770 @@
+(** The subset of YAML found in frontmatter, read without libyaml, into the
+    same value type as ocaml-yaml's [Yaml.value]. The browser build parses
+    frontmatter with it (see {!Yaml_backend}), as ocaml-yaml's C stubs have no
+    JavaScript implementation.
+
+    Supported: block mappings and sequences nested by indentation, including
+    [- key: value] items and sequences at their key's indentation; flow
+    sequences and mappings ([[a, b]], [{a: 1}]); plain, single- and
+    double-quoted scalars; literal ([|]) and folded ([>]) block scalars with
+    [-] and [+] chomping; comments. Not supported, and reported as errors:
+    anchors, aliases, tags, multi-line plain scalars and multiple documents.
+    Plain scalars resolve as ocaml-yaml resolves them: null, booleans, then
+    numbers as floats. *)
+
+open Core
+
+type value =
+  [ `Null
+  | `Bool of bool
+  | `Float of float
+  | `String of string
+  | `A of value list
+  | `O of (string * value) list
+  ]
+
+exception Parse_error of string
+
+let fail fmt = Printf.ksprintf (fun s -> raise (Parse_error s)) fmt
+
+(* A plain scalar, resolved as ocaml-yaml resolves it. *)
+let resolve (s : string) : value =
+  match s with
+  | "" | "~" | "null" | "Null" | "NULL" -> `Null
+  | "y" | "Y" | "yes" | "Yes" | "YES" | "true" | "True" | "TRUE" | "on" | "On" | "ON" ->
+    `Bool true
+  | "n" | "N" | "no" | "No" | "NO" | "false" | "False" | "FALSE" | "off" | "Off" | "OFF"
+    -> `Bool false
+  | ".inf" -> `Float Float.infinity
+  | "-.inf" -> `Float Float.neg_infinity
+  | ".nan" | ".NaN" | ".NAN" -> `Float Float.nan
+  | s ->
+    (match Stdlib.float_of_string_opt s with
+     | Some f -> `Float f
+     | None -> `String s)
+;;
+
+(* Can a quoted scalar start after [s.[i - 1]]? *)
+let quote_can_open (s : string) (i : int) : bool =
+  i = 0 || String.mem " \t[{,:" s.[i - 1]
+;;
+
+(* Position of the first character outside quotes satisfying [f], given the
+   flow nesting depth so far. *)
+let scan (s : string) ~(f : int -> depth:int -> bool) : int option =
+  let n = String.length s in
+  let rec go (i : int) (quote : char option) (depth : int) =
+    if i >= n
+    then None
+    else (
+      let ch = s.[i] in
+      match quote with
+      | Some '"' when Char.equal ch '\\' -> go (i + 2) quote depth
+      | Some q -> go (i + 1) (if Char.equal ch q then None else quote) depth
+      | None when f i ~depth -> Some i
+      | None ->
+        (match ch with
+         | ('"' | '\'') when quote_can_open s i -> go (i + 1) (Some ch) depth
+         | '[' | '{' -> go (i + 1) None (depth + 1)
+         | ']' | '}' -> go (i + 1) None (max 0 (depth - 1))
+         | _ -> go (i + 1) None depth))
+  in
+  go 0 None 0
+;;
+
+(* [s] up to a comment: a [#] at the start or after a blank, outside quotes. *)
+let strip_comment (s : string) : string =
+  match
+    scan s ~f:(fun i ~depth:_ ->
+      Char.equal s.[i] '#' && (i = 0 || Char.is_whitespace s.[i - 1]))
+  with
+  | Some i -> String.prefix s i
+  | None -> s
+;;
+
+(* Position of the [:] ending the key of a [key: value] line, if it is one. *)
+let key_end (s : string) : int option =
+  scan s ~f:(fun i ~depth ->
+    depth = 0
+    && Char.equal s.[i] ':'
+    && (i + 1 = String.length s || Char.is_whitespace s.[i + 1]))
+;;
+
+let is_item (s : string) : bool = String.equal s "-" || String.is_prefix s ~prefix:"- "
+
+let is_block_scalar_header (s : string) : bool =
+  (not (String.is_empty s))
+  && String.mem "|>" s.[0]
+  && String.for_all (String.drop_prefix s 1) ~f:(fun ch ->
+    Char.equal ch '-' || Char.equal ch '+' || Char.is_digit ch)
+;;
+
+(** {1 Flow values} *)
+
+type cursor =
+  { s : string
+  ; mutable i : int
+  }
+
+let peek (c : cursor) : char option =
+  if c.i < String.length c.s then Some c.s.[c.i] else None
+;;
+
+let skip_spaces (c : cursor) : unit =
+  while
+    match peek c with
+    | Some (' ' | '\t') -> true
+    | _ -> false
+  do
+    c.i <- c.i + 1
+  done
+;;
+
+let double_quoted (c : cursor) : string =
+  let buf = Buffer.create 16 in
+  c.i <- c.i + 1;
+  let rec loop () =
+    match peek c with
+    | None -> fail "unterminated string in %S" c.s
+    | Some '"' -> c.i <- c.i + 1
+    | Some '\\' when c.i + 1 < String.length c.s ->
+      let esc = c.s.[c.i + 1] in
+      c.i <- c.i + 2;
+      (match esc with
+       | 'n' -> Buffer.add_char buf '\n'
+       | 't' -> Buffer.add_char buf '\t'
+       | 'r' -> Buffer.add_char buf '\r'
+       | '0' -> Buffer.add_char buf '\000'
+       | 'u' when c.i + 4 <= String.length c.s ->
+         (match
+            Option.bind
+              (Int.of_string_opt ("0x" ^ String.sub c.s ~pos:c.i ~len:4))
+              ~f:Uchar.of_scalar
+          with
+          | Some u ->
+            let b = Stdlib.Buffer.create 4 in
+            Stdlib.Buffer.add_utf_8_uchar b u;
+            Buffer.add_string buf (Stdlib.Buffer.contents b);
+            c.i <- c.i + 4
+          | _ -> fail "bad escape in %S" c.s)
+       | other -> Buffer.add_char buf other);
+      loop ()
+    | Some ch ->
+      Buffer.add_char buf ch;
+      c.i <- c.i + 1;
+      loop ()
+  in
+  loop ();
+  Buffer.contents buf
+;;
+
+(* ['...'], where [''] stands for a quote. *)
+let single_quoted (c : cursor) : string =
+  let buf = Buffer.create 16 in
+  c.i <- c.i + 1;
+  let rec loop () =
+    match peek c with
+    | None -> fail "unterminated string in %S" c.s
+    | Some '\'' when c.i + 1 < String.length c.s && Char.equal c.s.[c.i + 1] '\'' ->
+      Buffer.add_char buf '\'';
+      c.i <- c.i + 2;
+      loop ()
+    | Some '\'' -> c.i <- c.i + 1
+    | Some ch ->
+      Buffer.add_char buf ch;
+      c.i <- c.i + 1;
+      loop ()
+  in
+  loop ();
+  Buffer.contents buf
+;;
+
+(* Text up to one of [stops] (or the end), without surrounding blanks. *)
+let plain (c : cursor) ~(stops : string) : string =
+  let start = c.i in
+  while
+    match peek c with
+    | Some ch -> not (String.mem stops ch)
+    | None -> false
+  do
+    c.i <- c.i + 1
+  done;
+  String.strip (String.sub c.s ~pos:start ~len:(c.i - start))
+;;
+
+(* A value inside brackets when [flow], otherwise the rest of the line. *)
+let rec flow_value ~(flow : bool) (c : cursor) : value =
+  skip_spaces c;
+  match peek c with
+  | Some '[' ->
+    c.i <- c.i + 1;
+    `A (flow_sequence c [])
+  | Some '{' ->
+    c.i <- c.i + 1;
+    `O (flow_mapping c [])
+  | Some '"' -> `String (double_quoted c)
+  | Some '\'' -> `String (single_quoted c)
+  | Some '&' -> fail "anchors are not supported: %S" c.s
+  | Some '*' -> fail "aliases are not supported: %S" c.s
+  | Some '!' -> fail "tags are not supported: %S" c.s
+  | _ -> resolve (plain c ~stops:(if flow then ",]}" else ""))
+
+and flow_sequence (c : cursor) (acc : value list) : value list =
+  skip_spaces c;
+  match peek c with
+  | None -> fail "unterminated '[' in %S" c.s
+  | Some ']' ->
+    c.i <- c.i + 1;
+    List.rev acc
+  | Some _ ->
+    let v = flow_value ~flow:true c in
+    skip_spaces c;
+    (match peek c with
+     | Some ',' ->
+       c.i <- c.i + 1;
+       flow_sequence c (v :: acc)
+     | Some ']' ->
+       c.i <- c.i + 1;
+       List.rev (v :: acc)
+     | _ -> fail "unterminated '[' in %S" c.s)
+
+and flow_mapping (c : cursor) (acc : (string * value) list) : (string * value) list =
+  skip_spaces c;
+  match peek c with
+  | None -> fail "unterminated '{' in %S" c.s
+  | Some '}' ->
+    c.i <- c.i + 1;
+    List.rev acc
+  | Some ch ->
+    let key =
+      match ch with
+      | '"' -> double_quoted c
+      | '\'' -> single_quoted c
+      | _ -> plain c ~stops:":,}"
+    in
+    skip_spaces c;
+    let v =
+      match peek c with
+      | Some ':' ->
+        c.i <- c.i + 1;
+        skip_spaces c;
+        (match peek c with
+         | Some (',' | '}') -> `Null
+         | _ -> flow_value ~flow:true c)
+      | _ -> `Null
+    in
+    skip_spaces c;
+    (match peek c with
+     | Some ',' ->
+       c.i <- c.i + 1;
+       flow_mapping c ((key, v) :: acc)
+     | Some '}' ->
+       c.i <- c.i + 1;
+       List.rev ((key, v) :: acc)
+     | _ -> fail "unterminated '{' in %S" c.s)
+;;
+
+(* The value written after a key or an item marker. *)
+let inline (s : string) : value =
+  let c = { s; i = 0 } in
+  let v = flow_value ~flow:false c in
+  skip_spaces c;
+  if c.i < String.length s
+  then fail "unexpected %S after a value" (String.drop_prefix s c.i);
+  v
+;;
+
+let key_of (s : string) : string =
+  match inline (String.strip s) with
+  | `String k -> k
+  | _ -> String.strip s
+;;
+
+(** {1 Block structure} *)
+
+type line =
+  { mutable indent : int
+  ; mutable text : string (** without indentation, comment and trailing blanks *)
+  ; raw : string
+  }
+
+type state =
+  { lines : line array
+  ; mutable pos : int
+  }
+
+let line_of_string (raw : string) : line =
+  let indent =
+    String.length raw - String.length (String.lstrip ~drop:(Char.equal ' ') raw)
+  in
+  { indent; text = String.rstrip (strip_comment (String.drop_prefix raw indent)); raw }
+;;
+
+(* The next line with content, if any. *)
+let rec current (st : state) : line option =
+  if st.pos >= Array.length st.lines
+  then None
+  else if String.is_empty st.lines.(st.pos).text
+  then (
+    st.pos <- st.pos + 1;
+    current st)
+  else Some st.lines.(st.pos)
+;;
+
+(* The lines after a [|] or [>] [header] of a key or item at [parent]: blank
+   ones and those indented past it. *)
+let block_scalar (st : state) ~(parent : int) (header : string) : value =
+  let rec take (acc : line list) : line list =
+    if st.pos >= Array.length st.lines
+    then List.rev acc
+    else (
+      let l = st.lines.(st.pos) in
+      if String.is_empty (String.strip l.raw) || l.indent > parent
+      then (
+        st.pos <- st.pos + 1;
+        take (l :: acc))
+      else List.rev acc)
+  in
+  let lines = take [] in
+  let indent =
+    List.find_map lines ~f:(fun l ->
+      Option.some_if (not (String.is_empty (String.strip l.raw))) l.indent)
+    |> Option.value ~default:(parent + 1)
+  in
+  let texts =
+    List.map lines ~f:(fun l ->
+      if String.is_empty (String.strip l.raw)
+      then ""
+      else String.drop_prefix l.raw indent)
+  in
+  let trailing = List.length (List.take_while (List.rev texts) ~f:String.is_empty) in
+  let texts = List.take texts (List.length texts - trailing) in
+  let body =
+    if Char.equal header.[0] '|'
+    then String.concat ~sep:"\n" texts
+    else (
+      (* lines fold into spaces, blank lines into line breaks *)
+      let buf = Buffer.create 64 in
+      let rec fold (after_break : bool) : string list -> unit = function
+        | [] -> ()
+        | "" :: rest ->
+          Buffer.add_char buf '\n';
+          fold true rest
+        | t :: rest ->
+          if not after_break then Buffer.add_char buf ' ';
+          Buffer.add_string buf t;
+          fold false rest
+      in
+      fold true texts;
+      Buffer.contents buf)
+  in
+  if String.mem header '-'
+  then `String body
+  else if String.mem header '+'
+  then `String (body ^ "\n" ^ String.make trailing '\n')
+  else if String.is_empty body
+  then `String ""
+  else `String (body ^ "\n")
+;;
+
+(* The node nested under a key or item at [parent], if any. *)
+let rec node (st : state) ~(parent : int) : value =
+  match current st with
+  | Some l when l.indent > parent -> block st l
+  | Some _ | None -> `Null
+
+and block (st : state) (l : line) : value =
+  if is_item l.text
+  then sequence st l.indent
+  else if Option.is_some (key_end l.text)
+  then mapping st l.indent
+  else (
+    st.pos <- st.pos + 1;
+    inline l.text)
+
+(* The value of a key or item at [parent], [rest] following it on its line. *)
+and value_after (st : state) ~(parent : int) (rest : string) : value =
+  st.pos <- st.pos + 1;
+  if String.is_empty rest
+  then node st ~parent
+  else if is_block_scalar_header rest
+  then block_scalar st ~parent rest
+  else inline rest
+
+and mapping (st : state) (indent : int) : value =
+  let rec loop (acc : (string * value) list) =
+    match current st with
+    | Some l when l.indent = indent && not (is_item l.text) ->
+      (match key_end l.text with
+       | None -> fail "expected 'key: value', got %S" l.text
+       | Some i ->
+         let key = key_of (String.prefix l.text i) in
+         let rest = String.strip (String.drop_prefix l.text (i + 1)) in
+         let v =
+           if String.is_empty rest
+           then (
+             st.pos <- st.pos + 1;
+             match current st with
+             (* a sequence may sit at its key's indentation *)
+             | Some next when next.indent = indent && is_item next.text ->
+               sequence st indent
+             | Some _ | None -> node st ~parent:indent)
+           else value_after st ~parent:indent rest
+         in
+         loop ((key, v) :: acc))
+    | Some l when l.indent > indent -> fail "bad indentation of %S" l.text
+    | Some _ | None -> `O (List.rev acc)
+  in
+  loop []
+
+and sequence (st : state) (indent : int) : value =
+  let rec loop (acc : value list) =
+    match current st with
+    | Some l when l.indent = indent && is_item l.text ->
+      let after_dash = String.drop_prefix l.text 1 in
+      let rest = String.lstrip after_dash in
+      let v =
+        if (is_item rest || Option.is_some (key_end rest))
+           && not (is_block_scalar_header rest)
+        then (
+          (* [- key: value] or [- - item]: a node opening on the item's line *)
+          l.indent <- indent + 1 + (String.length after_dash - String.length rest);
+          l.text <- rest;
+          block st l)
+        else value_after st ~parent:indent rest
+      in
+      loop (v :: acc)
+    | Some l when l.indent > indent -> fail "bad indentation of %S" l.text
+    | Some _ | None -> `A (List.rev acc)
+  in
+  loop []
+;;
+
+let of_string (s : string) : (value, [ `Msg of string ]) result =
+  let st =
+    { lines = Array.of_list (List.map (String.split_lines s) ~f:line_of_string); pos = 0 }
+  in
+  match
+    if Array.exists st.lines ~f:(fun l ->
+         l.indent = 0 && List.mem [ "---"; "..." ] l.text ~equal:String.equal)
+    then fail "multiple documents are not supported";
+    let v = Option.value_map (current st) ~default:`Null ~f:(block st) in
+    Option.iter (current st) ~f:(fun l -> fail "unexpected %S" l.text);
+    v
+  with
+  | v -> Ok v
+  | exception Parse_error msg -> Error (`Msg msg)
+;;
+
+let of_string_exn (s : string) : value =
+  match of_string s with
+  | Ok v -> v
+  | Error (`Msg msg) -> failwith msg
+;;
+
+(** {1 Writing} *)
+
+let needs_quotes (s : string) : bool =
+  String.is_empty s
+  || (match resolve s with
+      | `String _ -> false
+      | _ -> true)
+  || Char.is_whitespace s.[0]
+  || Char.is_whitespace s.[String.length s - 1]
+  || String.mem "-?:,[]{}#&*!|>'\"%@`" s.[0]
+  || String.is_substring s ~substring:": "
+  || String.is_substring s ~substring:" #"
+  || String.is_suffix s ~suffix:":"
+  || String.exists s ~f:(fun ch -> Char.( < ) ch ' ')
+;;
+
+let string_to_yaml (s : string) : string =
+  if not (needs_quotes s)
+  then s
+  else if String.exists s ~f:(fun ch -> Char.( < ) ch ' ')
+  then (
+    let buf = Buffer.create (String.length s + 2) in
+    Buffer.add_char buf '"';
+    String.iter s ~f:(function
+      | '"' -> Buffer.add_string buf "\\\""
+      | '\\' -> Buffer.add_string buf "\\\\"
+      | '\n' -> Buffer.add_string buf "\\n"
+      | '\t' -> Buffer.add_string buf "\\t"
+      | '\r' -> Buffer.add_string buf "\\r"
+      | ch when Char.( < ) ch ' ' ->
+        Buffer.add_string buf (sprintf "\\x%02x" (Char.to_int ch))
+      | ch -> Buffer.add_char buf ch);
+    Buffer.add_char buf '"';
+    Buffer.contents buf)
+  else "'" ^ String.substr_replace_all s ~pattern:"'" ~with_:"''" ^ "'"
+;;
+
+let float_to_string (f : float) : string =
+  if Float.is_nan f
+  then ".nan"
+  else if Float.is_inf f
+  then if Float.is_positive f then ".inf" else "-.inf"
+  else if Float.is_integer f && Float.( < ) (Float.abs f) 1e15
+  then Int.to_string (Float.to_int f)
+  else Float.to_string f
+;;
+
+let scalar (v : value) : string =
+  match v with
+  | `Null -> "null"
+  | `Bool b -> Bool.to_string b
+  | `Float f -> float_to_string f
+  | `String s -> string_to_yaml s
+  | `A _ -> "[]"
+  | `O _ -> "{}"
+;;
+
+(* Sequences stay at their key's indentation, as libyaml writes them. *)
+let rec render (indent : int) (v : value) : string =
+  let pad = String.make indent ' ' in
+  match v with
+  | `O (_ :: _ as pairs) ->
+    String.concat
+      (List.map pairs ~f:(fun (k, v) ->
+         let head = pad ^ string_to_yaml k ^ ":" in
+         match v with
+         | `O (_ :: _) -> head ^ "\n" ^ render (indent + 2) v
+         | `A (_ :: _) -> head ^ "\n" ^ render indent v
+         | v -> head ^ " " ^ scalar v ^ "\n"))
+  | `A (_ :: _ as items) ->
+    String.concat
+      (List.map items ~f:(fun v ->
+         match v with
+         | `O (_ :: _) | `A (_ :: _) ->
+           pad ^ "- " ^ String.drop_prefix (render (indent + 2) v) (indent + 2)
+         | v -> pad ^ "- " ^ scalar v ^ "\n"))
+  | v -> pad ^ scalar v ^ "\n"
+;;
+
+(** [v] as block-style YAML. *)
+let to_string_exn (v : value) : string = render 0 v
+
+let%expect_test "of_string and to_string_exn" =
+  let src =
+    {|title: "Hello \"world\""
+draft: no
+weight: 10
+tags: [a, 'b c', "d"]
+aliases:
+- One
+- 'it''s'
+author:
+  name: K # comment
+  links:
+    - url: u1
+      rel: me
+    - url: u2
+summary: |
+  Line one
+  # not a comment
+note: >-
+  folded
+  text
+empty:
+ratio: 1.5
+inline: {x: 1, y: z}
+|}
+  in
+  print_string (to_string_exn (of_string_exn src));
+  [%expect
+    {|
+    title: Hello "world"
+    draft: false
+    weight: 10
+    tags:
+    - a
+    - b c
+    - d
+    aliases:
+    - One
+    - it's
+    author:
+      name: K
+      links:
+      - url: u1
+        rel: me
+      - url: u2
+    summary: "Line one\n# not a comment\n"
+    note: folded text
+    empty: null
+    ratio: 1.5
+    inline:
+      x: 1
+      y: z
+    |}];
+  (match of_string "a: [1, 2" with
+   | Ok _ -> print_endline "ok"
+   | Error (`Msg msg) -> print_endline msg);
+  [%expect {| unterminated '[' in "[1, 2" |}]
+;;
+
+(* [v] on one line, strings quoted so they stand apart from resolved scalars. *)
+let rec show (v : value) : string =
+  match v with
+  | `String s -> sprintf "%S" s
+  | `A vs -> "[" ^ String.concat ~sep:", " (List.map vs ~f:show) ^ "]"
+  | `O pairs ->
+    let pair (k, v) = k ^ ": " ^ show v in
+    "{" ^ String.concat ~sep:", " (List.map pairs ~f:pair) ^ "}"
+  | (`Null | `Bool _ | `Float _) as v -> scalar v
+;;
+
+let print_keys (src : string) : unit =
+  match of_string src with
+  | Ok (`O pairs) -> List.iter pairs ~f:(fun (k, v) -> printf "%s: %s\n" k (show v))
+  | Ok v -> print_endline (show v)
+  | Error (`Msg msg) -> print_endline msg
+;;
+
+let%expect_test "block and flow collections" =
+  print_keys
+    {|list:
+  - a
+  - b
+flush:
+- c
+nested:
+  - - x
+    - y
+  - key: 1
+    other: 2
+flow: [1, [2, 3], {k: v}]
+map: {a: 1, 'b': "two", c:}
+deep:
+  inner:
+    leaf: z
+|};
+  [%expect
+    {|
+    list: ["a", "b"]
+    flush: ["c"]
+    nested: [["x", "y"], {key: 1, other: 2}]
+    flow: [1, [2, 3], {k: "v"}]
+    map: {a: 1, b: "two", c: null}
+    deep: {inner: {leaf: "z"}}
+    |}]
+;;
+
+let%expect_test "scalars" =
+  List.iter
+    [ "~"
+    ; "null"
+    ; "NULL"
+    ; ""
+    ; "true"
+    ; "yes"
+    ; "Off"
+    ; "n"
+    ; "10"
+    ; "-2"
+    ; "1.5"
+    ; "1e3"
+    ; "0x1F"
+    ; ".inf"
+    ; "-.inf"
+    ; ".nan"
+    ; "abc"
+    ; "2024-01-05"
+    ; "1.2.3"
+    ; "'10'"
+    ; {|"true"|}
+    ; "'it''s'"
+    ; {|"a\tb\"c"|}
+    ]
+    ~f:(fun s ->
+      match of_string ("k: " ^ s) with
+      | Ok (`O [ (_, v) ]) -> printf "%-12s %s\n" s (show v)
+      | Ok v -> printf "%-12s unexpected %s\n" s (show v)
+      | Error (`Msg msg) -> printf "%-12s %s\n" s msg);
+  [%expect
+    {|
+    ~            null
+    null         null
+    NULL         null
+                 null
+    true         true
+    yes          true
+    Off          false
+    n            false
+    10           10
+    -2           -2
+    1.5          1.5
+    1e3          1000
+    0x1F         31
+    .inf         .inf
+    -.inf        -.inf
+    .nan         .nan
+    abc          "abc"
+    2024-01-05   "2024-01-05"
+    1.2.3        "1.2.3"
+    '10'         "10"
+    "true"       "true"
+    'it''s'      "it's"
+    "a\tb\"c"    "a\tb\"c"
+    |}]
+;;
+
+let%expect_test "block scalars and comments" =
+  print_keys
+    {|# leading comment
+literal: |
+  one
+    indented
+
+  three
+folded: >
+  one
+  two
+
+  three
+strip: |-
+  text
+
+keep: |+
+  text
+
+after: done # trailing
+hash: 'a # b'
+inner: a#b
+|};
+  [%expect
+    {|
+    literal: "one\n  indented\n\nthree\n"
+    folded: "one two\nthree\n"
+    strip: "text"
+    keep: "text\n\n"
+    after: "done"
+    hash: "a # b"
+    inner: "a#b"
+    |}]
+;;
+
+let%expect_test "unsupported constructs" =
+  List.iter
+    [ "a: &x 1"
+    ; "a: *x"
+    ; "a: !!str 1"
+    ; "a: foo\n  bar"
+    ; "a: 1\n---\nb: 2"
+    ; "a: [1, 2"
+    ; "a: 'open"
+    ; "- a\nb: c"
+    ]
+    ~f:print_keys;
+  [%expect
+    {|
+    anchors are not supported: "&x 1"
+    aliases are not supported: "*x"
+    tags are not supported: "!!str 1"
+    bad indentation of "bar"
+    multiple documents are not supported
+    unterminated '[' in "[1, 2"
+    unterminated string in "'open"
+    unexpected "b: c"
+    |}]
+;;
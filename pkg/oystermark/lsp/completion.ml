@@ -90,8 +90,10 @@ let note_name_items (index : Oystermark.Vault.Index.t) : item list =
 
 (** The indexed file entry the fragment's note part refers to: the current file
     (parsed live for freshness) when [note_part] is empty, otherwise the note
-    resolved against the vault (first candidate), or [None] if unresolved. *)
+    resolved against the vault (as [config]'s link preference picks), or
+    [None] if unresolved. *)
 let target_entry
+      ?(config : Lsp_config.t = Lsp_config.default)
       ~(index : Oystermark.Vault.Index.t)
       ~(rel_path : string)
       ~(content : string)
@@ -112,7 +114,7 @@ let target_entry
     let link_ref =
       { Oystermark.Vault.Link_ref.target = Some note_part; fragment = None }
     in
-    match Oystermark.Vault.Resolve.resolve link_ref rel_path index with
+    match Lsp_config.resolve config link_ref rel_path index with
     | Note { path } | File { path } -> find path
     | Curr_file -> find rel_path
     | _ -> None)
@@ -158,6 +160,7 @@ let fragment_items (entry : Oystermark.Vault.Index.file_entry) : item list =
     [rel_path] within [index].  Empty when the cursor is not inside a wikilink
     or the fragment's note is unresolved.  See {!page-"feature-completion"}. *)
 let complete
+      ?(config : Lsp_config.t option)
       ~(index : Oystermark.Vault.Index.t)
       ~(rel_path : string)
       ~(content : string)
@@ -174,7 +177,7 @@ let complete
     (match String.lsplit2 prefix ~on:'#' with
      | None -> note_name_items index
      | Some (note_part, _fragment_prefix) ->
-       (match target_entry ~index ~rel_path ~content note_part with
+       (match target_entry ?config ~index ~rel_path ~content note_part with
         | None -> []
         | Some entry -> fragment_items entry))
 ;;
@@ -1,4 +1,5 @@
-(** Diagnostics: report unresolved links, embeds, and images as warnings.
+(** Diagnostics: report unresolved links, embeds, and images as warnings,
+    and prose issues.
 
     Spec: {!page-"feature-diagnostics"}. *)
 
@@ -43,11 +44,11 @@ let collect_anchor_occurrences (doc : Cmarkit.Doc.t) : (string * (int * int)) li
   headings @ blocks @ attrs
 ;;
 
-(** Diagnostics for anchor ids that occur more than once in [doc]: every
-    located occurrence of a duplicated id is reported.
-    See {!page-"feature-diagnostics".duplicate_ids}. *)
-let duplicate_id_diagnostics (doc : Cmarkit.Doc.t) : diagnostic list =
-  collect_anchor_occurrences doc
+(* Diagnostics for the ids that occur more than once in [occurrences]. *)
+let duplicates_of_occurrences (occurrences : (string * (int * int)) list)
+  : diagnostic list
+  =
+  occurrences
   |> String.Map.of_alist_multi
   |> Map.fold ~init:[] ~f:(fun ~key:id ~data:ranges acc ->
     if List.length ranges > 1
@@ -57,6 +58,91 @@ let duplicate_id_diagnostics (doc : Cmarkit.Doc.t) : diagnostic list =
     else acc)
 ;;
 
+(** Diagnostics for anchor ids that occur more than once in [doc]: every
+    located occurrence of a duplicated id is reported.
+    See {!page-"feature-diagnostics".duplicate_ids}. *)
+let duplicate_id_diagnostics (doc : Cmarkit.Doc.t) : diagnostic list =
+  duplicates_of_occurrences (collect_anchor_occurrences doc)
+;;
+
+(* Diagnostics for the unresolved and ambiguous links of [doc].
+   See {!page-"feature-diagnostics".resolution_check}. *)
+let link_diagnostics
+      ~(config : Lsp_config.t)
+      ~(index : Oystermark.Vault.Index.t)
+      ~(rel_path : string)
+      (doc : Cmarkit.Doc.t)
+  : diagnostic list
+  =
+  Link_collect.collect_links doc
+  |> List.filter_map ~f:(fun (ll : Link_collect.located_link) ->
+    let target = Lsp_config.resolve config ll.link_ref rel_path index in
+    (* A link is unresolved when: the target file doesn't exist, OR the file
+       exists but the heading/block fragment wasn't found (resolve falls back
+       to Note/File/Curr_file instead of Heading/Block/Curr_heading/Curr_block).
+       See {!page-"feature-diagnostics".resolution_check}. *)
+    let is_unresolved =
+      match target, ll.link_ref.fragment with
+      | Oystermark.Vault.Resolve.Unresolved, _ -> true
+      | (Note _ | File _), Some _ ->
+        Lsp_config.equal_fragment_behavior config.diag_unresolved_fragment Strict
+      | Curr_file, Some _ ->
+        Lsp_config.equal_fragment_behavior config.diag_unresolved_fragment Strict
+      | _ -> false
+    in
+    if is_unresolved
+    then (
+      let target_str =
+        match ll.link_ref.target with
+        | Some t -> t
+        | None -> ""
+      in
+      let fragment_str =
+        match ll.link_ref.fragment with
+        | Some (Oystermark.Vault.Link_ref.Heading h) -> "#" ^ String.concat ~sep:"#" h
+        | Some (Block_ref b) -> "#^" ^ b
+        | None -> ""
+      in
+      let category =
+        match ll.kind with
+        | Link_collect.Link -> "link"
+        | Embed -> "embed"
+        | Image -> "image"
+      in
+      Some
+        { first_byte = ll.first_byte
+        ; last_byte = ll.last_byte
+        ; message = "unresolved " ^ category ^ ": " ^ target_str ^ fragment_str
+        })
+    else (
+      (* Resolved, but other files match the target just as well.
+         See {!page-"feature-diagnostics".ambiguous_links}. *)
+      match
+        Oystermark.Vault.Resolve.ambiguous_candidates
+          ~curr_file:rel_path
+          ll.link_ref
+          index
+      with
+      | [] -> None
+      | candidates ->
+        Some
+          { first_byte = ll.first_byte
+          ; last_byte = ll.last_byte
+          ; message =
+              "ambiguous link: "
+              ^ Option.value ll.link_ref.target ~default:""
+              ^ " matches "
+              ^ String.concat ~sep:", " candidates
+          }))
+;;
+
+let sort_diagnostics (diagnostics : diagnostic list) : diagnostic list =
+  List.sort diagnostics ~compare:(fun a b ->
+    match Int.compare a.first_byte b.first_byte with
+    | 0 -> Int.compare a.last_byte b.last_byte
+    | c -> c)
+;;
+
 (** Compute diagnostics for unresolved links and duplicate anchor ids in
     [content] at [rel_path] within a vault [index].
 
@@ -73,61 +159,117 @@ let compute
   Trace_core.with_span ~__FILE__ ~__LINE__ "diagnostics.compute"
   @@ fun _sp ->
   Trace_core.add_data_to_span _sp [ "rel_path", `String rel_path ];
-  let doc = Lsp_util.parse_doc content in
-  let links = Link_collect.collect_links doc in
-  let diagnostics =
-    List.filter_map links ~f:(fun (ll : Link_collect.located_link) ->
-      let target = Oystermark.Vault.Resolve.resolve ll.link_ref rel_path index in
-      (* A link is unresolved when: the target file doesn't exist, OR the file
-         exists but the heading/block fragment wasn't found (resolve falls back
-         to Note/File/Curr_file instead of Heading/Block/Curr_heading/Curr_block).
-         See {!page-"feature-diagnostics".resolution_check}. *)
-      let is_unresolved =
-        match target, ll.link_ref.fragment with
-        | Oystermark.Vault.Resolve.Unresolved, _ -> true
-        | (Note _ | File _), Some _ ->
-          Lsp_config.equal_fragment_behavior config.diag_unresolved_fragment Strict
-        | Curr_file, Some _ ->
-          Lsp_config.equal_fragment_behavior config.diag_unresolved_fragment Strict
-        | _ -> false
-      in
-      if is_unresolved
-      then (
-        let target_str =
-          match ll.link_ref.target with
-          | Some t -> t
-          | None -> ""
-        in
-        let fragment_str =
-          match ll.link_ref.fragment with
-          | Some (Oystermark.Vault.Link_ref.Heading h) -> "#" ^ String.concat ~sep:"#" h
-          | Some (Block_ref b) -> "#^" ^ b
-          | None -> ""
-        in
-        let category =
-          match ll.kind with
-          | Link_collect.Link -> "link"
-          | Embed -> "embed"
-          | Image -> "image"
-        in
-        Some
-          { first_byte = ll.first_byte
-          ; last_byte = ll.last_byte
-          ; message = "unresolved " ^ category ^ ": " ^ target_str ^ fragment_str
-          })
-      else None)
-  in
-  let all = diagnostics @ duplicate_id_diagnostics doc in
+  let doc = Lsp_util.parse_doc ~config content in
   let sorted =
-    List.sort all ~compare:(fun a b ->
-      match Int.compare a.first_byte b.first_byte with
-      | 0 -> Int.compare a.last_byte b.last_byte
-      | c -> c)
+    sort_diagnostics
+      (link_diagnostics ~config ~index ~rel_path doc @ duplicate_id_diagnostics doc)
   in
   Trace_core.add_data_to_span _sp [ "num_diagnostics", `Int (List.length sorted) ];
   sorted
 ;;
 
+(** Diagnostics kept per top-level block of an open document, so that after a
+    small edit only the edited block is parsed and its links resolved again;
+    {!Oystermark.Parse.Incremental} says when that applies. Duplicate ids span
+    blocks, so each block keeps its anchors and they are compared across the
+    document. See {!page-"feature-diagnostics".incremental}. *)
+module Incremental = struct
+  module I = Oystermark.Parse.Incremental
+
+  type block =
+    { links : diagnostic list
+    ; anchors : (string * (int * int)) list
+    }
+
+  type t = block I.t
+
+  let extract ~config ~index ~rel_path (doc : Cmarkit.Doc.t) (b : Cmarkit.Block.t)
+    : block
+    =
+    let doc = Cmarkit.Doc.make ~defs:(Cmarkit.Doc.defs doc) b in
+    { links = link_diagnostics ~config ~index ~rel_path doc
+    ; anchors = collect_anchor_occurrences doc
+    }
+  ;;
+
+  let shift (delta : int) (b : block) : block =
+    { links =
+        List.map b.links ~f:(fun d ->
+          { d with first_byte = d.first_byte + delta; last_byte = d.last_byte + delta })
+    ; anchors =
+        List.map b.anchors ~f:(fun (id, (first, last)) ->
+          id, (first + delta, last + delta))
+    }
+  ;;
+
+  (** The state of [content], parsed in full. *)
+  let build
+        ?(config : Lsp_config.t = Lsp_config.default)
+        ~(index : Oystermark.Vault.Index.t)
+        ~(rel_path : string)
+        (content : string)
+    : t
+    =
+    I.build
+      ~parse:(Lsp_util.parse_doc ~config)
+      ~extract:(extract ~config ~index ~rel_path)
+      content
+  ;;
+
+  (** [t] brought up to [content], the document after an edit. *)
+  let update
+        ?(config : Lsp_config.t = Lsp_config.default)
+        ~(index : Oystermark.Vault.Index.t)
+        ~(rel_path : string)
+        (t : t)
+        (content : string)
+    : t
+    =
+    Trace_core.with_span ~__FILE__ ~__LINE__ "diagnostics.update"
+    @@ fun _sp ->
+    if String.equal (I.source t) content
+    then t
+    else (
+      let t, how =
+        I.update
+          t
+          ~parse:(Lsp_util.parse_doc ~config)
+          ~extract:(extract ~config ~index ~rel_path)
+          ~shift
+          (I.edit_of_diff (I.source t) content)
+      in
+      let incremental : bool =
+        match how with
+        | I.Incremental _ -> true
+        | I.Full -> false
+      in
+      Trace_core.add_data_to_span _sp [ "incremental", `Bool incremental ];
+      t)
+  ;;
+
+  (** The diagnostics {!compute} gives for [t]'s source. *)
+  let diagnostics (t : t) : diagnostic list =
+    let blocks = I.data t in
+    sort_diagnostics
+      (List.concat_map blocks ~f:(fun b -> b.links)
+       @ duplicates_of_occurrences (List.concat_map blocks ~f:(fun b -> b.anchors)))
+  ;;
+end
+
+(** Prose diagnostics for [content]: the spelling and style issues
+    [oyster lint --prose] reports, against [dictionary].
+    See {!page-"feature-diagnostics".prose}. *)
+let prose
+      ~(dictionary : Oystermark.Vault.Prose_lint.Dictionary.t)
+      ~(content : string)
+  : diagnostic list
+  =
+  Lsp_util.parse_doc content
+  |> Oystermark.Vault.Prose_lint.check ~dictionary
+  |> List.map ~f:(fun (i : Oystermark.Vault.Prose_lint.issue) ->
+    { first_byte = i.first_byte; last_byte = i.last_byte; message = i.message })
+;;
+
 (** {1:test Test} *)
 
 let%test_module "compute" =
@@ -183,6 +325,19 @@ let%test_module "compute" =
       [%expect {| |}]
     ;;
 
+    let%expect_test "ambiguous link" =
+      let index =
+        make_index [ "a/Ideas.md", "# A\n"; "b/Ideas.md", "# B\n"; "note.md", "" ]
+      in
+      compute ~index ~rel_path:"note.md" ~content:"See [[Ideas]] or [[a/Ideas]]." ()
+      |> List.iter ~f:(fun d -> print_s [%sexp (d : diagnostic)]);
+      [%expect
+        {|
+        ((first_byte 4) (last_byte 12)
+         (message "ambiguous link: Ideas matches a/Ideas.md, b/Ideas.md"))
+        |}]
+    ;;
+
     let%expect_test "empty document" =
       show ~rel_path:"note-b.md" ~content:"";
       [%expect {| |}]
@@ -280,5 +435,52 @@ let%test_module "compute" =
       show ~rel_path:"note-a.md" ~content:"# H\n\nOne [a]{#x} two [b]{#y}.\n";
       [%expect {| |}]
     ;;
+
+    (* See {!page-"feature-diagnostics".incremental}. *)
+    let%expect_test "incremental: same as compute after each edit" =
+      let rel_path = "note-a.md" in
+      let contents =
+        [ "# H\n\nSee [[missing]] here.\n\nOne [a]{#dup}.\n"
+        ; "# H\n\nSee [[missin]] here.\n\nOne [a]{#dup}.\n"
+        ; "# H\n\nSee [[note-b]] here.\n\nOne [a]{#dup}.\n"
+        ; "# H\n\nSee [[note-b]] here.\n\nOne [a]{#dup} [b]{#dup}.\n"
+        ; "# H\n\nSee [[note-b]] here.\n\nOne [a]{#dup} [b]{#dup}.\n\nNew [[x]].\n"
+        ]
+      in
+      let init = Incremental.build ~index ~rel_path (List.hd_exn contents) in
+      let (_ : Incremental.t) =
+        List.fold contents ~init ~f:(fun t content ->
+          let t = Incremental.update ~index ~rel_path t content in
+          let diags = Incremental.diagnostics t in
+          printf
+            "same: %b, %d diagnostics\n"
+            ([%equal: diagnostic list] diags (compute ~index ~rel_path ~content ()))
+            (List.length diags);
+          t)
+      in
+      [%expect
+        {|
+        same: true, 1 diagnostics
+        same: true, 1 diagnostics
+        same: true, 0 diagnostics
+        same: true, 2 diagnostics
+        same: true, 3 diagnostics
+        |}]
+    ;;
   end)
 ;;
+
+(* Prose lint. See {!page-"feature-diagnostics".prose}. *)
+let%expect_test "prose" =
+  let dictionary =
+    Oystermark.Vault.Prose_lint.Dictionary.of_lists [ "see\nthe\nnote\nor\n" ]
+  in
+  prose ~dictionary ~content:"See the the nte, not [nte](x) or `nte`.\n"
+  |> List.iter ~f:(fun d -> print_s [%sexp (d : diagnostic)]);
+  [%expect
+    {|
+    ((first_byte 4) (last_byte 10) (message "repeated word: \"the\""))
+    ((first_byte 12) (last_byte 14) (message "unknown word: \"nte\""))
+    ((first_byte 17) (last_byte 19) (message "unknown word: \"not\""))
+    |}]
+;;
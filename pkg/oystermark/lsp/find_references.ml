@@ -73,32 +73,45 @@ let attr_id_of_line (line_str : string) : string option =
   | _ -> None
 ;;
 
+let heading_slug_at (doc : Cmarkit.Doc.t) (offset : int) : string option =
+  Oystermark.Parse.Node_at.node_at doc offset
+  |> Option.bind ~f:(fun ({ node; ancestors } : Oystermark.Parse.Node_at.t) ->
+    List.find_map (node :: ancestors) ~f:(function
+      | Oystermark.Parse.Node_at.Block (Cmarkit.Block.Heading (_, meta)) ->
+        Cmarkit.Meta.find Oystermark.Parse.Heading_slug.meta_key meta
+      | _ -> None))
+;;
+
 (** Determine the reference target from cursor position.
 
     Tries in order:
     1. Cursor on a link — resolve it to get the target.
     2. Cursor on a heading — target is (current file, heading slug).
-    3. Cursor on a block ID line — target is (current file, block id). *)
+    3. Cursor on a block ID line — target is (current file, block id).
+
+    Heading slugs follow [config]'s slug style. *)
 let detect_target
+      ?(config : Lsp_config.t = Lsp_config.default)
       ~(index : Oystermark.Vault.Index.t)
       ~(rel_path : string)
       ~(content : string)
       ~(line : int)
       ~(character : int)
+      ()
   : target option
   =
   let offset = Lsp_util.byte_offset_of_position content ~line ~character in
-  let doc = Lsp_util.parse_doc content in
+  let doc = Lsp_util.parse_doc ~config content in
   let links = Link_collect.collect_links doc in
   match Link_collect.find_at_offset links offset with
   | Some link_ref ->
-    let resolved = Oystermark.Vault.Resolve.resolve link_ref rel_path index in
+    let resolved = Lsp_config.resolve config link_ref rel_path index in
     (match resolved with
      | Oystermark.Vault.Resolve.Note { path } | File { path } ->
        (match link_ref.fragment with
         | Some (Oystermark.Vault.Link_ref.Heading hs) ->
           let slug =
-            String.concat ~sep:"-" (List.map hs ~f:Oystermark.Parse.Heading_slug.slugify)
+            String.concat ~sep:"-" (List.map hs ~f:(Lsp_config.slugify config))
           in
           Some (Path_heading { path; slug })
         | Some (Block_ref bid) -> Some (Path_block { path; block_id = bid })
@@ -110,7 +123,7 @@ let detect_target
        (match link_ref.fragment with
         | Some (Oystermark.Vault.Link_ref.Heading hs) ->
           let slug =
-            String.concat ~sep:"-" (List.map hs ~f:Oystermark.Parse.Heading_slug.slugify)
+            String.concat ~sep:"-" (List.map hs ~f:(Lsp_config.slugify config))
           in
           Some (Path_heading { path = rel_path; slug })
         | Some (Block_ref bid) -> Some (Path_block { path = rel_path; block_id = bid })
@@ -127,12 +140,10 @@ let detect_target
      | Some line_str ->
        (match Hover.heading_level_of_line line_str with
         | Some _ ->
-          let text =
-            String.lstrip line_str ~drop:(fun c -> Char.equal c '#')
-            |> String.lstrip ~drop:(fun c -> Char.equal c ' ')
-          in
-          let slug = Oystermark.Parse.Heading_slug.slugify text in
-          Some (Path_heading { path = rel_path; slug })
+          (* The slug stamped at parse time, so duplicates get their suffix. *)
+          let line_start = Lsp_util.byte_offset_of_position content ~line ~character:0 in
+          Option.map (heading_slug_at doc line_start) ~f:(fun slug ->
+            Path_heading { path = rel_path; slug })
         | None ->
           (match block_id_of_line line_str with
            | Some block_id -> Some (Path_block { path = rel_path; block_id })
@@ -217,8 +228,11 @@ let resolved_matches
 ;;
 
 (** Collect references from a single pre-resolved document by folding over
-    its AST and reading {!Oystermark.Vault.Resolve.resolved_key} metadata. *)
+    its AST and reading {!Oystermark.Vault.Resolve.resolved_key} metadata.
+    With [link_definitions], the [[label]: dest] definitions that reference
+    links ([[text][label]]) share are included besides the links themselves. *)
 let collect_from_doc
+      ?(link_definitions = true)
       ~(source_rel_path : string)
       (ref_target : target)
       (doc : Cmarkit.Doc.t)
@@ -243,6 +257,11 @@ let collect_from_doc
   in
   let folder =
     Cmarkit.Folder.make
+      ~block:(fun _f acc b ->
+        match b with
+        | Cmarkit.Block.Link_reference_definition (_, meta) when link_definitions ->
+          Cmarkit.Folder.ret (check_meta acc meta)
+        | _ -> Cmarkit.Folder.default)
       ~inline:(fun _f acc i ->
         match i with
         | Cmarkit.Inline.Link (_, meta) | Cmarkit.Inline.Image (_, meta) ->
@@ -262,14 +281,17 @@ let collect_from_doc
 
     Each document's AST already has {!Oystermark.Vault.Resolve.resolved_key}
     metadata on every link node, so no re-parsing or re-resolving is needed. *)
-let scan_vault ~(docs : (string * Cmarkit.Doc.t) list) (ref_target : target)
+let scan_vault
+      ?(link_definitions : bool option)
+      ~(docs : (string * Cmarkit.Doc.t) list)
+      (ref_target : target)
   : reference list
   =
   Trace_core.with_span ~__FILE__ ~__LINE__ "find_references.scan_vault"
   @@ fun _sp ->
   let refs =
     List.concat_map docs ~f:(fun (source_rel_path, doc) ->
-      collect_from_doc ~source_rel_path ref_target doc)
+      collect_from_doc ?link_definitions ~source_rel_path ref_target doc)
   in
   let sorted =
     List.sort refs ~compare:(fun a b ->
@@ -293,6 +315,7 @@ let scan_vault ~(docs : (string * Cmarkit.Doc.t) list) (ref_target : target)
     Returns a sorted list of {!reference} values, or an empty list if the
     cursor is not on a link, heading, or block ID. *)
 let find_references
+      ?(config : Lsp_config.t option)
       ~(index : Oystermark.Vault.Index.t)
       ~(docs : (string * Cmarkit.Doc.t) list)
       ~(rel_path : string)
@@ -307,7 +330,7 @@ let find_references
   Trace_core.add_data_to_span
     _sp
     [ "rel_path", `String rel_path; "line", `Int line; "character", `Int character ];
-  match detect_target ~index ~rel_path ~content ~line ~character with
+  match detect_target ?config ~index ~rel_path ~content ~line ~character () with
   | None ->
     Trace_core.add_data_to_span _sp [ "result", `String "no_target" ];
     []
@@ -320,7 +343,7 @@ let find_references
 
 (** Count how many links across the vault resolve to [path] (any fragment). *)
 let count_file_refs ~(docs : (string * Cmarkit.Doc.t) list) ~(path : string) : int =
-  List.length (scan_vault ~docs (Path_only { path }))
+  List.length (scan_vault ~link_definitions:false ~docs (Path_only { path }))
 ;;
 
 (** Count how many links across the vault resolve to [path] with heading [slug]. *)
@@ -330,7 +353,7 @@ let count_heading_refs
       ~(slug : string)
   : int
   =
-  List.length (scan_vault ~docs (Path_heading { path; slug }))
+  List.length (scan_vault ~link_definitions:false ~docs (Path_heading { path; slug }))
 ;;
 
 (** {1:test Test} *)
@@ -388,7 +411,7 @@ let%test_module "detect_target" =
     let index, _docs = For_test.make_vault files
 
     let show ~rel_path ~content ~line ~character =
-      match detect_target ~index ~rel_path ~content ~line ~character with
+      match detect_target ~index ~rel_path ~content ~line ~character () with
       | None -> print_endline "<none>"
       | Some (Path_only { path }) -> printf "Path_only %s\n" path
       | Some (Path_heading { path; slug }) -> printf "Path_heading %s#%s\n" path slug
@@ -57,14 +57,20 @@ let go_to_definition
     _sp
     [ "rel_path", `String rel_path; "line", `Int line; "character", `Int character ];
   let offset = Lsp_util.byte_offset_of_position content ~line ~character in
-  let doc = Lsp_util.parse_doc content in
-  let links = Link_collect.collect_links doc in
-  match Link_collect.find_at_offset links offset with
+  let doc = Lsp_util.parse_doc ~config content in
+  match Link_collect.link_at doc offset with
   | None ->
-    Trace_core.add_data_to_span _sp [ "result", `String "no_link_at_cursor" ];
-    None
-  | Some link_ref ->
-    let target = Oystermark.Vault.Resolve.resolve link_ref rel_path index in
+    (* A footnote reference jumps to its definition in the same note. *)
+    (match Link_collect.footnote_at doc offset with
+     | Some (_label, loc) ->
+       Trace_core.add_data_to_span _sp [ "resolution", `String "footnote" ];
+       let line, character = position_of_textloc ~content (Some loc) in
+       Some { path = rel_path; line; character }
+     | None ->
+       Trace_core.add_data_to_span _sp [ "result", `String "no_link_at_cursor" ];
+       None)
+  | Some { link_ref; _ } ->
+    let target = Lsp_config.resolve config link_ref rel_path index in
     let resolution_tag =
       match target with
       | Oystermark.Vault.Resolve.Note _ -> "note"
@@ -233,6 +239,18 @@ let%test_module "go_to_definition" =
       [%expect {| (((path note-a.md) (line 0) (character 0))) |}]
     ;;
 
+    let%expect_test "reference link to note" =
+      let content = "See [alpha][a].\n\n[a]: note-a#Section%20One\n" in
+      show ~rel_path:"note-b.md" ~content ~line:0 ~character:6;
+      [%expect {| (((path note-a.md) (line 2) (character 0))) |}]
+    ;;
+
+    let%expect_test "footnote reference to its definition" =
+      let content = "A claim.[^src]\n\nMore.\n\n[^src]: Somewhere.\n" in
+      show ~rel_path:"note-b.md" ~content ~line:0 ~character:10;
+      [%expect {| (((path note-b.md) (line 4) (character 0))) |}]
+    ;;
+
     let%expect_test "cursor not on link" =
       let content = List.Assoc.find_exn files ~equal:String.equal "note-b.md" in
       show ~rel_path:"note-b.md" ~content ~line:0 ~character:0;
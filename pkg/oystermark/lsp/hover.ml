@@ -129,23 +129,28 @@ let heading_level_of_line (line : string) : int option =
   else None
 ;;
 
-(** Find the 0-based line number and ATX level of the heading whose slug
+(** The headings of [content] as [(line, level, slug)], [line] 0-based.  The
+    slugs are those stamped when parsing under [config]'s slug style, repeated
+    headings suffixed, so they match the index's and the exported site's. *)
+let headings_of_content ?(config : Lsp_config.t = Lsp_config.default) (content : string)
+  : (int * int * string) list
+  =
+  Oystermark.Vault.Index.extract_headings (Lsp_util.parse_doc ~config content)
+  |> List.filter_map ~f:(fun (h : Oystermark.Vault.Index.heading_entry) ->
+    Option.map h.loc ~f:(fun loc ->
+      fst (Cmarkit.Textloc.first_line loc) - 1, h.level, h.slug))
+;;
+
+(** Find the 0-based line number and level of the heading whose slug
     matches [slug] in [content].  Returns [None] if not found. *)
-let find_heading_in_content ~(slug : string) (content : string) : (int * int) option =
-  let lines = String.split_lines content in
-  List.findi lines ~f:(fun _i line ->
-    match heading_level_of_line line with
-    | None -> false
-    | Some _ ->
-      (* Strip leading '#'s and space, then slugify. *)
-      let text =
-        String.lstrip line ~drop:(fun c -> Char.equal c '#')
-        |> String.lstrip ~drop:(fun c -> Char.equal c ' ')
-      in
-      String.equal (Oystermark.Parse.Heading_slug.slugify text) slug)
-  |> Option.map ~f:(fun (i, line) ->
-    let level = heading_level_of_line line |> Option.value_exn in
-    i, level)
+let find_heading_in_content
+      ?(config : Lsp_config.t option)
+      ~(slug : string)
+      (content : string)
+  : (int * int) option
+  =
+  List.find_map (headings_of_content ?config content) ~f:(fun (line, level, s) ->
+    Option.some_if (String.equal s slug) (line, level))
 ;;
 
 (** {2 Formatting} *)
@@ -184,15 +189,11 @@ let hover
     _sp
     [ "rel_path", `String rel_path; "line", `Int line; "character", `Int character ];
   let offset = Lsp_util.byte_offset_of_position content ~line ~character in
-  let doc = Lsp_util.parse_doc content in
-  let links = Link_collect.collect_links doc in
-  match Link_collect.find_at_offset links offset with
+  let doc = Lsp_util.parse_doc ~config content in
+  match Link_collect.link_at doc offset with
   | None -> None
-  | Some link_ref ->
-    let ll =
-      List.find_exn links ~f:(fun ll -> ll.first_byte <= offset && offset <= ll.last_byte)
-    in
-    let target = Oystermark.Vault.Resolve.resolve link_ref rel_path index in
+  | Some ({ link_ref; _ } as ll) ->
+    let target = Lsp_config.resolve config link_ref rel_path index in
     (* Determine which file to read and which portion to extract. *)
     let result_opt =
       match target with
@@ -206,11 +207,9 @@ let hover
              | Some (Oystermark.Vault.Link_ref.Heading hs) ->
                (* Fragment present but resolve fell back — try to find section. *)
                let slug =
-                 String.concat
-                   ~sep:"-"
-                   (List.map hs ~f:Oystermark.Parse.Heading_slug.slugify)
+                 String.concat ~sep:"-" (List.map hs ~f:(Lsp_config.slugify config))
                in
-               (match find_heading_in_content ~slug file_content with
+               (match find_heading_in_content ~config ~slug file_content with
                 | Some (hline, hlevel) ->
                   extract_section ~heading_line:hline ~heading_level:hlevel file_content
                 | None -> file_content)
@@ -226,7 +225,7 @@ let hover
          | None -> None
          | Some file_content ->
            let body =
-             match find_heading_in_content ~slug file_content with
+             match find_heading_in_content ~config ~slug file_content with
              | Some (hline, hlevel) ->
                extract_section ~heading_line:hline ~heading_level:hlevel file_content
              | None -> file_content
@@ -255,11 +254,9 @@ let hover
           match link_ref.fragment with
           | Some (Oystermark.Vault.Link_ref.Heading hs) ->
             let slug =
-              String.concat
-                ~sep:"-"
-                (List.map hs ~f:Oystermark.Parse.Heading_slug.slugify)
+              String.concat ~sep:"-" (List.map hs ~f:(Lsp_config.slugify config))
             in
-            (match find_heading_in_content ~slug content with
+            (match find_heading_in_content ~config ~slug content with
              | Some (hline, hlevel) ->
                extract_section ~heading_line:hline ~heading_level:hlevel content
              | None -> content)
@@ -272,7 +269,7 @@ let hover
         Some (format_hover ~path:rel_path body)
       | Curr_heading { slug; _ } ->
         let body =
-          match find_heading_in_content ~slug content with
+          match find_heading_in_content ~config ~slug content with
           | Some (hline, hlevel) ->
             extract_section ~heading_line:hline ~heading_level:hlevel content
           | None -> content
@@ -22,28 +22,21 @@ let format_count (n : int) : string option =
 ;;
 
 (** Find all headings in [content] within the line range [\[range_start_line,
-    range_end_line)].  Returns [(line, end_character, slug)] triples. *)
+    range_end_line)].  Returns [(line, end_character, slug)] triples, the slugs
+    as {!Hover.headings_of_content} gives them under [config]. *)
 let headings_in_range
+      ?(config : Lsp_config.t option)
       ~(content : string)
       ~(range_start_line : int)
       ~(range_end_line : int)
+      ()
   : (int * int * string) list
   =
-  let lines = String.split_lines content in
-  List.filter_mapi lines ~f:(fun i line_str ->
-    if i < range_start_line || i >= range_end_line
+  let lines = Array.of_list (String.split_lines content) in
+  List.filter_map (Hover.headings_of_content ?config content) ~f:(fun (i, _, slug) ->
+    if i < range_start_line || i >= range_end_line || i >= Array.length lines
     then None
-    else (
-      match Hover.heading_level_of_line line_str with
-      | None -> None
-      | Some _ ->
-        let text =
-          String.lstrip line_str ~drop:(fun c -> Char.equal c '#')
-          |> String.lstrip ~drop:(fun c -> Char.equal c ' ')
-        in
-        let slug = Oystermark.Parse.Heading_slug.slugify text in
-        let end_char = String.length line_str in
-        Some (i, end_char, slug)))
+    else Some (i, String.length lines.(i), slug))
 ;;
 
 (** Compute inlay hints for [rel_path] within the given line range.
@@ -53,6 +46,7 @@ let headings_in_range
 
     See {!page-"feature-inlay-hints"}. *)
 let inlay_hints
+      ?(config : Lsp_config.t option)
       ~(docs : (string * Cmarkit.Doc.t) list)
       ~(rel_path : string)
       ~(content : string)
@@ -78,7 +72,9 @@ let inlay_hints
     | None -> ()
     | Some label -> hints := { line = 0; character = 0; label } :: !hints);
   (* Per-heading hints. *)
-  let headings = headings_in_range ~content ~range_start_line ~range_end_line in
+  let headings =
+    headings_in_range ?config ~content ~range_start_line ~range_end_line ()
+  in
   List.iter headings ~f:(fun (line, end_char, slug) ->
     let count = Find_references.count_heading_refs ~docs ~path:rel_path ~slug in
     match format_count count with
@@ -29,6 +29,53 @@ type located_link =
   ; last_byte : int
   }
 
+(** The link an inline node stands for, with its byte range: a wikilink or a
+    markdown link/image to a vault target. Reference links ([[text][label]])
+    take their destination from [defs], the document's {!Cmarkit.Doc.defs}.
+    [None] for any other inline, and for nodes without a location. *)
+let located_of_inline ~(defs : Cmarkit.Label.defs) (i : Cmarkit.Inline.t)
+  : located_link option
+  =
+  let located link_ref kind meta =
+    let loc = Cmarkit.Meta.textloc meta in
+    if Cmarkit.Textloc.is_none loc
+    then None
+    else
+      Some
+        { link_ref
+        ; kind
+        ; first_byte = Cmarkit.Textloc.first_byte loc
+        ; last_byte = Cmarkit.Textloc.last_byte loc
+        }
+  in
+  match i with
+  | Cmarkit.Inline.Ext_wikilink (wl, meta) ->
+    let link_ref = Oystermark.Vault.Link_ref.of_wikilink wl in
+    let kind =
+      if Cmarkit.Inline.Wikilink.embed wl
+      then (
+        match link_ref.target with
+        | Some target when is_image_target target -> Image
+        | _ -> Embed)
+      else Link
+    in
+    located link_ref kind meta
+  | (Cmarkit.Inline.Link (link, meta) | Cmarkit.Inline.Image (link, meta)) as inline ->
+    let ref_ = Cmarkit.Inline.Link.reference link in
+    Oystermark.Vault.Link_ref.of_cmark_reference ~defs ref_
+    |> Option.bind ~f:(fun link_ref ->
+      let kind =
+        match inline with
+        | Cmarkit.Inline.Image _ ->
+          (match link_ref.target with
+           | Some target when String.is_suffix target ~suffix:".md" -> Embed
+           | _ -> Image)
+        | _ -> Link
+      in
+      located link_ref kind meta)
+  | _ -> None
+;;
+
 (** Walk a parsed document's AST and collect all links (wikilinks and markdown
     links/images) together with their byte ranges from [Cmarkit.Meta.textloc].
 
@@ -37,54 +84,22 @@ type located_link =
 let collect_links (doc : Cmarkit.Doc.t) : located_link list =
   Trace_core.with_span ~__FILE__ ~__LINE__ "collect_links"
   @@ fun _sp ->
-  let try_add_link acc link_ref kind loc =
-    if Cmarkit.Textloc.is_none loc
-    then acc
-    else
-      { link_ref
-      ; kind
-      ; first_byte = Cmarkit.Textloc.first_byte loc
-      ; last_byte = Cmarkit.Textloc.last_byte loc
-      }
-      :: acc
-  in
+  let defs = Cmarkit.Doc.defs doc in
   let folder =
     Cmarkit.Folder.make
       ~inline_ext_default:(fun _f acc i ->
-        match i with
-        | Cmarkit.Inline.Ext_wikilink (wl, meta) ->
-          let link_ref = Oystermark.Vault.Link_ref.of_wikilink wl in
-          let kind =
-            if Cmarkit.Inline.Wikilink.embed wl
-            then (
-              match link_ref.target with
-              | Some target when is_image_target target -> Image
-              | _ -> Embed)
-            else Link
-          in
-          try_add_link acc link_ref kind (Cmarkit.Meta.textloc meta)
-        | _ -> acc)
+        match located_of_inline ~defs i with
+        | Some ll -> ll :: acc
+        | None -> acc)
         (* Keyed nodes are now native [Cmarkit.Block] constructors, so the
          default fold recurses into their label and body automatically. Only
          other block extensions (e.g. frontmatter) reach here; ignore them. *)
       ~block_ext_default:(fun _f acc _b -> acc)
       ~inline:(fun _f acc i ->
         match i with
-        | (Cmarkit.Inline.Link (link, meta) | Cmarkit.Inline.Image (link, meta)) as inline
-          ->
-          let loc = Cmarkit.Meta.textloc meta in
-          let ref_ = Cmarkit.Inline.Link.reference link in
-          (match Oystermark.Vault.Link_ref.of_cmark_reference ref_ with
-           | Some link_ref ->
-             let kind =
-               match inline with
-               | Cmarkit.Inline.Image _ ->
-                 (match link_ref.target with
-                  | Some target when String.is_suffix target ~suffix:".md" -> Embed
-                  | _ -> Image)
-               | _ -> Link
-             in
-             Cmarkit.Folder.ret (try_add_link acc link_ref kind loc)
+        | Cmarkit.Inline.Link _ | Cmarkit.Inline.Image _ ->
+          (match located_of_inline ~defs i with
+           | Some ll -> Cmarkit.Folder.ret (ll :: acc)
            | None -> Cmarkit.Folder.default)
         | _ -> Cmarkit.Folder.default)
       ()
@@ -94,6 +109,42 @@ let collect_links (doc : Cmarkit.Doc.t) : located_link list =
   links
 ;;
 
+(** The link under byte [offset]: the innermost link among the node at
+    [offset] and its ancestors ({!Oystermark.Parse.Node_at}). *)
+let link_at (doc : Cmarkit.Doc.t) (offset : int) : located_link option =
+  Trace_core.with_span ~__FILE__ ~__LINE__ "link_at"
+  @@ fun _sp ->
+  let module Node_at = Oystermark.Parse.Node_at in
+  let defs = Cmarkit.Doc.defs doc in
+  let result =
+    Option.bind (Node_at.node_at doc offset) ~f:(fun { node; ancestors } ->
+      List.find_map (node :: ancestors) ~f:(function
+        | Node_at.Inline i -> located_of_inline ~defs i
+        | Node_at.Block _ -> None))
+  in
+  Trace_core.add_data_to_span
+    _sp
+    [ "offset", `Int offset; "found", `Bool (Option.is_some result) ];
+  result
+;;
+
+(** The footnote reference ([[^label]]) under byte [offset]: its label
+    (without [^]) and the location of its [[^label]: ...] definition. *)
+let footnote_at (doc : Cmarkit.Doc.t) (offset : int)
+  : (string * Cmarkit.Textloc.t) option
+  =
+  let module Node_at = Oystermark.Parse.Node_at in
+  let module Link_def = Oystermark.Parse.Link_def in
+  let defs = Cmarkit.Doc.defs doc in
+  Option.bind (Node_at.node_at doc offset) ~f:(fun { node; ancestors } ->
+    List.find_map (node :: ancestors) ~f:(function
+      | Node_at.Inline (Cmarkit.Inline.Link (l, _)) ->
+        (match Link_def.of_link ~defs l with
+         | Some (Link_def.Footnote (label, loc)) -> Some (label, loc)
+         | Some (Link_def.Link _) | None -> None)
+      | _ -> None))
+;;
+
 (** Find the link whose byte range contains [offset].
     Returns the {!located_link} if found. *)
 let find_at_offset (links : located_link list) (offset : int)
@@ -164,6 +215,67 @@ let%test_module "collect_links" =
       show "see ![alt](img.png) here";
       [%expect {| [4-18] ((target(img.png))(fragment())) |}]
     ;;
+
+    let%expect_test "reference link takes its definition's destination" =
+      show "see [text][r] and [note]\n\n[r]: other#Usage\n[note]: https://example.com";
+      [%expect {| [4-12] ((target(other))(fragment((Heading(Usage))))) |}]
+    ;;
+
+    let%expect_test "footnote reference ignored" =
+      show "a claim[^1]\n\n[^1]: source";
+      [%expect {| |}]
+    ;;
+  end)
+;;
+
+let%test_module "link_at" =
+  (module struct
+    let show text offset =
+      match link_at (Lsp_util.parse_doc text) offset with
+      | None -> print_endline "<none>"
+      | Some ll ->
+        printf
+          "[%d-%d] %s\n"
+          ll.first_byte
+          ll.last_byte
+          (Sexp.to_string (Oystermark.Vault.Link_ref.sexp_of_t ll.link_ref))
+    ;;
+
+    let%expect_test "cursor inside emphasized wikilink" =
+      show "see *the [[Note]] here*" 12;
+      [%expect {| [9-16] ((target(Note))(fragment())) |}]
+    ;;
+
+    let%expect_test "cursor on markdown link text" =
+      show "a [text](other) b" 4;
+      [%expect {| [2-14] ((target(other))(fragment())) |}]
+    ;;
+
+    let%expect_test "cursor outside" =
+      show "see [[Note]] here" 14;
+      [%expect {| <none> |}]
+    ;;
+  end)
+;;
+
+let%test_module "footnote_at" =
+  (module struct
+    let show text offset =
+      match footnote_at (Lsp_util.parse_doc text) offset with
+      | None -> print_endline "<none>"
+      | Some (label, loc) ->
+        printf "%s defined at %d\n" label (Cmarkit.Textloc.first_byte loc)
+    ;;
+
+    let%expect_test "cursor on footnote reference" =
+      show "a claim[^1] here\n\n[^1]: source" 9;
+      [%expect {| 1 defined at 18 |}]
+    ;;
+
+    let%expect_test "cursor on link" =
+      show "a [text](other)[^1]\n\n[^1]: source" 4;
+      [%expect {| <none> |}]
+    ;;
   end)
 ;;
 
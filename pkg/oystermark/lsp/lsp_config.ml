@@ -13,6 +13,20 @@ type fragment_behavior =
       diagnostics report the fragment as unresolved. *)
 [@@deriving sexp, equal]
 
+(** How heading anchors are derived; see {!Oystermark.Config.Slug_style}. *)
+type slug_style = Oystermark.Config.Slug_style_def.t =
+  | Github
+  | Obsidian
+[@@deriving sexp, equal]
+
+(** Which file a link naming several resolves to; see
+    {!Oystermark.Config.Link_preference}. *)
+type link_preference = Oystermark.Config.Link_preference_def.t =
+  | First
+  | Shortest
+  | Nearest
+[@@deriving sexp, equal]
+
 type t =
   { gtd_unresolved_fragment : fragment_behavior
     (** Fragment behavior for {!Go_to_definition}. *)
@@ -23,15 +37,63 @@ type t =
       response.  Content exceeding this limit is truncated at the
       previous newline and a [*(truncated)*] suffix is appended.
       See {!page-"feature-hover".truncation}. *)
+  ; slug_style : slug_style
+    (** The site's [parse.slug_style], which notes are parsed with and every
+      slug computed from heading or fragment text follows, so anchors match
+      the exported site's. *)
+  ; link_preference : link_preference
+    (** The site's [link_resolution.prefer], so links resolve to the file the
+      exported site links to.  The site's [relative] option is not applied:
+      Markdown links resolve like wikilinks, [./] and [../] targets aside. *)
+  ; prose : bool
+    (** Report prose issues too; off unless the client's
+      [initializationOptions] hold [{"prose": true}].
+      See {!page-"feature-diagnostics".prose}. *)
   }
 [@@deriving sexp, equal]
 
 (** Default configuration: both features use {!Fallback}, matching the
     lenient behavior described in the go-to-definition spec.
-    Hover content is capped at 2 000 bytes. *)
+    Hover content is capped at 2 000 bytes. Slugs are GitHub-style, the
+    site's default, and so is picking the first of several files a link
+    names. No prose diagnostics. *)
 let default =
   { gtd_unresolved_fragment = Fallback
   ; diag_unresolved_fragment = Fallback
   ; hover_max_chars = 2000
+  ; slug_style = Github
+  ; link_preference = First
+  ; prose = false
   }
 ;;
+
+(** The config notes are parsed and resolved with: the site's defaults, with
+    [t]'s slug style and link preference. *)
+let parse_config (t : t) : Oystermark.Config.t =
+  let default : Oystermark.Config.t = Oystermark.Config.default in
+  let parse : Oystermark.Config.Parse_options.t =
+    { default.parse with slug_style = t.slug_style }
+  in
+  let link_resolution : Oystermark.Config.Link_resolution.t =
+    { default.link_resolution with prefer = t.link_preference }
+  in
+  { default with parse; link_resolution }
+;;
+
+(** {!Oystermark.Vault.Resolve.resolve} with [t]'s link preference. *)
+let resolve
+      (t : t)
+      (link_ref : Oystermark.Vault.Link_ref.t)
+      (curr_file : string)
+      (index : Oystermark.Vault.Index.t)
+  : Oystermark.Vault.Resolve.target
+  =
+  Oystermark.Vault.Resolve.resolve ~prefer:t.link_preference link_ref curr_file index
+;;
+
+(** Slug of heading or fragment [text] under [t]'s slug style. *)
+let slugify (t : t) (text : string) : string =
+  Oystermark.Parse.Heading_slug.slugify
+    ~style:(Oystermark.Parse.Heading_slug.of_config t.slug_style)
+    text
+;;
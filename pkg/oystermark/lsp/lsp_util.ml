@@ -145,12 +145,15 @@ let position_of_textloc ?content ?(encoding = Utf16) (tl : Cmarkit.Textloc.t) :
 
 (** {1 Parsing} *)
 
-(** Parse [content] into a [Cmarkit.Doc.t] with locations enabled. *)
-let parse_doc (content : string) : Cmarkit.Doc.t =
+(** Parse [content] into a [Cmarkit.Doc.t] with locations enabled, headings
+    slugged as [config] says; see {!Lsp_config.parse_config}. *)
+let parse_doc ?(config : Lsp_config.t = Lsp_config.default) (content : string)
+  : Cmarkit.Doc.t
+  =
   Trace_core.with_span ~__FILE__ ~__LINE__ "parse_doc"
   @@ fun _sp ->
   Trace_core.add_data_to_span _sp [ "content_len", `Int (String.length content) ];
-  Oystermark.Parse.of_string ~locs:true content
+  Oystermark.Parse.of_string ~locs:true ~config:(Lsp_config.parse_config config) content
 ;;
 
 (* Tests
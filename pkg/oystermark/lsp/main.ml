@@ -59,7 +59,15 @@ class oystermark_server ~sw =
         | Some uri -> Some (DocumentUri.to_path uri)
         | None -> Option.join params.rootPath
       in
-      Option.iter root ~f:(fun root -> Server.initialize server ~root);
+      let prose : bool option =
+        match params.initializationOptions with
+        | Some (`Assoc options) ->
+          (match List.Assoc.find options ~equal:String.equal "prose" with
+           | Some (`Bool prose) -> Some prose
+           | _ -> None)
+        | _ -> None
+      in
+      Option.iter root ~f:(fun root -> Server.initialize ?prose server ~root);
       super#on_req_initialize ~notify_back params
 
     (* Document synchronization
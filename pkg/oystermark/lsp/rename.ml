@@ -149,59 +149,30 @@ let target_path = function
   | Path_attr { path; _ } -> path
 ;;
 
-let find_definition_line content target =
-  String.split_lines content
-  |> List.find_mapi ~f:(fun line text ->
-    let found =
-      match target with
-      | Find_references.Path_heading { slug; _ } ->
-        Hover.heading_level_of_line text
-        |> Option.exists ~f:(fun _ ->
-          let heading =
-            String.lstrip text ~drop:(Char.equal '#')
-            |> String.lstrip ~drop:(Char.equal ' ')
-          in
-          String.equal (Oystermark.Parse.Heading_slug.slugify heading) slug)
-      | Path_block { block_id; _ } ->
-        Option.equal String.equal (Find_references.block_id_of_line text) (Some block_id)
-      | Path_attr { id; _ } -> Option.is_some (attr_id_offset ~id text)
-      | Path_only _ -> false
-    in
-    Option.some_if found line)
-;;
-
-(** Bounds of a link's destination within [slice] (the link's full source text):
-    [(style, dest_start, dest_stop)], both {e slice-relative}, with [dest_stop]
-    exclusive.  The destination excludes a wikilink's [|alias] and a markdown
-    link's title, so it is exactly the part a rename may rewrite. *)
-let destination_bounds slice =
-  match String.substr_index slice ~pattern:"[[" with
-  | Some open_pos ->
-    let start = open_pos + 2 in
-    let finish =
-      String.substr_index ~pos:start slice ~pattern:"]]"
-      |> Option.value_map ~default:(String.length slice) ~f:Fn.id
-    in
-    let finish =
-      String.index_from slice start '|'
-      |> Option.filter ~f:(fun p -> p < finish)
-      |> Option.value ~default:finish
-    in
-    Some (`Wikilink, start, finish)
-  | None ->
-    String.substr_index slice ~pattern:"]("
-    |> Option.bind ~f:(fun open_pos ->
-      let start = open_pos + 2 in
-      let rec finish i =
-        if i >= String.length slice
-        then i
-        else if Char.equal slice.[i] ')' || Char.is_whitespace slice.[i]
-        then i
-        else finish (i + 1)
+let find_definition_line ?config content target =
+  match target with
+  | Find_references.Path_heading { slug; _ } ->
+    (* By the slug stamped at parse time, as the references carry it *)
+    Hover.find_heading_in_content ?config ~slug content |> Option.map ~f:fst
+  | Path_block _ | Path_attr _ | Path_only _ ->
+    String.split_lines content
+    |> List.find_mapi ~f:(fun line text ->
+      let found =
+        match target with
+        | Path_block { block_id; _ } ->
+          Option.equal
+            String.equal
+            (Find_references.block_id_of_line text)
+            (Some block_id)
+        | Path_attr { id; _ } -> Option.is_some (attr_id_offset ~id text)
+        | Path_heading _ | Path_only _ -> false
       in
-      Some (`Markdown, start, finish start))
+      Option.some_if found line)
 ;;
 
+(** See {!Oystermark.Vault.Link_rewrite.destination_bounds}. *)
+let destination_bounds = Oystermark.Vault.Link_rewrite.destination_bounds
+
 let reference_edit ~content (r : Find_references.reference) target ~new_name =
   let len = r.last_byte - r.first_byte + 1 in
   if r.first_byte < 0 || len <= 0 || r.first_byte + len > String.length content
@@ -227,16 +198,17 @@ let reference_edit ~content (r : Find_references.reference) target ~new_name =
             if String.is_suffix new_name ~suffix:".md" then new_name else new_name ^ ".md"
           else Option.value (String.chop_suffix new_name ~suffix:".md") ~default:new_name
         in
+        (* [old_target]'s directory is kept as written, already encoded. *)
+        let basename =
+          match style with
+          | `Wikilink -> basename
+          | `Markdown -> Oystermark.Vault.Link_rewrite.percent_encode basename
+        in
         let replacement =
           match Filename.dirname old_target with
           | "." -> basename
           | dir -> Filename.concat dir basename
         in
-        let replacement =
-          match style with
-          | `Wikilink -> replacement
-          | `Markdown -> String.substr_replace_all replacement ~pattern:" " ~with_:"%20"
-        in
         Some
           { rel_path = r.rel_path
           ; first_byte = r.first_byte + dest_start
@@ -254,7 +226,7 @@ let reference_edit ~content (r : Find_references.reference) target ~new_name =
           let new_text =
             match style with
             | `Wikilink -> new_name
-            | `Markdown -> String.substr_replace_all new_name ~pattern:" " ~with_:"%20"
+            | `Markdown -> Oystermark.Vault.Link_rewrite.percent_encode new_name
           in
           { rel_path = r.rel_path
           ; first_byte = r.first_byte + dest_start + hash + 1 + String.length marker
@@ -263,8 +235,21 @@ let reference_edit ~content (r : Find_references.reference) target ~new_name =
           })))
 ;;
 
-let rename ~index ~docs ~read_file ~rel_path ~content ~line ~character ~new_name () =
-  match Find_references.detect_target ~index ~rel_path ~content ~line ~character with
+let rename
+      ?config
+      ~index
+      ~docs
+      ~read_file
+      ~rel_path
+      ~content
+      ~line
+      ~character
+      ~new_name
+      ()
+  =
+  match
+    Find_references.detect_target ?config ~index ~rel_path ~content ~line ~character ()
+  with
   | None -> []
   | Some target ->
     let ids_are_valid =
@@ -286,7 +271,7 @@ let rename ~index ~docs ~read_file ~rel_path ~content ~line ~character ~new_name
       let definition =
         read_file definition_path
         |> Option.bind ~f:(fun definition_content ->
-          find_definition_line definition_content target
+          find_definition_line ?config definition_content target
           |> Option.bind ~f:(fun definition_line ->
             definition_edit
               ~rel_path:definition_path
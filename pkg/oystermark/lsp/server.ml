@@ -36,16 +36,87 @@ type t =
           the editor currently has open.  Diagnostics and the
           cursor-position features answer against this; the rest read from
           disk.  See {!page-"feature-document-sync"}. *)
+  ; diagnostic_states : Feature.Diagnostics.Incremental.t String.Table.t
+    (** Per open document, the diagnostics {!did_change} brings up to date
+          by reparsing only the edited block.  Cleared with every vault
+          build, since resolution depends on the index.  See
+          {!page-"feature-diagnostics".incremental}. *)
+  ; mutable dictionary : Oystermark.Vault.Prose_lint.Dictionary.t
+    (** Loaded only with [config.prose], and read again with the vault only
+          when {!dictionary_stamp} changed, so wordlist edits show up on
+          save.  See {!page-"feature-diagnostics".prose}. *)
+  ; mutable dictionary_stamp : (string * float) list option
+    (** The {!dictionary_stamp} [dictionary] was loaded at. *)
+  ; mutable config : Lsp_config.t
+    (** Reloaded with the vault: its slug style is the one the root
+          [.oyster.toml] sets, if any. *)
   }
 
-let build_vault = Oystermark.Vault.of_root_path ~skip_expand:true
-let create () : t = { vault = None; open_docs = String.Table.create () }
-let initialize (t : t) ~(root : string) : unit = t.vault <- Some (build_vault root)
+let create () : t =
+  { vault = None
+  ; open_docs = String.Table.create ()
+  ; diagnostic_states = String.Table.create ()
+  ; dictionary = Oystermark.Vault.Prose_lint.Dictionary.empty ()
+  ; dictionary_stamp = None
+  ; config = Lsp_config.default
+  }
+;;
+
+(* The word lists the dictionary of [root] is read from, those that exist,
+   with their modification times. *)
+let dictionary_stamp (root : string) : (string * float) list =
+  [ Oystermark.Vault.Prose_lint.system_dictionary
+  ; Filename.concat root Oystermark.Vault.Prose_lint.wordlist_path
+  ]
+  |> List.filter_map ~f:(fun path ->
+    match Core_unix.stat path with
+    | st -> Some (path, st.st_mtime)
+    | exception _ -> None)
+;;
+
+(* The settings of the site config the vault's root [.oyster.toml] overrides
+   (see {!Oystermark.Vault.Folder_config}); the defaults if it is invalid. *)
+let config_of_root (root : string) : Lsp_config.t =
+  let read_file (rel_path : string) : string option =
+    Option.try_with (fun () -> In_channel.read_all (Filename.concat root rel_path))
+  in
+  match Oystermark.Vault.Folder_config.load ~read_file [] with
+  | folders ->
+    let site : Oystermark.Config.t =
+      Oystermark.Vault.Folder_config.config ~base:Oystermark.Config.default folders ""
+    in
+    { Lsp_config.default with
+      slug_style = site.parse.slug_style
+    ; link_preference = site.link_resolution.prefer
+    }
+  | exception _ -> Lsp_config.default
+;;
+
+let build_vault (t : t) (root : string) : Oystermark.Vault.t =
+  Oystermark.Vault.of_root_path
+    ~skip_expand:true
+    ~config:(Lsp_config.parse_config t.config)
+    root
+;;
+
+let initialize ?(prose : bool option) (t : t) ~(root : string) : unit =
+  let prose = Option.value prose ~default:t.config.prose in
+  t.config <- { (config_of_root root) with prose };
+  t.vault <- Some (build_vault t root);
+  Hashtbl.clear t.diagnostic_states;
+  if prose
+  then (
+    let stamp = dictionary_stamp root in
+    if not ([%equal: (string * float) list option] t.dictionary_stamp (Some stamp))
+    then (
+      t.dictionary <- Oystermark.Vault.Prose_lint.load_dictionary root;
+      t.dictionary_stamp <- Some stamp))
+;;
 
 let rebuild_vault (t : t) : unit =
   match t.vault with
   | None -> ()
-  | Some v -> t.vault <- Some (build_vault v.vault_root)
+  | Some v -> initialize t ~root:v.vault_root
 ;;
 
 let vault_root (t : t) : string option =
@@ -114,14 +185,42 @@ let diagnostics (t : t) ~(rel_path : string) ~(content : string) : Diagnostic.t
   match t.vault with
   | None -> []
   | Some v ->
-    Feature.Diagnostics.compute ~index:v.index ~rel_path ~content ()
-    |> List.map ~f:(fun (d : Feature.Diagnostics.diagnostic) ->
+    let create severity (d : Feature.Diagnostics.diagnostic) : Diagnostic.t =
       Diagnostic.create
         ~range:(range_of_bytes content ~first_byte:d.first_byte ~last_byte:d.last_byte)
-        ~severity:DiagnosticSeverity.Warning
+        ~severity
         ~source:"oystermark"
         ~message:(`String d.message)
-        ())
+        ()
+    in
+    let state : Feature.Diagnostics.Incremental.t =
+      match Hashtbl.find t.diagnostic_states rel_path with
+      | Some state ->
+        Feature.Diagnostics.Incremental.update
+          ~config:t.config
+          ~index:v.index
+          ~rel_path
+          state
+          content
+      | None ->
+        Feature.Diagnostics.Incremental.build
+          ~config:t.config
+          ~index:v.index
+          ~rel_path
+          content
+    in
+    if Hashtbl.mem t.open_docs rel_path
+    then Hashtbl.set t.diagnostic_states ~key:rel_path ~data:state;
+    List.map
+      (Feature.Diagnostics.Incremental.diagnostics state)
+      ~f:(create DiagnosticSeverity.Warning)
+    @
+    if t.config.prose
+    then
+      List.map
+        (Feature.Diagnostics.prose ~dictionary:t.dictionary ~content)
+        ~f:(create DiagnosticSeverity.Information)
+    else []
 ;;
 
 let did_open (t : t) ~(rel_path : string) ~(content : string) : Diagnostic.t list =
@@ -135,7 +234,10 @@ let did_change (t : t) ~(rel_path : string) ~(content : string) : Diagnostic.t l
   diagnostics t ~rel_path ~content
 ;;
 
-let did_close (t : t) ~(rel_path : string) : unit = Hashtbl.remove t.open_docs rel_path
+let did_close (t : t) ~(rel_path : string) : unit =
+  Hashtbl.remove t.open_docs rel_path;
+  Hashtbl.remove t.diagnostic_states rel_path
+;;
 
 let did_save (t : t) : (string * Diagnostic.t list) list =
   rebuild_vault t;
@@ -160,6 +262,7 @@ let hover (t : t) ~(rel_path : string) ~(line : int) ~(character : int) : Hover.
     let content = buffer_content t rel_path in
     (match
        Feature.Hover.hover
+         ~config:t.config
          ~index:v.index
          ~rel_path
          ~content
@@ -188,6 +291,7 @@ let definition (t : t) ~(rel_path : string) ~(line : int) ~(character : int)
   | Some v ->
     (match
        Feature.Go_to_definition.go_to_definition
+         ~config:t.config
          ~read_file:(read_file t)
          ~index:v.index
          ~rel_path
@@ -214,6 +318,7 @@ let references (t : t) ~(rel_path : string) ~(line : int) ~(character : int)
   | Some v ->
     let refs =
       Feature.Find_references.find_references
+        ~config:t.config
         ~index:v.index
         ~docs:v.docs
         ~rel_path
@@ -241,11 +346,13 @@ let prepare_rename (t : t) ~(rel_path : string) ~(line : int) ~(character : int)
   | Some v ->
     (match
        Feature.Find_references.detect_target
+         ~config:t.config
          ~index:v.index
          ~rel_path
          ~content:(disk_content t rel_path)
          ~line
          ~character
+         ()
      with
      | None -> None
      | Some _ ->
@@ -267,6 +374,7 @@ let rename
     let content = disk_content t rel_path in
     let edits =
       Feature.Rename.rename
+        ~config:t.config
         ~index:v.index
         ~docs:v.docs
         ~read_file:(read_file t)
@@ -304,11 +412,13 @@ let rename
     let documentChanges =
       match
         Feature.Find_references.detect_target
+          ~config:t.config
           ~index:v.index
           ~rel_path
           ~content
           ~line
           ~character
+          ()
       with
       | Some (Path_only { path }) when Feature.Rename.valid_note_name new_name ->
         let new_path = Feature.Rename.renamed_note_path ~path ~new_name in
@@ -427,6 +537,7 @@ let completion (t : t) ~(rel_path : string) ~(line : int) ~(character : int)
   | None -> None
   | Some v ->
     Feature.Completion.complete
+      ~config:t.config
       ~index:v.index
       ~rel_path
       ~content:(buffer_content t rel_path)
@@ -456,6 +567,7 @@ let inlay_hint (t : t) ~(rel_path : string) ~(start_line : int) ~(end_line : int
   | None -> None
   | Some v ->
     Feature.Inlay_hints.inlay_hints
+      ~config:t.config
       ~docs:v.docs
       ~rel_path
       ~content:(disk_content t rel_path)
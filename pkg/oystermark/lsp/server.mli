@@ -22,8 +22,10 @@ type t
 val create : unit -> t
 
 (** Build the vault from [root].  Called from [initialize] with the client's
-    [rootUri]. *)
-val initialize : t -> root:string -> unit
+    [rootUri], and [prose] from its [initializationOptions]; without [prose],
+    prose diagnostics stay as they were (off at first).  See
+    {!page-"feature-diagnostics".prose}. *)
+val initialize : ?prose:bool -> t -> root:string -> unit
 
 (** The vault root, or [None] before {!initialize}. *)
 val vault_root : t -> string option
@@ -44,7 +46,8 @@ val uri_of_rel_path : t -> string -> DocumentUri.t
 val did_open : t -> rel_path:string -> content:string -> Diagnostic.t list
 
 (** Recompute diagnostics against the in-flight buffer so squigglies update as
-    the user types.  The vault is {i not} rebuilt. *)
+    the user types.  The vault is {i not} rebuilt, and after a small edit only
+    the edited block is reparsed; see {!page-"feature-diagnostics".incremental}. *)
 val did_change : t -> rel_path:string -> content:string -> Diagnostic.t list
 
 (** Drop [rel_path] from the set of open documents; it stops being refreshed
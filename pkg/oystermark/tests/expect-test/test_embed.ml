@@ -55,6 +55,38 @@ let%expect_test "block ref" =
     |}]
 ;;
 
+(* The [as] attribute picks what the embed shows: a "map of content" note
+   gathering titles or summaries. *)
+let%expect_test "view: summary and list" =
+  let files =
+    [ ( "moc.md"
+      , "![[b#Tides]]{as=summary}\n\n![[b]]{as=list}\n\n![[b#Tides]]{as=list}\n" )
+    ; ( "b.md"
+      , "# Shore\n\nIntro.\n\n## Tides\n\nTwice a day.\n\nMore on tides.\n\n\
+         ### Spring tides\n\nBig ones.\n\n## Rocks\n\nHard.\n" )
+    ]
+  in
+  render files "moc.md";
+  [%expect
+    {|
+    <div class="embed" data-embed-depth="1" data-embed-view="summary">
+    <h2 id="tides">Tides</h2>
+    <p>Twice a day.</p>
+    </div>
+    <div class="embed" data-embed-depth="1" data-embed-view="list">
+    <ul>
+    <li><a href="/b/#tides">Tides</a></li>
+    <li><a href="/b/#rocks">Rocks</a></li>
+    </ul>
+    </div>
+    <div class="embed" data-embed-depth="1" data-embed-view="list">
+    <ul>
+    <li><a href="/b/#spring-tides">Spring tides</a></li>
+    </ul>
+    </div>
+    |}]
+;;
+
 (* Embed an attribute anchor: block attribute ({#note}) wrapping a blockquote.
    See {!page-"feature-attribute-anchors"}. *)
 let%expect_test "attribute anchor: block" =
@@ -274,6 +306,11 @@ let%expect_test "reverse_embed: full note" =
   [%expect {| ![[b]] |}]
 ;;
 
+let%expect_test "reverse_embed: view" =
+  render_reversed [ "a.md", "![[b]]{as=list}"; "b.md", "## One\n\nHello." ] "a.md";
+  [%expect {| ![[b]]{as=list} |}]
+;;
+
 let%expect_test "reverse_embed: heading section" =
   render_reversed
     [ "a.md", "![[b#Sec]]"
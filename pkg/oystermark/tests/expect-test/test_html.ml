@@ -5,16 +5,27 @@ let test_index : Vault.Index.t =
   { files =
       [ { rel_path = "Note 1.md"
         ; headings =
-            [ { text = "Level 3 title"; level = 3; slug = "level-3-title"; loc = None }
-            ; { text = "L2"; level = 2; slug = "l2"; loc = None }
-            ; { text = "L3"; level = 3; slug = "l3"; loc = None }
+            [ { text = "Level 3 title"
+              ; level = 3
+              ; slug = "level-3-title"
+              ; style = Atx
+              ; loc = None
+              }
+            ; { text = "L2"; level = 2; slug = "l2"; style = Atx; loc = None }
+            ; { text = "L3"; level = 3; slug = "l3"; style = Atx; loc = None }
             ]
         ; blocks = [ { id = "para1"; loc = None }; { id = "block-2"; loc = None } ]
         ; attrs = []
         }
       ; { rel_path = "Note 2.md"
         ; headings =
-            [ { text = "Some heading"; level = 2; slug = "some-heading"; loc = None } ]
+            [ { text = "Some heading"
+              ; level = 2
+              ; slug = "some-heading"
+              ; style = Atx
+              ; loc = None
+              }
+            ]
         ; blocks = []
         ; attrs = []
         }
@@ -32,11 +43,17 @@ let test_index : Vault.Index.t =
   }
 ;;
 
-let render ?(curr_file = "Note 1.md") (md : string) : unit =
+let render ?(curr_file = "Note 1.md") ?config (md : string) : unit =
   let doc = Oystermark.Parse.of_string md in
-  let mapper = Vault.Resolve.resolution_cmarkit_mapper ~index:test_index ~curr_file in
+  let mapper =
+    Vault.Resolve.resolution_cmarkit_mapper
+      ~defs:(Cmarkit.Doc.defs doc)
+      ~index:test_index
+      ~curr_file
+      ()
+  in
   let resolved = Cmarkit.Mapper.map_doc mapper doc in
-  print_string (Html.of_doc ~backend_blocks:true ~safe:false resolved)
+  print_string (Html.of_doc ~backend_blocks:true ~safe:false ?config resolved)
 ;;
 
 (* Wikilinks
@@ -136,6 +153,12 @@ let%expect_test "md link: unresolved" =
   [%expect {| <p><a href="#" class="unresolved">text</a></p> |}]
 ;;
 
+let%expect_test "md link: reference-style resolved through its definition" =
+  render "[click][n2] and [Note 2][]\n\n[note 2]: Note%202\n[n2]: Note%202#Some%20heading";
+  [%expect
+    {| <p><a href="/Note 2/#some-heading">click</a> and <a href="/Note 2/">Note 2</a></p> |}]
+;;
+
 (* Standard markdown images with resolution
    ==================================================================== *)
 
@@ -179,6 +202,18 @@ let%expect_test "plain markdown renders normally" =
     |}]
 ;;
 
+let%expect_test "section ids" =
+  Parse.of_string "# Setup\n\n## Linux\n"
+  |> Parse.Section_id.stamp ~path:"notes/a.md"
+  |> Html.of_doc ~backend_blocks:true ~safe:false
+  |> print_string;
+  [%expect
+    {|
+    <h1 id="setup" data-section-id="s25ddd8ceb5ce">Setup</h1>
+    <h2 id="linux" data-section-id="s3d9ac24cf0ec">Linux</h2>
+    |}]
+;;
+
 (* Callouts
    ==================================================================== *)
 
@@ -347,3 +382,114 @@ let%expect_test "inline attr: no attr leaves output unchanged" =
   render "_em_";
   [%expect {| <p><em>em</em></p> |}]
 ;;
+
+(* Image captions and lightbox
+   ==================================================================== *)
+
+let images_config caption lightbox : Config.t =
+  { Config.default with images = { caption; lightbox } }
+;;
+
+let%expect_test "image caption: from alt text" =
+  let config = images_config Config.Image_caption_def.Alt false in
+  render ~config "![[image.png|A sunset]]";
+  [%expect
+    {| <figure><img src="/image.png" alt="A sunset"/><figcaption>A sunset</figcaption></figure> |}];
+  (* A dimension spec is no caption *)
+  render ~config "![[image.png|300]]";
+  [%expect {| <p><img src="/image.png" alt="image.png" width="300"/></p> |}];
+  (* Nor is an image sharing its paragraph with text *)
+  render ~config "See ![photo](image.png)";
+  [%expect {| <p>See <img src="/image.png" alt="photo"/></p> |}]
+;;
+
+let%expect_test "image caption: from the italic line below" =
+  let config = images_config Config.Image_caption_def.Italic true in
+  render ~config "![photo](image.png)\n*Taken at dawn*";
+  [%expect
+    {| <figure><a href="/image.png"><img src="/image.png" alt="photo"/></a><figcaption>Taken at dawn</figcaption></figure> |}];
+  render ~config "![[image.png]]";
+  [%expect {| <p><a href="/image.png"><img src="/image.png" alt="image.png"/></a></p> |}]
+;;
+
+(* Link previews
+   ==================================================================== *)
+
+let%expect_test "link previews from the cache" =
+  let cache = Vault.Link_preview.empty_cache () in
+  cache
+  := String.Map.singleton
+       "https://example.com/"
+       (Some
+          { Vault.Link_preview.title = Some "Example & Co"
+          ; description = Some "A domain for examples."
+          ; image = None
+          });
+  let render_previews style =
+    let config : Config.t =
+      { Config.default with
+        link_previews =
+          { Config.Link_previews.default with
+            mode = Config.Link_preview_mode_def.Offline
+          ; style
+          }
+      }
+    in
+    let vault : Vault.t =
+      { vault_root = "."
+      ; index = { files = []; dirs = [] }
+      ; docs =
+          [ ( "a.md"
+            , Parse.of_string
+                "[Example](https://example.com/ \"Examples\") and <https://example.org/>"
+            )
+          ]
+      ; vault_meta = Cmarkit.Meta.none
+      }
+    in
+    let pipeline = Pipeline.link_previews ~config:config.link_previews ~cache () in
+    List.iter (pipeline.on_vault vault).docs ~f:(fun (_, doc) ->
+      print_string (Html.of_doc ~backend_blocks:true ~safe:false ~config doc))
+  in
+  render_previews Config.Link_preview_style_def.Hover;
+  [%expect
+    {| <p><span class="link-preview" data-style="hover"><a href="https://example.com/" title="Examples">Example</a><span class="link-preview-card"><span class="link-preview-title">Example &amp; Co</span><span class="link-preview-description">A domain for examples.</span></span></span> and <a href="https://example.org/">https://example.org/</a></p> |}];
+  render_previews Config.Link_preview_style_def.Inline;
+  [%expect
+    {| <p><span class="link-preview" data-style="inline"><a href="https://example.com/" title="Examples">Example</a><span class="link-preview-card"><span class="link-preview-title">Example &amp; Co</span><span class="link-preview-description">A domain for examples.</span></span></span> and <a href="https://example.org/">https://example.org/</a></p> |}]
+;;
+
+let%expect_test "no link previews on mailto links, whatever their case" =
+  let cache = Vault.Link_preview.empty_cache () in
+  cache
+  := String.Map.singleton
+       "MAILTO:me@example.com"
+       (Some { Vault.Link_preview.title = Some "Me"; description = None; image = None });
+  let config : Config.t =
+    { Config.default with
+      link_previews =
+        { Config.Link_previews.default with mode = Config.Link_preview_mode_def.Offline }
+    }
+  in
+  let vault : Vault.t =
+    { vault_root = "."
+    ; index = { files = []; dirs = [] }
+    ; docs = [ "a.md", Parse.of_string "[mail](MAILTO:me@example.com)" ]
+    ; vault_meta = Cmarkit.Meta.none
+    }
+  in
+  let pipeline = Pipeline.link_previews ~config:config.link_previews ~cache () in
+  List.iter (pipeline.on_vault vault).docs ~f:(fun (_, doc) ->
+    print_string (Html.of_doc ~backend_blocks:true ~safe:false ~config doc));
+  [%expect {| <p><a href="MAILTO:me@example.com">mail</a></p> |}]
+;;
+
+(* Page previews
+   ==================================================================== *)
+
+let%expect_test "page previews on links to notes" =
+  let config = { Config.default with page_previews = { enabled = true } } in
+  render ~config "[[Note 2]], [text](Note%202#Some%20heading) and ![[image.png]]";
+  [%expect
+    {| <p><a href="/Note 2/" data-preview="/_previews/Note 2/index.html">Note 2</a>, <a href="/Note 2/#some-heading" data-preview="/_previews/Note 2/index.html">text</a> and <a href="/image.png"><img src="/image.png" alt="image.png"/></a></p> |}]
+;;
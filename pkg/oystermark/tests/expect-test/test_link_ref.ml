@@ -6,13 +6,14 @@ module Link_ref = Vault.Link_ref
 
 (* Extract all Link_ref.t values from a parsed document, in order. *)
 let extract_link_refs (doc : Cmarkit.Doc.t) : Link_ref.t list =
+  let defs = Cmarkit.Doc.defs doc in
   let folder =
     Cmarkit.Folder.make
       ~inline:(fun _f acc i ->
         match i with
         | Cmarkit.Inline.Link (link, _meta) ->
           let ref_ = Cmarkit.Inline.Link.reference link in
-          (match Link_ref.of_cmark_reference ref_ with
+          (match Link_ref.of_cmark_reference ~defs ref_ with
            | Some lr -> Cmarkit.Folder.ret (acc @ [ lr ])
            | None -> Cmarkit.Folder.default)
         | _ -> Cmarkit.Folder.default)
@@ -140,3 +141,18 @@ let%expect_test "markdown_link_link_refs" =
     └────────────────────────┴────────────────────────────────────────┴─────────────────────────────────────────────────────────────────────┘
     |}]
 ;;
+
+let%expect_test "reference_link_link_refs" =
+  print_endline
+    (link_ref_of
+       "[full][n2], [collapsed][], [shortcut] and [ext][]\n\n\
+        [n2]: Note%202#Some%20heading\n\
+        [collapsed]: ww\n\
+        [shortcut]: #Level%203%20title\n\
+        [ext]: https://example.com");
+  [%expect
+    {| ((target ("Note 2")) (fragment ((Heading ("Some heading"))))) | ((target (ww)) (fragment ())) | ((target ()) (fragment ((Heading ("Level 3 title"))))) |}];
+  (* A footnote reference is not a vault link. *)
+  print_endline (link_ref_of "A claim[^1].\n\n[^1]: [[Source]]");
+  [%expect {| ((target (Source)) (fragment ())) |}]
+;;
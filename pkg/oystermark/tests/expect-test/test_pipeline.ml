@@ -35,7 +35,7 @@ let%expect_test "render_vault: home page" =
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a></nav><h1 id="home-page">Home Page</h1>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><h1 id="home-page">Home Page</h1>
     </body>
     </html>
     |}]
@@ -57,10 +57,10 @@ let%expect_test "render_vault: subdir index" =
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a></nav><ul>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><ul>
     <li><a href="/home/">home</a></li>
     <li><a href="/secret/">secret</a></li>
-    <li style="list-style: none"><details><summary><a href="/subdir/">subdir</a></summary><ul>
+    <li style="list-style: none"><details data-path="subdir"><summary><a href="/subdir/">subdir</a></summary><ul>
     <li><a href="/subdir/">index</a></li>
     <li><a href="/subdir/note-a/">note-a</a></li>
     <li><a href="/subdir/note-b/">note-b</a></li>
@@ -89,10 +89,10 @@ let%expect_test "render_vault: regular note unchanged" =
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a><span class="sep">/</span><a href="/subdir/">subdir</a></nav><ul>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a><span class="sep">/</span><a href="/subdir/">subdir</a></nav><ul>
     <li><a href="/home/">home</a></li>
     <li><a href="/secret/">secret</a></li>
-    <li style="list-style: none"><details><summary><a href="/subdir/">subdir</a></summary><ul>
+    <li style="list-style: none"><details data-path="subdir"><summary><a href="/subdir/">subdir</a></summary><ul>
     <li><a href="/subdir/">index</a></li>
     <li><a href="/subdir/note-a/">note-a</a></li>
     <li><a href="/subdir/note-b/">note-b</a></li>
@@ -143,9 +143,9 @@ let%expect_test "wikilink to dir-only name is unresolved" =
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a></nav><ul>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><ul>
     <li><a href="/main/">main</a></li>
-    <li style="list-style: none"><details><summary><a href="/mydir/">mydir</a></summary><ul>
+    <li style="list-style: none"><details data-path="mydir"><summary><a href="/mydir/">mydir</a></summary><ul>
     <li><a href="/mydir/child/">child</a></li>
     </ul></details></li>
     </ul><div class="frontmatter"><table><tr><th>publish</th><td>true</td></tr></table></div>
@@ -189,9 +189,9 @@ let%expect_test "dir_index: generated page has TOC with children" =
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a></nav><ul>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><ul>
     <li><a href="/main/">main</a></li>
-    <li style="list-style: none"><details><summary><a href="/mydir/">mydir</a></summary><ul>
+    <li style="list-style: none"><details data-path="mydir"><summary><a href="/mydir/">mydir</a></summary><ul>
     <li><a href="/mydir/child/">child</a></li>
     <li><a href="/mydir/">index</a></li>
     </ul></details></li>
@@ -221,7 +221,7 @@ let%expect_test "transclude_code_files: wikilink embed replaced with code block"
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a></nav><ul>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><ul>
     <li><a href="/note/">note</a></li>
     </ul><div class="frontmatter"><table><tr><th>publish</th><td>true</td></tr></table></div>
     <h1 id="note">Note</h1>
@@ -248,10 +248,10 @@ let%expect_test "dir_index: skips dir when index.md already exists" =
     <html>
     <head><meta charset="UTF-8"></head>
     <body>
-    <nav class="breadcrumb"><a href="/home/">Home</a></nav><ul>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><ul>
     <li><a href="/home/">home</a></li>
     <li><a href="/secret/">secret</a></li>
-    <li style="list-style: none"><details><summary><a href="/subdir/">subdir</a></summary><ul>
+    <li style="list-style: none"><details data-path="subdir"><summary><a href="/subdir/">subdir</a></summary><ul>
     <li><a href="/subdir/">index</a></li>
     <li><a href="/subdir/note-a/">note-a</a></li>
     <li><a href="/subdir/note-b/">note-b</a></li>
@@ -261,3 +261,131 @@ let%expect_test "dir_index: skips dir when index.md already exists" =
     </html>
     |}]
 ;;
+
+(* unlisted vault: notes/hidden.md has [unlisted: true]
+   ==================================================================== *)
+
+let unlisted_root = "../data/vault/unlisted"
+
+let%expect_test "unlisted: rendered, but left out of the sidebar and folder index" =
+  let results =
+    Oystermark.render_vault
+      ~pipeline:Pipeline.(dir_index ())
+      ~backend_blocks:true
+      ~safe:false
+      unlisted_root
+  in
+  List.map results ~f:fst
+  |> List.sort ~compare:String.compare
+  |> List.iter ~f:print_endline;
+  [%expect
+    {|
+    main/index.html
+    notes/hidden/index.html
+    notes/index.html
+    notes/shown/index.html
+    |}];
+  printf "%s" (List.Assoc.find_exn results ~equal:String.equal "notes/index.html");
+  [%expect
+    {|
+    <!DOCTYPE html>
+    <html>
+    <head><meta charset="UTF-8"></head>
+    <body>
+    <nav class="breadcrumb" aria-label="Breadcrumb"><a href="/home/">Home</a></nav><ul>
+    <li><a href="/main/">main</a></li>
+    <li style="list-style: none"><details data-path="notes"><summary><a href="/notes/">notes</a></summary><ul>
+    <li><a href="/notes/">index</a></li>
+    <li><a href="/notes/shown/">shown</a></li>
+    </ul></details></li>
+    </ul><ul>
+    <li><a href="/notes/shown/">shown</a></li>
+    </ul>
+    </body>
+    </html>
+    |}];
+  let hidden = List.Assoc.find_exn results ~equal:String.equal "notes/hidden/index.html" in
+  String.split_lines hidden
+  |> List.take_while ~f:(fun line -> not (String.equal line "<body>"))
+  |> List.iter ~f:print_endline;
+  [%expect
+    {|
+    <!DOCTYPE html>
+    <html>
+    <head><meta charset="UTF-8"><meta name="robots" content="noindex">
+    </head>
+    |}]
+;;
+
+let%expect_test "unlisted: left out of backlinks" =
+  let results =
+    Oystermark.render_vault
+      ~pipeline:Pipeline.(backlinks ())
+      ~backend_blocks:true
+      ~safe:false
+      unlisted_root
+  in
+  List.Assoc.find_exn results ~equal:String.equal "notes/shown/index.html"
+  |> String.split_lines
+  |> List.filter ~f:(String.is_substring ~substring:"<summary>")
+  |> List.iter ~f:print_endline;
+  [%expect
+    {|
+    <li style="list-style: none"><details open><summary><a href="/main/">main</a></summary><ul>
+    |}]
+;;
+
+(* Multi-vault builds
+   ==================================================================== *)
+
+let%expect_test "render_vaults: mounted vaults get URL prefixes" =
+  let mounts : Vault.mount list =
+    [ { prefix = ""; root = vault_root }; { prefix = "other"; root = dir_resolve_root } ]
+  in
+  let results =
+    Oystermark.render_vaults ~pipeline:Pipeline.id ~backend_blocks:true ~safe:false mounts
+  in
+  let files = List.map results ~f:fst |> List.sort ~compare:String.compare in
+  List.iter files ~f:(fun f -> printf "%s\n" f);
+  [%expect
+    {|
+    home/index.html
+    other/main/index.html
+    other/mydir/child/index.html
+    secret/index.html
+    subdir/index.html
+    subdir/note-a/index.html
+    subdir/note-b/index.html
+    |}]
+;;
+
+let%expect_test "mount_full_path: longest prefix wins" =
+  let mounts : Vault.mount list =
+    [ { prefix = ""; root = "/main" }
+    ; { prefix = "work"; root = "/w" }
+    ; { prefix = "work/archive"; root = "/old" }
+    ]
+  in
+  List.iter [ "a.md"; "work/b.md"; "work/archive/c.md" ] ~f:(fun p ->
+    printf "%s -> %s\n" p (Option.value_exn (Vault.mount_full_path mounts p)));
+  [%expect
+    {|
+    a.md -> /main/a.md
+    work/b.md -> /w/b.md
+    work/archive/c.md -> /old/c.md
+    |}]
+;;
+
+(* Assets
+   ==================================================================== *)
+
+let assets_root = "../data/vault/assets"
+
+let%expect_test "dedup_assets: identical assets collapse onto the attachment folder" =
+  let pipeline = Pipeline.dedup_assets in
+  let vault, _ =
+    Oystermark.render_site ~pipeline ~backend_blocks:true ~safe:false assets_root
+  in
+  List.iter (Vault.Assets.referenced_files vault.docs) ~f:(fun p -> printf "%s\n" p);
+  [%expect {| b/logo.svg |}]
+;;
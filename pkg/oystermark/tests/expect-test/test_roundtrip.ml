@@ -0,0 +1,187 @@
+open! Core
+open Oystermark
+
+(* Property tests over generated documents: headings (ATX and setext),
+   paragraphs with wikilinks, embeds, block refs, markdown links and images,
+   autolinks and block ids, tight and nested lists, task lists, block quotes
+   and callouts, tables, fenced and indented code and frontmatter. Words
+   include markup characters, entities, digits-only and non-ASCII text, so
+   escaping is exercised too. A failing case is shrunk and printed by
+   [Quickcheck.test].
+   ---------- *)
+
+module G = Quickcheck.Generator
+open G.Let_syntax
+
+let word : string G.t =
+  G.of_list
+    [ "alpha"
+    ; "beta"
+    ; "gamma"
+    ; "delta"
+    ; "Oyster"
+    ; "x1"
+    ; "42"
+    ; "snake_case"
+    ; "kebab-case"
+    ; "C++"
+    ; "a*b"
+    ; "1."
+    ; "#tag"
+    ; "Ünïcode"
+    ; "日本"
+    ; "&amp;"
+    ; "<br>"
+    ; "back\\slash"
+    ; "50%"
+    ; "$x$"
+    ]
+;;
+
+(* Note names, with folders and spaces, as link targets use them. *)
+let target : string G.t =
+  G.of_list [ "alpha"; "notes/beta"; "gamma"; "Some Note"; "missing"; "beta.md" ]
+;;
+
+let inline : string G.t =
+  G.union
+    [ word
+    ; G.map word ~f:(sprintf "*%s*")
+    ; G.map word ~f:(sprintf "**%s**")
+    ; G.map word ~f:(sprintf "~~%s~~")
+    ; G.map word ~f:(sprintf "`%s`")
+    ; G.map target ~f:(sprintf "[[%s]]")
+    ; G.map target ~f:(sprintf "![[%s]]")
+    ; G.map2 target word ~f:(sprintf "[[%s#%s]]")
+    ; G.map2 target word ~f:(sprintf "[[%s#^%s]]")
+    ; G.map word ~f:(sprintf "[[#%s]]")
+    ; G.map2 target word ~f:(sprintf "[[%s|%s]]")
+    ; G.map2 word target ~f:(sprintf "[%s](<%s.md>)")
+    ; G.map2 word word ~f:(sprintf "[x](%s.md#%s)")
+    ; G.map word ~f:(sprintf "![pic](img/%s.png)")
+    ; G.map word ~f:(sprintf "<https://example.com/%s>")
+    ]
+;;
+
+let line : string G.t =
+  let%bind n = Int.gen_incl 1 6 in
+  let%map words = G.list_with_length n inline in
+  String.concat ~sep:" " words
+;;
+
+let lines ~(max : int) : string list G.t =
+  let%bind n = Int.gen_incl 1 max in
+  G.list_with_length n line
+;;
+
+let block : string G.t =
+  G.union
+    [ (let%map level = Int.gen_incl 1 6
+       and text = line in
+       String.make level '#' ^ " " ^ text)
+    ; (let%map underline = G.of_list [ "==="; "---" ]
+       and text = line in
+       text ^ "\n" ^ underline)
+    ; (let%map ls = lines ~max:3
+       and id = G.option word in
+       String.concat ~sep:"\n" ls ^ Option.value_map id ~default:"" ~f:(sprintf " ^%s"))
+    ; G.map (lines ~max:4) ~f:(fun ls ->
+        String.concat ~sep:"\n" (List.map ls ~f:(sprintf "- %s")))
+    ; G.map (lines ~max:4) ~f:(fun ls ->
+        String.concat ~sep:"\n" (List.mapi ls ~f:(fun i l -> sprintf "%d. %s" (i + 1) l)))
+    ; (let%map ls = lines ~max:3
+       and done_ = G.list_with_length 3 G.bool in
+       List.mapi ls ~f:(fun i l ->
+         sprintf "- [%s] %s" (if List.nth_exn done_ i then "x" else " ") l)
+       |> String.concat ~sep:"\n")
+    ; (let%map outer = line
+       and inner = lines ~max:2 in
+       String.concat ~sep:"\n" (("- " ^ outer) :: List.map inner ~f:(sprintf "  - %s")))
+    ; G.map (lines ~max:2) ~f:(fun ls ->
+        String.concat ~sep:"\n" (List.map ls ~f:(sprintf "> %s")))
+    ; (let%map kind = G.of_list [ "note"; "tip"; "WARNING" ]
+       and ls = lines ~max:2 in
+       sprintf "> [!%s]" kind :: List.map ls ~f:(sprintf "> %s")
+       |> String.concat ~sep:"\n")
+    ; (let%map a = word
+       and b = word
+       and c = word in
+       sprintf "| %s | %s |\n| --- | --- |\n| %s | x |" a b c)
+    ; (let%map lang = word
+       and body = G.list_with_length 2 word in
+       sprintf "```%s\n%s\n```" lang (String.concat ~sep:"\n" body))
+    ; G.map (G.list_with_length 2 word) ~f:(fun body ->
+        String.concat ~sep:"\n" (List.map body ~f:(sprintf "    %s")))
+    ; G.return "***"
+    ]
+;;
+
+let frontmatter : string G.t =
+  let%map title = word
+  and tags = G.list_with_length 2 word
+  and n = Int.gen_incl 0 100
+  and date = G.of_list [ "2024-01-31"; "2024-06-01T09:30:00Z" ] in
+  sprintf
+    "---\ntitle: '%s'\ntags: [%s]\ncount: %d\ndate: %s\n---\n\n"
+    title
+    (String.concat ~sep:", " (List.map tags ~f:(sprintf "'%s'")))
+    n
+    date
+;;
+
+let doc_source : string G.t =
+  let%bind n = Int.gen_incl 1 8 in
+  let%map fm = G.option frontmatter
+  and blocks = G.list_with_length n block in
+  Option.value fm ~default:"" ^ String.concat ~sep:"\n\n" blocks ^ "\n"
+;;
+
+let check (f : string -> unit) : unit =
+  Quickcheck.test ~trials:300 ~sexp_of:String.sexp_of_t doc_source ~f
+;;
+
+(* Invariants
+   ---------- *)
+
+let%test_unit "commonmark output is a fixed point" =
+  check
+    (Parse.Common.For_test.commonmark_of_doc_idempotent
+       ~doc_of_string:Parse.of_string
+       ~commonmark_of_doc:Parse.commonmark_of_doc)
+;;
+
+let%test_unit "section line ranges nest" =
+  check (fun src ->
+    let root = Query.Section.of_doc (Parse.of_string src) in
+    let within ~(outer : (int * int) option) (inner : (int * int) option) : bool =
+      match outer, inner with
+      | Some (f, l), Some (f', l') -> f <= f' && l' <= l
+      | _ -> true
+    in
+    List.iter (Query.Section.descendants root) ~f:(fun (s : Query.Section.t) ->
+      Option.iter s.lines ~f:(fun (f, l) -> assert (f <= l));
+      List.iter s.children ~f:(fun c -> assert (within ~outer:s.lines c.lines));
+      List.iter2_exn
+        (List.drop_last s.children |> Option.value ~default:[])
+        (List.tl s.children |> Option.value ~default:[])
+        ~f:(fun (a : Query.Section.t) b ->
+          match a.lines, b.lines with
+          | Some (_, l), Some (f, _) -> assert (l < f)
+          | _ -> ())))
+;;
+
+let%test_unit "index, resolve and render don't raise" =
+  Quickcheck.test
+    ~trials:100
+    ~sexp_of:[%sexp_of: string list]
+    (G.list_with_length 3 doc_source)
+    ~f:(fun sources ->
+      let paths = [ "alpha.md"; "notes/beta.md"; "Some Note.md" ] in
+      let md_docs =
+        List.map2_exn paths sources ~f:(fun p src -> p, Parse.of_string src)
+      in
+      let index = Vault.build_index ~md_docs ~other_files:[] ~dirs:[ "notes/" ] in
+      let resolved = Vault.Resolve.resolve_docs md_docs index in
+      List.iter resolved ~f:(fun (_, doc) ->
+        ignore (Html.of_doc ~backend_blocks:true ~safe:true doc : string)))
+;;
@@ -0,0 +1,40 @@
+(** Yaml_lite, the browser build's frontmatter reader, against ocaml-yaml, the
+    native backend, on the frontmatter of every fixture note. *)
+
+open! Core
+open Oystermark
+
+let data_root = "../data"
+
+let frontmatter_text (src : string) : string option =
+  match String.split_lines src with
+  | "---" :: rest ->
+    let yaml, closing = List.split_while rest ~f:(fun l -> not (String.equal l "---")) in
+    Option.some_if (not (List.is_empty closing)) (String.concat ~sep:"\n" yaml ^ "\n")
+  | _ -> None
+;;
+
+let%expect_test "Yaml_lite agrees with ocaml-yaml on the fixtures" =
+  Vault.list_entries data_root
+  |> List.filter ~f:(String.is_suffix ~suffix:".md")
+  |> List.iter ~f:(fun rel ->
+    let src = In_channel.read_all (Filename.concat data_root rel) in
+    Option.iter (frontmatter_text src) ~f:(fun yaml ->
+      let native : Yaml.value = Yaml.of_string_exn yaml in
+      let lite : Yaml_lite.value = Yaml_lite.of_string_exn yaml in
+      if Poly.equal native lite
+      then printf "%s: same\n" rel
+      else
+        printf
+          "%s: differ\n%s---\n%s"
+          rel
+          (Yaml.to_string_exn native)
+          (Yaml_lite.to_string_exn lite)));
+  [%expect
+    {|
+    vault/code-embed/note.md: same
+    vault/dir-resolve/main.md: same
+    vault/pipeline/secret.md: same
+    vault/unlisted/notes/hidden.md: same
+    |}]
+;;
@@ -153,3 +153,24 @@ let%expect_test "server: resolved links produce no diagnostics" =
   printf "%d diagnostics\n" (List.length diags);
   [%expect {| 0 diagnostics |}]
 ;;
+
+(* Prose diagnostics are opt-in
+   ---------------------------- *)
+
+let%expect_test "prose: only with the prose option" =
+  with_tmp_vault ~files:[ "a.md", "See the the note.\n" ] (fun root ->
+    List.iter [ None; Some true ] ~f:(fun prose ->
+      let s = Server.create () in
+      Server.initialize ?prose s ~root;
+      printf "prose %s:\n" (Option.value_map prose ~default:"unset" ~f:Bool.to_string);
+      List.iter (open_doc s ~rel_path:"a.md") ~f:(fun (d : Linol_lsp.Lsp.Types.Diagnostic.t) ->
+        match d.message with
+        | `String m -> print_endline m
+        | _ -> ())));
+  [%expect
+    {|
+    prose unset:
+    prose true:
+    repeated word: "the"
+    |}]
+;;
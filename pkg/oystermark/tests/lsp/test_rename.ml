@@ -202,6 +202,46 @@ let%expect_test "attribute rename is not confused by prefixes or link fragments"
     |}]
 ;;
 
+let ref_files =
+  [ "target.md", "# Old Heading\n"
+  ; "refs.md", "See [one][t] and [two][t].\n\n[t]: target#Old%20Heading\n"
+  ]
+;;
+
+let ref_index, ref_docs = Lsp_lib.Find_references.For_test.make_vault ref_files
+let ref_read_file path = List.Assoc.find ref_files ~equal:String.equal path
+
+let%expect_test "reference links are renamed through their shared definition" =
+  let content = Option.value_exn (ref_read_file "target.md") in
+  let edits =
+    Lsp_lib.Rename.For_test.rename
+      ~index:ref_index
+      ~docs:ref_docs
+      ~read_file:ref_read_file
+      ~rel_path:"target.md"
+      ~content
+      ~line:0
+      ~character:3
+      ~new_name:"New Heading"
+      ()
+  in
+  show edits;
+  let refs_edits =
+    List.filter edits ~f:(fun (e : Lsp_lib.Rename.edit) ->
+      String.equal e.rel_path "refs.md")
+  in
+  printf "---\n%s" (apply_edits (Option.value_exn (ref_read_file "refs.md")) refs_edits);
+  [%expect
+    {|
+    refs.md [40-53] -> New%20Heading
+    target.md [2-13] -> New Heading
+    ---
+    See [one][t] and [two][t].
+
+    [t]: target#New%20Heading
+    |}]
+;;
+
 let%expect_test "server: note rename includes text edits and a file operation" =
   let vault_root = Filename.concat (Core_unix.getcwd ()) "data" in
   let s = start_server ~vault_root in